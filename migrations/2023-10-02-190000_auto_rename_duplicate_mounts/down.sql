@@ -0,0 +1 @@
+ALTER TABLE misc_settings DROP COLUMN auto_rename_duplicate_mounts;
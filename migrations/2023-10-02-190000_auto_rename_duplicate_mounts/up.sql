@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN auto_rename_duplicate_mounts BOOLEAN NOT NULL DEFAULT 0;
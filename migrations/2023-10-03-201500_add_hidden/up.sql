@@ -0,0 +1 @@
+ALTER TABLE songs ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE directories ADD COLUMN artwork_mime TEXT;
@@ -0,0 +1,4 @@
+ALTER TABLE songs DROP COLUMN grouping;
+ALTER TABLE songs DROP COLUMN work;
+ALTER TABLE songs DROP COLUMN movement_name;
+ALTER TABLE songs DROP COLUMN movement_number;
@@ -0,0 +1,3 @@
+ALTER TABLE misc_settings DROP COLUMN normalize_genres;
+ALTER TABLE misc_settings DROP COLUMN genre_aliases;
+ALTER TABLE songs DROP COLUMN original_genre;
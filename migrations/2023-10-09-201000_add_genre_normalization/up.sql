@@ -0,0 +1,3 @@
+ALTER TABLE misc_settings ADD COLUMN normalize_genres BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE misc_settings ADD COLUMN genre_aliases TEXT NOT NULL DEFAULT '{}';
+ALTER TABLE songs ADD COLUMN original_genre TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE misc_settings DROP COLUMN fallback_artist;
+ALTER TABLE misc_settings DROP COLUMN fallback_album;
@@ -0,0 +1,2 @@
+ALTER TABLE misc_settings ADD COLUMN fallback_artist TEXT NOT NULL DEFAULT 'Unknown Artist';
+ALTER TABLE misc_settings ADD COLUMN fallback_album TEXT NOT NULL DEFAULT '';
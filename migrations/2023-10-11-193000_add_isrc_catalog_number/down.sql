@@ -0,0 +1,2 @@
+ALTER TABLE songs DROP COLUMN isrc;
+ALTER TABLE songs DROP COLUMN catalog_number;
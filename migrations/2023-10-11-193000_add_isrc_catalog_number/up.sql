@@ -0,0 +1,2 @@
+ALTER TABLE songs ADD COLUMN isrc TEXT;
+ALTER TABLE songs ADD COLUMN catalog_number TEXT;
@@ -0,0 +1 @@
+ALTER TABLE mount_points DROP COLUMN album_art_pattern;
@@ -0,0 +1,9 @@
+CREATE TABLE play_counts (
+	id INTEGER PRIMARY KEY NOT NULL,
+	owner INTEGER NOT NULL,
+	path TEXT NOT NULL,
+	count INTEGER NOT NULL,
+	last_played INTEGER NOT NULL,
+	FOREIGN KEY(owner) REFERENCES users(id) ON DELETE CASCADE,
+	UNIQUE(owner, path) ON CONFLICT REPLACE
+);
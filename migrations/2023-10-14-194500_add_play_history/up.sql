@@ -0,0 +1,8 @@
+CREATE TABLE plays (
+	id INTEGER PRIMARY KEY NOT NULL,
+	owner INTEGER NOT NULL,
+	path TEXT NOT NULL,
+	played_at INTEGER NOT NULL,
+	FOREIGN KEY(owner) REFERENCES users(id) ON DELETE CASCADE
+);
+CREATE INDEX plays_owner_played_at ON plays(owner, played_at);
@@ -0,0 +1,2 @@
+ALTER TABLE playlists DROP COLUMN updated_at;
+ALTER TABLE playlists DROP COLUMN created_at;
@@ -0,0 +1,2 @@
+ALTER TABLE playlists ADD COLUMN created_at BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE playlists ADD COLUMN updated_at BIGINT NOT NULL DEFAULT 0;
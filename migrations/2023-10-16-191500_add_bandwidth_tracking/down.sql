@@ -0,0 +1,4 @@
+DROP TABLE bandwidth_usage;
+
+ALTER TABLE misc_settings DROP COLUMN bandwidth_rollover;
+ALTER TABLE misc_settings DROP COLUMN track_bandwidth;
@@ -0,0 +1,11 @@
+ALTER TABLE misc_settings ADD COLUMN track_bandwidth BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE misc_settings ADD COLUMN bandwidth_rollover TEXT NOT NULL DEFAULT 'monthly';
+
+CREATE TABLE bandwidth_usage (
+	id INTEGER PRIMARY KEY NOT NULL,
+	owner INTEGER NOT NULL,
+	period_start INTEGER NOT NULL,
+	bytes INTEGER NOT NULL,
+	FOREIGN KEY(owner) REFERENCES users(id) ON DELETE CASCADE,
+	UNIQUE(owner) ON CONFLICT REPLACE
+);
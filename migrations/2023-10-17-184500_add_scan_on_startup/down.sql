@@ -0,0 +1 @@
+ALTER TABLE misc_settings DROP COLUMN scan_on_startup;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN scan_on_startup BOOLEAN NOT NULL DEFAULT 1;
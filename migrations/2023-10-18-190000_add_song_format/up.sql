@@ -0,0 +1,13 @@
+ALTER TABLE songs ADD COLUMN format TEXT;
+
+UPDATE songs SET format = CASE
+	WHEN lower(path) LIKE '%.aif' OR lower(path) LIKE '%.aiff' THEN 'aiff'
+	WHEN lower(path) LIKE '%.ape' THEN 'ape'
+	WHEN lower(path) LIKE '%.flac' THEN 'flac'
+	WHEN lower(path) LIKE '%.mp3' THEN 'mp3'
+	WHEN lower(path) LIKE '%.m4a' THEN 'mp4'
+	WHEN lower(path) LIKE '%.mpc' THEN 'mpc'
+	WHEN lower(path) LIKE '%.ogg' THEN 'ogg'
+	WHEN lower(path) LIKE '%.opus' THEN 'opus'
+	WHEN lower(path) LIKE '%.wav' THEN 'wave'
+END;
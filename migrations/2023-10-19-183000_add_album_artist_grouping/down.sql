@@ -0,0 +1 @@
+ALTER TABLE misc_settings DROP COLUMN album_artist_grouping;
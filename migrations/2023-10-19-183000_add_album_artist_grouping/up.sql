@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN album_artist_grouping TEXT NOT NULL DEFAULT 'each';
@@ -0,0 +1 @@
+ALTER TABLE misc_settings DROP COLUMN snapshot_index_updates;
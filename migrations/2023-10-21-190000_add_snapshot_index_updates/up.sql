@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN snapshot_index_updates BOOLEAN NOT NULL DEFAULT 0;
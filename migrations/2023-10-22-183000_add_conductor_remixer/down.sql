@@ -0,0 +1,2 @@
+ALTER TABLE songs DROP COLUMN conductor;
+ALTER TABLE songs DROP COLUMN remixer;
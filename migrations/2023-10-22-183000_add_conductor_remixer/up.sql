@@ -0,0 +1,2 @@
+ALTER TABLE songs ADD COLUMN conductor TEXT;
+ALTER TABLE songs ADD COLUMN remixer TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE misc_settings DROP COLUMN expose_extra_tags;
+ALTER TABLE songs DROP COLUMN extra;
@@ -0,0 +1,2 @@
+ALTER TABLE songs ADD COLUMN extra TEXT;
+ALTER TABLE misc_settings ADD COLUMN expose_extra_tags BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings DROP COLUMN index_max_depth;
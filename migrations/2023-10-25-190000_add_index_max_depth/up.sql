@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN index_max_depth INTEGER NOT NULL DEFAULT 256;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN public_base_url TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,2 @@
+ALTER TABLE songs DROP COLUMN track_total;
+ALTER TABLE songs DROP COLUMN disc_total;
@@ -0,0 +1,2 @@
+ALTER TABLE songs ADD COLUMN disc_total INTEGER;
+ALTER TABLE songs ADD COLUMN track_total INTEGER;
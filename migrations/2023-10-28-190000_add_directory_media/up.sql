@@ -0,0 +1 @@
+ALTER TABLE directories ADD COLUMN media TEXT;
@@ -0,0 +1,10 @@
+CREATE TABLE shares (
+	id INTEGER PRIMARY KEY NOT NULL,
+	token TEXT NOT NULL,
+	path TEXT NOT NULL,
+	created_at BIGINT NOT NULL,
+	expires_at BIGINT NOT NULL,
+	max_downloads INTEGER,
+	download_count INTEGER NOT NULL DEFAULT 0,
+	UNIQUE(token)
+);
@@ -0,0 +1 @@
+ALTER TABLE misc_settings DROP COLUMN min_file_size_bytes;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN min_file_size_bytes BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN artwork_placeholder_path TEXT NOT NULL DEFAULT '';
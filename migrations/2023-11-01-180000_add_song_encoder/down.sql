@@ -0,0 +1,2 @@
+ALTER TABLE songs DROP COLUMN encoder_settings;
+ALTER TABLE songs DROP COLUMN encoded_by;
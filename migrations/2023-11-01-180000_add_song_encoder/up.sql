@@ -0,0 +1,2 @@
+ALTER TABLE songs ADD COLUMN encoded_by TEXT;
+ALTER TABLE songs ADD COLUMN encoder_settings TEXT;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN skip_hidden_files BOOLEAN NOT NULL DEFAULT 1;
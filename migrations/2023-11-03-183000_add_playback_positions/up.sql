@@ -0,0 +1,9 @@
+CREATE TABLE playback_positions (
+	id INTEGER PRIMARY KEY NOT NULL,
+	owner INTEGER NOT NULL,
+	path TEXT NOT NULL,
+	seconds INTEGER NOT NULL,
+	updated_at INTEGER NOT NULL,
+	FOREIGN KEY(owner) REFERENCES users(id) ON DELETE CASCADE,
+	UNIQUE(owner, path) ON CONFLICT REPLACE
+);
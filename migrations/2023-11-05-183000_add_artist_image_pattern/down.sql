@@ -0,0 +1 @@
+ALTER TABLE misc_settings DROP COLUMN artist_image_pattern;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN artist_image_pattern TEXT NOT NULL DEFAULT '^(artist|folder)\.(jpeg|jpg|png)$';
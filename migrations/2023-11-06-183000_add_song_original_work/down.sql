@@ -0,0 +1,2 @@
+ALTER TABLE songs DROP COLUMN original_album;
+ALTER TABLE songs DROP COLUMN original_artist;
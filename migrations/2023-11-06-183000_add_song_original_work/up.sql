@@ -0,0 +1,2 @@
+ALTER TABLE songs ADD COLUMN original_artist TEXT;
+ALTER TABLE songs ADD COLUMN original_album TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE misc_settings DROP COLUMN preferred_album_format;
+ALTER TABLE misc_settings DROP COLUMN merge_duplicate_albums;
@@ -0,0 +1,2 @@
+ALTER TABLE misc_settings ADD COLUMN merge_duplicate_albums BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE misc_settings ADD COLUMN preferred_album_format TEXT NOT NULL DEFAULT '';
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN tag_priority TEXT NOT NULL DEFAULT 'id3';
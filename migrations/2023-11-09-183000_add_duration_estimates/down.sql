@@ -0,0 +1 @@
+ALTER TABLE misc_settings DROP COLUMN estimate_missing_durations;
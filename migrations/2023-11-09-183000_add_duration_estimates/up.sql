@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN estimate_missing_durations BOOLEAN NOT NULL DEFAULT 0;
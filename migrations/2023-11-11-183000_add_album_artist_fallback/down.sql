@@ -0,0 +1,2 @@
+ALTER TABLE songs DROP COLUMN effective_album_artist;
+ALTER TABLE misc_settings DROP COLUMN album_artist_fallback;
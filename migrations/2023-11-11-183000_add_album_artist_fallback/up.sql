@@ -0,0 +1,2 @@
+ALTER TABLE misc_settings ADD COLUMN album_artist_fallback BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE songs ADD COLUMN effective_album_artist TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE misc_settings DROP COLUMN max_page_size;
+ALTER TABLE misc_settings DROP COLUMN default_page_size;
@@ -0,0 +1 @@
+ALTER TABLE mount_points DROP COLUMN reindex_every_n_seconds;
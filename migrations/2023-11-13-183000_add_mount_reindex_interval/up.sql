@@ -0,0 +1 @@
+ALTER TABLE mount_points ADD COLUMN reindex_every_n_seconds INTEGER;
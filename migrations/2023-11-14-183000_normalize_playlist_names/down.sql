@@ -0,0 +1 @@
+-- Original playlist names cannot be restored.
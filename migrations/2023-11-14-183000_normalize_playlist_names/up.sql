@@ -0,0 +1,22 @@
+-- Playlist names are trimmed, with runs of whitespace collapsed to a single space.
+-- Playlists whose normalized name would collide with another playlist of the same owner keep their name.
+CREATE TEMPORARY TABLE normalized_playlists AS
+SELECT id, owner, trim(
+	replace(replace(replace(replace(
+		replace(replace(replace(name, char(9), ' '), char(10), ' '), char(13), ' '),
+	'  ', ' '), '  ', ' '), '  ', ' '), '  ', ' ')
+) AS name
+FROM playlists;
+
+UPDATE playlists SET name = (
+	SELECT n.name FROM normalized_playlists n WHERE n.id = playlists.id
+)
+WHERE NOT EXISTS (
+	SELECT 1 FROM normalized_playlists n, normalized_playlists other
+	WHERE n.id = playlists.id
+		AND other.owner = n.owner
+		AND other.id != n.id
+		AND lower(other.name) = lower(n.name)
+);
+
+DROP TABLE normalized_playlists;
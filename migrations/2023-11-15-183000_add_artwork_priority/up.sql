@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN artwork_priority TEXT NOT NULL DEFAULT 'embedded';
@@ -0,0 +1,2 @@
+ALTER TABLE misc_settings DROP COLUMN artist_name_articles;
+ALTER TABLE misc_settings DROP COLUMN normalize_artist_names;
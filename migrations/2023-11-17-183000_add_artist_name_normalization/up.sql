@@ -0,0 +1,2 @@
+ALTER TABLE misc_settings ADD COLUMN normalize_artist_names BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE misc_settings ADD COLUMN artist_name_articles TEXT NOT NULL DEFAULT 'the,a,an';
@@ -0,0 +1 @@
+ALTER TABLE songs DROP COLUMN media_type;
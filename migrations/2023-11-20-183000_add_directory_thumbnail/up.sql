@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN directory_thumbnail TEXT NOT NULL DEFAULT 'disabled';
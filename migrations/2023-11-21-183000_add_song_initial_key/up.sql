@@ -0,0 +1 @@
+ALTER TABLE songs ADD COLUMN initial_key TEXT;
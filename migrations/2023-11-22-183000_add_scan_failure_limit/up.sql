@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN scan_failure_limit INTEGER NOT NULL DEFAULT 5;
@@ -0,0 +1,3 @@
+ALTER TABLE songs DROP COLUMN album_loudness;
+ALTER TABLE songs DROP COLUMN track_loudness;
+ALTER TABLE misc_settings DROP COLUMN analyze_loudness;
@@ -0,0 +1,3 @@
+ALTER TABLE misc_settings ADD COLUMN analyze_loudness BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE songs ADD COLUMN track_loudness INTEGER;
+ALTER TABLE songs ADD COLUMN album_loudness INTEGER;
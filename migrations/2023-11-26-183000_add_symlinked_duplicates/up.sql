@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN symlinked_duplicates TEXT NOT NULL DEFAULT 'alias';
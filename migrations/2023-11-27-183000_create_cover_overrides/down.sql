@@ -0,0 +1,2 @@
+DROP TABLE cover_overrides;
+ALTER TABLE misc_settings DROP COLUMN max_cover_bytes;
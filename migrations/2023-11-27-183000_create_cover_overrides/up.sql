@@ -0,0 +1,7 @@
+ALTER TABLE misc_settings ADD COLUMN max_cover_bytes INTEGER NOT NULL DEFAULT 2097152;
+CREATE TABLE cover_overrides (
+	id INTEGER PRIMARY KEY NOT NULL,
+	path TEXT NOT NULL,
+	file_name TEXT NOT NULL,
+	UNIQUE(path)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE misc_settings DROP COLUMN podcast_directories;
+ALTER TABLE misc_settings DROP COLUMN audiobook_directories;
+ALTER TABLE songs DROP COLUMN content_type;
@@ -0,0 +1,3 @@
+ALTER TABLE songs ADD COLUMN content_type TEXT NOT NULL DEFAULT 'music';
+ALTER TABLE misc_settings ADD COLUMN audiobook_directories TEXT NOT NULL DEFAULT '';
+ALTER TABLE misc_settings ADD COLUMN podcast_directories TEXT NOT NULL DEFAULT '';
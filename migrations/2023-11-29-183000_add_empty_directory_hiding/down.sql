@@ -0,0 +1 @@
+ALTER TABLE misc_settings DROP COLUMN hide_empty_directories;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN hide_empty_directories BOOLEAN NOT NULL DEFAULT 0;
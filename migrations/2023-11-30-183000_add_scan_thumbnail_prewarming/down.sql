@@ -0,0 +1 @@
+ALTER TABLE misc_settings DROP COLUMN prewarm_thumbnails_on_scan;
@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN prewarm_thumbnails_on_scan BOOLEAN NOT NULL DEFAULT 0;
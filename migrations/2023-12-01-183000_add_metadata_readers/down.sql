@@ -0,0 +1 @@
+ALTER TABLE misc_settings DROP COLUMN metadata_readers;
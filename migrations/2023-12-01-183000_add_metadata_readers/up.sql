@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN metadata_readers TEXT NOT NULL DEFAULT '{}';
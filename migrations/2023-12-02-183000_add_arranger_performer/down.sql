@@ -0,0 +1,2 @@
+ALTER TABLE songs DROP COLUMN arranger;
+ALTER TABLE songs DROP COLUMN performer;
@@ -0,0 +1,2 @@
+ALTER TABLE songs ADD COLUMN arranger TEXT;
+ALTER TABLE songs ADD COLUMN performer TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE misc_settings DROP COLUMN mount_order;
+ALTER TABLE mount_points DROP COLUMN hide_from_root;
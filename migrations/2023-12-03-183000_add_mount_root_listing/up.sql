@@ -0,0 +1,2 @@
+ALTER TABLE mount_points ADD COLUMN hide_from_root BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE misc_settings ADD COLUMN mount_order TEXT NOT NULL DEFAULT 'alphabetical';
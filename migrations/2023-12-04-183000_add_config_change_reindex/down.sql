@@ -0,0 +1 @@
+ALTER TABLE misc_settings DROP COLUMN reindex_on_config_change;
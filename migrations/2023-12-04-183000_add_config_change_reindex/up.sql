@@ -0,0 +1 @@
+ALTER TABLE misc_settings ADD COLUMN reindex_on_config_change BOOLEAN NOT NULL DEFAULT 0;
@@ -4,12 +4,19 @@ use std::path::PathBuf;
 use crate::db::{self, DB};
 use crate::paths::Paths;
 
+pub mod archive;
+pub mod bandwidth;
 pub mod config;
+pub mod cover;
 pub mod ddns;
 pub mod index;
 pub mod lastfm;
+pub mod lyrics;
+pub mod play_count;
 pub mod playlist;
+pub mod progress;
 pub mod settings;
+pub mod share;
 pub mod thumbnail;
 pub mod user;
 pub mod vfs;
@@ -27,6 +34,8 @@ pub enum Error {
 	Io(PathBuf, std::io::Error),
 	#[error(transparent)]
 	Settings(#[from] settings::Error),
+	#[error(transparent)]
+	User(#[from] user::Error),
 }
 
 #[derive(Clone)]
@@ -37,19 +46,25 @@ pub struct App {
 	pub swagger_dir_path: PathBuf,
 	pub db: DB,
 	pub index: index::Index,
+	pub archive_manager: archive::Manager,
+	pub bandwidth_manager: bandwidth::Manager,
 	pub config_manager: config::Manager,
+	pub cover_manager: cover::Manager,
 	pub ddns_manager: ddns::Manager,
 	pub lastfm_manager: lastfm::Manager,
+	pub play_count_manager: play_count::Manager,
 	pub playlist_manager: playlist::Manager,
+	pub progress_manager: progress::Manager,
 	pub settings_manager: settings::Manager,
+	pub share_manager: share::Manager,
 	pub thumbnail_manager: thumbnail::Manager,
 	pub user_manager: user::Manager,
 	pub vfs_manager: vfs::Manager,
 }
 
 impl App {
-	pub fn new(port: u16, paths: Paths) -> Result<Self, Error> {
-		let db = DB::new(&paths.db_file_path)?;
+	pub fn new(port: u16, paths: Paths, db_read_pool_size: u32) -> Result<Self, Error> {
+		let db = DB::with_read_pool_size(&paths.db_file_path, db_read_pool_size)?;
 		fs::create_dir_all(&paths.web_dir_path)
 			.map_err(|e| Error::Io(paths.web_dir_path.clone(), e))?;
 		fs::create_dir_all(&paths.swagger_dir_path)
@@ -59,28 +74,48 @@ impl App {
 		fs::create_dir_all(&thumbnails_dir_path)
 			.map_err(|e| Error::Io(thumbnails_dir_path.clone(), e))?;
 
+		let covers_dir_path = paths.cache_dir_path.join("covers");
+		fs::create_dir_all(&covers_dir_path).map_err(|e| Error::Io(covers_dir_path.clone(), e))?;
+
 		let vfs_manager = vfs::Manager::new(db.clone());
 		let settings_manager = settings::Manager::new(db.clone());
-		let auth_secret = settings_manager.get_auth_secret()?;
+		let auth_secret = get_auth_secret(&settings_manager)?;
 		let ddns_manager = ddns::Manager::new(db.clone());
 		let user_manager = user::Manager::new(db.clone(), auth_secret);
-		let index = index::Index::new(db.clone(), vfs_manager.clone(), settings_manager.clone());
+		let thumbnail_manager = thumbnail::Manager::new(thumbnails_dir_path);
+		let index = index::Index::new(
+			db.clone(),
+			vfs_manager.clone(),
+			settings_manager.clone(),
+			thumbnail_manager.clone(),
+		);
 		let config_manager = config::Manager::new(
 			settings_manager.clone(),
 			user_manager.clone(),
 			vfs_manager.clone(),
 			ddns_manager.clone(),
+			index.clone(),
 		);
+		let archive_manager = archive::Manager::new(index.clone(), vfs_manager.clone());
+		let bandwidth_manager = bandwidth::Manager::new(db.clone(), settings_manager.clone());
+		let play_count_manager = play_count::Manager::new(db.clone(), vfs_manager.clone());
 		let playlist_manager = playlist::Manager::new(db.clone(), vfs_manager.clone());
-		let thumbnail_manager = thumbnail::Manager::new(thumbnails_dir_path);
+		let progress_manager = progress::Manager::new(db.clone(), vfs_manager.clone());
+		let share_manager = share::Manager::new(db.clone(), vfs_manager.clone());
+		let cover_manager = cover::Manager::new(
+			db.clone(),
+			vfs_manager.clone(),
+			settings_manager.clone(),
+			covers_dir_path,
+		);
 		let lastfm_manager = lastfm::Manager::new(index.clone(), user_manager.clone());
 
 		if let Some(config_path) = paths.config_file_path {
 			let config = config::Config::from_path(&config_path)?;
-			config_manager.apply(&config)?;
+			config_manager.apply_without_reindex(&config)?;
 		}
 
-		let auth_secret = settings_manager.get_auth_secret()?;
+		let auth_secret = get_auth_secret(&settings_manager)?;
 
 		Ok(Self {
 			port,
@@ -88,11 +123,17 @@ impl App {
 			web_dir_path: paths.web_dir_path,
 			swagger_dir_path: paths.swagger_dir_path,
 			index,
+			archive_manager,
+			bandwidth_manager,
 			config_manager,
+			cover_manager,
 			ddns_manager,
 			lastfm_manager,
+			play_count_manager,
 			playlist_manager,
+			progress_manager,
 			settings_manager,
+			share_manager,
 			thumbnail_manager,
 			user_manager,
 			vfs_manager,
@@ -100,3 +141,11 @@ impl App {
 		})
 	}
 }
+
+/// The `POLARIS_SECRET` environment variable, when set, overrides the secret persisted in the database.
+fn get_auth_secret(settings_manager: &settings::Manager) -> Result<settings::AuthSecret, Error> {
+	match std::env::var("POLARIS_SECRET") {
+		Ok(secret) => Ok(settings::AuthSecret::from_base64(&secret)?),
+		Err(_) => Ok(settings_manager.get_auth_secret()?),
+	}
+}
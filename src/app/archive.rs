@@ -0,0 +1,378 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use crate::app::index::{self, Index};
+use crate::app::vfs;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+
+const LOCAL_FILE_HEADER_SIZE: u64 = 30;
+const DATA_DESCRIPTOR_SIZE: u64 = 16;
+const CENTRAL_DIRECTORY_HEADER_SIZE: u64 = 46;
+const END_OF_CENTRAL_DIRECTORY_SIZE: u64 = 22;
+
+/// Version 2.0 of the zip specification, which introduced data descriptors.
+const ZIP_VERSION: u16 = 20;
+/// Sizes and CRC follow the file data, and file names are UTF-8.
+const GENERAL_PURPOSE_FLAGS: u16 = 0x0008 | 0x0800;
+const COMPRESSION_METHOD_STORE: u16 = 0;
+/// 1980-01-01, the earliest date zip files can represent.
+const MODIFICATION_DATE: u16 = (1 << 5) | 1;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("Path `{0}` is not a valid location within the collection")]
+	InvalidPath(PathBuf),
+	#[error("Filesystem error for `{0}`: `{1}`")]
+	Io(PathBuf, std::io::Error),
+	#[error("No songs were found under `{0}`")]
+	NoSongs(PathBuf),
+	#[error(transparent)]
+	Query(#[from] index::QueryError),
+	#[error("Archive of `{0}` would exceed the size limits of the zip format")]
+	TooLarge(PathBuf),
+	#[error(transparent)]
+	Vfs(#[from] vfs::Error),
+}
+
+#[derive(Clone)]
+pub struct Manager {
+	index: Index,
+	vfs_manager: vfs::Manager,
+}
+
+impl Manager {
+	pub fn new(index: Index, vfs_manager: vfs::Manager) -> Self {
+		Self { index, vfs_manager }
+	}
+
+	/// Lists the songs under a virtual directory, to be streamed as an uncompressed zip archive.
+	pub fn prepare(&self, virtual_path: &Path) -> Result<Archive, Error> {
+		let escapes_collection = virtual_path
+			.components()
+			.any(|c| !matches!(c, Component::Normal(_)));
+		if escapes_collection || virtual_path.parent().is_none() {
+			return Err(Error::InvalidPath(virtual_path.to_owned()));
+		}
+
+		let vfs = self.vfs_manager.get_vfs()?;
+		let songs = self.index.flatten(virtual_path, false)?;
+		if songs.is_empty() {
+			return Err(Error::NoSongs(virtual_path.to_owned()));
+		}
+
+		let base_path = virtual_path.parent().unwrap_or(virtual_path);
+		let mut entries = Vec::with_capacity(songs.len());
+		let mut archive_size = END_OF_CENTRAL_DIRECTORY_SIZE;
+		for song in &songs {
+			let song_virtual_path = Path::new(&song.path);
+			let name = song_virtual_path
+				.strip_prefix(base_path)
+				.map_err(|_| Error::InvalidPath(song_virtual_path.to_owned()))?
+				.components()
+				.map(|c| c.as_os_str().to_string_lossy())
+				.collect::<Vec<_>>()
+				.join("/");
+			let real_path = vfs.virtual_to_real(song_virtual_path)?;
+			let size = std::fs::metadata(&real_path)
+				.map_err(|e| Error::Io(real_path.clone(), e))?
+				.len();
+			archive_size += LOCAL_FILE_HEADER_SIZE
+				+ DATA_DESCRIPTOR_SIZE
+				+ CENTRAL_DIRECTORY_HEADER_SIZE
+				+ 2 * name.len() as u64
+				+ size;
+			entries.push(Entry { name, real_path });
+		}
+
+		if archive_size > u32::MAX as u64 || entries.len() > u16::MAX as usize {
+			return Err(Error::TooLarge(virtual_path.to_owned()));
+		}
+
+		Ok(Archive {
+			file_name: archive_file_name(virtual_path, &songs),
+			virtual_path: virtual_path.to_owned(),
+			entries: entries.into_iter(),
+			current_entry: None,
+			central_directory: Vec::new(),
+			num_entries: 0,
+			offset: 0,
+			finished: false,
+		})
+	}
+}
+
+/// Names the archive after the album of its songs, or after the directory when songs belong to several albums.
+fn archive_file_name(virtual_path: &Path, songs: &[index::Song]) -> String {
+	let album = songs[0]
+		.album
+		.as_ref()
+		.filter(|album| !album.is_empty() && songs.iter().all(|s| s.album.as_ref() == Some(album)));
+	let name = match album {
+		Some(album) => album.clone(),
+		None => virtual_path
+			.file_name()
+			.map(|n| n.to_string_lossy().into_owned())
+			.unwrap_or_default(),
+	};
+	let name: String = name
+		.chars()
+		.map(|c| match c {
+			'/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+			c if c.is_control() => '_',
+			c => c,
+		})
+		.collect();
+	format!("{}.zip", name.trim())
+}
+
+struct Entry {
+	name: String,
+	real_path: PathBuf,
+}
+
+struct CurrentEntry {
+	name: String,
+	real_path: PathBuf,
+	file: File,
+	local_header_offset: u32,
+	hasher: crc32fast::Hasher,
+	size: u64,
+}
+
+/// Zip archive produced incrementally, one chunk at a time, so that it never has to be held in memory.
+/// Files are stored without compression since audio files barely compress.
+pub struct Archive {
+	file_name: String,
+	virtual_path: PathBuf,
+	entries: std::vec::IntoIter<Entry>,
+	current_entry: Option<CurrentEntry>,
+	central_directory: Vec<u8>,
+	num_entries: u16,
+	offset: u64,
+	finished: bool,
+}
+
+impl Archive {
+	pub fn file_name(&self) -> &str {
+		&self.file_name
+	}
+
+	/// Returns the next bytes of the archive, or `None` once the archive is complete.
+	pub fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, Error> {
+		let chunk = if let Some(mut current_entry) = self.current_entry.take() {
+			let mut chunk = vec![0; CHUNK_SIZE];
+			let num_bytes = current_entry
+				.file
+				.read(&mut chunk)
+				.map_err(|e| Error::Io(current_entry.real_path.clone(), e))?;
+			if num_bytes > 0 {
+				chunk.truncate(num_bytes);
+				current_entry.hasher.update(&chunk);
+				current_entry.size += num_bytes as u64;
+				self.current_entry = Some(current_entry);
+				chunk
+			} else {
+				self.end_entry(current_entry)?
+			}
+		} else if let Some(entry) = self.entries.next() {
+			self.begin_entry(entry)?
+		} else if !self.finished {
+			self.finished = true;
+			self.end_archive()?
+		} else {
+			return Ok(None);
+		};
+
+		self.offset += chunk.len() as u64;
+		Ok(Some(chunk))
+	}
+
+	fn begin_entry(&mut self, entry: Entry) -> Result<Vec<u8>, Error> {
+		let file =
+			File::open(&entry.real_path).map_err(|e| Error::Io(entry.real_path.clone(), e))?;
+
+		let mut header = Vec::new();
+		put_u32(&mut header, LOCAL_FILE_HEADER_SIGNATURE);
+		put_u16(&mut header, ZIP_VERSION);
+		put_u16(&mut header, GENERAL_PURPOSE_FLAGS);
+		put_u16(&mut header, COMPRESSION_METHOD_STORE);
+		put_u16(&mut header, 0);
+		put_u16(&mut header, MODIFICATION_DATE);
+		// CRC and sizes are written in the data descriptor
+		put_u32(&mut header, 0);
+		put_u32(&mut header, 0);
+		put_u32(&mut header, 0);
+		put_u16(&mut header, entry.name.len() as u16);
+		put_u16(&mut header, 0);
+		header.extend_from_slice(entry.name.as_bytes());
+
+		self.current_entry = Some(CurrentEntry {
+			name: entry.name,
+			real_path: entry.real_path,
+			file,
+			local_header_offset: self.checked_offset()?,
+			hasher: crc32fast::Hasher::new(),
+			size: 0,
+		});
+
+		Ok(header)
+	}
+
+	fn end_entry(&mut self, entry: CurrentEntry) -> Result<Vec<u8>, Error> {
+		let crc = entry.hasher.finalize();
+		let size =
+			u32::try_from(entry.size).map_err(|_| Error::TooLarge(self.virtual_path.clone()))?;
+
+		let mut descriptor = Vec::new();
+		put_u32(&mut descriptor, DATA_DESCRIPTOR_SIGNATURE);
+		put_u32(&mut descriptor, crc);
+		put_u32(&mut descriptor, size);
+		put_u32(&mut descriptor, size);
+
+		let record = &mut self.central_directory;
+		put_u32(record, CENTRAL_DIRECTORY_HEADER_SIGNATURE);
+		put_u16(record, ZIP_VERSION);
+		put_u16(record, ZIP_VERSION);
+		put_u16(record, GENERAL_PURPOSE_FLAGS);
+		put_u16(record, COMPRESSION_METHOD_STORE);
+		put_u16(record, 0);
+		put_u16(record, MODIFICATION_DATE);
+		put_u32(record, crc);
+		put_u32(record, size);
+		put_u32(record, size);
+		put_u16(record, entry.name.len() as u16);
+		put_u16(record, 0);
+		put_u16(record, 0);
+		put_u16(record, 0);
+		put_u16(record, 0);
+		put_u32(record, 0);
+		put_u32(record, entry.local_header_offset);
+		record.extend_from_slice(entry.name.as_bytes());
+		self.num_entries += 1;
+
+		Ok(descriptor)
+	}
+
+	fn end_archive(&mut self) -> Result<Vec<u8>, Error> {
+		let central_directory_offset = self.checked_offset()?;
+		let mut chunk = std::mem::take(&mut self.central_directory);
+		let central_directory_size = chunk.len() as u32;
+		put_u32(&mut chunk, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+		put_u16(&mut chunk, 0);
+		put_u16(&mut chunk, 0);
+		put_u16(&mut chunk, self.num_entries);
+		put_u16(&mut chunk, self.num_entries);
+		put_u32(&mut chunk, central_directory_size);
+		put_u32(&mut chunk, central_directory_offset);
+		put_u16(&mut chunk, 0);
+		Ok(chunk)
+	}
+
+	fn checked_offset(&self) -> Result<u32, Error> {
+		u32::try_from(self.offset).map_err(|_| Error::TooLarge(self.virtual_path.clone()))
+	}
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+	buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+	buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_MOUNT_NAME: &str = "root";
+
+	fn read_archive(mut archive: Archive) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		while let Some(chunk) = archive.next_chunk().unwrap() {
+			bytes.extend(chunk);
+		}
+		bytes
+	}
+
+	fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+		u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+	}
+
+	fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+		u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+	}
+
+	#[test]
+	fn archive_lists_songs_in_central_directory() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+		ctx.index.update().unwrap();
+
+		let hunted_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+		let archive = ctx.archive_manager.prepare(&hunted_path).unwrap();
+		assert_eq!(archive.file_name(), "Hunted.zip");
+		let bytes = read_archive(archive);
+
+		let end = bytes.len() - END_OF_CENTRAL_DIRECTORY_SIZE as usize;
+		assert_eq!(read_u32(&bytes, end), END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+		let num_entries = read_u16(&bytes, end + 10);
+		let mut offset = read_u32(&bytes, end + 16) as usize;
+
+		let mut names = Vec::new();
+		for _ in 0..num_entries {
+			assert_eq!(read_u32(&bytes, offset), CENTRAL_DIRECTORY_HEADER_SIGNATURE);
+			let size = read_u32(&bytes, offset + 24) as u64;
+			let name_length = read_u16(&bytes, offset + 28) as usize;
+			let name_start = offset + CENTRAL_DIRECTORY_HEADER_SIZE as usize;
+			let name =
+				String::from_utf8(bytes[name_start..name_start + name_length].to_vec()).unwrap();
+
+			let local_header_offset = read_u32(&bytes, offset + 42) as usize;
+			assert_eq!(
+				read_u32(&bytes, local_header_offset),
+				LOCAL_FILE_HEADER_SIGNATURE
+			);
+			let real_path = Path::new("test-data/small-collection/Khemmis").join(&name);
+			assert_eq!(size, std::fs::metadata(real_path).unwrap().len());
+
+			names.push(name);
+			offset = name_start + name_length;
+		}
+
+		assert_eq!(
+			names,
+			vec![
+				"Hunted/01 - Above The Water.mp3",
+				"Hunted/02 - Candlelight.mp3",
+				"Hunted/03 - Three Gates.mp3",
+				"Hunted/04 - Beyond The Door.mp3",
+				"Hunted/05 - Hunted.mp3",
+			]
+		);
+	}
+
+	#[test]
+	fn archive_rejects_paths_escaping_mount() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+		ctx.index.update().unwrap();
+
+		let escaping_path: PathBuf = [TEST_MOUNT_NAME, "..", "small-collection"].iter().collect();
+		assert!(matches!(
+			ctx.archive_manager.prepare(&escaping_path),
+			Err(Error::InvalidPath(_))
+		));
+	}
+}
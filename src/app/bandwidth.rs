@@ -0,0 +1,214 @@
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+use crate::app::settings::{self, BandwidthRollover};
+use crate::db::{self, bandwidth_usage, users, DB};
+use crate::utils::now;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error(transparent)]
+	Database(#[from] diesel::result::Error),
+	#[error(transparent)]
+	DatabaseConnection(#[from] db::Error),
+	#[error(transparent)]
+	Settings(#[from] settings::Error),
+	#[error("User not found")]
+	UserNotFound,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Usage {
+	pub username: String,
+	pub bytes: u64,
+	/// Seconds since the UNIX epoch.
+	pub period_start: i64,
+}
+
+#[derive(Clone)]
+pub struct Manager {
+	db: DB,
+	settings_manager: settings::Manager,
+}
+
+impl Manager {
+	pub fn new(db: DB, settings_manager: settings::Manager) -> Self {
+		Self {
+			db,
+			settings_manager,
+		}
+	}
+
+	pub fn is_enabled(&self) -> Result<bool, Error> {
+		Ok(self.settings_manager.read()?.track_bandwidth)
+	}
+
+	/// Adds bytes served to a user to their counter for the current period.
+	pub fn record(&self, username: &str, bytes: u64) -> Result<(), Error> {
+		let rollover = self.settings_manager.read()?.bandwidth_rollover;
+		let current_period_start = period_start(rollover, now());
+
+		let mut connection = self.db.connect()?;
+		let user_id: i32 = users::table
+			.select(users::id)
+			.filter(users::name.eq(username))
+			.get_result(&mut connection)
+			.optional()?
+			.ok_or(Error::UserNotFound)?;
+
+		connection.transaction::<_, Error, _>(|connection| {
+			let previous: Option<(i64, i64)> = bandwidth_usage::table
+				.select((bandwidth_usage::period_start, bandwidth_usage::bytes))
+				.filter(bandwidth_usage::owner.eq(user_id))
+				.get_result(&mut *connection)
+				.optional()?;
+			let previous_bytes = match previous {
+				Some((start, bytes)) if start >= current_period_start => bytes,
+				_ => 0,
+			};
+			diesel::insert_into(bandwidth_usage::table)
+				.values((
+					bandwidth_usage::owner.eq(user_id),
+					bandwidth_usage::period_start.eq(current_period_start),
+					bandwidth_usage::bytes.eq(previous_bytes + bytes as i64),
+				))
+				.execute(&mut *connection)?;
+			Ok(())
+		})
+	}
+
+	/// Returns bytes served to each user during the current period.
+	pub fn usage(&self) -> Result<Vec<Usage>, Error> {
+		let rollover = self.settings_manager.read()?.bandwidth_rollover;
+		let current_period_start = period_start(rollover, now());
+
+		let mut connection = self.db.connect()?;
+		let usernames: Vec<(i32, String)> = users::table
+			.select((users::id, users::name))
+			.order(users::name)
+			.load(&mut connection)?;
+		let counters: HashMap<i32, (i64, i64)> = bandwidth_usage::table
+			.select((
+				bandwidth_usage::owner,
+				bandwidth_usage::period_start,
+				bandwidth_usage::bytes,
+			))
+			.load::<(i32, i64, i64)>(&mut connection)?
+			.into_iter()
+			.map(|(owner, start, bytes)| (owner, (start, bytes)))
+			.collect();
+
+		Ok(usernames
+			.into_iter()
+			.map(|(id, username)| {
+				let bytes = match counters.get(&id) {
+					Some((start, bytes)) if *start >= current_period_start => *bytes as u64,
+					_ => 0,
+				};
+				Usage {
+					username,
+					bytes,
+					period_start: current_period_start,
+				}
+			})
+			.collect())
+	}
+}
+
+/// Returns the start of the period containing a timestamp. Periods begin at midnight UTC.
+fn period_start(rollover: BandwidthRollover, timestamp: i64) -> i64 {
+	let days = timestamp.div_euclid(SECONDS_PER_DAY);
+	match rollover {
+		BandwidthRollover::Never => 0,
+		BandwidthRollover::Daily => days * SECONDS_PER_DAY,
+		BandwidthRollover::Monthly => (days - (day_of_month(days) - 1)) * SECONDS_PER_DAY,
+	}
+}
+
+/// Computes the day of the month from a number of days since the UNIX epoch.
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn day_of_month(days_since_epoch: i64) -> i64 {
+	let z = days_since_epoch + 719_468;
+	let era = z.div_euclid(146_097);
+	let day_of_era = z - era * 146_097;
+	let year_of_era =
+		(day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let month_index = (5 * day_of_year + 2) / 153;
+	day_of_year - (153 * month_index + 2) / 5 + 1
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+	const TEST_PASSWORD: &str = "password";
+	const OTHER_USER: &str = "other_user";
+
+	#[test]
+	fn period_start_follows_rollover() {
+		// 2023-10-14 12:00:00 UTC
+		let timestamp = 1_697_284_800;
+		assert_eq!(period_start(BandwidthRollover::Never, timestamp), 0);
+		assert_eq!(
+			period_start(BandwidthRollover::Daily, timestamp),
+			1_697_241_600
+		);
+		assert_eq!(
+			period_start(BandwidthRollover::Monthly, timestamp),
+			1_696_118_400
+		);
+
+		// 2024-02-29 23:59:59 UTC
+		assert_eq!(
+			period_start(BandwidthRollover::Monthly, 1_709_251_199),
+			1_706_745_600
+		);
+	}
+
+	#[test]
+	fn record_adds_to_user_counter() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.user(OTHER_USER, TEST_PASSWORD, false)
+			.build();
+
+		ctx.bandwidth_manager.record(TEST_USER, 100).unwrap();
+		ctx.bandwidth_manager.record(TEST_USER, 50).unwrap();
+
+		let usage = ctx.bandwidth_manager.usage().unwrap();
+		let bytes_of = |username: &str| {
+			usage
+				.iter()
+				.find(|u| u.username == username)
+				.map(|u| u.bytes)
+		};
+		assert_eq!(bytes_of(TEST_USER), Some(150));
+		assert_eq!(bytes_of(OTHER_USER), Some(0));
+	}
+
+	#[test]
+	fn counters_from_previous_period_are_reset() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build();
+
+		ctx.bandwidth_manager.record(TEST_USER, 100).unwrap();
+		{
+			let mut connection = ctx.db.connect().unwrap();
+			diesel::update(bandwidth_usage::table)
+				.set(bandwidth_usage::period_start.eq(0))
+				.execute(&mut connection)
+				.unwrap();
+		}
+
+		assert_eq!(ctx.bandwidth_manager.usage().unwrap()[0].bytes, 0);
+		ctx.bandwidth_manager.record(TEST_USER, 10).unwrap();
+		assert_eq!(ctx.bandwidth_manager.usage().unwrap()[0].bytes, 10);
+	}
+}
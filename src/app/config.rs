@@ -2,12 +2,16 @@ use serde::Deserialize;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 
-use crate::app::{ddns, settings, user, vfs};
+use crate::app::{ddns, index, settings, user, vfs};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
 	#[error(transparent)]
 	Ddns(#[from] ddns::Error),
+	#[error(transparent)]
+	Index(#[from] index::Error),
+	#[error("Invalid configuration: {0:?}")]
+	Invalid(Vec<Problem>),
 	#[error("Filesystem error for `{0}`: `{1}`")]
 	Io(PathBuf, std::io::Error),
 	#[error(transparent)]
@@ -20,6 +24,28 @@ pub enum Error {
 	Vfs(#[from] vfs::Error),
 }
 
+const MIN_REINDEX_INTERVAL_SECONDS: i32 = 10;
+const MAX_REINDEX_INTERVAL_SECONDS: i32 = 365 * 24 * 60 * 60;
+
+/// A field of a configuration which could not be applied, and why.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+	pub field: String,
+	pub message: String,
+}
+
+impl Problem {
+	fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+		Self {
+			field: field.into(),
+			message: message.into(),
+		}
+	}
+}
+
+/// A set of configuration changes. Sections set to `None` are left untouched
+/// when the configuration is applied, while sections set to an empty list are cleared.
+/// Empty passwords leave the existing passwords of users and of the DDNS account intact.
 #[derive(Default, Deserialize)]
 pub struct Config {
 	pub settings: Option<settings::NewSettings>,
@@ -39,6 +65,199 @@ impl Config {
 		let config = toml::de::from_str::<Self>(&config_file_content)?;
 		Ok(config)
 	}
+
+	/// Lists all fields holding invalid values, using dotted paths to identify them.
+	pub fn validate(&self) -> Vec<Problem> {
+		let mut problems = Vec::new();
+
+		if let Some(settings) = &self.settings {
+			if let Some(pattern) = &settings.album_art_pattern {
+				if settings::compile_album_art_pattern(pattern).is_err() {
+					problems.push(Problem::new(
+						"settings.album_art_pattern",
+						"Not a valid regular expression",
+					));
+				}
+			}
+			if let Some(interval) = settings.reindex_every_n_seconds {
+				if !(MIN_REINDEX_INTERVAL_SECONDS..=MAX_REINDEX_INTERVAL_SECONDS)
+					.contains(&interval)
+				{
+					problems.push(Problem::new(
+						"settings.reindex_every_n_seconds",
+						format!(
+							"Must be between {} and {} seconds",
+							MIN_REINDEX_INTERVAL_SECONDS, MAX_REINDEX_INTERVAL_SECONDS
+						),
+					));
+				}
+			}
+			if let Some(max_depth) = settings.index_max_depth {
+				if max_depth < 1 {
+					problems.push(Problem::new(
+						"settings.index_max_depth",
+						"Must be at least 1",
+					));
+				}
+			}
+			if let Some(min_size) = settings.min_file_size_bytes {
+				if min_size < 0 {
+					problems.push(Problem::new(
+						"settings.min_file_size_bytes",
+						"Must not be negative",
+					));
+				}
+			}
+			if let Some(limit) = settings.scan_failure_limit {
+				if limit < 0 {
+					problems.push(Problem::new(
+						"settings.scan_failure_limit",
+						"Must not be negative",
+					));
+				}
+			}
+			if let Some(max_bytes) = settings.max_cover_bytes {
+				if max_bytes < 0 {
+					problems.push(Problem::new(
+						"settings.max_cover_bytes",
+						"Must not be negative",
+					));
+				}
+			}
+			if let Some(placeholder_path) = &settings.artwork_placeholder_path {
+				if !placeholder_path.is_empty() && !Path::new(placeholder_path).is_file() {
+					problems.push(Problem::new(
+						"settings.artwork_placeholder_path",
+						"Must be an existing image file",
+					));
+				}
+			}
+			if let Some(priority) = &settings.tag_priority {
+				if priority.is_empty() {
+					problems.push(Problem::new(
+						"settings.tag_priority",
+						"Must list at least one tag container",
+					));
+				}
+			}
+			if let Some(readers) = &settings.metadata_readers {
+				if readers
+					.keys()
+					.any(|e| settings::normalize_extension(e).is_empty())
+				{
+					problems.push(Problem::new(
+						"settings.metadata_readers",
+						"File extensions must not be empty",
+					));
+				}
+			}
+			if let Some(pattern) = &settings.artist_image_pattern {
+				if settings::compile_artist_image_pattern(pattern).is_err() {
+					problems.push(Problem::new(
+						"settings.artist_image_pattern",
+						"Not a valid regular expression",
+					));
+				}
+			}
+			if let Some(size) = settings.default_page_size {
+				if size < 1 {
+					problems.push(Problem::new(
+						"settings.default_page_size",
+						"Must be at least 1",
+					));
+				}
+			}
+			if let Some(size) = settings.max_page_size {
+				if size < 1 {
+					problems.push(Problem::new("settings.max_page_size", "Must be at least 1"));
+				} else if settings.default_page_size.is_some_and(|d| d > size) {
+					problems.push(Problem::new(
+						"settings.max_page_size",
+						"Must not be less than the default page size",
+					));
+				}
+			}
+			if let Some(url) = &settings.public_base_url {
+				if settings::parse_public_base_url(url).is_err() {
+					problems.push(Problem::new(
+						"settings.public_base_url",
+						"Must be an absolute http or https URL",
+					));
+				}
+			}
+		}
+
+		if let Some(mount_dirs) = &self.mount_dirs {
+			problems.extend(validate_mount_dirs(mount_dirs));
+		}
+
+		if let Some(ydns) = &self.ydns {
+			if !ydns.host.is_empty() && !is_valid_hostname(&ydns.host) {
+				problems.push(Problem::new("ydns.host", "Not a valid host name"));
+			}
+		}
+
+		problems
+	}
+}
+
+/// Lists mount points which can't be applied. Names are checked once surrounding whitespace is
+/// trimmed, and must be usable as a single component of virtual paths.
+fn validate_mount_dirs(mount_dirs: &[vfs::MountDir]) -> Vec<Problem> {
+	let mut problems = Vec::new();
+	for (index, mount_dir) in mount_dirs.iter().enumerate() {
+		let name = mount_dir.name.trim();
+		if name.is_empty() {
+			problems.push(Problem::new(
+				format!("mount_dirs[{index}].name"),
+				"Must not be empty",
+			));
+		} else if name.contains(['/', '\\']) {
+			problems.push(Problem::new(
+				format!("mount_dirs[{index}].name"),
+				format!("Must not contain path separators, found `{name}`"),
+			));
+		} else if name == "." || name == ".." {
+			problems.push(Problem::new(
+				format!("mount_dirs[{index}].name"),
+				"Must not be `.` or `..`",
+			));
+		}
+		if mount_dir.source.trim().is_empty() {
+			problems.push(Problem::new(
+				format!("mount_dirs[{index}].source"),
+				"Must not be empty",
+			));
+		}
+		if let Some(interval) = mount_dir.reindex_every_n_seconds {
+			if !(MIN_REINDEX_INTERVAL_SECONDS..=MAX_REINDEX_INTERVAL_SECONDS).contains(&interval) {
+				problems.push(Problem::new(
+					format!("mount_dirs[{index}].reindex_every_n_seconds"),
+					format!(
+						"Must be between {} and {} seconds",
+						MIN_REINDEX_INTERVAL_SECONDS, MAX_REINDEX_INTERVAL_SECONDS
+					),
+				));
+			}
+		}
+	}
+	problems
+}
+
+/// Checks a host name against RFC 1123, allowing non-ASCII labels.
+fn is_valid_hostname(host: &str) -> bool {
+	if host.len() > 253 {
+		return false;
+	}
+	host.split('.').all(|label| {
+		!label.is_empty()
+			&& label.len() <= 63
+			&& !label.starts_with('-')
+			&& !label.ends_with('-')
+			&& label
+				.chars()
+				.all(|c| c.is_ascii_alphanumeric() || c == '-' || !c.is_ascii())
+	})
 }
 
 #[derive(Clone)]
@@ -47,6 +266,7 @@ pub struct Manager {
 	user_manager: user::Manager,
 	vfs_manager: vfs::Manager,
 	ddns_manager: ddns::Manager,
+	index: index::Index,
 }
 
 impl Manager {
@@ -55,26 +275,123 @@ impl Manager {
 		user_manager: user::Manager,
 		vfs_manager: vfs::Manager,
 		ddns_manager: ddns::Manager,
+		index: index::Index,
 	) -> Self {
 		Self {
 			settings_manager,
 			user_manager,
 			vfs_manager,
 			ddns_manager,
+			index,
 		}
 	}
 
+	/// Reads the current configuration. Passwords are left empty, so that applying
+	/// the exported configuration preserves existing passwords.
+	pub fn export(&self) -> Result<Config, Error> {
+		let settings = self.settings_manager.read()?;
+		let mount_dirs = self.vfs_manager.mount_dirs()?;
+		let ydns = ddns::Config {
+			password: String::new(),
+			..self.ddns_manager.config()?
+		};
+		let users = self
+			.user_manager
+			.list()?
+			.into_iter()
+			.map(|u| user::NewUser {
+				admin: u.is_admin(),
+				name: u.name,
+				password: String::new(),
+			})
+			.collect();
+
+		Ok(Config {
+			settings: Some(settings::NewSettings {
+				reindex_every_n_seconds: Some(settings.index_sleep_duration_seconds),
+				album_art_pattern: Some(settings.index_album_art_pattern),
+				auto_rename_duplicate_mounts: Some(settings.auto_rename_duplicate_mounts),
+				normalize_genres: Some(settings.normalize_genres),
+				genre_aliases: Some(settings.genre_aliases),
+				fallback_artist: Some(settings.fallback_artist),
+				fallback_album: Some(settings.fallback_album),
+				track_bandwidth: Some(settings.track_bandwidth),
+				bandwidth_rollover: Some(settings.bandwidth_rollover),
+				scan_on_startup: Some(settings.scan_on_startup),
+				album_artist_grouping: Some(settings.album_artist_grouping),
+				snapshot_index_updates: Some(settings.snapshot_index_updates),
+				expose_extra_tags: Some(settings.expose_extra_tags),
+				index_max_depth: Some(settings.index_max_depth),
+				public_base_url: Some(settings.public_base_url),
+				min_file_size_bytes: Some(settings.min_file_size_bytes),
+				artwork_placeholder_path: Some(settings.artwork_placeholder_path),
+				skip_hidden_files: Some(settings.skip_hidden_files),
+				artist_image_pattern: Some(settings.artist_image_pattern),
+				merge_duplicate_albums: Some(settings.merge_duplicate_albums),
+				preferred_album_format: Some(settings.preferred_album_format),
+				tag_priority: Some(settings.tag_priority),
+				estimate_missing_durations: Some(settings.estimate_missing_durations),
+				album_artist_fallback: Some(settings.album_artist_fallback),
+				default_page_size: Some(settings.default_page_size),
+				max_page_size: Some(settings.max_page_size),
+				artwork_priority: Some(settings.artwork_priority),
+				normalize_artist_names: Some(settings.normalize_artist_names),
+				artist_name_articles: Some(settings.artist_name_articles),
+				directory_thumbnail: Some(settings.directory_thumbnail),
+				scan_failure_limit: Some(settings.scan_failure_limit),
+				analyze_loudness: Some(settings.analyze_loudness),
+				symlinked_duplicates: Some(settings.symlinked_duplicates),
+				max_cover_bytes: Some(settings.max_cover_bytes),
+				audiobook_directories: Some(settings.audiobook_directories),
+				podcast_directories: Some(settings.podcast_directories),
+				hide_empty_directories: Some(settings.hide_empty_directories),
+				prewarm_thumbnails_on_scan: Some(settings.prewarm_thumbnails_on_scan),
+				metadata_readers: Some(settings.metadata_readers),
+				mount_order: Some(settings.mount_order),
+				reindex_on_config_change: Some(settings.reindex_on_config_change),
+			}),
+			mount_dirs: Some(mount_dirs),
+			ydns: Some(ydns),
+			users: Some(users),
+		})
+	}
+
+	/// Applies a configuration. When the `reindex_on_config_change` setting is enabled, this also
+	/// refreshes the index if mount points or the album art pattern changed. Only the affected
+	/// mounts are scanned when possible.
 	pub fn apply(&self, config: &Config) -> Result<(), Error> {
+		let old_album_art_pattern = self.settings_manager.read()?.index_album_art_pattern;
+		let old_mount_dirs = self.vfs_manager.mount_dirs()?;
+		self.apply_without_reindex(config)?;
+		self.reindex_changes(&old_album_art_pattern, &old_mount_dirs)
+	}
+
+	/// Applies a configuration without refreshing the index, as when loading the configuration
+	/// file on startup before the initial scan.
+	pub fn apply_without_reindex(&self, config: &Config) -> Result<(), Error> {
+		let problems = config.validate();
+		if !problems.is_empty() {
+			return Err(Error::Invalid(problems));
+		}
+
 		if let Some(new_settings) = &config.settings {
 			self.settings_manager.amend(new_settings)?;
 		}
 
 		if let Some(mount_dirs) = &config.mount_dirs {
-			self.vfs_manager.set_mount_dirs(mount_dirs)?;
+			self.set_mount_dirs(mount_dirs)?;
 		}
 
 		if let Some(ddns_config) = &config.ydns {
-			self.ddns_manager.set_config(ddns_config)?;
+			if ddns_config.password.is_empty() {
+				let ddns_config = ddns::Config {
+					password: self.ddns_manager.config()?.password,
+					..ddns_config.clone()
+				};
+				self.ddns_manager.set_config(&ddns_config)?;
+			} else {
+				self.ddns_manager.set_config(ddns_config)?;
+			}
 		}
 
 		if let Some(ref users) = config.users {
@@ -98,13 +415,84 @@ impl Manager {
 
 			// Update users
 			for user in users {
-				self.user_manager.set_password(&user.name, &user.password)?;
+				if !user.password.is_empty() {
+					self.user_manager.set_password(&user.name, &user.password)?;
+				}
 				self.user_manager.set_is_admin(&user.name, user.admin)?;
 			}
 		}
 
 		Ok(())
 	}
+
+	/// Saves mount points, renaming duplicates when the corresponding setting is enabled.
+	/// Names are trimmed of surrounding whitespace. Returns the mount points as they were saved.
+	pub fn set_mount_dirs(
+		&self,
+		mount_dirs: &[vfs::MountDir],
+	) -> Result<Vec<vfs::MountDir>, Error> {
+		let problems = validate_mount_dirs(mount_dirs);
+		if !problems.is_empty() {
+			return Err(Error::Invalid(problems));
+		}
+		let mount_dirs: Vec<vfs::MountDir> = mount_dirs
+			.iter()
+			.map(|m| vfs::MountDir {
+				name: m.name.trim().to_owned(),
+				..m.clone()
+			})
+			.collect();
+		let settings = self.settings_manager.read()?;
+		let mount_dirs = if settings.auto_rename_duplicate_mounts {
+			vfs::resolve_name_collisions(&mount_dirs)
+		} else {
+			mount_dirs
+		};
+		self.vfs_manager.set_mount_dirs(&mount_dirs)?;
+		Ok(mount_dirs)
+	}
+
+	fn reindex_changes(
+		&self,
+		old_album_art_pattern: &str,
+		old_mount_dirs: &[vfs::MountDir],
+	) -> Result<(), Error> {
+		let settings = self.settings_manager.read()?;
+		let mount_dirs = self.vfs_manager.mount_dirs()?;
+
+		// Songs of removed mounts are dropped right away instead of lingering until the next scan
+		let removed_mount = old_mount_dirs
+			.iter()
+			.any(|old| !mount_dirs.iter().any(|m| m.name == old.name));
+		if removed_mount {
+			self.index.prune_unmounted()?;
+		}
+
+		if !settings.reindex_on_config_change {
+			return Ok(());
+		}
+
+		if settings.index_album_art_pattern != old_album_art_pattern {
+			self.index.trigger_reindex();
+			return Ok(());
+		}
+
+		let changed_mounts: Vec<String> = mount_dirs
+			.into_iter()
+			.filter(|m| {
+				!old_mount_dirs.iter().any(|old| {
+					old.name == m.name
+						&& old.source == m.source
+						&& old.album_art_pattern == m.album_art_pattern
+				})
+			})
+			.map(|m| m.name)
+			.collect();
+		if !changed_mounts.is_empty() {
+			self.index.trigger_mount_reindex(changed_mounts);
+		}
+		Ok(())
+	}
 }
 
 #[cfg(test)]
@@ -121,6 +509,7 @@ mod test {
 			settings: Some(settings::NewSettings {
 				album_art_pattern: Some("🖼️\\.jpg".into()),
 				reindex_every_n_seconds: Some(100),
+				..Default::default()
 			}),
 			..Default::default()
 		};
@@ -138,6 +527,152 @@ mod test {
 		);
 	}
 
+	#[test]
+	fn apply_reports_invalid_fields() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+
+		let new_config = Config {
+			settings: Some(settings::NewSettings {
+				album_art_pattern: Some("(cover".into()),
+				reindex_every_n_seconds: Some(0),
+				index_max_depth: Some(0),
+				min_file_size_bytes: Some(-1),
+				public_base_url: Some("music.example.com".into()),
+				..Default::default()
+			}),
+			mount_dirs: Some(vec![
+				vfs::MountDir {
+					source: "/home/music".into(),
+					name: "music".into(),
+					album_art_pattern: None,
+					reindex_every_n_seconds: None,
+					hide_from_root: false,
+				},
+				vfs::MountDir {
+					source: "".into(),
+					name: "a/b".into(),
+					album_art_pattern: None,
+					reindex_every_n_seconds: None,
+					hide_from_root: false,
+				},
+			]),
+			ydns: Some(ddns::Config {
+				host: "-invalid..ydns.eu".into(),
+				username: "user".into(),
+				password: "password".into(),
+			}),
+			..Default::default()
+		};
+
+		let problems = match ctx.config_manager.apply(&new_config) {
+			Err(Error::Invalid(problems)) => problems,
+			_ => panic!("Expected configuration to be rejected"),
+		};
+		let fields: Vec<&str> = problems.iter().map(|p| p.field.as_str()).collect();
+		assert_eq!(
+			fields,
+			vec![
+				"settings.album_art_pattern",
+				"settings.reindex_every_n_seconds",
+				"settings.index_max_depth",
+				"settings.min_file_size_bytes",
+				"settings.public_base_url",
+				"mount_dirs[1].name",
+				"mount_dirs[1].source",
+				"ydns.host",
+			]
+		);
+	}
+
+	#[test]
+	fn apply_rejects_mount_name_with_slash() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+
+		for name in ["music/", "/music", "music/rock"] {
+			let new_config = Config {
+				mount_dirs: Some(vec![vfs::MountDir {
+					source: "test-data/small-collection".into(),
+					name: name.into(),
+					album_art_pattern: None,
+					reindex_every_n_seconds: None,
+					hide_from_root: false,
+				}]),
+				..Default::default()
+			};
+			let problems = match ctx.config_manager.apply(&new_config) {
+				Err(Error::Invalid(problems)) => problems,
+				_ => panic!("Expected mount name `{name}` to be rejected"),
+			};
+			assert_eq!(
+				problems,
+				vec![Problem::new(
+					"mount_dirs[0].name",
+					format!("Must not contain path separators, found `{name}`"),
+				)]
+			);
+		}
+
+		assert!(ctx.vfs_manager.mount_dirs().unwrap().is_empty());
+	}
+
+	#[test]
+	fn apply_accepts_clean_mount_name() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+
+		let new_config = Config {
+			mount_dirs: Some(vec![vfs::MountDir {
+				source: "test-data/small-collection".into(),
+				name: " Library ".into(),
+				album_art_pattern: None,
+				reindex_every_n_seconds: None,
+				hide_from_root: false,
+			}]),
+			..Default::default()
+		};
+		ctx.config_manager.apply(&new_config).unwrap();
+
+		let names: Vec<String> = ctx
+			.vfs_manager
+			.mount_dirs()
+			.unwrap()
+			.into_iter()
+			.map(|m| m.name)
+			.collect();
+		assert_eq!(names, vec!["Library".to_owned()]);
+	}
+
+	#[test]
+	fn apply_invalid_config_changes_nothing() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.mount("root", "test-data/small-collection")
+			.build();
+
+		let new_config = Config {
+			settings: Some(settings::NewSettings {
+				reindex_every_n_seconds: Some(-5),
+				..Default::default()
+			}),
+			mount_dirs: Some(Vec::new()),
+			..Default::default()
+		};
+		assert!(ctx.config_manager.apply(&new_config).is_err());
+
+		assert_eq!(ctx.vfs_manager.mount_dirs().unwrap().len(), 1);
+	}
+
+	#[test]
+	fn hostname_validation() {
+		assert!(is_valid_hostname("example.ydns.eu"));
+		assert!(is_valid_hostname("my-host.ydns.eu"));
+		assert!(is_valid_hostname("🐸🐸🐸.ydns.eu"));
+		assert!(!is_valid_hostname("example..ydns.eu"));
+		assert!(!is_valid_hostname("-example.ydns.eu"));
+		assert!(!is_valid_hostname("example-.ydns.eu"));
+		assert!(!is_valid_hostname("exa_mple.ydns.eu"));
+		assert!(!is_valid_hostname("http://example.ydns.eu"));
+		assert!(!is_valid_hostname(&"a".repeat(64)));
+	}
+
 	#[test]
 	fn apply_saves_mount_points() {
 		let ctx = test::ContextBuilder::new(test_name!()).build();
@@ -146,6 +681,9 @@ mod test {
 			mount_dirs: Some(vec![vfs::MountDir {
 				source: "/home/music".into(),
 				name: "🎵📁".into(),
+				album_art_pattern: Some("cover\\.png".into()),
+				reindex_every_n_seconds: Some(3600),
+				hide_from_root: false,
 			}]),
 			..Default::default()
 		};
@@ -155,6 +693,106 @@ mod test {
 		assert_eq!(actual_mount_dirs, new_config.mount_dirs.unwrap());
 	}
 
+	#[test]
+	fn apply_renames_duplicate_mount_points() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+
+		let new_config = Config {
+			settings: Some(settings::NewSettings {
+				auto_rename_duplicate_mounts: Some(true),
+				..Default::default()
+			}),
+			mount_dirs: Some(vec![
+				vfs::MountDir {
+					source: "/home/music".into(),
+					name: "music".into(),
+					album_art_pattern: None,
+					reindex_every_n_seconds: None,
+					hide_from_root: false,
+				},
+				vfs::MountDir {
+					source: "/mnt/music".into(),
+					name: "music".into(),
+					album_art_pattern: None,
+					reindex_every_n_seconds: None,
+					hide_from_root: false,
+				},
+			]),
+			..Default::default()
+		};
+
+		ctx.config_manager.apply(&new_config).unwrap();
+		let actual_mount_dirs: Vec<vfs::MountDir> = ctx.vfs_manager.mount_dirs().unwrap();
+		let names: Vec<&str> = actual_mount_dirs.iter().map(|m| m.name.as_str()).collect();
+		assert_eq!(names, vec!["music", "music-2"]);
+	}
+
+	#[test]
+	fn apply_rejects_duplicate_mount_points_by_default() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+
+		let new_config = Config {
+			mount_dirs: Some(vec![
+				vfs::MountDir {
+					source: "/home/music".into(),
+					name: "music".into(),
+					album_art_pattern: None,
+					reindex_every_n_seconds: None,
+					hide_from_root: false,
+				},
+				vfs::MountDir {
+					source: "/mnt/music".into(),
+					name: "music".into(),
+					album_art_pattern: None,
+					reindex_every_n_seconds: None,
+					hide_from_root: false,
+				},
+			]),
+			..Default::default()
+		};
+
+		assert!(ctx.config_manager.apply(&new_config).is_err());
+	}
+
+	#[test]
+	fn apply_partial_config_leaves_other_sections_intact() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user("Walter", "Tasty🍖", true)
+			.mount("root", "test-data/small-collection")
+			.build();
+
+		let new_config = Config {
+			settings: Some(settings::NewSettings {
+				reindex_every_n_seconds: Some(100),
+				..Default::default()
+			}),
+			..Default::default()
+		};
+		ctx.config_manager.apply(&new_config).unwrap();
+
+		let users = ctx.user_manager.list().unwrap();
+		assert_eq!(users.len(), 1);
+		assert_eq!(users[0].name, "Walter");
+		let mount_dirs = ctx.vfs_manager.mount_dirs().unwrap();
+		assert_eq!(mount_dirs.len(), 1);
+		assert_eq!(mount_dirs[0].name, "root");
+	}
+
+	#[test]
+	fn apply_empty_mount_dirs_clears_mount_points() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.mount("root", "test-data/small-collection")
+			.build();
+
+		let new_config = Config {
+			mount_dirs: Some(Vec::new()),
+			..Default::default()
+		};
+		ctx.config_manager.apply(&new_config).unwrap();
+
+		assert!(ctx.vfs_manager.mount_dirs().unwrap().is_empty());
+	}
+
 	#[test]
 	fn apply_saves_ddns_settings() {
 		let ctx = test::ContextBuilder::new(test_name!()).build();
@@ -192,4 +830,175 @@ mod test {
 		ctx.config_manager.apply(&new_config).unwrap();
 		assert!(!ctx.user_manager.list().unwrap()[0].is_admin());
 	}
+
+	#[test]
+	fn exported_config_round_trips() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user("Walter", "Tasty🍖", true)
+			.user("Jesse", "Yo🧪", false)
+			.mount("root", "test-data/small-collection")
+			.build();
+		ctx.ddns_manager
+			.set_config(&ddns::Config {
+				host: "🐸🐸🐸.ydns.eu".into(),
+				username: "kfr🐸g".into(),
+				password: "tasty🐞".into(),
+			})
+			.unwrap();
+
+		let exported = ctx.config_manager.export().unwrap();
+		let exported_users = exported.users.as_ref().unwrap();
+		assert_eq!(exported_users.len(), 2);
+		assert!(exported_users.iter().all(|u| u.password.is_empty()));
+		assert!(exported.ydns.as_ref().unwrap().password.is_empty());
+
+		ctx.config_manager
+			.apply(&Config {
+				mount_dirs: Some(Vec::new()),
+				..Default::default()
+			})
+			.unwrap();
+		ctx.config_manager.apply(&exported).unwrap();
+
+		let mount_dirs = ctx.vfs_manager.mount_dirs().unwrap();
+		assert_eq!(mount_dirs.len(), 1);
+		assert_eq!(mount_dirs[0].name, "root");
+		assert!(ctx.user_manager.login("Walter", "Tasty🍖").is_ok());
+		assert!(ctx.user_manager.login("Jesse", "Yo🧪").is_ok());
+		let users = ctx.user_manager.list().unwrap();
+		assert!(users.iter().any(|u| u.name == "Walter" && u.is_admin()));
+		assert!(users.iter().any(|u| u.name == "Jesse" && !u.is_admin()));
+		assert_eq!(ctx.ddns_manager.config().unwrap().password, "tasty🐞");
+	}
+
+	#[test]
+	fn apply_reindexes_only_when_indexing_config_changes() {
+		use std::path::Path;
+		use std::time::Duration;
+
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		ctx.settings_manager
+			.amend(&settings::NewSettings {
+				reindex_on_config_change: Some(true),
+				..Default::default()
+			})
+			.unwrap();
+		let generation = ctx.index.get_generation();
+
+		ctx.config_manager
+			.apply(&Config {
+				ydns: Some(ddns::Config {
+					host: "polaris.ydns.eu".into(),
+					username: "walter".into(),
+					password: "tasty".into(),
+				}),
+				..Default::default()
+			})
+			.unwrap();
+		std::thread::sleep(Duration::from_millis(500));
+		assert_eq!(ctx.index.get_generation(), generation);
+
+		ctx.config_manager
+			.apply(&Config {
+				mount_dirs: Some(vec![vfs::MountDir {
+					source: "test-data/small-collection".into(),
+					name: "root".into(),
+					album_art_pattern: None,
+					reindex_every_n_seconds: None,
+					hide_from_root: false,
+				}]),
+				..Default::default()
+			})
+			.unwrap();
+		let mut num_songs = 0;
+		for _ in 0..100 {
+			num_songs = ctx.index.flatten(Path::new("root"), false).unwrap().len();
+			if num_songs == 13 {
+				break;
+			}
+			std::thread::sleep(Duration::from_millis(100));
+		}
+		assert_eq!(num_songs, 13);
+	}
+
+	#[test]
+	fn apply_does_not_reindex_by_default() {
+		use std::time::Duration;
+
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		let generation = ctx.index.get_generation();
+
+		ctx.config_manager
+			.apply(&Config {
+				mount_dirs: Some(vec![vfs::MountDir {
+					source: "test-data/small-collection".into(),
+					name: "root".into(),
+					album_art_pattern: None,
+					reindex_every_n_seconds: None,
+					hide_from_root: false,
+				}]),
+				..Default::default()
+			})
+			.unwrap();
+		std::thread::sleep(Duration::from_millis(500));
+		assert_eq!(ctx.index.get_generation(), generation);
+	}
+
+	#[test]
+	fn apply_prunes_songs_of_removed_mounts() {
+		use crate::db::songs;
+		use diesel::prelude::*;
+		use std::path::Path;
+		use std::time::Duration;
+
+		let mount = |name: &str| vfs::MountDir {
+			source: format!("test-data/small-collection/{}", name),
+			name: name.into(),
+			album_art_pattern: None,
+			reindex_every_n_seconds: None,
+			hide_from_root: false,
+		};
+		let count_songs = |ctx: &test::Context| -> i64 {
+			let mut connection = ctx.db.connect().unwrap();
+			songs::table.count().get_result(&mut connection).unwrap()
+		};
+
+		let ctx = test::ContextBuilder::new(test_name!())
+			.mount("Khemmis", "test-data/small-collection/Khemmis")
+			.mount("Tobokegao", "test-data/small-collection/Tobokegao")
+			.build();
+		ctx.settings_manager
+			.amend(&settings::NewSettings {
+				reindex_on_config_change: Some(true),
+				..Default::default()
+			})
+			.unwrap();
+		ctx.index.update().unwrap();
+		assert_eq!(count_songs(&ctx), 13);
+
+		ctx.config_manager
+			.apply(&Config {
+				mount_dirs: Some(vec![mount("Khemmis")]),
+				..Default::default()
+			})
+			.unwrap();
+		assert_eq!(count_songs(&ctx), 5);
+		assert_eq!(ctx.index.browse(Path::new(""), false).unwrap().len(), 1);
+
+		ctx.config_manager
+			.apply(&Config {
+				mount_dirs: Some(vec![mount("Khemmis"), mount("Tobokegao")]),
+				..Default::default()
+			})
+			.unwrap();
+		let mut num_songs = 0;
+		for _ in 0..100 {
+			num_songs = count_songs(&ctx);
+			if num_songs == 13 {
+				break;
+			}
+			std::thread::sleep(Duration::from_millis(100));
+		}
+		assert_eq!(num_songs, 13);
+	}
 }
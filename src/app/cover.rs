@@ -0,0 +1,292 @@
+use diesel::prelude::*;
+use image::ImageFormat;
+use log::warn;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::app::{settings, vfs};
+use crate::db::{self, cover_overrides, DB};
+
+const FILE_NAME_LENGTH: usize = 32;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("No cover override was found for `{0}`")]
+	CoverNotFound(PathBuf),
+	#[error("Cover image is too large ({0} bytes, at most {1} are allowed)")]
+	CoverTooLarge(usize, usize),
+	#[error(transparent)]
+	Database(#[from] diesel::result::Error),
+	#[error(transparent)]
+	DatabaseConnection(#[from] db::Error),
+	#[error("Could not decode cover image:\n\n{0}")]
+	Image(image::error::ImageError),
+	#[error("Filesystem error for `{0}`: `{1}`")]
+	Io(PathBuf, std::io::Error),
+	#[error("Path was not found: `{0}`")]
+	PathNotFound(PathBuf),
+	#[error(transparent)]
+	Settings(#[from] settings::Error),
+	#[error("Cover images must be JPEG, PNG, GIF or BMP files")]
+	UnsupportedFormat,
+	#[error(transparent)]
+	Vfs(#[from] vfs::Error),
+}
+
+/// Images uploaded to replace the artwork of albums, or of any other location within the
+/// collection. Overrides are stored apart from the index, so that they survive rescans.
+#[derive(Clone)]
+pub struct Manager {
+	db: DB,
+	vfs_manager: vfs::Manager,
+	settings_manager: settings::Manager,
+	covers_dir_path: PathBuf,
+}
+
+impl Manager {
+	pub fn new(
+		db: DB,
+		vfs_manager: vfs::Manager,
+		settings_manager: settings::Manager,
+		covers_dir_path: PathBuf,
+	) -> Self {
+		Self {
+			db,
+			vfs_manager,
+			settings_manager,
+			covers_dir_path,
+		}
+	}
+
+	/// Validates an uploaded image and makes it the cover of a virtual path, replacing any
+	/// previous override.
+	pub fn set(&self, virtual_path: &Path, content: &[u8]) -> Result<(), Error> {
+		let real_path = self.resolve(virtual_path)?;
+
+		let max_bytes = self.settings_manager.read()?.max_cover_bytes.max(0) as usize;
+		if content.len() > max_bytes {
+			return Err(Error::CoverTooLarge(content.len(), max_bytes));
+		}
+		let format = image::guess_format(content).map_err(|_| Error::UnsupportedFormat)?;
+		let extension = match format {
+			ImageFormat::Bmp => "bmp",
+			ImageFormat::Gif => "gif",
+			ImageFormat::Jpeg => "jpg",
+			ImageFormat::Png => "png",
+			_ => return Err(Error::UnsupportedFormat),
+		};
+		image::load_from_memory_with_format(content, format).map_err(Error::Image)?;
+
+		fs::create_dir_all(&self.covers_dir_path)
+			.map_err(|e| Error::Io(self.covers_dir_path.clone(), e))?;
+		let file_name = format!("{}.{}", make_file_name(), extension);
+		let file_path = self.covers_dir_path.join(&file_name);
+		fs::write(&file_path, content).map_err(|e| Error::Io(file_path.clone(), e))?;
+
+		let path = real_path.to_string_lossy().into_owned();
+		let mut connection = self.db.connect()?;
+		let previous_file_name = connection.transaction::<_, Error, _>(|connection| {
+			let previous_file_name = cover_overrides::table
+				.select(cover_overrides::file_name)
+				.filter(cover_overrides::path.eq(&path))
+				.get_result::<String>(connection)
+				.optional()?;
+			diesel::delete(cover_overrides::table.filter(cover_overrides::path.eq(&path)))
+				.execute(connection)?;
+			diesel::insert_into(cover_overrides::table)
+				.values((
+					cover_overrides::path.eq(&path),
+					cover_overrides::file_name.eq(&file_name),
+				))
+				.execute(connection)?;
+			Ok(previous_file_name)
+		})?;
+
+		if let Some(previous_file_name) = previous_file_name {
+			self.remove_file(&previous_file_name);
+		}
+		Ok(())
+	}
+
+	/// Removes the cover override of a virtual path, restoring its original artwork.
+	pub fn clear(&self, virtual_path: &Path) -> Result<(), Error> {
+		let real_path = self.resolve(virtual_path)?;
+		let path = real_path.to_string_lossy().into_owned();
+		let mut connection = self.db.connect()?;
+		let file_name = connection.transaction::<_, Error, _>(|connection| {
+			let file_name = cover_overrides::table
+				.select(cover_overrides::file_name)
+				.filter(cover_overrides::path.eq(&path))
+				.get_result::<String>(connection)
+				.optional()?
+				.ok_or_else(|| Error::CoverNotFound(virtual_path.to_owned()))?;
+			diesel::delete(cover_overrides::table.filter(cover_overrides::path.eq(&path)))
+				.execute(connection)?;
+			Ok(file_name)
+		})?;
+		self.remove_file(&file_name);
+		Ok(())
+	}
+
+	/// Finds the cover override applying to a file or directory of the collection, given its
+	/// real path. Files fall back to the cover of the directory containing them, so that a cover
+	/// set on an album also replaces the artwork embedded in its songs.
+	pub fn find(&self, real_path: &Path) -> Result<Option<PathBuf>, Error> {
+		let mut candidates = vec![real_path.to_string_lossy().into_owned()];
+		if !real_path.is_dir() {
+			if let Some(parent) = real_path.parent() {
+				candidates.push(parent.to_string_lossy().into_owned());
+			}
+		}
+
+		let mut connection = self.db.connect()?;
+		let overrides: Vec<(String, String)> = cover_overrides::table
+			.select((cover_overrides::path, cover_overrides::file_name))
+			.filter(cover_overrides::path.eq_any(&candidates))
+			.load(&mut connection)?;
+		let file_name = candidates
+			.iter()
+			.find_map(|c| overrides.iter().find(|(p, _)| p == c))
+			.map(|(_, f)| self.covers_dir_path.join(f));
+		Ok(file_name)
+	}
+
+	fn resolve(&self, virtual_path: &Path) -> Result<PathBuf, Error> {
+		if !virtual_path
+			.components()
+			.all(|c| matches!(c, Component::Normal(_)))
+		{
+			return Err(Error::PathNotFound(virtual_path.to_owned()));
+		}
+		let real_path = self.vfs_manager.get_vfs()?.virtual_to_real(virtual_path)?;
+		if !real_path.exists() {
+			return Err(Error::PathNotFound(virtual_path.to_owned()));
+		}
+		Ok(real_path)
+	}
+
+	fn remove_file(&self, file_name: &str) {
+		let file_path = self.covers_dir_path.join(file_name);
+		if let Err(e) = fs::remove_file(&file_path) {
+			warn!("Could not remove cover `{}`: {}", file_path.display(), e);
+		}
+	}
+}
+
+fn make_file_name() -> String {
+	rand::thread_rng()
+		.sample_iter(&Alphanumeric)
+		.take(FILE_NAME_LENGTH)
+		.map(char::from)
+		.collect()
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_MOUNT_NAME: &str = "root";
+
+	fn make_context(test_name: String) -> test::Context {
+		test::ContextBuilder::new(test_name)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build()
+	}
+
+	fn hunted_path() -> PathBuf {
+		[TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect()
+	}
+
+	fn cover_content() -> Vec<u8> {
+		fs::read("test-data/small-collection/Khemmis/Hunted/Folder.jpg").unwrap()
+	}
+
+	#[test]
+	fn cover_applies_to_directory_and_its_songs() {
+		let ctx = make_context(test_name!());
+		let real_directory = ctx
+			.vfs_manager
+			.get_vfs()
+			.unwrap()
+			.virtual_to_real(hunted_path())
+			.unwrap();
+		let real_song = real_directory.join("02 - Candlelight.mp3");
+		assert_eq!(ctx.cover_manager.find(&real_directory).unwrap(), None);
+
+		ctx.cover_manager
+			.set(&hunted_path(), &cover_content())
+			.unwrap();
+		let cover = ctx.cover_manager.find(&real_directory).unwrap().unwrap();
+		assert_eq!(fs::read(&cover).unwrap(), cover_content());
+		assert_eq!(ctx.cover_manager.find(&real_song).unwrap(), Some(cover));
+
+		ctx.cover_manager.clear(&hunted_path()).unwrap();
+		assert_eq!(ctx.cover_manager.find(&real_directory).unwrap(), None);
+		assert!(matches!(
+			ctx.cover_manager.clear(&hunted_path()),
+			Err(Error::CoverNotFound(_))
+		));
+	}
+
+	#[test]
+	fn replacing_cover_removes_previous_file() {
+		let ctx = make_context(test_name!());
+		let real_directory = ctx
+			.vfs_manager
+			.get_vfs()
+			.unwrap()
+			.virtual_to_real(hunted_path())
+			.unwrap();
+
+		ctx.cover_manager
+			.set(&hunted_path(), &cover_content())
+			.unwrap();
+		let first = ctx.cover_manager.find(&real_directory).unwrap().unwrap();
+		ctx.cover_manager
+			.set(&hunted_path(), &cover_content())
+			.unwrap();
+		let second = ctx.cover_manager.find(&real_directory).unwrap().unwrap();
+
+		assert_ne!(first, second);
+		assert!(!first.exists());
+		assert!(second.exists());
+	}
+
+	#[test]
+	fn invalid_uploads_are_rejected() {
+		let ctx = make_context(test_name!());
+
+		assert!(matches!(
+			ctx.cover_manager.set(&hunted_path(), b"not an image"),
+			Err(Error::UnsupportedFormat)
+		));
+
+		let mut truncated = cover_content();
+		truncated.truncate(64);
+		assert!(matches!(
+			ctx.cover_manager.set(&hunted_path(), &truncated),
+			Err(Error::Image(_))
+		));
+
+		ctx.settings_manager
+			.amend(&settings::NewSettings {
+				max_cover_bytes: Some(16),
+				..Default::default()
+			})
+			.unwrap();
+		assert!(matches!(
+			ctx.cover_manager.set(&hunted_path(), &cover_content()),
+			Err(Error::CoverTooLarge(_, 16))
+		));
+
+		let missing_path: PathBuf = [TEST_MOUNT_NAME, "Missing"].iter().collect();
+		assert!(matches!(
+			ctx.cover_manager.set(&missing_path, &cover_content()),
+			Err(Error::PathNotFound(_))
+		));
+	}
+}
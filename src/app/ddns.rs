@@ -2,6 +2,7 @@ use base64::prelude::*;
 use diesel::prelude::*;
 use log::{debug, error};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::thread;
 use std::time;
 
@@ -21,7 +22,7 @@ pub enum Error {
 	Database(#[from] diesel::result::Error),
 }
 
-#[derive(Clone, Debug, Deserialize, Insertable, PartialEq, Eq, Queryable, Serialize)]
+#[derive(Clone, Deserialize, Insertable, PartialEq, Eq, Queryable, Serialize)]
 #[diesel(table_name = ddns_config)]
 pub struct Config {
 	pub host: String,
@@ -29,39 +30,94 @@ pub struct Config {
 	pub password: String,
 }
 
+impl fmt::Debug for Config {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Config")
+			.field("host", &self.host)
+			.field("username", &self.username)
+			.field("password", &"<redacted>")
+			.finish()
+	}
+}
+
+/// Outcome of an update query sent to the DDNS provider.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestReport {
+	pub success: bool,
+	/// HTTP status code returned by the provider, if it could be reached.
+	pub status: Option<u16>,
+	/// Response body from the provider, or a description of the transport error.
+	pub response: String,
+}
+
 #[derive(Clone)]
 pub struct Manager {
 	db: DB,
+	update_url: String,
 }
 
 impl Manager {
 	pub fn new(db: DB) -> Self {
-		Self { db }
+		Self {
+			db,
+			update_url: DDNS_UPDATE_URL.to_owned(),
+		}
 	}
 
-	fn update_my_ip(&self) -> Result<(), Error> {
-		let config = self.config()?;
-		if config.host.is_empty() || config.username.is_empty() {
-			debug!("Skipping DDNS update because credentials are missing");
-			return Ok(());
-		}
+	/// Sends update queries to a different provider endpoint.
+	#[cfg(test)]
+	pub fn with_update_url(mut self, update_url: &str) -> Self {
+		self.update_url = update_url.to_owned();
+		self
+	}
 
-		let full_url = format!("{}?host={}", DDNS_UPDATE_URL, &config.host);
+	#[allow(clippy::result_large_err)]
+	fn send_update(&self, config: &Config) -> Result<ureq::Response, ureq::Error> {
+		let full_url = format!("{}?host={}", self.update_url, &config.host);
 		let credentials = format!("{}:{}", &config.username, &config.password);
-		let response = ureq::get(full_url.as_str())
+		ureq::get(full_url.as_str())
 			.set(
 				"Authorization",
 				&format!("Basic {}", BASE64_STANDARD_NO_PAD.encode(credentials)),
 			)
-			.call();
+			.call()
+	}
 
-		match response {
+	fn update_my_ip(&self) -> Result<(), Error> {
+		let config = self.config()?;
+		if config.host.is_empty() || config.username.is_empty() {
+			debug!("Skipping DDNS update because credentials are missing");
+			return Ok(());
+		}
+
+		match self.send_update(&config) {
 			Ok(_) => Ok(()),
 			Err(ureq::Error::Status(code, _)) => Err(Error::UpdateQueryFailed(code)),
 			Err(ureq::Error::Transport(_)) => Err(Error::UpdateQueryTransport),
 		}
 	}
 
+	/// Sends an update query using the given configuration, without saving it.
+	pub fn test_config(&self, config: &Config) -> TestReport {
+		match self.send_update(config) {
+			Ok(response) => TestReport {
+				success: true,
+				status: Some(response.status()),
+				response: response.into_string().unwrap_or_default(),
+			},
+			Err(ureq::Error::Status(code, response)) => TestReport {
+				success: false,
+				status: Some(code),
+				response: response.into_string().unwrap_or_default(),
+			},
+			Err(ureq::Error::Transport(transport)) => TestReport {
+				success: false,
+				status: None,
+				response: transport.to_string(),
+			},
+		}
+	}
+
 	pub fn config(&self) -> Result<Config, Error> {
 		use crate::db::ddns_config::dsl::*;
 		let mut connection = self.db.connect()?;
@@ -99,3 +155,106 @@ impl Manager {
 		}
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use std::io::{BufRead, BufReader, Write};
+	use std::net::TcpListener;
+
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	/// Answers a single update query, accepting only the given credentials.
+	fn spawn_provider(username: &str, password: &str) -> String {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let address = listener.local_addr().unwrap();
+		let expected_authorization = format!(
+			"authorization: basic {}",
+			BASE64_STANDARD_NO_PAD.encode(format!("{}:{}", username, password))
+		)
+		.to_lowercase();
+		thread::spawn(move || {
+			let (mut stream, _) = listener.accept().unwrap();
+			let mut authorized = false;
+			let mut reader = BufReader::new(stream.try_clone().unwrap());
+			loop {
+				let mut line = String::new();
+				if reader.read_line(&mut line).unwrap() == 0 || line.trim().is_empty() {
+					break;
+				}
+				authorized |= line.trim().to_lowercase() == expected_authorization;
+			}
+			let (status, body) = if authorized {
+				("200 OK", "ok")
+			} else {
+				("401 Unauthorized", "invalid credentials")
+			};
+			let response = format!(
+				"HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+				status,
+				body.len(),
+				body
+			);
+			stream.write_all(response.as_bytes()).unwrap();
+		});
+		format!("http://{}/update", address)
+	}
+
+	#[test]
+	fn test_config_reports_success() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		let url = spawn_provider("kfrog", "tasty");
+		let ddns_manager = ctx.ddns_manager.clone().with_update_url(&url);
+
+		let report = ddns_manager.test_config(&Config {
+			host: "frog.ydns.eu".to_owned(),
+			username: "kfrog".to_owned(),
+			password: "tasty".to_owned(),
+		});
+		assert!(report.success);
+		assert_eq!(report.status, Some(200));
+		assert_eq!(report.response, "ok");
+	}
+
+	#[test]
+	fn test_config_reports_failure() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		let url = spawn_provider("kfrog", "tasty");
+		let ddns_manager = ctx.ddns_manager.clone().with_update_url(&url);
+
+		let report = ddns_manager.test_config(&Config {
+			host: "frog.ydns.eu".to_owned(),
+			username: "kfrog".to_owned(),
+			password: "wrong".to_owned(),
+		});
+		assert!(!report.success);
+		assert_eq!(report.status, Some(401));
+		assert_eq!(report.response, "invalid credentials");
+	}
+
+	#[test]
+	fn test_config_does_not_save_config() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		let url = spawn_provider("kfrog", "tasty");
+		let ddns_manager = ctx.ddns_manager.clone().with_update_url(&url);
+		let initial_config = ddns_manager.config().unwrap();
+
+		ddns_manager.test_config(&Config {
+			host: "frog.ydns.eu".to_owned(),
+			username: "kfrog".to_owned(),
+			password: "tasty".to_owned(),
+		});
+		assert_eq!(ddns_manager.config().unwrap(), initial_config);
+	}
+
+	#[test]
+	fn debug_output_hides_password() {
+		let config = Config {
+			host: "frog.ydns.eu".to_owned(),
+			username: "kfrog".to_owned(),
+			password: "tasty".to_owned(),
+		};
+		assert!(!format!("{:?}", config).contains("tasty"));
+	}
+}
@@ -1,41 +1,96 @@
-use log::error;
+use log::{error, info};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::app::{settings, vfs};
+use crate::app::{settings, thumbnail, vfs};
 use crate::db::DB;
 
+mod cache;
+mod loudness;
 mod metadata;
 mod query;
+mod schedule;
 #[cfg(test)]
 mod test;
 mod types;
 mod update;
 
+pub use self::metadata::{lint, read_raw, LintWarning, RawTags, SongTags};
 pub use self::query::*;
 pub use self::types::*;
 pub use self::update::*;
 
+/// Directories (and index entries) which can be waiting between each stage of an index update.
+/// Scanning stages block when the next stage falls behind, which keeps memory usage bounded.
+const DEFAULT_SCAN_CHANNEL_CAPACITY: usize = 256;
+
+/// Aggregates over the whole index (artists, years, formats) are cached until the index changes,
+/// unless this environment variable is set to `0` or `false`.
+const AGGREGATE_CACHE_ENV_VAR: &str = "POLARIS_CACHE_AGGREGATES";
+
+/// Longest time between two checks of the automatic scan schedule, so that changes to scan
+/// intervals are picked up without waiting for the previous interval to elapse.
+const MAX_SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Clone)]
 pub struct Index {
 	db: DB,
 	vfs_manager: vfs::Manager,
 	settings_manager: settings::Manager,
+	thumbnail_manager: thumbnail::Manager,
 	pending_reindex: Arc<(Mutex<bool>, Condvar)>,
+	pending_rebuild: Arc<AtomicBool>,
+	pending_update: Arc<AtomicBool>,
+	pending_mount_updates: Arc<Mutex<HashSet<String>>>,
+	update_control: UpdateControl,
+	scan_events: ScanEvents,
+	scan_errors: Arc<Mutex<Vec<ScanError>>>,
+	failed_files: FailedFiles,
+	remote_metadata: RemoteMetadata,
+	scan_channel_capacity: Arc<AtomicUsize>,
+	aggregate_cache: cache::AggregateCache,
 }
 
 impl Index {
-	pub fn new(db: DB, vfs_manager: vfs::Manager, settings_manager: settings::Manager) -> Self {
+	pub fn new(
+		db: DB,
+		vfs_manager: vfs::Manager,
+		settings_manager: settings::Manager,
+		thumbnail_manager: thumbnail::Manager,
+	) -> Self {
+		let key = "POLARIS_SCAN_CHANNEL_CAPACITY";
+		let scan_channel_capacity = std::env::var_os(key)
+			.map(|v| v.to_string_lossy().to_string())
+			.and_then(|v| usize::from_str(&v).ok())
+			.unwrap_or(DEFAULT_SCAN_CHANNEL_CAPACITY);
+		let cache_aggregates = std::env::var_os(AGGREGATE_CACHE_ENV_VAR)
+			.map(|v| v.to_string_lossy().to_string())
+			.is_none_or(|v| v != "0" && !v.eq_ignore_ascii_case("false"));
+
 		let index = Self {
 			db,
 			vfs_manager,
 			settings_manager,
+			thumbnail_manager,
 
 			pending_reindex: Arc::new((
 				#[allow(clippy::mutex_atomic)]
 				Mutex::new(false),
 				Condvar::new(),
 			)),
+			pending_rebuild: Arc::new(AtomicBool::new(false)),
+			pending_update: Arc::new(AtomicBool::new(false)),
+			pending_mount_updates: Arc::new(Mutex::new(HashSet::new())),
+			update_control: UpdateControl::new(),
+			scan_events: ScanEvents::default(),
+			scan_errors: Arc::new(Mutex::new(Vec::new())),
+			failed_files: FailedFiles::default(),
+			remote_metadata: RemoteMetadata::default(),
+			scan_channel_capacity: Arc::new(AtomicUsize::new(scan_channel_capacity.max(1))),
+			aggregate_cache: cache::AggregateCache::new(cache_aggregates),
 		};
 
 		let commands_index = index.clone();
@@ -47,12 +102,49 @@ impl Index {
 	}
 
 	pub fn trigger_reindex(&self) {
+		self.pending_update.store(true, Ordering::SeqCst);
+		self.notify_pending_reindex();
+	}
+
+	/// Schedules an index update covering only the given mounts.
+	pub fn trigger_mount_reindex<I: IntoIterator<Item = String>>(&self, mount_names: I) {
+		self.pending_mount_updates
+			.lock()
+			.unwrap()
+			.extend(mount_names);
+		self.notify_pending_reindex();
+	}
+
+	fn notify_pending_reindex(&self) {
 		let (lock, cvar) = &*self.pending_reindex;
 		let mut pending_reindex = lock.lock().unwrap();
 		*pending_reindex = true;
 		cvar.notify_one();
 	}
 
+	/// Schedules an index update which discards all existing index entries.
+	pub fn trigger_rebuild(&self) {
+		self.pending_rebuild.store(true, Ordering::SeqCst);
+		self.trigger_reindex();
+	}
+
+	/// Sets how many items can be waiting between each stage of subsequent index updates.
+	#[cfg(test)]
+	pub fn set_scan_channel_capacity(&self, capacity: usize) {
+		self.scan_channel_capacity
+			.store(capacity.max(1), Ordering::SeqCst);
+	}
+
+	pub fn update_control(&self) -> &UpdateControl {
+		&self.update_control
+	}
+
+	/// Identifies the current content of the index. It changes every time an update or refresh
+	/// completes, so clients can tell when data they cached is outdated.
+	pub fn get_generation(&self) -> u64 {
+		self.aggregate_cache.generation()
+	}
+
 	pub fn begin_periodic_updates(&self) {
 		let auto_index = self.clone();
 		std::thread::spawn(move || {
@@ -70,23 +162,81 @@ impl Index {
 				}
 				*pending = false;
 			}
-			if let Err(e) = self.update() {
+			let mount_names: Vec<String> =
+				self.pending_mount_updates.lock().unwrap().drain().collect();
+			let result = if self.pending_rebuild.swap(false, Ordering::SeqCst) {
+				self.pending_update.store(false, Ordering::SeqCst);
+				self.rebuild()
+			} else if self.pending_update.swap(false, Ordering::SeqCst) {
+				self.update()
+			} else if !mount_names.is_empty() {
+				self.update_mounts(&mount_names)
+			} else {
+				continue;
+			};
+			if let Err(e) = result {
 				error!("Error while updating index: {}", e);
 			}
 		}
 	}
 
 	fn automatic_reindex(&self) {
+		let scan_on_startup = self
+			.settings_manager
+			.read()
+			.map(|s| s.scan_on_startup)
+			.unwrap_or_else(|e| {
+				error!("Could not retrieve scan on startup setting: {}", e);
+				true
+			});
+		if !scan_on_startup {
+			info!("Skipping index update on startup");
+		}
+
+		let mut schedule = schedule::ScanSchedule::new(Instant::now(), scan_on_startup);
 		loop {
-			self.trigger_reindex();
-			let sleep_duration = self
-				.settings_manager
-				.get_index_sleep_duration()
-				.unwrap_or_else(|e| {
-					error!("Could not retrieve index sleep duration: {}", e);
-					Duration::from_secs(1800)
-				});
-			std::thread::sleep(sleep_duration);
+			let (default_interval, intervals) = self.get_scan_intervals();
+			let due = schedule.take_due(Instant::now(), &intervals);
+			if !due.is_empty() {
+				if due.len() == intervals.len() {
+					self.trigger_reindex();
+				} else {
+					self.trigger_mount_reindex(due);
+				}
+			}
+			let sleep_duration = match schedule.next_scan() {
+				Some(next_scan) => next_scan.saturating_duration_since(Instant::now()),
+				None => default_interval,
+			};
+			std::thread::sleep(sleep_duration.min(MAX_SCHEDULE_CHECK_INTERVAL));
 		}
 	}
+
+	/// Returns the global interval between automatic scans, and the interval for each mount.
+	fn get_scan_intervals(&self) -> (Duration, HashMap<String, Duration>) {
+		let default_interval = self
+			.settings_manager
+			.get_index_sleep_duration()
+			.unwrap_or_else(|e| {
+				error!("Could not retrieve index sleep duration: {}", e);
+				Duration::from_secs(1800)
+			});
+		let mounts = match self.vfs_manager.get_vfs() {
+			Ok(vfs) => vfs.mounts().clone(),
+			Err(e) => {
+				error!("Could not retrieve mounts to schedule scans: {}", e);
+				Vec::new()
+			}
+		};
+		let intervals = mounts
+			.into_iter()
+			.map(|mount| {
+				let interval = mount
+					.reindex_every_n_seconds
+					.map_or(default_interval, |s| Duration::from_secs(s.max(0) as u64));
+				(mount.name, interval)
+			})
+			.collect();
+		(default_interval, intervals)
+	}
 }
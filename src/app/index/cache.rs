@@ -0,0 +1,88 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::{Artist, ArtistNameNormalizer, FormatCount, Label, YearCount};
+use crate::app::settings::AlbumArtistGrouping;
+
+/// Settings the artist list was computed with.
+type ArtistsKey = (AlbumArtistGrouping, Option<ArtistNameNormalizer>);
+
+/// Aggregates computed for a given index generation, along with the key they were computed for.
+#[derive(Default)]
+pub(super) struct Entries {
+	generation: u64,
+	pub artists: Option<(ArtistsKey, Vec<Artist>)>,
+	pub years: Option<((), Vec<YearCount>)>,
+	pub formats: Option<((), Vec<FormatCount>)>,
+	/// Labels with real artwork paths, which are virtualized after reading them from the cache.
+	pub labels: Option<((), Vec<Label>)>,
+}
+
+/// Keeps results of queries aggregating the whole index until the index content changes.
+#[derive(Clone)]
+pub(super) struct AggregateCache {
+	enabled: bool,
+	generation: Arc<AtomicU64>,
+	entries: Arc<Mutex<Entries>>,
+}
+
+impl AggregateCache {
+	pub fn new(enabled: bool) -> Self {
+		Self {
+			enabled,
+			generation: Arc::new(AtomicU64::new(0)),
+			entries: Arc::new(Mutex::new(Entries::default())),
+		}
+	}
+
+	/// Discards all cached aggregates. Called whenever index content changes.
+	pub fn invalidate(&self) {
+		self.generation.fetch_add(1, Ordering::SeqCst);
+	}
+
+	/// Number of times index content changed since the server started.
+	pub fn generation(&self) -> u64 {
+		self.generation.load(Ordering::SeqCst)
+	}
+
+	/// Returns the cached value of an aggregate, or computes it when it is missing or was computed
+	/// for a different key. Values computed while the index changed are not kept.
+	pub fn get_or_compute<K, T, E>(
+		&self,
+		slot: fn(&mut Entries) -> &mut Option<(K, T)>,
+		key: K,
+		compute: impl FnOnce() -> Result<T, E>,
+	) -> Result<T, E>
+	where
+		K: PartialEq,
+		T: Clone,
+	{
+		if !self.enabled {
+			return compute();
+		}
+
+		let generation = self.generation.load(Ordering::SeqCst);
+		{
+			let mut entries = self.entries.lock().unwrap();
+			if entries.generation != generation {
+				*entries = Entries {
+					generation,
+					..Default::default()
+				};
+			}
+			if let Some((cached_key, value)) = slot(&mut entries) {
+				if *cached_key == key {
+					return Ok(value.clone());
+				}
+			}
+		}
+
+		let value = compute()?;
+		let mut entries = self.entries.lock().unwrap();
+		if entries.generation == generation && self.generation.load(Ordering::SeqCst) == generation
+		{
+			*slot(&mut entries) = Some((key, value.clone()));
+		}
+		Ok(value)
+	}
+}
@@ -0,0 +1,352 @@
+use lewton::inside_ogg::OggStreamReader;
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::utils;
+use crate::utils::AudioFormat;
+
+/// Loudness of blocks quieter than this are left out of measurements, in LUFS.
+const ABSOLUTE_GATE: f64 = -70.0;
+
+/// Blocks quieter than the ungated loudness by more than this are left out of measurements, in LU.
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Mean square energies of the overlapping 400ms blocks of a song, as defined by ITU-R BS.1770.
+/// Blocks of several songs combine into the loudness of an album.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Blocks(Vec<f64>);
+
+impl Blocks {
+	pub fn extend(&mut self, other: &Blocks) {
+		self.0.extend_from_slice(&other.0);
+	}
+
+	/// Computes the gated integrated loudness of these blocks, in LUFS.
+	/// Returns `None` for silence.
+	pub fn integrated_loudness(&self) -> Option<f64> {
+		let audible: Vec<f64> = self
+			.0
+			.iter()
+			.copied()
+			.filter(|z| block_loudness(*z) > ABSOLUTE_GATE)
+			.collect();
+		if audible.is_empty() {
+			return None;
+		}
+		let relative_gate = block_loudness(mean(&audible)) + RELATIVE_GATE;
+		let gated: Vec<f64> = audible
+			.into_iter()
+			.filter(|z| block_loudness(*z) > relative_gate)
+			.collect();
+		if gated.is_empty() {
+			return None;
+		}
+		Some(block_loudness(mean(&gated)))
+	}
+}
+
+fn mean(values: &[f64]) -> f64 {
+	values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn block_loudness(mean_square: f64) -> f64 {
+	-0.691 + 10.0 * mean_square.log10()
+}
+
+/// Decodes a song and measures its loudness. Only formats which can be decoded without
+/// external tools are supported: uncompressed WAVE files and Ogg Vorbis.
+pub fn measure(path: &Path) -> Option<Blocks> {
+	match utils::get_audio_format(path)? {
+		AudioFormat::OGG => measure_vorbis(path),
+		AudioFormat::WAVE => measure_wave(path),
+		_ => None,
+	}
+}
+
+fn measure_vorbis(path: &Path) -> Option<Blocks> {
+	let file = fs::File::open(path).ok()?;
+	let mut reader = OggStreamReader::new(BufReader::new(file)).ok()?;
+	let mut meter = Meter::new(
+		reader.ident_hdr.audio_channels as usize,
+		reader.ident_hdr.audio_sample_rate,
+	)?;
+	while let Some(samples) = reader.read_dec_packet_itl().ok()? {
+		meter.add_samples(samples.iter().map(|s| *s as f64 / 32768.0));
+	}
+	Some(meter.finish())
+}
+
+fn measure_wave(path: &Path) -> Option<Blocks> {
+	let mut file = BufReader::new(fs::File::open(path).ok()?);
+	let mut riff_header = [0u8; 12];
+	file.read_exact(&mut riff_header).ok()?;
+	if &riff_header[0..4] != b"RIFF" || &riff_header[8..12] != b"WAVE" {
+		return None;
+	}
+	let mut format = None;
+	loop {
+		let mut chunk_header = [0u8; 8];
+		file.read_exact(&mut chunk_header).ok()?;
+		let chunk_size = u32::from_le_bytes(chunk_header[4..8].try_into().ok()?);
+		match &chunk_header[0..4] {
+			b"fmt " => {
+				let mut chunk = vec![0u8; chunk_size as usize];
+				file.read_exact(&mut chunk).ok()?;
+				format = Some(WaveFormat::parse(&chunk)?);
+			}
+			b"data" => {
+				let format = format?;
+				let mut meter = Meter::new(format.channels, format.sample_rate)?;
+				let mut data = Vec::new();
+				file.take(chunk_size as u64).read_to_end(&mut data).ok()?;
+				meter.add_samples(
+					data.chunks_exact(format.bytes_per_sample())
+						.filter_map(|sample| format.decode(sample)),
+				);
+				return Some(meter.finish());
+			}
+			_ => {
+				file.seek(SeekFrom::Current(chunk_size as i64)).ok()?;
+			}
+		}
+		// Chunks are padded to an even size
+		if chunk_size % 2 == 1 {
+			file.seek(SeekFrom::Current(1)).ok()?;
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+struct WaveFormat {
+	float: bool,
+	channels: usize,
+	sample_rate: u32,
+	bits_per_sample: u16,
+}
+
+impl WaveFormat {
+	const PCM: u16 = 1;
+	const IEEE_FLOAT: u16 = 3;
+	const EXTENSIBLE: u16 = 0xFFFE;
+
+	fn parse(chunk: &[u8]) -> Option<Self> {
+		let u16_at = |i: usize| Some(u16::from_le_bytes(chunk.get(i..i + 2)?.try_into().ok()?));
+		let u32_at = |i: usize| Some(u32::from_le_bytes(chunk.get(i..i + 4)?.try_into().ok()?));
+		let format_tag = match u16_at(0)? {
+			// The actual format is the first field of the sub-format GUID
+			Self::EXTENSIBLE => u16_at(24)?,
+			tag => tag,
+		};
+		let bits_per_sample = u16_at(14)?;
+		let float = match (format_tag, bits_per_sample) {
+			(Self::PCM, 8 | 16 | 24 | 32) => false,
+			(Self::IEEE_FLOAT, 32) => true,
+			_ => return None,
+		};
+		Some(Self {
+			float,
+			channels: u16_at(2)? as usize,
+			sample_rate: u32_at(4)?,
+			bits_per_sample,
+		})
+	}
+
+	fn bytes_per_sample(&self) -> usize {
+		self.bits_per_sample as usize / 8
+	}
+
+	fn decode(&self, bytes: &[u8]) -> Option<f64> {
+		Some(match (self.float, bytes.len()) {
+			(true, 4) => f32::from_le_bytes(bytes.try_into().ok()?) as f64,
+			(false, 1) => (bytes[0] as f64 - 128.0) / 128.0,
+			(false, 2) => i16::from_le_bytes(bytes.try_into().ok()?) as f64 / 32768.0,
+			(false, 3) => {
+				let sample = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+				sample as f64 / 8388608.0
+			}
+			(false, 4) => i32::from_le_bytes(bytes.try_into().ok()?) as f64 / 2147483648.0,
+			_ => return None,
+		})
+	}
+}
+
+/// Second order IIR filter, in transposed direct form II.
+#[derive(Clone, Copy, Debug)]
+struct Biquad {
+	b: [f64; 3],
+	a: [f64; 3],
+	z1: f64,
+	z2: f64,
+}
+
+impl Biquad {
+	fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+		Self {
+			b,
+			a,
+			z1: 0.0,
+			z2: 0.0,
+		}
+	}
+
+	fn process(&mut self, x: f64) -> f64 {
+		let y = self.b[0] * x + self.z1;
+		self.z1 = self.b[1] * x - self.a[1] * y + self.z2;
+		self.z2 = self.b[2] * x - self.a[2] * y;
+		y
+	}
+}
+
+/// Filters approximating how loud each frequency sounds, as specified by ITU-R BS.1770:
+/// a high shelf modelling the head, followed by a high-pass filter.
+/// Coefficients are derived for the sample rate of the song, rather than tabulated for 48kHz.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+	let rate = sample_rate as f64;
+
+	let f0 = 1681.97445095553;
+	let gain = 3.99984385397335;
+	let q = 0.70717523695542;
+	let k = (PI * f0 / rate).tan();
+	let vh = 10f64.powf(gain / 20.0);
+	let vb = vh.powf(0.499666774154542);
+	let a0 = 1.0 + k / q + k * k;
+	let shelf = Biquad::new(
+		[
+			(vh + vb * k / q + k * k) / a0,
+			2.0 * (k * k - vh) / a0,
+			(vh - vb * k / q + k * k) / a0,
+		],
+		[1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+	);
+
+	let f0 = 38.1354708760244;
+	let q = 0.500327037323877;
+	let k = (PI * f0 / rate).tan();
+	let a0 = 1.0 + k / q + k * k;
+	let high_pass = Biquad::new(
+		[1.0, -2.0, 1.0],
+		[1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+	);
+
+	[shelf, high_pass]
+}
+
+/// Accumulates the K-weighted energy of interleaved samples, 100ms at a time.
+struct Meter {
+	filters: Vec<[Biquad; 2]>,
+	weights: Vec<f64>,
+	frames_per_step: usize,
+	channel: usize,
+	frames: usize,
+	energy: f64,
+	steps: Vec<f64>,
+}
+
+impl Meter {
+	fn new(channels: usize, sample_rate: u32) -> Option<Self> {
+		if channels == 0 || sample_rate < 10 {
+			return None;
+		}
+		// Surround channels of 5.1 layouts weigh more, while the LFE channel is ignored
+		let weights = match channels {
+			6 => vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41],
+			_ => vec![1.0; channels],
+		};
+		Some(Self {
+			filters: vec![k_weighting(sample_rate); channels],
+			weights,
+			frames_per_step: sample_rate as usize / 10,
+			channel: 0,
+			frames: 0,
+			energy: 0.0,
+			steps: Vec::new(),
+		})
+	}
+
+	fn add_samples(&mut self, samples: impl Iterator<Item = f64>) {
+		for sample in samples {
+			let [shelf, high_pass] = &mut self.filters[self.channel];
+			let filtered = high_pass.process(shelf.process(sample));
+			self.energy += self.weights[self.channel] * filtered * filtered;
+			self.channel += 1;
+			if self.channel == self.filters.len() {
+				self.channel = 0;
+				self.frames += 1;
+				if self.frames == self.frames_per_step {
+					self.steps.push(self.energy);
+					self.frames = 0;
+					self.energy = 0.0;
+				}
+			}
+		}
+	}
+
+	/// Blocks last 400ms and overlap by 75%, so each one spans four steps.
+	fn finish(self) -> Blocks {
+		let block_frames = (4 * self.frames_per_step) as f64;
+		Blocks(
+			self.steps
+				.windows(4)
+				.map(|steps| steps.iter().sum::<f64>() / block_frames)
+				.collect(),
+		)
+	}
+}
+
+#[cfg(test)]
+pub mod test {
+	use super::*;
+	use crate::test::prepare_test_directory;
+	use crate::test_name;
+
+	/// Writes a stereo 16-bit WAVE file holding a 997Hz sine wave, peaking at `level` dBFS.
+	pub fn write_sine_wave(path: &Path, level: f64, seconds: u32) {
+		let sample_rate = 48000u32;
+		let amplitude = 10f64.powf(level / 20.0) * 32767.0;
+		let num_frames = sample_rate * seconds;
+		let mut data = Vec::with_capacity(num_frames as usize * 4);
+		for frame in 0..num_frames {
+			let t = frame as f64 / sample_rate as f64;
+			let sample = (amplitude * (2.0 * PI * 997.0 * t).sin()) as i16;
+			data.extend_from_slice(&sample.to_le_bytes());
+			data.extend_from_slice(&sample.to_le_bytes());
+		}
+
+		let mut wave = Vec::new();
+		wave.extend_from_slice(b"RIFF");
+		wave.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+		wave.extend_from_slice(b"WAVE");
+		wave.extend_from_slice(b"fmt ");
+		wave.extend_from_slice(&16u32.to_le_bytes());
+		wave.extend_from_slice(&1u16.to_le_bytes());
+		wave.extend_from_slice(&2u16.to_le_bytes());
+		wave.extend_from_slice(&sample_rate.to_le_bytes());
+		wave.extend_from_slice(&(sample_rate * 4).to_le_bytes());
+		wave.extend_from_slice(&4u16.to_le_bytes());
+		wave.extend_from_slice(&16u16.to_le_bytes());
+		wave.extend_from_slice(b"data");
+		wave.extend_from_slice(&(data.len() as u32).to_le_bytes());
+		wave.extend_from_slice(&data);
+		fs::write(path, wave).unwrap();
+	}
+
+	#[test]
+	fn measures_sine_wave_loudness() {
+		let path = prepare_test_directory(test_name!()).join("sine.wav");
+		write_sine_wave(&path, -20.0, 3);
+
+		// Stereo sine waves measure as loud as their level in each channel (EBU Tech 3341)
+		let loudness = measure(&path).unwrap().integrated_loudness().unwrap();
+		assert!((loudness - -20.0).abs() < 0.1, "{loudness}");
+	}
+
+	#[test]
+	fn silence_has_no_loudness() {
+		let path = prepare_test_directory(test_name!()).join("silence.wav");
+		write_sine_wave(&path, -200.0, 1);
+
+		assert_eq!(measure(&path).unwrap().integrated_loudness(), None);
+	}
+}
@@ -2,9 +2,13 @@ use id3::TagLike;
 use lewton::inside_ogg::OggStreamReader;
 use log::error;
 use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
+use crate::app::settings::{MetadataReader, TagContainer};
 use crate::utils;
 use crate::utils::AudioFormat;
 
@@ -12,6 +16,8 @@ use crate::utils::AudioFormat;
 pub enum Error {
 	#[error(transparent)]
 	Ape(#[from] ape::Error),
+	#[error("Could not find an ASF header object within wma file")]
+	AsfHeaderNotFound,
 	#[error(transparent)]
 	Id3(#[from] id3::Error),
 	#[error("Filesystem error for `{0}`: `{1}`")]
@@ -28,21 +34,201 @@ pub enum Error {
 	VorbisCommentNotFoundInFlacFile,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Custom tags which, when set to a truthy value, mark a song as hidden from browsing.
+const HIDDEN_TAG_KEYS: [&str; 2] = ["POLARIS_HIDDEN", "HIDDEN"];
+
+/// Freeform MP4 atoms which are read into dedicated fields.
+const MP4_FREEFORM_KEYS: [&str; 20] = [
+	"Label",
+	"LYRICIST",
+	"ISRC",
+	"CATALOGNUMBER",
+	"CONDUCTOR",
+	"REMIXER",
+	"ARRANGER",
+	"PERFORMER",
+	"ORIGINALDATE",
+	"ORIGINALYEAR",
+	"ORIGINALARTIST",
+	"ORIGINALALBUM",
+	"LANGUAGE",
+	"MEDIA",
+	"DISCSUBTITLE",
+	"initialkey",
+	"KEY",
+	"RATING",
+	"POLARIS_HIDDEN",
+	"HIDDEN",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct SongTags {
 	pub disc_number: Option<u32>,
 	pub track_number: Option<u32>,
+	pub disc_total: Option<u32>,
+	pub track_total: Option<u32>,
+	/// Name of the disc within a multi-disc set, such as "The Early Years".
+	pub disc_subtitle: Option<String>,
 	pub title: Option<String>,
 	pub duration: Option<u32>,
+	/// Where `duration` was obtained from, so estimates can be told apart from exact values.
+	pub duration_source: Option<DurationSource>,
 	pub artist: Option<String>,
 	pub album_artist: Option<String>,
 	pub album: Option<String>,
 	pub year: Option<i32>,
+	pub original_year: Option<i32>,
 	pub has_artwork: bool,
-	pub lyricist: Option<String>,
-	pub composer: Option<String>,
+	pub artwork_mime: Option<String>,
+	/// Size of the embedded artwork, in bytes.
+	pub artwork_size: Option<u64>,
+	pub lyricists: Vec<String>,
+	pub composers: Vec<String>,
+	pub conductors: Vec<String>,
+	pub remixers: Vec<String>,
+	pub arrangers: Vec<String>,
+	/// Musicians credited on the recording, as opposed to the artist it is released under.
+	pub performers: Vec<String>,
 	pub genre: Option<String>,
 	pub label: Option<String>,
+	pub hidden: bool,
+	pub grouping: Option<String>,
+	pub work: Option<String>,
+	pub movement_name: Option<String>,
+	pub movement_number: Option<u32>,
+	pub isrc: Option<String>,
+	pub catalog_number: Option<String>,
+	pub encoded_by: Option<String>,
+	pub encoder_settings: Option<String>,
+	/// Performers of the original work, for cover versions.
+	pub original_artists: Vec<String>,
+	pub original_album: Option<String>,
+	/// Languages of the lyrics, as ISO 639-2 codes when recognized.
+	pub languages: Vec<String>,
+	/// Medium the song was sourced from, such as CD, Vinyl or Digital Media.
+	pub media_type: Option<String>,
+	/// Musical key the song starts in, as written by the tagger (eg. `Am` or `8A`).
+	pub initial_key: Option<String>,
+	/// Rating on a scale from 0 to 100, where 100 stands for five stars.
+	pub rating: Option<u8>,
+	/// Kind of content declared by the tags, such as the MP4 `stik` atom or ID3 chapters.
+	pub content_type: Option<ContentType>,
+	/// Custom fields not read into any of the above, keyed by their descriptor.
+	pub extra: HashMap<String, Vec<String>>,
+}
+
+/// How the duration of a song was obtained, from most to least reliable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DurationSource {
+	/// Length written in the tags of the file, such as ID3 `TLEN` frames.
+	Tag,
+	/// Computed by the decoding library from stream headers or audio frames.
+	Stream,
+	/// Estimated from the size of the file and the bitrate of its audio.
+	Estimate,
+}
+
+impl DurationSource {
+	/// Source of the durations read along with the tags of a reader. Only ID3 tags of AIFF, WAVE
+	/// and other non-MP3 files provide durations from tags: the `TLEN` frames of MP3 files are
+	/// often wrong, so their durations are computed from audio frames instead.
+	fn reported_by(reader: MetadataReader) -> Self {
+		match reader {
+			MetadataReader::Aiff | MetadataReader::Id3 | MetadataReader::Wave => {
+				DurationSource::Tag
+			}
+			_ => DurationSource::Stream,
+		}
+	}
+
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			DurationSource::Tag => "tag",
+			DurationSource::Stream => "stream",
+			DurationSource::Estimate => "estimate",
+		}
+	}
+}
+
+/// Kind of content a song holds, so that clients can play audiobooks and podcasts differently
+/// from music (eg. resuming playback where it was left).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ContentType {
+	#[default]
+	Music,
+	Audiobook,
+	Podcast,
+}
+
+impl ContentType {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			ContentType::Music => "music",
+			ContentType::Audiobook => "audiobook",
+			ContentType::Podcast => "podcast",
+		}
+	}
+}
+
+impl SongTags {
+	/// Fills fields which are missing from these tags with values from `other`.
+	fn merge(self, other: SongTags) -> SongTags {
+		fn or_vec(a: Vec<String>, b: Vec<String>) -> Vec<String> {
+			if a.is_empty() {
+				b
+			} else {
+				a
+			}
+		}
+		let mut extra = other.extra;
+		extra.extend(self.extra);
+		SongTags {
+			disc_number: self.disc_number.or(other.disc_number),
+			track_number: self.track_number.or(other.track_number),
+			disc_total: self.disc_total.or(other.disc_total),
+			track_total: self.track_total.or(other.track_total),
+			disc_subtitle: self.disc_subtitle.or(other.disc_subtitle),
+			title: self.title.or(other.title),
+			duration: self.duration.or(other.duration),
+			duration_source: match self.duration {
+				Some(_) => self.duration_source,
+				None => other.duration_source,
+			},
+			artist: self.artist.or(other.artist),
+			album_artist: self.album_artist.or(other.album_artist),
+			album: self.album.or(other.album),
+			year: self.year.or(other.year),
+			original_year: self.original_year.or(other.original_year),
+			has_artwork: self.has_artwork || other.has_artwork,
+			artwork_mime: self.artwork_mime.or(other.artwork_mime),
+			artwork_size: self.artwork_size.or(other.artwork_size),
+			lyricists: or_vec(self.lyricists, other.lyricists),
+			composers: or_vec(self.composers, other.composers),
+			conductors: or_vec(self.conductors, other.conductors),
+			remixers: or_vec(self.remixers, other.remixers),
+			arrangers: or_vec(self.arrangers, other.arrangers),
+			performers: or_vec(self.performers, other.performers),
+			genre: self.genre.or(other.genre),
+			label: self.label.or(other.label),
+			hidden: self.hidden || other.hidden,
+			grouping: self.grouping.or(other.grouping),
+			work: self.work.or(other.work),
+			movement_name: self.movement_name.or(other.movement_name),
+			movement_number: self.movement_number.or(other.movement_number),
+			isrc: self.isrc.or(other.isrc),
+			catalog_number: self.catalog_number.or(other.catalog_number),
+			encoded_by: self.encoded_by.or(other.encoded_by),
+			encoder_settings: self.encoder_settings.or(other.encoder_settings),
+			original_artists: or_vec(self.original_artists, other.original_artists),
+			original_album: self.original_album.or(other.original_album),
+			languages: or_vec(self.languages, other.languages),
+			media_type: self.media_type.or(other.media_type),
+			initial_key: self.initial_key.or(other.initial_key),
+			rating: self.rating.or(other.rating),
+			content_type: self.content_type.or(other.content_type),
+			extra,
+		}
+	}
 }
 
 impl From<id3::Tag> for SongTags {
@@ -51,53 +237,185 @@ impl From<id3::Tag> for SongTags {
 		let album_artist = tag.album_artist().map(|s| s.to_string());
 		let album = tag.album().map(|s| s.to_string());
 		let title = tag.title().map(|s| s.to_string());
-		let duration = tag.duration();
+		// Some taggers write a length of zero when they do not know it
+		let duration = tag.duration().filter(|d| *d > 0);
 		let disc_number = tag.disc();
 		let track_number = tag.track();
+		let disc_total = tag.total_discs();
+		let track_total = tag.total_tracks();
 		let year = tag
 			.year()
 			.or_else(|| tag.date_released().map(|d| d.year))
 			.or_else(|| tag.original_date_released().map(|d| d.year))
 			.or_else(|| tag.date_recorded().map(|d| d.year));
-		let has_artwork = tag.pictures().count() > 0;
-		let lyricist = tag.get_text("TEXT");
-		let composer = tag.get_text("TCOM");
+		let original_year = tag
+			.original_date_released()
+			.map(|d| d.year)
+			.or_else(|| tag.get_text("TORY").and_then(|y| parse_year(&y)));
+		let artwork_mime = tag.pictures().next().map(|p| p.mime_type.clone());
+		let artwork_size = tag.pictures().next().map(|p| p.data.len() as u64);
+		let has_artwork = artwork_mime.is_some();
+		let lyricists = tag
+			.get_text("TEXT")
+			.map(|v| split_values(&v))
+			.unwrap_or_default();
+		let composers = tag
+			.get_text("TCOM")
+			.map(|v| split_values(&v))
+			.unwrap_or_default();
+		let conductors = tag
+			.get_text("TPE3")
+			.map(|v| split_values(&v))
+			.unwrap_or_default();
+		let remixers = tag
+			.get_text("TPE4")
+			.map(|v| split_values(&v))
+			.unwrap_or_default();
+		let arrangers = involved_people(&tag, "arranger");
+		let performers = involved_people(&tag, "performer");
 		let genre = tag.genre().map(|s| s.to_string());
 		let label = tag.get_text("TPUB");
+		let grouping = tag.get_text("GRP1");
+		let work = tag.get_text("TIT1");
+		let movement_name = tag.get_text("MVNM");
+		let movement_number = tag.get_text("MVIN").and_then(|n| parse_x_of_y(&n));
+		let isrc = tag.get_text("TSRC");
+		let catalog_number = tag
+			.extended_texts()
+			.find(|t| t.description.eq_ignore_ascii_case("CATALOGNUMBER"))
+			.map(|t| t.value.clone());
+		let encoded_by = tag.get_text("TENC");
+		let encoder_settings = tag.get_text("TSSE");
+		let original_artists = tag
+			.get_text("TOPE")
+			.map(|v| split_values(&v))
+			.unwrap_or_default();
+		let original_album = tag.get_text("TOAL");
+		let languages = tag
+			.get_text("TLAN")
+			.map(|v| normalize_languages(split_values(&v)))
+			.unwrap_or_default();
+		let media_type = tag.get_text("TMED");
+		let disc_subtitle = tag.get_text("TSST");
+		let initial_key = tag.get_text("TKEY");
+		let rating = tag.frames().find_map(|frame| match frame.content() {
+			id3::Content::Popularimeter(p) => popularimeter_to_rating(p.rating),
+			_ => None,
+		});
+		// iTunes marks podcast episodes with a PCST frame, while audiobooks are split into chapters
+		let content_type = if tag.get("PCST").is_some() {
+			Some(ContentType::Podcast)
+		} else if tag.chapters().next().is_some() {
+			Some(ContentType::Audiobook)
+		} else {
+			None
+		};
+		let hidden = tag.extended_texts().any(|t| {
+			HIDDEN_TAG_KEYS
+				.iter()
+				.any(|k| k.eq_ignore_ascii_case(&t.description))
+				&& is_flag_set(&t.value)
+		});
+		let mut extra: HashMap<String, Vec<String>> = HashMap::new();
+		for text in tag.extended_texts() {
+			let recognized = text.description.eq_ignore_ascii_case("CATALOGNUMBER")
+				|| HIDDEN_TAG_KEYS
+					.iter()
+					.any(|k| k.eq_ignore_ascii_case(&text.description));
+			if !recognized {
+				extra
+					.entry(text.description.clone())
+					.or_default()
+					.extend(split_values(&text.value));
+			}
+		}
 
 		SongTags {
 			disc_number,
 			track_number,
+			disc_total,
+			track_total,
+			disc_subtitle,
 			title,
 			duration,
+			duration_source: None,
 			artist,
 			album_artist,
 			album,
 			year,
+			original_year,
 			has_artwork,
-			lyricist,
-			composer,
+			artwork_mime,
+			artwork_size,
+			lyricists,
+			composers,
+			conductors,
+			remixers,
+			arrangers,
+			performers,
 			genre,
 			label,
+			hidden,
+			grouping,
+			work,
+			movement_name,
+			movement_number,
+			isrc,
+			catalog_number,
+			encoded_by,
+			encoder_settings,
+			original_artists,
+			original_album,
+			languages,
+			media_type,
+			initial_key,
+			rating,
+			content_type,
+			extra,
 		}
 	}
 }
 
+/// Tag containers read from MP3 files when no priority is configured.
+const DEFAULT_TAG_PRIORITY: [TagContainer; 1] = [TagContainer::Id3];
+
 pub fn read(path: &Path) -> Option<SongTags> {
-	let data = match utils::get_audio_format(path) {
-		Some(AudioFormat::AIFF) => read_aiff(path),
-		Some(AudioFormat::APE) => read_ape(path),
-		Some(AudioFormat::FLAC) => read_flac(path),
-		Some(AudioFormat::MP3) => read_mp3(path),
-		Some(AudioFormat::MP4) => read_mp4(path),
-		Some(AudioFormat::MPC) => read_ape(path),
-		Some(AudioFormat::OGG) => read_vorbis(path),
-		Some(AudioFormat::OPUS) => read_opus(path),
-		Some(AudioFormat::WAVE) => read_wave(path),
-		None => return None,
+	read_with_tag_priority(path, &DEFAULT_TAG_PRIORITY)
+}
+
+/// Reads the tags of a song. Files which can hold several tag containers (eg. MP3 files with both
+/// ID3v2 and APEv2 tags) are read in `tag_priority` order, and fields missing from a container are
+/// filled from the next ones.
+pub fn read_with_tag_priority(path: &Path, tag_priority: &[TagContainer]) -> Option<SongTags> {
+	read_with_readers(path, tag_priority, &HashMap::new())
+}
+
+/// Same as `read_with_tag_priority`, except that files whose extension appears in `readers` are
+/// read using the reader it maps to, instead of the default reader of their audio format.
+pub fn read_with_readers(
+	path: &Path,
+	tag_priority: &[TagContainer],
+	readers: &HashMap<String, MetadataReader>,
+) -> Option<SongTags> {
+	let reader = get_reader(path, readers)?;
+	let data = match reader {
+		MetadataReader::Aiff => read_aiff(path),
+		MetadataReader::Ape => read_ape(path),
+		MetadataReader::Flac => read_flac(path),
+		MetadataReader::Id3 => read_id3(path),
+		MetadataReader::Mp3 => read_mp3(path, tag_priority),
+		MetadataReader::Mp4 => read_mp4(path),
+		MetadataReader::Opus => read_opus(path),
+		MetadataReader::Vorbis => read_vorbis(path),
+		MetadataReader::Wave => read_wave(path),
+		MetadataReader::Wma => read_wma(path),
+		MetadataReader::Skip => return None,
 	};
 	match data {
-		Ok(d) => Some(d),
+		Ok(mut tags) => {
+			tags.duration_source = tags.duration.map(|_| DurationSource::reported_by(reader));
+			Some(tags)
+		}
 		Err(e) => {
 			error!("Error while reading file metadata for '{:?}': {}", path, e);
 			None
@@ -105,6 +423,350 @@ pub fn read(path: &Path) -> Option<SongTags> {
 	}
 }
 
+/// Picks the reader of a file: the reader configured for its extension, or else the default reader
+/// of its audio format. Files which are skipped or are not audio files have no reader.
+pub fn get_reader(
+	path: &Path,
+	readers: &HashMap<String, MetadataReader>,
+) -> Option<MetadataReader> {
+	let configured_reader = path
+		.extension()
+		.and_then(|e| e.to_str())
+		.and_then(|e| readers.get(&e.to_lowercase()))
+		.copied();
+	let reader = match configured_reader {
+		Some(reader) => reader,
+		None => match utils::get_audio_format(path)? {
+			AudioFormat::AIFF => MetadataReader::Aiff,
+			AudioFormat::APE => MetadataReader::Ape,
+			AudioFormat::FLAC => MetadataReader::Flac,
+			AudioFormat::MP3 => MetadataReader::Mp3,
+			AudioFormat::MP4 => MetadataReader::Mp4,
+			AudioFormat::MPC => MetadataReader::Ape,
+			AudioFormat::OGG => MetadataReader::Vorbis,
+			AudioFormat::OPUS => MetadataReader::Opus,
+			AudioFormat::WAVE => MetadataReader::Wave,
+			AudioFormat::WMA => MetadataReader::Wma,
+		},
+	};
+	(reader != MetadataReader::Skip).then_some(reader)
+}
+
+/// Problems with the tags of a song, which make it harder to find once indexed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LintWarning {
+	MissingTitle,
+	MissingArtist,
+	/// The year tag is set, but its value could not be understood.
+	UnparseableYear(String),
+	MissingArtwork,
+}
+
+/// Reads the tags of a song without indexing it, and reports problems with them.
+pub fn lint(path: &Path) -> Option<(SongTags, Vec<LintWarning>)> {
+	let tags = read(path)?;
+	let mut warnings = Vec::new();
+	if tags.title.is_none() {
+		warnings.push(LintWarning::MissingTitle);
+	}
+	if tags.artist.is_none() && tags.album_artist.is_none() {
+		warnings.push(LintWarning::MissingArtist);
+	}
+	if tags.year.is_none() {
+		if let Some(year) = read_year_text(path) {
+			warnings.push(LintWarning::UnparseableYear(year));
+		}
+	}
+	if !tags.has_artwork {
+		warnings.push(LintWarning::MissingArtwork);
+	}
+	Some((tags, warnings))
+}
+
+/// Computes the duration of songs whose tags or decoding library do not provide one, from the
+/// audio headers of the file. Durations are in whole seconds, so songs shorter than a second have
+/// a duration of zero, while `None` means the duration is unknown.
+pub fn estimate_duration(path: &Path) -> Option<u32> {
+	let mut file = fs::File::open(path).ok()?;
+	match utils::get_audio_format(path)? {
+		AudioFormat::APE => estimate_ape_duration(&mut file),
+		AudioFormat::MP3 => estimate_mp3_duration(&mut file),
+		AudioFormat::WAVE => estimate_wave_duration(&mut file),
+		_ => None,
+	}
+}
+
+/// Last step of the duration fallback chain: estimates the duration of songs whose tags and
+/// decoding library did not provide one.
+pub fn fill_missing_duration(path: &Path, tags: &mut SongTags) {
+	if tags.duration.is_some() {
+		return;
+	}
+	tags.duration = estimate_duration(path);
+	tags.duration_source = tags.duration.map(|_| DurationSource::Estimate);
+}
+
+/// Bitrates of MPEG-1 Layer III frames in kbps, by bitrate index. Index 0 is free format.
+const MPEG1_LAYER3_BITRATES: [u32; 15] = [
+	0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+];
+
+/// Bitrates of MPEG-2 and MPEG-2.5 Layer III frames in kbps, by bitrate index.
+const MPEG2_LAYER3_BITRATES: [u32; 15] =
+	[0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+/// Assumes a constant bitrate: the duration is the size of the audio data divided by the bitrate
+/// of its first frame.
+fn estimate_mp3_duration(file: &mut fs::File) -> Option<u32> {
+	let file_length = file.metadata().ok()?.len();
+	let mut id3_header = [0u8; 10];
+	file.read_exact(&mut id3_header).ok()?;
+	// Audio frames follow the ID3v2 tag, whose size is stored as a syncsafe integer
+	let audio_start = if &id3_header[0..3] == b"ID3" {
+		let size = id3_header[6..10]
+			.iter()
+			.fold(0u64, |size, b| (size << 7) | (*b & 0x7F) as u64);
+		let footer_size = if id3_header[5] & 0x10 != 0 { 10 } else { 0 };
+		10 + size + footer_size
+	} else {
+		0
+	};
+
+	file.seek(SeekFrom::Start(audio_start)).ok()?;
+	let mut frame_header = [0u8; 4];
+	file.read_exact(&mut frame_header).ok()?;
+	if frame_header[0] != 0xFF || frame_header[1] & 0xE0 != 0xE0 {
+		return None;
+	}
+	// Version 3 is MPEG-1 and version 1 is reserved. Layer 1 is Layer III.
+	let version = (frame_header[1] >> 3) & 0x03;
+	let layer = (frame_header[1] >> 1) & 0x03;
+	if version == 1 || layer != 1 {
+		return None;
+	}
+	let bitrates = match version {
+		3 => &MPEG1_LAYER3_BITRATES,
+		_ => &MPEG2_LAYER3_BITRATES,
+	};
+	let bitrate = bitrates
+		.get((frame_header[2] >> 4) as usize)
+		.filter(|b| **b > 0)?;
+
+	let audio_length = file_length.checked_sub(audio_start)?;
+	Some((audio_length * 8 / (*bitrate as u64 * 1000)) as u32)
+}
+
+/// Monkey's Audio headers store the number of audio blocks (samples per channel) per frame.
+fn estimate_ape_duration(file: &mut fs::File) -> Option<u32> {
+	let mut header = [0u8; 128];
+	let header_length = file.read(&mut header).ok()?;
+	let header = &header[..header_length];
+	if header.get(0..4)? != b"MAC " {
+		return None;
+	}
+	let u16_at = |i: usize| Some(u16::from_le_bytes(header.get(i..i + 2)?.try_into().ok()?));
+	let u32_at = |i: usize| Some(u32::from_le_bytes(header.get(i..i + 4)?.try_into().ok()?));
+
+	let version = u16_at(4)?;
+	let (blocks_per_frame, final_frame_blocks, total_frames, sample_rate) = if version >= 3980 {
+		// The header follows a descriptor of variable length
+		let offset = u32_at(8)? as usize;
+		(
+			u32_at(offset + 4)?,
+			u32_at(offset + 8)?,
+			u32_at(offset + 12)?,
+			u32_at(offset + 20)?,
+		)
+	} else {
+		let compression_level = u16_at(6)?;
+		let blocks_per_frame = if version >= 3950 {
+			73728 * 4
+		} else if version >= 3900 || (version >= 3800 && compression_level == 4000) {
+			73728
+		} else {
+			9216
+		};
+		(blocks_per_frame, u32_at(28)?, u32_at(24)?, u32_at(12)?)
+	};
+
+	if sample_rate == 0 {
+		return None;
+	}
+	let total_blocks = match total_frames {
+		0 => 0,
+		n => (n as u64 - 1) * blocks_per_frame as u64 + final_frame_blocks as u64,
+	};
+	Some((total_blocks / sample_rate as u64) as u32)
+}
+
+/// Wave files are uncompressed, so their duration is the size of their audio data divided by its
+/// byte rate.
+fn estimate_wave_duration(file: &mut fs::File) -> Option<u32> {
+	let mut riff_header = [0u8; 12];
+	file.read_exact(&mut riff_header).ok()?;
+	if &riff_header[0..4] != b"RIFF" || &riff_header[8..12] != b"WAVE" {
+		return None;
+	}
+	let mut byte_rate = None;
+	loop {
+		let mut chunk_header = [0u8; 8];
+		file.read_exact(&mut chunk_header).ok()?;
+		let chunk_size = u32::from_le_bytes(chunk_header[4..8].try_into().ok()?);
+		match &chunk_header[0..4] {
+			b"fmt " => {
+				let mut format = [0u8; 12];
+				file.read_exact(&mut format).ok()?;
+				byte_rate = Some(u32::from_le_bytes(format[8..12].try_into().ok()?));
+				file.seek(SeekFrom::Current(chunk_size as i64 - 12)).ok()?;
+			}
+			b"data" => {
+				let byte_rate = byte_rate.filter(|r| *r > 0)?;
+				return Some(chunk_size / byte_rate);
+			}
+			_ => {
+				file.seek(SeekFrom::Current(chunk_size as i64)).ok()?;
+			}
+		}
+		// Chunks are padded to an even size
+		if chunk_size % 2 == 1 {
+			file.seek(SeekFrom::Current(1)).ok()?;
+		}
+	}
+}
+
+/// Tag keys and values as they are stored in a file, before any interpretation.
+pub type RawTags = BTreeMap<String, Vec<String>>;
+
+/// Reads every tag of a song verbatim: ID3 frames, APE items, Vorbis comments or MP4 atoms.
+/// Values which are not text are summarized.
+pub fn read_raw(path: &Path) -> Option<RawTags> {
+	let mut raw_tags = RawTags::new();
+	let mut push = |key: String, value: String| raw_tags.entry(key).or_default().push(value);
+	let read_id3 = |tag: id3::Tag, push: &mut dyn FnMut(String, String)| {
+		for frame in tag.frames() {
+			match frame.content() {
+				id3::Content::Text(value) => push(frame.id().to_owned(), value.clone()),
+				id3::Content::ExtendedText(t) => {
+					push(format!("{}:{}", frame.id(), t.description), t.value.clone())
+				}
+				id3::Content::Comment(c) => {
+					push(format!("{}:{}", frame.id(), c.description), c.text.clone())
+				}
+				content => push(frame.id().to_owned(), content.to_string()),
+			}
+		}
+	};
+	match utils::get_audio_format(path)? {
+		AudioFormat::AIFF => read_id3(id3::Tag::read_from_aiff_path(path).ok()?, &mut push),
+		AudioFormat::MP3 => read_id3(id3::Tag::read_from_path(path).ok()?, &mut push),
+		AudioFormat::WAVE => read_id3(id3::Tag::read_from_wav_path(path).ok()?, &mut push),
+		AudioFormat::APE | AudioFormat::MPC => {
+			for item in ape::read_from_path(path).ok()?.iter() {
+				let value = match item.value {
+					ape::ItemValue::Text(ref s) | ape::ItemValue::Locator(ref s) => s.clone(),
+					ape::ItemValue::Binary(ref b) => format!("<{} bytes>", b.len()),
+				};
+				push(item.key.clone(), value);
+			}
+		}
+		AudioFormat::FLAC => {
+			let tag = metaflac::Tag::read_from_path(path).ok()?;
+			for (key, values) in &tag.vorbis_comments()?.comments {
+				for value in values {
+					push(key.clone(), value.clone());
+				}
+			}
+		}
+		AudioFormat::MP4 => {
+			let tag = mp4ameta::Tag::read_from_path(path).ok()?;
+			for (ident, data) in tag.data() {
+				let value = match data.string() {
+					Some(s) => s.to_owned(),
+					None => "<binary data>".to_owned(),
+				};
+				push(ident.to_string(), value);
+			}
+		}
+		AudioFormat::OGG => {
+			let file = fs::File::open(path).ok()?;
+			let source = OggStreamReader::new(file).ok()?;
+			for (key, value) in source.comment_hdr.comment_list {
+				push(key, value);
+			}
+		}
+		AudioFormat::OPUS => {
+			let headers = opus_headers::parse_from_path(path).ok()?;
+			for (key, value) in headers.comments.user_comments {
+				push(key, value);
+			}
+		}
+		AudioFormat::WMA => {
+			for (name, value) in read_asf_header(path).ok()?.attributes {
+				push(name, value.to_string());
+			}
+		}
+	}
+	Some(raw_tags)
+}
+
+/// Reads the year of a song as it is written in its tags, before any parsing.
+fn read_year_text(path: &Path) -> Option<String> {
+	let read_id3_year = |tag: id3::Tag| {
+		tag.get_text("TYER")
+			.or_else(|| tag.get_text("TDRL"))
+			.or_else(|| tag.get_text("TDRC"))
+	};
+	fn find_date(comments: impl IntoIterator<Item = (String, String)>) -> Option<String> {
+		comments
+			.into_iter()
+			.find(|(k, _)| k.eq_ignore_ascii_case("DATE"))
+			.map(|(_, v)| v)
+	}
+	match utils::get_audio_format(path)? {
+		AudioFormat::AIFF => id3::Tag::read_from_aiff_path(path)
+			.ok()
+			.and_then(read_id3_year),
+		AudioFormat::APE | AudioFormat::MPC => ape::read_from_path(path)
+			.ok()?
+			.item("Year")
+			.and_then(read_ape_string),
+		AudioFormat::FLAC => metaflac::Tag::read_from_path(path)
+			.ok()?
+			.vorbis_comments()?
+			.get("DATE")?
+			.first()
+			.cloned(),
+		AudioFormat::MP3 => id3::Tag::read_from_path(path).ok().and_then(read_id3_year),
+		AudioFormat::MP4 => mp4ameta::Tag::read_from_path(path)
+			.ok()?
+			.year()
+			.map(|y| y.to_owned()),
+		AudioFormat::OGG => {
+			let file = fs::File::open(path).ok()?;
+			let source = OggStreamReader::new(file).ok()?;
+			find_date(source.comment_hdr.comment_list)
+		}
+		AudioFormat::OPUS => {
+			let headers = opus_headers::parse_from_path(path).ok()?;
+			find_date(headers.comments.user_comments)
+		}
+		AudioFormat::WAVE => id3::Tag::read_from_wav_path(path)
+			.ok()
+			.and_then(read_id3_year),
+		AudioFormat::WMA => read_asf_header(path)
+			.ok()?
+			.attributes
+			.into_iter()
+			.find(|(name, _)| name == "WM/Year")
+			.and_then(|(_, value)| value.text()),
+	}
+}
+
+fn is_flag_set(value: &str) -> bool {
+	let value = value.trim();
+	value == "1" || value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("yes")
+}
+
 trait FrameContent {
 	/// Returns the value stored, if any, in the Frame.
 	/// Say "TCOM" returns composer field.
@@ -121,46 +783,162 @@ impl FrameContent for id3::Tag {
 	}
 }
 
-fn read_mp3(path: &Path) -> Result<SongTags, Error> {
-	let tag = id3::Tag::read_from_path(path).or_else(|error| {
-		if let Some(tag) = error.partial_tag {
-			Ok(tag)
+fn read_id3(path: &Path) -> Result<SongTags, Error> {
+	read_id3_ignoring_broken_pictures(path, |p| id3::Tag::read_from_path(p), false)
+}
+
+/// Reads an ID3 tag using `read_tag`. Tags which cannot be read as a whole are read again without their
+/// pictures, so that a broken picture only leaves the song without artwork. When that fails too, the
+/// frames preceding the problem are kept.
+fn read_id3_ignoring_broken_pictures(
+	path: &Path,
+	read_tag: fn(&Path) -> id3::Result<id3::Tag>,
+	in_chunk: bool,
+) -> Result<SongTags, Error> {
+	let error = match read_tag(path) {
+		Ok(tag) => return Ok(tag.into()),
+		Err(error) => error,
+	};
+	let tag_without_pictures = fs::File::open(path).ok().and_then(|mut file| {
+		if in_chunk {
+			seek_to_id3_chunk(&mut file)?;
+		}
+		read_id3_without_pictures(&mut file)
+	});
+	if let Some(tag) = tag_without_pictures {
+		Ok(tag.into())
+	} else if let Some(tag) = error.partial_tag {
+		Ok(tag.into())
+	} else {
+		Err(error.into())
+	}
+}
+
+/// Reads the ID3v2 tag starting at the current position of `reader`, leaving out its picture frames.
+/// Tags using unsynchronisation or an extended header are not supported.
+fn read_id3_without_pictures(reader: &mut impl Read) -> Option<id3::Tag> {
+	let mut header = [0u8; 10];
+	reader.read_exact(&mut header).ok()?;
+	let version = header[3];
+	if &header[0..3] != b"ID3" || header[5] & 0xC0 != 0 {
+		return None;
+	}
+	let (id_length, header_length) = match version {
+		2 => (3, 6),
+		3 | 4 => (4, 10),
+		_ => return None,
+	};
+	let mut frames = vec![0u8; decode_synchsafe(&header[6..10]) as usize];
+	reader.read_exact(&mut frames).ok()?;
+
+	let mut kept_frames = Vec::new();
+	let mut offset = 0;
+	while offset + header_length <= frames.len() && frames[offset] != 0 {
+		let id = &frames[offset..offset + id_length];
+		let size_bytes = &frames[offset + id_length..offset + header_length.min(id_length + 4)];
+		let size = match version {
+			2 | 3 => size_bytes
+				.iter()
+				.fold(0, |size, b| (size << 8) | *b as usize),
+			_ => decode_synchsafe(size_bytes) as usize,
+		};
+		let end = offset + header_length + size;
+		if end > frames.len() {
+			break;
+		}
+		if id != b"APIC" && id != b"PIC" {
+			kept_frames.extend_from_slice(&frames[offset..end]);
+		}
+		offset = end;
+	}
+
+	let mut tag = header[0..6].to_vec();
+	tag[5] = 0;
+	tag.extend(encode_synchsafe(kept_frames.len() as u32));
+	tag.extend(kept_frames);
+	id3::Tag::read_from(&mut Cursor::new(tag)).ok()
+}
+
+fn decode_synchsafe(bytes: &[u8]) -> u32 {
+	bytes
+		.iter()
+		.fold(0, |value, b| (value << 7) | (*b & 0x7F) as u32)
+}
+
+fn encode_synchsafe(value: u32) -> [u8; 4] {
+	[
+		((value >> 21) & 0x7F) as u8,
+		((value >> 14) & 0x7F) as u8,
+		((value >> 7) & 0x7F) as u8,
+		(value & 0x7F) as u8,
+	]
+}
+
+/// Moves `file` to the start of the ID3 chunk of an AIFF or WAVE file.
+fn seek_to_id3_chunk(file: &mut fs::File) -> Option<()> {
+	let mut header = [0u8; 12];
+	file.read_exact(&mut header).ok()?;
+	let big_endian = match &header[0..4] {
+		b"FORM" => true,
+		b"RIFF" => false,
+		_ => return None,
+	};
+	loop {
+		let mut chunk_header = [0u8; 8];
+		file.read_exact(&mut chunk_header).ok()?;
+		if chunk_header[0..4].eq_ignore_ascii_case(b"ID3 ") {
+			return Some(());
+		}
+		let size_bytes = chunk_header[4..8].try_into().ok()?;
+		let size = if big_endian {
+			u32::from_be_bytes(size_bytes)
 		} else {
-			Err(error)
+			u32::from_le_bytes(size_bytes)
+		} as i64;
+		// Chunks are padded to an even size
+		file.seek(SeekFrom::Current(size + size % 2)).ok()?;
+	}
+}
+
+fn read_mp3(path: &Path, tag_priority: &[TagContainer]) -> Result<SongTags, Error> {
+	let mut song_tags: Option<SongTags> = None;
+	let mut first_error = None;
+	for container in tag_priority {
+		let tags = match container {
+			TagContainer::Id3 => read_id3(path),
+			TagContainer::Ape => read_ape(path),
+		};
+		match (tags, song_tags.take()) {
+			(Ok(tags), Some(primary)) => song_tags = Some(primary.merge(tags)),
+			(Ok(tags), None) => song_tags = Some(tags),
+			(Err(e), primary) => {
+				song_tags = primary;
+				first_error.get_or_insert(e);
+			}
 		}
-	})?;
+	}
+
+	let mut song_tags = match (song_tags, first_error) {
+		(Some(tags), _) => tags,
+		(None, Some(e)) => return Err(e),
+		(None, None) => SongTags::default(),
+	};
 
 	let duration = {
 		mp3_duration::from_path(path)
 			.map(|d| d.as_secs() as u32)
 			.ok()
 	};
-
-	let mut song_tags: SongTags = tag.into();
 	song_tags.duration = duration; // Use duration from mp3_duration instead of from tags.
 	Ok(song_tags)
 }
 
 fn read_aiff(path: &Path) -> Result<SongTags, Error> {
-	let tag = id3::Tag::read_from_aiff_path(path).or_else(|error| {
-		if let Some(tag) = error.partial_tag {
-			Ok(tag)
-		} else {
-			Err(error)
-		}
-	})?;
-	Ok(tag.into())
+	read_id3_ignoring_broken_pictures(path, |p| id3::Tag::read_from_aiff_path(p), true)
 }
 
 fn read_wave(path: &Path) -> Result<SongTags, Error> {
-	let tag = id3::Tag::read_from_wav_path(path).or_else(|error| {
-		if let Some(tag) = error.partial_tag {
-			Ok(tag)
-		} else {
-			Err(error)
-		}
-	})?;
-	Ok(tag.into())
+	read_id3_ignoring_broken_pictures(path, |p| id3::Tag::read_from_wav_path(p), true)
 }
 
 fn read_ape_string(item: &ape::Item) -> Option<String> {
@@ -179,18 +957,153 @@ fn read_ape_i32(item: &ape::Item) -> Option<i32> {
 
 fn read_ape_x_of_y(item: &ape::Item) -> Option<u32> {
 	match item.value {
-		ape::ItemValue::Text(ref s) => {
-			let format = Regex::new(r#"^\d+"#).unwrap();
-			if let Some(m) = format.find(s) {
-				s[m.start()..m.end()].parse().ok()
-			} else {
-				None
-			}
-		}
+		ape::ItemValue::Text(ref s) => parse_x_of_y(s),
 		_ => None,
 	}
 }
 
+fn read_ape_total_of_x_of_y(item: &ape::Item) -> Option<u32> {
+	match item.value {
+		ape::ItemValue::Text(ref s) => parse_total_of_x_of_y(s),
+		_ => None,
+	}
+}
+
+/// Splits tag values holding several entries separated by null characters.
+fn split_values(value: &str) -> Vec<String> {
+	value
+		.split('\0')
+		.map(str::trim)
+		.filter(|v| !v.is_empty())
+		.map(str::to_owned)
+		.collect()
+}
+
+/// Common languages as their ISO 639-1 code, ISO 639-2/B code (when it differs), ISO 639-2/T code
+/// and English name.
+const LANGUAGES: [(&str, Option<&str>, &str, &str); 24] = [
+	("ar", None, "ara", "arabic"),
+	("zh", Some("chi"), "zho", "chinese"),
+	("da", None, "dan", "danish"),
+	("nl", Some("dut"), "nld", "dutch"),
+	("en", None, "eng", "english"),
+	("fi", None, "fin", "finnish"),
+	("fr", Some("fre"), "fra", "french"),
+	("de", Some("ger"), "deu", "german"),
+	("el", Some("gre"), "ell", "greek"),
+	("he", None, "heb", "hebrew"),
+	("hi", None, "hin", "hindi"),
+	("is", Some("ice"), "isl", "icelandic"),
+	("it", None, "ita", "italian"),
+	("ja", None, "jpn", "japanese"),
+	("ko", None, "kor", "korean"),
+	("la", None, "lat", "latin"),
+	("no", None, "nor", "norwegian"),
+	("pl", None, "pol", "polish"),
+	("pt", None, "por", "portuguese"),
+	("ru", None, "rus", "russian"),
+	("es", None, "spa", "spanish"),
+	("sv", None, "swe", "swedish"),
+	("tr", None, "tur", "turkish"),
+	("uk", None, "ukr", "ukrainian"),
+];
+
+/// Converts a language to its ISO 639-2/T code when it is a recognized code or English name.
+/// Other three letter values are assumed to be ISO 639-2 codes already, and anything else is kept.
+pub(super) fn normalize_language(value: &str) -> String {
+	let value = value.trim();
+	let lowercase = value.to_lowercase();
+	let known_language = LANGUAGES.iter().find(|(code_1, code_2b, code_2t, name)| {
+		[Some(*code_1), *code_2b, Some(*code_2t), Some(*name)].contains(&Some(lowercase.as_str()))
+	});
+	match known_language {
+		Some((_, _, code, _)) => (*code).to_owned(),
+		None if lowercase.len() == 3 && lowercase.chars().all(|c| c.is_ascii_alphabetic()) => {
+			lowercase
+		}
+		None => value.to_owned(),
+	}
+}
+
+fn normalize_languages(values: Vec<String>) -> Vec<String> {
+	values.iter().map(|v| normalize_language(v)).collect()
+}
+
+/// Parses the year of dates like `1987` or `1987-05-12`.
+fn parse_year(value: &str) -> Option<i32> {
+	let format = Regex::new(r#"^\d{4}"#).unwrap();
+	format
+		.find(value.trim())
+		.and_then(|m| m.as_str().parse().ok())
+}
+
+/// Parses the leading number of values like `3` or `3/12`.
+fn parse_x_of_y(value: &str) -> Option<u32> {
+	let format = Regex::new(r#"^\d+"#).unwrap();
+	format
+		.find(value.trim())
+		.and_then(|m| m.as_str().parse().ok())
+}
+
+/// Parses the trailing total of values like `3/12`.
+fn parse_total_of_x_of_y(value: &str) -> Option<u32> {
+	static FORMAT: OnceLock<Regex> = OnceLock::new();
+	FORMAT
+		.get_or_init(|| Regex::new(r#"^\d*\s*/\s*(\d+)$"#).unwrap())
+		.captures(value.trim())
+		.and_then(|c| c[1].parse().ok())
+}
+
+/// Maps the rating byte of an ID3 popularimeter to a 0-100 rating, using the star ranges
+/// of most taggers (1 is one star, 64 two stars, 128 three stars, 196 four stars, 255 five stars).
+/// Zero means the song is not rated.
+fn popularimeter_to_rating(value: u8) -> Option<u8> {
+	let stars = match value {
+		0 => return None,
+		1..=31 => 1,
+		32..=95 => 2,
+		96..=159 => 3,
+		160..=223 => 4,
+		_ => 5,
+	};
+	Some(stars * 20)
+}
+
+/// Names credited with a role in the involved people frames of an ID3 tag (`TIPL`, or `IPLS`
+/// in ID3v2.3), such as `arranger` or `performer`.
+fn involved_people(tag: &id3::Tag, role: &str) -> Vec<String> {
+	tag.frames()
+		.filter(|frame| matches!(frame.id(), "TIPL" | "IPLS"))
+		.filter_map(|frame| match frame.content() {
+			id3::Content::InvolvedPeopleList(list) => Some(list),
+			_ => None,
+		})
+		.flat_map(|list| list.items.iter())
+		.filter(|item| item.involvement.trim().eq_ignore_ascii_case(role))
+		.flat_map(|item| split_values(&item.involvee))
+		.collect()
+}
+
+/// Parses `RATING` values, written either as a number of stars (0 to 5) or on a 0-100 scale.
+fn parse_rating(value: &str) -> Option<u8> {
+	let rating = value.trim().parse::<f64>().ok()?;
+	if (0.0..=5.0).contains(&rating) {
+		Some((rating * 20.0).round() as u8)
+	} else if (0.0..=100.0).contains(&rating) {
+		Some(rating.round() as u8)
+	} else {
+		None
+	}
+}
+
+/// Parses `FMPS_RATING` values, which range from 0 to 1.
+fn parse_fmps_rating(value: &str) -> Option<u8> {
+	let rating = value.trim().parse::<f64>().ok()?;
+	(0.0..=1.0)
+		.contains(&rating)
+		.then(|| (rating * 100.0).round() as u8)
+}
+
 fn read_ape(path: &Path) -> Result<SongTags, Error> {
 	let tag = ape::read_from_path(path)?;
 	let artist = tag.item("Artist").and_then(read_ape_string);
@@ -198,198 +1111,890 @@ fn read_ape(path: &Path) -> Result<SongTags, Error> {
 	let album_artist = tag.item("Album artist").and_then(read_ape_string);
 	let title = tag.item("Title").and_then(read_ape_string);
 	let year = tag.item("Year").and_then(read_ape_i32);
+	let original_year = ["ORIGINALDATE", "ORIGINALYEAR"]
+		.iter()
+		.filter_map(|k| tag.item(k).and_then(read_ape_string))
+		.find_map(|v| parse_year(&v));
 	let disc_number = tag.item("Disc").and_then(read_ape_x_of_y);
 	let track_number = tag.item("Track").and_then(read_ape_x_of_y);
-	let lyricist = tag.item("LYRICIST").and_then(read_ape_string);
-	let composer = tag.item("COMPOSER").and_then(read_ape_string);
+	let disc_total = tag.item("Disc").and_then(read_ape_total_of_x_of_y);
+	let track_total = tag.item("Track").and_then(read_ape_total_of_x_of_y);
+	let lyricists = tag
+		.item("LYRICIST")
+		.and_then(read_ape_string)
+		.map(|v| split_values(&v))
+		.unwrap_or_default();
+	let composers = tag
+		.item("COMPOSER")
+		.and_then(read_ape_string)
+		.map(|v| split_values(&v))
+		.unwrap_or_default();
+	let conductors = tag
+		.item("CONDUCTOR")
+		.and_then(read_ape_string)
+		.map(|v| split_values(&v))
+		.unwrap_or_default();
+	let remixers = tag
+		.item("MIXARTIST")
+		.and_then(read_ape_string)
+		.map(|v| split_values(&v))
+		.unwrap_or_default();
+	let arrangers = tag
+		.item("ARRANGER")
+		.and_then(read_ape_string)
+		.map(|v| split_values(&v))
+		.unwrap_or_default();
+	let performers = tag
+		.item("PERFORMER")
+		.and_then(read_ape_string)
+		.map(|v| split_values(&v))
+		.unwrap_or_default();
 	let genre = tag.item("GENRE").and_then(read_ape_string);
 	let label = tag.item("PUBLISHER").and_then(read_ape_string);
+	let grouping = tag.item("GROUPING").and_then(read_ape_string);
+	let work = tag.item("WORK").and_then(read_ape_string);
+	let movement_name = tag.item("MOVEMENTNAME").and_then(read_ape_string);
+	let movement_number = tag.item("MOVEMENT").and_then(read_ape_x_of_y);
+	let isrc = tag.item("ISRC").and_then(read_ape_string);
+	let catalog_number = tag.item("CATALOGNUMBER").and_then(read_ape_string);
+	let encoded_by = tag.item("ENCODEDBY").and_then(read_ape_string);
+	let encoder_settings = tag.item("ENCODER").and_then(read_ape_string);
+	let original_artists = tag
+		.item("ORIGINALARTIST")
+		.and_then(read_ape_string)
+		.map(|v| split_values(&v))
+		.unwrap_or_default();
+	let original_album = tag.item("ORIGINALALBUM").and_then(read_ape_string);
+	let languages = tag
+		.item("LANGUAGE")
+		.and_then(read_ape_string)
+		.map(|v| normalize_languages(split_values(&v)))
+		.unwrap_or_default();
+	let media_type = tag.item("MEDIA").and_then(read_ape_string);
+	let disc_subtitle = tag.item("DISCSUBTITLE").and_then(read_ape_string);
+	let initial_key = ["INITIALKEY", "KEY"]
+		.iter()
+		.find_map(|k| tag.item(k).and_then(read_ape_string));
+	let rating = tag
+		.item("RATING")
+		.and_then(read_ape_string)
+		.and_then(|v| parse_rating(&v));
+	let hidden = HIDDEN_TAG_KEYS
+		.iter()
+		.filter_map(|k| tag.item(k).and_then(read_ape_string))
+		.any(|v| is_flag_set(&v));
 	Ok(SongTags {
 		artist,
 		album_artist,
 		album,
 		title,
 		duration: None,
+		duration_source: None,
 		disc_number,
 		track_number,
+		disc_total,
+		track_total,
+		disc_subtitle,
 		year,
+		original_year,
 		has_artwork: false,
-		lyricist,
-		composer,
+		artwork_mime: None,
+		artwork_size: None,
+		lyricists,
+		composers,
+		conductors,
+		remixers,
+		arrangers,
+		performers,
 		genre,
 		label,
+		hidden,
+		grouping,
+		work,
+		movement_name,
+		movement_number,
+		isrc,
+		catalog_number,
+		encoded_by,
+		encoder_settings,
+		original_artists,
+		original_album,
+		languages,
+		media_type,
+		initial_key,
+		rating,
+		content_type: None,
+		extra: HashMap::new(),
 	})
 }
 
+/// Ogg files may chain several logical streams one after the other. Tags are read from the first stream,
+/// while the duration covers all streams.
 fn read_vorbis(path: &Path) -> Result<SongTags, Error> {
 	let file = fs::File::open(path).map_err(|e| Error::Io(path.to_owned(), e))?;
 	let source = OggStreamReader::new(file)?;
+	Ok(SongTags {
+		duration: read_ogg_duration(path),
+		..read_vorbis_comments(source.comment_hdr.comment_list)
+	})
+}
 
-	let mut tags = SongTags {
-		artist: None,
-		album_artist: None,
-		album: None,
-		title: None,
-		duration: None,
-		disc_number: None,
-		track_number: None,
-		year: None,
-		has_artwork: false,
-		lyricist: None,
-		composer: None,
-		genre: None,
-		label: None,
-	};
+fn read_opus(path: &Path) -> Result<SongTags, Error> {
+	let headers = opus_headers::parse_from_path(path)?;
+	Ok(SongTags {
+		duration: read_ogg_duration(path),
+		..read_vorbis_comments(headers.comments.user_comments)
+	})
+}
 
-	for (key, value) in source.comment_hdr.comment_list {
-		utils::match_ignore_case! {
-			match key {
-				"TITLE" => tags.title = Some(value),
-				"ALBUM" => tags.album = Some(value),
-				"ARTIST" => tags.artist = Some(value),
-				"ALBUMARTIST" => tags.album_artist = Some(value),
-				"TRACKNUMBER" => tags.track_number = value.parse::<u32>().ok(),
-				"DISCNUMBER" => tags.disc_number = value.parse::<u32>().ok(),
-				"DATE" => tags.year = value.parse::<i32>().ok(),
-				"LYRICIST" => tags.lyricist = Some(value),
-				"COMPOSER" => tags.composer = Some(value),
-				"GENRE" => tags.genre = Some(value),
-				"PUBLISHER" => tags.label = Some(value),
-				_ => (),
+struct OggStream {
+	serial: u32,
+	sample_rate: Option<u32>,
+	pre_skip: u64,
+	last_granule_position: Option<u64>,
+}
+
+/// Adds up the durations of all logical streams in an Ogg file, based on the granule position
+/// of their last page. Reading stops at the first malformed page, so a damaged chain only
+/// contributes the pages preceding the damage.
+fn read_ogg_duration(path: &Path) -> Option<u32> {
+	const BEGINNING_OF_STREAM: u8 = 0x02;
+
+	let mut reader = std::io::BufReader::new(fs::File::open(path).ok()?);
+	let mut streams: Vec<OggStream> = Vec::new();
+
+	loop {
+		let mut header = [0u8; 27];
+		if reader.read_exact(&mut header).is_err() || &header[0..4] != b"OggS" {
+			break;
+		}
+		let header_type = header[5];
+		let granule_position = i64::from_le_bytes(header[6..14].try_into().ok()?);
+		let serial = u32::from_le_bytes(header[14..18].try_into().ok()?);
+		let mut segment_table = vec![0u8; header[26] as usize];
+		if reader.read_exact(&mut segment_table).is_err() {
+			break;
+		}
+		let mut payload = vec![0u8; segment_table.iter().map(|s| *s as usize).sum()];
+		if reader.read_exact(&mut payload).is_err() {
+			break;
+		}
+
+		if header_type & BEGINNING_OF_STREAM != 0 {
+			let (sample_rate, pre_skip) =
+				if payload.starts_with(b"\x01vorbis") && payload.len() >= 16 {
+					let sample_rate = u32::from_le_bytes(payload[12..16].try_into().ok()?);
+					(Some(sample_rate), 0)
+				} else if payload.starts_with(b"OpusHead") && payload.len() >= 12 {
+					let pre_skip = u16::from_le_bytes(payload[10..12].try_into().ok()?);
+					(Some(48_000), pre_skip as u64)
+				} else {
+					(None, 0)
+				};
+			streams.push(OggStream {
+				serial,
+				sample_rate,
+				pre_skip,
+				last_granule_position: None,
+			});
+		}
+
+		// A granule position of -1 means no packet ends on this page
+		if granule_position >= 0 {
+			if let Some(stream) = streams.iter_mut().rev().find(|s| s.serial == serial) {
+				stream.last_granule_position = Some(granule_position as u64);
 			}
 		}
 	}
 
-	Ok(tags)
+	let durations: Vec<f64> = streams
+		.iter()
+		.filter_map(|s| match (s.sample_rate, s.last_granule_position) {
+			(Some(rate), Some(granule)) if rate > 0 => {
+				Some(granule.saturating_sub(s.pre_skip) as f64 / rate as f64)
+			}
+			_ => None,
+		})
+		.collect();
+	if durations.is_empty() {
+		return None;
+	}
+	Some(durations.iter().sum::<f64>() as u32)
 }
 
-fn read_opus(path: &Path) -> Result<SongTags, Error> {
-	let headers = opus_headers::parse_from_path(path)?;
+/// Comment keys are matched regardless of case, spaces, underscores and dashes, so that variants
+/// such as `ALBUM ARTIST` or `album_artist` are recognized.
+fn normalize_comment_key(key: &str) -> String {
+	key.chars()
+		.filter(|c| !matches!(c, ' ' | '_' | '-'))
+		.collect::<String>()
+		.to_uppercase()
+}
 
-	let mut tags = SongTags {
-		artist: None,
-		album_artist: None,
-		album: None,
-		title: None,
-		duration: None,
-		disc_number: None,
-		track_number: None,
-		year: None,
-		has_artwork: false,
-		lyricist: None,
-		composer: None,
-		genre: None,
-		label: None,
-	};
+fn read_vorbis_comments<I>(comments: I) -> SongTags
+where
+	I: IntoIterator<Item = (String, String)>,
+{
+	let mut tags = SongTags::default();
 
-	for (key, value) in headers.comments.user_comments {
+	for (key, value) in comments {
+		let normalized_key = normalize_comment_key(&key);
 		utils::match_ignore_case! {
-			match key {
+			match normalized_key {
 				"TITLE" => tags.title = Some(value),
 				"ALBUM" => tags.album = Some(value),
 				"ARTIST" => tags.artist = Some(value),
 				"ALBUMARTIST" => tags.album_artist = Some(value),
-				"TRACKNUMBER" => tags.track_number = value.parse::<u32>().ok(),
-				"DISCNUMBER" => tags.disc_number = value.parse::<u32>().ok(),
+				"TRACKNUMBER" => {
+					tags.track_number = parse_x_of_y(&value);
+					tags.track_total = tags.track_total.or(parse_total_of_x_of_y(&value));
+				},
+				"DISCNUMBER" => {
+					tags.disc_number = parse_x_of_y(&value);
+					tags.disc_total = tags.disc_total.or(parse_total_of_x_of_y(&value));
+				},
+				"TRACKTOTAL" => tags.track_total = value.parse::<u32>().ok().or(tags.track_total),
+				"TOTALTRACKS" => tags.track_total = value.parse::<u32>().ok().or(tags.track_total),
+				"DISCTOTAL" => tags.disc_total = value.parse::<u32>().ok().or(tags.disc_total),
+				"TOTALDISCS" => tags.disc_total = value.parse::<u32>().ok().or(tags.disc_total),
 				"DATE" => tags.year = value.parse::<i32>().ok(),
-				"LYRICIST" => tags.lyricist = Some(value),
-				"COMPOSER" => tags.composer = Some(value),
+				"ORIGINALDATE" => tags.original_year = parse_year(&value).or(tags.original_year),
+				"ORIGINALYEAR" => tags.original_year = tags.original_year.or(parse_year(&value)),
+				"LYRICIST" => tags.lyricists.push(value),
+				"COMPOSER" => tags.composers.push(value),
+				"CONDUCTOR" => tags.conductors.push(value),
+				"REMIXER" => tags.remixers.push(value),
+				"ARRANGER" => tags.arrangers.push(value),
+				"PERFORMER" => tags.performers.push(value),
 				"GENRE" => tags.genre = Some(value),
 				"PUBLISHER" => tags.label = Some(value),
-				_ => (),
+				"GROUPING" => tags.grouping = Some(value),
+				"WORK" => tags.work = Some(value),
+				"MOVEMENTNAME" => tags.movement_name = Some(value),
+				"MOVEMENT" => tags.movement_number = parse_x_of_y(&value),
+				"ISRC" => tags.isrc = Some(value),
+				"CATALOGNUMBER" => tags.catalog_number = Some(value),
+				"ENCODEDBY" => tags.encoded_by = Some(value),
+				"ENCODER" => tags.encoder_settings = Some(value),
+				"ORIGINALARTIST" => tags.original_artists.push(value),
+				"ORIGINALALBUM" => tags.original_album = Some(value),
+				"LANGUAGE" => tags.languages.push(normalize_language(&value)),
+				"MEDIA" => tags.media_type = Some(value),
+				"DISCSUBTITLE" => tags.disc_subtitle = Some(value),
+				"INITIALKEY" => tags.initial_key = Some(value),
+				"KEY" => tags.initial_key = tags.initial_key.or(Some(value)),
+				"SOURCEMEDIA" => tags.media_type = tags.media_type.or(Some(value)),
+				"RATING" => tags.rating = parse_rating(&value).or(tags.rating),
+				"FMPSRATING" => tags.rating = tags.rating.or(parse_fmps_rating(&value)),
+				"POLARISHIDDEN" => tags.hidden |= is_flag_set(&value),
+				"HIDDEN" => tags.hidden |= is_flag_set(&value),
+				_ => tags.extra.entry(key).or_default().push(value),
 			}
 		}
 	}
 
-	Ok(tags)
+	tags
 }
 
 fn read_flac(path: &Path) -> Result<SongTags, Error> {
-	let tag = metaflac::Tag::read_from_path(path)?;
+	// A broken picture only leaves the song without artwork
+	let tag = match metaflac::Tag::read_from_path(path) {
+		Ok(tag) => tag,
+		Err(e) => read_flac_without_pictures(path).ok_or(e)?,
+	};
 	let vorbis = tag
 		.vorbis_comments()
 		.ok_or(Error::VorbisCommentNotFoundInFlacFile)?;
-	let disc_number = vorbis
-		.get("DISCNUMBER")
-		.and_then(|d| d[0].parse::<u32>().ok());
-	let year = vorbis.get("DATE").and_then(|d| d[0].parse::<i32>().ok());
 	let mut streaminfo = tag.get_blocks(metaflac::BlockType::StreamInfo);
 	let duration = match streaminfo.next() {
-		Some(metaflac::Block::StreamInfo(s)) => Some(s.total_samples as u32 / s.sample_rate),
+		Some(metaflac::Block::StreamInfo(s)) if s.sample_rate > 0 && s.total_samples > 0 => {
+			Some((s.total_samples / s.sample_rate as u64) as u32)
+		}
 		_ => None,
 	};
-	let has_artwork = tag.pictures().count() > 0;
+	let artwork_mime = tag.pictures().next().map(|p| p.mime_type.clone());
+	let artwork_size = tag.pictures().next().map(|p| p.data.len() as u64);
+	let has_artwork = artwork_mime.is_some();
+	let comments = read_vorbis_comments(
+		vorbis
+			.comments
+			.iter()
+			.flat_map(|(k, values)| values.iter().map(move |v| (k.clone(), v.clone()))),
+	);
 
 	Ok(SongTags {
-		artist: vorbis.artist().map(|v| v[0].clone()),
-		album_artist: vorbis.album_artist().map(|v| v[0].clone()),
-		album: vorbis.album().map(|v| v[0].clone()),
-		title: vorbis.title().map(|v| v[0].clone()),
 		duration,
-		disc_number,
-		track_number: vorbis.track(),
-		year,
 		has_artwork,
-		lyricist: vorbis.get("LYRICIST").map(|v| v[0].clone()),
-		composer: vorbis.get("COMPOSER").map(|v| v[0].clone()),
-		genre: vorbis.get("GENRE").map(|v| v[0].clone()),
-		label: vorbis.get("PUBLISHER").map(|v| v[0].clone()),
+		artwork_mime,
+		artwork_size,
+		..comments
 	})
 }
 
-fn read_mp4(path: &Path) -> Result<SongTags, Error> {
-	let mut tag = mp4ameta::Tag::read_from_path(path)?;
-	let label_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "Label");
+/// Reads the metadata blocks of a FLAC file, leaving out its pictures.
+fn read_flac_without_pictures(path: &Path) -> Option<metaflac::Tag> {
+	const PICTURE_BLOCK_TYPE: u8 = 6;
+	let mut file = fs::File::open(path).ok()?;
+	let mut marker = [0u8; 4];
+	file.read_exact(&mut marker).ok()?;
+	if marker != *b"fLaC" {
+		return None;
+	}
 
-	Ok(SongTags {
-		artist: tag.take_artist(),
-		album_artist: tag.take_album_artist(),
-		album: tag.take_album(),
-		title: tag.take_title(),
-		duration: tag.duration().map(|v| v.as_secs() as u32),
-		disc_number: tag.disc_number().map(|d| d as u32),
-		track_number: tag.track_number().map(|d| d as u32),
-		year: tag.year().and_then(|v| v.parse::<i32>().ok()),
-		has_artwork: tag.artwork().is_some(),
-		lyricist: tag.take_lyricist(),
-		composer: tag.take_composer(),
-		genre: tag.take_genre(),
-		label: tag.take_strings_of(&label_ident).next(),
-	})
+	let mut blocks = Vec::new();
+	loop {
+		let mut header = [0u8; 4];
+		file.read_exact(&mut header).ok()?;
+		let is_last = header[0] & 0x80 != 0;
+		let block_type = header[0] & 0x7F;
+		let length = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+		let mut data = vec![0u8; length as usize];
+		file.read_exact(&mut data).ok()?;
+		if block_type != PICTURE_BLOCK_TYPE {
+			blocks.push((block_type, data));
+		}
+		if is_last {
+			break;
+		}
+	}
+
+	let mut bytes = marker.to_vec();
+	let num_blocks = blocks.len();
+	for (index, (block_type, data)) in blocks.into_iter().enumerate() {
+		let last_flag = if index + 1 == num_blocks { 0x80 } else { 0 };
+		bytes.push(block_type | last_flag);
+		bytes.extend(&(data.len() as u32).to_be_bytes()[1..]);
+		bytes.extend(data);
+	}
+	metaflac::Tag::read_from(&mut Cursor::new(bytes)).ok()
 }
 
-#[test]
-fn reads_file_metadata() {
-	let sample_tags = SongTags {
-		disc_number: Some(3),
-		track_number: Some(1),
+/// Reads the metadata of an MP4 file, leaving out its artwork. Only the `ftyp` and `moov` atoms are
+/// kept, with `covr` atoms removed from the item list.
+fn read_mp4_without_artwork(path: &Path) -> Option<mp4ameta::Tag> {
+	let mut file = fs::File::open(path).ok()?;
+	let file_size = file.metadata().ok()?.len();
+	let mut bytes = Vec::new();
+	let mut position = 0;
+	while position + 8 <= file_size {
+		let mut header = [0u8; 8];
+		file.read_exact(&mut header).ok()?;
+		let mut header_length = 8;
+		let size = match u32::from_be_bytes(header[0..4].try_into().ok()?) {
+			0 => file_size - position,
+			1 => {
+				let mut large_size = [0u8; 8];
+				file.read_exact(&mut large_size).ok()?;
+				header_length = 16;
+				u64::from_be_bytes(large_size)
+			}
+			size => size as u64,
+		};
+		if size < header_length || position + size > file_size {
+			return None;
+		}
+		match &header[4..8] {
+			b"ftyp" | b"moov" if size <= u32::MAX as u64 => {
+				let mut content = vec![0u8; (size - header_length) as usize];
+				file.read_exact(&mut content).ok()?;
+				if &header[4..8] == b"moov" {
+					content = strip_mp4_artwork(&content, &[b"udta", b"meta", b"ilst"])?;
+				}
+				bytes.extend(((content.len() + 8) as u32).to_be_bytes());
+				bytes.extend(&header[4..8]);
+				bytes.extend(content);
+			}
+			_ => {
+				file.seek(SeekFrom::Start(position + size)).ok()?;
+			}
+		}
+		position += size;
+	}
+	mp4ameta::Tag::read_from(&mut Cursor::new(bytes)).ok()
+}
+
+/// Copies the children of an MP4 atom, leaving out `covr` atoms found within the atoms along `path`.
+fn strip_mp4_artwork(content: &[u8], path: &[&[u8; 4]]) -> Option<Vec<u8>> {
+	let mut output = Vec::with_capacity(content.len());
+	let mut offset = 0;
+	while offset + 8 <= content.len() {
+		let size = u32::from_be_bytes(content[offset..offset + 4].try_into().ok()?) as usize;
+		if size < 8 || offset + size > content.len() {
+			return None;
+		}
+		let atom = &content[offset..offset + size];
+		let kind = &atom[4..8];
+		match path.split_first() {
+			Some((next, rest)) if kind == next.as_slice() => {
+				// `meta` is a full atom, whose version and flags precede its children
+				let header_length = if kind == b"meta" { 12 } else { 8 };
+				let children = strip_mp4_artwork(atom.get(header_length..)?, rest)?;
+				output.extend(((header_length + children.len()) as u32).to_be_bytes());
+				output.extend(&atom[4..header_length]);
+				output.extend(children);
+			}
+			None if kind == b"covr" => (),
+			_ => output.extend(atom),
+		}
+		offset += size;
+	}
+	Some(output)
+}
+
+fn read_mp4(path: &Path) -> Result<SongTags, Error> {
+	// A broken picture only leaves the song without artwork
+	let mut tag = match mp4ameta::Tag::read_from_path(path) {
+		Ok(tag) => tag,
+		Err(e) => read_mp4_without_artwork(path).ok_or(e)?,
+	};
+	let label_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "Label");
+	let isrc_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "ISRC");
+	let catalog_number_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "CATALOGNUMBER");
+	let conductor_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "CONDUCTOR");
+	let remixer_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "REMIXER");
+	let arranger_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "ARRANGER");
+	let performer_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "PERFORMER");
+	let original_artist_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "ORIGINALARTIST");
+	let original_album_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "ORIGINALALBUM");
+	let language_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "LANGUAGE");
+	let media_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "MEDIA");
+	let disc_subtitle_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "DISCSUBTITLE");
+	let rating_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "RATING");
+	let original_year = ["ORIGINALDATE", "ORIGINALYEAR"].iter().find_map(|k| {
+		let ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", k);
+		let year = tag.strings_of(&ident).find_map(parse_year);
+		year
+	});
+	let initial_key = ["initialkey", "INITIALKEY", "KEY"].iter().find_map(|k| {
+		let ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", k);
+		let key = tag.strings_of(&ident).next().map(str::to_owned);
+		key
+	});
+	let hidden = HIDDEN_TAG_KEYS.iter().any(|k| {
+		let ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", k);
+		let is_hidden = tag.strings_of(&ident).any(is_flag_set);
+		is_hidden
+	});
+	let rating = tag.strings_of(&rating_ident).find_map(parse_rating);
+	let content_type = match tag.media_type() {
+		Some(mp4ameta::MediaType::AudioBook) => Some(ContentType::Audiobook),
+		_ => None,
+	};
+	let mut extra: HashMap<String, Vec<String>> = HashMap::new();
+	for (ident, data) in tag.data() {
+		if let mp4ameta::DataIdent::Freeform { name, .. } = ident {
+			if MP4_FREEFORM_KEYS
+				.iter()
+				.any(|k| k.eq_ignore_ascii_case(name))
+			{
+				continue;
+			}
+			if let Some(value) = data.string() {
+				extra
+					.entry(name.clone())
+					.or_default()
+					.push(value.to_owned());
+			}
+		}
+	}
+
+	Ok(SongTags {
+		artist: tag.take_artist(),
+		album_artist: tag.take_album_artist(),
+		album: tag.take_album(),
+		title: tag.take_title(),
+		duration: tag.duration().map(|v| v.as_secs() as u32),
+		duration_source: None,
+		disc_number: tag.disc_number().map(|d| d as u32),
+		track_number: tag.track_number().map(|d| d as u32),
+		disc_total: tag.total_discs().filter(|d| *d > 0).map(|d| d as u32),
+		track_total: tag.total_tracks().filter(|d| *d > 0).map(|d| d as u32),
+		disc_subtitle: tag.take_strings_of(&disc_subtitle_ident).next(),
+		year: tag.year().and_then(|v| v.parse::<i32>().ok()),
+		original_year,
+		has_artwork: tag.artwork().is_some(),
+		artwork_mime: tag.artwork().map(|a| {
+			match a.fmt {
+				mp4ameta::ImgFmt::Bmp => "image/bmp",
+				mp4ameta::ImgFmt::Jpeg => "image/jpeg",
+				mp4ameta::ImgFmt::Png => "image/png",
+			}
+			.to_owned()
+		}),
+		artwork_size: tag.artwork().map(|a| a.data.len() as u64),
+		lyricists: tag.take_lyricists().collect(),
+		composers: tag.take_composers().collect(),
+		conductors: tag.take_strings_of(&conductor_ident).collect(),
+		remixers: tag.take_strings_of(&remixer_ident).collect(),
+		arrangers: tag.take_strings_of(&arranger_ident).collect(),
+		performers: tag.take_strings_of(&performer_ident).collect(),
+		genre: tag.take_genre(),
+		label: tag.take_strings_of(&label_ident).next(),
+		hidden,
+		grouping: tag.take_grouping(),
+		work: tag.take_work(),
+		movement_name: tag.take_movement(),
+		movement_number: tag.movement_index().map(|n| n as u32),
+		isrc: tag.take_strings_of(&isrc_ident).next(),
+		catalog_number: tag.take_strings_of(&catalog_number_ident).next(),
+		encoded_by: None,
+		encoder_settings: tag.take_encoder(),
+		original_artists: tag.take_strings_of(&original_artist_ident).collect(),
+		original_album: tag.take_strings_of(&original_album_ident).next(),
+		languages: normalize_languages(tag.take_strings_of(&language_ident).collect()),
+		media_type: tag.take_strings_of(&media_ident).next(),
+		initial_key,
+		rating,
+		content_type,
+		extra,
+	})
+}
+
+const ASF_HEADER_GUID: [u8; 16] = [
+	0x30, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11, 0xA6, 0xD9, 0x00, 0xAA, 0x00, 0x62, 0xCE, 0x6C,
+];
+const ASF_FILE_PROPERTIES_GUID: [u8; 16] = [
+	0xA1, 0xDC, 0xAB, 0x8C, 0x47, 0xA9, 0xCF, 0x11, 0x8E, 0xE4, 0x00, 0xC0, 0x0C, 0x20, 0x53, 0x65,
+];
+const ASF_CONTENT_DESCRIPTION_GUID: [u8; 16] = [
+	0x33, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11, 0xA6, 0xD9, 0x00, 0xAA, 0x00, 0x62, 0xCE, 0x6C,
+];
+const ASF_EXTENDED_CONTENT_DESCRIPTION_GUID: [u8; 16] = [
+	0x40, 0xA4, 0xD0, 0xD2, 0x07, 0xE3, 0xD2, 0x11, 0x97, 0xF0, 0x00, 0xA0, 0xC9, 0x5E, 0xA8, 0x50,
+];
+
+/// Value of an attribute stored in the header of an ASF file.
+enum AsfValue {
+	Text(String),
+	Binary(usize),
+	Bool(bool),
+	Number(u64),
+}
+
+impl AsfValue {
+	fn text(&self) -> Option<String> {
+		match self {
+			AsfValue::Text(s) => Some(s.clone()),
+			AsfValue::Binary(_) => None,
+			AsfValue::Bool(b) => Some(b.to_string()),
+			AsfValue::Number(n) => Some(n.to_string()),
+		}
+	}
+}
+
+impl std::fmt::Display for AsfValue {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			AsfValue::Binary(length) => write!(f, "<{} bytes>", length),
+			value => write!(f, "{}", value.text().unwrap_or_default()),
+		}
+	}
+}
+
+/// Metadata found in the header object of an ASF file. Fields of the Content Description object
+/// are listed as attributes named after them (`Title`, `Author`, etc.), ahead of the attributes
+/// of the Extended Content Description object.
+#[derive(Default)]
+struct AsfHeader {
+	duration: Option<u32>,
+	attributes: Vec<(String, AsfValue)>,
+}
+
+fn read_asf_header(path: &Path) -> Result<AsfHeader, Error> {
+	let io_error = |e| Error::Io(path.to_owned(), e);
+	let mut file = fs::File::open(path).map_err(io_error)?;
+	let file_length = file.metadata().map_err(io_error)?.len();
+
+	let mut header = [0u8; 30];
+	file.read_exact(&mut header).map_err(io_error)?;
+	if header[0..16] != ASF_HEADER_GUID {
+		return Err(Error::AsfHeaderNotFound);
+	}
+	let header_size = u64::from_le_bytes(header[16..24].try_into().unwrap());
+	let mut objects = vec![0u8; header_size.min(file_length).saturating_sub(30) as usize];
+	file.read_exact(&mut objects).map_err(io_error)?;
+
+	let mut asf_header = AsfHeader::default();
+	let mut offset = 0;
+	while let Some(object_header) = objects.get(offset..offset + 24) {
+		let object_size = u64::from_le_bytes(object_header[16..24].try_into().unwrap()) as usize;
+		let data = match objects.get(offset + 24..offset.saturating_add(object_size)) {
+			Some(data) if object_size >= 24 => data,
+			_ => break,
+		};
+		let guid = &object_header[0..16];
+		if guid == ASF_FILE_PROPERTIES_GUID {
+			asf_header.duration = read_asf_duration(data);
+		} else if guid == ASF_CONTENT_DESCRIPTION_GUID {
+			read_asf_content_description(data, &mut asf_header.attributes);
+		} else if guid == ASF_EXTENDED_CONTENT_DESCRIPTION_GUID {
+			read_asf_extended_content_description(data, &mut asf_header.attributes);
+		}
+		offset += object_size;
+	}
+
+	Ok(asf_header)
+}
+
+/// The play duration of an ASF file is expressed in 100-nanosecond units and includes the
+/// preroll, in milliseconds, which precedes the first audible sample.
+fn read_asf_duration(data: &[u8]) -> Option<u32> {
+	const BROADCAST: u32 = 0x01;
+	let u64_at = |i: usize| Some(u64::from_le_bytes(data.get(i..i + 8)?.try_into().ok()?));
+	let flags = u32::from_le_bytes(data.get(64..68)?.try_into().ok()?);
+	if flags & BROADCAST != 0 {
+		return None;
+	}
+	let play_duration_ms = u64_at(40)? / 10_000;
+	let preroll_ms = u64_at(56)?;
+	Some((play_duration_ms.saturating_sub(preroll_ms) / 1000) as u32)
+}
+
+fn read_utf16(bytes: &[u8]) -> String {
+	let units: Vec<u16> = bytes
+		.chunks_exact(2)
+		.map(|c| u16::from_le_bytes([c[0], c[1]]))
+		.collect();
+	String::from_utf16_lossy(&units)
+		.trim_end_matches('\0')
+		.to_owned()
+}
+
+fn read_asf_content_description(data: &[u8], attributes: &mut Vec<(String, AsfValue)>) {
+	const FIELDS: [&str; 5] = ["Title", "Author", "Copyright", "Description", "Rating"];
+	let mut offset = 2 * FIELDS.len();
+	for (index, name) in FIELDS.iter().enumerate() {
+		let length = match data.get(2 * index..2 * index + 2) {
+			Some(l) => u16::from_le_bytes([l[0], l[1]]) as usize,
+			None => return,
+		};
+		let value = match data.get(offset..offset + length) {
+			Some(v) => read_utf16(v),
+			None => return,
+		};
+		offset += length;
+		if !value.is_empty() {
+			attributes.push((name.to_string(), AsfValue::Text(value)));
+		}
+	}
+}
+
+fn read_asf_extended_content_description(data: &[u8], attributes: &mut Vec<(String, AsfValue)>) {
+	let u16_at = |i: usize| Some(u16::from_le_bytes(data.get(i..i + 2)?.try_into().ok()?) as usize);
+	let read_attribute = |offset: usize| -> Option<(String, AsfValue, usize)> {
+		let name_length = u16_at(offset)?;
+		let name = read_utf16(data.get(offset + 2..offset + 2 + name_length)?);
+		let value_type = u16_at(offset + 2 + name_length)?;
+		let value_length = u16_at(offset + 4 + name_length)?;
+		let value_start = offset + 6 + name_length;
+		let value = data.get(value_start..value_start + value_length)?;
+		let number = |n: usize| -> Option<u64> {
+			let mut bytes = [0u8; 8];
+			bytes[..n].copy_from_slice(value.get(..n)?);
+			Some(u64::from_le_bytes(bytes))
+		};
+		let value = match value_type {
+			0 => AsfValue::Text(read_utf16(value)),
+			1 => AsfValue::Binary(value.len()),
+			2 => AsfValue::Bool(number(4)? != 0),
+			3 => AsfValue::Number(number(4)?),
+			4 => AsfValue::Number(number(8)?),
+			5 => AsfValue::Number(number(2)?),
+			_ => return None,
+		};
+		Some((name, value, value_start + value_length))
+	};
+
+	let count = match u16_at(0) {
+		Some(c) => c,
+		None => return,
+	};
+	let mut offset = 2;
+	for _ in 0..count {
+		match read_attribute(offset) {
+			Some((name, value, next_offset)) => {
+				attributes.push((name, value));
+				offset = next_offset;
+			}
+			None => return,
+		}
+	}
+}
+
+/// Shared user ratings are stored as 1, 25, 50, 75 or 99 for one to five stars.
+fn shared_user_rating_to_rating(value: u64) -> Option<u8> {
+	match value {
+		0 => None,
+		1..=12 => Some(20),
+		13..=37 => Some(40),
+		38..=62 => Some(60),
+		63..=86 => Some(80),
+		_ => Some(100),
+	}
+}
+
+fn read_wma(path: &Path) -> Result<SongTags, Error> {
+	let header = read_asf_header(path)?;
+	let mut tags = SongTags {
+		duration: header.duration,
+		..Default::default()
+	};
+
+	for (name, value) in header.attributes {
+		if name == "WM/SharedUserRating" {
+			if let AsfValue::Number(n) = value {
+				tags.rating = shared_user_rating_to_rating(n);
+			}
+			continue;
+		}
+		let value = match value.text() {
+			Some(v) => v,
+			None => continue,
+		};
+		match name.as_str() {
+			"Title" => tags.title = Some(value),
+			"Author" => tags.artist = Some(value),
+			"WM/AlbumTitle" => tags.album = Some(value),
+			"WM/AlbumArtist" => tags.album_artist = Some(value),
+			"WM/TrackNumber" => {
+				tags.track_number = parse_x_of_y(&value);
+				tags.track_total = tags.track_total.or(parse_total_of_x_of_y(&value));
+			}
+			// Zero-based track numbers written by older encoders
+			"WM/Track" => {
+				let track_number = value.parse::<u32>().ok().map(|n| n + 1);
+				tags.track_number = tags.track_number.or(track_number);
+			}
+			"WM/PartOfSet" => {
+				tags.disc_number = parse_x_of_y(&value);
+				tags.disc_total = tags.disc_total.or(parse_total_of_x_of_y(&value));
+			}
+			"WM/Year" => tags.year = parse_year(&value),
+			"WM/OriginalReleaseYear" => tags.original_year = parse_year(&value),
+			"WM/Writer" => tags.lyricists.push(value),
+			"WM/Composer" => tags.composers.push(value),
+			"WM/Conductor" => tags.conductors.push(value),
+			"WM/ModifiedBy" => tags.remixers.push(value),
+			"WM/Genre" => tags.genre = Some(value),
+			"WM/Publisher" => tags.label = Some(value),
+			"WM/ContentGroupDescription" => tags.grouping = Some(value),
+			"WM/ISRC" => tags.isrc = Some(value),
+			"WM/CatalogNo" => tags.catalog_number = Some(value),
+			"WM/EncodedBy" => tags.encoded_by = Some(value),
+			"WM/EncodingSettings" => tags.encoder_settings = Some(value),
+			"WM/OriginalArtist" => tags.original_artists.push(value),
+			"WM/OriginalAlbumTitle" => tags.original_album = Some(value),
+			"WM/Language" => tags.languages.push(normalize_language(&value)),
+			"WM/Media" => tags.media_type = Some(value),
+			"WM/SetSubTitle" => tags.disc_subtitle = Some(value),
+			"WM/InitialKey" => tags.initial_key = Some(value),
+			name if HIDDEN_TAG_KEYS.contains(&name) => tags.hidden |= is_flag_set(&value),
+			_ => (),
+		}
+	}
+
+	Ok(tags)
+}
+
+#[test]
+fn reads_file_metadata() {
+	let sample_tags = SongTags {
+		disc_number: Some(3),
+		track_number: Some(1),
+		disc_total: Some(4),
+		track_total: Some(2),
+		disc_subtitle: None,
 		title: Some("TEST TITLE".into()),
 		artist: Some("TEST ARTIST".into()),
 		album_artist: Some("TEST ALBUM ARTIST".into()),
 		album: Some("TEST ALBUM".into()),
 		duration: None,
+		duration_source: None,
 		year: Some(2016),
+		original_year: None,
 		has_artwork: false,
-		lyricist: Some("TEST LYRICIST".into()),
-		composer: Some("TEST COMPOSER".into()),
+		artwork_mime: None,
+		artwork_size: None,
+		lyricists: vec!["TEST LYRICIST".into()],
+		composers: vec!["TEST COMPOSER".into()],
+		conductors: Vec::new(),
+		remixers: Vec::new(),
+		arrangers: Vec::new(),
+		performers: Vec::new(),
 		genre: Some("TEST GENRE".into()),
 		label: Some("TEST LABEL".into()),
+		hidden: false,
+		grouping: None,
+		work: None,
+		movement_name: None,
+		movement_number: None,
+		isrc: None,
+		catalog_number: None,
+		encoded_by: None,
+		encoder_settings: None,
+		original_artists: Vec::new(),
+		original_album: None,
+		languages: Vec::new(),
+		media_type: None,
+		initial_key: None,
+		rating: None,
+		content_type: None,
+		extra: HashMap::new(),
+	};
+	let extra = |fields: &[(&str, &str)]| -> HashMap<String, Vec<String>> {
+		fields
+			.iter()
+			.map(|(k, v)| (k.to_string(), vec![v.to_string()]))
+			.collect()
+	};
+	let id3_sample_tag = SongTags {
+		extra: extra(&[("Category", "TEST CATEGORY")]),
+		..sample_tags.clone()
 	};
 	let flac_sample_tag = SongTags {
 		duration: Some(0),
+		duration_source: Some(DurationSource::Stream),
+		extra: extra(&[("CATEGORY", "TEST CATEGORY")]),
 		..sample_tags.clone()
 	};
 	let mp3_sample_tag = SongTags {
 		duration: Some(0),
-		..sample_tags.clone()
+		duration_source: Some(DurationSource::Stream),
+		..id3_sample_tag.clone()
 	};
 	let m4a_sample_tag = SongTags {
 		duration: Some(0),
+		duration_source: Some(DurationSource::Stream),
+		disc_total: None,
+		track_total: None,
+		encoder_settings: Some("Lavf58.29.100".into()),
+		..sample_tags.clone()
+	};
+	let ogg_sample_tag = SongTags {
+		duration: Some(0),
+		..flac_sample_tag.clone()
+	};
+	let opus_sample_tag = SongTags {
+		duration: Some(30),
+		duration_source: Some(DurationSource::Stream),
+		disc_total: None,
+		track_total: None,
+		extra: extra(&[
+			("CATEGORY", "TEST CATEGORY"),
+			("COMMENT", "some random comment"),
+			("DESCRIPTION", "silent"),
+		]),
+		encoder_settings: Some("Lavc58.54.100 libopus".into()),
+		..sample_tags.clone()
+	};
+	let wma_sample_tag = SongTags {
+		duration: Some(0),
+		duration_source: Some(DurationSource::Stream),
 		..sample_tags.clone()
 	};
 	assert_eq!(
 		read(Path::new("test-data/formats/sample.aif")).unwrap(),
-		sample_tags
+		id3_sample_tag
 	);
 	assert_eq!(
 		read(Path::new("test-data/formats/sample.mp3")).unwrap(),
@@ -397,7 +2002,7 @@ fn reads_file_metadata() {
 	);
 	assert_eq!(
 		read(Path::new("test-data/formats/sample.ogg")).unwrap(),
-		sample_tags
+		ogg_sample_tag
 	);
 	assert_eq!(
 		read(Path::new("test-data/formats/sample.flac")).unwrap(),
@@ -409,7 +2014,7 @@ fn reads_file_metadata() {
 	);
 	assert_eq!(
 		read(Path::new("test-data/formats/sample.opus")).unwrap(),
-		sample_tags
+		opus_sample_tag
 	);
 	assert_eq!(
 		read(Path::new("test-data/formats/sample.ape")).unwrap(),
@@ -417,7 +2022,11 @@ fn reads_file_metadata() {
 	);
 	assert_eq!(
 		read(Path::new("test-data/formats/sample.wav")).unwrap(),
-		sample_tags
+		id3_sample_tag
+	);
+	assert_eq!(
+		read(Path::new("test-data/formats/sample.wma")).unwrap(),
+		wma_sample_tag
 	);
 }
 
@@ -449,3 +2058,832 @@ fn reads_embedded_artwork() {
 			.has_artwork
 	);
 }
+
+#[test]
+fn reads_hidden_flag_from_id3_tag() {
+	let mut tag = id3::Tag::new();
+	tag.add_frame(id3::frame::ExtendedText {
+		description: "POLARIS_HIDDEN".into(),
+		value: "1".into(),
+	});
+	let song_tags: SongTags = tag.into();
+	assert!(song_tags.hidden);
+
+	let mut tag = id3::Tag::new();
+	tag.add_frame(id3::frame::ExtendedText {
+		description: "POLARIS_HIDDEN".into(),
+		value: "0".into(),
+	});
+	let song_tags: SongTags = tag.into();
+	assert!(!song_tags.hidden);
+}
+
+#[test]
+fn reads_hidden_flag_from_vorbis_comments() {
+	let comments = vec![
+		("TITLE".to_owned(), "Bonus".to_owned()),
+		("hidden".to_owned(), "true".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.title, Some("Bonus".to_owned()));
+	assert!(song_tags.hidden);
+
+	let song_tags = read_vorbis_comments(vec![("TITLE".to_owned(), "Bonus".to_owned())]);
+	assert!(!song_tags.hidden);
+}
+
+#[test]
+fn reads_every_composer_and_lyricist() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+	let composers = vec![
+		"TEST COMPOSER 1".to_owned(),
+		"TEST COMPOSER 2".to_owned(),
+		"TEST COMPOSER 3".to_owned(),
+	];
+	let lyricists = vec!["TEST LYRICIST 1".to_owned(), "TEST LYRICIST 2".to_owned()];
+
+	let mut id3_tag = id3::Tag::new();
+	id3_tag.set_text_values("TCOM", composers.clone());
+	id3_tag.set_text_values("TEXT", lyricists.clone());
+	let id3_tags: SongTags = id3_tag.into();
+
+	let mut comments = Vec::new();
+	comments.extend(composers.iter().map(|c| ("COMPOSER".to_owned(), c.clone())));
+	comments.extend(lyricists.iter().map(|l| ("LYRICIST".to_owned(), l.clone())));
+	let vorbis_tags = read_vorbis_comments(comments);
+
+	let ape_path = test_directory.join("sample.ape");
+	fs::copy("test-data/formats/sample.ape", &ape_path).unwrap();
+	let mut ape_tag = ape::read_from_path(&ape_path).unwrap();
+	ape_tag.set_item(ape::Item::from_text("COMPOSER", composers.join("\0")).unwrap());
+	ape_tag.set_item(ape::Item::from_text("LYRICIST", lyricists.join("\0")).unwrap());
+	ape::write_to_path(&ape_tag, &ape_path).unwrap();
+	let ape_tags = read(&ape_path).unwrap();
+
+	let mp4_path = test_directory.join("sample.m4a");
+	fs::copy("test-data/formats/sample.m4a", &mp4_path).unwrap();
+	let mut mp4_tag = mp4ameta::Tag::read_from_path(&mp4_path).unwrap();
+	mp4_tag.set_composers(composers.clone());
+	mp4_tag.set_lyricists(lyricists.clone());
+	mp4_tag.write_to_path(&mp4_path).unwrap();
+	let mp4_tags = read(&mp4_path).unwrap();
+
+	for tags in [id3_tags, vorbis_tags, ape_tags, mp4_tags] {
+		assert_eq!(tags.composers, composers);
+		assert_eq!(tags.lyricists, lyricists);
+	}
+}
+
+#[test]
+fn reads_arrangers_and_performers() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+	let arrangers = vec!["TEST ARRANGER".to_owned()];
+	let performers = vec!["TEST PERFORMER 1".to_owned(), "TEST PERFORMER 2".to_owned()];
+
+	let credit = |involvement: &str, involvee: &str| id3::frame::InvolvedPeopleListItem {
+		involvement: involvement.to_owned(),
+		involvee: involvee.to_owned(),
+	};
+	let mut id3_tag = id3::Tag::new();
+	id3_tag.add_frame(id3::Frame::with_content(
+		"TIPL",
+		id3::Content::InvolvedPeopleList(id3::frame::InvolvedPeopleList {
+			items: vec![
+				credit("arranger", &arrangers[0]),
+				credit("producer", "TEST PRODUCER"),
+				credit("performer", &performers[0]),
+				credit("Performer", &performers[1]),
+			],
+		}),
+	));
+	let id3_tags: SongTags = id3_tag.into();
+
+	let mut comments = Vec::new();
+	comments.extend(arrangers.iter().map(|a| ("ARRANGER".to_owned(), a.clone())));
+	comments.extend(
+		performers
+			.iter()
+			.map(|p| ("PERFORMER".to_owned(), p.clone())),
+	);
+	let vorbis_tags = read_vorbis_comments(comments);
+
+	let ape_path = test_directory.join("sample.ape");
+	fs::copy("test-data/formats/sample.ape", &ape_path).unwrap();
+	let mut ape_tag = ape::read_from_path(&ape_path).unwrap();
+	ape_tag.set_item(ape::Item::from_text("ARRANGER", arrangers.join("\0")).unwrap());
+	ape_tag.set_item(ape::Item::from_text("PERFORMER", performers.join("\0")).unwrap());
+	ape::write_to_path(&ape_tag, &ape_path).unwrap();
+	let ape_tags = read(&ape_path).unwrap();
+
+	let mp4_path = test_directory.join("sample.m4a");
+	fs::copy("test-data/formats/sample.m4a", &mp4_path).unwrap();
+	let mut mp4_tag = mp4ameta::Tag::read_from_path(&mp4_path).unwrap();
+	let arranger_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "ARRANGER");
+	let performer_ident = mp4ameta::FreeformIdent::new("com.apple.iTunes", "PERFORMER");
+	mp4_tag.set_all_data(
+		arranger_ident,
+		arrangers.iter().map(|a| mp4ameta::Data::Utf8(a.clone())),
+	);
+	mp4_tag.set_all_data(
+		performer_ident,
+		performers.iter().map(|p| mp4ameta::Data::Utf8(p.clone())),
+	);
+	mp4_tag.write_to_path(&mp4_path).unwrap();
+	let mp4_tags = read(&mp4_path).unwrap();
+	assert!(mp4_tags.extra.is_empty());
+
+	for tags in [id3_tags, vorbis_tags, ape_tags, mp4_tags] {
+		assert_eq!(tags.arrangers, arrangers);
+		assert_eq!(tags.performers, performers);
+	}
+}
+
+#[test]
+fn reads_classical_work_from_id3_tag() {
+	let mut tag = id3::Tag::new();
+	tag.set_text("GRP1", "Symphonies");
+	tag.set_text("TIT1", "Symphony No. 9 in D minor, Op. 125");
+	tag.set_text("MVNM", "Presto - Allegro assai");
+	tag.set_text("MVIN", "4/4");
+	let song_tags: SongTags = tag.into();
+	assert_eq!(song_tags.grouping, Some("Symphonies".to_owned()));
+	assert_eq!(
+		song_tags.work,
+		Some("Symphony No. 9 in D minor, Op. 125".to_owned())
+	);
+	assert_eq!(
+		song_tags.movement_name,
+		Some("Presto - Allegro assai".to_owned())
+	);
+	assert_eq!(song_tags.movement_number, Some(4));
+}
+
+#[test]
+fn reads_classical_work_from_vorbis_comments() {
+	let comments = vec![
+		("GROUPING".to_owned(), "Symphonies".to_owned()),
+		(
+			"WORK".to_owned(),
+			"Symphony No. 9 in D minor, Op. 125".to_owned(),
+		),
+		("MOVEMENTNAME".to_owned(), "Molto vivace".to_owned()),
+		("MOVEMENT".to_owned(), "2".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.grouping, Some("Symphonies".to_owned()));
+	assert_eq!(
+		song_tags.work,
+		Some("Symphony No. 9 in D minor, Op. 125".to_owned())
+	);
+	assert_eq!(song_tags.movement_name, Some("Molto vivace".to_owned()));
+	assert_eq!(song_tags.movement_number, Some(2));
+}
+
+#[test]
+fn reads_and_normalizes_languages() {
+	let mut tag = id3::Tag::new();
+	tag.set_text("TLAN", "ger\0jpn");
+	let song_tags: SongTags = tag.into();
+	assert_eq!(
+		song_tags.languages,
+		vec!["deu".to_owned(), "jpn".to_owned()]
+	);
+
+	let comments = vec![
+		("LANGUAGE".to_owned(), "English".to_owned()),
+		("LANGUAGE".to_owned(), "es".to_owned()),
+		("LANGUAGE".to_owned(), "Klingon".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(
+		song_tags.languages,
+		vec!["eng".to_owned(), "spa".to_owned(), "Klingon".to_owned()]
+	);
+}
+
+#[test]
+fn reads_media_type() {
+	let mut tag = id3::Tag::new();
+	tag.set_text("TMED", "Vinyl");
+	let song_tags: SongTags = tag.into();
+	assert_eq!(song_tags.media_type, Some("Vinyl".to_owned()));
+
+	let comments = vec![
+		("SOURCEMEDIA".to_owned(), "CD".to_owned()),
+		("MEDIA".to_owned(), "Vinyl".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.media_type, Some("Vinyl".to_owned()));
+
+	let comments = vec![("SOURCEMEDIA".to_owned(), "Digital Media".to_owned())];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.media_type, Some("Digital Media".to_owned()));
+}
+
+#[test]
+fn reads_disc_subtitle() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+
+	let mp3_path = test_directory.join("sample.mp3");
+	fs::copy("test-data/formats/sample.mp3", &mp3_path).unwrap();
+	let mut tag = id3::Tag::read_from_path(&mp3_path).unwrap();
+	tag.set_text("TPOS", "1");
+	tag.set_text("TSST", "The Early Years");
+	tag.write_to_path(&mp3_path, id3::Version::Id3v24).unwrap();
+	let song_tags = read(&mp3_path).unwrap();
+	assert_eq!(song_tags.disc_number, Some(1));
+	assert_eq!(song_tags.disc_subtitle, Some("The Early Years".to_owned()));
+
+	let ape_path = test_directory.join("sample.ape");
+	fs::copy("test-data/formats/sample.ape", &ape_path).unwrap();
+	let mut ape_tag = ape::read_from_path(&ape_path).unwrap();
+	ape_tag.set_item(ape::Item::from_text("DISCSUBTITLE", "Rarities").unwrap());
+	ape::write_to_path(&ape_tag, &ape_path).unwrap();
+	let song_tags = read(&ape_path).unwrap();
+	assert_eq!(song_tags.disc_subtitle, Some("Rarities".to_owned()));
+
+	let comments = vec![
+		("DISCNUMBER".to_owned(), "2".to_owned()),
+		("DISCSUBTITLE".to_owned(), "Live".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.disc_number, Some(2));
+	assert_eq!(song_tags.disc_subtitle, Some("Live".to_owned()));
+	assert!(song_tags.extra.is_empty());
+}
+
+#[test]
+fn reads_initial_key() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+
+	let mp3_path = test_directory.join("sample.mp3");
+	fs::copy("test-data/formats/sample.mp3", &mp3_path).unwrap();
+	let mut tag = id3::Tag::read_from_path(&mp3_path).unwrap();
+	tag.set_text("TKEY", "Am");
+	tag.write_to_path(&mp3_path, id3::Version::Id3v24).unwrap();
+	let song_tags = read(&mp3_path).unwrap();
+	assert_eq!(song_tags.initial_key, Some("Am".to_owned()));
+
+	let ape_path = test_directory.join("sample.ape");
+	fs::copy("test-data/formats/sample.ape", &ape_path).unwrap();
+	let mut ape_tag = ape::read_from_path(&ape_path).unwrap();
+	ape_tag.set_item(ape::Item::from_text("KEY", "8A").unwrap());
+	ape::write_to_path(&ape_tag, &ape_path).unwrap();
+	let song_tags = read(&ape_path).unwrap();
+	assert_eq!(song_tags.initial_key, Some("8A".to_owned()));
+
+	let comments = vec![
+		("KEY".to_owned(), "C".to_owned()),
+		("INITIALKEY".to_owned(), "F#m".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.initial_key, Some("F#m".to_owned()));
+	assert!(song_tags.extra.is_empty());
+}
+
+#[test]
+fn reads_isrc_and_catalog_number_from_id3_tag() {
+	let mut tag = id3::Tag::new();
+	tag.set_text("TSRC", "USRC17607839");
+	tag.add_frame(id3::frame::ExtendedText {
+		description: "CATALOGNUMBER".to_owned(),
+		value: "PR-0042".to_owned(),
+	});
+	let song_tags: SongTags = tag.into();
+	assert_eq!(song_tags.isrc, Some("USRC17607839".to_owned()));
+	assert_eq!(song_tags.catalog_number, Some("PR-0042".to_owned()));
+}
+
+#[test]
+fn reads_isrc_and_catalog_number_from_vorbis_comments() {
+	let comments = vec![
+		("ISRC".to_owned(), "USRC17607839".to_owned()),
+		("CATALOGNUMBER".to_owned(), "PR-0042".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.isrc, Some("USRC17607839".to_owned()));
+	assert_eq!(song_tags.catalog_number, Some("PR-0042".to_owned()));
+}
+
+#[test]
+fn reads_vorbis_comments_with_key_variants() {
+	let comments = vec![
+		("ALBUM ARTIST".to_owned(), "Khemmis".to_owned()),
+		("track_number".to_owned(), "4".to_owned()),
+		("Polaris-Hidden".to_owned(), "1".to_owned()),
+		("MY CUSTOM KEY".to_owned(), "value".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.album_artist, Some("Khemmis".to_owned()));
+	assert_eq!(song_tags.track_number, Some(4));
+	assert!(song_tags.hidden);
+	assert_eq!(
+		song_tags.extra.get("MY CUSTOM KEY"),
+		Some(&vec!["value".to_owned()])
+	);
+}
+
+#[test]
+fn reads_encoder_from_id3_tag() {
+	let mut tag = id3::Tag::new();
+	tag.set_text("TENC", "Polaris Test Suite");
+	tag.set_text("TSSE", "LAME 3.100 -V0");
+	let song_tags: SongTags = tag.into();
+	assert_eq!(song_tags.encoded_by, Some("Polaris Test Suite".to_owned()));
+	assert_eq!(
+		song_tags.encoder_settings,
+		Some("LAME 3.100 -V0".to_owned())
+	);
+}
+
+#[test]
+fn reads_rating_from_id3_popularimeter() {
+	let rating = |value: u8| -> Option<u8> {
+		let mut tag = id3::Tag::new();
+		tag.add_frame(id3::Frame::with_content(
+			"POPM",
+			id3::Content::Popularimeter(id3::frame::Popularimeter {
+				user: "polaris@example.com".to_owned(),
+				rating: value,
+				counter: 0,
+			}),
+		));
+		let song_tags: SongTags = tag.into();
+		song_tags.rating
+	};
+	assert_eq!(rating(0), None);
+	assert_eq!(rating(1), Some(20));
+	assert_eq!(rating(64), Some(40));
+	assert_eq!(rating(128), Some(60));
+	assert_eq!(rating(196), Some(80));
+	assert_eq!(rating(255), Some(100));
+}
+
+#[test]
+fn reads_rating_from_vorbis_comments() {
+	let rating = |key: &str, value: &str| {
+		read_vorbis_comments(vec![(key.to_owned(), value.to_owned())]).rating
+	};
+	assert_eq!(rating("RATING", "4"), Some(80));
+	assert_eq!(rating("RATING", "60"), Some(60));
+	assert_eq!(rating("FMPS_RATING", "0.9"), Some(90));
+	assert_eq!(rating("FMPS_RATING", "2"), None);
+}
+
+#[test]
+fn reads_encoder_from_vorbis_comments() {
+	let comments = vec![
+		("ENCODED-BY".to_owned(), "Polaris Test Suite".to_owned()),
+		("ENCODER".to_owned(), "Lavc58.54.100 libopus".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.encoded_by, Some("Polaris Test Suite".to_owned()));
+	assert_eq!(
+		song_tags.encoder_settings,
+		Some("Lavc58.54.100 libopus".to_owned())
+	);
+	assert!(song_tags.extra.is_empty());
+}
+
+#[test]
+fn reads_original_work_from_id3_tag() {
+	let mut tag = id3::Tag::new();
+	tag.set_artist("Cover Band");
+	tag.set_text("TOPE", "Original Artist A\0Original Artist B");
+	tag.set_text("TOAL", "Original Album");
+	let song_tags: SongTags = tag.into();
+	assert_eq!(song_tags.artist, Some("Cover Band".to_owned()));
+	assert_eq!(
+		song_tags.original_artists,
+		vec![
+			"Original Artist A".to_owned(),
+			"Original Artist B".to_owned()
+		]
+	);
+	assert_eq!(song_tags.original_album, Some("Original Album".to_owned()));
+}
+
+#[test]
+fn reads_original_work_from_vorbis_comments() {
+	let comments = vec![
+		("ORIGINALARTIST".to_owned(), "Original Artist".to_owned()),
+		("ORIGINAL ALBUM".to_owned(), "Original Album".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(
+		song_tags.original_artists,
+		vec!["Original Artist".to_owned()]
+	);
+	assert_eq!(song_tags.original_album, Some("Original Album".to_owned()));
+	assert!(song_tags.extra.is_empty());
+}
+
+#[test]
+fn lint_reports_incomplete_tags() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+	let source_path: PathBuf = [
+		"test-data",
+		"small-collection",
+		"Khemmis",
+		"Hunted",
+		"02 - Candlelight.mp3",
+	]
+	.iter()
+	.collect();
+
+	let tagged_path = test_directory.join("tagged.mp3");
+	fs::copy(&source_path, &tagged_path).unwrap();
+	let mut tag = id3::Tag::read_from_path(&tagged_path).unwrap();
+	tag.add_frame(id3::frame::Picture {
+		mime_type: "image/png".to_owned(),
+		picture_type: id3::frame::PictureType::CoverFront,
+		description: String::new(),
+		data: fs::read("test-data/artwork/Folder.png").unwrap(),
+	});
+	tag.write_to_path(&tagged_path, id3::Version::Id3v24)
+		.unwrap();
+	let (tags, warnings) = lint(&tagged_path).unwrap();
+	assert_eq!(tags.title, Some("Candlelight".to_owned()));
+	assert!(warnings.is_empty());
+
+	let bare_path = test_directory.join("bare.mp3");
+	fs::copy(&source_path, &bare_path).unwrap();
+	let mut tag = id3::Tag::new();
+	tag.set_text("TYER", "someday");
+	tag.write_to_path(&bare_path, id3::Version::Id3v23).unwrap();
+	let (_, warnings) = lint(&bare_path).unwrap();
+	assert_eq!(
+		warnings,
+		vec![
+			LintWarning::MissingTitle,
+			LintWarning::MissingArtist,
+			LintWarning::UnparseableYear("someday".to_owned()),
+			LintWarning::MissingArtwork,
+		]
+	);
+}
+
+#[test]
+fn reads_chained_ogg_streams() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+	let vorbis = fs::read("test-data/formats/sample.ogg").unwrap();
+	let opus = fs::read("test-data/formats/sample.opus").unwrap();
+
+	let chained_path = test_directory.join("chained.ogg");
+	fs::write(&chained_path, [vorbis.as_slice(), opus.as_slice()].concat()).unwrap();
+	let song_tags = read(&chained_path).unwrap();
+	assert_eq!(song_tags.title, Some("TEST TITLE".into()));
+	assert_eq!(song_tags.duration, Some(30));
+
+	let malformed_path = test_directory.join("malformed.opus");
+	let truncated_opus = &opus[..opus.len() / 2];
+	fs::write(&malformed_path, [opus.as_slice(), truncated_opus].concat()).unwrap();
+	let song_tags = read(&malformed_path).unwrap();
+	assert_eq!(song_tags.title, Some("TEST TITLE".into()));
+	assert!(song_tags.duration.unwrap() >= 30);
+}
+
+#[test]
+fn reads_original_year_from_id3_tag() {
+	let mut tag = id3::Tag::new();
+	tag.set_year(2011);
+	tag.set_text("TORY", "1987");
+	let song_tags: SongTags = tag.into();
+	assert_eq!(song_tags.year, Some(2011));
+	assert_eq!(song_tags.original_year, Some(1987));
+
+	let mut tag = id3::Tag::new();
+	tag.set_year(2011);
+	tag.set_original_date_released(id3::Timestamp {
+		year: 1987,
+		month: Some(5),
+		day: Some(12),
+		hour: None,
+		minute: None,
+		second: None,
+	});
+	let song_tags: SongTags = tag.into();
+	assert_eq!(song_tags.year, Some(2011));
+	assert_eq!(song_tags.original_year, Some(1987));
+}
+
+#[test]
+fn reads_original_year_from_vorbis_comments() {
+	let comments = vec![
+		("DATE".to_owned(), "2011".to_owned()),
+		("ORIGINALDATE".to_owned(), "1987-05-12".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.year, Some(2011));
+	assert_eq!(song_tags.original_year, Some(1987));
+
+	let comments = vec![
+		("DATE".to_owned(), "2011".to_owned()),
+		("ORIGINALYEAR".to_owned(), "1987".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.original_year, Some(1987));
+}
+
+#[test]
+fn reads_conductors_and_remixers_from_id3_tag() {
+	let mut tag = id3::Tag::new();
+	tag.set_text("TPE3", "Herbert von Karajan");
+	tag.set_text("TPE4", "Remixer A\0Remixer B");
+	let song_tags: SongTags = tag.into();
+	assert_eq!(song_tags.conductors, vec!["Herbert von Karajan".to_owned()]);
+	assert_eq!(
+		song_tags.remixers,
+		vec!["Remixer A".to_owned(), "Remixer B".to_owned()]
+	);
+}
+
+#[test]
+fn reads_conductors_and_remixers_from_vorbis_comments() {
+	let comments = vec![
+		("CONDUCTOR".to_owned(), "Herbert von Karajan".to_owned()),
+		("REMIXER".to_owned(), "Remixer A".to_owned()),
+		("REMIXER".to_owned(), "Remixer B".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.conductors, vec!["Herbert von Karajan".to_owned()]);
+	assert_eq!(
+		song_tags.remixers,
+		vec!["Remixer A".to_owned(), "Remixer B".to_owned()]
+	);
+}
+
+#[test]
+fn reads_custom_fields_from_id3_tag() {
+	let mut tag = id3::Tag::new();
+	tag.add_frame(id3::frame::ExtendedText {
+		description: "FOO".into(),
+		value: "bar".into(),
+	});
+	tag.add_frame(id3::frame::ExtendedText {
+		description: "CATALOGNUMBER".into(),
+		value: "ABC-123".into(),
+	});
+	let song_tags: SongTags = tag.into();
+	assert_eq!(song_tags.extra.get("FOO"), Some(&vec!["bar".to_owned()]));
+	assert_eq!(song_tags.extra.get("CATALOGNUMBER"), None);
+	assert_eq!(song_tags.catalog_number, Some("ABC-123".to_owned()));
+}
+
+#[test]
+fn reads_custom_fields_from_vorbis_comments() {
+	let comments = vec![
+		("TITLE".to_owned(), "Title".to_owned()),
+		("FOO".to_owned(), "bar".to_owned()),
+		("FOO".to_owned(), "baz".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(
+		song_tags.extra.get("FOO"),
+		Some(&vec!["bar".to_owned(), "baz".to_owned()])
+	);
+	assert_eq!(song_tags.extra.get("TITLE"), None);
+}
+
+#[test]
+fn reads_track_and_disc_totals_from_id3_tag() {
+	let mut tag = id3::Tag::new();
+	tag.set_text("TRCK", "3/12");
+	tag.set_text("TPOS", "1/2");
+	let song_tags: SongTags = tag.into();
+	assert_eq!(song_tags.track_number, Some(3));
+	assert_eq!(song_tags.track_total, Some(12));
+	assert_eq!(song_tags.disc_number, Some(1));
+	assert_eq!(song_tags.disc_total, Some(2));
+}
+
+#[test]
+fn reads_track_and_disc_totals_from_vorbis_comments() {
+	let comments = vec![
+		("TRACKNUMBER".to_owned(), "3/12".to_owned()),
+		("DISCNUMBER".to_owned(), "1".to_owned()),
+		("TOTALDISCS".to_owned(), "2".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.track_number, Some(3));
+	assert_eq!(song_tags.track_total, Some(12));
+	assert_eq!(song_tags.disc_number, Some(1));
+	assert_eq!(song_tags.disc_total, Some(2));
+	assert!(song_tags.extra.is_empty());
+
+	let comments = vec![
+		("TRACKNUMBER".to_owned(), "3".to_owned()),
+		("TRACKTOTAL".to_owned(), "12".to_owned()),
+	];
+	let song_tags = read_vorbis_comments(comments);
+	assert_eq!(song_tags.track_number, Some(3));
+	assert_eq!(song_tags.track_total, Some(12));
+}
+
+#[test]
+fn parses_totals_of_x_of_y_values() {
+	assert_eq!(parse_total_of_x_of_y("3/12"), Some(12));
+	assert_eq!(parse_total_of_x_of_y(" 3 / 12 "), Some(12));
+	assert_eq!(parse_total_of_x_of_y("3"), None);
+	assert_eq!(parse_total_of_x_of_y("3/"), None);
+}
+
+#[test]
+fn merges_ape_tags_of_mp3_files_by_priority() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+	let song_path = test_directory.join("sample.mp3");
+	fs::copy("test-data/formats/sample.mp3", &song_path).unwrap();
+	let mut ape_tag = ape::Tag::new();
+	ape_tag.set_item(ape::Item::from_text("Title", "APE Title").unwrap());
+	ape_tag.set_item(ape::Item::from_text("ORIGINALALBUM", "APE Original Album").unwrap());
+	ape::write_to_path(&ape_tag, &song_path).unwrap();
+
+	let id3_only = read(&song_path).unwrap();
+	assert_eq!(id3_only.original_album, None);
+
+	let id3_first =
+		read_with_tag_priority(&song_path, &[TagContainer::Id3, TagContainer::Ape]).unwrap();
+	assert_eq!(id3_first.title, id3_only.title);
+	assert_eq!(
+		id3_first.original_album,
+		Some("APE Original Album".to_owned())
+	);
+	assert_eq!(id3_first.album, Some("TEST ALBUM".to_owned()));
+
+	let ape_first =
+		read_with_tag_priority(&song_path, &[TagContainer::Ape, TagContainer::Id3]).unwrap();
+	assert_eq!(ape_first.title, Some("APE Title".to_owned()));
+	assert_eq!(ape_first.album, Some("TEST ALBUM".to_owned()));
+}
+
+#[test]
+fn reads_raw_tags_verbatim() {
+	let path: PathBuf = [
+		"test-data",
+		"small-collection",
+		"Khemmis",
+		"Hunted",
+		"02 - Candlelight.mp3",
+	]
+	.iter()
+	.collect();
+	let raw_tags = read_raw(&path).unwrap();
+	assert_eq!(raw_tags.get("TPE1"), Some(&vec!["Khemmis".to_owned()]));
+	assert_eq!(raw_tags.get("TIT2"), Some(&vec!["Candlelight".to_owned()]));
+
+	let raw_tags = read_raw(Path::new("test-data/formats/sample.flac")).unwrap();
+	assert!(raw_tags.keys().any(|k| k.eq_ignore_ascii_case("ARTIST")));
+
+	assert!(read_raw(Path::new("test-data/artwork/Folder.png")).is_none());
+}
+
+#[test]
+fn estimates_missing_durations() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+
+	let ape_path = Path::new("test-data/formats/sample.ape");
+	assert_eq!(read(ape_path).unwrap().duration, None);
+	assert_eq!(estimate_duration(ape_path), Some(0));
+
+	// Three frames of 73728 blocks at 44.1kHz, the last frame holding 20375 blocks
+	let mut ape = fs::read(ape_path).unwrap();
+	ape[64..68].copy_from_slice(&3u32.to_le_bytes());
+	let long_ape_path = test_directory.join("long.ape");
+	fs::write(&long_ape_path, ape).unwrap();
+	assert_eq!(estimate_duration(&long_ape_path), Some(3));
+
+	assert_eq!(
+		estimate_duration(Path::new("test-data/artwork/Folder.png")),
+		None
+	);
+}
+
+#[test]
+fn duration_fallback_chain() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+
+	let mut ape = fs::read("test-data/formats/sample.ape").unwrap();
+	ape[64..68].copy_from_slice(&3u32.to_le_bytes());
+	let ape_path = test_directory.join("long.ape");
+	fs::write(&ape_path, ape).unwrap();
+	let mut ape_tags = read(&ape_path).unwrap();
+	assert_eq!(ape_tags.duration_source, None);
+	fill_missing_duration(&ape_path, &mut ape_tags);
+	assert_eq!(ape_tags.duration, Some(3));
+	assert_eq!(ape_tags.duration_source, Some(DurationSource::Estimate));
+
+	let flac_path = Path::new("test-data/formats/sample.flac");
+	let mut flac_tags = read(flac_path).unwrap();
+	fill_missing_duration(flac_path, &mut flac_tags);
+	assert_eq!(flac_tags.duration, Some(0));
+	assert_eq!(flac_tags.duration_source, Some(DurationSource::Stream));
+}
+
+#[test]
+fn reads_content_type() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+
+	let m4b_path = test_directory.join("sample.m4b");
+	fs::copy("test-data/formats/sample.m4a", &m4b_path).unwrap();
+	let mut mp4_tag = mp4ameta::Tag::read_from_path(&m4b_path).unwrap();
+	mp4_tag.set_media_type(mp4ameta::MediaType::AudioBook);
+	mp4_tag.write_to_path(&m4b_path).unwrap();
+	let m4b_tags = read(&m4b_path).unwrap();
+	assert_eq!(m4b_tags.content_type, Some(ContentType::Audiobook));
+
+	let m4a_tags = read(Path::new("test-data/formats/sample.m4a")).unwrap();
+	assert_eq!(m4a_tags.content_type, None);
+
+	let mut tag = id3::Tag::new();
+	tag.add_frame(id3::frame::Chapter {
+		element_id: "chp0".to_owned(),
+		start_time: 0,
+		end_time: 1000,
+		start_offset: 0xFFFFFFFF,
+		end_offset: 0xFFFFFFFF,
+		frames: Vec::new(),
+	});
+	let id3_tags: SongTags = tag.into();
+	assert_eq!(id3_tags.content_type, Some(ContentType::Audiobook));
+}
+
+#[test]
+fn reads_text_tags_next_to_corrupt_artwork() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+
+	fn frame(id: &[u8; 4], content: &[u8]) -> Vec<u8> {
+		let mut frame = id.to_vec();
+		frame.extend((content.len() as u32).to_be_bytes());
+		frame.extend([0, 0]);
+		frame.extend(content);
+		frame
+	}
+
+	// The picture frame uses an encoding which does not exist
+	let mut frames = frame(b"APIC", &[0x07, b'x']);
+	frames.extend(frame(b"TIT2", b"\x00Corrupt Cover"));
+	frames.extend(frame(b"TPE1", b"\x00Khemmis"));
+	let mut bytes = b"ID3\x03\x00\x00".to_vec();
+	bytes.extend(encode_synchsafe(frames.len() as u32));
+	bytes.extend(frames);
+
+	let path = test_directory.join("corrupt_artwork.mp3");
+	fs::write(&path, bytes).unwrap();
+	assert!(id3::Tag::read_from_path(&path).is_err());
+
+	let tags = read(&path).unwrap();
+	assert_eq!(tags.title, Some("Corrupt Cover".to_owned()));
+	assert_eq!(tags.artist, Some("Khemmis".to_owned()));
+	assert!(!tags.has_artwork);
+}
+
+#[test]
+fn reads_flac_text_tags_next_to_corrupt_picture() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+
+	// The picture block uses a picture type which does not exist
+	let mut picture = 0xFFu32.to_be_bytes().to_vec();
+	picture.extend([0u8; 28]);
+	let mut picture_block = vec![6u8];
+	picture_block.extend(&(picture.len() as u32).to_be_bytes()[1..]);
+	picture_block.extend(picture);
+
+	// Inserted right after the STREAMINFO block, which is never the last one in this sample
+	let mut bytes = fs::read("test-data/formats/sample.flac").unwrap();
+	let streaminfo_length = u32::from_be_bytes([0, bytes[5], bytes[6], bytes[7]]) as usize;
+	let insert_at = 8 + streaminfo_length;
+	bytes.splice(insert_at..insert_at, picture_block);
+
+	let path = test_directory.join("corrupt_picture.flac");
+	fs::write(&path, bytes).unwrap();
+	assert!(metaflac::Tag::read_from_path(&path).is_err());
+
+	let tags = read(&path).unwrap();
+	assert_eq!(tags.title, Some("TEST TITLE".to_owned()));
+	assert_eq!(tags.artist, Some("TEST ARTIST".to_owned()));
+	assert!(!tags.has_artwork);
+}
+
+#[test]
+fn reads_mp4_text_tags_next_to_corrupt_artwork() {
+	let test_directory = crate::test::prepare_test_directory(crate::test_name!());
+
+	let path = test_directory.join("corrupt_artwork.m4a");
+	fs::copy("test-data/formats/sample.m4a", &path).unwrap();
+	let mut mp4_tag = mp4ameta::Tag::read_from_path(&path).unwrap();
+	mp4_tag.set_artwork(mp4ameta::Img::png(vec![0u8; 16]));
+	mp4_tag.write_to_path(&path).unwrap();
+
+	// The data atom of the artwork is given a data type which does not exist
+	let mut bytes = fs::read(&path).unwrap();
+	let covr = bytes.windows(4).position(|w| w == b"covr").unwrap();
+	assert_eq!(&bytes[covr + 8..covr + 12], b"data");
+	bytes[covr + 15] = 0xFF;
+	fs::write(&path, bytes).unwrap();
+	assert!(mp4ameta::Tag::read_from_path(&path).is_err());
+
+	let tags = read(&path).unwrap();
+	assert_eq!(tags.title, Some("TEST TITLE".to_owned()));
+	assert_eq!(tags.artist, Some("TEST ARTIST".to_owned()));
+	assert!(!tags.has_artwork);
+}
@@ -1,9 +1,15 @@
-use diesel::dsl::sql;
+use diesel::dsl::{exists, sql};
 use diesel::prelude::*;
 use diesel::sql_types;
+use diesel::sqlite::Sqlite;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 use super::*;
+use crate::app::settings::{AlbumArtistGrouping, MountOrder};
 use crate::db::{self, directories, songs};
 
 #[derive(thiserror::Error, Debug)]
@@ -12,8 +18,14 @@ pub enum QueryError {
 	Database(#[from] diesel::result::Error),
 	#[error(transparent)]
 	DatabaseConnection(#[from] db::Error),
+	#[error("Path was not found in the index: `{0}`")]
+	PathNotFound(PathBuf),
 	#[error("Song was not found: `{0}`")]
 	SongNotFound(PathBuf),
+	#[error("No image was found for artist `{0}`")]
+	ArtistImageNotFound(String),
+	#[error(transparent)]
+	Settings(#[from] settings::Error),
 	#[error(transparent)]
 	Vfs(#[from] vfs::Error),
 }
@@ -23,42 +35,355 @@ sql_function!(
 	fn random() -> Integer;
 );
 
+/// Matches directories directly containing at least one song which is not hidden.
+fn has_visible_songs() -> diesel::expression::SqlLiteral<sql_types::Bool> {
+	sql::<sql_types::Bool>(
+		"EXISTS (SELECT 1 FROM songs WHERE songs.parent = directories.path AND songs.hidden = 0)",
+	)
+}
+
+/// Matches directories containing at least one song, directly or within sub-directories. Hidden
+/// songs only count when they are included in results.
+fn has_descendant_songs(include_hidden: bool) -> diesel::expression::SqlLiteral<sql_types::Bool> {
+	let hidden_filter = if include_hidden {
+		""
+	} else {
+		" AND songs.hidden = 0"
+	};
+	sql::<sql_types::Bool>(&format!(
+		"EXISTS (SELECT 1 FROM songs WHERE songs.path LIKE directories.path || '{}%'{})",
+		std::path::MAIN_SEPARATOR,
+		hidden_filter
+	))
+}
+
+/// Song fields which can be searched using `field:value` clauses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SearchField {
+	Album,
+	AlbumArtist,
+	Artist,
+	Composer,
+	Conductor,
+	Genre,
+	Grouping,
+	Label,
+	Lyricist,
+	Remixer,
+	Title,
+	Work,
+}
+
+impl SearchField {
+	fn from_name(name: &str) -> Option<Self> {
+		match name.to_lowercase().as_str() {
+			"album" => Some(Self::Album),
+			"album_artist" | "albumartist" => Some(Self::AlbumArtist),
+			"artist" => Some(Self::Artist),
+			"composer" => Some(Self::Composer),
+			"conductor" => Some(Self::Conductor),
+			"genre" => Some(Self::Genre),
+			"grouping" => Some(Self::Grouping),
+			"label" => Some(Self::Label),
+			"lyricist" => Some(Self::Lyricist),
+			"remixer" => Some(Self::Remixer),
+			"title" => Some(Self::Title),
+			"work" => Some(Self::Work),
+			_ => None,
+		}
+	}
+}
+
+/// A search query split into `field:value` clauses and the remaining free text.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SearchQuery {
+	text: String,
+	clauses: Vec<(SearchField, String)>,
+}
+
+/// Values containing spaces can be quoted, as in `composer:"Johann Sebastian Bach"`.
+/// Words with an unknown field prefix are treated as free text.
+fn parse_search_query(query: &str) -> SearchQuery {
+	let mut words = Vec::new();
+	let mut current = String::new();
+	let mut quoted = false;
+	for c in query.chars() {
+		match c {
+			'"' => quoted = !quoted,
+			c if c.is_whitespace() && !quoted => {
+				if !current.is_empty() {
+					words.push(std::mem::take(&mut current));
+				}
+			}
+			c => current.push(c),
+		}
+	}
+	if !current.is_empty() {
+		words.push(current);
+	}
+
+	let mut search_query = SearchQuery::default();
+	let mut text = Vec::new();
+	for word in words {
+		let clause = word.split_once(':').and_then(|(name, value)| {
+			let field = SearchField::from_name(name)?;
+			(!value.is_empty()).then(|| (field, value.to_owned()))
+		});
+		match clause {
+			Some(clause) => search_query.clauses.push(clause),
+			None => text.push(word),
+		}
+	}
+	search_query.text = text.join(" ");
+	search_query
+}
+
+/// Album artist tags can hold multiple names, separated by semicolons or (in ID3v2.4) null characters.
+fn split_artist_names(names: &str) -> impl Iterator<Item = &str> {
+	names
+		.split([';', '\0'])
+		.map(str::trim)
+		.filter(|n| !n.is_empty())
+}
+
+/// Splits fields which can credit several people, like album artists or conductors, into individual names.
+pub fn split_names(names: Option<&str>) -> Vec<String> {
+	names
+		.map(|names| split_artist_names(names).map(str::to_owned).collect())
+		.unwrap_or_default()
+}
+
+/// Songs whose title, album, artist or path match the free text of a search, and whose fields
+/// match its `field:value` clauses.
+fn search_songs(search_query: &SearchQuery) -> songs::BoxedQuery<'static, Sqlite> {
+	use self::songs::dsl::*;
+	let like_test = format!("%{}%", search_query.text);
+	let mut query = songs.into_boxed();
+	if !search_query.text.is_empty() || search_query.clauses.is_empty() {
+		query = query.filter(
+			path.like(like_test.clone())
+				.or(title.like(like_test.clone()))
+				.or(album.like(like_test.clone()))
+				.or(artist.like(like_test.clone()))
+				.or(album_artist.like(like_test)),
+		);
+	}
+	for (field, value) in &search_query.clauses {
+		let pattern = format!("%{}%", value);
+		query = match field {
+			SearchField::Album => query.filter(album.like(pattern)),
+			SearchField::AlbumArtist => query.filter(album_artist.like(pattern)),
+			SearchField::Artist => query.filter(artist.like(pattern)),
+			SearchField::Composer => query.filter(composer.like(pattern)),
+			SearchField::Conductor => query.filter(conductor.like(pattern)),
+			SearchField::Genre => query.filter(genre.like(pattern)),
+			SearchField::Grouping => query.filter(grouping.like(pattern)),
+			SearchField::Label => query.filter(label.like(pattern)),
+			SearchField::Lyricist => query.filter(lyricist.like(pattern)),
+			SearchField::Remixer => query.filter(remixer.like(pattern)),
+			SearchField::Title => query.filter(title.like(pattern)),
+			SearchField::Work => query.filter(work.like(pattern)),
+		};
+	}
+	query
+}
+
+/// Reduces artist names to keys under which different spellings of the same artist are grouped.
+/// Whitespace is collapsed, case is ignored and leading articles are disregarded, whether they come
+/// first ("The Beatles") or were moved to the end ("Beatles, The").
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArtistNameNormalizer {
+	articles: Vec<String>,
+}
+
+impl ArtistNameNormalizer {
+	pub fn new(articles: &[String]) -> Self {
+		Self {
+			articles: articles
+				.iter()
+				.map(|a| a.trim().to_lowercase())
+				.filter(|a| !a.is_empty())
+				.collect(),
+		}
+	}
+
+	pub fn key(&self, name: &str) -> String {
+		let name = name
+			.split_whitespace()
+			.collect::<Vec<_>>()
+			.join(" ")
+			.to_lowercase();
+		for article in &self.articles {
+			let leading = name
+				.strip_prefix(article.as_str())
+				.and_then(|n| n.strip_prefix(' '));
+			let trailing = name
+				.strip_suffix(article.as_str())
+				.and_then(|n| n.trim_end().strip_suffix(','));
+			if let Some(stripped) = leading.or(trailing) {
+				let stripped = stripped.trim();
+				if !stripped.is_empty() {
+					return stripped.to_owned();
+				}
+			}
+		}
+		name
+	}
+}
+
+#[derive(Default)]
+struct ArtistCounts {
+	albums: HashSet<String>,
+	track_count: usize,
+	spellings: HashMap<String, usize>,
+}
+
+/// Aggregates (album artist, artist, album) rows into per-artist album and track counts.
+/// Songs without an album artist are attributed to their artist. When a normalizer is given,
+/// names sharing the same key are counted as a single artist, listed under its most common spelling.
+fn count_artists(
+	rows: Vec<(Option<String>, Option<String>, Option<String>)>,
+	grouping: AlbumArtistGrouping,
+	normalizer: Option<&ArtistNameNormalizer>,
+) -> Vec<Artist> {
+	let mut counts: HashMap<String, ArtistCounts> = HashMap::new();
+	for (album_artist, artist, album) in rows {
+		let names = match album_artist.or(artist) {
+			Some(n) => n,
+			None => continue,
+		};
+		let names: Vec<String> = match grouping {
+			AlbumArtistGrouping::Each => split_artist_names(&names).map(str::to_owned).collect(),
+			AlbumArtistGrouping::Joined => {
+				vec![split_artist_names(&names).collect::<Vec<_>>().join("; ")]
+			}
+		};
+		for name in names.into_iter().filter(|n| !n.is_empty()) {
+			let key = match normalizer {
+				Some(normalizer) => normalizer.key(&name),
+				None => name.clone(),
+			};
+			let entry = counts.entry(key).or_default();
+			if let Some(album) = &album {
+				entry.albums.insert(album.clone());
+			}
+			entry.track_count += 1;
+			*entry.spellings.entry(name).or_default() += 1;
+		}
+	}
+
+	let mut artists: Vec<Artist> = counts
+		.into_values()
+		.filter_map(|counts| {
+			let name = counts
+				.spellings
+				.into_iter()
+				.max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(b.cmp(a)))
+				.map(|(name, _)| name)?;
+			Some(Artist {
+				name,
+				album_count: counts.albums.len(),
+				track_count: counts.track_count,
+			})
+		})
+		.collect();
+	artists.sort_by(|a, b| {
+		a.name
+			.to_lowercase()
+			.cmp(&b.name.to_lowercase())
+			.then(a.name.cmp(&b.name))
+	});
+	artists
+}
+
 impl Index {
-	pub fn browse<P>(&self, virtual_path: P) -> Result<Vec<CollectionFile>, QueryError>
+	pub fn browse<P>(
+		&self,
+		virtual_path: P,
+		include_hidden: bool,
+	) -> Result<Vec<CollectionFile>, QueryError>
 	where
 		P: AsRef<Path>,
 	{
 		let mut output = Vec::new();
 		let vfs = self.vfs_manager.get_vfs()?;
-		let mut connection = self.db.connect()?;
+		let settings = self.settings_manager.read()?;
+		let hide_empty_directories = settings.hide_empty_directories;
+		let mut connection = self.db.connect_read()?;
 
 		if virtual_path.as_ref().components().count() == 0 {
 			// Browse top-level
-			let real_directories: Vec<Directory> = directories::table
+			let mut directories_query = directories::table
 				.filter(directories::parent.is_null())
-				.load(&mut connection)?;
-			let virtual_directories = real_directories
+				.into_boxed();
+			if hide_empty_directories {
+				directories_query = directories_query.filter(has_descendant_songs(include_hidden));
+			}
+			let real_directories: Vec<Directory> = directories_query.load(&mut connection)?;
+			let mounts = vfs.mounts();
+			let mount_index = |d: &Directory| mounts.iter().position(|m| m.name == d.path);
+			let mut virtual_directories: Vec<Directory> = real_directories
 				.into_iter()
-				.filter_map(|d| d.virtualize(&vfs));
-			output.extend(virtual_directories.map(CollectionFile::Directory));
+				.filter_map(|d| d.virtualize(&vfs))
+				.filter(|d| mount_index(d).is_none_or(|i| !mounts[i].hide_from_root))
+				.collect();
+			match settings.mount_order {
+				MountOrder::Alphabetical => {
+					virtual_directories.sort_by_key(|d| d.path.to_lowercase())
+				}
+				MountOrder::Config => virtual_directories.sort_by_key(mount_index),
+			}
+			output.extend(
+				virtual_directories
+					.into_iter()
+					.map(CollectionFile::Directory),
+			);
 		} else {
 			// Browse sub-directory
-			let real_path = vfs.virtual_to_real(virtual_path)?;
+			let real_path = vfs.virtual_to_real(virtual_path.as_ref())?;
 			let real_path_string = real_path.as_path().to_string_lossy().into_owned();
 
-			let real_directories: Vec<Directory> = directories::table
+			let is_directory: bool = diesel::select(exists(
+				directories::table.filter(directories::path.eq(&real_path_string)),
+			))
+			.get_result(&mut connection)?;
+
+			if !is_directory {
+				// Browsing a song yields that song alone
+				let mut song_query = songs::table
+					.filter(songs::path.eq(&real_path_string))
+					.into_boxed();
+				if !include_hidden {
+					song_query = song_query.filter(songs::hidden.eq(false));
+				}
+				let song: Option<Song> = song_query.first(&mut connection).optional()?;
+				return match song.and_then(|s| s.virtualize(&vfs)) {
+					Some(song) => Ok(vec![CollectionFile::Song(song)]),
+					None => Err(QueryError::PathNotFound(virtual_path.as_ref().to_owned())),
+				};
+			}
+
+			let mut directories_query = directories::table
 				.filter(directories::parent.eq(&real_path_string))
 				.order(sql::<sql_types::Bool>("path COLLATE NOCASE ASC"))
-				.load(&mut connection)?;
+				.into_boxed();
+			if hide_empty_directories {
+				directories_query = directories_query.filter(has_descendant_songs(include_hidden));
+			}
+			let real_directories: Vec<Directory> = directories_query.load(&mut connection)?;
 			let virtual_directories = real_directories
 				.into_iter()
 				.filter_map(|d| d.virtualize(&vfs));
 			output.extend(virtual_directories.map(CollectionFile::Directory));
 
-			let real_songs: Vec<Song> = songs::table
+			let mut songs_query = songs::table
 				.filter(songs::parent.eq(&real_path_string))
 				.order(sql::<sql_types::Bool>("path COLLATE NOCASE ASC"))
-				.load(&mut connection)?;
+				.into_boxed();
+			if !include_hidden {
+				songs_query = songs_query.filter(songs::hidden.eq(false));
+			}
+			let real_songs: Vec<Song> = songs_query.load(&mut connection)?;
 			let virtual_songs = real_songs.into_iter().filter_map(|s| s.virtualize(&vfs));
 			output.extend(virtual_songs.map(CollectionFile::Song));
 		}
@@ -66,71 +391,647 @@ impl Index {
 		Ok(output)
 	}
 
-	pub fn flatten<P>(&self, virtual_path: P) -> Result<Vec<Song>, QueryError>
+	/// Lists companion files such as cover scans or cue sheets found directly within a directory.
+	pub fn get_media_files<P>(&self, virtual_path: P) -> Result<Vec<MediaFile>, QueryError>
 	where
 		P: AsRef<Path>,
 	{
-		use self::songs::dsl::*;
+		if virtual_path.as_ref().components().count() == 0 {
+			return Ok(Vec::new());
+		}
+
 		let vfs = self.vfs_manager.get_vfs()?;
 		let mut connection = self.db.connect()?;
+		let real_path = vfs.virtual_to_real(virtual_path.as_ref())?;
+		let real_path_string = real_path.as_path().to_string_lossy().into_owned();
+
+		let media: Option<Option<String>> = directories::table
+			.select(directories::media)
+			.filter(directories::path.eq(&real_path_string))
+			.get_result(&mut connection)
+			.optional()?;
+		let real_paths: Vec<String> = media
+			.flatten()
+			.and_then(|m| serde_json::from_str(&m).ok())
+			.unwrap_or_default();
+
+		Ok(real_paths
+			.into_iter()
+			.filter_map(|p| {
+				let kind = MediaKind::from_path(Path::new(&p))?;
+				let virtual_path = vfs.real_to_virtual(Path::new(&p)).ok()?;
+				Some(MediaFile {
+					path: virtual_path.to_string_lossy().into_owned(),
+					kind,
+				})
+			})
+			.collect())
+	}
+
+	pub fn flatten<P>(&self, virtual_path: P, include_hidden: bool) -> Result<Vec<Song>, QueryError>
+	where
+		P: AsRef<Path>,
+	{
+		use self::songs::dsl::*;
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect_read()?;
+
+		let mut query = songs.order(path).into_boxed();
+		if virtual_path.as_ref().parent().is_some() {
+			let real_path = vfs.virtual_to_real(virtual_path.as_ref())?;
+			let real_path_string = real_path.as_path().to_string_lossy().into_owned();
+
+			// Mount points can be flattened before they are first indexed
+			let is_mount_point = virtual_path.as_ref().components().count() == 1;
+			let is_directory = is_mount_point
+				|| diesel::select(exists(
+					directories::table.filter(directories::path.eq(&real_path_string)),
+				))
+				.get_result(&mut connection)?;
+
+			if !is_directory {
+				// Flattening a song yields that song alone
+				let mut song_query = songs.filter(path.eq(&real_path_string)).into_boxed();
+				if !include_hidden {
+					song_query = song_query.filter(hidden.eq(false));
+				}
+				let song: Option<Song> = song_query.first(&mut connection).optional()?;
+				return match song.and_then(|s| s.virtualize(&vfs)) {
+					Some(song) => Ok(vec![song]),
+					None => Err(QueryError::PathNotFound(virtual_path.as_ref().to_owned())),
+				};
+			}
 
-		let real_songs: Vec<Song> = if virtual_path.as_ref().parent().is_some() {
-			let real_path = vfs.virtual_to_real(virtual_path)?;
 			let song_path_filter = {
 				let mut path_buf = real_path;
 				path_buf.push("%");
 				path_buf.as_path().to_string_lossy().into_owned()
 			};
-			songs
-				.filter(path.like(&song_path_filter))
-				.order(path)
-				.load(&mut connection)?
-		} else {
-			songs.order(path).load(&mut connection)?
-		};
+			query = query.filter(path.like(song_path_filter));
+		}
+		if !include_hidden {
+			query = query.filter(hidden.eq(false));
+		}
+		let real_songs: Vec<Song> = query.load(&mut connection)?;
 
-		let virtual_songs = real_songs.into_iter().filter_map(|s| s.virtualize(&vfs));
-		Ok(virtual_songs.collect::<Vec<_>>())
+		// Songs are sorted by virtual path, which does not depend on how mounts map to real paths
+		let mut virtual_songs: Vec<Song> = real_songs
+			.into_iter()
+			.filter_map(|s| s.virtualize(&vfs))
+			.collect();
+		virtual_songs.sort_by(|a, b| a.path.cmp(&b.path));
+		Ok(virtual_songs)
 	}
 
-	pub fn get_random_albums(&self, count: i64) -> Result<Vec<Directory>, QueryError> {
+	pub fn get_random_albums(
+		&self,
+		count: i64,
+		include_hidden: bool,
+	) -> Result<Vec<Directory>, QueryError> {
 		use self::directories::dsl::*;
 		let vfs = self.vfs_manager.get_vfs()?;
 		let mut connection = self.db.connect()?;
-		let real_directories: Vec<Directory> = directories
+		let mut query = directories
 			.filter(album.is_not_null())
 			.limit(count)
 			.order(random())
-			.load(&mut connection)?;
+			.into_boxed();
+		if !include_hidden {
+			query = query.filter(has_visible_songs());
+		}
+		let real_directories: Vec<Directory> = query.load(&mut connection)?;
 		let virtual_directories = real_directories
 			.into_iter()
 			.filter_map(|d| d.virtualize(&vfs));
 		Ok(virtual_directories.collect::<Vec<_>>())
 	}
 
-	pub fn get_recent_albums(&self, count: i64) -> Result<Vec<Directory>, QueryError> {
+	/// Picks up to `count` distinct songs at random, optionally restricted to a genre or artist.
+	/// Passing the same seed yields the same songs for as long as the index is unchanged.
+	pub fn shuffle(
+		&self,
+		count: usize,
+		seed: Option<u64>,
+		genre_filter: Option<&str>,
+		artist_filter: Option<&str>,
+	) -> Result<Vec<Song>, QueryError> {
+		use self::songs::dsl::*;
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+
+		let mut query = songs.filter(hidden.eq(false)).order(path).into_boxed();
+		if let Some(g) = genre_filter {
+			query = query.filter(genre.eq(g));
+		}
+		if let Some(a) = artist_filter {
+			query = query.filter(artist.eq(a).or(album_artist.eq(a)));
+		}
+		let mut real_songs: Vec<Song> = query.load(&mut connection)?;
+
+		let mut rng = match seed {
+			Some(s) => StdRng::seed_from_u64(s),
+			None => StdRng::from_entropy(),
+		};
+		real_songs.shuffle(&mut rng);
+		real_songs.truncate(count);
+
+		let virtual_songs = real_songs.into_iter().filter_map(|s| s.virtualize(&vfs));
+		Ok(virtual_songs.collect::<Vec<_>>())
+	}
+
+	pub fn get_recent_albums(
+		&self,
+		count: i64,
+		include_hidden: bool,
+	) -> Result<Vec<Directory>, QueryError> {
 		use self::directories::dsl::*;
 		let vfs = self.vfs_manager.get_vfs()?;
 		let mut connection = self.db.connect()?;
-		let real_directories: Vec<Directory> = directories
+		let mut query = directories
 			.filter(album.is_not_null())
 			.order(date_added.desc())
 			.limit(count)
+			.into_boxed();
+		if !include_hidden {
+			query = query.filter(has_visible_songs());
+		}
+		let real_directories: Vec<Directory> = query.load(&mut connection)?;
+		let virtual_directories = real_directories
+			.into_iter()
+			.filter_map(|d| d.virtualize(&vfs));
+		Ok(virtual_directories.collect::<Vec<_>>())
+	}
+
+	/// Merges albums which have several copies in the collection (eg. FLAC and MP3 rips), when
+	/// enabled in settings. Copies share the same album artist, album and year. The copy in the
+	/// preferred format represents the album, and tracks it lacks are taken from other copies.
+	pub fn merge_duplicate_albums(&self, albums: Vec<Directory>) -> Result<Vec<Album>, QueryError> {
+		let settings = self.settings_manager.read()?;
+		if !settings.merge_duplicate_albums {
+			return Ok(albums.into_iter().map(Album::from).collect());
+		}
+
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let mut seen_keys = HashSet::new();
+		let mut merged_albums = Vec::new();
+
+		for listed_album in albums {
+			let album_name = match listed_album.album.clone() {
+				Some(a) => a,
+				None => {
+					merged_albums.push(Album::from(listed_album));
+					continue;
+				}
+			};
+			let key = (
+				listed_album.artist.clone(),
+				album_name.clone(),
+				listed_album.year,
+			);
+			if !seen_keys.insert(key) {
+				continue;
+			}
+
+			let copies: Vec<Directory> = {
+				use self::directories::dsl::*;
+				let mut query = directories
+					.filter(album.eq(&album_name))
+					.order(path)
+					.into_boxed();
+				query = match &listed_album.artist {
+					Some(a) => query.filter(artist.eq(a)),
+					None => query.filter(artist.is_null()),
+				};
+				query = match listed_album.year {
+					Some(y) => query.filter(year.eq(y)),
+					None => query.filter(year.is_null()),
+				};
+				query.load(&mut connection)?
+			};
+			if copies.len() < 2 {
+				merged_albums.push(Album::from(listed_album));
+				continue;
+			}
+
+			let copy_paths: Vec<&String> = copies.iter().map(|d| &d.path).collect();
+			let copy_songs: Vec<Song> = {
+				use self::songs::dsl::*;
+				songs
+					.filter(parent.eq_any(copy_paths))
+					.filter(hidden.eq(false))
+					.order(path)
+					.load(&mut connection)?
+			};
+
+			let is_preferred = |copy: &Directory| {
+				copy_songs.iter().any(|s| {
+					s.parent == copy.path
+						&& s.format.as_deref().is_some_and(|f| {
+							f.eq_ignore_ascii_case(&settings.preferred_album_format)
+						})
+				})
+			};
+			let preferred_index = copies.iter().position(is_preferred).unwrap_or(0);
+
+			// Tracks of the preferred copy come first so they win over their duplicates
+			let preferred_path = copies[preferred_index].path.clone();
+			let (mut ordered_songs, other_songs): (Vec<Song>, Vec<Song>) = copy_songs
+				.into_iter()
+				.partition(|s| s.parent == preferred_path);
+			ordered_songs.extend(other_songs);
+			let mut track_keys = HashSet::new();
+			let mut combined_songs: Vec<Song> = ordered_songs
+				.into_iter()
+				.filter(|s| {
+					let title = s.track_number.is_none().then(|| s.title.clone());
+					track_keys.insert((s.disc_number, s.track_number, title))
+				})
+				.collect();
+			combined_songs.sort_by(Song::cmp_album_order);
+
+			let mut copies = copies;
+			let preferred_copy = copies.remove(preferred_index);
+			let directory = match preferred_copy.virtualize(&vfs) {
+				Some(d) => d,
+				None => continue,
+			};
+			merged_albums.push(Album {
+				directory,
+				merged_paths: copies
+					.into_iter()
+					.filter_map(|d| d.virtualize(&vfs))
+					.map(|d| d.path)
+					.collect(),
+				songs: combined_songs
+					.into_iter()
+					.filter_map(|s| s.virtualize(&vfs))
+					.collect(),
+			});
+		}
+
+		Ok(merged_albums)
+	}
+
+	/// Lists directories containing songs, for which no artwork was found during indexing
+	/// or whose artwork file has since disappeared.
+	pub fn get_albums_without_artwork(&self) -> Result<Vec<Directory>, QueryError> {
+		use self::directories::dsl::*;
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let real_directories: Vec<Directory> = directories
+			.filter(sql::<sql_types::Bool>(
+				"EXISTS (SELECT 1 FROM songs WHERE songs.parent = directories.path)",
+			))
+			.order(path)
 			.load(&mut connection)?;
 		let virtual_directories = real_directories
 			.into_iter()
+			.filter(|d| match &d.artwork {
+				Some(artwork_path) => !Path::new(artwork_path).exists(),
+				None => true,
+			})
 			.filter_map(|d| d.virtualize(&vfs));
 		Ok(virtual_directories.collect::<Vec<_>>())
 	}
 
-	pub fn search(&self, query: &str) -> Result<Vec<CollectionFile>, QueryError> {
+	/// Lists the real paths of artwork found for directories within a virtual path.
+	pub fn get_artwork_paths<P>(&self, virtual_path: P) -> Result<Vec<PathBuf>, QueryError>
+	where
+		P: AsRef<Path>,
+	{
+		use self::directories::dsl::*;
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+
+		let mut query = directories
+			.select(artwork)
+			.filter(artwork.is_not_null())
+			.distinct()
+			.order(artwork)
+			.into_boxed();
+		if virtual_path.as_ref().parent().is_some() {
+			let real_path = vfs.virtual_to_real(virtual_path)?;
+			let directory_path_filter = {
+				let mut path_buf = real_path.clone();
+				path_buf.push("%");
+				path_buf.as_path().to_string_lossy().into_owned()
+			};
+			query = query.filter(
+				path.eq(real_path.to_string_lossy().into_owned())
+					.or(path.like(directory_path_filter)),
+			);
+		}
+		let artworks: Vec<Option<String>> = query.load(&mut connection)?;
+		Ok(artworks.into_iter().flatten().map(PathBuf::from).collect())
+	}
+
+	/// Finds an image file for an artist within the directories holding their albums. Mount
+	/// points are never considered to be artist directories.
+	pub fn get_artist_image(&self, name: &str) -> Result<PathBuf, QueryError> {
+		use self::directories::dsl::*;
+		let image_pattern = self.settings_manager.get_artist_image_pattern()?;
+		let mut connection = self.db.connect()?;
+
+		let album_parents: Vec<Option<String>> = directories
+			.select(parent)
+			.filter(artist.eq(name))
+			.distinct()
+			.load(&mut connection)?;
+		let album_parents: Vec<String> = album_parents.into_iter().flatten().collect();
+		let artist_directories: Vec<String> = directories
+			.select(path)
+			.filter(path.eq_any(&album_parents))
+			.filter(parent.is_not_null())
+			.order(path)
+			.load(&mut connection)?;
+
+		artist_directories
+			.iter()
+			.find_map(|directory| {
+				let mut images: Vec<PathBuf> = std::fs::read_dir(directory)
+					.ok()?
+					.flatten()
+					.map(|entry| entry.path())
+					.filter(|image_path| {
+						image_path.is_file()
+							&& image_path
+								.file_name()
+								.and_then(|file_name| file_name.to_str())
+								.is_some_and(|file_name| image_pattern.is_match(file_name))
+					})
+					.collect();
+				images.sort();
+				images.into_iter().next()
+			})
+			.ok_or_else(|| QueryError::ArtistImageNotFound(name.to_owned()))
+	}
+
+	pub fn get_artists(&self) -> Result<Vec<Artist>, QueryError> {
+		let settings = self.settings_manager.read()?;
+		let artist_grouping = settings.album_artist_grouping;
+		let normalizer = settings
+			.normalize_artist_names
+			.then(|| ArtistNameNormalizer::new(&settings.artist_name_articles));
+		self.aggregate_cache.get_or_compute(
+			|e| &mut e.artists,
+			(artist_grouping, normalizer.clone()),
+			|| {
+				use self::songs::dsl::*;
+				let mut connection = self.db.connect()?;
+				let rows: Vec<(Option<String>, Option<String>, Option<String>)> = songs
+					.select((effective_album_artist, artist, album))
+					.filter(hidden.eq(false))
+					.load(&mut connection)?;
+				Ok(count_artists(rows, artist_grouping, normalizer.as_ref()))
+			},
+		)
+	}
+
+	/// Counts songs and albums by release year, oldest first. Songs without a year are counted last.
+	pub fn get_years(&self) -> Result<Vec<YearCount>, QueryError> {
+		self.aggregate_cache
+			.get_or_compute(|e| &mut e.years, (), || self.count_years())
+	}
+
+	fn count_years(&self) -> Result<Vec<YearCount>, QueryError> {
+		use self::songs::dsl::*;
+		let mut connection = self.db.connect()?;
+		let rows: Vec<(Option<i32>, String)> = songs
+			.select((year, parent))
+			.filter(hidden.eq(false))
+			.load(&mut connection)?;
+
+		let mut counts: HashMap<Option<i32>, (usize, HashSet<String>)> = HashMap::new();
+		for (y, p) in rows {
+			let (song_count, albums) = counts.entry(y).or_default();
+			*song_count += 1;
+			albums.insert(p);
+		}
+
+		let mut year_counts: Vec<YearCount> = counts
+			.into_iter()
+			.map(|(y, (song_count, albums))| YearCount {
+				year: y,
+				song_count,
+				album_count: albums.len(),
+			})
+			.collect();
+		year_counts.sort_by_key(|c| (c.year.is_none(), c.year));
+		Ok(year_counts)
+	}
+
+	/// Lists albums released during the decade containing a given year, or albums without a year
+	/// when no decade is given.
+	pub fn get_decade_albums(&self, decade: Option<i32>) -> Result<Vec<Directory>, QueryError> {
+		use self::directories::dsl::*;
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let mut query = directories
+			.filter(album.is_not_null())
+			.filter(has_visible_songs())
+			.order((year, album, path))
+			.into_boxed();
+		query = match decade {
+			Some(d) => {
+				let start = d - d.rem_euclid(10);
+				query.filter(year.between(start, start + 9))
+			}
+			None => query.filter(year.is_null()),
+		};
+		let real_directories: Vec<Directory> = query.load(&mut connection)?;
+		let virtual_directories = real_directories
+			.into_iter()
+			.filter_map(|d| d.virtualize(&vfs));
+		Ok(virtual_directories.collect::<Vec<_>>())
+	}
+
+	/// Lists the record labels of visible songs, alphabetically, with their song and album counts.
+	pub fn get_labels(&self) -> Result<Vec<Label>, QueryError> {
 		let vfs = self.vfs_manager.get_vfs()?;
+		let labels =
+			self.aggregate_cache
+				.get_or_compute(|e| &mut e.labels, (), || self.count_labels())?;
+		Ok(labels
+			.into_iter()
+			.map(|mut l| {
+				l.artwork = l
+					.artwork
+					.and_then(|a| vfs.real_to_virtual(Path::new(&a)).ok())
+					.map(|a| a.to_string_lossy().into_owned());
+				l
+			})
+			.collect())
+	}
+
+	fn count_labels(&self) -> Result<Vec<Label>, QueryError> {
+		let mut connection = self.db.connect()?;
+		let rows: Vec<(Option<String>, String)> = {
+			use self::songs::dsl::*;
+			songs
+				.select((label, parent))
+				.filter(label.is_not_null())
+				.filter(hidden.eq(false))
+				.load(&mut connection)?
+		};
+		let covers: Vec<(String, Option<String>)> = {
+			use self::directories::dsl::*;
+			directories
+				.select((path, artwork))
+				.filter(artwork.is_not_null())
+				.order((year, album, path))
+				.load(&mut connection)?
+		};
+
+		let mut counts: BTreeMap<String, (usize, HashSet<String>)> = BTreeMap::new();
+		for (name, p) in rows.into_iter().filter_map(|(n, p)| Some((n?, p))) {
+			let (song_count, albums) = counts.entry(name).or_default();
+			*song_count += 1;
+			albums.insert(p);
+		}
+
+		Ok(counts
+			.into_iter()
+			.map(|(name, (song_count, albums))| Label {
+				artwork: covers
+					.iter()
+					.find(|(p, _)| albums.contains(p))
+					.and_then(|(_, a)| a.clone()),
+				name,
+				song_count,
+				album_count: albums.len(),
+			})
+			.collect())
+	}
+
+	/// Lists the albums holding visible songs released on a record label.
+	pub fn get_label_albums(&self, name: &str) -> Result<Vec<Directory>, QueryError> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let album_paths: Vec<String> = songs::table
+			.select(songs::parent)
+			.filter(songs::label.eq(name))
+			.filter(songs::hidden.eq(false))
+			.distinct()
+			.load(&mut connection)?;
+		let real_directories: Vec<Directory> = {
+			use self::directories::dsl::*;
+			directories
+				.filter(path.eq_any(album_paths))
+				.order((year, album, path))
+				.load(&mut connection)?
+		};
+		Ok(real_directories
+			.into_iter()
+			.filter_map(|d| d.virtualize(&vfs))
+			.collect())
+	}
+
+	/// Lists the distinct grouping labels (e.g. DJ crates or classical groupings) of visible songs.
+	pub fn get_groupings(&self) -> Result<Vec<String>, QueryError> {
+		use self::songs::dsl::*;
+		let mut connection = self.db.connect()?;
+		let labels: Vec<Option<String>> = songs
+			.select(grouping)
+			.filter(grouping.is_not_null())
+			.filter(hidden.eq(false))
+			.distinct()
+			.order(grouping)
+			.load(&mut connection)?;
+		Ok(labels.into_iter().flatten().collect())
+	}
+
+	/// Lists the visible songs sharing a grouping label.
+	pub fn get_grouping_songs(&self, name: &str) -> Result<Vec<Song>, QueryError> {
+		use self::songs::dsl::*;
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let real_songs: Vec<Song> = songs
+			.filter(grouping.eq(name))
+			.filter(hidden.eq(false))
+			.order(path)
+			.load(&mut connection)?;
+		Ok(real_songs
+			.into_iter()
+			.filter_map(|s| s.virtualize(&vfs))
+			.collect())
+	}
+
+	/// Lists the distinct languages of visible songs.
+	pub fn get_languages(&self) -> Result<Vec<String>, QueryError> {
+		use self::songs::dsl::*;
+		let mut connection = self.db.connect()?;
+		let values: Vec<Option<String>> = songs
+			.select(language)
+			.filter(language.is_not_null())
+			.filter(hidden.eq(false))
+			.distinct()
+			.load(&mut connection)?;
+		let languages: BTreeSet<String> = values
+			.iter()
+			.flat_map(|v| split_names(v.as_deref()))
+			.collect();
+		Ok(languages.into_iter().collect())
+	}
+
+	/// Lists the visible songs in a given language, which can be given by code or English name.
+	pub fn get_language_songs(&self, name: &str) -> Result<Vec<Song>, QueryError> {
+		let code = super::metadata::normalize_language(name);
+		use self::songs::dsl::*;
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let real_songs: Vec<Song> = songs
+			.filter(language.like(format!("%{}%", code)))
+			.filter(hidden.eq(false))
+			.order(path)
+			.load(&mut connection)?;
+		Ok(real_songs
+			.into_iter()
+			.filter(|s| {
+				split_names(s.language.as_deref())
+					.iter()
+					.any(|l| l.eq_ignore_ascii_case(&code))
+			})
+			.filter_map(|s| s.virtualize(&vfs))
+			.collect())
+	}
+
+	/// Counts indexed songs by audio format, most common formats first.
+	pub fn get_format_counts(&self) -> Result<Vec<FormatCount>, QueryError> {
+		self.aggregate_cache
+			.get_or_compute(|e| &mut e.formats, (), || self.count_formats())
+	}
+
+	fn count_formats(&self) -> Result<Vec<FormatCount>, QueryError> {
+		use self::songs::dsl::*;
 		let mut connection = self.db.connect()?;
-		let like_test = format!("%{}%", query);
+		let formats: Vec<Option<String>> = songs.select(format).load(&mut connection)?;
+
+		let mut counts: HashMap<String, usize> = HashMap::new();
+		for f in formats.into_iter().flatten() {
+			*counts.entry(f).or_default() += 1;
+		}
+
+		let mut format_counts: Vec<FormatCount> = counts
+			.into_iter()
+			.map(|(f, count)| FormatCount { format: f, count })
+			.collect();
+		format_counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.format.cmp(&b.format)));
+		Ok(format_counts)
+	}
+
+	/// Finds directories and songs matching a query. Queries can contain `field:value` clauses
+	/// (e.g. `composer:Bach genre:Baroque`), which all need to match along with any free text.
+	/// Only songs can match field clauses.
+	pub fn search(&self, query: &str) -> Result<Vec<CollectionFile>, QueryError> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect_read()?;
+		let search_query = parse_search_query(query);
+		let like_test = format!("%{}%", search_query.text);
 		let mut output = Vec::new();
 
 		// Find dirs with matching path and parent not matching
-		{
+		if search_query.clauses.is_empty() {
 			use self::directories::dsl::*;
 			let real_directories: Vec<Directory> = directories
 				.filter(path.like(&like_test))
@@ -144,19 +1045,13 @@ impl Index {
 			output.extend(virtual_directories.map(CollectionFile::Directory));
 		}
 
-		// Find songs with matching title/album/artist and non-matching parent
+		// Find songs with matching title/album/artist and non-matching parent, and matching fields
 		{
-			use self::songs::dsl::*;
-			let real_songs: Vec<Song> = songs
-				.filter(
-					path.like(&like_test)
-						.or(title.like(&like_test))
-						.or(album.like(&like_test))
-						.or(artist.like(&like_test))
-						.or(album_artist.like(&like_test)),
-				)
-				.filter(parent.not_like(&like_test))
-				.load(&mut connection)?;
+			let mut query = search_songs(&search_query);
+			if !search_query.text.is_empty() || search_query.clauses.is_empty() {
+				query = query.filter(songs::parent.not_like(like_test.clone()));
+			}
+			let real_songs: Vec<Song> = query.load(&mut connection)?;
 
 			let virtual_songs = real_songs.into_iter().filter_map(|d| d.virtualize(&vfs));
 
@@ -166,6 +1061,71 @@ impl Index {
 		Ok(output)
 	}
 
+	/// Runs a search and groups results into songs, albums and artists. Each section holds up to
+	/// `count` entries.
+	pub fn search_grouped(&self, query: &str, count: i64) -> Result<SearchResults, QueryError> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect_read()?;
+		let search_query = parse_search_query(query);
+
+		let real_songs: Vec<Song> = search_songs(&search_query)
+			.order(songs::path)
+			.limit(count)
+			.load(&mut connection)?;
+
+		let album_paths: Vec<String> = search_songs(&search_query)
+			.select(songs::parent)
+			.distinct()
+			.load(&mut connection)?;
+		let real_albums: Vec<Directory> = {
+			use self::directories::dsl::*;
+			directories
+				.filter(path.eq_any(album_paths))
+				.filter(album.is_not_null())
+				.order((album, path))
+				.limit(count)
+				.load(&mut connection)?
+		};
+
+		// Artist names are matched against the free text and artist clauses. When there are
+		// neither, every artist credited on a matching song is listed.
+		let mut patterns: Vec<String> = search_query
+			.clauses
+			.iter()
+			.filter(|(f, _)| *f == SearchField::Artist || *f == SearchField::AlbumArtist)
+			.map(|(_, v)| v.to_lowercase())
+			.collect();
+		if !search_query.text.is_empty() {
+			patterns.push(search_query.text.to_lowercase());
+		}
+		let credits: Vec<(Option<String>, Option<String>)> = search_songs(&search_query)
+			.select((songs::artist, songs::album_artist))
+			.distinct()
+			.load(&mut connection)?;
+		let artists: BTreeMap<String, String> = credits
+			.iter()
+			.flat_map(|(a, aa)| a.iter().chain(aa.iter()))
+			.flat_map(|names| split_artist_names(names))
+			.filter(|n| {
+				let name = n.to_lowercase();
+				patterns.is_empty() || patterns.iter().any(|p| name.contains(p))
+			})
+			.map(|n| (n.to_lowercase(), n.to_owned()))
+			.collect();
+
+		Ok(SearchResults {
+			songs: real_songs
+				.into_iter()
+				.filter_map(|s| s.virtualize(&vfs))
+				.collect(),
+			albums: real_albums
+				.into_iter()
+				.filter_map(|d| d.virtualize(&vfs))
+				.collect(),
+			artists: artists.into_values().take(count as usize).collect(),
+		})
+	}
+
 	pub fn get_song(&self, virtual_path: &Path) -> Result<Song, QueryError> {
 		let vfs = self.vfs_manager.get_vfs()?;
 		let mut connection = self.db.connect()?;
@@ -174,13 +1134,224 @@ impl Index {
 		let real_path_string = real_path.as_path().to_string_lossy();
 
 		use self::songs::dsl::*;
-		let real_song: Song = songs
+		let real_song: Option<Song> = songs
 			.filter(path.eq(real_path_string))
-			.get_result(&mut connection)?;
+			.get_result(&mut connection)
+			.optional()?;
 
-		match real_song.virtualize(&vfs) {
+		match real_song.and_then(|s| s.virtualize(&vfs)) {
 			Some(s) => Ok(s),
 			None => Err(QueryError::SongNotFound(real_path)),
 		}
 	}
+
+	/// Finds the songs played before and after a song within its directory, in album order.
+	/// Hidden songs are skipped.
+	pub fn get_song_siblings(&self, virtual_path: &Path) -> Result<SongSiblings, QueryError> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect_read()?;
+
+		let real_path = vfs.virtual_to_real(virtual_path)?;
+		let real_path_string = real_path.as_path().to_string_lossy().into_owned();
+
+		use self::songs::dsl::*;
+		let song: Song = songs
+			.filter(path.eq(&real_path_string))
+			.get_result(&mut connection)
+			.optional()?
+			.ok_or(QueryError::SongNotFound(real_path))?;
+		let mut directory_songs: Vec<Song> = songs
+			.filter(parent.eq(&song.parent))
+			.filter(hidden.eq(false).or(path.eq(&real_path_string)))
+			.load(&mut connection)?;
+		directory_songs.sort_by(Song::cmp_album_order);
+
+		let position = directory_songs
+			.iter()
+			.position(|s| s.path == song.path)
+			.unwrap_or_default();
+		let previous = position
+			.checked_sub(1)
+			.and_then(|i| directory_songs.get(i).cloned());
+		let next = directory_songs.get(position + 1).cloned();
+		Ok(SongSiblings {
+			previous: previous.and_then(|s| s.virtualize(&vfs)),
+			next: next.and_then(|s| s.virtualize(&vfs)),
+		})
+	}
+
+	/// Finds an indexed directory along with its songs, sorted by disc and track number.
+	pub fn get_album_details(&self, virtual_path: &Path) -> Result<AlbumDetails, QueryError> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect_read()?;
+
+		let real_path = vfs.virtual_to_real(virtual_path)?;
+		let real_path_string = real_path.as_path().to_string_lossy().into_owned();
+
+		let directory = directories::table
+			.filter(directories::path.eq(&real_path_string))
+			.get_result::<Directory>(&mut connection)
+			.optional()?
+			.and_then(|d| d.virtualize(&vfs))
+			.ok_or_else(|| QueryError::PathNotFound(virtual_path.to_owned()))?;
+		let mut songs = self.flatten(virtual_path, false)?;
+		songs.sort_by(Song::cmp_album_order);
+		Ok(AlbumDetails { directory, songs })
+	}
+
+	/// Lists the indexed directories containing a virtual path, from the top-level mount down to
+	/// the path itself when it is a directory.
+	pub fn get_ancestors(&self, virtual_path: &Path) -> Result<Vec<Directory>, QueryError> {
+		use self::directories::dsl::*;
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+
+		let real_path = vfs.virtual_to_real(virtual_path)?;
+		let candidates: Vec<String> = real_path
+			.ancestors()
+			.map(|p| p.to_string_lossy().into_owned())
+			.collect();
+		let mut real_directories: Vec<Directory> = directories
+			.filter(path.eq_any(&candidates))
+			.load(&mut connection)?;
+		if real_directories.is_empty() {
+			return Err(QueryError::PathNotFound(virtual_path.to_owned()));
+		}
+		real_directories.sort_by_key(|d| d.path.len());
+		Ok(real_directories
+			.into_iter()
+			.filter_map(|d| d.virtualize(&vfs))
+			.collect())
+	}
+
+	/// Looks up several songs at once, by virtual path.
+	pub fn get_songs(&self, virtual_paths: &[String]) -> Result<SongBatch, QueryError> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+
+		let real_paths: Vec<Option<String>> = virtual_paths
+			.iter()
+			.map(|p| {
+				vfs.virtual_to_real(Path::new(p))
+					.ok()
+					.map(|p| p.to_string_lossy().into_owned())
+			})
+			.collect();
+
+		let mut found_songs: HashMap<String, Song> = HashMap::new();
+		let lookup_paths: Vec<&String> = real_paths.iter().flatten().collect();
+		// Query in chunks to stay within SQLite's limit on bound parameters
+		for chunk in lookup_paths.chunks(1000) {
+			use self::songs::dsl::*;
+			let chunk_songs: Vec<Song> = songs.filter(path.eq_any(chunk)).load(&mut connection)?;
+			found_songs.extend(chunk_songs.into_iter().map(|s| (s.path.clone(), s)));
+		}
+
+		let mut batch = SongBatch::default();
+		for (virtual_path, real_path) in virtual_paths.iter().zip(real_paths) {
+			let song = real_path
+				.and_then(|p| found_songs.get(&p).cloned())
+				.and_then(|s| s.virtualize(&vfs));
+			match song {
+				Some(s) => batch.songs.push(s),
+				None => batch.not_found.push(virtual_path.clone()),
+			}
+		}
+		Ok(batch)
+	}
+
+	/// Returns whether at least one directory has been added to the index.
+	pub fn has_indexed_content(&self) -> Result<bool, QueryError> {
+		let mut connection = self.db.connect()?;
+		let indexed = diesel::select(exists(directories::table.select(directories::id)))
+			.get_result(&mut connection)?;
+		Ok(indexed)
+	}
+}
+
+#[test]
+fn counts_multi_valued_album_artists() {
+	let rows = vec![
+		(Some("A; B".to_owned()), None, Some("Split".to_owned())),
+		(Some("A".to_owned()), None, Some("Solo".to_owned())),
+		(None, Some("C".to_owned()), None),
+	];
+	let artists = count_artists(rows, AlbumArtistGrouping::Each, None);
+	assert_eq!(
+		artists,
+		vec![
+			Artist {
+				name: "A".to_owned(),
+				album_count: 2,
+				track_count: 2,
+			},
+			Artist {
+				name: "B".to_owned(),
+				album_count: 1,
+				track_count: 1,
+			},
+			Artist {
+				name: "C".to_owned(),
+				album_count: 0,
+				track_count: 1,
+			},
+		]
+	);
+}
+
+#[test]
+fn groups_artist_name_spellings_under_normalized_key() {
+	let normalizer = ArtistNameNormalizer::new(&["the".to_owned(), "a".to_owned()]);
+	assert_eq!(normalizer.key("Beatles, The"), "beatles");
+	assert_eq!(normalizer.key(" The  Beatles "), "beatles");
+	assert_eq!(normalizer.key("Theatre of Tragedy"), "theatre of tragedy");
+	assert_eq!(normalizer.key("The The"), "the");
+
+	let rows = vec![
+		(
+			Some("The Beatles".to_owned()),
+			None,
+			Some("Abbey Road".to_owned()),
+		),
+		(
+			Some("The Beatles".to_owned()),
+			None,
+			Some("Abbey Road".to_owned()),
+		),
+		(
+			Some("Beatles, The".to_owned()),
+			None,
+			Some("Help!".to_owned()),
+		),
+	];
+	let artists = count_artists(rows, AlbumArtistGrouping::Each, Some(&normalizer));
+	assert_eq!(
+		artists,
+		vec![Artist {
+			name: "The Beatles".to_owned(),
+			album_count: 2,
+			track_count: 3,
+		}]
+	);
+}
+
+#[test]
+fn parses_search_query_clauses() {
+	assert_eq!(
+		parse_search_query(r#"composer:Bach Genre:"Early Baroque" toccata AC:DC"#),
+		SearchQuery {
+			text: "toccata AC:DC".to_owned(),
+			clauses: vec![
+				(SearchField::Composer, "Bach".to_owned()),
+				(SearchField::Genre, "Early Baroque".to_owned()),
+			],
+		}
+	);
+	assert_eq!(
+		parse_search_query("door"),
+		SearchQuery {
+			text: "door".to_owned(),
+			clauses: Vec::new(),
+		}
+	);
 }
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Keeps track of when each mount is next due for an automatic scan.
+/// Each mount follows its own interval, regardless of when other mounts are scanned.
+pub(super) struct ScanSchedule {
+	start: Instant,
+	scan_on_start: bool,
+	next_scans: HashMap<String, Instant>,
+}
+
+impl ScanSchedule {
+	pub fn new(start: Instant, scan_on_start: bool) -> Self {
+		Self {
+			start,
+			scan_on_start,
+			next_scans: HashMap::new(),
+		}
+	}
+
+	/// Returns the names of the mounts due for a scan at `now`, and schedules their next scan one
+	/// interval later. Mounts are first due when the schedule starts, or one interval after that
+	/// when not scanning on start.
+	pub fn take_due(&mut self, now: Instant, intervals: &HashMap<String, Duration>) -> Vec<String> {
+		self.next_scans
+			.retain(|name, _| intervals.contains_key(name));
+
+		let mut due = Vec::new();
+		for (name, interval) in intervals {
+			let first_scan = match self.scan_on_start {
+				true => self.start,
+				false => self.start + *interval,
+			};
+			let next_scan = self.next_scans.entry(name.clone()).or_insert(first_scan);
+			if *next_scan <= now {
+				*next_scan = now + *interval;
+				due.push(name.clone());
+			}
+		}
+		due.sort();
+		due
+	}
+
+	/// Returns when the next scan is due, if any mount is scheduled.
+	pub fn next_scan(&self) -> Option<Instant> {
+		self.next_scans.values().min().copied()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn mounts_are_scheduled_independently() {
+		let start = Instant::now();
+		let intervals = HashMap::from([
+			("local".to_owned(), Duration::from_secs(60)),
+			("nas".to_owned(), Duration::from_secs(600)),
+		]);
+		let mut schedule = ScanSchedule::new(start, true);
+
+		assert_eq!(schedule.take_due(start, &intervals), vec!["local", "nas"]);
+		assert_eq!(schedule.next_scan(), Some(start + Duration::from_secs(60)));
+
+		let mut local_scans = 0;
+		let mut nas_scans = 0;
+		for minute in 1..=10 {
+			let now = start + Duration::from_secs(60 * minute);
+			for name in schedule.take_due(now, &intervals) {
+				match name.as_str() {
+					"local" => local_scans += 1,
+					"nas" => nas_scans += 1,
+					_ => unreachable!(),
+				}
+			}
+		}
+		assert_eq!(local_scans, 10);
+		assert_eq!(nas_scans, 1);
+	}
+
+	#[test]
+	fn first_scans_wait_for_interval_when_not_scanning_on_start() {
+		let start = Instant::now();
+		let intervals = HashMap::from([
+			("local".to_owned(), Duration::from_secs(60)),
+			("nas".to_owned(), Duration::from_secs(600)),
+		]);
+		let mut schedule = ScanSchedule::new(start, false);
+
+		assert!(schedule.take_due(start, &intervals).is_empty());
+		let now = start + Duration::from_secs(60);
+		assert_eq!(schedule.take_due(now, &intervals), vec!["local"]);
+		assert_eq!(schedule.next_scan(), Some(now + Duration::from_secs(60)));
+	}
+}
@@ -1,6 +1,11 @@
 use diesel::prelude::*;
+use id3::TagLike;
+use std::collections::HashMap;
 use std::default::Default;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use super::*;
 use crate::app::test;
@@ -9,6 +14,22 @@ use crate::test_name;
 
 const TEST_MOUNT_NAME: &str = "root";
 
+/// Mounts the small collection as `remote`, served by a mock storage backend.
+fn mount_remote_collection(ctx: &test::Context) -> Arc<test::MockBackend> {
+	let backend = test::MockBackend::new("test-data/small-collection");
+	ctx.vfs_manager.register_backend("mock", backend.factory());
+	ctx.vfs_manager
+		.set_mount_dirs(&[vfs::MountDir {
+			source: "mock://collection".to_owned(),
+			name: "remote".to_owned(),
+			album_art_pattern: None,
+			reindex_every_n_seconds: None,
+			hide_from_root: false,
+		}])
+		.unwrap();
+	backend
+}
+
 #[test]
 fn update_adds_new_content() {
 	let ctx = test::ContextBuilder::new(test_name!())
@@ -25,6 +46,61 @@ fn update_adds_new_content() {
 	assert_eq!(all_songs.len(), 13);
 }
 
+#[test]
+fn mount_updates_leave_other_mounts_untouched() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.mount("formats", "test-data/formats")
+		.build();
+
+	ctx.index.update_mounts(&["formats".to_owned()]).unwrap();
+	assert!(ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap()
+		.is_empty());
+	let num_formats = ctx
+		.index
+		.flatten(Path::new("formats"), false)
+		.unwrap()
+		.len();
+	assert!(num_formats > 0);
+
+	ctx.index
+		.update_mounts(&[TEST_MOUNT_NAME.to_owned()])
+		.unwrap();
+	assert_eq!(
+		ctx.index
+			.flatten(Path::new(TEST_MOUNT_NAME), false)
+			.unwrap()
+			.len(),
+		13
+	);
+	assert_eq!(
+		ctx.index
+			.flatten(Path::new("formats"), false)
+			.unwrap()
+			.len(),
+		num_formats
+	);
+}
+
+#[test]
+fn update_completes_with_small_scan_channels() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.set_scan_channel_capacity(1);
+
+	ctx.index.update().unwrap();
+
+	let mut connection = ctx.db.connect().unwrap();
+	let all_directories: Vec<Directory> = directories::table.load(&mut connection).unwrap();
+	let all_songs: Vec<Song> = songs::table.load(&mut connection).unwrap();
+	assert_eq!(all_directories.len(), 6);
+	assert_eq!(all_songs.len(), 13);
+}
+
 #[test]
 fn update_removes_missing_content() {
 	let builder = test::ContextBuilder::new(test_name!());
@@ -66,6 +142,110 @@ fn update_removes_missing_content() {
 	}
 }
 
+#[test]
+fn browsing_during_update_sees_complete_snapshots() {
+	let builder = test::ContextBuilder::new(test_name!());
+
+	let original_collection_dir: PathBuf = ["test-data", "small-collection"].iter().collect();
+	let test_collection_dir: PathBuf = builder.test_directory.join("small-collection");
+	let copy_options = fs_extra::dir::CopyOptions::new();
+	fs_extra::dir::copy(
+		original_collection_dir,
+		&builder.test_directory,
+		&copy_options,
+	)
+	.unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, test_collection_dir.to_str().unwrap())
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			snapshot_index_updates: Some(true),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+
+	let list_songs = |index: &Index| -> Vec<String> {
+		let mut paths: Vec<String> = index
+			.flatten(Path::new(TEST_MOUNT_NAME), true)
+			.unwrap()
+			.into_iter()
+			.map(|s| s.path)
+			.collect();
+		paths.sort();
+		paths
+	};
+	let before = list_songs(&ctx.index);
+
+	// Move an album, so that a partial update would show it missing, or in both locations
+	std::fs::rename(
+		test_collection_dir.join("Khemmis"),
+		test_collection_dir.join("Khemmis (Moved)"),
+	)
+	.unwrap();
+
+	let done = Arc::new(AtomicBool::new(false));
+	let reader = {
+		let done = done.clone();
+		let index = ctx.index.clone();
+		std::thread::spawn(move || {
+			let mut observed = Vec::new();
+			while !done.load(Ordering::SeqCst) {
+				observed.push(list_songs(&index));
+			}
+			observed
+		})
+	};
+
+	ctx.index.update().unwrap();
+	done.store(true, Ordering::SeqCst);
+	let observed = reader.join().unwrap();
+	let after = list_songs(&ctx.index);
+
+	assert_ne!(before, after);
+	assert_eq!(after.len(), 13);
+	for snapshot in observed {
+		assert!(snapshot == before || snapshot == after);
+	}
+}
+
+#[test]
+fn scanned_directories_are_browsable_before_scan_completes() {
+	let ctx = test::ContextBuilder::new(test_name!()).build();
+	let backend = mount_remote_collection(&ctx);
+	// The sibling of `Khemmis` keeps being scanned until its listing is released
+	backend.hold_listing("Tobokegao");
+
+	let update_index = ctx.index.clone();
+	let update = std::thread::spawn(move || update_index.update().unwrap());
+
+	let list_directories = || -> Vec<String> {
+		ctx.index
+			.browse(Path::new("remote"), false)
+			.unwrap_or_default()
+			.into_iter()
+			.filter_map(|f| match f {
+				CollectionFile::Directory(d) => Some(d.path),
+				_ => None,
+			})
+			.collect()
+	};
+	let deadline = Instant::now() + Duration::from_secs(10);
+	let mut directories = list_directories();
+	while directories.is_empty() && Instant::now() < deadline {
+		std::thread::sleep(Duration::from_millis(50));
+		directories = list_directories();
+	}
+
+	backend.release_listing();
+	update.join().unwrap();
+	let khemmis: PathBuf = ["remote", "Khemmis"].iter().collect();
+	assert_eq!(directories, vec![khemmis.to_string_lossy().into_owned()]);
+	assert_eq!(list_directories().len(), 2);
+}
+
 #[test]
 fn can_browse_top_level() {
 	let ctx = test::ContextBuilder::new(test_name!())
@@ -74,7 +254,7 @@ fn can_browse_top_level() {
 	ctx.index.update().unwrap();
 
 	let root_path = Path::new(TEST_MOUNT_NAME);
-	let files = ctx.index.browse(Path::new("")).unwrap();
+	let files = ctx.index.browse(Path::new(""), false).unwrap();
 	assert_eq!(files.len(), 1);
 	match files[0] {
 		CollectionFile::Directory(ref d) => assert_eq!(d.path, root_path.to_str().unwrap()),
@@ -92,7 +272,7 @@ fn can_browse_directory() {
 		.build();
 	ctx.index.update().unwrap();
 
-	let files = ctx.index.browse(Path::new(TEST_MOUNT_NAME)).unwrap();
+	let files = ctx.index.browse(Path::new(TEST_MOUNT_NAME), false).unwrap();
 
 	assert_eq!(files.len(), 2);
 	match files[0] {
@@ -106,13 +286,106 @@ fn can_browse_directory() {
 	}
 }
 
+#[test]
+fn can_browse_song() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let song_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "05 - Hunted.mp3"]
+		.iter()
+		.collect();
+	let files = ctx.index.browse(&song_path, false).unwrap();
+	assert_eq!(files.len(), 1);
+	match files[0] {
+		CollectionFile::Song(ref s) => assert_eq!(s.path, song_path.to_str().unwrap()),
+		_ => panic!("Expected song"),
+	}
+}
+
+#[test]
+fn browsing_missing_path_is_an_error() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let missing_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Not An Album"]
+		.iter()
+		.collect();
+	assert!(matches!(
+		ctx.index.browse(&missing_path, false),
+		Err(QueryError::PathNotFound(_))
+	));
+}
+
+#[test]
+fn directory_artwork_mime_is_reported() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let picnic_path: PathBuf = [TEST_MOUNT_NAME, "Tobokegao"].iter().collect();
+	let files = ctx.index.browse(&picnic_path, false).unwrap();
+	let picnic = files
+		.iter()
+		.find_map(|f| match f {
+			CollectionFile::Directory(d) if d.path.ends_with("Picnic") => Some(d),
+			_ => None,
+		})
+		.unwrap();
+	assert_eq!(picnic.artwork_mime, Some("image/png".to_owned()));
+
+	let hunted_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis"].iter().collect();
+	let files = ctx.index.browse(&hunted_path, false).unwrap();
+	match files[0] {
+		CollectionFile::Directory(ref d) => {
+			assert_eq!(d.artwork_mime, Some("image/jpeg".to_owned()))
+		}
+		_ => panic!("Expected directory"),
+	}
+}
+
+#[test]
+fn directory_duration_is_sum_of_song_durations() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("durations");
+	let album_dir = collection_dir.join("Album");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	let song_path: PathBuf = ["test-data", "formats", "sample.opus"].iter().collect();
+	std::fs::copy(&song_path, album_dir.join("01.opus")).unwrap();
+	std::fs::copy(&song_path, album_dir.join("02.opus")).unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	let album_path: PathBuf = [TEST_MOUNT_NAME, "Album"].iter().collect();
+	let songs = ctx.index.flatten(&album_path, false).unwrap();
+	assert_eq!(songs.len(), 2);
+	let expected_duration: i32 = songs.iter().filter_map(|s| s.duration).sum();
+	assert!(expected_duration > 0);
+
+	let files = ctx.index.browse(Path::new(TEST_MOUNT_NAME), false).unwrap();
+	match files[0] {
+		CollectionFile::Directory(ref d) => assert_eq!(d.duration, Some(expected_duration)),
+		_ => panic!("Expected directory"),
+	}
+}
+
 #[test]
 fn can_flatten_root() {
 	let ctx = test::ContextBuilder::new(test_name!())
 		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
 		.build();
 	ctx.index.update().unwrap();
-	let songs = ctx.index.flatten(Path::new(TEST_MOUNT_NAME)).unwrap();
+	let songs = ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap();
 	assert_eq!(songs.len(), 13);
 	assert_eq!(songs[0].title, Some("Above The Water".to_owned()));
 }
@@ -124,7 +397,7 @@ fn can_flatten_directory() {
 		.build();
 	ctx.index.update().unwrap();
 	let path: PathBuf = [TEST_MOUNT_NAME, "Tobokegao"].iter().collect();
-	let songs = ctx.index.flatten(path).unwrap();
+	let songs = ctx.index.flatten(path, false).unwrap();
 	assert_eq!(songs.len(), 8);
 }
 
@@ -135,17 +408,104 @@ fn can_flatten_directory_with_shared_prefix() {
 		.build();
 	ctx.index.update().unwrap();
 	let path: PathBuf = [TEST_MOUNT_NAME, "Tobokegao", "Picnic"].iter().collect(); // Prefix of '(Picnic Remixes)'
-	let songs = ctx.index.flatten(path).unwrap();
+	let songs = ctx.index.flatten(path, false).unwrap();
 	assert_eq!(songs.len(), 7);
 }
 
+#[test]
+fn hidden_songs_are_excluded_by_default() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/hidden-songs")
+		.build();
+	ctx.index.update().unwrap();
+
+	let songs = ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap();
+	assert_eq!(songs.len(), 1);
+	assert_eq!(songs[0].title, Some("Visible".to_owned()));
+
+	let files = ctx.index.browse(Path::new(TEST_MOUNT_NAME), false).unwrap();
+	assert_eq!(files.len(), 1);
+}
+
+#[test]
+fn hidden_songs_are_included_when_requested() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/hidden-songs")
+		.build();
+	ctx.index.update().unwrap();
+
+	let songs = ctx.index.flatten(Path::new(TEST_MOUNT_NAME), true).unwrap();
+	assert_eq!(songs.len(), 2);
+	assert!(!songs[0].hidden);
+	assert!(songs[1].hidden);
+	assert_eq!(songs[1].title, Some("Bonus".to_owned()));
+
+	let files = ctx.index.browse(Path::new(TEST_MOUNT_NAME), true).unwrap();
+	assert_eq!(files.len(), 2);
+}
+
+#[test]
+fn can_get_artists() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+	let artists = ctx.index.get_artists().unwrap();
+	assert_eq!(
+		artists,
+		vec![
+			Artist {
+				name: "Khemmis".to_owned(),
+				album_count: 1,
+				track_count: 5,
+			},
+			Artist {
+				name: "Tobokegao".to_owned(),
+				album_count: 2,
+				track_count: 8,
+			},
+		]
+	);
+}
+
+#[test]
+fn can_count_songs_by_format() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.mount("formats", "test-data/formats")
+		.build();
+	ctx.index.update().unwrap();
+
+	let format_count = |format: &str, count: usize| FormatCount {
+		format: format.to_owned(),
+		count,
+	};
+	assert_eq!(
+		ctx.index.get_format_counts().unwrap(),
+		vec![
+			format_count("mp3", 14),
+			format_count("aiff", 1),
+			format_count("ape", 1),
+			format_count("flac", 1),
+			format_count("mp4", 1),
+			format_count("ogg", 1),
+			format_count("opus", 1),
+			format_count("wave", 1),
+			format_count("wma", 1),
+		]
+	);
+}
+
 #[test]
 fn can_get_random_albums() {
 	let ctx = test::ContextBuilder::new(test_name!())
 		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
 		.build();
 	ctx.index.update().unwrap();
-	let albums = ctx.index.get_random_albums(1).unwrap();
+	let albums = ctx.index.get_random_albums(1, false).unwrap();
 	assert_eq!(albums.len(), 1);
 }
 
@@ -155,11 +515,37 @@ fn can_get_recent_albums() {
 		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
 		.build();
 	ctx.index.update().unwrap();
-	let albums = ctx.index.get_recent_albums(2).unwrap();
+	let albums = ctx.index.get_recent_albums(2, false).unwrap();
 	assert_eq!(albums.len(), 2);
 	assert!(albums[0].date_added >= albums[1].date_added);
 }
 
+#[test]
+fn songs_report_file_size_and_format() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+
+	ctx.index.update().unwrap();
+
+	let real_path: PathBuf = [
+		"test-data",
+		"small-collection",
+		"Khemmis",
+		"Hunted",
+		"02 - Candlelight.mp3",
+	]
+	.iter()
+	.collect();
+	let virtual_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let song = ctx.index.get_song(&virtual_path).unwrap();
+	let expected_size = std::fs::metadata(&real_path).unwrap().len() as i64;
+	assert_eq!(song.file_size, Some(expected_size));
+	assert_eq!(song.format, Some("mp3".to_owned()));
+}
+
 #[test]
 fn can_get_a_song() {
 	let ctx = test::ContextBuilder::new(test_name!())
@@ -205,6 +591,112 @@ fn indexes_embedded_artwork() {
 	);
 }
 
+#[test]
+fn folder_artwork_takes_priority_when_configured() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			artwork_priority: Some(settings::ArtworkPriority::Folder),
+			..Default::default()
+		})
+		.unwrap();
+
+	ctx.index.update().unwrap();
+
+	let picnic_virtual_dir: PathBuf = [TEST_MOUNT_NAME, "Tobokegao", "Picnic"].iter().collect();
+	let song_virtual_path = picnic_virtual_dir.join("07 - なぜ (Why).mp3");
+	let artwork_virtual_path = picnic_virtual_dir.join("Folder.png");
+
+	let song = ctx.index.get_song(&song_virtual_path).unwrap();
+	assert_eq!(
+		song.artwork,
+		Some(artwork_virtual_path.to_string_lossy().into_owned())
+	);
+
+	ctx.index.refresh(&song_virtual_path).unwrap();
+	let song = ctx.index.get_song(&song_virtual_path).unwrap();
+	assert_eq!(
+		song.artwork,
+		Some(artwork_virtual_path.to_string_lossy().into_owned())
+	);
+}
+
+#[test]
+fn smaller_artwork_is_picked_when_configured() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	// Large embedded artwork next to a tiny folder image
+	let small_folder_dir = collection_dir.join("SmallFolder");
+	std::fs::create_dir_all(&small_folder_dir).unwrap();
+	std::fs::copy(
+		"test-data/small-collection/Tobokegao/Picnic/07 - なぜ (Why).mp3",
+		small_folder_dir.join("01.mp3"),
+	)
+	.unwrap();
+	std::fs::copy(
+		"test-data/artwork/Folder.png",
+		small_folder_dir.join("Folder.png"),
+	)
+	.unwrap();
+	// Tiny embedded artwork next to a large folder image
+	let large_folder_dir = collection_dir.join("LargeFolder");
+	std::fs::create_dir_all(&large_folder_dir).unwrap();
+	std::fs::copy(
+		"test-data/artwork/sample.mp3",
+		large_folder_dir.join("01.mp3"),
+	)
+	.unwrap();
+	std::fs::copy(
+		"test-data/small-collection/Khemmis/Hunted/Folder.jpg",
+		large_folder_dir.join("Folder.jpg"),
+	)
+	.unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			artwork_priority: Some(settings::ArtworkPriority::Smaller),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+
+	let virtual_path = |components: &[&str]| -> PathBuf {
+		std::iter::once(TEST_MOUNT_NAME)
+			.chain(components.iter().copied())
+			.collect()
+	};
+	let cases = [
+		(
+			virtual_path(&["SmallFolder", "01.mp3"]),
+			virtual_path(&["SmallFolder", "Folder.png"]),
+		),
+		(
+			virtual_path(&["LargeFolder", "01.mp3"]),
+			virtual_path(&["LargeFolder", "01.mp3"]),
+		),
+	];
+
+	for (song_virtual_path, artwork_virtual_path) in &cases {
+		let song = ctx.index.get_song(song_virtual_path).unwrap();
+		assert_eq!(
+			song.artwork,
+			Some(artwork_virtual_path.to_string_lossy().into_owned())
+		);
+
+		ctx.index.refresh(song_virtual_path).unwrap();
+		let song = ctx.index.get_song(song_virtual_path).unwrap();
+		assert_eq!(
+			song.artwork,
+			Some(artwork_virtual_path.to_string_lossy().into_owned())
+		);
+	}
+}
+
 #[test]
 fn album_art_pattern_is_case_insensitive() {
 	let ctx = test::ContextBuilder::new(test_name!())
@@ -224,10 +716,1530 @@ fn album_art_pattern_is_case_insensitive() {
 
 		let hunted_virtual_dir: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
 		let artwork_virtual_path = hunted_virtual_dir.join("Folder.jpg");
-		let song = &ctx.index.flatten(&hunted_virtual_dir).unwrap()[0];
+		let song = &ctx.index.flatten(&hunted_virtual_dir, false).unwrap()[0];
 		assert_eq!(
 			song.artwork,
 			Some(artwork_virtual_path.to_string_lossy().into_owned())
 		);
 	}
 }
+
+#[test]
+fn paused_update_waits_for_resume() {
+	let ctx = test::ContextBuilder::new(test_name!()).build();
+	let backend = mount_remote_collection(&ctx);
+	// Albums of `Tobokegao` are only found once its listing is released
+	backend.hold_listing("Tobokegao");
+
+	let events = ctx.index.subscribe_to_scan_events();
+	let update_index = ctx.index.clone();
+	let update = std::thread::spawn(move || update_index.update().unwrap());
+	events
+		.iter()
+		.find(|e| matches!(e, ScanEvent::DirectoryEntered { path } if path.ends_with("Tobokegao")))
+		.unwrap();
+
+	let control = ctx.index.update_control();
+	control.pause();
+	assert_eq!(control.status(), UpdateStatus::Paused);
+	backend.release_listing();
+
+	let count_songs = || {
+		ctx.index
+			.flatten(Path::new("remote"), false)
+			.map(|songs| songs.len())
+			.unwrap_or_default()
+	};
+	// Leaves time for songs read before the pause to be written
+	std::thread::sleep(Duration::from_secs(1));
+	let num_songs = count_songs();
+	std::thread::sleep(Duration::from_secs(1));
+	assert_eq!(count_songs(), num_songs);
+	assert!(num_songs <= 5);
+	assert!(!update.is_finished());
+
+	control.resume();
+	update.join().unwrap();
+	assert_eq!(count_songs(), 13);
+}
+
+#[test]
+fn cancelled_update_stops_waiting() {
+	let control = UpdateControl::new();
+	control.begin();
+	control.pause();
+	control.cancel();
+	assert!(!control.wait_while_paused());
+	control.end();
+	assert_eq!(control.status(), UpdateStatus::Cancelled);
+}
+
+#[test]
+fn scan_events_are_streamed_until_update_completes() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+
+	let events = ctx.index.subscribe_to_scan_events();
+	let update_index = ctx.index.clone();
+	let update = std::thread::spawn(move || update_index.update().unwrap());
+
+	// Iteration ends once the update completes and disconnects subscribers
+	let events: Vec<ScanEvent> = events.iter().collect();
+	update.join().unwrap();
+
+	assert!(events
+		.iter()
+		.any(|e| matches!(e, ScanEvent::DirectoryEntered { .. })));
+	let num_indexed = events
+		.iter()
+		.filter(|e| matches!(e, ScanEvent::FileIndexed { .. }))
+		.count();
+	assert_eq!(num_indexed, 13);
+}
+
+#[test]
+fn concurrent_browsing_succeeds_during_update() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let update_index = ctx.index.clone();
+	let update = std::thread::spawn(move || update_index.update().unwrap());
+
+	let readers: Vec<_> = (0..8)
+		.map(|_| {
+			let index = ctx.index.clone();
+			std::thread::spawn(move || {
+				for _ in 0..10 {
+					index.browse(Path::new(TEST_MOUNT_NAME), false).unwrap();
+					index.flatten(Path::new(TEST_MOUNT_NAME), false).unwrap();
+					index.search("Khemmis").unwrap();
+				}
+			})
+		})
+		.collect();
+
+	for reader in readers {
+		reader.join().unwrap();
+	}
+	update.join().unwrap();
+}
+
+#[test]
+fn refresh_updates_single_song() {
+	let builder = test::ContextBuilder::new(test_name!());
+
+	let original_collection_dir: PathBuf = ["test-data", "small-collection"].iter().collect();
+	let test_collection_dir: PathBuf = builder.test_directory.join("small-collection");
+
+	let copy_options = fs_extra::dir::CopyOptions::new();
+	fs_extra::dir::copy(
+		original_collection_dir,
+		&builder.test_directory,
+		&copy_options,
+	)
+	.unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, test_collection_dir.to_str().unwrap())
+		.build();
+
+	ctx.index.update().unwrap();
+
+	let candlelight_path = test_collection_dir
+		.join("Khemmis")
+		.join("Hunted")
+		.join("02 - Candlelight.mp3");
+	let mut tag = id3::Tag::read_from_path(&candlelight_path).unwrap();
+	tag.set_title("Candlelight (Remastered)");
+	tag.write_to_path(&candlelight_path, tag.version()).unwrap();
+
+	let virtual_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	ctx.index.refresh(&virtual_path).unwrap();
+
+	let hunted_virtual_dir: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let songs = ctx.index.flatten(&hunted_virtual_dir, false).unwrap();
+	let read_title = |file_name: &str| {
+		songs
+			.iter()
+			.find(|s| s.path == hunted_virtual_dir.join(file_name).to_string_lossy())
+			.and_then(|s| s.title.clone())
+	};
+	assert_eq!(
+		read_title("02 - Candlelight.mp3"),
+		Some("Candlelight (Remastered)".to_owned())
+	);
+	assert_eq!(
+		read_title("01 - Above The Water.mp3"),
+		Some("Above The Water".to_owned())
+	);
+}
+
+#[test]
+fn refresh_rejects_unindexed_path() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+
+	let virtual_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis"].iter().collect();
+	assert!(matches!(
+		ctx.index.refresh(&virtual_path),
+		Err(Error::SongNotFound(_))
+	));
+}
+
+#[test]
+fn untagged_songs_use_fallback_artist() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/untagged-songs")
+		.build();
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			fallback_artist: Some("Nobody".to_owned()),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+
+	let songs = ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap();
+	assert_eq!(songs.len(), 1);
+	assert_eq!(songs[0].title, Some("Untagged".to_owned()));
+	assert_eq!(songs[0].artist, Some("Nobody".to_owned()));
+	assert_eq!(songs[0].album, None);
+
+	let artists = ctx.index.get_artists().unwrap();
+	assert_eq!(artists.len(), 1);
+	assert_eq!(artists[0].name, "Nobody");
+}
+
+#[test]
+fn rebuild_replaces_index_content() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let mut connection = ctx.db.connect().unwrap();
+	let stale_path = "stale/song.mp3";
+	diesel::insert_into(songs::table)
+		.values((songs::path.eq(stale_path), songs::parent.eq("stale")))
+		.execute(&mut connection)
+		.unwrap();
+	diesel::update(songs::table)
+		.set(songs::title.eq("Outdated"))
+		.execute(&mut connection)
+		.unwrap();
+
+	ctx.index.rebuild().unwrap();
+
+	let all_directories: Vec<Directory> = directories::table.load(&mut connection).unwrap();
+	let all_songs: Vec<Song> = songs::table.load(&mut connection).unwrap();
+	assert_eq!(all_directories.len(), 6);
+	assert_eq!(all_songs.len(), 13);
+	assert!(all_songs.iter().all(|s| s.path != stale_path));
+	assert!(all_songs
+		.iter()
+		.all(|s| s.title != Some("Outdated".to_owned())));
+}
+
+#[test]
+fn mounts_can_override_album_art_pattern() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount_with_album_art_pattern(
+			"khemmis",
+			"test-data/small-collection/Khemmis",
+			"folder\\.jpg",
+		)
+		.mount_with_album_art_pattern(
+			"tobokegao",
+			"test-data/small-collection/Tobokegao",
+			"folder\\.png",
+		)
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			album_art_pattern: Some("cover\\.jpg".to_owned()),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+
+	let read_artwork = |parent: &str, directory_name: &str| {
+		ctx.index
+			.browse(Path::new(parent), false)
+			.unwrap()
+			.into_iter()
+			.find_map(|f| match f {
+				CollectionFile::Directory(d) if d.path.ends_with(directory_name) => d.artwork,
+				_ => None,
+			})
+	};
+
+	let hunted_artwork: PathBuf = ["khemmis", "Hunted", "Folder.jpg"].iter().collect();
+	assert_eq!(
+		read_artwork("khemmis", "Hunted"),
+		Some(hunted_artwork.to_string_lossy().into_owned())
+	);
+
+	let picnic_artwork: PathBuf = ["tobokegao", "Picnic", "Folder.png"].iter().collect();
+	assert_eq!(
+		read_artwork("tobokegao", "Picnic"),
+		Some(picnic_artwork.to_string_lossy().into_owned())
+	);
+}
+
+#[cfg(unix)]
+#[test]
+fn colliding_virtual_paths_are_resolved_and_reported() {
+	use std::ffi::OsStr;
+	use std::os::unix::ffi::OsStrExt;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collisions");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+
+	let hunted_dir: PathBuf = ["test-data", "small-collection", "Khemmis", "Hunted"]
+		.iter()
+		.collect();
+	let kept = collection_dir.join(OsStr::from_bytes(b"Song \xfe.mp3"));
+	let ignored = collection_dir.join(OsStr::from_bytes(b"Song \xff.mp3"));
+	std::fs::copy(hunted_dir.join("02 - Candlelight.mp3"), &kept).unwrap();
+	std::fs::copy(hunted_dir.join("03 - Three Gates.mp3"), &ignored).unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+
+	for _ in 0..2 {
+		ctx.index.update().unwrap();
+
+		let songs = ctx
+			.index
+			.flatten(Path::new(TEST_MOUNT_NAME), false)
+			.unwrap();
+		assert_eq!(songs.len(), 1);
+		assert_eq!(songs[0].title, Some("Candlelight".to_owned()));
+
+		assert_eq!(
+			ctx.index.scan_errors(),
+			vec![ScanError::PathCollision {
+				kept: kept.clone(),
+				ignored: vec![ignored.clone()],
+			}]
+		);
+	}
+}
+
+#[test]
+fn update_stops_at_max_depth() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("deep");
+	let song_path: PathBuf = [
+		"test-data",
+		"small-collection",
+		"Khemmis",
+		"Hunted",
+		"02 - Candlelight.mp3",
+	]
+	.iter()
+	.collect();
+	let mut directory = collection_dir.clone();
+	for name in ["a", "b", "c", "d"] {
+		std::fs::create_dir_all(&directory).unwrap();
+		std::fs::copy(&song_path, directory.join("song.mp3")).unwrap();
+		directory.push(name);
+	}
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			index_max_depth: Some(2),
+			..Default::default()
+		})
+		.unwrap();
+
+	ctx.index.update().unwrap();
+
+	let songs = ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap();
+	assert_eq!(songs.len(), 3);
+	assert_eq!(
+		ctx.index.scan_errors(),
+		vec![ScanError::DepthLimitReached {
+			path: collection_dir.join("a").join("b").join("c"),
+		}]
+	);
+}
+
+#[test]
+fn update_skips_files_below_min_size() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("stubs");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	let song_path: PathBuf = [
+		"test-data",
+		"small-collection",
+		"Khemmis",
+		"Hunted",
+		"02 - Candlelight.mp3",
+	]
+	.iter()
+	.collect();
+	std::fs::copy(&song_path, collection_dir.join("song.mp3")).unwrap();
+	std::fs::write(collection_dir.join("stub.mp3"), [0u8; 16]).unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			min_file_size_bytes: Some(1024),
+			..Default::default()
+		})
+		.unwrap();
+
+	ctx.index.update().unwrap();
+
+	let songs = ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap();
+	assert_eq!(songs.len(), 1);
+	assert_eq!(songs[0].title, Some("Candlelight".to_owned()));
+	assert_eq!(
+		ctx.index.scan_errors(),
+		vec![ScanError::FileTooSmall {
+			path: collection_dir.join("stub.mp3"),
+			size: 16,
+		}]
+	);
+}
+
+#[test]
+fn update_gives_up_on_files_failing_repeatedly() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("broken");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	let broken_path = collection_dir.join("track.flac");
+	std::fs::write(&broken_path, [0u8; 2048]).unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			scan_failure_limit: Some(2),
+			..Default::default()
+		})
+		.unwrap();
+
+	ctx.index.update().unwrap();
+	assert_eq!(ctx.index.scan_errors(), vec![]);
+
+	let given_up = vec![ScanError::PersistentlyBroken {
+		path: broken_path.clone(),
+		failures: 2,
+	}];
+	ctx.index.update().unwrap();
+	assert_eq!(ctx.index.scan_errors(), given_up);
+	ctx.index.update().unwrap();
+	assert_eq!(ctx.index.scan_errors(), given_up);
+
+	let song_path: PathBuf = ["test-data", "formats", "sample.flac"].iter().collect();
+	std::fs::copy(&song_path, &broken_path).unwrap();
+	ctx.index.update().unwrap();
+
+	let songs = ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap();
+	assert_eq!(songs.len(), 1);
+	assert_eq!(ctx.index.scan_errors(), vec![]);
+}
+
+#[test]
+fn update_measures_loudness() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("tones");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	for (name, level) in [("loud.wav", -20.0), ("quiet.wav", -30.0)] {
+		let path = collection_dir.join(name);
+		super::loudness::test::write_sine_wave(&path, level, 3);
+		let mut tag = id3::Tag::new();
+		tag.set_title(name);
+		tag.write_to_wav_path(&path, id3::Version::Id3v24).unwrap();
+	}
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			analyze_loudness: Some(true),
+			..Default::default()
+		})
+		.unwrap();
+
+	ctx.index.update().unwrap();
+
+	let songs = ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap();
+	assert_eq!(songs.len(), 2);
+	let loud = songs[0].track_loudness.unwrap();
+	let quiet = songs[1].track_loudness.unwrap();
+	assert!((-2010..=-1990).contains(&loud), "{loud}");
+	assert!((-3010..=-2990).contains(&quiet), "{quiet}");
+	let album = songs[0].album_loudness.unwrap();
+	assert_eq!(songs[1].album_loudness, Some(album));
+	assert!(quiet < album && album < loud, "{album}");
+}
+
+#[test]
+fn update_skips_junk_files() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("junk");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	let song_path: PathBuf = ["test-data", "formats", "sample.flac"].iter().collect();
+	std::fs::copy(&song_path, collection_dir.join("track.flac")).unwrap();
+	std::fs::copy(&song_path, collection_dir.join("._track.flac")).unwrap();
+	std::fs::write(collection_dir.join(".DS_Store"), [0u8; 16]).unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+
+	ctx.index.update().unwrap();
+
+	let songs = ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap();
+	assert_eq!(songs.len(), 1);
+	assert_eq!(
+		songs[0].path,
+		Path::new(TEST_MOUNT_NAME)
+			.join("track.flac")
+			.to_string_lossy()
+			.into_owned()
+	);
+	assert!(ctx.index.scan_errors().is_empty());
+}
+
+#[test]
+fn can_group_albums_by_year_and_decade() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let original_collection_dir: PathBuf = ["test-data", "small-collection"].iter().collect();
+	let test_collection_dir: PathBuf = builder.test_directory.join("small-collection");
+	let copy_options = fs_extra::dir::CopyOptions::new();
+	fs_extra::dir::copy(
+		&original_collection_dir,
+		&builder.test_directory,
+		&copy_options,
+	)
+	.unwrap();
+
+	let demo_dir = test_collection_dir.join("Demo");
+	let demo_song = demo_dir.join("Demo.mp3");
+	std::fs::create_dir_all(&demo_dir).unwrap();
+	std::fs::copy(
+		original_collection_dir.join("Khemmis/Hunted/02 - Candlelight.mp3"),
+		&demo_song,
+	)
+	.unwrap();
+	let mut tag = id3::Tag::read_from_path(&demo_song).unwrap();
+	tag.remove_year();
+	tag.remove_date_recorded();
+	tag.write_to_path(&demo_song, id3::Version::Id3v24).unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, test_collection_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	assert_eq!(
+		ctx.index.get_years().unwrap(),
+		vec![
+			YearCount {
+				year: Some(2016),
+				song_count: 13,
+				album_count: 3,
+			},
+			YearCount {
+				year: None,
+				song_count: 1,
+				album_count: 1,
+			},
+		]
+	);
+
+	for decade in [2010, 2016] {
+		assert_eq!(ctx.index.get_decade_albums(Some(decade)).unwrap().len(), 3);
+	}
+	assert!(ctx.index.get_decade_albums(Some(1990)).unwrap().is_empty());
+
+	let unknown_decade = ctx.index.get_decade_albums(None).unwrap();
+	assert_eq!(unknown_decade.len(), 1);
+	assert_eq!(
+		unknown_decade[0].path,
+		Path::new(TEST_MOUNT_NAME)
+			.join("Demo")
+			.to_string_lossy()
+			.into_owned()
+	);
+}
+
+#[test]
+fn periodic_updates_skip_startup_scan_when_disabled() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			scan_on_startup: Some(false),
+			..Default::default()
+		})
+		.unwrap();
+
+	ctx.index.begin_periodic_updates();
+	std::thread::sleep(Duration::from_millis(500));
+	assert!(ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap()
+		.is_empty());
+
+	ctx.index.trigger_reindex();
+	let mut num_songs = 0;
+	for _ in 0..100 {
+		num_songs = ctx
+			.index
+			.flatten(Path::new(TEST_MOUNT_NAME), false)
+			.unwrap()
+			.len();
+		if num_songs == 13 {
+			break;
+		}
+		std::thread::sleep(Duration::from_millis(100));
+	}
+	assert_eq!(num_songs, 13);
+}
+
+#[test]
+fn album_with_several_album_artists_is_grouped_per_setting() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let album_dir = builder.test_directory.join("Split");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	let hunted_dir: PathBuf = ["test-data", "small-collection", "Khemmis", "Hunted"]
+		.iter()
+		.collect();
+	for entry in std::fs::read_dir(hunted_dir).unwrap() {
+		let path = entry.unwrap().path();
+		if path.extension().map(|e| e == "mp3") != Some(true) {
+			continue;
+		}
+		let song_path = album_dir.join(path.file_name().unwrap());
+		std::fs::copy(&path, &song_path).unwrap();
+		let mut tag = id3::Tag::read_from_path(&song_path).unwrap();
+		tag.set_album_artist("Khemmis; Tobokegao");
+		tag.write_to_path(&song_path, tag.version()).unwrap();
+	}
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, album_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	let artist = |name: &str| Artist {
+		name: name.to_owned(),
+		album_count: 1,
+		track_count: 5,
+	};
+	assert_eq!(
+		ctx.index.get_artists().unwrap(),
+		vec![artist("Khemmis"), artist("Tobokegao")]
+	);
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			album_artist_grouping: Some(settings::AlbumArtistGrouping::Joined),
+			..Default::default()
+		})
+		.unwrap();
+	assert_eq!(
+		ctx.index.get_artists().unwrap(),
+		vec![artist("Khemmis; Tobokegao")]
+	);
+}
+
+#[test]
+fn search_can_filter_by_field() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let album_dir = builder.test_directory.join("Hunted");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	let hunted_dir: PathBuf = ["test-data", "small-collection", "Khemmis", "Hunted"]
+		.iter()
+		.collect();
+	for entry in std::fs::read_dir(hunted_dir).unwrap() {
+		let path = entry.unwrap().path();
+		if path.extension().map(|e| e == "mp3") != Some(true) {
+			continue;
+		}
+		let song_path = album_dir.join(path.file_name().unwrap());
+		std::fs::copy(&path, &song_path).unwrap();
+		let mut tag = id3::Tag::read_from_path(&song_path).unwrap();
+		let composer = match tag.track() {
+			Some(1) | Some(2) => "Johann Sebastian Bach",
+			_ => "Someone Else",
+		};
+		tag.set_text("TCOM", composer);
+		tag.write_to_path(&song_path, tag.version()).unwrap();
+	}
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, album_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	let titles = |query: &str| -> Vec<String> {
+		let mut titles: Vec<String> = ctx
+			.index
+			.search(query)
+			.unwrap()
+			.into_iter()
+			.map(|f| match f {
+				CollectionFile::Song(s) => s.title.unwrap(),
+				CollectionFile::Directory(d) => panic!("Unexpected directory `{}`", d.path),
+			})
+			.collect();
+		titles.sort();
+		titles
+	};
+
+	assert_eq!(
+		titles("composer:bach"),
+		vec!["Above The Water".to_owned(), "Candlelight".to_owned()]
+	);
+	assert_eq!(
+		titles("COMPOSER:Bach candle"),
+		vec!["Candlelight".to_owned()]
+	);
+	assert!(titles("composer:Bach genre:Polka").is_empty());
+}
+
+#[test]
+fn lists_albums_without_artwork() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	let song_path: PathBuf = [
+		"test-data",
+		"small-collection",
+		"Khemmis",
+		"Hunted",
+		"02 - Candlelight.mp3",
+	]
+	.iter()
+	.collect();
+	for album in ["With Art", "Without Art"] {
+		let album_dir = collection_dir.join(album);
+		std::fs::create_dir_all(&album_dir).unwrap();
+		std::fs::copy(&song_path, album_dir.join("Candlelight.mp3")).unwrap();
+	}
+	std::fs::copy(
+		"test-data/small-collection/Khemmis/Hunted/Folder.jpg",
+		collection_dir.join("With Art").join("cover.jpg"),
+	)
+	.unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			album_art_pattern: Some("cover\\.jpg".to_owned()),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+
+	let without_artwork: Vec<String> = ctx
+		.index
+		.get_albums_without_artwork()
+		.unwrap()
+		.into_iter()
+		.map(|d| d.path)
+		.collect();
+	let expected_path: PathBuf = [TEST_MOUNT_NAME, "Without Art"].iter().collect();
+	assert_eq!(
+		without_artwork,
+		vec![expected_path.to_string_lossy().into_owned()]
+	);
+}
+
+#[test]
+fn directory_reports_consensus_year_and_genres() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let original_album_dir: PathBuf = ["test-data", "small-collection", "Khemmis", "Hunted"]
+		.iter()
+		.collect();
+	let album_dir = builder.test_directory.join("Album");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	let tracks = [
+		("02 - Candlelight.mp3", 2016, "Doom Metal"),
+		("03 - Three Gates.mp3", 2010, "Heavy Metal"),
+		("04 - Beyond The Door.mp3", 2016, "Doom Metal"),
+	];
+	for (file_name, year, genre) in tracks {
+		let song = album_dir.join(file_name);
+		std::fs::copy(original_album_dir.join(file_name), &song).unwrap();
+		let mut tag = id3::Tag::read_from_path(&song).unwrap();
+		tag.set_year(year);
+		tag.set_genre(genre);
+		tag.write_to_path(&song, id3::Version::Id3v24).unwrap();
+	}
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, album_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	let mut connection = ctx.db.connect().unwrap();
+	let directory: Directory = directories::table
+		.filter(directories::path.eq(album_dir.to_string_lossy().as_ref()))
+		.get_result(&mut connection)
+		.unwrap();
+	assert_eq!(directory.year, Some(2016));
+	assert_eq!(
+		directory.genres,
+		vec!["Doom Metal".to_owned(), "Heavy Metal".to_owned()]
+	);
+}
+
+#[test]
+fn indexes_original_work_of_cover_versions() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("covers");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	let cover_song = collection_dir.join("Cover.mp3");
+	std::fs::copy(
+		"test-data/small-collection/Khemmis/Hunted/02 - Candlelight.mp3",
+		&cover_song,
+	)
+	.unwrap();
+	let mut tag = id3::Tag::read_from_path(&cover_song).unwrap();
+	tag.set_text("TOPE", "Original Artist A\0Original Artist B");
+	tag.set_text("TOAL", "Original Album");
+	tag.write_to_path(&cover_song, id3::Version::Id3v24)
+		.unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	let songs = ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap();
+	assert_eq!(songs.len(), 1);
+	assert_eq!(songs[0].artist, Some("Khemmis".to_owned()));
+	assert_eq!(
+		split_names(songs[0].original_artist.as_deref()),
+		vec![
+			"Original Artist A".to_owned(),
+			"Original Artist B".to_owned()
+		]
+	);
+	assert_eq!(songs[0].original_album, Some("Original Album".to_owned()));
+}
+
+#[test]
+fn indexes_song_languages() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	let song = collection_dir.join("Chanson.mp3");
+	std::fs::copy("test-data/formats/sample.mp3", &song).unwrap();
+	let mut tag = id3::Tag::read_from_path(&song).unwrap();
+	tag.set_text("TLAN", "fre\0English");
+	tag.write_to_path(&song, id3::Version::Id3v24).unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	assert_eq!(
+		ctx.index.get_languages().unwrap(),
+		vec!["eng".to_owned(), "fra".to_owned()]
+	);
+	let songs = ctx.index.get_language_songs("French").unwrap();
+	assert_eq!(songs.len(), 1);
+	assert_eq!(
+		split_names(songs[0].language.as_deref()),
+		vec!["fra".to_owned(), "eng".to_owned()]
+	);
+	assert!(ctx.index.get_language_songs("deu").unwrap().is_empty());
+}
+
+#[test]
+fn can_hide_empty_directories_when_browsing() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	let album_dir = collection_dir.join("Artist").join("Album");
+	let scans_dir = collection_dir.join("Scans");
+	let empty_dir = collection_dir.join("Empty").join("Nested");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	std::fs::create_dir_all(&scans_dir).unwrap();
+	std::fs::create_dir_all(&empty_dir).unwrap();
+	std::fs::copy("test-data/formats/sample.mp3", album_dir.join("01.mp3")).unwrap();
+	std::fs::copy(
+		"test-data/small-collection/Khemmis/Hunted/Folder.jpg",
+		scans_dir.join("Folder.jpg"),
+	)
+	.unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	let browse_paths = || -> Vec<String> {
+		ctx.index
+			.browse(Path::new(TEST_MOUNT_NAME), false)
+			.unwrap()
+			.into_iter()
+			.map(|f| match f {
+				CollectionFile::Directory(d) => d.path,
+				CollectionFile::Song(s) => s.path,
+			})
+			.collect()
+	};
+	let virtual_path = |name: &str| -> String {
+		[TEST_MOUNT_NAME, name]
+			.iter()
+			.collect::<PathBuf>()
+			.to_string_lossy()
+			.into_owned()
+	};
+
+	assert_eq!(
+		browse_paths(),
+		vec![
+			virtual_path("Artist"),
+			virtual_path("Empty"),
+			virtual_path("Scans")
+		]
+	);
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			hide_empty_directories: Some(true),
+			..Default::default()
+		})
+		.unwrap();
+	assert_eq!(browse_paths(), vec![virtual_path("Artist")]);
+	assert_eq!(ctx.index.browse(Path::new(""), false).unwrap().len(), 1);
+}
+
+#[test]
+fn scan_can_prewarm_album_thumbnails() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			prewarm_thumbnails_on_scan: Some(true),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+
+	let artwork_paths = ctx.index.get_artwork_paths(Path::new("")).unwrap();
+	assert!(artwork_paths.len() >= 2);
+	let progress = ctx
+		.thumbnail_manager
+		.prewarm(&artwork_paths, &thumbnail::Options::default())
+		.unwrap();
+	assert_eq!(progress.generated, 0);
+	assert_eq!(progress.skipped, artwork_paths.len());
+}
+
+#[test]
+fn scan_does_not_prewarm_thumbnails_by_default() {
+	let ctx = test::ContextBuilder::new(test_name!())
+		.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+		.build();
+	ctx.index.update().unwrap();
+
+	let artwork_paths = ctx.index.get_artwork_paths(Path::new("")).unwrap();
+	let progress = ctx
+		.thumbnail_manager
+		.prewarm(&artwork_paths, &thumbnail::Options::default())
+		.unwrap();
+	assert_eq!(progress.generated, artwork_paths.len());
+}
+
+#[test]
+fn metadata_readers_can_be_overridden_by_extension() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	std::fs::copy(
+		"test-data/formats/sample.mp3",
+		collection_dir.join("id3.dsf"),
+	)
+	.unwrap();
+	std::fs::copy(
+		"test-data/formats/sample.mp3",
+		collection_dir.join("skipped.mp3"),
+	)
+	.unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	let song_names = || -> Vec<String> {
+		ctx.index
+			.browse(Path::new(TEST_MOUNT_NAME), false)
+			.unwrap()
+			.into_iter()
+			.filter_map(|f| match f {
+				CollectionFile::Song(s) => {
+					assert!(s.title.is_some());
+					Path::new(&s.path)
+						.file_name()
+						.map(|n| n.to_string_lossy().into_owned())
+				}
+				CollectionFile::Directory(_) => None,
+			})
+			.collect()
+	};
+
+	ctx.index.update().unwrap();
+	assert_eq!(song_names(), vec!["skipped.mp3".to_owned()]);
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			metadata_readers: Some(HashMap::from([
+				(".DSF".to_owned(), settings::MetadataReader::Id3),
+				("mp3".to_owned(), settings::MetadataReader::Skip),
+			])),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.rebuild().unwrap();
+	assert_eq!(song_names(), vec!["id3.dsf".to_owned()]);
+}
+
+#[test]
+fn merges_duplicate_albums_in_preferred_format() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	let flac_dir = collection_dir.join("Album (FLAC)");
+	let mp3_dir = collection_dir.join("Album (MP3)");
+	std::fs::create_dir_all(&flac_dir).unwrap();
+	std::fs::create_dir_all(&mp3_dir).unwrap();
+	std::fs::copy("test-data/formats/sample.flac", flac_dir.join("01.flac")).unwrap();
+	std::fs::copy("test-data/formats/sample.mp3", mp3_dir.join("01.mp3")).unwrap();
+	let bonus_track = mp3_dir.join("02.mp3");
+	std::fs::copy("test-data/formats/sample.mp3", &bonus_track).unwrap();
+	let mut tag = id3::Tag::read_from_path(&bonus_track).unwrap();
+	tag.set_track(2);
+	tag.write_to_path(&bonus_track, id3::Version::Id3v24)
+		.unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			merge_duplicate_albums: Some(true),
+			preferred_album_format: Some("flac".to_owned()),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+
+	let albums = ctx.index.get_recent_albums(20, false).unwrap();
+	let albums: Vec<Album> = ctx
+		.index
+		.merge_duplicate_albums(albums)
+		.unwrap()
+		.into_iter()
+		.filter(|a| a.directory.album.is_some())
+		.collect();
+	assert_eq!(albums.len(), 1);
+
+	let flac_virtual_dir: PathBuf = [TEST_MOUNT_NAME, "Album (FLAC)"].iter().collect();
+	let mp3_virtual_dir: PathBuf = [TEST_MOUNT_NAME, "Album (MP3)"].iter().collect();
+	assert_eq!(albums[0].directory.path, flac_virtual_dir.to_string_lossy());
+	assert_eq!(
+		albums[0].merged_paths,
+		vec![mp3_virtual_dir.to_string_lossy().into_owned()]
+	);
+
+	let tracks: Vec<(Option<i32>, Option<&str>)> = albums[0]
+		.songs
+		.iter()
+		.map(|s| (s.track_number, s.format.as_deref()))
+		.collect();
+	assert_eq!(
+		tracks,
+		vec![(Some(1), Some("flac")), (Some(2), Some("mp3"))]
+	);
+}
+
+#[test]
+fn estimates_missing_durations_when_enabled() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	std::fs::create_dir_all(&collection_dir).unwrap();
+	// Three frames of 73728 blocks at 44.1kHz, the last frame holding 20375 blocks
+	let mut ape = std::fs::read("test-data/formats/sample.ape").unwrap();
+	ape[64..68].copy_from_slice(&3u32.to_le_bytes());
+	std::fs::write(collection_dir.join("sample.ape"), ape).unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	let song_path: PathBuf = [TEST_MOUNT_NAME, "sample.ape"].iter().collect();
+
+	ctx.index.update().unwrap();
+	assert_eq!(ctx.index.get_song(&song_path).unwrap().duration, None);
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			estimate_missing_durations: Some(true),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.update().unwrap();
+	assert_eq!(ctx.index.get_song(&song_path).unwrap().duration, Some(3));
+}
+
+#[test]
+fn songs_sharing_a_grouping_are_listed_together() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let album_dir = builder.test_directory.join("Hunted");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	let hunted_dir: PathBuf = ["test-data", "small-collection", "Khemmis", "Hunted"]
+		.iter()
+		.collect();
+	for entry in std::fs::read_dir(hunted_dir).unwrap() {
+		let path = entry.unwrap().path();
+		if path.extension().map(|e| e == "mp3") != Some(true) {
+			continue;
+		}
+		let song_path = album_dir.join(path.file_name().unwrap());
+		std::fs::copy(&path, &song_path).unwrap();
+		let mut tag = id3::Tag::read_from_path(&song_path).unwrap();
+		match tag.track() {
+			Some(1) | Some(2) => tag.set_text("GRP1", "Warm Up"),
+			Some(3) => tag.set_text("GRP1", "Peak Time"),
+			_ => continue,
+		}
+		tag.write_to_path(&song_path, tag.version()).unwrap();
+	}
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, album_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	assert_eq!(
+		ctx.index.get_groupings().unwrap(),
+		vec!["Peak Time".to_owned(), "Warm Up".to_owned()]
+	);
+
+	let mut titles: Vec<String> = ctx
+		.index
+		.get_grouping_songs("Warm Up")
+		.unwrap()
+		.into_iter()
+		.map(|s| s.title.unwrap())
+		.collect();
+	titles.sort();
+	assert_eq!(
+		titles,
+		vec!["Above The Water".to_owned(), "Candlelight".to_owned()]
+	);
+	assert!(ctx
+		.index
+		.get_grouping_songs("Cool Down")
+		.unwrap()
+		.is_empty());
+}
+
+#[test]
+fn labels_list_counts_and_representative_artwork() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("collection");
+	let albums = [
+		(["Khemmis", "Hunted"], "20 Buck Spin"),
+		(["Tobokegao", "Picnic"], "Tobokegao Records"),
+		(["Tobokegao", "Picnic (Remixes)"], "Tobokegao Records"),
+	];
+	for (album, label) in albums {
+		let source_dir: PathBuf = ["test-data", "small-collection"]
+			.iter()
+			.chain(album.iter())
+			.collect();
+		let album_dir = collection_dir.join(album[1]);
+		std::fs::create_dir_all(&album_dir).unwrap();
+		for entry in std::fs::read_dir(source_dir).unwrap() {
+			let path = entry.unwrap().path();
+			let target = album_dir.join(path.file_name().unwrap());
+			std::fs::copy(&path, &target).unwrap();
+			if path.extension().map(|e| e == "mp3") == Some(true) {
+				let mut tag = id3::Tag::read_from_path(&target).unwrap();
+				tag.set_text("TPUB", label);
+				tag.write_to_path(&target, tag.version()).unwrap();
+			}
+		}
+	}
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	let labels = ctx.index.get_labels().unwrap();
+	assert_eq!(labels.len(), 2);
+
+	assert_eq!(labels[0].name, "20 Buck Spin");
+	assert_eq!(labels[0].song_count, 5);
+	assert_eq!(labels[0].album_count, 1);
+	let hunted_artwork: PathBuf = [TEST_MOUNT_NAME, "Hunted", "Folder.jpg"].iter().collect();
+	assert_eq!(
+		labels[0].artwork,
+		Some(hunted_artwork.to_string_lossy().into_owned())
+	);
+
+	assert_eq!(labels[1].name, "Tobokegao Records");
+	assert_eq!(labels[1].song_count, 8);
+	assert_eq!(labels[1].album_count, 2);
+	let picnic_artwork: PathBuf = [TEST_MOUNT_NAME, "Picnic", "Folder.png"].iter().collect();
+	assert_eq!(
+		labels[1].artwork,
+		Some(picnic_artwork.to_string_lossy().into_owned())
+	);
+
+	let label_albums = ctx.index.get_label_albums("Tobokegao Records").unwrap();
+	assert_eq!(label_albums.len(), 2);
+	assert!(ctx
+		.index
+		.get_label_albums("Unknown Records")
+		.unwrap()
+		.is_empty());
+}
+
+#[test]
+fn aggregates_are_cached_until_index_changes() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let album_dir = builder.test_directory.join("Hunted");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	let hunted_dir: PathBuf = ["test-data", "small-collection", "Khemmis", "Hunted"]
+		.iter()
+		.collect();
+	for entry in std::fs::read_dir(hunted_dir).unwrap() {
+		let path = entry.unwrap().path();
+		std::fs::copy(&path, album_dir.join(path.file_name().unwrap())).unwrap();
+	}
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, album_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	let mp3_count = || {
+		let counts = ctx.index.get_format_counts().unwrap();
+		counts.iter().find(|c| c.format == "mp3").map(|c| c.count)
+	};
+	assert_eq!(mp3_count(), Some(5));
+
+	// Changes made behind the index's back are not seen until the next update
+	{
+		let mut connection = ctx.db.connect().unwrap();
+		diesel::delete(songs::table.filter(songs::path.like("%Hunted.mp3")))
+			.execute(&mut connection)
+			.unwrap();
+	}
+	assert_eq!(mp3_count(), Some(5));
+
+	std::fs::remove_file(album_dir.join("05 - Hunted.mp3")).unwrap();
+	std::fs::remove_file(album_dir.join("04 - Beyond The Door.mp3")).unwrap();
+	ctx.index.update().unwrap();
+	assert_eq!(mp3_count(), Some(3));
+}
+
+#[test]
+fn songs_without_album_artist_are_grouped_under_their_artist() {
+	use id3::TagLike;
+
+	let builder = test::ContextBuilder::new(test_name!());
+	let album_dir = builder.test_directory.join("Hunted");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	let song_path = album_dir.join("01 - Above The Water.mp3");
+	std::fs::copy(
+		"test-data/small-collection/Khemmis/Hunted/01 - Above The Water.mp3",
+		&song_path,
+	)
+	.unwrap();
+	let mut tag = id3::Tag::read_from_path(&song_path).unwrap();
+	tag.remove_album_artist();
+	tag.set_artist("Khemmis");
+	tag.write_to_path(&song_path, id3::Version::Id3v24).unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, album_dir.to_str().unwrap())
+		.build();
+	ctx.index.update().unwrap();
+
+	let directory_artist = || -> Option<String> {
+		let mut connection = ctx.db.connect().unwrap();
+		directories::table
+			.select(directories::artist)
+			.filter(directories::parent.is_null())
+			.get_result(&mut connection)
+			.unwrap()
+	};
+
+	let songs = ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap();
+	assert_eq!(songs.len(), 1);
+	assert_eq!(songs[0].album_artist, None);
+	assert_eq!(songs[0].effective_album_artist, Some("Khemmis".to_owned()));
+	assert_eq!(directory_artist(), Some("Khemmis".to_owned()));
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			album_artist_fallback: Some(false),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.rebuild().unwrap();
+
+	let songs = ctx
+		.index
+		.flatten(Path::new(TEST_MOUNT_NAME), false)
+		.unwrap();
+	assert_eq!(songs[0].album_artist, None);
+	assert_eq!(songs[0].effective_album_artist, None);
+	assert_eq!(directory_artist(), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_duplicates_are_read_once_or_skipped() {
+	let builder = test::ContextBuilder::new(test_name!());
+	let collection_dir = builder.test_directory.join("links");
+	let album_dir = collection_dir.join("Album");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	let hunted_dir: PathBuf = ["test-data", "small-collection", "Khemmis", "Hunted"]
+		.iter()
+		.collect();
+	for entry in std::fs::read_dir(&hunted_dir).unwrap() {
+		let path = entry.unwrap().path();
+		if path.extension().is_some_and(|e| e == "mp3") {
+			std::fs::copy(&path, album_dir.join(path.file_name().unwrap())).unwrap();
+		}
+	}
+	// Link targets are relative to the directory holding the link
+	std::os::unix::fs::symlink("Album", collection_dir.join("Favorites")).unwrap();
+	std::os::unix::fs::symlink("Album", collection_dir.join("Highlights")).unwrap();
+
+	let ctx = builder
+		.mount(TEST_MOUNT_NAME, collection_dir.to_str().unwrap())
+		.build();
+
+	let events = ctx.index.subscribe_to_scan_events();
+	let update_index = ctx.index.clone();
+	let update = std::thread::spawn(move || update_index.update().unwrap());
+	let events: Vec<ScanEvent> = events.iter().collect();
+	update.join().unwrap();
+
+	let num_indexed = events
+		.iter()
+		.filter(|e| matches!(e, ScanEvent::FileIndexed { .. }))
+		.count();
+	// Original files are read on their own, and both links share a single read
+	assert_eq!(num_indexed, 10);
+
+	let album_path: PathBuf = [TEST_MOUNT_NAME, "Album"].iter().collect();
+	let favorites_path: PathBuf = [TEST_MOUNT_NAME, "Favorites"].iter().collect();
+	let album_songs = ctx.index.flatten(&album_path, false).unwrap();
+	let highlights_path: PathBuf = [TEST_MOUNT_NAME, "Highlights"].iter().collect();
+	let favorite_songs = ctx.index.flatten(&favorites_path, false).unwrap();
+	let highlight_songs = ctx.index.flatten(&highlights_path, false).unwrap();
+	assert_eq!(album_songs.len(), 5);
+	assert_eq!(favorite_songs.len(), 5);
+	assert_eq!(highlight_songs.len(), 5);
+	assert_eq!(album_songs[0].title, favorite_songs[0].title);
+	assert_eq!(album_songs[0].title, highlight_songs[0].title);
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			symlinked_duplicates: Some(settings::SymlinkedDuplicates::Skip),
+			..Default::default()
+		})
+		.unwrap();
+	ctx.index.rebuild().unwrap();
+
+	assert_eq!(ctx.index.flatten(&album_path, false).unwrap().len(), 5);
+	assert!(ctx
+		.index
+		.flatten(&favorites_path, false)
+		.unwrap()
+		.is_empty());
+	assert!(ctx
+		.index
+		.flatten(&highlights_path, false)
+		.unwrap()
+		.is_empty());
+	let mut connection = ctx.db.connect().unwrap();
+	let num_songs: i64 = songs::table.count().get_result(&mut connection).unwrap();
+	assert_eq!(num_songs, 5);
+}
+
+#[test]
+fn root_listing_honors_mount_order_and_hidden_mounts() {
+	let ctx = test::ContextBuilder::new(test_name!()).build();
+	ctx.vfs_manager
+		.set_mount_dirs(&[
+			vfs::MountDir {
+				source: "test-data/small-collection/Tobokegao".to_owned(),
+				name: "zeta".to_owned(),
+				hide_from_root: false,
+				album_art_pattern: None,
+				reindex_every_n_seconds: None,
+			},
+			vfs::MountDir {
+				source: "test-data/small-collection/Khemmis".to_owned(),
+				name: "Alpha".to_owned(),
+				hide_from_root: false,
+				album_art_pattern: None,
+				reindex_every_n_seconds: None,
+			},
+			vfs::MountDir {
+				source: "test-data/artwork".to_owned(),
+				name: "hidden".to_owned(),
+				hide_from_root: true,
+				album_art_pattern: None,
+				reindex_every_n_seconds: None,
+			},
+		])
+		.unwrap();
+	ctx.index.update().unwrap();
+
+	let root_names = || -> Vec<String> {
+		ctx.index
+			.browse(Path::new(""), false)
+			.unwrap()
+			.into_iter()
+			.map(|f| match f {
+				CollectionFile::Directory(d) => d.path,
+				_ => panic!("Expected directory"),
+			})
+			.collect()
+	};
+
+	assert_eq!(root_names(), vec!["Alpha", "zeta"]);
+
+	ctx.settings_manager
+		.amend(&settings::NewSettings {
+			mount_order: Some(settings::MountOrder::Config),
+			..Default::default()
+		})
+		.unwrap();
+	assert_eq!(root_names(), vec!["zeta", "Alpha"]);
+
+	assert!(!ctx
+		.index
+		.browse(Path::new("hidden"), false)
+		.unwrap()
+		.is_empty());
+}
+
+#[test]
+fn remote_mounts_can_be_browsed() {
+	let ctx = test::ContextBuilder::new(test_name!()).build();
+	let backend = mount_remote_collection(&ctx);
+	ctx.index.update().unwrap();
+
+	let files = ctx.index.browse(Path::new("remote"), false).unwrap();
+	let names: Vec<String> = files
+		.into_iter()
+		.map(|f| match f {
+			CollectionFile::Directory(d) => d.path,
+			_ => panic!("Expected directory"),
+		})
+		.collect();
+	let khemmis_path: PathBuf = ["remote", "Khemmis"].iter().collect();
+	let tobokegao_path: PathBuf = ["remote", "Tobokegao"].iter().collect();
+	assert_eq!(
+		names,
+		vec![
+			khemmis_path.to_string_lossy().into_owned(),
+			tobokegao_path.to_string_lossy().into_owned()
+		]
+	);
+
+	let hunted_path: PathBuf = ["remote", "Khemmis", "Hunted"].iter().collect();
+	let songs = ctx.index.flatten(&hunted_path, false).unwrap();
+	assert_eq!(songs.len(), 5);
+	assert_eq!(songs[0].title, Some("Above The Water".to_owned()));
+	assert!(backend.num_reads.load(Ordering::SeqCst) > 0);
+}
+
+#[test]
+fn unchanged_remote_files_are_not_downloaded_again() {
+	let ctx = test::ContextBuilder::new(test_name!()).build();
+	let backend = mount_remote_collection(&ctx);
+
+	ctx.index.update().unwrap();
+	let num_reads = backend.num_reads.load(Ordering::SeqCst);
+	assert!(num_reads > 0);
+
+	ctx.index.update().unwrap();
+	assert_eq!(backend.num_reads.load(Ordering::SeqCst), num_reads);
+	let hunted_path: PathBuf = ["remote", "Khemmis", "Hunted"].iter().collect();
+	let songs = ctx.index.flatten(&hunted_path, false).unwrap();
+	assert_eq!(songs.len(), 5);
+	assert_eq!(songs[0].title, Some("Above The Water".to_owned()));
+}
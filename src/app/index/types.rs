@@ -1,16 +1,21 @@
+use diesel::deserialize::{self, Queryable};
+use diesel::sql_types::{Nullable, Text};
+use diesel::sqlite::Sqlite;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::path::Path;
 
 use crate::app::vfs::VFS;
 use crate::db::songs;
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CollectionFile {
 	Directory(Directory),
 	Song(Song),
 }
 
-#[derive(Debug, PartialEq, Eq, Queryable, QueryableByName, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Queryable, QueryableByName, Serialize, Deserialize)]
 #[diesel(table_name = songs)]
 pub struct Song {
 	#[serde(skip_serializing, skip_deserializing)]
@@ -31,6 +36,52 @@ pub struct Song {
 	pub composer: Option<String>,
 	pub genre: Option<String>,
 	pub label: Option<String>,
+	pub hidden: bool,
+	pub grouping: Option<String>,
+	pub work: Option<String>,
+	pub movement_name: Option<String>,
+	pub movement_number: Option<i32>,
+	pub original_genre: Option<String>,
+	pub isrc: Option<String>,
+	pub catalog_number: Option<String>,
+	pub format: Option<String>,
+	pub original_year: Option<i32>,
+	pub conductor: Option<String>,
+	pub remixer: Option<String>,
+	pub file_size: Option<i64>,
+	/// Unrecognized tags, stored as a JSON object of value lists.
+	#[serde(skip_serializing, skip_deserializing)]
+	pub extra: Option<String>,
+	pub disc_total: Option<i32>,
+	pub track_total: Option<i32>,
+	pub encoded_by: Option<String>,
+	pub encoder_settings: Option<String>,
+	pub original_artist: Option<String>,
+	pub original_album: Option<String>,
+	/// From 0 to 100, where 100 stands for five stars.
+	pub rating: Option<i32>,
+	/// Album artist under which the song is grouped, which is the track artist for songs
+	/// without an album artist (unless disabled in settings).
+	pub effective_album_artist: Option<String>,
+	/// Languages of the lyrics, as ISO 639-2 codes when recognized.
+	pub language: Option<String>,
+	/// Medium the song was sourced from, such as CD, Vinyl or Digital Media.
+	pub media_type: Option<String>,
+	/// Name of the disc within a multi-disc set, such as "The Early Years".
+	pub disc_subtitle: Option<String>,
+	/// Musical key the song starts in, as written by the tagger (eg. `Am` or `8A`).
+	pub initial_key: Option<String>,
+	/// Integrated loudness of the song measured during scans, in hundredths of LUFS.
+	pub track_loudness: Option<i32>,
+	/// Integrated loudness of the directory containing the song, in hundredths of LUFS.
+	pub album_loudness: Option<i32>,
+	/// Where the duration was obtained from: `tag`, `stream` or `estimate`.
+	pub duration_source: Option<String>,
+	/// Kind of content: `music`, `audiobook` or `podcast`.
+	pub content_type: String,
+	pub arranger: Option<String>,
+	/// Musicians credited on the recording, as opposed to the artist it is released under.
+	pub performer: Option<String>,
 }
 
 impl Song {
@@ -47,6 +98,108 @@ impl Song {
 		}
 		Some(self)
 	}
+
+	/// Orders songs the way they appear on their album: by disc, then by track number.
+	pub fn cmp_album_order(&self, other: &Song) -> Ordering {
+		(self.disc_number, self.track_number, &self.path).cmp(&(
+			other.disc_number,
+			other.track_number,
+			&other.path,
+		))
+	}
+}
+
+/// Kinds of non-audio files which accompany songs within a directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+	Image,
+	Document,
+	CueSheet,
+	Lyrics,
+	Playlist,
+}
+
+impl MediaKind {
+	pub fn from_path(path: &Path) -> Option<Self> {
+		let extension = path.extension()?.to_str()?.to_lowercase();
+		match extension.as_str() {
+			"bmp" | "gif" | "jpeg" | "jpg" | "png" | "webp" => Some(Self::Image),
+			"pdf" => Some(Self::Document),
+			"cue" => Some(Self::CueSheet),
+			"lrc" => Some(Self::Lyrics),
+			"m3u" | "m3u8" => Some(Self::Playlist),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaFile {
+	pub path: String,
+	pub kind: MediaKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artist {
+	pub name: String,
+	pub album_count: usize,
+	pub track_count: usize,
+}
+
+/// Record label listed in label views. Its artwork is the cover of the first of its albums with
+/// artwork, by year of release.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Label {
+	pub name: String,
+	pub song_count: usize,
+	pub album_count: usize,
+	pub artwork: Option<String>,
+}
+
+/// Songs released in a given year. A `None` year groups songs whose year is unknown.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct YearCount {
+	pub year: Option<i32>,
+	pub song_count: usize,
+	pub album_count: usize,
+}
+
+/// Songs surrounding a song within its directory, in album order. A song at either end of its
+/// directory has no song on that side.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SongSiblings {
+	pub previous: Option<Song>,
+	pub next: Option<Song>,
+}
+
+/// An album directory along with its visible songs, including those of its sub-directories, in
+/// album order.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AlbumDetails {
+	pub directory: Directory,
+	pub songs: Vec<Song>,
+}
+
+/// Songs found by a batch lookup, in the order they were requested.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SongBatch {
+	pub songs: Vec<Song>,
+	/// Requested paths which are not in the index.
+	pub not_found: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchResults {
+	pub songs: Vec<Song>,
+	pub albums: Vec<Directory>,
+	pub artists: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormatCount {
+	pub format: String,
+	pub count: usize,
 }
 
 #[derive(Debug, PartialEq, Eq, Queryable, Serialize, Deserialize)]
@@ -61,6 +214,54 @@ pub struct Directory {
 	pub album: Option<String>,
 	pub artwork: Option<String>,
 	pub date_added: i32,
+	pub artwork_mime: Option<String>,
+	pub duration: Option<i32>,
+	/// Companion files, stored as a JSON list of paths.
+	#[serde(skip_serializing, skip_deserializing)]
+	pub media: Option<String>,
+	/// Union of the genres of songs within the directory, sorted alphabetically.
+	#[diesel(deserialize_as = JsonList)]
+	pub genres: Vec<String>,
+}
+
+/// Album listed in album views. When duplicate albums are merged, `directory` is the preferred
+/// copy of the album and `merged_paths` lists the directories holding the other copies.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Album {
+	pub directory: Directory,
+	pub merged_paths: Vec<String>,
+	/// Combined track listing of all copies. Only filled for merged albums.
+	pub songs: Vec<Song>,
+}
+
+impl From<Directory> for Album {
+	fn from(directory: Directory) -> Self {
+		Self {
+			directory,
+			merged_paths: Vec::new(),
+			songs: Vec::new(),
+		}
+	}
+}
+
+/// List of strings stored as JSON in a nullable text column.
+pub struct JsonList(Vec<String>);
+
+impl Queryable<Nullable<Text>, Sqlite> for JsonList {
+	type Row = Option<String>;
+
+	fn build(row: Self::Row) -> deserialize::Result<Self> {
+		let list = row
+			.and_then(|l| serde_json::from_str(&l).ok())
+			.unwrap_or_default();
+		Ok(Self(list))
+	}
+}
+
+impl From<JsonList> for Vec<String> {
+	fn from(list: JsonList) -> Self {
+		list.0
+	}
 }
 
 impl Directory {
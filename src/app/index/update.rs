@@ -1,18 +1,33 @@
+use crossbeam_channel::{Receiver, Sender};
 use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Condvar, Mutex};
 use std::time;
 
 mod cleaner;
 mod collector;
+mod fallback;
+mod genre;
 mod inserter;
+mod prewarmer;
+mod refresher;
 mod traverser;
 
-use crate::app::index::Index;
-use crate::app::vfs;
+use crate::app::index::{Index, SongTags};
+use crate::app::{settings, vfs};
 use crate::db;
 
 use cleaner::Cleaner;
-use collector::Collector;
+use collector::{AlbumArtPatterns, Collector};
+use fallback::TagFallbacks;
+use genre::GenreNormalizer;
 use inserter::Inserter;
+use prewarmer::Prewarmer;
+use refresher::Refresher;
 use traverser::Traverser;
 
 #[derive(thiserror::Error, Debug)]
@@ -25,36 +40,423 @@ pub enum Error {
 	DatabaseConnection(#[from] db::Error),
 	#[error(transparent)]
 	Vfs(#[from] vfs::Error),
+	#[error(transparent)]
+	Settings(#[from] settings::Error),
+	#[error("No indexed song was found at `{0}`")]
+	SongNotFound(PathBuf),
+}
+
+/// A problem found while scanning the collection, which did not prevent the rest of it from being indexed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScanError {
+	/// Several files or directories map to the same virtual path. Only `kept` is indexed.
+	PathCollision {
+		kept: PathBuf,
+		ignored: Vec<PathBuf>,
+	},
+	/// A directory lies deeper than the configured maximum depth. Its content is not indexed.
+	DepthLimitReached { path: PathBuf },
+	/// An audio file is smaller than the configured minimum file size. It is not indexed.
+	FileTooSmall { path: PathBuf, size: u64 },
+	/// Metadata of an audio file could not be read during too many consecutive scans. It is not
+	/// read again until it is modified.
+	PersistentlyBroken { path: PathBuf, failures: u32 },
+}
+
+/// Progress notification emitted while the collection is being scanned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScanEvent {
+	/// A directory is about to be scanned.
+	DirectoryEntered { path: PathBuf },
+	/// An audio file was read and will be indexed.
+	FileIndexed { path: PathBuf },
+	/// An audio file could not be indexed.
+	FileFailed { path: PathBuf, reason: String },
+}
+
+/// Forwards scan events to every subscriber, until the update they were emitted by completes.
+#[derive(Clone, Default)]
+pub struct ScanEvents {
+	subscribers: Arc<Mutex<Vec<Sender<ScanEvent>>>>,
+}
+
+impl ScanEvents {
+	/// Receives events of the update currently running, or of the next one if no update is running.
+	/// The receiver is disconnected when that update completes.
+	pub fn subscribe(&self) -> Receiver<ScanEvent> {
+		let (sender, receiver) = crossbeam_channel::unbounded();
+		self.subscribers.lock().unwrap().push(sender);
+		receiver
+	}
+
+	pub(super) fn publish(&self, event: ScanEvent) {
+		let mut subscribers = self.subscribers.lock().unwrap();
+		if subscribers.is_empty() {
+			return;
+		}
+		subscribers.retain(|s| s.send(event.clone()).is_ok());
+	}
+
+	fn close(&self) {
+		self.subscribers.lock().unwrap().clear();
+	}
+}
+
+/// Audio files whose metadata could not be read, along with how many scans in a row failed to read them.
+/// A file which is modified starts over from a clean slate.
+#[derive(Clone, Default)]
+pub struct FailedFiles {
+	entries: Arc<Mutex<HashMap<PathBuf, FailedFile>>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FailedFile {
+	failures: u32,
+	version: Option<(time::SystemTime, u64)>,
+}
+
+impl FailedFiles {
+	/// Returns how many scans in a row failed to read a file, if that reaches `limit` and the file
+	/// did not change since. A limit of zero never gives up on files.
+	pub(super) fn given_up(&self, path: &Path, limit: u32) -> Option<u32> {
+		if limit == 0 {
+			return None;
+		}
+		let entries = self.entries.lock().unwrap();
+		let entry = entries.get(path)?;
+		(entry.failures >= limit && entry.version == file_version(path)).then_some(entry.failures)
+	}
+
+	/// Counts a failed attempt at reading a file, and returns how many scans in a row failed to read it.
+	pub(super) fn record_failure(&self, path: &Path) -> u32 {
+		let version = file_version(path);
+		let mut entries = self.entries.lock().unwrap();
+		let entry = entries.entry(path.to_owned()).or_insert(FailedFile {
+			failures: 0,
+			version,
+		});
+		if entry.version != version {
+			*entry = FailedFile {
+				failures: 0,
+				version,
+			};
+		}
+		entry.failures += 1;
+		entry.failures
+	}
+
+	pub(super) fn record_success(&self, path: &Path) {
+		let mut entries = self.entries.lock().unwrap();
+		if !entries.is_empty() {
+			entries.remove(path);
+		}
+	}
+}
+
+fn file_version(path: &Path) -> Option<(time::SystemTime, u64)> {
+	let metadata = fs::metadata(path).ok()?;
+	Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Metadata read from files of remote mounts, along with the size and ETag of the file it was read from.
+/// Remote files have to be downloaded to be read, which is skipped on later scans while they do not change.
+#[derive(Clone, Default)]
+pub struct RemoteMetadata {
+	entries: Arc<Mutex<HashMap<PathBuf, RemoteMetadataEntry>>>,
+}
+
+struct RemoteMetadataEntry {
+	size: u64,
+	etag: String,
+	metadata: SongTags,
+}
+
+impl RemoteMetadata {
+	/// Returns the metadata previously read from a file, if the file did not change since.
+	pub(super) fn get(&self, path: &Path, size: u64, etag: &str) -> Option<SongTags> {
+		let entries = self.entries.lock().unwrap();
+		let entry = entries.get(path)?;
+		(entry.size == size && entry.etag == etag).then(|| entry.metadata.clone())
+	}
+
+	pub(super) fn insert(&self, path: &Path, size: u64, etag: &str, metadata: &SongTags) {
+		self.entries.lock().unwrap().insert(
+			path.to_owned(),
+			RemoteMetadataEntry {
+				size,
+				etag: etag.to_owned(),
+				metadata: metadata.clone(),
+			},
+		);
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateStatus {
+	Idle,
+	Running,
+	Paused,
+	Cancelled,
+}
+
+#[derive(Clone)]
+pub struct UpdateControl {
+	status: Arc<(Mutex<UpdateStatus>, Condvar)>,
+}
+
+impl UpdateControl {
+	pub fn new() -> Self {
+		Self {
+			status: Arc::new((Mutex::new(UpdateStatus::Idle), Condvar::new())),
+		}
+	}
+
+	pub fn status(&self) -> UpdateStatus {
+		let (lock, _) = &*self.status;
+		*lock.lock().unwrap()
+	}
+
+	fn set_status(&self, from: &[UpdateStatus], to: UpdateStatus) {
+		let (lock, cvar) = &*self.status;
+		let mut status = lock.lock().unwrap();
+		if from.contains(&status) {
+			*status = to;
+			cvar.notify_all();
+		}
+	}
+
+	pub fn pause(&self) {
+		self.set_status(&[UpdateStatus::Running], UpdateStatus::Paused);
+	}
+
+	pub fn resume(&self) {
+		self.set_status(&[UpdateStatus::Paused], UpdateStatus::Running);
+	}
+
+	pub fn cancel(&self) {
+		self.set_status(
+			&[UpdateStatus::Running, UpdateStatus::Paused],
+			UpdateStatus::Cancelled,
+		);
+	}
+
+	pub(super) fn begin(&self) {
+		let (lock, cvar) = &*self.status;
+		*lock.lock().unwrap() = UpdateStatus::Running;
+		cvar.notify_all();
+	}
+
+	pub(super) fn end(&self) {
+		// Cancellation is left visible until the next update begins
+		self.set_status(
+			&[UpdateStatus::Running, UpdateStatus::Paused],
+			UpdateStatus::Idle,
+		);
+	}
+
+	pub fn is_cancelled(&self) -> bool {
+		self.status() == UpdateStatus::Cancelled
+	}
+
+	/// Blocks while the update is paused.
+	/// Returns false if the update was cancelled and work should stop.
+	pub fn wait_while_paused(&self) -> bool {
+		let (lock, cvar) = &*self.status;
+		let mut status = lock.lock().unwrap();
+		while *status == UpdateStatus::Paused {
+			status = cvar.wait(status).unwrap();
+		}
+		*status != UpdateStatus::Cancelled
+	}
+}
+
+impl Default for UpdateControl {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl Index {
+	/// Brings the index up to date with the content of the collection.
+	/// When snapshot updates are enabled, the existing index keeps being served until the update completes,
+	/// and a cancelled update leaves the index untouched.
+	/// An empty index is instead filled in directory by directory, while the collection is being scanned.
 	pub fn update(&self) -> Result<(), Error> {
+		self.run_update(false, None)
+	}
+
+	/// Brings the index up to date with the content of some mounts only.
+	/// Entries within other mounts are left untouched, while entries outside of any mount are removed.
+	pub fn update_mounts(&self, mount_names: &[String]) -> Result<(), Error> {
+		self.run_update(false, Some(mount_names))
+	}
+
+	/// Indexes the whole collection from scratch, discarding all existing index entries.
+	/// The existing index keeps being served until the new one replaces it.
+	pub fn rebuild(&self) -> Result<(), Error> {
+		self.run_update(true, None)
+	}
+
+	fn run_update(&self, full_rebuild: bool, mount_names: Option<&[String]>) -> Result<(), Error> {
+		let kind = match (full_rebuild, mount_names) {
+			(true, _) => "rebuild".to_owned(),
+			(false, None) => "update".to_owned(),
+			(false, Some(names)) => format!("update of {}", names.join(", ")),
+		};
 		let start = time::Instant::now();
-		info!("Beginning library index update");
+		info!("Beginning library index {}", kind);
 
-		let album_art_pattern = self.settings_manager.get_index_album_art_pattern().ok();
+		self.update_control.begin();
+		let result = self.update_internal(full_rebuild, mount_names);
+		self.update_control.end();
+		self.scan_events.close();
+		self.aggregate_cache.invalidate();
+
+		if self.update_control.is_cancelled() {
+			info!(
+				"Library index {} was cancelled after {} seconds",
+				kind,
+				start.elapsed().as_millis() as f32 / 1000.0
+			);
+		} else {
+			info!(
+				"Library index {} took {} seconds",
+				kind,
+				start.elapsed().as_millis() as f32 / 1000.0
+			);
+		}
 
-		let cleaner = Cleaner::new(self.db.clone(), self.vfs_manager.clone());
-		cleaner.clean()?;
+		result
+	}
+
+	fn update_internal(
+		&self,
+		full_rebuild: bool,
+		mount_names: Option<&[String]>,
+	) -> Result<(), Error> {
+		let settings = self.settings_manager.read()?;
+		let vfs = self.vfs_manager.get_vfs()?;
+		let sources: Vec<PathBuf> = vfs
+			.mounts()
+			.iter()
+			.filter(|mount| mount_names.is_none_or(|names| names.contains(&mount.name)))
+			.map(|mount| mount.source.clone())
+			.collect();
+		let collection_roots: Vec<PathBuf> = vfs
+			.mounts()
+			.iter()
+			.map(|mount| mount.source.clone())
+			.collect();
+		let album_art_patterns = get_album_art_patterns(&settings, &vfs);
+		let genre_normalizer = get_genre_normalizer(&settings);
+		let tag_fallbacks = get_tag_fallbacks(&settings);
 
-		let (insert_sender, insert_receiver) = crossbeam_channel::unbounded();
+		// When nothing is indexed yet, there is no previous snapshot worth serving: content is written
+		// as soon as each directory is scanned, so that it can be browsed while the scan goes on.
+		let index_is_empty = match self.has_indexed_content() {
+			Ok(has_content) => !has_content,
+			Err(e) => {
+				error!("Could not check for existing index content: {}", e);
+				false
+			}
+		};
+
+		// With snapshot updates, stale entries are only removed once the new content is ready
+		let mut stale_entries = None;
+		if !full_rebuild && !index_is_empty {
+			let mut cleaner = Cleaner::new(self.db.clone(), self.vfs_manager.clone());
+			if mount_names.is_some() {
+				cleaner = cleaner.within(sources.clone());
+			}
+			if settings.snapshot_index_updates {
+				stale_entries = Some(cleaner.find_stale_entries()?);
+			} else {
+				cleaner.clean()?;
+			}
+		}
+
+		let channel_capacity = self.scan_channel_capacity.load(Ordering::SeqCst);
+		let (insert_sender, insert_receiver) = crossbeam_channel::bounded(channel_capacity);
 		let inserter_db = self.db.clone();
+		let inserter_update_control = self.update_control.clone();
 		let insertion_thread = std::thread::spawn(move || {
-			let mut inserter = Inserter::new(inserter_db, insert_receiver);
-			inserter.insert();
+			if full_rebuild && !index_is_empty {
+				let mut inserter = Inserter::buffering_all(inserter_db, insert_receiver);
+				inserter.insert();
+				// A cancelled rebuild only saw part of the collection
+				if !inserter_update_control.is_cancelled() {
+					inserter.replace_all();
+				}
+			} else if let Some(stale_entries) = stale_entries {
+				let mut inserter = Inserter::buffering_all(inserter_db, insert_receiver);
+				inserter.insert();
+				if !inserter_update_control.is_cancelled() {
+					inserter.apply_all(&stale_entries);
+				}
+			} else {
+				let mut inserter = Inserter::new(inserter_db, insert_receiver);
+				inserter.insert();
+			}
 		});
 
-		let (collect_sender, collect_receiver) = crossbeam_channel::unbounded();
+		// Thumbnails are generated apart from the other stages, which never wait for them
+		let mut prewarm_sender = None;
+		let mut prewarm_thread = None;
+		if settings.prewarm_thumbnails_on_scan {
+			let (sender, prewarm_receiver) = crossbeam_channel::unbounded();
+			let thumbnail_manager = self.thumbnail_manager.clone();
+			prewarm_sender = Some(sender);
+			prewarm_thread = Some(std::thread::spawn(move || {
+				Prewarmer::new(prewarm_receiver, thumbnail_manager).prewarm();
+			}));
+		}
+
+		let (collect_sender, collect_receiver) = crossbeam_channel::bounded(channel_capacity);
+		let artwork_priority = settings.artwork_priority;
 		let collector_thread = std::thread::spawn(move || {
-			let collector = Collector::new(collect_receiver, insert_sender, album_art_pattern);
+			let collector = Collector::new(
+				collect_receiver,
+				insert_sender,
+				album_art_patterns,
+				genre_normalizer,
+				tag_fallbacks,
+				artwork_priority,
+				prewarm_sender,
+			);
 			collector.collect();
 		});
 
-		let vfs = self.vfs_manager.get_vfs()?;
+		let update_control = self.update_control.clone();
+		let scan_errors = Arc::new(Mutex::new(Vec::new()));
+		let traverser_scan_errors = scan_errors.clone();
+		let max_depth = settings.index_max_depth.max(1) as usize;
+		let min_file_size = settings.min_file_size_bytes.max(0) as u64;
+		let skip_hidden_files = settings.skip_hidden_files;
+		let tag_priority = settings.tag_priority;
+		let metadata_readers = settings.metadata_readers;
+		let estimate_missing_durations = settings.estimate_missing_durations;
+		let scan_failure_limit = settings.scan_failure_limit.max(0) as u32;
+		let analyze_loudness = settings.analyze_loudness;
+		let symlinked_duplicates = settings.symlinked_duplicates;
+		let failed_files = self.failed_files.clone();
+		let remote_metadata = self.remote_metadata.clone();
+		let scan_events = self.scan_events.clone();
 		let traverser_thread = std::thread::spawn(move || {
-			let mounts = vfs.mounts();
-			let traverser = Traverser::new(collect_sender);
-			traverser.traverse(mounts.iter().map(|p| p.source.clone()).collect());
+			let traverser = Traverser::new(collect_sender, update_control, traverser_scan_errors)
+				.with_max_depth(max_depth)
+				.with_min_file_size(min_file_size)
+				.with_skip_hidden_files(skip_hidden_files)
+				.with_tag_priority(tag_priority)
+				.with_metadata_readers(metadata_readers)
+				.with_duration_estimates(estimate_missing_durations)
+				.with_failed_files(failed_files, scan_failure_limit)
+				.with_remote_metadata(remote_metadata)
+				.with_loudness_analysis(analyze_loudness)
+				.with_symlinked_duplicates(symlinked_duplicates, collection_roots)
+				.with_scan_events(scan_events)
+				.with_vfs(vfs);
+			traverser.traverse(sources);
 		});
 
 		if let Err(e) = traverser_thread.join() {
@@ -69,11 +471,94 @@ impl Index {
 			error!("Error joining on inserter thread: {:?}", e);
 		}
 
-		info!(
-			"Library index update took {} seconds",
-			start.elapsed().as_millis() as f32 / 1000.0
-		);
+		if let Some(Err(e)) = prewarm_thread.map(|t| t.join()) {
+			error!("Error joining on thumbnail prewarm thread: {:?}", e);
+		}
+
+		let scan_errors = std::mem::take(&mut *scan_errors.lock().unwrap());
+		*self.scan_errors.lock().unwrap() = scan_errors;
+
+		Ok(())
+	}
+
+	/// Returns problems found during the most recent index update.
+	pub fn scan_errors(&self) -> Vec<ScanError> {
+		self.scan_errors.lock().unwrap().clone()
+	}
 
+	/// Receives progress notifications of the update currently running, or of the next one.
+	pub fn subscribe_to_scan_events(&self) -> Receiver<ScanEvent> {
+		self.scan_events.subscribe()
+	}
+
+	/// Removes index entries which no longer belong to any mount, without scanning the collection.
+	pub fn prune_unmounted(&self) -> Result<(), Error> {
+		Cleaner::new(self.db.clone(), self.vfs_manager.clone())
+			.within(Vec::new())
+			.clean()?;
+		self.aggregate_cache.invalidate();
 		Ok(())
 	}
+
+	/// Re-reads metadata for a single song, or for all songs within a directory, and updates their index entries.
+	/// The rest of the index, including directory entries, is left untouched.
+	pub fn refresh<P: AsRef<Path>>(&self, virtual_path: P) -> Result<(), Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let real_path = vfs.virtual_to_real(virtual_path.as_ref())?;
+		let settings = self.settings_manager.read()?;
+		let refresher = Refresher::new(
+			self.db.clone(),
+			get_genre_normalizer(&settings),
+			get_tag_fallbacks(&settings),
+			settings.tag_priority,
+			settings.metadata_readers,
+			settings.estimate_missing_durations,
+			settings.artwork_priority,
+		);
+		let refreshed = refresher.refresh(&real_path)?;
+		self.aggregate_cache.invalidate();
+		match refreshed {
+			0 => Err(Error::SongNotFound(virtual_path.as_ref().to_owned())),
+			_ => Ok(()),
+		}
+	}
+}
+
+fn get_album_art_patterns(settings: &settings::Settings, vfs: &vfs::VFS) -> AlbumArtPatterns {
+	let default = settings::compile_album_art_pattern(&settings.index_album_art_pattern).ok();
+	let mount_overrides = vfs
+		.mounts()
+		.iter()
+		.filter_map(|mount| {
+			let pattern = mount.album_art_pattern.as_ref()?;
+			match settings::compile_album_art_pattern(pattern) {
+				Ok(regex) => Some((mount.source.clone(), regex)),
+				Err(e) => {
+					error!(
+						"Invalid album art pattern for mount `{}`: {}",
+						mount.name, e
+					);
+					None
+				}
+			}
+		})
+		.collect();
+	AlbumArtPatterns::new(default, mount_overrides)
+}
+
+fn get_tag_fallbacks(settings: &settings::Settings) -> TagFallbacks {
+	let non_empty = |s: &String| if s.is_empty() { None } else { Some(s.clone()) };
+	TagFallbacks {
+		artist: non_empty(&settings.fallback_artist),
+		album: non_empty(&settings.fallback_album),
+		album_artist_from_artist: settings.album_artist_fallback,
+		audiobook_directories: settings.audiobook_directories.clone(),
+		podcast_directories: settings.podcast_directories.clone(),
+	}
+}
+
+fn get_genre_normalizer(settings: &settings::Settings) -> Option<GenreNormalizer> {
+	settings
+		.normalize_genres
+		.then(|| GenreNormalizer::new(settings.genre_aliases.clone()))
 }
@@ -1,6 +1,7 @@
 use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
 use rayon::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::app::vfs;
 use crate::db::{self, directories, songs, DB};
@@ -19,17 +20,74 @@ pub enum Error {
 	Vfs(#[from] vfs::Error),
 }
 
+/// Index entries whose file or directory no longer exists, or is no longer part of any mount.
+pub struct StaleEntries {
+	directories: Vec<String>,
+	songs: Vec<String>,
+}
+
+impl StaleEntries {
+	pub fn delete(&self, connection: &mut SqliteConnection) -> QueryResult<()> {
+		for chunk in self.directories.chunks(INDEX_BUILDING_CLEAN_BUFFER_SIZE) {
+			diesel::delete(directories::table.filter(directories::path.eq_any(chunk)))
+				.execute(&mut *connection)?;
+		}
+		for chunk in self.songs.chunks(INDEX_BUILDING_CLEAN_BUFFER_SIZE) {
+			diesel::delete(songs::table.filter(songs::path.eq_any(chunk)))
+				.execute(&mut *connection)?;
+		}
+		Ok(())
+	}
+}
+
 pub struct Cleaner {
 	db: DB,
 	vfs_manager: vfs::Manager,
+	roots: Option<Vec<PathBuf>>,
 }
 
 impl Cleaner {
 	pub fn new(db: DB, vfs_manager: vfs::Manager) -> Self {
-		Self { db, vfs_manager }
+		Self {
+			db,
+			vfs_manager,
+			roots: None,
+		}
+	}
+
+	/// Only checks whether files and directories still exist within the given roots.
+	/// Entries elsewhere are kept as long as they belong to a mount.
+	pub fn within(mut self, roots: Vec<PathBuf>) -> Self {
+		self.roots = Some(roots);
+		self
+	}
+
+	fn is_stale(&self, vfs: &vfs::VFS, path: &Path) -> bool {
+		if vfs.real_to_virtual(path).is_err() {
+			return true;
+		}
+		let is_checked = match &self.roots {
+			Some(roots) => roots.iter().any(|root| path.starts_with(root)),
+			None => true,
+		};
+		if !is_checked {
+			return false;
+		}
+		match vfs.locate_remote(path) {
+			// Entries of remotes which cannot be reached are kept until they can be checked
+			Some(remote) => !remote.exists().unwrap_or(true),
+			None => !path.exists(),
+		}
 	}
 
 	pub fn clean(&self) -> Result<(), Error> {
+		let stale_entries = self.find_stale_entries()?;
+		let mut connection = self.db.connect()?;
+		stale_entries.delete(&mut connection)?;
+		Ok(())
+	}
+
+	pub fn find_stale_entries(&self) -> Result<StaleEntries, Error> {
 		let vfs = self.vfs_manager.get_vfs()?;
 
 		let all_directories: Vec<String> = {
@@ -47,39 +105,26 @@ impl Cleaner {
 		let list_missing_directories = || {
 			all_directories
 				.par_iter()
-				.filter(|ref directory_path| {
-					let path = Path::new(&directory_path);
-					!path.exists() || vfs.real_to_virtual(path).is_err()
-				})
+				.filter(|directory_path| self.is_stale(&vfs, Path::new(directory_path)))
+				.cloned()
 				.collect::<Vec<_>>()
 		};
 
 		let list_missing_songs = || {
 			all_songs
 				.par_iter()
-				.filter(|ref song_path| {
-					let path = Path::new(&song_path);
-					!path.exists() || vfs.real_to_virtual(path).is_err()
-				})
+				.filter(|song_path| self.is_stale(&vfs, Path::new(song_path)))
+				.cloned()
 				.collect::<Vec<_>>()
 		};
 
 		let thread_pool = rayon::ThreadPoolBuilder::new().build()?;
-		let (missing_directories, missing_songs) =
+		let (stale_directories, stale_songs) =
 			thread_pool.join(list_missing_directories, list_missing_songs);
 
-		{
-			let mut connection = self.db.connect()?;
-			for chunk in missing_directories[..].chunks(INDEX_BUILDING_CLEAN_BUFFER_SIZE) {
-				diesel::delete(directories::table.filter(directories::path.eq_any(chunk)))
-					.execute(&mut connection)?;
-			}
-			for chunk in missing_songs[..].chunks(INDEX_BUILDING_CLEAN_BUFFER_SIZE) {
-				diesel::delete(songs::table.filter(songs::path.eq_any(chunk)))
-					.execute(&mut connection)?;
-			}
-		}
-
-		Ok(())
+		Ok(StaleEntries {
+			directories: stale_directories,
+			songs: stale_songs,
+		})
 	}
 }
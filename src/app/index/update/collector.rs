@@ -1,25 +1,66 @@
 use crossbeam_channel::{Receiver, Sender};
 use log::error;
 use regex::Regex;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
 
 use super::*;
+use crate::app::index::metadata::SongTags;
+use crate::app::index::MediaKind;
+use crate::app::settings::ArtworkPriority;
+use crate::utils::get_audio_format;
+
+/// Album art patterns to use within each mount, falling back to the global pattern.
+pub struct AlbumArtPatterns {
+	default: Option<Regex>,
+	mount_overrides: Vec<(PathBuf, Regex)>,
+}
+
+impl AlbumArtPatterns {
+	pub fn new(default: Option<Regex>, mount_overrides: Vec<(PathBuf, Regex)>) -> Self {
+		Self {
+			default,
+			mount_overrides,
+		}
+	}
+
+	fn for_directory(&self, path: &Path) -> Option<&Regex> {
+		self.mount_overrides
+			.iter()
+			.find(|(source, _)| path.starts_with(source))
+			.map(|(_, pattern)| pattern)
+			.or(self.default.as_ref())
+	}
+}
 
 pub struct Collector {
 	receiver: Receiver<traverser::Directory>,
 	sender: Sender<inserter::Item>,
-	album_art_pattern: Option<Regex>,
+	album_art_patterns: AlbumArtPatterns,
+	genre_normalizer: Option<GenreNormalizer>,
+	tag_fallbacks: TagFallbacks,
+	artwork_priority: ArtworkPriority,
+	prewarm_sender: Option<Sender<PathBuf>>,
 }
 
 impl Collector {
 	pub fn new(
 		receiver: Receiver<traverser::Directory>,
 		sender: Sender<inserter::Item>,
-		album_art_pattern: Option<Regex>,
+		album_art_patterns: AlbumArtPatterns,
+		genre_normalizer: Option<GenreNormalizer>,
+		tag_fallbacks: TagFallbacks,
+		artwork_priority: ArtworkPriority,
+		prewarm_sender: Option<Sender<PathBuf>>,
 	) -> Self {
 		Self {
 			receiver,
 			sender,
-			album_art_pattern,
+			album_art_patterns,
+			genre_normalizer,
+			tag_fallbacks,
+			artwork_priority,
+			prewarm_sender,
 		}
 	}
 
@@ -31,24 +72,37 @@ impl Collector {
 
 	fn collect_directory(&self, directory: traverser::Directory) {
 		let mut directory_album = None;
-		let mut directory_year = None;
+		let mut directory_years: HashMap<i32, usize> = HashMap::new();
+		let mut directory_genres = BTreeSet::new();
 		let mut directory_artist = None;
+		let mut directory_duration = None;
 		let mut inconsistent_directory_album = false;
-		let mut inconsistent_directory_year = false;
 		let mut inconsistent_directory_artist = false;
 
-		let directory_artwork = self.get_artwork(&directory);
+		let folder_artwork = self.get_folder_artwork(&directory);
+		let folder_artwork_path = folder_artwork.as_ref().map(|(path, _)| path.clone());
+		let (directory_artwork, directory_artwork_mime) =
+			match folder_artwork.or_else(|| Self::get_embedded_artwork(&directory)) {
+				Some((path, mime)) => (Some(path), mime),
+				None => (None, None),
+			};
+		if let (Some(sender), Some(artwork)) = (&self.prewarm_sender, &directory_artwork) {
+			if let Err(e) = sender.send(PathBuf::from(artwork)) {
+				error!("Error while sending artwork from collector: {}", e);
+			}
+		}
+		let directory_media = Self::get_media(&directory);
 		let directory_path_string = directory.path.to_string_lossy().to_string();
 		let directory_parent_string = directory.parent.map(|p| p.to_string_lossy().to_string());
 
 		for song in directory.songs {
-			let tags = song.metadata;
+			let song_loudness = (song.track_loudness, song.album_loudness);
+			let mut tags = self.tag_fallbacks.apply(song.metadata);
+			tags.content_type = Some(self.tag_fallbacks.content_type(&song.path, &tags));
 			let path_string = song.path.to_string_lossy().to_string();
 
-			if tags.year.is_some() {
-				inconsistent_directory_year |=
-					directory_year.is_some() && directory_year != tags.year;
-				directory_year = tags.year;
+			if let Some(year) = tags.year {
+				*directory_years.entry(year).or_default() += 1;
 			}
 
 			if tags.album.is_some() {
@@ -57,46 +111,58 @@ impl Collector {
 				directory_album = tags.album.as_ref().cloned();
 			}
 
-			if tags.album_artist.is_some() {
+			let effective_album_artist = self.tag_fallbacks.effective_album_artist(&tags);
+			if effective_album_artist.is_some() {
 				inconsistent_directory_artist |=
-					directory_artist.is_some() && directory_artist != tags.album_artist;
-				directory_artist = tags.album_artist.as_ref().cloned();
-			} else if tags.artist.is_some() {
-				inconsistent_directory_artist |=
-					directory_artist.is_some() && directory_artist != tags.artist;
-				directory_artist = tags.artist.as_ref().cloned();
+					directory_artist.is_some() && directory_artist != effective_album_artist;
+				directory_artist = effective_album_artist.clone();
 			}
 
-			let artwork_path = if tags.has_artwork {
-				Some(path_string.clone())
-			} else {
-				directory_artwork.as_ref().cloned()
-			};
+			if let Some(duration) = tags.duration {
+				directory_duration = Some(directory_duration.unwrap_or(0) + duration as i32);
+			}
 
-			if let Err(e) = self.sender.send(inserter::Item::Song(inserter::Song {
-				path: path_string,
-				parent: directory_path_string.clone(),
-				disc_number: tags.disc_number.map(|n| n as i32),
-				track_number: tags.track_number.map(|n| n as i32),
-				title: tags.title,
-				duration: tags.duration.map(|n| n as i32),
-				artist: tags.artist,
-				album_artist: tags.album_artist,
-				album: tags.album,
-				year: tags.year,
-				artwork: artwork_path,
-				lyricist: tags.lyricist,
-				composer: tags.composer,
-				genre: tags.genre,
-				label: tags.label,
-			})) {
+			let artwork_path = pick_song_artwork(
+				&path_string,
+				tags.has_artwork,
+				tags.artwork_size,
+				folder_artwork_path.as_ref(),
+				directory_artwork.as_ref(),
+				self.artwork_priority,
+			);
+
+			let mut song = make_song(
+				path_string,
+				directory_path_string.clone(),
+				tags,
+				effective_album_artist,
+				artwork_path,
+				self.genre_normalizer.as_ref(),
+			);
+			song.track_loudness = song_loudness.0;
+			song.album_loudness = song_loudness.1;
+			if let Some(genre) = &song.genre {
+				directory_genres.insert(genre.clone());
+			}
+			if let Err(e) = self.sender.send(inserter::Item::Song(song)) {
 				error!("Error while sending song from collector: {}", e);
 			}
 		}
 
-		if inconsistent_directory_year {
-			directory_year = None;
-		}
+		// Songs disagreeing on the year usually mean a few tracks are mistagged or were
+		// released earlier, so the most common year wins, and the earliest one breaks ties.
+		let directory_year = directory_years
+			.into_iter()
+			.max_by(|(year_a, count_a), (year_b, count_b)| {
+				count_a.cmp(count_b).then(year_b.cmp(year_a))
+			})
+			.map(|(year, _)| year);
+		let directory_genres = if directory_genres.is_empty() {
+			None
+		} else {
+			serde_json::to_string(&directory_genres).ok()
+		};
+
 		if inconsistent_directory_album {
 			directory_album = None;
 		}
@@ -110,40 +176,194 @@ impl Collector {
 				path: directory_path_string,
 				parent: directory_parent_string,
 				artwork: directory_artwork,
+				artwork_mime: directory_artwork_mime,
+				duration: directory_duration,
 				album: directory_album,
 				artist: directory_artist,
 				year: directory_year,
 				date_added: directory.created,
+				media: directory_media,
+				genres: directory_genres,
 			})) {
 			error!("Error while sending directory from collector: {}", e);
 		}
 	}
 
-	fn get_artwork(&self, directory: &traverser::Directory) -> Option<String> {
-		let regex_artwork = directory.other_files.iter().find_map(|path| {
+	/// Returns the path of the image matching the album art pattern within a directory, along with
+	/// its MIME type when known.
+	fn get_folder_artwork(
+		&self,
+		directory: &traverser::Directory,
+	) -> Option<(String, Option<String>)> {
+		let album_art_pattern = self.album_art_patterns.for_directory(&directory.path);
+		directory.other_files.iter().find_map(|path| {
 			let matches = path
 				.file_name()
 				.and_then(|name| name.to_str())
-				.map(|name| match &self.album_art_pattern {
+				.map(|name| match album_art_pattern {
 					Some(pattern) => pattern.is_match(name),
 					None => false,
 				})
 				.unwrap_or(false);
 			if matches {
-				Some(path.to_string_lossy().to_string())
+				Some((path.to_string_lossy().to_string(), get_image_mime(path)))
 			} else {
 				None
 			}
-		});
+		})
+	}
 
-		let embedded_artwork = directory.songs.iter().find_map(|song| {
+	/// Returns the path of the first song with embedded artwork within a directory, along with the
+	/// MIME type of its artwork when known.
+	fn get_embedded_artwork(directory: &traverser::Directory) -> Option<(String, Option<String>)> {
+		directory.songs.iter().find_map(|song| {
 			if song.metadata.has_artwork {
-				Some(song.path.to_string_lossy().to_string())
+				Some((
+					song.path.to_string_lossy().to_string(),
+					song.metadata.artwork_mime.clone(),
+				))
 			} else {
 				None
 			}
-		});
+		})
+	}
+
+	/// Returns the paths of companion files such as cover scans or cue sheets, as a JSON list.
+	fn get_media(directory: &traverser::Directory) -> Option<String> {
+		let mut media: Vec<String> = directory
+			.other_files
+			.iter()
+			.filter(|path| MediaKind::from_path(path).is_some())
+			.map(|path| path.to_string_lossy().to_string())
+			.collect();
+		if media.is_empty() {
+			return None;
+		}
+		media.sort();
+		serde_json::to_string(&media).ok()
+	}
+}
+
+/// Picks the artwork of a song among its embedded artwork and the image found in its folder.
+/// When neither is available, the song uses the artwork of its directory, which may be embedded
+/// in another song.
+pub fn pick_song_artwork(
+	song_path: &str,
+	has_embedded_artwork: bool,
+	embedded_artwork_size: Option<u64>,
+	folder_artwork: Option<&String>,
+	directory_artwork: Option<&String>,
+	priority: ArtworkPriority,
+) -> Option<String> {
+	let embedded_artwork = has_embedded_artwork.then(|| song_path.to_owned());
+	let preferred_artwork = match priority {
+		ArtworkPriority::Embedded => embedded_artwork.or_else(|| folder_artwork.cloned()),
+		ArtworkPriority::Folder => folder_artwork.cloned().or(embedded_artwork),
+		ArtworkPriority::Smaller => match (embedded_artwork, folder_artwork) {
+			(Some(embedded_artwork), Some(folder_artwork)) => {
+				// Embedded artwork wins ties, and comparisons involving unknown sizes
+				let folder_artwork_size = std::fs::metadata(folder_artwork).map(|m| m.len()).ok();
+				match (embedded_artwork_size, folder_artwork_size) {
+					(Some(e), Some(f)) if f < e => Some(folder_artwork.clone()),
+					_ => Some(embedded_artwork),
+				}
+			}
+			(embedded_artwork, folder_artwork) => {
+				embedded_artwork.or_else(|| folder_artwork.cloned())
+			}
+		},
+	};
+	preferred_artwork.or_else(|| directory_artwork.cloned())
+}
 
-		regex_artwork.or(embedded_artwork)
+pub fn make_song(
+	path: String,
+	parent: String,
+	tags: SongTags,
+	effective_album_artist: Option<String>,
+	artwork: Option<String>,
+	genre_normalizer: Option<&GenreNormalizer>,
+) -> inserter::Song {
+	let (genre, original_genre) = match (genre_normalizer, tags.genre) {
+		(Some(normalizer), Some(original)) => normalizer.apply(original),
+		(_, genre) => (genre, None),
+	};
+	let format = get_audio_format(Path::new(&path)).map(|f| f.name().to_owned());
+	let file_size = std::fs::metadata(&path).ok().map(|m| m.len() as i64);
+	let extra = if tags.extra.is_empty() {
+		None
+	} else {
+		serde_json::to_string(&tags.extra).ok()
+	};
+	inserter::Song {
+		path,
+		parent,
+		disc_number: tags.disc_number.map(|n| n as i32),
+		track_number: tags.track_number.map(|n| n as i32),
+		title: tags.title,
+		duration: tags.duration.map(|n| n as i32),
+		artist: tags.artist,
+		album_artist: tags.album_artist,
+		effective_album_artist,
+		album: tags.album,
+		year: tags.year,
+		artwork,
+		lyricist: join_names(&tags.lyricists),
+		composer: join_names(&tags.composers),
+		genre,
+		label: tags.label,
+		hidden: tags.hidden,
+		grouping: tags.grouping,
+		work: tags.work,
+		movement_name: tags.movement_name,
+		movement_number: tags.movement_number.map(|n| n as i32),
+		original_genre,
+		isrc: tags.isrc,
+		catalog_number: tags.catalog_number,
+		format,
+		original_year: tags.original_year,
+		conductor: join_names(&tags.conductors),
+		remixer: join_names(&tags.remixers),
+		file_size,
+		extra,
+		disc_total: tags.disc_total.map(|n| n as i32),
+		track_total: tags.track_total.map(|n| n as i32),
+		encoded_by: tags.encoded_by,
+		encoder_settings: tags.encoder_settings,
+		original_artist: join_names(&tags.original_artists),
+		original_album: tags.original_album,
+		rating: tags.rating.map(|r| r as i32),
+		language: join_names(&tags.languages),
+		media_type: tags.media_type,
+		disc_subtitle: tags.disc_subtitle,
+		initial_key: tags.initial_key,
+		track_loudness: None,
+		album_loudness: None,
+		duration_source: tags.duration_source.map(|s| s.as_str().to_owned()),
+		content_type: tags.content_type.unwrap_or_default().as_str().to_owned(),
+		arranger: join_names(&tags.arrangers),
+		performer: join_names(&tags.performers),
 	}
 }
+
+/// Stores multiple names in a single field, the same way album artist tags list several artists.
+fn join_names(names: &[String]) -> Option<String> {
+	if names.is_empty() {
+		None
+	} else {
+		Some(names.join("; "))
+	}
+}
+
+fn get_image_mime(path: &Path) -> Option<String> {
+	let extension = path.extension()?.to_str()?.to_lowercase();
+	let mime = match extension.as_str() {
+		"bmp" => "image/bmp",
+		"gif" => "image/gif",
+		"jpeg" | "jpg" => "image/jpeg",
+		"png" => "image/png",
+		"webp" => "image/webp",
+		_ => return None,
+	};
+	Some(mime.to_owned())
+}
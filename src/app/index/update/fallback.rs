@@ -0,0 +1,101 @@
+use std::path::Path;
+
+use crate::app::index::metadata::{ContentType, SongTags};
+
+/// Values substituted for missing tags while indexing, so that untagged songs group together.
+#[derive(Clone, Debug, Default)]
+pub struct TagFallbacks {
+	pub artist: Option<String>,
+	pub album: Option<String>,
+	/// Whether songs without an album artist are grouped under their track artist.
+	pub album_artist_from_artist: bool,
+	/// Names of directories holding audiobooks, for songs whose tags do not tell their content type.
+	pub audiobook_directories: Vec<String>,
+	/// Names of directories holding podcasts, for songs whose tags do not tell their content type.
+	pub podcast_directories: Vec<String>,
+}
+
+impl TagFallbacks {
+	pub fn apply(&self, mut tags: SongTags) -> SongTags {
+		if tags.artist.is_none() {
+			tags.artist = self.artist.clone();
+		}
+		if tags.album.is_none() {
+			tags.album = self.album.clone();
+		}
+		tags
+	}
+
+	/// Album artist used to group songs into albums. The album artist tag itself is left untouched.
+	pub fn effective_album_artist(&self, tags: &SongTags) -> Option<String> {
+		match (&tags.album_artist, self.album_artist_from_artist) {
+			(Some(album_artist), _) => Some(album_artist.clone()),
+			(None, true) => tags.artist.clone(),
+			(None, false) => None,
+		}
+	}
+
+	/// Content type of a song, from its tags or else from the names of the directories containing it.
+	pub fn content_type(&self, path: &Path, tags: &SongTags) -> ContentType {
+		if let Some(content_type) = tags.content_type {
+			return content_type;
+		}
+		let is_within = |names: &[String]| {
+			path.ancestors().skip(1).any(|directory| {
+				let name = directory.file_name().map(|n| n.to_string_lossy());
+				name.is_some_and(|n| names.iter().any(|d| d.eq_ignore_ascii_case(&n)))
+			})
+		};
+		if is_within(&self.audiobook_directories) {
+			ContentType::Audiobook
+		} else if is_within(&self.podcast_directories) {
+			ContentType::Podcast
+		} else {
+			ContentType::Music
+		}
+	}
+}
+
+#[test]
+fn fallbacks_do_not_overwrite_present_tags() {
+	let fallbacks = TagFallbacks {
+		artist: Some("Unknown Artist".to_owned()),
+		album: Some("Unknown Album".to_owned()),
+		album_artist_from_artist: true,
+		..Default::default()
+	};
+
+	let tags = fallbacks.apply(SongTags {
+		artist: Some("Khemmis".to_owned()),
+		..Default::default()
+	});
+	assert_eq!(tags.artist, Some("Khemmis".to_owned()));
+	assert_eq!(tags.album, Some("Unknown Album".to_owned()));
+}
+
+#[test]
+fn content_type_falls_back_to_directory_names() {
+	let fallbacks = TagFallbacks {
+		audiobook_directories: vec!["Audiobooks".to_owned()],
+		podcast_directories: vec!["Podcasts".to_owned()],
+		..Default::default()
+	};
+
+	let path = Path::new("/music/audiobooks/Dune/01.mp3");
+	let tags = SongTags::default();
+	assert_eq!(fallbacks.content_type(path, &tags), ContentType::Audiobook);
+
+	let path = Path::new("/music/Podcasts/Show/episode.mp3");
+	assert_eq!(fallbacks.content_type(path, &tags), ContentType::Podcast);
+
+	let tags = SongTags {
+		content_type: Some(ContentType::Podcast),
+		..Default::default()
+	};
+	let path = Path::new("/music/Audiobooks/Dune/01.mp3");
+	assert_eq!(fallbacks.content_type(path, &tags), ContentType::Podcast);
+
+	let path = Path::new("/music/Khemmis/Hunted/01.mp3");
+	let tags = SongTags::default();
+	assert_eq!(fallbacks.content_type(path, &tags), ContentType::Music);
+}
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+/// Collapses equivalent genre spellings into a single canonical label.
+///
+/// Genres are compared after trimming and lowercasing. Aliases map any such
+/// spelling to the label stored in the index; genres without an alias are
+/// stored in their trimmed, lowercase form.
+pub struct GenreNormalizer {
+	aliases: HashMap<String, String>,
+}
+
+impl GenreNormalizer {
+	pub fn new(aliases: HashMap<String, String>) -> Self {
+		let mut normalized_aliases = HashMap::new();
+		for canonical in aliases.values() {
+			normalized_aliases.insert(normalize_key(canonical), canonical.trim().to_owned());
+		}
+		for (alias, canonical) in aliases {
+			normalized_aliases.insert(normalize_key(&alias), canonical.trim().to_owned());
+		}
+		Self {
+			aliases: normalized_aliases,
+		}
+	}
+
+	pub fn normalize(&self, genre: &str) -> String {
+		let key = normalize_key(genre);
+		match self.aliases.get(&key) {
+			Some(canonical) => canonical.clone(),
+			None => key,
+		}
+	}
+
+	/// Returns the genre to index, along with the original tag value when normalization altered it.
+	pub fn apply(&self, genre: String) -> (Option<String>, Option<String>) {
+		let normalized = self.normalize(&genre);
+		if normalized == genre {
+			(Some(normalized), None)
+		} else {
+			(Some(normalized), Some(genre))
+		}
+	}
+}
+
+fn normalize_key(genre: &str) -> String {
+	genre.trim().to_lowercase()
+}
+
+#[test]
+fn collapses_spellings_using_aliases() {
+	let normalizer = GenreNormalizer::new(HashMap::from([
+		("hip hop".to_owned(), "Hip-Hop".to_owned()),
+		("hiphop".to_owned(), "Hip-Hop".to_owned()),
+	]));
+	assert_eq!(normalizer.normalize("Hip-Hop"), "Hip-Hop");
+	assert_eq!(normalizer.normalize(" Hip Hop"), "Hip-Hop");
+	assert_eq!(normalizer.normalize("hiphop"), "Hip-Hop");
+}
+
+#[test]
+fn lowercases_genres_without_alias() {
+	let normalizer = GenreNormalizer::new(HashMap::new());
+	assert_eq!(normalizer.normalize(" Doom Metal "), "doom metal");
+}
@@ -1,13 +1,17 @@
-use crossbeam_channel::Receiver;
+use crossbeam_channel::{Receiver, RecvTimeoutError};
 use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
 use log::error;
+use std::time::{Duration, Instant};
 
+use super::cleaner::StaleEntries;
 use crate::db::{directories, songs, DB};
 
 const INDEX_BUILDING_INSERT_BUFFER_SIZE: usize = 1000; // Insertions in each transaction
+const STREAMING_FLUSH_INTERVAL: Duration = Duration::from_millis(500); // Longest delay before scanned directories become visible
 
-#[derive(Debug, Insertable)]
-#[diesel(table_name = songs)]
+#[derive(Debug, Insertable, AsChangeset)]
+#[diesel(table_name = songs, treat_none_as_null = true)]
 pub struct Song {
 	pub path: String,
 	pub parent: String,
@@ -24,6 +28,38 @@ pub struct Song {
 	pub composer: Option<String>,
 	pub genre: Option<String>,
 	pub label: Option<String>,
+	pub hidden: bool,
+	pub grouping: Option<String>,
+	pub work: Option<String>,
+	pub movement_name: Option<String>,
+	pub movement_number: Option<i32>,
+	pub original_genre: Option<String>,
+	pub isrc: Option<String>,
+	pub catalog_number: Option<String>,
+	pub format: Option<String>,
+	pub original_year: Option<i32>,
+	pub conductor: Option<String>,
+	pub remixer: Option<String>,
+	pub file_size: Option<i64>,
+	pub extra: Option<String>,
+	pub disc_total: Option<i32>,
+	pub track_total: Option<i32>,
+	pub encoded_by: Option<String>,
+	pub encoder_settings: Option<String>,
+	pub original_artist: Option<String>,
+	pub original_album: Option<String>,
+	pub rating: Option<i32>,
+	pub effective_album_artist: Option<String>,
+	pub language: Option<String>,
+	pub media_type: Option<String>,
+	pub disc_subtitle: Option<String>,
+	pub initial_key: Option<String>,
+	pub track_loudness: Option<i32>,
+	pub album_loudness: Option<i32>,
+	pub duration_source: Option<String>,
+	pub content_type: String,
+	pub arranger: Option<String>,
+	pub performer: Option<String>,
 }
 
 #[derive(Debug, Insertable)]
@@ -36,8 +72,13 @@ pub struct Directory {
 	pub album: Option<String>,
 	pub artwork: Option<String>,
 	pub date_added: i32,
+	pub artwork_mime: Option<String>,
+	pub duration: Option<i32>,
+	pub media: Option<String>,
+	pub genres: Option<String>,
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum Item {
 	Directory(Directory),
 	Song(Song),
@@ -48,6 +89,8 @@ pub struct Inserter {
 	new_directories: Vec<Directory>,
 	new_songs: Vec<Song>,
 	db: DB,
+	buffer_all: bool,
+	last_flush: Instant,
 }
 
 impl Inserter {
@@ -59,12 +102,74 @@ impl Inserter {
 			new_directories,
 			new_songs,
 			db,
+			buffer_all: false,
+			last_flush: Instant::now(),
 		}
 	}
 
+	/// Creates an inserter which holds on to all received items until `replace_all` or `apply_all` is called.
+	/// Items which have not been written by then are discarded.
+	pub fn buffering_all(db: DB, receiver: Receiver<Item>) -> Self {
+		let mut inserter = Self::new(db, receiver);
+		inserter.buffer_all = true;
+		inserter
+	}
+
+	/// Replaces the entire content of the index with the received items, in a single transaction.
+	pub fn replace_all(&mut self) {
+		self.write_all(|connection| {
+			diesel::delete(songs::table).execute(&mut *connection)?;
+			diesel::delete(directories::table).execute(&mut *connection)?;
+			Ok(())
+		});
+	}
+
+	/// Removes stale entries from the index and writes the received items, in a single transaction.
+	pub fn apply_all(&mut self, stale_entries: &StaleEntries) {
+		self.write_all(|connection| stale_entries.delete(connection));
+	}
+
+	fn write_all<F>(&mut self, clear: F)
+	where
+		F: FnOnce(&mut SqliteConnection) -> QueryResult<()>,
+	{
+		let res = self.db.connect().ok().and_then(|mut connection| {
+			diesel::Connection::transaction::<_, diesel::result::Error, _>(
+				&mut *connection,
+				|connection| {
+					clear(connection)?;
+					for chunk in self
+						.new_directories
+						.chunks(INDEX_BUILDING_INSERT_BUFFER_SIZE)
+					{
+						diesel::insert_into(directories::table)
+							.values(chunk)
+							.execute(connection)?;
+					}
+					for chunk in self.new_songs.chunks(INDEX_BUILDING_INSERT_BUFFER_SIZE) {
+						diesel::insert_into(songs::table)
+							.values(chunk)
+							.execute(connection)?;
+					}
+					Ok(())
+				},
+			)
+			.ok()
+		});
+		if res.is_none() {
+			error!("Could not write index content to database");
+		}
+		self.new_directories.clear();
+		self.new_songs.clear();
+	}
+
 	pub fn insert(&mut self) {
-		while let Ok(item) = self.receiver.recv() {
-			self.insert_item(item);
+		loop {
+			match self.receiver.recv_timeout(STREAMING_FLUSH_INTERVAL) {
+				Ok(item) => self.insert_item(item),
+				Err(RecvTimeoutError::Timeout) => self.flush_pending(),
+				Err(RecvTimeoutError::Disconnected) => break,
+			}
 		}
 	}
 
@@ -72,19 +177,38 @@ impl Inserter {
 		match insert {
 			Item::Directory(d) => {
 				self.new_directories.push(d);
-				if self.new_directories.len() >= INDEX_BUILDING_INSERT_BUFFER_SIZE {
-					self.flush_directories();
+				// Songs are received before their parent directory, so this is a point where
+				// every buffered directory is complete and can be made visible.
+				if !self.buffer_all
+					&& (self.new_directories.len() >= INDEX_BUILDING_INSERT_BUFFER_SIZE
+						|| self.last_flush.elapsed() >= STREAMING_FLUSH_INTERVAL)
+				{
+					self.flush_pending();
 				}
 			}
 			Item::Song(s) => {
 				self.new_songs.push(s);
-				if self.new_songs.len() >= INDEX_BUILDING_INSERT_BUFFER_SIZE {
+				if !self.buffer_all && self.new_songs.len() >= INDEX_BUILDING_INSERT_BUFFER_SIZE {
 					self.flush_songs();
 				}
 			}
 		};
 	}
 
+	/// Writes all buffered items, unless they are held until `replace_all` or `apply_all`.
+	fn flush_pending(&mut self) {
+		if self.buffer_all {
+			return;
+		}
+		if !self.new_directories.is_empty() {
+			self.flush_directories();
+		}
+		if !self.new_songs.is_empty() {
+			self.flush_songs();
+		}
+		self.last_flush = Instant::now();
+	}
+
 	fn flush_directories(&mut self) {
 		let res = self.db.connect().ok().and_then(|mut connection| {
 			diesel::insert_into(directories::table)
@@ -114,11 +238,6 @@ impl Inserter {
 
 impl Drop for Inserter {
 	fn drop(&mut self) {
-		if !self.new_directories.is_empty() {
-			self.flush_directories();
-		}
-		if !self.new_songs.is_empty() {
-			self.flush_songs();
-		}
+		self.flush_pending();
 	}
 }
@@ -0,0 +1,34 @@
+use crossbeam_channel::Receiver;
+use log::warn;
+use rayon::prelude::*;
+use std::path::PathBuf;
+
+use crate::app::thumbnail;
+
+/// Generates thumbnails for the album artwork found while scanning, so that clients do not wait
+/// for them on first load. Generation shares its permits with regular thumbnail requests.
+pub struct Prewarmer {
+	receiver: Receiver<PathBuf>,
+	thumbnail_manager: thumbnail::Manager,
+}
+
+impl Prewarmer {
+	pub fn new(receiver: Receiver<PathBuf>, thumbnail_manager: thumbnail::Manager) -> Self {
+		Self {
+			receiver,
+			thumbnail_manager,
+		}
+	}
+
+	pub fn prewarm(&self) {
+		let options = thumbnail::Options::default();
+		self.receiver.iter().par_bridge().for_each(|artwork_path| {
+			if let Err(e) = self
+				.thumbnail_manager
+				.get_thumbnail(&artwork_path, &options)
+			{
+				warn!("Could not prewarm thumbnail during scan: {}", e);
+			}
+		});
+	}
+}
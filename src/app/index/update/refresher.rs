@@ -0,0 +1,129 @@
+use diesel::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::collector;
+use super::fallback::TagFallbacks;
+use super::genre::GenreNormalizer;
+use super::Error;
+use crate::app::index::metadata;
+use crate::app::settings::{ArtworkPriority, MetadataReader, TagContainer};
+use crate::db::{directories, songs, DB};
+use crate::utils::get_audio_format;
+
+/// Re-reads metadata for songs which are already in the index, without traversing the rest of the collection.
+pub struct Refresher {
+	db: DB,
+	genre_normalizer: Option<GenreNormalizer>,
+	tag_fallbacks: TagFallbacks,
+	tag_priority: Vec<TagContainer>,
+	metadata_readers: HashMap<String, MetadataReader>,
+	estimate_missing_durations: bool,
+	artwork_priority: ArtworkPriority,
+}
+
+impl Refresher {
+	pub fn new(
+		db: DB,
+		genre_normalizer: Option<GenreNormalizer>,
+		tag_fallbacks: TagFallbacks,
+		tag_priority: Vec<TagContainer>,
+		metadata_readers: HashMap<String, MetadataReader>,
+		estimate_missing_durations: bool,
+		artwork_priority: ArtworkPriority,
+	) -> Self {
+		Self {
+			db,
+			genre_normalizer,
+			tag_fallbacks,
+			tag_priority,
+			metadata_readers,
+			estimate_missing_durations,
+			artwork_priority,
+		}
+	}
+
+	/// Refreshes the song at `real_path`, or every indexed song below it if it is a directory.
+	/// Returns how many songs were updated.
+	pub fn refresh(&self, real_path: &Path) -> Result<usize, Error> {
+		let mut connection = self.db.connect()?;
+
+		let real_path_string = real_path.to_string_lossy().into_owned();
+		let song_path_filter = {
+			let mut path_buf = real_path.to_path_buf();
+			path_buf.push("%");
+			path_buf.as_path().to_string_lossy().into_owned()
+		};
+		let indexed_songs: Vec<(String, String, Option<i32>, Option<i32>)> = songs::table
+			.select((
+				songs::path,
+				songs::parent,
+				songs::track_loudness,
+				songs::album_loudness,
+			))
+			.filter(
+				songs::path
+					.eq(&real_path_string)
+					.or(songs::path.like(&song_path_filter)),
+			)
+			.load(&mut connection)?;
+
+		let mut num_refreshed = 0;
+		for (song_path, parent, track_loudness, album_loudness) in indexed_songs {
+			let song_file = Path::new(&song_path);
+			let tags =
+				metadata::read_with_readers(song_file, &self.tag_priority, &self.metadata_readers);
+			let mut tags = match tags {
+				Some(tags) => self.tag_fallbacks.apply(tags),
+				None => continue,
+			};
+			if self.estimate_missing_durations {
+				metadata::fill_missing_duration(song_file, &mut tags);
+			}
+			tags.content_type = Some(self.tag_fallbacks.content_type(song_file, &tags));
+
+			let directory_artwork =
+				if tags.has_artwork && self.artwork_priority == ArtworkPriority::Embedded {
+					None
+				} else {
+					directories::table
+						.select(directories::artwork)
+						.filter(directories::path.eq(&parent))
+						.get_result::<Option<String>>(&mut connection)
+						.optional()?
+						.flatten()
+				};
+			// Directory artwork is embedded in a song when no image was found in the folder
+			let folder_artwork = directory_artwork
+				.as_ref()
+				.filter(|path| get_audio_format(Path::new(path)).is_none());
+			let artwork = collector::pick_song_artwork(
+				&song_path,
+				tags.has_artwork,
+				tags.artwork_size,
+				folder_artwork,
+				directory_artwork.as_ref(),
+				self.artwork_priority,
+			);
+
+			let effective_album_artist = self.tag_fallbacks.effective_album_artist(&tags);
+			let mut song = collector::make_song(
+				song_path.clone(),
+				parent,
+				tags,
+				effective_album_artist,
+				artwork,
+				self.genre_normalizer.as_ref(),
+			);
+			// Loudness is only measured by full scans, which see every song of an album
+			song.track_loudness = track_loudness;
+			song.album_loudness = album_loudness;
+			diesel::update(songs::table.filter(songs::path.eq(&song_path)))
+				.set(&song)
+				.execute(&mut connection)?;
+			num_refreshed += 1;
+		}
+
+		Ok(num_refreshed)
+	}
+}
@@ -1,20 +1,42 @@
 use crossbeam_channel::{self, Receiver, Sender};
-use log::{error, info};
+use log::{error, info, warn};
 use std::cmp::min;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
 use std::time::Duration;
 
+use crate::app::index::loudness;
 use crate::app::index::metadata::{self, SongTags};
+use crate::app::index::{
+	FailedFiles, RemoteMetadata, ScanError, ScanEvent, ScanEvents, UpdateControl,
+};
+use crate::app::settings::{MetadataReader, SymlinkedDuplicates, TagContainer};
+use crate::app::vfs::{self, storage::RemoteLocation};
+
+/// Files created by operating systems and file managers, which are never worth scanning.
+const JUNK_FILE_NAMES: [&str; 3] = [".DS_Store", "Thumbs.db", "desktop.ini"];
+
+/// Prefix of AppleDouble files, which macOS creates next to real files on foreign file systems.
+const APPLE_DOUBLE_PREFIX: &str = "._";
+
+/// Metadata of audio files reached through symbolic links, by real location, so that files linked
+/// several times are read once. Each entry is filled by the first worker reading the file, while
+/// others wait for it.
+type SharedMetadata = Arc<Mutex<HashMap<PathBuf, Arc<OnceLock<Option<SongTags>>>>>>;
 
 #[derive(Debug)]
 pub struct Song {
 	pub path: PathBuf,
 	pub metadata: SongTags,
+	/// Measured loudness of the song, in hundredths of LUFS.
+	pub track_loudness: Option<i32>,
+	/// Measured loudness of all songs in the directory, in hundredths of LUFS.
+	pub album_loudness: Option<i32>,
 }
 
 #[derive(Debug)]
@@ -28,17 +50,162 @@ pub struct Directory {
 
 pub struct Traverser {
 	directory_sender: Sender<Directory>,
+	update_control: UpdateControl,
+	scan_errors: Arc<Mutex<Vec<ScanError>>>,
+	scan_events: ScanEvents,
+	max_depth: usize,
+	min_file_size: u64,
+	skip_hidden_files: bool,
+	tag_priority: Vec<TagContainer>,
+	metadata_readers: HashMap<String, MetadataReader>,
+	estimate_missing_durations: bool,
+	failed_files: FailedFiles,
+	failure_limit: u32,
+	remote_metadata: RemoteMetadata,
+	analyze_loudness: bool,
+	symlinked_duplicates: SymlinkedDuplicates,
+	collection_roots: Vec<PathBuf>,
+	vfs: vfs::VFS,
+}
+
+/// File or directory found while listing the content of a directory.
+#[derive(Debug)]
+struct DirectoryEntry {
+	path: PathBuf,
+	is_dir: bool,
+	/// Size of the file, when it is known without looking it up.
+	size: Option<u64>,
+	/// ETag of files listed by a storage backend.
+	etag: Option<String>,
 }
 
 #[derive(Debug)]
 struct WorkItem {
 	parent: Option<PathBuf>,
 	path: PathBuf,
+	/// Real location of the directory when no symbolic link is followed below the root.
+	unaliased_path: PathBuf,
+	/// Whether a symbolic link was followed to reach the directory.
+	through_link: bool,
+	depth: usize,
 }
 
 impl Traverser {
-	pub fn new(directory_sender: Sender<Directory>) -> Self {
-		Self { directory_sender }
+	pub fn new(
+		directory_sender: Sender<Directory>,
+		update_control: UpdateControl,
+		scan_errors: Arc<Mutex<Vec<ScanError>>>,
+	) -> Self {
+		Self {
+			directory_sender,
+			update_control,
+			scan_errors,
+			scan_events: ScanEvents::default(),
+			max_depth: usize::MAX,
+			min_file_size: 0,
+			skip_hidden_files: true,
+			tag_priority: vec![TagContainer::Id3],
+			metadata_readers: HashMap::new(),
+			estimate_missing_durations: false,
+			failed_files: FailedFiles::default(),
+			failure_limit: 0,
+			remote_metadata: RemoteMetadata::default(),
+			analyze_loudness: false,
+			symlinked_duplicates: SymlinkedDuplicates::Alias,
+			collection_roots: Vec::new(),
+			vfs: vfs::VFS::new(Vec::new()),
+		}
+	}
+
+	/// Limits how many directories deep below each root the traversal goes.
+	/// Directories beyond this depth are skipped and reported.
+	pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+		self.max_depth = max_depth;
+		self
+	}
+
+	/// Skips audio files smaller than `min_file_size` bytes, without reading their metadata.
+	/// Skipped files are reported.
+	pub fn with_min_file_size(mut self, min_file_size: u64) -> Self {
+		self.min_file_size = min_file_size;
+		self
+	}
+
+	/// Controls whether files and directories whose name starts with a dot are skipped.
+	/// Well-known junk files such as `.DS_Store` are skipped regardless.
+	pub fn with_skip_hidden_files(mut self, skip_hidden_files: bool) -> Self {
+		self.skip_hidden_files = skip_hidden_files;
+		self
+	}
+
+	/// Sets the order in which tag containers are read, for files holding several of them.
+	pub fn with_tag_priority(mut self, tag_priority: Vec<TagContainer>) -> Self {
+		self.tag_priority = tag_priority;
+		self
+	}
+
+	/// Overrides the reader used for files with the given extensions. Files with other extensions
+	/// are read using the default reader of their audio format.
+	pub fn with_metadata_readers(
+		mut self,
+		metadata_readers: HashMap<String, MetadataReader>,
+	) -> Self {
+		self.metadata_readers = metadata_readers;
+		self
+	}
+
+	/// Controls whether durations missing from tags are computed from the audio headers of files.
+	pub fn with_duration_estimates(mut self, estimate_missing_durations: bool) -> Self {
+		self.estimate_missing_durations = estimate_missing_durations;
+		self
+	}
+
+	/// Keeps track of audio files whose metadata could not be read. Files failing to be read during
+	/// `failure_limit` scans in a row are skipped and reported, until they are modified.
+	pub fn with_failed_files(mut self, failed_files: FailedFiles, failure_limit: u32) -> Self {
+		self.failed_files = failed_files;
+		self.failure_limit = failure_limit;
+		self
+	}
+
+	/// Remembers metadata read from remote files, so that they are only downloaded again once they change.
+	pub fn with_remote_metadata(mut self, remote_metadata: RemoteMetadata) -> Self {
+		self.remote_metadata = remote_metadata;
+		self
+	}
+
+	/// Controls whether songs are decoded to measure their loudness, and that of their directory.
+	pub fn with_loudness_analysis(mut self, analyze_loudness: bool) -> Self {
+		self.analyze_loudness = analyze_loudness;
+		self
+	}
+
+	/// Controls how audio files reached through symbolic links are indexed, when their real location
+	/// lies within one of the `collection_roots` and is indexed on its own.
+	pub fn with_symlinked_duplicates(
+		mut self,
+		symlinked_duplicates: SymlinkedDuplicates,
+		collection_roots: Vec<PathBuf>,
+	) -> Self {
+		self.symlinked_duplicates = symlinked_duplicates;
+		self.collection_roots = collection_roots
+			.iter()
+			.filter_map(|r| fs::canonicalize(r).ok())
+			.collect();
+		self
+	}
+
+	/// Reports progress of the traversal to subscribers of these events.
+	pub fn with_scan_events(mut self, scan_events: ScanEvents) -> Self {
+		self.scan_events = scan_events;
+		self
+	}
+
+	/// Lists directories and reads files of remote mounts through the storage backends of this VFS.
+	/// Other roots are read from the local file system.
+	pub fn with_vfs(mut self, vfs: vfs::VFS) -> Self {
+		self.vfs = vfs;
+		self
 	}
 
 	pub fn traverse(&self, roots: Vec<PathBuf>) {
@@ -52,18 +219,53 @@ impl Traverser {
 			.unwrap_or_else(|| min(num_cpus::get(), 4));
 		info!("Browsing collection using {} threads", num_threads);
 
+		let shared_metadata = SharedMetadata::default();
 		let mut threads = Vec::new();
 		for _ in 0..num_threads {
 			let work_item_sender = work_item_sender.clone();
 			let work_item_receiver = work_item_receiver.clone();
 			let directory_sender = self.directory_sender.clone();
 			let num_pending_work_items = num_pending_work_items.clone();
+			let update_control = self.update_control.clone();
+			let scan_errors = self.scan_errors.clone();
+			let scan_events = self.scan_events.clone();
+			let max_depth = self.max_depth;
+			let min_file_size = self.min_file_size;
+			let skip_hidden_files = self.skip_hidden_files;
+			let tag_priority = self.tag_priority.clone();
+			let metadata_readers = self.metadata_readers.clone();
+			let estimate_missing_durations = self.estimate_missing_durations;
+			let failed_files = self.failed_files.clone();
+			let failure_limit = self.failure_limit;
+			let remote_metadata = self.remote_metadata.clone();
+			let analyze_loudness = self.analyze_loudness;
+			let symlinked_duplicates = self.symlinked_duplicates;
+			let collection_roots = self.collection_roots.clone();
+			let vfs = self.vfs.clone();
+			let shared_metadata = shared_metadata.clone();
 			threads.push(thread::spawn(move || {
 				let worker = Worker {
 					work_item_sender,
 					work_item_receiver,
 					directory_sender,
 					num_pending_work_items,
+					update_control,
+					scan_errors,
+					scan_events,
+					max_depth,
+					min_file_size,
+					skip_hidden_files,
+					tag_priority,
+					metadata_readers,
+					estimate_missing_durations,
+					failed_files,
+					failure_limit,
+					remote_metadata,
+					analyze_loudness,
+					symlinked_duplicates,
+					collection_roots,
+					vfs,
+					shared_metadata,
 				};
 				worker.run();
 			}));
@@ -72,7 +274,10 @@ impl Traverser {
 		for root in roots {
 			let work_item = WorkItem {
 				parent: None,
+				unaliased_path: fs::canonicalize(&root).unwrap_or_else(|_| root.clone()),
 				path: root,
+				through_link: false,
+				depth: 0,
 			};
 			if let Err(e) = work_item_sender.send(work_item) {
 				error!("Error initializing traverser: {:#?}", e);
@@ -92,11 +297,31 @@ struct Worker {
 	work_item_receiver: Receiver<WorkItem>,
 	directory_sender: Sender<Directory>,
 	num_pending_work_items: Arc<AtomicUsize>,
+	update_control: UpdateControl,
+	scan_errors: Arc<Mutex<Vec<ScanError>>>,
+	scan_events: ScanEvents,
+	max_depth: usize,
+	min_file_size: u64,
+	skip_hidden_files: bool,
+	tag_priority: Vec<TagContainer>,
+	metadata_readers: HashMap<String, MetadataReader>,
+	estimate_missing_durations: bool,
+	failed_files: FailedFiles,
+	failure_limit: u32,
+	remote_metadata: RemoteMetadata,
+	analyze_loudness: bool,
+	symlinked_duplicates: SymlinkedDuplicates,
+	collection_roots: Vec<PathBuf>,
+	vfs: vfs::VFS,
+	shared_metadata: SharedMetadata,
 }
 
 impl Worker {
 	fn run(&self) {
 		while let Some(work_item) = self.find_work_item() {
+			if !self.update_control.wait_while_paused() {
+				return;
+			}
 			self.process_work_item(work_item);
 			self.on_item_processed();
 		}
@@ -104,7 +329,7 @@ impl Worker {
 
 	fn find_work_item(&self) -> Option<WorkItem> {
 		loop {
-			if self.is_all_work_done() {
+			if self.is_all_work_done() || self.update_control.is_cancelled() {
 				return None;
 			}
 			if let Ok(w) = self
@@ -134,44 +359,51 @@ impl Worker {
 	}
 
 	pub fn process_work_item(&self, work_item: WorkItem) {
-		let read_dir = match fs::read_dir(&work_item.path) {
-			Ok(read_dir) => read_dir,
-			Err(e) => {
-				error!(
-					"Directory read error for `{}`: {}",
-					work_item.path.display(),
-					e
-				);
-				return;
-			}
+		self.scan_events.publish(ScanEvent::DirectoryEntered {
+			path: work_item.path.clone(),
+		});
+
+		let remote = self.vfs.locate_remote(&work_item.path);
+		let entries = match &remote {
+			Some(remote) => self.list_remote_directory(&work_item.path, remote),
+			None => self.list_directory(&work_item.path),
+		};
+		let entries = match entries {
+			Some(entries) => entries,
+			None => return,
 		};
 
 		let mut sub_directories = Vec::new();
 		let mut songs = Vec::new();
 		let mut other_files = Vec::new();
 
-		for entry in read_dir {
-			let path = match entry {
-				Ok(ref f) => f.path(),
-				Err(e) => {
-					error!(
-						"File read error within `{}`: {}",
-						work_item.path.display(),
-						e
-					);
-					break;
-				}
-			};
-
-			if path.is_dir() {
-				sub_directories.push(path);
-			} else if let Some(metadata) = metadata::read(&path) {
-				songs.push(Song { path, metadata });
+		for entry in self.resolve_collisions(entries) {
+			if entry.is_dir {
+				sub_directories.push(entry.path);
+			} else if self.is_too_small(&entry.path, entry.size) {
+				continue;
+			} else if self.is_given_up(&entry.path) {
+				other_files.push(entry.path);
+			} else if let Some(metadata) = self.read_metadata(&entry, &work_item) {
+				songs.push(Song {
+					path: entry.path,
+					metadata,
+					track_loudness: None,
+					album_loudness: None,
+				});
 			} else {
-				other_files.push(path);
+				if self.is_audio_file(&entry.path) {
+					self.on_read_failure(&entry.path);
+				}
+				other_files.push(entry.path);
 			}
 		}
 
+		// Loudness is measured by decoding local files, which songs of remote mounts are not
+		if self.analyze_loudness && remote.is_none() {
+			Self::measure_loudness(&mut songs);
+		}
+
 		let created = Self::get_date_created(&work_item.path).unwrap_or_default();
 
 		self.emit_directory(Directory {
@@ -182,14 +414,340 @@ impl Worker {
 			created,
 		});
 
+		let depth = work_item.depth + 1;
 		for sub_directory in sub_directories.into_iter() {
+			let unaliased_path = match sub_directory.file_name() {
+				Some(name) => work_item.unaliased_path.join(name),
+				None => sub_directory.clone(),
+			};
+			if depth > self.max_depth {
+				warn!(
+					"Skipping `{}`, which is more than {} directories deep",
+					sub_directory.display(),
+					self.max_depth
+				);
+				self.scan_errors
+					.lock()
+					.unwrap()
+					.push(ScanError::DepthLimitReached {
+						path: sub_directory,
+					});
+				continue;
+			}
+			let through_link = work_item.through_link || Self::is_symlink(&sub_directory);
 			self.queue_work(WorkItem {
 				parent: Some(work_item.path.clone()),
 				path: sub_directory,
+				unaliased_path,
+				through_link,
+				depth,
+			});
+		}
+	}
+
+	fn list_directory(&self, path: &Path) -> Option<Vec<DirectoryEntry>> {
+		let read_dir = match fs::read_dir(path) {
+			Ok(read_dir) => read_dir,
+			Err(e) => {
+				error!("Directory read error for `{}`: {}", path.display(), e);
+				return None;
+			}
+		};
+
+		let mut entries = Vec::new();
+		for entry in read_dir {
+			match entry {
+				Ok(ref f) if self.is_ignored(&f.path()) => (),
+				Ok(ref f) => entries.push(DirectoryEntry {
+					is_dir: f.path().is_dir(),
+					path: f.path(),
+					size: None,
+					etag: None,
+				}),
+				Err(e) => {
+					error!("File read error within `{}`: {}", path.display(), e);
+					break;
+				}
+			};
+		}
+		Some(entries)
+	}
+
+	fn list_remote_directory(
+		&self,
+		path: &Path,
+		remote: &RemoteLocation,
+	) -> Option<Vec<DirectoryEntry>> {
+		let remote_entries = match remote.list() {
+			Ok(entries) => entries,
+			Err(e) => {
+				error!("Directory read error for `{}`: {}", path.display(), e);
+				return None;
+			}
+		};
+		let entries = remote_entries
+			.into_iter()
+			.filter_map(|entry| {
+				let path = path.join(entry.path.file_name()?);
+				Some(DirectoryEntry {
+					path,
+					is_dir: entry.is_dir,
+					size: Some(entry.size),
+					etag: entry.etag,
+				})
+			})
+			.filter(|entry| !self.is_ignored(&entry.path))
+			.collect();
+		Some(entries)
+	}
+
+	fn is_ignored(&self, path: &Path) -> bool {
+		let name = match path.file_name().and_then(|n| n.to_str()) {
+			Some(name) => name,
+			None => return false,
+		};
+		if JUNK_FILE_NAMES.iter().any(|j| j.eq_ignore_ascii_case(name))
+			|| name.starts_with(APPLE_DOUBLE_PREFIX)
+		{
+			return true;
+		}
+		self.skip_hidden_files && name.starts_with('.')
+	}
+
+	fn is_audio_file(&self, path: &Path) -> bool {
+		metadata::get_reader(path, &self.metadata_readers).is_some()
+	}
+
+	/// Whether an audio file is too small to be worth reading. The size of the file is looked up
+	/// when it was not found while listing its directory.
+	fn is_too_small(&self, path: &Path, size: Option<u64>) -> bool {
+		if self.min_file_size == 0 || !self.is_audio_file(path) {
+			return false;
+		}
+		let size = match size.map_or_else(|| fs::metadata(path).map(|m| m.len()), Ok) {
+			Ok(size) => size,
+			Err(_) => return false,
+		};
+		if size >= self.min_file_size {
+			return false;
+		}
+		warn!(
+			"Skipping `{}`, which is smaller than {} bytes",
+			path.display(),
+			self.min_file_size
+		);
+		self.scan_events.publish(ScanEvent::FileFailed {
+			path: path.to_owned(),
+			reason: format!("File is smaller than {} bytes", self.min_file_size),
+		});
+		self.scan_errors
+			.lock()
+			.unwrap()
+			.push(ScanError::FileTooSmall {
+				path: path.to_owned(),
+				size,
+			});
+		true
+	}
+
+	/// Real location of an audio file reached through a symbolic link, when that location lies
+	/// within the collection and is indexed on its own.
+	fn find_aliased_location(&self, path: &Path, unaliased_path: &Path) -> Option<PathBuf> {
+		let real_path = fs::canonicalize(path).ok()?;
+		let is_aliased = real_path != unaliased_path
+			&& self
+				.collection_roots
+				.iter()
+				.any(|r| real_path.starts_with(r));
+		is_aliased.then_some(real_path)
+	}
+
+	/// Reads the metadata of an audio file within the directory of `work_item`. Symlinked duplicates
+	/// are either skipped, or read once and shared by every link leading to them.
+	fn read_metadata(&self, entry: &DirectoryEntry, work_item: &WorkItem) -> Option<SongTags> {
+		let path = entry.path.as_path();
+		let read = || {
+			let remote = self.vfs.locate_remote(path);
+			let remote_version = remote.as_ref().and(entry.size.zip(entry.etag.as_deref()));
+			let cached_metadata =
+				remote_version.and_then(|(size, etag)| self.remote_metadata.get(path, size, etag));
+			let metadata = match cached_metadata {
+				Some(metadata) => metadata,
+				None => {
+					let metadata = self.read_file(path, remote.as_ref())?;
+					if let Some((size, etag)) = remote_version {
+						self.remote_metadata.insert(path, size, etag, &metadata);
+					}
+					metadata
+				}
+			};
+			self.failed_files.record_success(path);
+			self.scan_events.publish(ScanEvent::FileIndexed {
+				path: path.to_owned(),
+			});
+			Some(metadata)
+		};
+
+		// Only files reached through a symbolic link can be duplicates
+		let real_path = if work_item.through_link || Self::is_symlink(path) {
+			let unaliased_path = work_item.unaliased_path.join(path.file_name()?);
+			self.find_aliased_location(path, &unaliased_path)
+		} else {
+			None
+		};
+		match (self.symlinked_duplicates, real_path) {
+			(_, None) => read(),
+			(SymlinkedDuplicates::Skip, Some(real_path)) => {
+				info!(
+					"Skipping `{}`, which links to `{}`",
+					path.display(),
+					real_path.display()
+				);
+				None
+			}
+			(SymlinkedDuplicates::Alias, Some(real_path)) => {
+				let entry = self
+					.shared_metadata
+					.lock()
+					.unwrap()
+					.entry(real_path)
+					.or_default()
+					.clone();
+				entry.get_or_init(read).clone()
+			}
+		}
+	}
+
+	/// Reads the metadata of an audio file. Readers only work with local files, so remote files are
+	/// downloaded first.
+	fn read_file(&self, path: &Path, remote: Option<&RemoteLocation>) -> Option<SongTags> {
+		// Artwork and other files no reader handles are not worth downloading
+		if !self.is_audio_file(path) {
+			return None;
+		}
+		let local_copy = match remote.map(|r| r.make_local_copy()) {
+			Some(Ok(local_copy)) => Some(local_copy),
+			Some(Err(e)) => {
+				error!("Could not download `{}`: {}", path.display(), e);
+				return None;
+			}
+			None => None,
+		};
+		let local_path = local_copy.as_ref().map_or(path, |c| c.path());
+		let mut metadata =
+			metadata::read_with_readers(local_path, &self.tag_priority, &self.metadata_readers)?;
+		if self.estimate_missing_durations {
+			metadata::fill_missing_duration(local_path, &mut metadata);
+		}
+		Some(metadata)
+	}
+
+	/// Measures the loudness of each song, and of the directory as a whole for album normalization.
+	fn measure_loudness(songs: &mut [Song]) {
+		let measurements: Vec<Option<loudness::Blocks>> =
+			songs.iter().map(|s| loudness::measure(&s.path)).collect();
+		let mut album_blocks = loudness::Blocks::default();
+		for blocks in measurements.iter().flatten() {
+			album_blocks.extend(blocks);
+		}
+		let to_hundredths = |lufs: f64| (lufs * 100.0).round() as i32;
+		let album_loudness = album_blocks.integrated_loudness().map(to_hundredths);
+		for (song, blocks) in songs.iter_mut().zip(measurements) {
+			song.track_loudness = blocks
+				.and_then(|b| b.integrated_loudness())
+				.map(to_hundredths);
+			song.album_loudness = song.track_loudness.and(album_loudness);
+		}
+	}
+
+	/// Files which could not be read during too many scans in a row are not read again until they
+	/// change. They are reported on every scan.
+	fn is_given_up(&self, path: &Path) -> bool {
+		let failures = match self.failed_files.given_up(path, self.failure_limit) {
+			Some(failures) => failures,
+			None => return false,
+		};
+		self.scan_events.publish(ScanEvent::FileFailed {
+			path: path.to_owned(),
+			reason: format!("Metadata could not be read during {} scans", failures),
+		});
+		self.scan_errors
+			.lock()
+			.unwrap()
+			.push(ScanError::PersistentlyBroken {
+				path: path.to_owned(),
+				failures,
 			});
+		true
+	}
+
+	fn on_read_failure(&self, path: &Path) {
+		let failures = self.failed_files.record_failure(path);
+		self.scan_events.publish(ScanEvent::FileFailed {
+			path: path.to_owned(),
+			reason: "Metadata could not be read".to_owned(),
+		});
+		if self.failure_limit > 0 && failures >= self.failure_limit {
+			warn!(
+				"Giving up on `{}`, whose metadata could not be read during {} scans",
+				path.display(),
+				failures
+			);
+			self.scan_errors
+				.lock()
+				.unwrap()
+				.push(ScanError::PersistentlyBroken {
+					path: path.to_owned(),
+					failures,
+				});
 		}
 	}
 
+	/// Entries whose names only differ by bytes that are not valid UTF-8 would share the same
+	/// virtual path. Only the entry with the smallest real path is kept, and the others are reported.
+	fn resolve_collisions(&self, entries: Vec<DirectoryEntry>) -> Vec<DirectoryEntry> {
+		let mut entries_by_name: BTreeMap<String, Vec<DirectoryEntry>> = BTreeMap::new();
+		for entry in entries {
+			let name = entry
+				.path
+				.file_name()
+				.map(|n| n.to_string_lossy().into_owned())
+				.unwrap_or_default();
+			entries_by_name.entry(name).or_default().push(entry);
+		}
+
+		entries_by_name
+			.into_values()
+			.map(|mut entries| {
+				entries.sort_by(|a, b| a.path.cmp(&b.path));
+				let kept = entries.remove(0);
+				if !entries.is_empty() {
+					for ignored in &entries {
+						error!(
+							"`{}` and `{}` map to the same virtual path, only the former is indexed",
+							kept.path.display(),
+							ignored.path.display()
+						);
+					}
+					self.scan_errors
+						.lock()
+						.unwrap()
+						.push(ScanError::PathCollision {
+							kept: kept.path.clone(),
+							ignored: entries.into_iter().map(|e| e.path).collect(),
+						});
+				}
+				kept
+			})
+			.collect()
+	}
+
+	fn is_symlink(path: &Path) -> bool {
+		fs::symlink_metadata(path)
+			.map(|m| m.file_type().is_symlink())
+			.unwrap_or(false)
+	}
+
 	fn get_date_created(path: &Path) -> Option<i32> {
 		if let Ok(t) = fs::metadata(path).and_then(|m| m.created().or_else(|_| m.modified())) {
 			t.duration_since(std::time::UNIX_EPOCH)
@@ -200,3 +758,30 @@ impl Worker {
 		}
 	}
 }
+
+#[test]
+fn traversal_waits_for_directories_to_be_consumed() {
+	let (sender, receiver) = crossbeam_channel::bounded(1);
+	let traverser_thread = thread::spawn(move || {
+		let traverser = Traverser::new(
+			sender,
+			UpdateControl::new(),
+			Arc::new(Mutex::new(Vec::new())),
+		);
+		traverser.traverse(vec![PathBuf::from("test-data/small-collection")]);
+	});
+
+	// Consume slowly, so that the traverser keeps running into the channel capacity
+	let mut num_directories = 0;
+	loop {
+		thread::sleep(Duration::from_millis(20));
+		assert!(receiver.len() <= 1);
+		match receiver.recv_timeout(Duration::from_secs(10)) {
+			Ok(_) => num_directories += 1,
+			Err(_) => break,
+		}
+	}
+
+	traverser_thread.join().unwrap();
+	assert_eq!(num_directories, 6);
+}
@@ -0,0 +1,245 @@
+use id3::frame::TimestampFormat;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::utils::{get_audio_format, AudioFormat};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("Could not read lyrics from ID3 tag in `{0}`:\n\n{1}")]
+	Id3(PathBuf, id3::Error),
+	#[error("Filesystem error for `{0}`: `{1}`")]
+	Io(PathBuf, std::io::Error),
+	#[error("No lyrics were found for `{0}`")]
+	LyricsNotFound(PathBuf),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Lyrics {
+	/// Whether every line carries a timestamp.
+	pub synced: bool,
+	pub lines: Vec<Line>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Line {
+	pub timestamp: Option<Duration>,
+	pub text: String,
+}
+
+/// Reads lyrics for a song, from a `.lrc` sidecar file or from the song's own tags.
+/// Synchronized lyrics are preferred over plain lyrics, and sidecar files over embedded lyrics.
+pub fn read(path: &Path) -> Result<Lyrics, Error> {
+	let sidecar = match read_sidecar(path)? {
+		Some(lyrics) if lyrics.synced => return Ok(lyrics),
+		sidecar => sidecar,
+	};
+
+	let embedded = match get_audio_format(path) {
+		Some(AudioFormat::AIFF) => read_id3_tag(path, id3::Tag::read_from_aiff_path(path))?,
+		Some(AudioFormat::MP3) => read_id3_tag(path, id3::Tag::read_from_path(path))?,
+		Some(AudioFormat::WAVE) => read_id3_tag(path, id3::Tag::read_from_wav_path(path))?,
+		_ => None,
+	};
+
+	match (sidecar, embedded) {
+		(_, Some(embedded)) if embedded.synced => Ok(embedded),
+		(Some(sidecar), _) => Ok(sidecar),
+		(None, Some(embedded)) => Ok(embedded),
+		(None, None) => Err(Error::LyricsNotFound(path.to_owned())),
+	}
+}
+
+fn read_sidecar(path: &Path) -> Result<Option<Lyrics>, Error> {
+	let sidecar_path = path.with_extension("lrc");
+	match fs::read_to_string(&sidecar_path) {
+		Ok(content) => Ok(Some(parse_lrc(&content))),
+		Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+		Err(e) => Err(Error::Io(sidecar_path, e)),
+	}
+}
+
+fn read_id3_tag(path: &Path, tag: id3::Result<id3::Tag>) -> Result<Option<Lyrics>, Error> {
+	match tag {
+		Ok(tag) => Ok(read_id3(&tag)),
+		Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => Ok(None),
+		Err(e) => Err(Error::Id3(path.to_owned(), e)),
+	}
+}
+
+fn read_id3(tag: &id3::Tag) -> Option<Lyrics> {
+	// Frame-based timestamps (MPEG format) cannot be converted to times without decoding the audio
+	let synced = tag
+		.synchronised_lyrics()
+		.find(|l| l.timestamp_format == TimestampFormat::Ms && !l.content.is_empty());
+	if let Some(synced) = synced {
+		let mut lines: Vec<Line> = synced
+			.content
+			.iter()
+			.map(|(timestamp, text)| Line {
+				timestamp: Some(Duration::from_millis(*timestamp as u64)),
+				text: text.trim().to_owned(),
+			})
+			.collect();
+		lines.sort_by_key(|l| l.timestamp);
+		return Some(Lyrics {
+			synced: true,
+			lines,
+		});
+	}
+
+	tag.lyrics().next().map(|l| Lyrics {
+		synced: false,
+		lines: l
+			.text
+			.lines()
+			.map(|text| Line {
+				timestamp: None,
+				text: text.trim().to_owned(),
+			})
+			.collect(),
+	})
+}
+
+/// Parses the LRC format, where each line of lyrics is prefixed by one or more `[mm:ss.xx]` timestamps.
+/// Files without any timestamps are read as plain lyrics.
+fn parse_lrc(content: &str) -> Lyrics {
+	let mut timed_lines = Vec::new();
+	let mut plain_lines = Vec::new();
+
+	for line in content.lines() {
+		let mut text = line.trim();
+		let mut timestamps = Vec::new();
+		let mut is_id_tag = false;
+		while text.starts_with('[') {
+			let end = match text.find(']') {
+				Some(end) => end,
+				None => break,
+			};
+			match parse_lrc_timestamp(&text[1..end]) {
+				Some(timestamp) => timestamps.push(timestamp),
+				None => {
+					// ID tags such as `[ar:Artist]` hold metadata rather than lyrics
+					is_id_tag = timestamps.is_empty();
+					break;
+				}
+			}
+			text = &text[end + 1..];
+		}
+
+		if is_id_tag {
+			continue;
+		}
+
+		let text = text.trim();
+		if timestamps.is_empty() {
+			plain_lines.push(Line {
+				timestamp: None,
+				text: text.to_owned(),
+			});
+		} else {
+			for timestamp in timestamps {
+				timed_lines.push(Line {
+					timestamp: Some(timestamp),
+					text: text.to_owned(),
+				});
+			}
+		}
+	}
+
+	if timed_lines.is_empty() {
+		return Lyrics {
+			synced: false,
+			lines: plain_lines,
+		};
+	}
+
+	timed_lines.sort_by_key(|l| l.timestamp);
+	Lyrics {
+		synced: true,
+		lines: timed_lines,
+	}
+}
+
+fn parse_lrc_timestamp(timestamp: &str) -> Option<Duration> {
+	let (minutes, seconds) = timestamp.split_once(':')?;
+	let minutes: u64 = minutes.trim().parse().ok()?;
+	let seconds: f64 = seconds.trim().parse().ok()?;
+	if !(0.0..60.0).contains(&seconds) {
+		return None;
+	}
+	Some(Duration::from_millis(
+		minutes * 60_000 + (seconds * 1000.0).round() as u64,
+	))
+}
+
+#[cfg(test)]
+mod test {
+	use id3::frame::{SynchronisedLyrics, SynchronisedLyricsType};
+	use id3::TagLike;
+
+	use super::*;
+
+	fn timed_line(millis: u64, text: &str) -> Line {
+		Line {
+			timestamp: Some(Duration::from_millis(millis)),
+			text: text.to_owned(),
+		}
+	}
+
+	#[test]
+	fn reads_timestamps_from_lrc_sidecar() {
+		let path: PathBuf = ["test-data", "lyrics", "Timed.mp3"].iter().collect();
+		let lyrics = read(&path).unwrap();
+		assert_eq!(
+			lyrics,
+			Lyrics {
+				synced: true,
+				lines: vec![
+					timed_line(1_500, "First line"),
+					timed_line(3_100, "Second line"),
+					timed_line(4_000, "Chorus"),
+					timed_line(62_250, "Chorus"),
+				],
+			}
+		);
+	}
+
+	#[test]
+	fn reads_synchronised_lyrics_from_id3_tag() {
+		let mut tag = id3::Tag::new();
+		tag.add_frame(SynchronisedLyrics {
+			lang: "eng".to_owned(),
+			timestamp_format: TimestampFormat::Ms,
+			content_type: SynchronisedLyricsType::Lyrics,
+			description: String::new(),
+			content: vec![(2_000, "Second".to_owned()), (500, "First".to_owned())],
+		});
+		assert_eq!(
+			read_id3(&tag),
+			Some(Lyrics {
+				synced: true,
+				lines: vec![timed_line(500, "First"), timed_line(2_000, "Second")],
+			})
+		);
+	}
+
+	#[test]
+	fn lrc_without_timestamps_is_plain() {
+		let lyrics = parse_lrc("[ar:Someone]\nFirst line\nSecond line\n");
+		assert!(!lyrics.synced);
+		assert_eq!(lyrics.lines.len(), 2);
+		assert_eq!(lyrics.lines[0].timestamp, None);
+		assert_eq!(lyrics.lines[1].text, "Second line");
+	}
+
+	#[test]
+	fn missing_lyrics_is_an_error() {
+		let path: PathBuf = ["test-data", "untagged-songs", "Untagged.mp3"]
+			.iter()
+			.collect();
+		assert!(matches!(read(&path), Err(Error::LyricsNotFound(_))));
+	}
+}
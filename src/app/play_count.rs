@@ -0,0 +1,635 @@
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::app::{index, vfs};
+use crate::db::{self, directories, play_counts, plays, songs, users, DB};
+use crate::utils::now;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error(transparent)]
+	Database(#[from] diesel::result::Error),
+	#[error(transparent)]
+	DatabaseConnection(#[from] db::Error),
+	#[error("Song was not found: `{0}`")]
+	SongNotFound(String),
+	#[error("User not found")]
+	UserNotFound,
+	#[error(transparent)]
+	Vfs(#[from] vfs::Error),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlayCount {
+	pub count: u32,
+	/// Seconds since the UNIX epoch.
+	pub last_played: Option<i64>,
+}
+
+/// A single play of a song by a user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryEntry {
+	/// Virtual path of the song.
+	pub path: String,
+	/// Seconds since the UNIX epoch.
+	pub played_at: i64,
+	/// Number of times the user played this song, across their whole history.
+	pub play_count: u32,
+}
+
+/// A play listed in the activity feed, which gathers the plays of all users sharing them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActivityEntry {
+	pub username: String,
+	/// Virtual path of the song.
+	pub path: String,
+	/// Seconds since the UNIX epoch.
+	pub played_at: i64,
+}
+
+/// A slice of a user's play history, oldest plays first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HistoryPage {
+	pub entries: Vec<HistoryEntry>,
+	/// Cursor to pass back to read the following page, if there are more plays.
+	pub next: Option<i32>,
+}
+
+/// Period of time over which plays are counted when ranking songs or albums.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Window {
+	#[default]
+	AllTime,
+	LastThirtyDays,
+}
+
+#[derive(Clone)]
+pub struct Manager {
+	db: DB,
+	vfs_manager: vfs::Manager,
+}
+
+impl Manager {
+	pub fn new(db: DB, vfs_manager: vfs::Manager) -> Self {
+		Self { db, vfs_manager }
+	}
+
+	/// Records that a user played a song, and returns the updated play count.
+	pub fn record_play(&self, username: &str, virtual_path: &str) -> Result<PlayCount, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let real_path = vfs.virtual_to_real(Path::new(virtual_path))?;
+		let real_path = real_path.to_string_lossy().into_owned();
+		let now = now();
+
+		let mut connection = self.db.connect()?;
+		let user_id = get_user_id(&mut connection, username)?;
+
+		let is_indexed: bool = diesel::select(diesel::dsl::exists(
+			songs::table.filter(songs::path.eq(&real_path)),
+		))
+		.get_result(&mut connection)?;
+		if !is_indexed {
+			return Err(Error::SongNotFound(virtual_path.to_owned()));
+		}
+
+		connection.transaction::<_, Error, _>(|connection| {
+			let previous_count: Option<i32> = play_counts::table
+				.select(play_counts::count)
+				.filter(play_counts::owner.eq(user_id))
+				.filter(play_counts::path.eq(&real_path))
+				.get_result(&mut *connection)
+				.optional()?;
+			let count = previous_count.unwrap_or(0) + 1;
+			diesel::insert_into(play_counts::table)
+				.values((
+					play_counts::owner.eq(user_id),
+					play_counts::path.eq(&real_path),
+					play_counts::count.eq(count),
+					play_counts::last_played.eq(now),
+				))
+				.execute(&mut *connection)?;
+			diesel::insert_into(plays::table)
+				.values((
+					plays::owner.eq(user_id),
+					plays::path.eq(&real_path),
+					plays::played_at.eq(now),
+				))
+				.execute(&mut *connection)?;
+			Ok(PlayCount {
+				count: count as u32,
+				last_played: Some(now),
+			})
+		})
+	}
+
+	/// Returns play counts of a user for the given songs, keyed by virtual path.
+	/// Songs which were never played are omitted.
+	pub fn read_play_counts<'a, I>(
+		&self,
+		username: &str,
+		virtual_paths: I,
+	) -> Result<HashMap<String, PlayCount>, Error>
+	where
+		I: IntoIterator<Item = &'a str>,
+	{
+		let vfs = self.vfs_manager.get_vfs()?;
+		let real_to_virtual: HashMap<String, String> = virtual_paths
+			.into_iter()
+			.filter_map(|virtual_path| {
+				let real_path = vfs.virtual_to_real(Path::new(virtual_path)).ok()?;
+				Some((
+					real_path.to_string_lossy().into_owned(),
+					virtual_path.to_owned(),
+				))
+			})
+			.collect();
+
+		let mut connection = self.db.connect()?;
+		let user_id = get_user_id(&mut connection, username)?;
+
+		let mut play_counts = HashMap::new();
+		let real_paths: Vec<&String> = real_to_virtual.keys().collect();
+		for chunk in real_paths.chunks(1000) {
+			let rows: Vec<(String, i32, i64)> = play_counts::table
+				.select((
+					play_counts::path,
+					play_counts::count,
+					play_counts::last_played,
+				))
+				.filter(play_counts::owner.eq(user_id))
+				.filter(play_counts::path.eq_any(chunk))
+				.load(&mut connection)?;
+			for (real_path, count, last_played) in rows {
+				if let Some(virtual_path) = real_to_virtual.get(&real_path) {
+					play_counts.insert(
+						virtual_path.clone(),
+						PlayCount {
+							count: count as u32,
+							last_played: Some(last_played),
+						},
+					);
+				}
+			}
+		}
+
+		Ok(play_counts)
+	}
+
+	/// Returns up to `count` plays of a user, oldest first, starting after the given cursor.
+	/// Plays of songs which are no longer within a mount are omitted.
+	pub fn read_history(
+		&self,
+		username: &str,
+		after: Option<i32>,
+		count: usize,
+	) -> Result<HistoryPage, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let user_id = get_user_id(&mut connection, username)?;
+
+		let rows: Vec<(i32, String, i64)> = plays::table
+			.select((plays::id, plays::path, plays::played_at))
+			.filter(plays::owner.eq(user_id))
+			.filter(plays::id.gt(after.unwrap_or(0)))
+			.order(plays::id.asc())
+			.limit(count as i64)
+			.load(&mut connection)?;
+
+		let mut paths: Vec<&String> = rows.iter().map(|(_, path, _)| path).collect();
+		paths.sort();
+		paths.dedup();
+		let counts: HashMap<String, i32> = play_counts::table
+			.select((play_counts::path, play_counts::count))
+			.filter(play_counts::owner.eq(user_id))
+			.filter(play_counts::path.eq_any(paths))
+			.load::<(String, i32)>(&mut connection)?
+			.into_iter()
+			.collect();
+
+		let next = match rows.last() {
+			Some((id, _, _)) if rows.len() == count => Some(*id),
+			_ => None,
+		};
+		let entries = rows
+			.into_iter()
+			.filter_map(|(_, real_path, played_at)| {
+				let virtual_path = vfs.real_to_virtual(Path::new(&real_path)).ok()?;
+				Some(HistoryEntry {
+					path: virtual_path.to_string_lossy().into_owned(),
+					played_at,
+					play_count: counts.get(&real_path).copied().unwrap_or(1) as u32,
+				})
+			})
+			.collect();
+
+		Ok(HistoryPage { entries, next })
+	}
+
+	/// Returns the most recent plays of users who opted into sharing their activity, most recent first.
+	pub fn read_activity(&self, count: usize) -> Result<Vec<ActivityEntry>, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+
+		let rows: Vec<(String, String, i64)> = plays::table
+			.inner_join(users::table)
+			.select((users::name, plays::path, plays::played_at))
+			.filter(users::share_activity.eq(true))
+			.order((plays::played_at.desc(), plays::id.desc()))
+			.limit(count as i64)
+			.load(&mut connection)?;
+
+		Ok(rows
+			.into_iter()
+			.filter_map(|(username, real_path, played_at)| {
+				let virtual_path = vfs.real_to_virtual(Path::new(&real_path)).ok()?;
+				Some(ActivityEntry {
+					username,
+					path: virtual_path.to_string_lossy().into_owned(),
+					played_at,
+				})
+			})
+			.collect())
+	}
+
+	/// Returns the songs a user played the most, along with their play counts within the time window.
+	/// Ties are broken by most recent play.
+	pub fn get_top_songs(
+		&self,
+		username: &str,
+		window: Window,
+		count: usize,
+	) -> Result<Vec<(index::Song, PlayCount)>, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let user_id = get_user_id(&mut connection, username)?;
+
+		let ranking = read_ranking(&mut connection, user_id, window)?;
+		let real_paths: Vec<&String> = ranking.iter().map(|(path, _)| path).collect();
+		let mut songs: HashMap<String, index::Song> = HashMap::new();
+		for chunk in real_paths.chunks(1000) {
+			let chunk_songs: Vec<index::Song> = songs::table
+				.filter(songs::path.eq_any(chunk))
+				.load(&mut connection)?;
+			songs.extend(chunk_songs.into_iter().map(|s| (s.path.clone(), s)));
+		}
+
+		Ok(ranking
+			.into_iter()
+			.filter_map(|(path, play_count)| {
+				let song = songs.remove(&path)?.virtualize(&vfs)?;
+				Some((song, play_count))
+			})
+			.take(count)
+			.collect())
+	}
+
+	/// Returns the albums a user played the most, ranked by the sum of play counts of their songs
+	/// within the time window. Ties are broken by most recent play.
+	pub fn get_top_albums(
+		&self,
+		username: &str,
+		window: Window,
+		count: usize,
+	) -> Result<Vec<index::Directory>, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let user_id = get_user_id(&mut connection, username)?;
+
+		let ranking = read_ranking(&mut connection, user_id, window)?;
+		let play_counts: HashMap<&String, &PlayCount> =
+			ranking.iter().map(|(path, p)| (path, p)).collect();
+		let real_paths: Vec<&String> = play_counts.keys().copied().collect();
+
+		let mut album_play_counts: HashMap<String, PlayCount> = HashMap::new();
+		for chunk in real_paths.chunks(1000) {
+			let parents: Vec<(String, String)> = songs::table
+				.select((songs::path, songs::parent))
+				.filter(songs::path.eq_any(chunk))
+				.load(&mut connection)?;
+			for (path, parent) in parents {
+				let song_play_count = play_counts[&path];
+				let album_play_count = album_play_counts.entry(parent).or_default();
+				album_play_count.count += song_play_count.count;
+				album_play_count.last_played = album_play_count
+					.last_played
+					.max(song_play_count.last_played);
+			}
+		}
+
+		let mut albums = Vec::new();
+		for (parent, _) in sort_ranking(album_play_counts.into_iter().collect()) {
+			if albums.len() >= count {
+				break;
+			}
+			let directory: Option<index::Directory> = directories::table
+				.filter(directories::path.eq(&parent))
+				.get_result(&mut connection)
+				.optional()?;
+			if let Some(directory) = directory.and_then(|d| d.virtualize(&vfs)) {
+				albums.push(directory);
+			}
+		}
+		Ok(albums)
+	}
+}
+
+/// Returns play counts of a user within a time window keyed by real path, from most to least played.
+fn read_ranking(
+	connection: &mut SqliteConnection,
+	user_id: i32,
+	window: Window,
+) -> Result<Vec<(String, PlayCount)>, Error> {
+	let ranking: Vec<(String, PlayCount)> = match window {
+		Window::AllTime => {
+			let rows: Vec<(String, i32, i64)> = play_counts::table
+				.select((
+					play_counts::path,
+					play_counts::count,
+					play_counts::last_played,
+				))
+				.filter(play_counts::owner.eq(user_id))
+				.load(connection)?;
+			rows.into_iter()
+				.map(|(path, count, last_played)| {
+					let play_count = PlayCount {
+						count: count as u32,
+						last_played: Some(last_played),
+					};
+					(path, play_count)
+				})
+				.collect()
+		}
+		Window::LastThirtyDays => {
+			let since = now() - 30 * SECONDS_PER_DAY;
+			let rows: Vec<(String, i64)> = plays::table
+				.select((plays::path, plays::played_at))
+				.filter(plays::owner.eq(user_id))
+				.filter(plays::played_at.ge(since))
+				.load(connection)?;
+			let mut play_counts: HashMap<String, PlayCount> = HashMap::new();
+			for (path, played_at) in rows {
+				let play_count = play_counts.entry(path).or_default();
+				play_count.count += 1;
+				play_count.last_played = play_count.last_played.max(Some(played_at));
+			}
+			play_counts.into_iter().collect()
+		}
+	};
+	Ok(sort_ranking(ranking))
+}
+
+fn sort_ranking(mut ranking: Vec<(String, PlayCount)>) -> Vec<(String, PlayCount)> {
+	ranking.sort_by(|(path_a, a), (path_b, b)| {
+		b.count
+			.cmp(&a.count)
+			.then(b.last_played.cmp(&a.last_played))
+			.then(path_a.cmp(path_b))
+	});
+	ranking
+}
+
+fn get_user_id(connection: &mut SqliteConnection, username: &str) -> Result<i32, Error> {
+	users::table
+		.select(users::id)
+		.filter(users::name.eq(username))
+		.get_result(connection)
+		.optional()?
+		.ok_or(Error::UserNotFound)
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use super::Window;
+	use crate::app::{test, user};
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+	const TEST_PASSWORD: &str = "password";
+	const OTHER_USER: &str = "other_user";
+	const TEST_MOUNT_NAME: &str = "root";
+
+	#[test]
+	fn record_play_increments_count_of_user_only() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.user(OTHER_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+		ctx.index.update().unwrap();
+
+		let song_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+			.iter()
+			.collect();
+		let song_path = song_path.to_str().unwrap();
+
+		ctx.play_count_manager
+			.record_play(TEST_USER, song_path)
+			.unwrap();
+		let play_count = ctx
+			.play_count_manager
+			.record_play(TEST_USER, song_path)
+			.unwrap();
+		assert_eq!(play_count.count, 2);
+
+		let play_counts = ctx
+			.play_count_manager
+			.read_play_counts(TEST_USER, [song_path])
+			.unwrap();
+		assert_eq!(play_counts.get(song_path).map(|p| p.count), Some(2));
+		assert!(play_counts[song_path].last_played.is_some());
+
+		let play_counts = ctx
+			.play_count_manager
+			.read_play_counts(OTHER_USER, [song_path])
+			.unwrap();
+		assert!(play_counts.is_empty());
+	}
+
+	#[test]
+	fn cannot_record_play_of_unknown_song() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+		ctx.index.update().unwrap();
+
+		let song_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Not A Song.mp3"]
+			.iter()
+			.collect();
+		assert!(matches!(
+			ctx.play_count_manager
+				.record_play(TEST_USER, song_path.to_str().unwrap()),
+			Err(super::Error::SongNotFound(_))
+		));
+	}
+
+	#[test]
+	fn history_lists_plays_of_user_only() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.user(OTHER_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+		ctx.index.update().unwrap();
+
+		let hunted: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+		let candlelight = hunted.join("02 - Candlelight.mp3");
+		let three_gates = hunted.join("03 - Three Gates.mp3");
+		let candlelight = candlelight.to_str().unwrap();
+		let three_gates = three_gates.to_str().unwrap();
+
+		for path in [candlelight, three_gates, candlelight] {
+			ctx.play_count_manager.record_play(TEST_USER, path).unwrap();
+		}
+		ctx.play_count_manager
+			.record_play(OTHER_USER, three_gates)
+			.unwrap();
+
+		let first_page = ctx
+			.play_count_manager
+			.read_history(TEST_USER, None, 2)
+			.unwrap();
+		assert!(first_page.next.is_some());
+		let last_page = ctx
+			.play_count_manager
+			.read_history(TEST_USER, first_page.next, 2)
+			.unwrap();
+		assert_eq!(last_page.next, None);
+
+		let history: Vec<(String, u32)> = first_page
+			.entries
+			.into_iter()
+			.chain(last_page.entries)
+			.map(|e| (e.path, e.play_count))
+			.collect();
+		assert_eq!(
+			history,
+			vec![
+				(candlelight.to_owned(), 2),
+				(three_gates.to_owned(), 1),
+				(candlelight.to_owned(), 2),
+			]
+		);
+
+		let other_history = ctx
+			.play_count_manager
+			.read_history(OTHER_USER, None, 10)
+			.unwrap();
+		assert_eq!(other_history.entries.len(), 1);
+		assert_eq!(other_history.entries[0].path, three_gates);
+	}
+
+	#[test]
+	fn activity_lists_plays_of_sharing_users_only() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.user(OTHER_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+		ctx.index.update().unwrap();
+		ctx.user_manager
+			.write_preferences(
+				TEST_USER,
+				&user::Preferences {
+					share_activity: true,
+					..Default::default()
+				},
+			)
+			.unwrap();
+
+		let hunted: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+		let candlelight = hunted.join("02 - Candlelight.mp3");
+		let three_gates = hunted.join("03 - Three Gates.mp3");
+		let candlelight = candlelight.to_str().unwrap();
+		let three_gates = three_gates.to_str().unwrap();
+
+		ctx.play_count_manager
+			.record_play(TEST_USER, candlelight)
+			.unwrap();
+		ctx.play_count_manager
+			.record_play(OTHER_USER, three_gates)
+			.unwrap();
+		ctx.play_count_manager
+			.record_play(TEST_USER, three_gates)
+			.unwrap();
+
+		let activity: Vec<(String, String)> = ctx
+			.play_count_manager
+			.read_activity(10)
+			.unwrap()
+			.into_iter()
+			.map(|e| (e.username, e.path))
+			.collect();
+		assert_eq!(
+			activity,
+			vec![
+				(TEST_USER.to_owned(), three_gates.to_owned()),
+				(TEST_USER.to_owned(), candlelight.to_owned()),
+			]
+		);
+	}
+
+	#[test]
+	fn top_songs_are_ranked_by_play_count() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.user(OTHER_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+		ctx.index.update().unwrap();
+
+		let hunted: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+		let picnic: PathBuf = [TEST_MOUNT_NAME, "Tobokegao", "Picnic"].iter().collect();
+		let candlelight = hunted.join("02 - Candlelight.mp3");
+		let three_gates = hunted.join("03 - Three Gates.mp3");
+		let why = picnic.join("07 - なぜ (Why).mp3");
+
+		for (path, plays) in [(&candlelight, 1), (&three_gates, 3), (&why, 2)] {
+			for _ in 0..plays {
+				ctx.play_count_manager
+					.record_play(TEST_USER, path.to_str().unwrap())
+					.unwrap();
+			}
+		}
+		for _ in 0..5 {
+			ctx.play_count_manager
+				.record_play(OTHER_USER, candlelight.to_str().unwrap())
+				.unwrap();
+		}
+
+		for window in [Window::AllTime, Window::LastThirtyDays] {
+			let top_songs = ctx
+				.play_count_manager
+				.get_top_songs(TEST_USER, window, 10)
+				.unwrap();
+			let ranking: Vec<(PathBuf, u32)> = top_songs
+				.into_iter()
+				.map(|(song, play_count)| (PathBuf::from(song.path), play_count.count))
+				.collect();
+			assert_eq!(
+				ranking,
+				vec![
+					(three_gates.clone(), 3),
+					(why.clone(), 2),
+					(candlelight.clone(), 1)
+				]
+			);
+
+			let top_albums = ctx
+				.play_count_manager
+				.get_top_albums(TEST_USER, window, 1)
+				.unwrap();
+			let top_albums: Vec<PathBuf> = top_albums
+				.into_iter()
+				.map(|d| PathBuf::from(d.path))
+				.collect();
+			assert_eq!(top_albums, vec![hunted.clone()]);
+		}
+	}
+}
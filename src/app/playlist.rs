@@ -1,12 +1,20 @@
 use core::clone::Clone;
 use diesel::prelude::*;
 use diesel::sql_types;
+use diesel::sqlite::SqliteConnection;
 use diesel::BelongingToDsl;
+use log::warn;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::app::index::Song;
 use crate::app::vfs;
-use crate::db::{self, playlist_songs, playlists, users, DB};
+use crate::db::{self, playlist_songs, playlists, songs, users, DB};
+use crate::utils::now;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -18,22 +26,65 @@ pub enum Error {
 	UserNotFound,
 	#[error("Playlist not found")]
 	PlaylistNotFound,
+	#[error("A playlist named `{0}` already exists")]
+	PlaylistNameConflict(String),
+	#[error("Playlist contains tracks which are not in the collection")]
+	InvalidTracks(Vec<String>),
+	#[error("Playlist has no track at index {0}")]
+	TrackIndexOutOfRange(usize),
+	#[error("Playlist save session not found")]
+	SaveSessionNotFound,
 	#[error(transparent)]
 	Vfs(#[from] vfs::Error),
 }
 
+/// Order in which playlists are listed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListOrder {
+	/// Alphabetical order.
+	#[default]
+	Name,
+	/// Most recently created first.
+	Created,
+	/// Most recently saved first.
+	Updated,
+}
+
+/// Chunked saves which receive no tracks for this long are abandoned.
+const SAVE_SESSION_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+const SAVE_SESSION_ID_LENGTH: usize = 32;
+
+/// Disc and track number of an indexed song.
+type TrackPosition = (Option<i32>, Option<i32>);
+
+/// Playlist being uploaded over several requests, before it is committed.
+struct SaveSession {
+	playlist_name: String,
+	owner: String,
+	tracks: Vec<String>,
+	strict: bool,
+	sort_by_disc_and_track: bool,
+	last_activity: Instant,
+}
+
 #[derive(Clone)]
 pub struct Manager {
 	db: DB,
 	vfs_manager: vfs::Manager,
+	save_sessions: Arc<Mutex<HashMap<String, SaveSession>>>,
 }
 
 impl Manager {
 	pub fn new(db: DB, vfs_manager: vfs::Manager) -> Self {
-		Self { db, vfs_manager }
+		Self {
+			db,
+			vfs_manager,
+			save_sessions: Arc::default(),
+		}
 	}
 
-	pub fn list_playlists(&self, owner: &str) -> Result<Vec<String>, Error> {
+	pub fn list_playlists(&self, owner: &str, order: ListOrder) -> Result<Vec<String>, Error> {
 		let mut connection = self.db.connect()?;
 
 		let user: User = {
@@ -48,23 +99,71 @@ impl Manager {
 
 		{
 			use self::playlists::dsl::*;
-			let found_playlists: Vec<String> = Playlist::belonging_to(&user)
-				.select(name)
-				.load(&mut connection)?;
+			let query = Playlist::belonging_to(&user).select(name);
+			// Saving a playlist replaces its row, so ids order playlists by last save
+			let found_playlists: Vec<String> = match order {
+				ListOrder::Name => query.order((name, id)).load(&mut connection)?,
+				ListOrder::Created => query
+					.order((created_at.desc(), id.desc()))
+					.load(&mut connection)?,
+				ListOrder::Updated => query
+					.order((updated_at.desc(), id.desc()))
+					.load(&mut connection)?,
+			};
 			Ok(found_playlists)
 		}
 	}
 
+	/// Saves a playlist and returns the tracks which could not be found in the collection.
+	/// The name is normalized, and must not differ only by case from another playlist of the owner.
+	/// In strict mode, the playlist is not saved if any such track is present. Otherwise, tracks
+	/// which are not indexed yet are kept (they may be added by a pending reindex).
+	/// When `sort_by_disc_and_track` is set, tracks are saved in disc and track number order
+	/// instead of the order of `content`.
 	pub fn save_playlist(
 		&self,
 		playlist_name: &str,
 		owner: &str,
 		content: &[String],
-	) -> Result<(), Error> {
+		strict: bool,
+		sort_by_disc_and_track: bool,
+	) -> Result<Vec<String>, Error> {
+		let playlist_name = &normalize_name(playlist_name);
 		let new_playlist: NewPlaylist;
 		let playlist: Playlist;
 		let vfs = self.vfs_manager.get_vfs()?;
 
+		let mut real_paths: Vec<Option<String>> = content
+			.iter()
+			.map(|path| {
+				vfs.virtual_to_real(Path::new(&path))
+					.ok()
+					.and_then(|p| p.to_str().map(|s| s.to_owned()))
+			})
+			.collect();
+
+		let indexed_paths = self.find_indexed_paths(real_paths.iter().flatten())?;
+		let invalid_tracks: Vec<String> = content
+			.iter()
+			.zip(&real_paths)
+			.filter(|(_, real_path)| match real_path {
+				Some(p) => !indexed_paths.contains(p),
+				None => true,
+			})
+			.map(|(path, _)| path.clone())
+			.collect();
+
+		if !invalid_tracks.is_empty() {
+			if strict {
+				return Err(Error::InvalidTracks(invalid_tracks));
+			}
+			warn!(
+				"Playlist `{}` contains {} tracks which are not in the collection",
+				playlist_name,
+				invalid_tracks.len()
+			);
+		}
+
 		{
 			let mut connection = self.db.connect()?;
 
@@ -79,10 +178,36 @@ impl Manager {
 					.ok_or(Error::UserNotFound)?
 			};
 
-			// Create playlist
+			// Names only differing by case would be confusing, replacing either is an error
+			let conflicting_name: Option<String> = {
+				use self::playlists::dsl::*;
+				Playlist::belonging_to(&user)
+					.select(name)
+					.load::<String>(&mut connection)?
+					.into_iter()
+					.find(|n| {
+						n != playlist_name && n.to_lowercase() == playlist_name.to_lowercase()
+					})
+			};
+			if let Some(conflicting_name) = conflicting_name {
+				return Err(Error::PlaylistNameConflict(conflicting_name));
+			}
+
+			// Create playlist, keeping the creation time of the playlist it replaces (if any)
+			let now = now();
+			let previous_created_at: Option<i64> = {
+				use self::playlists::dsl::*;
+				playlists
+					.select(created_at)
+					.filter(name.eq(playlist_name).and(owner.eq(user.id)))
+					.get_result(&mut connection)
+					.optional()?
+			};
 			new_playlist = NewPlaylist {
 				name: playlist_name.into(),
 				owner: user.id,
+				created_at: previous_created_at.unwrap_or(now),
+				updated_at: now,
 			};
 
 			diesel::insert_into(playlists::table)
@@ -98,16 +223,26 @@ impl Manager {
 			}
 		}
 
+		if sort_by_disc_and_track {
+			let positions = self.find_track_positions(real_paths.iter().flatten())?;
+			// Tracks missing from the index go last, tracks without a disc number come first
+			real_paths.sort_by_key(|real_path| {
+				match real_path.as_ref().and_then(|p| positions.get(p)) {
+					Some((disc_number, track_number)) => (
+						false,
+						disc_number.unwrap_or(0),
+						track_number.unwrap_or(i32::MAX),
+					),
+					None => (true, 0, 0),
+				}
+			});
+		}
+
 		let mut new_songs: Vec<NewPlaylistSong> = Vec::new();
 		new_songs.reserve(content.len());
 
-		for (i, path) in content.iter().enumerate() {
-			let virtual_path = Path::new(&path);
-			if let Some(real_path) = vfs
-				.virtual_to_real(virtual_path)
-				.ok()
-				.and_then(|p| p.to_str().map(|s| s.to_owned()))
-			{
+		for (i, real_path) in real_paths.into_iter().enumerate() {
+			if let Some(real_path) = real_path {
 				new_songs.push(NewPlaylistSong {
 					playlist: playlist.id,
 					path: real_path,
@@ -131,12 +266,147 @@ impl Manager {
 			})?;
 		}
 
+		Ok(invalid_tracks)
+	}
+
+	/// Starts saving a playlist in chunks, for playlists too large to upload in a single request.
+	/// Tracks are added with `append_to_save` and the playlist is written by `commit_save`.
+	/// Returns the id of the save session.
+	pub fn begin_save(
+		&self,
+		playlist_name: &str,
+		owner: &str,
+		strict: bool,
+		sort_by_disc_and_track: bool,
+	) -> String {
+		let session_id: String = rand::thread_rng()
+			.sample_iter(&Alphanumeric)
+			.take(SAVE_SESSION_ID_LENGTH)
+			.map(char::from)
+			.collect();
+		let mut sessions = self.save_sessions.lock().unwrap();
+		sessions.retain(|_, s| s.last_activity.elapsed() < SAVE_SESSION_TIMEOUT);
+		sessions.insert(
+			session_id.clone(),
+			SaveSession {
+				playlist_name: playlist_name.to_owned(),
+				owner: owner.to_owned(),
+				tracks: Vec::new(),
+				strict,
+				sort_by_disc_and_track,
+				last_activity: Instant::now(),
+			},
+		);
+		session_id
+	}
+
+	/// Adds tracks at the end of a playlist being saved in chunks.
+	pub fn append_to_save(
+		&self,
+		session_id: &str,
+		owner: &str,
+		tracks: &[String],
+	) -> Result<(), Error> {
+		let mut sessions = self.save_sessions.lock().unwrap();
+		sessions.retain(|_, s| s.last_activity.elapsed() < SAVE_SESSION_TIMEOUT);
+		let session = sessions
+			.get_mut(session_id)
+			.filter(|s| s.owner == owner)
+			.ok_or(Error::SaveSessionNotFound)?;
+		session.tracks.extend_from_slice(tracks);
+		session.last_activity = Instant::now();
 		Ok(())
 	}
 
+	/// Writes a playlist saved in chunks, replacing any playlist with the same name.
+	/// Returns the tracks which are not in the collection, like `save_playlist`.
+	pub fn commit_save(&self, session_id: &str, owner: &str) -> Result<Vec<String>, Error> {
+		let session = {
+			let mut sessions = self.save_sessions.lock().unwrap();
+			sessions.retain(|_, s| s.last_activity.elapsed() < SAVE_SESSION_TIMEOUT);
+			match sessions.get(session_id) {
+				Some(s) if s.owner == owner => sessions.remove(session_id),
+				_ => None,
+			}
+			.ok_or(Error::SaveSessionNotFound)?
+		};
+		self.save_playlist(
+			&session.playlist_name,
+			owner,
+			&session.tracks,
+			session.strict,
+			session.sort_by_disc_and_track,
+		)
+	}
+
+	fn find_indexed_paths<'a, I>(&self, real_paths: I) -> Result<HashSet<String>, Error>
+	where
+		I: Iterator<Item = &'a String>,
+	{
+		use self::songs::dsl::*;
+		let mut connection = self.db.connect()?;
+		let real_paths: Vec<&String> = real_paths.collect();
+		let mut indexed_paths = HashSet::new();
+		// Query in chunks to stay within SQLite's limit on bound parameters
+		for chunk in real_paths.chunks(1000) {
+			let found: Vec<String> = songs
+				.select(path)
+				.filter(path.eq_any(chunk))
+				.load(&mut connection)?;
+			indexed_paths.extend(found);
+		}
+		Ok(indexed_paths)
+	}
+
+	fn find_track_positions<'a, I>(
+		&self,
+		real_paths: I,
+	) -> Result<HashMap<String, TrackPosition>, Error>
+	where
+		I: Iterator<Item = &'a String>,
+	{
+		use self::songs::dsl::*;
+		let mut connection = self.db.connect()?;
+		let real_paths: Vec<&String> = real_paths.collect();
+		let mut positions = HashMap::new();
+		// Query in chunks to stay within SQLite's limit on bound parameters
+		for chunk in real_paths.chunks(1000) {
+			let found: Vec<(String, Option<i32>, Option<i32>)> = songs
+				.select((path, disc_number, track_number))
+				.filter(path.eq_any(chunk))
+				.load(&mut connection)?;
+			positions.extend(found.into_iter().map(|(p, d, t)| (p, (d, t))));
+		}
+		Ok(positions)
+	}
+
 	pub fn read_playlist(&self, playlist_name: &str, owner: &str) -> Result<Vec<Song>, Error> {
+		let (songs, _) = self.read_playlist_range(playlist_name, owner, 0, None)?;
+		Ok(songs)
+	}
+
+	pub fn read_playlist_page(
+		&self,
+		playlist_name: &str,
+		owner: &str,
+		offset: usize,
+		count: usize,
+	) -> Result<PlaylistPage, Error> {
+		let (songs, total) = self.read_playlist_range(playlist_name, owner, offset, Some(count))?;
+		Ok(PlaylistPage { songs, total })
+	}
+
+	fn read_playlist_range(
+		&self,
+		playlist_name: &str,
+		owner: &str,
+		offset: usize,
+		count: Option<usize>,
+	) -> Result<(Vec<Song>, usize), Error> {
+		let playlist_name = &normalize_name(playlist_name);
 		let vfs = self.vfs_manager.get_vfs()?;
 		let songs: Vec<Song>;
+		let total: i64;
 
 		{
 			let mut connection = self.db.connect()?;
@@ -163,17 +433,27 @@ impl Manager {
 					.ok_or(Error::PlaylistNotFound)?
 			};
 
-			// Select songs. Not using Diesel because we need to LEFT JOIN using a custom column
+			total = PlaylistSong::belonging_to(&playlist)
+				.count()
+				.get_result(&mut connection)?;
+
+			// Select songs. Not using Diesel because we need to JOIN using a custom column
+			// Tracks missing from the index are left out, but still count towards the total
+			// A negative LIMIT means no upper bound in SQLite
 			let query = diesel::sql_query(
 				r#"
-			SELECT s.id, s.path, s.parent, s.track_number, s.disc_number, s.title, s.artist, s.album_artist, s.year, s.album, s.artwork, s.duration, s.lyricist, s.composer, s.genre, s.label
+			SELECT s.id, s.path, s.parent, s.track_number, s.disc_number, s.title, s.artist, s.album_artist, s.year, s.album, s.artwork, s.duration, s.lyricist, s.composer, s.genre, s.label, s.hidden, s.grouping, s.work, s.movement_name, s.movement_number, s.original_genre, s.isrc, s.catalog_number, s.format, s.original_year, s.conductor, s.remixer, s.file_size, s.extra, s.disc_total, s.track_total, s.encoded_by, s.encoder_settings, s.original_artist, s.original_album, s.rating, s.effective_album_artist, s.language, s.media_type, s.disc_subtitle, s.initial_key, s.track_loudness, s.album_loudness, s.duration_source, s.content_type, s.arranger, s.performer
 			FROM playlist_songs ps
-			LEFT JOIN songs s ON ps.path = s.path
+			INNER JOIN songs s ON ps.path = s.path
 			WHERE ps.playlist = ?
 			ORDER BY ps.ordering
+			LIMIT ? OFFSET ?
 		"#,
 			);
-			let query = query.bind::<sql_types::Integer, _>(playlist.id);
+			let query = query
+				.bind::<sql_types::Integer, _>(playlist.id)
+				.bind::<sql_types::BigInt, _>(count.map_or(-1, |c| c as i64))
+				.bind::<sql_types::BigInt, _>(offset as i64);
 			songs = query.get_results(&mut connection)?;
 		}
 
@@ -183,10 +463,11 @@ impl Manager {
 			.filter_map(|s| s.virtualize(&vfs))
 			.collect();
 
-		Ok(virtual_songs)
+		Ok((virtual_songs, total as usize))
 	}
 
 	pub fn delete_playlist(&self, playlist_name: &str, owner: &str) -> Result<(), Error> {
+		let playlist_name = &normalize_name(playlist_name);
 		let mut connection = self.db.connect()?;
 
 		let user: User = {
@@ -208,6 +489,231 @@ impl Manager {
 			}
 		}
 	}
+
+	/// Moves the track at `from_index` to `to_index`, shifting the tracks in between.
+	pub fn move_track(
+		&self,
+		playlist_name: &str,
+		owner: &str,
+		from_index: usize,
+		to_index: usize,
+	) -> Result<(), Error> {
+		let playlist_name = &normalize_name(playlist_name);
+		let mut connection = self.db.connect()?;
+
+		let user: User = {
+			use self::users::dsl::*;
+			users
+				.filter(name.eq(owner))
+				.select((id,))
+				.first(&mut connection)
+				.optional()?
+				.ok_or(Error::UserNotFound)?
+		};
+
+		let now = now();
+
+		connection.transaction::<_, Error, _>(|connection| {
+			let playlist: Playlist = {
+				use self::playlists::dsl::*;
+				Playlist::belonging_to(&user)
+					.select((id, owner))
+					.filter(name.eq(playlist_name))
+					.get_result(&mut *connection)
+					.optional()?
+					.ok_or(Error::PlaylistNotFound)?
+			};
+
+			let mut track_ids: Vec<i32> = PlaylistSong::belonging_to(&playlist)
+				.select(playlist_songs::id)
+				.order(playlist_songs::ordering)
+				.load(&mut *connection)?;
+			for index in [from_index, to_index] {
+				if index >= track_ids.len() {
+					return Err(Error::TrackIndexOutOfRange(index));
+				}
+			}
+
+			let track_id = track_ids.remove(from_index);
+			track_ids.insert(to_index, track_id);
+
+			// Orderings are unique within a playlist, and a conflicting update replaces the track
+			// already holding a position. Tracks are moved out of the way before being renumbered.
+			diesel::update(PlaylistSong::belonging_to(&playlist))
+				.set(playlist_songs::ordering.eq(playlist_songs::ordering * -1 - 1))
+				.execute(&mut *connection)?;
+
+			// Renumbering every track also closes gaps left by tracks dropped when saving
+			for (position, track_id) in track_ids.into_iter().enumerate() {
+				use self::playlist_songs::dsl::*;
+				diesel::update(playlist_songs.filter(id.eq(track_id)))
+					.set(ordering.eq(position as i32))
+					.execute(&mut *connection)?;
+			}
+
+			{
+				use self::playlists::dsl::*;
+				diesel::update(playlists.filter(id.eq(playlist.id)))
+					.set(updated_at.eq(now))
+					.execute(&mut *connection)?;
+			}
+
+			Ok(())
+		})
+	}
+
+	/// Deletes several playlists of a user at once. Either all existing playlists are deleted, or none.
+	pub fn delete_playlists(
+		&self,
+		playlist_names: &[String],
+		owner: &str,
+	) -> Result<BulkDeletion, Error> {
+		let mut connection = self.db.connect()?;
+
+		let user: User = {
+			use self::users::dsl::*;
+			users
+				.filter(name.eq(owner))
+				.select((id,))
+				.first(&mut connection)
+				.optional()?
+				.ok_or(Error::UserNotFound)?
+		};
+
+		connection.transaction::<_, Error, _>(|connection| {
+			use self::playlists::dsl::*;
+			let mut deletion = BulkDeletion::default();
+			for playlist_name in playlist_names {
+				let q =
+					Playlist::belonging_to(&user).filter(name.eq(normalize_name(playlist_name)));
+				match diesel::delete(q).execute(&mut *connection)? {
+					0 => deletion.not_found.push(playlist_name.clone()),
+					_ => deletion.deleted.push(playlist_name.clone()),
+				}
+			}
+			Ok(deletion)
+		})
+	}
+
+	/// Lists tracks of a playlist which are no longer in the index, by virtual path.
+	pub fn find_orphans(&self, playlist_name: &str, owner: &str) -> Result<Vec<String>, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let playlist = Self::find_playlist(&mut connection, playlist_name, owner)?;
+		let orphans = Self::read_orphans(&mut connection, &playlist)?;
+		Ok(orphans
+			.into_iter()
+			.map(|(_, real_path)| virtualize_path(&vfs, real_path))
+			.collect())
+	}
+
+	/// Removes tracks which are no longer in the index from a playlist, and returns their virtual paths.
+	pub fn prune_orphans(&self, playlist_name: &str, owner: &str) -> Result<Vec<String>, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let playlist = Self::find_playlist(&mut connection, playlist_name, owner)?;
+
+		let now = now();
+
+		let orphans = connection.transaction::<_, Error, _>(|connection| {
+			let orphans = Self::read_orphans(connection, &playlist)?;
+			if orphans.is_empty() {
+				return Ok(orphans);
+			}
+
+			let orphan_ids: Vec<i32> = orphans.iter().map(|(id, _)| *id).collect();
+			{
+				use self::playlist_songs::dsl::*;
+				diesel::delete(playlist_songs.filter(id.eq_any(&orphan_ids)))
+					.execute(&mut *connection)?;
+			}
+			{
+				use self::playlists::dsl::*;
+				diesel::update(playlists.filter(id.eq(playlist.id)))
+					.set(updated_at.eq(now))
+					.execute(&mut *connection)?;
+			}
+			Ok(orphans)
+		})?;
+
+		Ok(orphans
+			.into_iter()
+			.map(|(_, real_path)| virtualize_path(&vfs, real_path))
+			.collect())
+	}
+
+	fn find_playlist(
+		connection: &mut SqliteConnection,
+		playlist_name: &str,
+		owner: &str,
+	) -> Result<Playlist, Error> {
+		let playlist_name = &normalize_name(playlist_name);
+		let user: User = {
+			use self::users::dsl::*;
+			users
+				.filter(name.eq(owner))
+				.select((id,))
+				.first(connection)
+				.optional()?
+				.ok_or(Error::UserNotFound)?
+		};
+
+		let playlist: Playlist = {
+			use self::playlists::dsl::*;
+			Playlist::belonging_to(&user)
+				.select((id, owner))
+				.filter(name.eq(playlist_name))
+				.get_result(connection)
+				.optional()?
+				.ok_or(Error::PlaylistNotFound)?
+		};
+		Ok(playlist)
+	}
+
+	/// Reads the ids and real paths of playlist tracks missing from the index, in playlist order.
+	fn read_orphans(
+		connection: &mut SqliteConnection,
+		parent_playlist: &Playlist,
+	) -> Result<Vec<(i32, String)>, Error> {
+		let orphans = {
+			use self::playlist_songs::dsl::*;
+			PlaylistSong::belonging_to(parent_playlist)
+				.select((id, path))
+				.filter(path.ne_all(songs::table.select(songs::path)))
+				.order(ordering)
+				.load(connection)?
+		};
+		Ok(orphans)
+	}
+}
+
+/// Trims a playlist name and collapses runs of whitespace within it to a single space.
+fn normalize_name(playlist_name: &str) -> String {
+	playlist_name
+		.split_whitespace()
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+/// Maps a real path to its virtual path, keeping the real path when it is not within any mount.
+fn virtualize_path(vfs: &vfs::VFS, real_path: String) -> String {
+	match vfs.real_to_virtual(Path::new(&real_path)) {
+		Ok(p) => p.to_string_lossy().into_owned(),
+		Err(_) => real_path,
+	}
+}
+
+/// Outcome of deleting several playlists at once.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BulkDeletion {
+	pub deleted: Vec<String>,
+	pub not_found: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct PlaylistPage {
+	pub songs: Vec<Song>,
+	pub total: usize,
 }
 
 #[derive(Identifiable, Queryable, Associations)]
@@ -229,6 +735,8 @@ struct PlaylistSong {
 struct NewPlaylist {
 	name: String,
 	owner: i32,
+	created_at: i64,
+	updated_at: i64,
 }
 
 #[derive(Insertable)]
@@ -248,6 +756,7 @@ struct User {
 mod test {
 	use std::path::{Path, PathBuf};
 
+	use super::{Error, ListOrder};
 	use crate::app::test;
 	use crate::test_name;
 
@@ -263,14 +772,63 @@ mod test {
 			.build();
 
 		ctx.playlist_manager
-			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &Vec::new())
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &Vec::new(), false, false)
 			.unwrap();
 
-		let found_playlists = ctx.playlist_manager.list_playlists(TEST_USER).unwrap();
+		let found_playlists = ctx
+			.playlist_manager
+			.list_playlists(TEST_USER, ListOrder::Name)
+			.unwrap();
 		assert_eq!(found_playlists.len(), 1);
 		assert_eq!(found_playlists[0], TEST_PLAYLIST_NAME);
 	}
 
+	#[test]
+	fn playlist_names_are_normalized() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build();
+
+		for playlist_name in ["  My Mix ", "My Mix", "My \t Mix"] {
+			ctx.playlist_manager
+				.save_playlist(playlist_name, TEST_USER, &Vec::new(), false, false)
+				.unwrap();
+		}
+
+		let found_playlists = ctx
+			.playlist_manager
+			.list_playlists(TEST_USER, ListOrder::Name)
+			.unwrap();
+		assert_eq!(found_playlists, vec!["My Mix".to_owned()]);
+		assert!(ctx
+			.playlist_manager
+			.read_playlist("  My Mix ", TEST_USER)
+			.is_ok());
+		ctx.playlist_manager
+			.delete_playlist(" My Mix", TEST_USER)
+			.unwrap();
+		assert!(ctx
+			.playlist_manager
+			.list_playlists(TEST_USER, ListOrder::Name)
+			.unwrap()
+			.is_empty());
+	}
+
+	#[test]
+	fn playlist_names_differing_by_case_conflict() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build();
+
+		ctx.playlist_manager
+			.save_playlist("My Mix", TEST_USER, &Vec::new(), false, false)
+			.unwrap();
+		let result =
+			ctx.playlist_manager
+				.save_playlist(" my mix", TEST_USER, &Vec::new(), false, false);
+		assert!(matches!(result, Err(Error::PlaylistNameConflict(ref n)) if n == "My Mix"));
+	}
+
 	#[test]
 	fn save_playlist_is_idempotent() {
 		let ctx = test::ContextBuilder::new(test_name!())
@@ -282,7 +840,7 @@ mod test {
 
 		let playlist_content: Vec<String> = ctx
 			.index
-			.flatten(Path::new(TEST_MOUNT_NAME))
+			.flatten(Path::new(TEST_MOUNT_NAME), false)
 			.unwrap()
 			.into_iter()
 			.map(|s| s.path)
@@ -290,11 +848,23 @@ mod test {
 		assert_eq!(playlist_content.len(), 13);
 
 		ctx.playlist_manager
-			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content)
+			.save_playlist(
+				TEST_PLAYLIST_NAME,
+				TEST_USER,
+				&playlist_content,
+				false,
+				false,
+			)
 			.unwrap();
 
 		ctx.playlist_manager
-			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content)
+			.save_playlist(
+				TEST_PLAYLIST_NAME,
+				TEST_USER,
+				&playlist_content,
+				false,
+				false,
+			)
 			.unwrap();
 
 		let songs = ctx
@@ -304,6 +874,64 @@ mod test {
 		assert_eq!(songs.len(), 13);
 	}
 
+	#[test]
+	fn save_playlist_can_sort_by_disc_and_track() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+
+		ctx.index.update().unwrap();
+
+		let album_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+		let mut playlist_content: Vec<String> = ctx
+			.index
+			.flatten(&album_path, false)
+			.unwrap()
+			.into_iter()
+			.map(|s| s.path)
+			.collect();
+		playlist_content.reverse();
+
+		ctx.playlist_manager
+			.save_playlist(
+				TEST_PLAYLIST_NAME,
+				TEST_USER,
+				&playlist_content,
+				false,
+				true,
+			)
+			.unwrap();
+		let songs = ctx
+			.playlist_manager
+			.read_playlist(TEST_PLAYLIST_NAME, TEST_USER)
+			.unwrap();
+		let track_numbers: Vec<Option<i32>> = songs.iter().map(|s| s.track_number).collect();
+		assert_eq!(
+			track_numbers,
+			vec![Some(1), Some(2), Some(3), Some(4), Some(5)]
+		);
+
+		ctx.playlist_manager
+			.save_playlist(
+				TEST_PLAYLIST_NAME,
+				TEST_USER,
+				&playlist_content,
+				false,
+				false,
+			)
+			.unwrap();
+		let songs = ctx
+			.playlist_manager
+			.read_playlist(TEST_PLAYLIST_NAME, TEST_USER)
+			.unwrap();
+		let track_numbers: Vec<Option<i32>> = songs.iter().map(|s| s.track_number).collect();
+		assert_eq!(
+			track_numbers,
+			vec![Some(5), Some(4), Some(3), Some(2), Some(1)]
+		);
+	}
+
 	#[test]
 	fn delete_playlist_golden_path() {
 		let ctx = test::ContextBuilder::new(test_name!())
@@ -313,17 +941,166 @@ mod test {
 		let playlist_content = Vec::new();
 
 		ctx.playlist_manager
-			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content)
+			.save_playlist(
+				TEST_PLAYLIST_NAME,
+				TEST_USER,
+				&playlist_content,
+				false,
+				false,
+			)
 			.unwrap();
 
 		ctx.playlist_manager
 			.delete_playlist(TEST_PLAYLIST_NAME, TEST_USER)
 			.unwrap();
 
-		let found_playlists = ctx.playlist_manager.list_playlists(TEST_USER).unwrap();
+		let found_playlists = ctx
+			.playlist_manager
+			.list_playlists(TEST_USER, ListOrder::Name)
+			.unwrap();
 		assert_eq!(found_playlists.len(), 0);
 	}
 
+	#[test]
+	fn delete_playlists_reports_missing_playlists() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build();
+
+		for playlist_name in ["Chill", "Road Trip", "Workout"] {
+			ctx.playlist_manager
+				.save_playlist(playlist_name, TEST_USER, &[], false, false)
+				.unwrap();
+		}
+
+		let names = ["Chill", "Road Trip", "Nonexistent"].map(str::to_owned);
+		let deletion = ctx
+			.playlist_manager
+			.delete_playlists(&names, TEST_USER)
+			.unwrap();
+		assert_eq!(deletion.deleted, vec!["Chill", "Road Trip"]);
+		assert_eq!(deletion.not_found, vec!["Nonexistent"]);
+
+		let found_playlists = ctx
+			.playlist_manager
+			.list_playlists(TEST_USER, ListOrder::Name)
+			.unwrap();
+		assert_eq!(found_playlists, vec!["Workout"]);
+	}
+
+	#[test]
+	fn move_track_reorders_playlist() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+
+		ctx.index.update().unwrap();
+
+		let album_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+		let playlist_content: Vec<String> = ctx
+			.index
+			.flatten(&album_path, false)
+			.unwrap()
+			.into_iter()
+			.map(|s| s.path)
+			.collect();
+		ctx.playlist_manager
+			.save_playlist(
+				TEST_PLAYLIST_NAME,
+				TEST_USER,
+				&playlist_content,
+				false,
+				false,
+			)
+			.unwrap();
+
+		ctx.playlist_manager
+			.move_track(TEST_PLAYLIST_NAME, TEST_USER, 0, 2)
+			.unwrap();
+
+		let songs = ctx
+			.playlist_manager
+			.read_playlist(TEST_PLAYLIST_NAME, TEST_USER)
+			.unwrap();
+		let track_numbers: Vec<Option<i32>> = songs.iter().map(|s| s.track_number).collect();
+		assert_eq!(
+			track_numbers,
+			vec![Some(2), Some(3), Some(1), Some(4), Some(5)]
+		);
+
+		assert!(matches!(
+			ctx.playlist_manager
+				.move_track(TEST_PLAYLIST_NAME, TEST_USER, 0, 5),
+			Err(Error::TrackIndexOutOfRange(5))
+		));
+		assert!(matches!(
+			ctx.playlist_manager
+				.move_track("Nonexistent", TEST_USER, 0, 1),
+			Err(Error::PlaylistNotFound)
+		));
+	}
+
+	#[test]
+	fn chunked_save_assembles_full_playlist() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.user("other_user", TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+
+		ctx.index.update().unwrap();
+
+		let album_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+		let playlist_content: Vec<String> = ctx
+			.index
+			.flatten(&album_path, false)
+			.unwrap()
+			.into_iter()
+			.map(|s| s.path)
+			.rev()
+			.collect();
+
+		let session_id =
+			ctx.playlist_manager
+				.begin_save(TEST_PLAYLIST_NAME, TEST_USER, true, false);
+		for chunk in [
+			&playlist_content[..2],
+			&playlist_content[2..3],
+			&playlist_content[3..],
+		] {
+			ctx.playlist_manager
+				.append_to_save(&session_id, TEST_USER, chunk)
+				.unwrap();
+		}
+
+		assert!(matches!(
+			ctx.playlist_manager.commit_save(&session_id, "other_user"),
+			Err(Error::SaveSessionNotFound)
+		));
+		let invalid_tracks = ctx
+			.playlist_manager
+			.commit_save(&session_id, TEST_USER)
+			.unwrap();
+		assert!(invalid_tracks.is_empty());
+
+		let songs = ctx
+			.playlist_manager
+			.read_playlist(TEST_PLAYLIST_NAME, TEST_USER)
+			.unwrap();
+		let track_numbers: Vec<Option<i32>> = songs.iter().map(|s| s.track_number).collect();
+		assert_eq!(
+			track_numbers,
+			vec![Some(5), Some(4), Some(3), Some(2), Some(1)]
+		);
+
+		assert!(matches!(
+			ctx.playlist_manager
+				.append_to_save(&session_id, TEST_USER, &playlist_content),
+			Err(Error::SaveSessionNotFound)
+		));
+	}
+
 	#[test]
 	fn read_playlist_golden_path() {
 		let ctx = test::ContextBuilder::new(test_name!())
@@ -335,7 +1112,7 @@ mod test {
 
 		let playlist_content: Vec<String> = ctx
 			.index
-			.flatten(Path::new(TEST_MOUNT_NAME))
+			.flatten(Path::new(TEST_MOUNT_NAME), false)
 			.unwrap()
 			.into_iter()
 			.map(|s| s.path)
@@ -343,7 +1120,13 @@ mod test {
 		assert_eq!(playlist_content.len(), 13);
 
 		ctx.playlist_manager
-			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, &playlist_content)
+			.save_playlist(
+				TEST_PLAYLIST_NAME,
+				TEST_USER,
+				&playlist_content,
+				false,
+				false,
+			)
 			.unwrap();
 
 		let songs = ctx
@@ -364,4 +1147,250 @@ mod test {
 		.collect();
 		assert_eq!(songs[0].path, first_song_path.to_str().unwrap());
 	}
+
+	#[test]
+	fn read_playlist_pages_preserve_order() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+
+		ctx.index.update().unwrap();
+
+		let songs: Vec<String> = ctx
+			.index
+			.flatten(Path::new(TEST_MOUNT_NAME), false)
+			.unwrap()
+			.into_iter()
+			.map(|s| s.path)
+			.collect();
+		let playlist_content: Vec<String> = (0..700)
+			.map(|i| songs[(i * 7) % songs.len()].clone())
+			.collect();
+		assert_eq!(playlist_content.len(), 700);
+
+		ctx.playlist_manager
+			.save_playlist(
+				TEST_PLAYLIST_NAME,
+				TEST_USER,
+				&playlist_content,
+				false,
+				false,
+			)
+			.unwrap();
+
+		let page_size = 33;
+		let mut offset = 0;
+		let mut reassembled = Vec::new();
+		loop {
+			let page = ctx
+				.playlist_manager
+				.read_playlist_page(TEST_PLAYLIST_NAME, TEST_USER, offset, page_size)
+				.unwrap();
+			assert_eq!(page.total, playlist_content.len());
+			if page.songs.is_empty() {
+				break;
+			}
+			assert!(page.songs.len() <= page_size);
+			offset += page.songs.len();
+			reassembled.extend(page.songs.into_iter().map(|s| s.path));
+		}
+
+		assert_eq!(reassembled, playlist_content);
+	}
+
+	#[test]
+	fn save_playlist_reports_invalid_tracks() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+
+		ctx.index.update().unwrap();
+
+		let mut playlist_content: Vec<String> = ctx
+			.index
+			.flatten(Path::new(TEST_MOUNT_NAME), false)
+			.unwrap()
+			.into_iter()
+			.map(|s| s.path)
+			.collect();
+		let bogus_path: PathBuf = [TEST_MOUNT_NAME, "Nowhere", "Bogus.mp3"].iter().collect();
+		let bogus_path = bogus_path.to_string_lossy().into_owned();
+		playlist_content.insert(3, bogus_path.clone());
+
+		let invalid_tracks = ctx
+			.playlist_manager
+			.save_playlist(
+				TEST_PLAYLIST_NAME,
+				TEST_USER,
+				&playlist_content,
+				false,
+				false,
+			)
+			.unwrap();
+		assert_eq!(invalid_tracks, vec![bogus_path]);
+
+		let songs = ctx
+			.playlist_manager
+			.read_playlist_page(TEST_PLAYLIST_NAME, TEST_USER, 0, 100)
+			.unwrap();
+		assert_eq!(songs.total, 14);
+		assert_eq!(songs.songs.len(), 13);
+	}
+
+	#[test]
+	fn save_playlist_strict_rejects_invalid_tracks() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+
+		ctx.index.update().unwrap();
+
+		let mut playlist_content: Vec<String> = ctx
+			.index
+			.flatten(Path::new(TEST_MOUNT_NAME), false)
+			.unwrap()
+			.into_iter()
+			.map(|s| s.path)
+			.collect();
+		let bogus_path: PathBuf = [TEST_MOUNT_NAME, "Nowhere", "Bogus.mp3"].iter().collect();
+		playlist_content.push(bogus_path.to_string_lossy().into_owned());
+
+		let result = ctx.playlist_manager.save_playlist(
+			TEST_PLAYLIST_NAME,
+			TEST_USER,
+			&playlist_content,
+			true,
+			false,
+		);
+		assert!(matches!(result, Err(Error::InvalidTracks(ref t)) if t.len() == 1));
+
+		let found_playlists = ctx
+			.playlist_manager
+			.list_playlists(TEST_USER, ListOrder::Name)
+			.unwrap();
+		assert!(found_playlists.is_empty());
+	}
+
+	#[test]
+	fn list_playlists_is_alphabetical_by_default() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build();
+
+		for playlist_name in ["Road Trip", "Chill", "Workout"] {
+			ctx.playlist_manager
+				.save_playlist(playlist_name, TEST_USER, &Vec::new(), false, false)
+				.unwrap();
+		}
+
+		let found_playlists = ctx
+			.playlist_manager
+			.list_playlists(TEST_USER, ListOrder::default())
+			.unwrap();
+		assert_eq!(found_playlists, vec!["Chill", "Road Trip", "Workout"]);
+
+		let found_playlists = ctx
+			.playlist_manager
+			.list_playlists(TEST_USER, ListOrder::Created)
+			.unwrap();
+		assert_eq!(found_playlists, vec!["Workout", "Chill", "Road Trip"]);
+	}
+
+	#[test]
+	fn list_playlists_by_update_time() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build();
+
+		for playlist_name in ["Road Trip", "Chill", "Workout", "Road Trip"] {
+			ctx.playlist_manager
+				.save_playlist(playlist_name, TEST_USER, &Vec::new(), false, false)
+				.unwrap();
+		}
+
+		let found_playlists = ctx
+			.playlist_manager
+			.list_playlists(TEST_USER, ListOrder::Updated)
+			.unwrap();
+		assert_eq!(found_playlists, vec!["Road Trip", "Workout", "Chill"]);
+	}
+
+	#[test]
+	fn prune_removes_tracks_missing_from_index() {
+		let builder = test::ContextBuilder::new(test_name!());
+		let album_dir = builder.test_directory.join("Hunted");
+		std::fs::create_dir_all(&album_dir).unwrap();
+		let hunted_dir: PathBuf = ["test-data", "small-collection", "Khemmis", "Hunted"]
+			.iter()
+			.collect();
+		for entry in std::fs::read_dir(hunted_dir).unwrap() {
+			let path = entry.unwrap().path();
+			std::fs::copy(&path, album_dir.join(path.file_name().unwrap())).unwrap();
+		}
+
+		let ctx = builder
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, album_dir.to_str().unwrap())
+			.build();
+		ctx.index.update().unwrap();
+
+		let playlist_content: Vec<String> = ctx
+			.index
+			.flatten(Path::new(TEST_MOUNT_NAME), false)
+			.unwrap()
+			.into_iter()
+			.map(|s| s.path)
+			.collect();
+		assert_eq!(playlist_content.len(), 5);
+		ctx.playlist_manager
+			.save_playlist(
+				TEST_PLAYLIST_NAME,
+				TEST_USER,
+				&playlist_content,
+				true,
+				false,
+			)
+			.unwrap();
+		assert!(ctx
+			.playlist_manager
+			.find_orphans(TEST_PLAYLIST_NAME, TEST_USER)
+			.unwrap()
+			.is_empty());
+
+		std::fs::remove_file(album_dir.join("03 - Three Gates.mp3")).unwrap();
+		ctx.index.update().unwrap();
+
+		let removed_song: String = [TEST_MOUNT_NAME, "03 - Three Gates.mp3"]
+			.iter()
+			.collect::<PathBuf>()
+			.to_string_lossy()
+			.into_owned();
+		assert_eq!(
+			ctx.playlist_manager
+				.find_orphans(TEST_PLAYLIST_NAME, TEST_USER)
+				.unwrap(),
+			vec![removed_song.clone()]
+		);
+		assert_eq!(
+			ctx.playlist_manager
+				.prune_orphans(TEST_PLAYLIST_NAME, TEST_USER)
+				.unwrap(),
+			vec![removed_song.clone()]
+		);
+		assert!(ctx
+			.playlist_manager
+			.find_orphans(TEST_PLAYLIST_NAME, TEST_USER)
+			.unwrap()
+			.is_empty());
+
+		let songs = ctx
+			.playlist_manager
+			.read_playlist(TEST_PLAYLIST_NAME, TEST_USER)
+			.unwrap();
+		assert_eq!(songs.len(), 4);
+		assert!(songs.iter().all(|s| s.path != removed_song));
+	}
 }
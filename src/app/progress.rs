@@ -0,0 +1,236 @@
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::app::{index, vfs};
+use crate::db::{self, playback_positions, songs, users, DB};
+use crate::utils::now;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error(transparent)]
+	Database(#[from] diesel::result::Error),
+	#[error(transparent)]
+	DatabaseConnection(#[from] db::Error),
+	#[error("Song was not found: `{0}`")]
+	SongNotFound(String),
+	#[error("No playback position was saved for `{0}`")]
+	PositionNotFound(String),
+	#[error("User not found")]
+	UserNotFound,
+	#[error(transparent)]
+	Vfs(#[from] vfs::Error),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+	/// Seconds into the song at which playback should resume.
+	pub seconds: u32,
+	/// Seconds since the UNIX epoch.
+	pub updated_at: i64,
+}
+
+#[derive(Clone)]
+pub struct Manager {
+	db: DB,
+	vfs_manager: vfs::Manager,
+}
+
+impl Manager {
+	pub fn new(db: DB, vfs_manager: vfs::Manager) -> Self {
+		Self { db, vfs_manager }
+	}
+
+	/// Saves how far into a song a user got, replacing any previously saved position.
+	pub fn set_position(
+		&self,
+		username: &str,
+		virtual_path: &str,
+		seconds: u32,
+	) -> Result<Position, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let real_path = vfs.virtual_to_real(Path::new(virtual_path))?;
+		let real_path = real_path.to_string_lossy().into_owned();
+		let now = now();
+
+		let mut connection = self.db.connect()?;
+		let user_id = get_user_id(&mut connection, username)?;
+
+		let is_indexed: bool = diesel::select(diesel::dsl::exists(
+			songs::table.filter(songs::path.eq(&real_path)),
+		))
+		.get_result(&mut connection)?;
+		if !is_indexed {
+			return Err(Error::SongNotFound(virtual_path.to_owned()));
+		}
+
+		diesel::insert_into(playback_positions::table)
+			.values((
+				playback_positions::owner.eq(user_id),
+				playback_positions::path.eq(&real_path),
+				playback_positions::seconds.eq(seconds as i32),
+				playback_positions::updated_at.eq(now),
+			))
+			.execute(&mut connection)?;
+
+		Ok(Position {
+			seconds,
+			updated_at: now,
+		})
+	}
+
+	/// Returns the position at which a user should resume playing a song.
+	pub fn get_position(&self, username: &str, virtual_path: &str) -> Result<Position, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let real_path = vfs.virtual_to_real(Path::new(virtual_path))?;
+		let real_path = real_path.to_string_lossy().into_owned();
+
+		let mut connection = self.db.connect()?;
+		let user_id = get_user_id(&mut connection, username)?;
+
+		let (seconds, updated_at): (i32, i64) = playback_positions::table
+			.select((playback_positions::seconds, playback_positions::updated_at))
+			.filter(playback_positions::owner.eq(user_id))
+			.filter(playback_positions::path.eq(&real_path))
+			.get_result(&mut connection)
+			.optional()?
+			.ok_or_else(|| Error::PositionNotFound(virtual_path.to_owned()))?;
+
+		Ok(Position {
+			seconds: seconds as u32,
+			updated_at,
+		})
+	}
+
+	/// Returns the songs a user most recently saved a playback position for, most recent first.
+	/// Songs which are no longer indexed are omitted.
+	pub fn get_recent(
+		&self,
+		username: &str,
+		count: usize,
+	) -> Result<Vec<(index::Song, Position)>, Error> {
+		let vfs = self.vfs_manager.get_vfs()?;
+		let mut connection = self.db.connect()?;
+		let user_id = get_user_id(&mut connection, username)?;
+
+		let rows: Vec<(String, i32, i64)> = playback_positions::table
+			.select((
+				playback_positions::path,
+				playback_positions::seconds,
+				playback_positions::updated_at,
+			))
+			.filter(playback_positions::owner.eq(user_id))
+			.filter(playback_positions::seconds.gt(0))
+			.order((
+				playback_positions::updated_at.desc(),
+				playback_positions::id.desc(),
+			))
+			.load(&mut connection)?;
+
+		let real_paths: Vec<&String> = rows.iter().map(|(path, _, _)| path).collect();
+		let mut songs: HashMap<String, index::Song> = HashMap::new();
+		for chunk in real_paths.chunks(1000) {
+			let chunk_songs: Vec<index::Song> = songs::table
+				.filter(songs::path.eq_any(chunk))
+				.load(&mut connection)?;
+			songs.extend(chunk_songs.into_iter().map(|s| (s.path.clone(), s)));
+		}
+
+		Ok(rows
+			.into_iter()
+			.filter_map(|(path, seconds, updated_at)| {
+				let song = songs.remove(&path)?.virtualize(&vfs)?;
+				let position = Position {
+					seconds: seconds as u32,
+					updated_at,
+				};
+				Some((song, position))
+			})
+			.take(count)
+			.collect())
+	}
+}
+
+fn get_user_id(connection: &mut SqliteConnection, username: &str) -> Result<i32, Error> {
+	users::table
+		.select(users::id)
+		.filter(users::name.eq(username))
+		.get_result(connection)
+		.optional()?
+		.ok_or(Error::UserNotFound)
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+	const TEST_PASSWORD: &str = "password";
+	const OTHER_USER: &str = "other_user";
+	const TEST_MOUNT_NAME: &str = "root";
+
+	#[test]
+	fn position_round_trips_for_user_only() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.user(OTHER_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+		ctx.index.update().unwrap();
+
+		let song_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+			.iter()
+			.collect();
+		let song_path = song_path.to_str().unwrap();
+
+		ctx.progress_manager
+			.set_position(TEST_USER, song_path, 42)
+			.unwrap();
+		ctx.progress_manager
+			.set_position(TEST_USER, song_path, 95)
+			.unwrap();
+
+		let position = ctx
+			.progress_manager
+			.get_position(TEST_USER, song_path)
+			.unwrap();
+		assert_eq!(position.seconds, 95);
+
+		let recent = ctx.progress_manager.get_recent(TEST_USER, 10).unwrap();
+		assert_eq!(recent.len(), 1);
+		assert_eq!(recent[0].0.path, song_path);
+		assert_eq!(recent[0].1.seconds, 95);
+
+		assert!(matches!(
+			ctx.progress_manager.get_position(OTHER_USER, song_path),
+			Err(super::Error::PositionNotFound(_))
+		));
+		assert!(ctx
+			.progress_manager
+			.get_recent(OTHER_USER, 10)
+			.unwrap()
+			.is_empty());
+	}
+
+	#[test]
+	fn cannot_set_position_of_unknown_song() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build();
+		ctx.index.update().unwrap();
+
+		let song_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Not A Song.mp3"]
+			.iter()
+			.collect();
+		assert!(matches!(
+			ctx.progress_manager
+				.set_position(TEST_USER, song_path.to_str().unwrap(), 10),
+			Err(super::Error::SongNotFound(_))
+		));
+	}
+}
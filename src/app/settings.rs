@@ -1,8 +1,12 @@
+use base64::prelude::*;
 use diesel::prelude::*;
+use log::info;
 use regex::Regex;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::time::Duration;
+use url::Url;
 
 use crate::db::{self, misc_settings, DB};
 
@@ -18,6 +22,32 @@ pub enum Error {
 	MiscSettingsNotFound,
 	#[error("Index album art pattern is not a valid regex")]
 	IndexAlbumArtPatternInvalid,
+	#[error("Artist image pattern is not a valid regex")]
+	ArtistImagePatternInvalid,
+	#[error("Genre aliases could not be serialized")]
+	GenreAliasesInvalid(#[from] serde_json::Error),
+	#[error("Unknown bandwidth rollover period: `{0}`")]
+	BandwidthRolloverInvalid(String),
+	#[error("Unknown album artist grouping: `{0}`")]
+	AlbumArtistGroupingInvalid(String),
+	#[error("Unknown artwork priority: `{0}`")]
+	ArtworkPriorityInvalid(String),
+	#[error("Unknown directory thumbnail mode: `{0}`")]
+	DirectoryThumbnailInvalid(String),
+	#[error("Unknown symlinked duplicates handling: `{0}`")]
+	SymlinkedDuplicatesInvalid(String),
+	#[error("Unknown metadata reader: `{0}`")]
+	MetadataReaderInvalid(String),
+	#[error("Unknown mount order: `{0}`")]
+	MountOrderInvalid(String),
+	#[error("Unknown tag container: `{0}`")]
+	TagContainerInvalid(String),
+	#[error("Tag priority must list at least one tag container")]
+	TagPriorityEmpty,
+	#[error("Public base URL is not a valid http(s) URL: `{0}`")]
+	PublicBaseUrlInvalid(String),
+	#[error("Page size must be at least 1: `{0}`")]
+	PageSizeInvalid(i32),
 	#[error(transparent)]
 	Database(#[from] diesel::result::Error),
 }
@@ -27,16 +57,517 @@ pub struct AuthSecret {
 	pub key: [u8; 32],
 }
 
-#[derive(Debug, Queryable)]
+impl AuthSecret {
+	/// Parses a secret provided as base64-encoded 32 bytes.
+	pub fn from_base64(encoded: &str) -> Result<Self, Error> {
+		BASE64_STANDARD
+			.decode(encoded.trim())
+			.ok()
+			.and_then(|bytes| bytes.try_into().ok())
+			.map(|key| AuthSecret { key })
+			.ok_or(Error::AuthenticationSecretInvalid)
+	}
+}
+
+/// How often per-user bandwidth counters are reset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BandwidthRollover {
+	Never,
+	Daily,
+	Monthly,
+}
+
+impl BandwidthRollover {
+	fn as_str(&self) -> &'static str {
+		match self {
+			Self::Never => "never",
+			Self::Daily => "daily",
+			Self::Monthly => "monthly",
+		}
+	}
+}
+
+impl std::str::FromStr for BandwidthRollover {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"never" => Ok(Self::Never),
+			"daily" => Ok(Self::Daily),
+			"monthly" => Ok(Self::Monthly),
+			_ => Err(Error::BandwidthRolloverInvalid(s.to_owned())),
+		}
+	}
+}
+
+/// How albums credited to several album artists are grouped when aggregating artists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlbumArtistGrouping {
+	/// The album is listed under each of its album artists.
+	Each,
+	/// The album is listed under a single artist combining all its album artists.
+	Joined,
+}
+
+impl AlbumArtistGrouping {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Each => "each",
+			Self::Joined => "joined",
+		}
+	}
+}
+
+impl std::str::FromStr for AlbumArtistGrouping {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"each" => Ok(Self::Each),
+			"joined" => Ok(Self::Joined),
+			_ => Err(Error::AlbumArtistGroupingInvalid(s.to_owned())),
+		}
+	}
+}
+
+/// Which artwork is shown for a song, when it has embedded artwork and its folder contains an
+/// image matching the album art pattern. The other source is only used when the preferred one is missing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtworkPriority {
+	Embedded,
+	Folder,
+	/// Whichever source takes the fewest bytes, for bandwidth-sensitive clients.
+	Smaller,
+}
+
+impl ArtworkPriority {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Embedded => "embedded",
+			Self::Folder => "folder",
+			Self::Smaller => "smaller",
+		}
+	}
+}
+
+impl std::str::FromStr for ArtworkPriority {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"embedded" => Ok(Self::Embedded),
+			"folder" => Ok(Self::Folder),
+			"smaller" => Ok(Self::Smaller),
+			_ => Err(Error::ArtworkPriorityInvalid(s.to_owned())),
+		}
+	}
+}
+
+/// How thumbnails are made for directories which have no artwork of their own, such as artist
+/// directories holding several albums.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirectoryThumbnail {
+	/// Directories without artwork have no thumbnail.
+	Disabled,
+	/// Uses the first cover found within the directory.
+	FirstCover,
+	/// Lays out up to four distinct covers found within the directory in a grid.
+	Collage,
+}
+
+impl DirectoryThumbnail {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Disabled => "disabled",
+			Self::FirstCover => "first_cover",
+			Self::Collage => "collage",
+		}
+	}
+}
+
+impl std::str::FromStr for DirectoryThumbnail {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"disabled" => Ok(Self::Disabled),
+			"first_cover" => Ok(Self::FirstCover),
+			"collage" => Ok(Self::Collage),
+			_ => Err(Error::DirectoryThumbnailInvalid(s.to_owned())),
+		}
+	}
+}
+
+/// How audio files reached through symbolic links are indexed, when the file they link to lies
+/// within the collection as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkedDuplicates {
+	/// Lists the file under every path leading to it, reading its metadata once for all of its links.
+	Alias,
+	/// Only lists the file under its real location.
+	Skip,
+}
+
+impl SymlinkedDuplicates {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Alias => "alias",
+			Self::Skip => "skip",
+		}
+	}
+}
+
+impl std::str::FromStr for SymlinkedDuplicates {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"alias" => Ok(Self::Alias),
+			"skip" => Ok(Self::Skip),
+			_ => Err(Error::SymlinkedDuplicatesInvalid(s.to_owned())),
+		}
+	}
+}
+
+/// Order of the mounts listed when browsing the root of the collection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MountOrder {
+	/// Sorts mounts by name, ignoring case.
+	Alphabetical,
+	/// Keeps the order in which mounts are configured.
+	Config,
+}
+
+impl MountOrder {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Alphabetical => "alphabetical",
+			Self::Config => "config",
+		}
+	}
+}
+
+impl std::str::FromStr for MountOrder {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"alphabetical" => Ok(Self::Alphabetical),
+			"config" => Ok(Self::Config),
+			_ => Err(Error::MountOrderInvalid(s.to_owned())),
+		}
+	}
+}
+
+/// Readers which can parse the metadata of audio files. Each audio format has a default reader,
+/// which can be overridden for a file extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataReader {
+	Aiff,
+	Ape,
+	Flac,
+	/// ID3v2 tag at the start of the file, regardless of what follows it.
+	Id3,
+	/// Tag containers listed in the tag priority, with durations computed from MP3 frames.
+	Mp3,
+	Mp4,
+	Opus,
+	Vorbis,
+	Wave,
+	Wma,
+	/// Does not read the file, which is not indexed as a song.
+	Skip,
+}
+
+impl MetadataReader {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Aiff => "aiff",
+			Self::Ape => "ape",
+			Self::Flac => "flac",
+			Self::Id3 => "id3",
+			Self::Mp3 => "mp3",
+			Self::Mp4 => "mp4",
+			Self::Opus => "opus",
+			Self::Vorbis => "vorbis",
+			Self::Wave => "wave",
+			Self::Wma => "wma",
+			Self::Skip => "skip",
+		}
+	}
+}
+
+impl std::str::FromStr for MetadataReader {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"aiff" => Ok(Self::Aiff),
+			"ape" => Ok(Self::Ape),
+			"flac" => Ok(Self::Flac),
+			"id3" => Ok(Self::Id3),
+			"mp3" => Ok(Self::Mp3),
+			"mp4" => Ok(Self::Mp4),
+			"opus" => Ok(Self::Opus),
+			"vorbis" => Ok(Self::Vorbis),
+			"wave" => Ok(Self::Wave),
+			"wma" => Ok(Self::Wma),
+			"skip" => Ok(Self::Skip),
+			_ => Err(Error::MetadataReaderInvalid(s.to_owned())),
+		}
+	}
+}
+
+/// Metadata reader overrides are stored as a JSON object, from file extensions to reader names.
+fn parse_metadata_readers(readers: &str) -> Result<HashMap<String, MetadataReader>, Error> {
+	let readers: HashMap<String, String> = serde_json::from_str(readers)?;
+	readers
+		.into_iter()
+		.map(|(extension, reader)| Ok((extension, reader.parse()?)))
+		.collect()
+}
+
+/// Extensions are matched ignoring case, and may be written with a leading dot.
+pub fn normalize_extension(extension: &str) -> String {
+	extension.trim().trim_start_matches('.').to_lowercase()
+}
+
+/// Tag formats which can coexist within a single file, such as ID3v2 and APEv2 tags in MP3 files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagContainer {
+	Id3,
+	Ape,
+}
+
+impl TagContainer {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Id3 => "id3",
+			Self::Ape => "ape",
+		}
+	}
+}
+
+impl std::str::FromStr for TagContainer {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"id3" => Ok(Self::Id3),
+			"ape" => Ok(Self::Ape),
+			_ => Err(Error::TagContainerInvalid(s.to_owned())),
+		}
+	}
+}
+
+/// Tag priority is stored as a comma separated list of tag containers, highest priority first.
+fn parse_tag_priority(priority: &str) -> Result<Vec<TagContainer>, Error> {
+	let containers = priority
+		.split(',')
+		.map(str::trim)
+		.filter(|c| !c.is_empty())
+		.map(str::parse)
+		.collect::<Result<Vec<TagContainer>, Error>>()?;
+	if containers.is_empty() {
+		return Err(Error::TagPriorityEmpty);
+	}
+	Ok(containers)
+}
+
+/// Lists of names, such as artist name articles, are stored comma separated (eg. `the,a,an`).
+fn parse_name_list(names: &str) -> Vec<String> {
+	names
+		.split(',')
+		.map(str::trim)
+		.filter(|a| !a.is_empty())
+		.map(str::to_owned)
+		.collect()
+}
+
+fn join_name_list(names: &[String]) -> String {
+	let names: Vec<&str> = names
+		.iter()
+		.map(|n| n.trim())
+		.filter(|n| !n.is_empty())
+		.collect();
+	names.join(",")
+}
+
+#[derive(Debug)]
 pub struct Settings {
 	pub index_sleep_duration_seconds: i32,
 	pub index_album_art_pattern: String,
+	pub auto_rename_duplicate_mounts: bool,
+	pub normalize_genres: bool,
+	pub genre_aliases: HashMap<String, String>,
+	pub fallback_artist: String,
+	pub fallback_album: String,
+	pub track_bandwidth: bool,
+	pub bandwidth_rollover: BandwidthRollover,
+	pub scan_on_startup: bool,
+	pub album_artist_grouping: AlbumArtistGrouping,
+	pub snapshot_index_updates: bool,
+	pub expose_extra_tags: bool,
+	pub index_max_depth: i32,
+	pub public_base_url: String,
+	pub min_file_size_bytes: i64,
+	pub artwork_placeholder_path: String,
+	pub skip_hidden_files: bool,
+	pub artist_image_pattern: String,
+	pub merge_duplicate_albums: bool,
+	pub preferred_album_format: String,
+	pub tag_priority: Vec<TagContainer>,
+	pub estimate_missing_durations: bool,
+	pub album_artist_fallback: bool,
+	pub default_page_size: i32,
+	pub max_page_size: i32,
+	pub artwork_priority: ArtworkPriority,
+	pub normalize_artist_names: bool,
+	pub artist_name_articles: Vec<String>,
+	pub directory_thumbnail: DirectoryThumbnail,
+	/// Number of consecutive scans failing to read an audio file, after which the file is no longer
+	/// read until it is modified. Zero means files are retried forever.
+	pub scan_failure_limit: i32,
+	/// Whether scans measure the loudness of songs and albums, for formats which can be decoded.
+	pub analyze_loudness: bool,
+	pub symlinked_duplicates: SymlinkedDuplicates,
+	/// Largest size in bytes of the cover images uploaded to replace album artwork.
+	pub max_cover_bytes: i32,
+	/// Names of directories holding audiobooks, for songs whose tags do not tell their content type.
+	pub audiobook_directories: Vec<String>,
+	/// Names of directories holding podcasts, for songs whose tags do not tell their content type.
+	pub podcast_directories: Vec<String>,
+	/// Whether browsing omits directories which hold no songs, directly or within sub-directories.
+	pub hide_empty_directories: bool,
+	/// Whether scans generate thumbnails for the artwork of each album they find.
+	pub prewarm_thumbnails_on_scan: bool,
+	/// Readers to use for file extensions, instead of the default reader of their audio format.
+	pub metadata_readers: HashMap<String, MetadataReader>,
+	pub mount_order: MountOrder,
+	/// Whether applying a configuration which changes mounts or the album art pattern refreshes
+	/// the index right away, rather than at the next scheduled scan.
+	pub reindex_on_config_change: bool,
+}
+
+#[derive(Queryable)]
+struct SettingsRow {
+	index_sleep_duration_seconds: i32,
+	index_album_art_pattern: String,
+	auto_rename_duplicate_mounts: bool,
+	normalize_genres: bool,
+	genre_aliases: String,
+	fallback_artist: String,
+	fallback_album: String,
+	track_bandwidth: bool,
+	bandwidth_rollover: String,
+	scan_on_startup: bool,
+	album_artist_grouping: String,
+	snapshot_index_updates: bool,
+	expose_extra_tags: bool,
+	index_max_depth: i32,
+	public_base_url: String,
+	min_file_size_bytes: i64,
+	artwork_placeholder_path: String,
+	skip_hidden_files: bool,
+	artist_image_pattern: String,
+	merge_duplicate_albums: bool,
+	preferred_album_format: String,
+	tag_priority: String,
+	estimate_missing_durations: bool,
+	album_artist_fallback: bool,
+	default_page_size: i32,
+	max_page_size: i32,
+	artwork_priority: String,
+	normalize_artist_names: bool,
+	artist_name_articles: String,
+	directory_thumbnail: String,
+	scan_failure_limit: i32,
+	analyze_loudness: bool,
+	symlinked_duplicates: String,
+	max_cover_bytes: i32,
+	audiobook_directories: String,
+	podcast_directories: String,
+	hide_empty_directories: bool,
+	prewarm_thumbnails_on_scan: bool,
+	metadata_readers: String,
+	mount_order: String,
+	reindex_on_config_change: bool,
 }
 
 #[derive(Debug, Default, Deserialize)]
 pub struct NewSettings {
 	pub reindex_every_n_seconds: Option<i32>,
 	pub album_art_pattern: Option<String>,
+	pub auto_rename_duplicate_mounts: Option<bool>,
+	pub normalize_genres: Option<bool>,
+	pub genre_aliases: Option<HashMap<String, String>>,
+	pub fallback_artist: Option<String>,
+	pub fallback_album: Option<String>,
+	pub track_bandwidth: Option<bool>,
+	pub bandwidth_rollover: Option<BandwidthRollover>,
+	pub scan_on_startup: Option<bool>,
+	pub album_artist_grouping: Option<AlbumArtistGrouping>,
+	pub snapshot_index_updates: Option<bool>,
+	pub expose_extra_tags: Option<bool>,
+	pub index_max_depth: Option<i32>,
+	pub public_base_url: Option<String>,
+	pub min_file_size_bytes: Option<i64>,
+	pub artwork_placeholder_path: Option<String>,
+	pub skip_hidden_files: Option<bool>,
+	pub artist_image_pattern: Option<String>,
+	pub merge_duplicate_albums: Option<bool>,
+	pub preferred_album_format: Option<String>,
+	pub tag_priority: Option<Vec<TagContainer>>,
+	pub estimate_missing_durations: Option<bool>,
+	pub album_artist_fallback: Option<bool>,
+	pub default_page_size: Option<i32>,
+	pub max_page_size: Option<i32>,
+	pub artwork_priority: Option<ArtworkPriority>,
+	pub normalize_artist_names: Option<bool>,
+	pub artist_name_articles: Option<Vec<String>>,
+	pub directory_thumbnail: Option<DirectoryThumbnail>,
+	pub scan_failure_limit: Option<i32>,
+	pub analyze_loudness: Option<bool>,
+	pub symlinked_duplicates: Option<SymlinkedDuplicates>,
+	pub max_cover_bytes: Option<i32>,
+	pub audiobook_directories: Option<Vec<String>>,
+	pub podcast_directories: Option<Vec<String>>,
+	pub hide_empty_directories: Option<bool>,
+	pub prewarm_thumbnails_on_scan: Option<bool>,
+	pub metadata_readers: Option<HashMap<String, MetadataReader>>,
+	pub mount_order: Option<MountOrder>,
+	pub reindex_on_config_change: Option<bool>,
+}
+
+/// Album art patterns are matched against file names, ignoring case.
+pub fn compile_album_art_pattern(pattern: &str) -> Result<Regex, Error> {
+	Regex::new(&format!("(?i){}", pattern)).map_err(|_| Error::IndexAlbumArtPatternInvalid)
+}
+
+/// Artist image patterns are matched against file names, ignoring case.
+pub fn compile_artist_image_pattern(pattern: &str) -> Result<Regex, Error> {
+	Regex::new(&format!("(?i){}", pattern)).map_err(|_| Error::ArtistImagePatternInvalid)
+}
+
+/// Parses the URL under which the server is publicly reachable. An empty value means none was set.
+pub fn parse_public_base_url(url: &str) -> Result<Option<Url>, Error> {
+	if url.is_empty() {
+		return Ok(None);
+	}
+	match Url::parse(url) {
+		Ok(parsed) if ["http", "https"].contains(&parsed.scheme()) && parsed.has_host() => {
+			Ok(Some(parsed))
+		}
+		_ => Err(Error::PublicBaseUrlInvalid(url.to_owned())),
+	}
 }
 
 #[derive(Clone)]
@@ -59,10 +590,17 @@ impl Manager {
 				diesel::result::Error::NotFound => Error::AuthenticationSecretNotFound,
 				e => e.into(),
 			})?;
-		secret
-			.try_into()
-			.map_err(|_| Error::AuthenticationSecretInvalid)
-			.map(|key| AuthSecret { key })
+
+		if let Ok(key) = secret.try_into() {
+			return Ok(AuthSecret { key });
+		}
+
+		info!("No valid auth secret found, generating a new one");
+		let key: [u8; 32] = rand::random();
+		diesel::update(misc_settings)
+			.set(auth_secret.eq(key.to_vec()))
+			.execute(&mut connection)?;
+		Ok(AuthSecret { key })
 	}
 
 	pub fn get_index_sleep_duration(&self) -> Result<Duration, Error> {
@@ -72,26 +610,124 @@ impl Manager {
 		))
 	}
 
-	pub fn get_index_album_art_pattern(&self) -> Result<Regex, Error> {
+	pub fn get_artist_image_pattern(&self) -> Result<Regex, Error> {
+		let settings = self.read()?;
+		compile_artist_image_pattern(&settings.artist_image_pattern)
+	}
+
+	/// Returns how many entries a paginated endpoint should return when asked for `count` of them.
+	/// The default page size applies when no count is given, and no page exceeds the maximum size.
+	pub fn get_page_size(&self, count: Option<usize>) -> Result<usize, Error> {
 		let settings = self.read()?;
-		let regex = Regex::new(&format!("(?i){}", &settings.index_album_art_pattern))
-			.map_err(|_| Error::IndexAlbumArtPatternInvalid)?;
-		Ok(regex)
+		let max_page_size = settings.max_page_size.max(1) as usize;
+		let default_page_size = settings.default_page_size.max(1) as usize;
+		Ok(count.unwrap_or(default_page_size).min(max_page_size))
+	}
+
+	/// Returns the genre alias map to apply while indexing, or `None` when genre normalization is disabled.
+	#[cfg(test)]
+	pub fn get_genre_aliases(&self) -> Result<Option<HashMap<String, String>>, Error> {
+		let settings = self.read()?;
+		Ok(settings.normalize_genres.then_some(settings.genre_aliases))
 	}
 
 	pub fn read(&self) -> Result<Settings, Error> {
 		use self::misc_settings::dsl::*;
 		let mut connection = self.db.connect()?;
 
-		let settings: Settings = misc_settings
-			.select((index_sleep_duration_seconds, index_album_art_pattern))
+		let row: SettingsRow = misc_settings
+			.select((
+				index_sleep_duration_seconds,
+				index_album_art_pattern,
+				auto_rename_duplicate_mounts,
+				normalize_genres,
+				genre_aliases,
+				fallback_artist,
+				fallback_album,
+				track_bandwidth,
+				bandwidth_rollover,
+				scan_on_startup,
+				album_artist_grouping,
+				snapshot_index_updates,
+				expose_extra_tags,
+				index_max_depth,
+				public_base_url,
+				min_file_size_bytes,
+				artwork_placeholder_path,
+				skip_hidden_files,
+				artist_image_pattern,
+				merge_duplicate_albums,
+				preferred_album_format,
+				tag_priority,
+				estimate_missing_durations,
+				album_artist_fallback,
+				default_page_size,
+				max_page_size,
+				artwork_priority,
+				normalize_artist_names,
+				artist_name_articles,
+				directory_thumbnail,
+				scan_failure_limit,
+				analyze_loudness,
+				symlinked_duplicates,
+				max_cover_bytes,
+				audiobook_directories,
+				podcast_directories,
+				hide_empty_directories,
+				prewarm_thumbnails_on_scan,
+				metadata_readers,
+				mount_order,
+				reindex_on_config_change,
+			))
 			.get_result(&mut connection)
 			.map_err(|e| match e {
 				diesel::result::Error::NotFound => Error::MiscSettingsNotFound,
 				e => e.into(),
 			})?;
 
-		Ok(settings)
+		Ok(Settings {
+			index_sleep_duration_seconds: row.index_sleep_duration_seconds,
+			index_album_art_pattern: row.index_album_art_pattern,
+			auto_rename_duplicate_mounts: row.auto_rename_duplicate_mounts,
+			normalize_genres: row.normalize_genres,
+			genre_aliases: serde_json::from_str(&row.genre_aliases)?,
+			fallback_artist: row.fallback_artist,
+			fallback_album: row.fallback_album,
+			track_bandwidth: row.track_bandwidth,
+			bandwidth_rollover: row.bandwidth_rollover.parse()?,
+			scan_on_startup: row.scan_on_startup,
+			album_artist_grouping: row.album_artist_grouping.parse()?,
+			snapshot_index_updates: row.snapshot_index_updates,
+			expose_extra_tags: row.expose_extra_tags,
+			index_max_depth: row.index_max_depth,
+			public_base_url: row.public_base_url,
+			min_file_size_bytes: row.min_file_size_bytes,
+			artwork_placeholder_path: row.artwork_placeholder_path,
+			skip_hidden_files: row.skip_hidden_files,
+			artist_image_pattern: row.artist_image_pattern,
+			merge_duplicate_albums: row.merge_duplicate_albums,
+			preferred_album_format: row.preferred_album_format,
+			tag_priority: parse_tag_priority(&row.tag_priority)?,
+			estimate_missing_durations: row.estimate_missing_durations,
+			album_artist_fallback: row.album_artist_fallback,
+			default_page_size: row.default_page_size,
+			max_page_size: row.max_page_size,
+			artwork_priority: row.artwork_priority.parse()?,
+			normalize_artist_names: row.normalize_artist_names,
+			artist_name_articles: parse_name_list(&row.artist_name_articles),
+			directory_thumbnail: row.directory_thumbnail.parse()?,
+			scan_failure_limit: row.scan_failure_limit,
+			analyze_loudness: row.analyze_loudness,
+			symlinked_duplicates: row.symlinked_duplicates.parse()?,
+			max_cover_bytes: row.max_cover_bytes,
+			audiobook_directories: parse_name_list(&row.audiobook_directories),
+			podcast_directories: parse_name_list(&row.podcast_directories),
+			hide_empty_directories: row.hide_empty_directories,
+			prewarm_thumbnails_on_scan: row.prewarm_thumbnails_on_scan,
+			metadata_readers: parse_metadata_readers(&row.metadata_readers)?,
+			mount_order: row.mount_order.parse()?,
+			reindex_on_config_change: row.reindex_on_config_change,
+		})
 	}
 
 	pub fn amend(&self, new_settings: &NewSettings) -> Result<(), Error> {
@@ -109,6 +745,329 @@ impl Manager {
 				.execute(&mut connection)?;
 		}
 
+		if let Some(auto_rename) = new_settings.auto_rename_duplicate_mounts {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::auto_rename_duplicate_mounts.eq(auto_rename))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(normalize) = new_settings.normalize_genres {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::normalize_genres.eq(normalize))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref aliases) = new_settings.genre_aliases {
+			let aliases = serde_json::to_string(aliases)?;
+			diesel::update(misc_settings::table)
+				.set(misc_settings::genre_aliases.eq(aliases))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref artist) = new_settings.fallback_artist {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::fallback_artist.eq(artist))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref album) = new_settings.fallback_album {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::fallback_album.eq(album))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(track) = new_settings.track_bandwidth {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::track_bandwidth.eq(track))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(rollover) = new_settings.bandwidth_rollover {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::bandwidth_rollover.eq(rollover.as_str()))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(scan) = new_settings.scan_on_startup {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::scan_on_startup.eq(scan))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(grouping) = new_settings.album_artist_grouping {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::album_artist_grouping.eq(grouping.as_str()))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(snapshot) = new_settings.snapshot_index_updates {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::snapshot_index_updates.eq(snapshot))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(expose) = new_settings.expose_extra_tags {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::expose_extra_tags.eq(expose))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(max_depth) = new_settings.index_max_depth {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::index_max_depth.eq(max_depth))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref url) = new_settings.public_base_url {
+			parse_public_base_url(url)?;
+			diesel::update(misc_settings::table)
+				.set(misc_settings::public_base_url.eq(url))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(min_size) = new_settings.min_file_size_bytes {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::min_file_size_bytes.eq(min_size))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref placeholder_path) = new_settings.artwork_placeholder_path {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::artwork_placeholder_path.eq(placeholder_path))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(skip) = new_settings.skip_hidden_files {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::skip_hidden_files.eq(skip))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref pattern) = new_settings.artist_image_pattern {
+			compile_artist_image_pattern(pattern)?;
+			diesel::update(misc_settings::table)
+				.set(misc_settings::artist_image_pattern.eq(pattern))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(merge) = new_settings.merge_duplicate_albums {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::merge_duplicate_albums.eq(merge))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref format) = new_settings.preferred_album_format {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::preferred_album_format.eq(format))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref priority) = new_settings.tag_priority {
+			if priority.is_empty() {
+				return Err(Error::TagPriorityEmpty);
+			}
+			let priority: Vec<&str> = priority.iter().map(|c| c.as_str()).collect();
+			diesel::update(misc_settings::table)
+				.set(misc_settings::tag_priority.eq(priority.join(",")))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(estimate) = new_settings.estimate_missing_durations {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::estimate_missing_durations.eq(estimate))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(fallback) = new_settings.album_artist_fallback {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::album_artist_fallback.eq(fallback))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(size) = new_settings.default_page_size {
+			if size < 1 {
+				return Err(Error::PageSizeInvalid(size));
+			}
+			diesel::update(misc_settings::table)
+				.set(misc_settings::default_page_size.eq(size))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(size) = new_settings.max_page_size {
+			if size < 1 {
+				return Err(Error::PageSizeInvalid(size));
+			}
+			diesel::update(misc_settings::table)
+				.set(misc_settings::max_page_size.eq(size))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(priority) = new_settings.artwork_priority {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::artwork_priority.eq(priority.as_str()))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(normalize) = new_settings.normalize_artist_names {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::normalize_artist_names.eq(normalize))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref articles) = new_settings.artist_name_articles {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::artist_name_articles.eq(join_name_list(articles)))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(mode) = new_settings.directory_thumbnail {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::directory_thumbnail.eq(mode.as_str()))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(limit) = new_settings.scan_failure_limit {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::scan_failure_limit.eq(limit))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(analyze) = new_settings.analyze_loudness {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::analyze_loudness.eq(analyze))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(mode) = new_settings.symlinked_duplicates {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::symlinked_duplicates.eq(mode.as_str()))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(max_bytes) = new_settings.max_cover_bytes {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::max_cover_bytes.eq(max_bytes))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref directories) = new_settings.audiobook_directories {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::audiobook_directories.eq(join_name_list(directories)))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref directories) = new_settings.podcast_directories {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::podcast_directories.eq(join_name_list(directories)))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(hide) = new_settings.hide_empty_directories {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::hide_empty_directories.eq(hide))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(prewarm) = new_settings.prewarm_thumbnails_on_scan {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::prewarm_thumbnails_on_scan.eq(prewarm))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(ref readers) = new_settings.metadata_readers {
+			let readers: HashMap<String, &str> = readers
+				.iter()
+				.map(|(extension, reader)| (normalize_extension(extension), reader.as_str()))
+				.collect();
+			let readers = serde_json::to_string(&readers)?;
+			diesel::update(misc_settings::table)
+				.set(misc_settings::metadata_readers.eq(readers))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(order) = new_settings.mount_order {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::mount_order.eq(order.as_str()))
+				.execute(&mut connection)?;
+		}
+
+		if let Some(reindex) = new_settings.reindex_on_config_change {
+			diesel::update(misc_settings::table)
+				.set(misc_settings::reindex_on_config_change.eq(reindex))
+				.execute(&mut connection)?;
+		}
+
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	#[test]
+	fn auth_secret_is_generated_and_persisted() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+
+		{
+			use self::misc_settings::dsl::*;
+			let mut connection = ctx.db.connect().unwrap();
+			diesel::update(misc_settings)
+				.set(auth_secret.eq(Vec::<u8>::new()))
+				.execute(&mut connection)
+				.unwrap();
+		}
+
+		let generated = ctx.settings_manager.get_auth_secret().unwrap();
+		let reused = ctx.settings_manager.get_auth_secret().unwrap();
+		assert_ne!(generated.key, [0; 32]);
+		assert_eq!(generated.key, reused.key);
+	}
+
+	#[test]
+	fn can_parse_auth_secret_from_base64() {
+		let encoded = BASE64_STANDARD.encode([7u8; 32]);
+		assert_eq!(AuthSecret::from_base64(&encoded).unwrap().key, [7u8; 32]);
+		assert!(AuthSecret::from_base64("not base64!").is_err());
+		assert!(AuthSecret::from_base64(&BASE64_STANDARD.encode([7u8; 16])).is_err());
+	}
+
+	#[test]
+	fn genre_normalization_is_disabled_by_default() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		assert_eq!(ctx.settings_manager.get_genre_aliases().unwrap(), None);
+	}
+
+	#[test]
+	fn can_amend_genre_aliases() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		let aliases = HashMap::from([("hiphop".to_owned(), "Hip-Hop".to_owned())]);
+		ctx.settings_manager
+			.amend(&NewSettings {
+				normalize_genres: Some(true),
+				genre_aliases: Some(aliases.clone()),
+				..Default::default()
+			})
+			.unwrap();
+		assert_eq!(
+			ctx.settings_manager.get_genre_aliases().unwrap(),
+			Some(aliases)
+		);
+	}
+
+	#[test]
+	fn public_base_url_must_be_absolute_http_url() {
+		assert!(parse_public_base_url("").unwrap().is_none());
+		assert!(parse_public_base_url("https://music.example.com/polaris")
+			.unwrap()
+			.is_some());
+		assert!(parse_public_base_url("http://192.168.1.2:5050")
+			.unwrap()
+			.is_some());
+		assert!(parse_public_base_url("music.example.com").is_err());
+		assert!(parse_public_base_url("ftp://music.example.com").is_err());
+		assert!(parse_public_base_url("https://").is_err());
+	}
+}
@@ -0,0 +1,363 @@
+use diesel::prelude::*;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::path::{Component, Path, PathBuf};
+
+use crate::app::vfs;
+use crate::db::{self, shares, DB};
+use crate::utils::now;
+
+const TOKEN_LENGTH: usize = 32;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error(transparent)]
+	Database(#[from] diesel::result::Error),
+	#[error(transparent)]
+	DatabaseConnection(#[from] db::Error),
+	#[error("Share link expiry must be in the future")]
+	InvalidExpiry,
+	#[error("Path was not found: `{0}`")]
+	PathNotFound(PathBuf),
+	#[error("Path is outside of the shared location: `{0}`")]
+	PathOutsideShare(PathBuf),
+	#[error("Share link has expired")]
+	ShareExpired,
+	#[error("Share link has reached its download limit")]
+	ShareDownloadLimitReached,
+	#[error("Share link not found")]
+	ShareNotFound,
+	#[error(transparent)]
+	Vfs(#[from] vfs::Error),
+}
+
+/// Read-only access to a location within the collection, granted to anyone holding its token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Share {
+	pub token: String,
+	/// Virtual path of the shared directory or song.
+	pub path: String,
+	/// Seconds since the UNIX epoch.
+	pub created_at: i64,
+	/// Seconds since the UNIX epoch.
+	pub expires_at: i64,
+	pub max_downloads: Option<u32>,
+	pub download_count: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NewShare {
+	pub path: String,
+	/// Seconds since the UNIX epoch.
+	pub expires_at: i64,
+	pub max_downloads: Option<u32>,
+}
+
+#[derive(Queryable)]
+struct ShareRow {
+	token: String,
+	path: String,
+	created_at: i64,
+	expires_at: i64,
+	max_downloads: Option<i32>,
+	download_count: i32,
+}
+
+impl From<ShareRow> for Share {
+	fn from(row: ShareRow) -> Self {
+		Self {
+			token: row.token,
+			path: row.path,
+			created_at: row.created_at,
+			expires_at: row.expires_at,
+			max_downloads: row.max_downloads.map(|m| m.max(0) as u32),
+			download_count: row.download_count.max(0) as u32,
+		}
+	}
+}
+
+#[derive(Clone)]
+pub struct Manager {
+	db: DB,
+	vfs_manager: vfs::Manager,
+}
+
+impl Manager {
+	pub fn new(db: DB, vfs_manager: vfs::Manager) -> Self {
+		Self { db, vfs_manager }
+	}
+
+	pub fn create(&self, new_share: &NewShare) -> Result<Share, Error> {
+		if new_share.expires_at <= now() {
+			return Err(Error::InvalidExpiry);
+		}
+
+		let virtual_path = Path::new(&new_share.path);
+		if !is_normal_path(virtual_path) {
+			return Err(Error::PathNotFound(virtual_path.to_owned()));
+		}
+		let real_path = self.vfs_manager.get_vfs()?.virtual_to_real(virtual_path)?;
+		if !real_path.exists() {
+			return Err(Error::PathNotFound(virtual_path.to_owned()));
+		}
+
+		let share = Share {
+			token: make_token(),
+			path: new_share.path.clone(),
+			created_at: now(),
+			expires_at: new_share.expires_at,
+			max_downloads: new_share.max_downloads,
+			download_count: 0,
+		};
+
+		let mut connection = self.db.connect()?;
+		diesel::insert_into(shares::table)
+			.values((
+				shares::token.eq(&share.token),
+				shares::path.eq(&share.path),
+				shares::created_at.eq(share.created_at),
+				shares::expires_at.eq(share.expires_at),
+				shares::max_downloads.eq(share.max_downloads.map(|m| m as i32)),
+				shares::download_count.eq(0),
+			))
+			.execute(&mut connection)?;
+
+		Ok(share)
+	}
+
+	/// Lists all share links, most recently created first.
+	pub fn list(&self) -> Result<Vec<Share>, Error> {
+		let mut connection = self.db.connect()?;
+		let rows: Vec<ShareRow> = shares::table
+			.select((
+				shares::token,
+				shares::path,
+				shares::created_at,
+				shares::expires_at,
+				shares::max_downloads,
+				shares::download_count,
+			))
+			.order((shares::created_at.desc(), shares::id.desc()))
+			.load(&mut connection)?;
+		Ok(rows.into_iter().map(Share::from).collect())
+	}
+
+	pub fn delete(&self, token: &str) -> Result<(), Error> {
+		let mut connection = self.db.connect()?;
+		let deleted = diesel::delete(shares::table.filter(shares::token.eq(token)))
+			.execute(&mut connection)?;
+		match deleted {
+			0 => Err(Error::ShareNotFound),
+			_ => Ok(()),
+		}
+	}
+
+	/// Returns the share link with the given token, unless it has expired.
+	/// Download limits are only enforced by `record_download`, so that interrupted downloads can be resumed.
+	pub fn get(&self, token: &str) -> Result<Share, Error> {
+		let mut connection = self.db.connect()?;
+		let row: ShareRow = shares::table
+			.select((
+				shares::token,
+				shares::path,
+				shares::created_at,
+				shares::expires_at,
+				shares::max_downloads,
+				shares::download_count,
+			))
+			.filter(shares::token.eq(token))
+			.get_result(&mut connection)
+			.optional()?
+			.ok_or(Error::ShareNotFound)?;
+		let share = Share::from(row);
+
+		if share.expires_at <= now() {
+			return Err(Error::ShareExpired);
+		}
+		Ok(share)
+	}
+
+	/// Checks that a virtual path lies within the location shared by a share link.
+	pub fn authorize<P: AsRef<Path>>(&self, token: &str, virtual_path: P) -> Result<Share, Error> {
+		let share = self.get(token)?;
+		let virtual_path = virtual_path.as_ref();
+		if !is_normal_path(virtual_path) || !virtual_path.starts_with(&share.path) {
+			return Err(Error::PathOutsideShare(virtual_path.to_owned()));
+		}
+		Ok(share)
+	}
+
+	/// Counts a download against the limit of a share link.
+	pub fn record_download(&self, token: &str) -> Result<(), Error> {
+		let mut connection = self.db.connect()?;
+		let updated = diesel::update(
+			shares::table.filter(shares::token.eq(token)).filter(
+				shares::max_downloads
+					.is_null()
+					.or(shares::max_downloads.gt(shares::download_count.nullable())),
+			),
+		)
+		.set(shares::download_count.eq(shares::download_count + 1))
+		.execute(&mut connection)?;
+		match updated {
+			0 => Err(Error::ShareDownloadLimitReached),
+			_ => Ok(()),
+		}
+	}
+}
+
+/// Rejects paths which could step outside of a shared location, like `a/../b`.
+fn is_normal_path(path: &Path) -> bool {
+	path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+fn make_token() -> String {
+	rand::thread_rng()
+		.sample_iter(&Alphanumeric)
+		.take(TOKEN_LENGTH)
+		.map(char::from)
+		.collect()
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_MOUNT_NAME: &str = "root";
+	const ONE_DAY: i64 = 24 * 60 * 60;
+
+	fn make_context(test_name: String) -> test::Context {
+		test::ContextBuilder::new(test_name)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build()
+	}
+
+	fn hunted_path() -> String {
+		[TEST_MOUNT_NAME, "Khemmis", "Hunted"]
+			.iter()
+			.collect::<PathBuf>()
+			.to_string_lossy()
+			.into_owned()
+	}
+
+	#[test]
+	fn can_create_and_list_shares() {
+		let ctx = make_context(test_name!());
+		let share = ctx
+			.share_manager
+			.create(&NewShare {
+				path: hunted_path(),
+				expires_at: now() + ONE_DAY,
+				max_downloads: Some(3),
+			})
+			.unwrap();
+		assert_eq!(share.token.len(), TOKEN_LENGTH);
+		assert_eq!(ctx.share_manager.list().unwrap(), vec![share.clone()]);
+
+		ctx.share_manager.delete(&share.token).unwrap();
+		assert!(ctx.share_manager.list().unwrap().is_empty());
+		assert!(matches!(
+			ctx.share_manager.delete(&share.token),
+			Err(Error::ShareNotFound)
+		));
+	}
+
+	#[test]
+	fn cannot_share_missing_path_or_already_expired() {
+		let ctx = make_context(test_name!());
+		let missing_path: PathBuf = [TEST_MOUNT_NAME, "Nothing"].iter().collect();
+		assert!(matches!(
+			ctx.share_manager.create(&NewShare {
+				path: missing_path.to_string_lossy().into_owned(),
+				expires_at: now() + ONE_DAY,
+				max_downloads: None,
+			}),
+			Err(Error::PathNotFound(_))
+		));
+		assert!(matches!(
+			ctx.share_manager.create(&NewShare {
+				path: hunted_path(),
+				expires_at: now() - 1,
+				max_downloads: None,
+			}),
+			Err(Error::InvalidExpiry)
+		));
+	}
+
+	#[test]
+	fn paths_outside_share_are_rejected() {
+		let ctx = make_context(test_name!());
+		let share = ctx
+			.share_manager
+			.create(&NewShare {
+				path: hunted_path(),
+				expires_at: now() + ONE_DAY,
+				max_downloads: None,
+			})
+			.unwrap();
+
+		let song_path = Path::new(&share.path).join("05 - Hunted.mp3");
+		assert!(ctx
+			.share_manager
+			.authorize(&share.token, &song_path)
+			.is_ok());
+
+		let escaping_path = Path::new(&share.path).join("..").join("..");
+		let other_path: PathBuf = [TEST_MOUNT_NAME, "Tobokegao"].iter().collect();
+		for path in [escaping_path, other_path] {
+			assert!(matches!(
+				ctx.share_manager.authorize(&share.token, &path),
+				Err(Error::PathOutsideShare(_))
+			));
+		}
+	}
+
+	#[test]
+	fn expired_shares_are_denied() {
+		let ctx = make_context(test_name!());
+		let share = ctx
+			.share_manager
+			.create(&NewShare {
+				path: hunted_path(),
+				expires_at: now() + ONE_DAY,
+				max_downloads: None,
+			})
+			.unwrap();
+		{
+			let mut connection = ctx.db.connect().unwrap();
+			diesel::update(shares::table)
+				.set(shares::expires_at.eq(now() - 1))
+				.execute(&mut connection)
+				.unwrap();
+		}
+		assert!(matches!(
+			ctx.share_manager.authorize(&share.token, &share.path),
+			Err(Error::ShareExpired)
+		));
+	}
+
+	#[test]
+	fn downloads_are_limited() {
+		let ctx = make_context(test_name!());
+		let share = ctx
+			.share_manager
+			.create(&NewShare {
+				path: hunted_path(),
+				expires_at: now() + ONE_DAY,
+				max_downloads: Some(2),
+			})
+			.unwrap();
+		ctx.share_manager.record_download(&share.token).unwrap();
+		ctx.share_manager.record_download(&share.token).unwrap();
+		assert!(matches!(
+			ctx.share_manager.record_download(&share.token),
+			Err(Error::ShareDownloadLimitReached)
+		));
+		assert_eq!(
+			ctx.share_manager.get(&share.token).unwrap().download_count,
+			2
+		);
+	}
+}
@@ -1,17 +1,32 @@
-use std::path::PathBuf;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::UNIX_EPOCH;
 
-use crate::app::{config, ddns, index::Index, lastfm, playlist, settings, thumbnail, user, vfs};
+use crate::app::vfs::storage::{self, Backend, Entry};
+use crate::app::{
+	archive, bandwidth, config, cover, ddns, index::Index, lastfm, play_count, playlist, progress,
+	settings, share, thumbnail, user, vfs,
+};
 use crate::db::DB;
 use crate::test::*;
 
 pub struct Context {
 	pub db: DB,
 	pub index: Index,
+	pub archive_manager: archive::Manager,
+	pub bandwidth_manager: bandwidth::Manager,
 	pub config_manager: config::Manager,
+	pub cover_manager: cover::Manager,
 	pub ddns_manager: ddns::Manager,
 	pub lastfm_manager: lastfm::Manager,
+	pub play_count_manager: play_count::Manager,
 	pub playlist_manager: playlist::Manager,
+	pub progress_manager: progress::Manager,
 	pub settings_manager: settings::Manager,
+	pub share_manager: share::Manager,
 	pub thumbnail_manager: thumbnail::Manager,
 	pub user_manager: user::Manager,
 	pub vfs_manager: vfs::Manager,
@@ -50,6 +65,23 @@ impl ContextBuilder {
 			.push(vfs::MountDir {
 				name: name.to_owned(),
 				source: source.to_owned(),
+				album_art_pattern: None,
+				reindex_every_n_seconds: None,
+				hide_from_root: false,
+			});
+		self
+	}
+
+	pub fn mount_with_album_art_pattern(mut self, name: &str, source: &str, pattern: &str) -> Self {
+		self.config
+			.mount_dirs
+			.get_or_insert(Vec::new())
+			.push(vfs::MountDir {
+				name: name.to_owned(),
+				source: source.to_owned(),
+				album_art_pattern: Some(pattern.to_owned()),
+				reindex_every_n_seconds: None,
+				hide_from_root: false,
 			});
 		self
 	}
@@ -64,27 +96,50 @@ impl ContextBuilder {
 		let user_manager = user::Manager::new(db.clone(), auth_secret);
 		let vfs_manager = vfs::Manager::new(db.clone());
 		let ddns_manager = ddns::Manager::new(db.clone());
+		let thumbnail_manager = thumbnail::Manager::new(cache_output_dir);
+		let index = Index::new(
+			db.clone(),
+			vfs_manager.clone(),
+			settings_manager.clone(),
+			thumbnail_manager.clone(),
+		);
 		let config_manager = config::Manager::new(
 			settings_manager.clone(),
 			user_manager.clone(),
 			vfs_manager.clone(),
 			ddns_manager.clone(),
+			index.clone(),
 		);
-		let index = Index::new(db.clone(), vfs_manager.clone(), settings_manager.clone());
+		let archive_manager = archive::Manager::new(index.clone(), vfs_manager.clone());
+		let bandwidth_manager = bandwidth::Manager::new(db.clone(), settings_manager.clone());
+		let play_count_manager = play_count::Manager::new(db.clone(), vfs_manager.clone());
 		let playlist_manager = playlist::Manager::new(db.clone(), vfs_manager.clone());
-		let thumbnail_manager = thumbnail::Manager::new(cache_output_dir);
+		let progress_manager = progress::Manager::new(db.clone(), vfs_manager.clone());
+		let share_manager = share::Manager::new(db.clone(), vfs_manager.clone());
+		let cover_manager = cover::Manager::new(
+			db.clone(),
+			vfs_manager.clone(),
+			settings_manager.clone(),
+			self.test_directory.join("covers"),
+		);
 		let lastfm_manager = lastfm::Manager::new(index.clone(), user_manager.clone());
 
-		config_manager.apply(&self.config).unwrap();
+		config_manager.apply_without_reindex(&self.config).unwrap();
 
 		Context {
 			db,
 			index,
+			archive_manager,
+			bandwidth_manager,
 			config_manager,
+			cover_manager,
 			ddns_manager,
 			lastfm_manager,
+			play_count_manager,
 			playlist_manager,
+			progress_manager,
 			settings_manager,
+			share_manager,
 			thumbnail_manager,
 			user_manager,
 			vfs_manager,
@@ -92,3 +147,82 @@ impl ContextBuilder {
 		}
 	}
 }
+
+/// Storage backend serving the content of a local directory, as if it were a remote.
+pub struct MockBackend {
+	root: PathBuf,
+	/// Number of `read_range` calls received.
+	pub num_reads: AtomicUsize,
+	/// Directory whose listing does not complete until `release_listing` is called.
+	held_listing: (Mutex<Option<PathBuf>>, Condvar),
+}
+
+impl MockBackend {
+	pub fn new<P: AsRef<Path>>(root: P) -> Arc<Self> {
+		Arc::new(Self {
+			root: root.as_ref().to_owned(),
+			num_reads: AtomicUsize::new(0),
+			held_listing: (Mutex::new(None), Condvar::new()),
+		})
+	}
+
+	/// Makes listings of a directory wait, as if it took long to browse.
+	pub fn hold_listing<P: AsRef<Path>>(&self, path: P) {
+		*self.held_listing.0.lock().unwrap() = Some(path.as_ref().to_owned());
+	}
+
+	pub fn release_listing(&self) {
+		let (lock, cvar) = &self.held_listing;
+		*lock.lock().unwrap() = None;
+		cvar.notify_all();
+	}
+
+	/// Factory for `vfs::Manager::register_backend`, always serving this backend.
+	pub fn factory(self: &Arc<Self>) -> storage::BackendFactory {
+		let backend = self.clone();
+		Arc::new(move |_: &str| Ok(backend.clone() as Arc<dyn Backend>))
+	}
+}
+
+impl Backend for MockBackend {
+	fn list(&self, path: &Path) -> Result<Vec<Entry>, storage::Error> {
+		let (lock, cvar) = &self.held_listing;
+		let mut held_listing = lock.lock().unwrap();
+		while held_listing.as_deref() == Some(path) {
+			held_listing = cvar.wait(held_listing).unwrap();
+		}
+		drop(held_listing);
+
+		let mut entries = Vec::new();
+		for entry in fs::read_dir(self.root.join(path))? {
+			let entry = entry?;
+			let metadata = entry.metadata()?;
+			let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap();
+			entries.push(Entry {
+				path: path.join(entry.file_name()),
+				is_dir: metadata.is_dir(),
+				size: if metadata.is_dir() { 0 } else { metadata.len() },
+				etag: (!metadata.is_dir())
+					.then(|| format!("{:x}-{:x}", modified.as_nanos(), metadata.len())),
+			});
+		}
+		Ok(entries)
+	}
+
+	fn exists(&self, path: &Path) -> Result<bool, storage::Error> {
+		Ok(self.root.join(path).exists())
+	}
+
+	fn size(&self, path: &Path) -> Result<u64, storage::Error> {
+		Ok(fs::metadata(self.root.join(path))?.len())
+	}
+
+	fn read_range(&self, path: &Path, offset: u64, length: u64) -> Result<Vec<u8>, storage::Error> {
+		self.num_reads.fetch_add(1, Ordering::SeqCst);
+		let mut file = fs::File::open(self.root.join(path))?;
+		file.seek(SeekFrom::Start(offset))?;
+		let mut bytes = Vec::new();
+		file.take(length).read_to_end(&mut bytes)?;
+		Ok(bytes)
+	}
+}
@@ -1,14 +1,30 @@
-use image::{DynamicImage, GenericImage, GenericImageView, ImageBuffer, ImageOutputFormat};
+use image::codecs::jpeg::JpegDecoder;
+use image::imageops::FilterType;
+use image::{
+	DynamicImage, GenericImage, GenericImageView, ImageBuffer, ImageFormat, ImageOutputFormat,
+};
+use log::warn;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::cmp;
 use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
 use std::fs::{self, File};
 use std::hash::{Hash, Hasher};
+use std::io::Cursor;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 
 use crate::utils::{get_audio_format, AudioFormat};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
+	#[error("No artwork file was found at `{0}`")]
+	ArtworkNotFound(PathBuf),
+	#[error("Artwork in `{0}` is too large to decode ({1} bytes)")]
+	ArtworkTooLarge(PathBuf, usize),
 	#[error("No embedded artwork was found in `{0}`")]
 	EmbeddedArtworkNotFound(PathBuf),
 	#[error("Could not read thumbnail from ID3 tag in `{0}`:\n\n{1}")]
@@ -21,63 +37,587 @@ pub enum Error {
 	Metaflac(PathBuf, metaflac::Error),
 	#[error("Could not read thumbnail from mp4 file in `{0}`:\n\n{1}")]
 	Mp4aMeta(PathBuf, mp4ameta::Error),
+	#[error("Thumbnails are already being prewarmed")]
+	PrewarmInProgress,
 	#[error("This file format is not supported: {0}")]
 	UnsupportedFormat(&'static str),
 }
 
-#[derive(Debug, Hash)]
+/// Interpolation filters which can be used to resize artwork, from fastest to highest quality.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Filter {
+	Nearest,
+	Triangle,
+	CatmullRom,
+	Lanczos3,
+}
+
+impl From<Filter> for FilterType {
+	fn from(filter: Filter) -> Self {
+		match filter {
+			Filter::Nearest => FilterType::Nearest,
+			Filter::Triangle => FilterType::Triangle,
+			Filter::CatmullRom => FilterType::CatmullRom,
+			Filter::Lanczos3 => FilterType::Lanczos3,
+		}
+	}
+}
+
+/// Ways to fit artwork which is not square into a square thumbnail.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Fit {
+	/// Scales the whole artwork into the square and fills the remaining space.
+	Pad,
+	/// Scales the artwork to cover the square and cuts off what overflows around its center.
+	Crop,
+	/// Scales the artwork to the dimensions of the square, distorting it.
+	Stretch,
+}
+
+/// Roles of embedded pictures, as defined by ID3 and FLAC picture blocks.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum PictureType {
+	Front,
+	Back,
+	Leaflet,
+	Media,
+	Artist,
+}
+
+impl PictureType {
+	fn matches_id3(&self, picture_type: id3::frame::PictureType) -> bool {
+		use id3::frame::PictureType as Id3PictureType;
+		matches!(
+			(self, picture_type),
+			(Self::Front, Id3PictureType::CoverFront)
+				| (Self::Back, Id3PictureType::CoverBack)
+				| (Self::Leaflet, Id3PictureType::Leaflet)
+				| (Self::Media, Id3PictureType::Media)
+				| (Self::Artist, Id3PictureType::LeadArtist)
+		)
+	}
+
+	fn matches_flac(&self, picture_type: metaflac::block::PictureType) -> bool {
+		use metaflac::block::PictureType as FlacPictureType;
+		matches!(
+			(self, picture_type),
+			(Self::Front, FlacPictureType::CoverFront)
+				| (Self::Back, FlacPictureType::CoverBack)
+				| (Self::Leaflet, FlacPictureType::Leaflet)
+				| (Self::Media, FlacPictureType::Media)
+				| (Self::Artist, FlacPictureType::LeadArtist)
+		)
+	}
+}
+
+/// Encodings in which thumbnails can be written.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum Format {
+	#[default]
+	Jpeg,
+	WebP,
+	Png,
+	/// Picks PNG for artwork with transparency or few colors (eg. flat art), and JPEG for photographic artwork.
+	Auto,
+}
+
+/// Opaque artwork using at most this many colors is considered flat art, which PNG stores
+/// without loss in less space than JPEG.
+const FLAT_ART_MAX_COLORS: usize = 256;
+
+impl Format {
+	fn extension(&self) -> &'static str {
+		match self {
+			Self::Jpeg | Self::Auto => "jpg",
+			Self::WebP => "webp",
+			Self::Png => "png",
+		}
+	}
+
+	fn output_format(&self, quality: u8) -> ImageOutputFormat {
+		match self {
+			Self::Jpeg | Self::Auto => ImageOutputFormat::Jpeg(quality),
+			Self::WebP => ImageOutputFormat::WebP,
+			Self::Png => ImageOutputFormat::Png,
+		}
+	}
+
+	fn preserves_alpha(&self) -> bool {
+		matches!(self, Self::Png | Self::Auto)
+	}
+
+	/// Formats a thumbnail requested in this format may end up written in.
+	fn candidates(&self) -> &'static [Format] {
+		match self {
+			Self::Jpeg => &[Self::Jpeg],
+			Self::WebP => &[Self::WebP],
+			Self::Png => &[Self::Png],
+			Self::Auto => &[Self::Jpeg, Self::Png],
+		}
+	}
+
+	/// Resolves the automatic format into the one best suited to a given thumbnail.
+	fn resolve(&self, image: &DynamicImage) -> Format {
+		if *self != Self::Auto {
+			return *self;
+		}
+		if has_transparency(image) {
+			return Self::Png;
+		}
+		let mut colors = HashSet::new();
+		for pixel in image.to_rgb8().pixels() {
+			colors.insert(pixel.0);
+			if colors.len() > FLAT_ART_MAX_COLORS {
+				return Self::Jpeg;
+			}
+		}
+		Self::Png
+	}
+}
+
+fn has_transparency(image: &DynamicImage) -> bool {
+	image.color().has_alpha() && image.to_rgba8().pixels().any(|p| p[3] < u8::MAX)
+}
+
+#[derive(Clone, Debug, Hash)]
 pub struct Options {
 	pub max_dimension: Option<u32>,
 	pub resize_if_almost_square: bool,
-	pub pad_to_square: bool,
+	/// When unset, thumbnails keep the aspect ratio of their artwork instead of being square.
+	pub fit: Option<Fit>,
+	/// When unset, artwork is resized using fast sampling tuned for thumbnails.
+	pub filter: Option<Filter>,
+	/// Embedded picture to read from audio files. When unset, the front cover is preferred
+	/// but any other embedded picture is used as a fallback.
+	pub picture_type: Option<PictureType>,
+	pub format: Format,
 }
 
 impl Default for Options {
 	fn default() -> Self {
 		Self {
-			max_dimension: Some(400),
+			max_dimension: Some(DEFAULT_MAX_DIMENSION),
 			resize_if_almost_square: true,
-			pad_to_square: true,
+			fit: Some(Fit::Pad),
+			filter: None,
+			picture_type: None,
+			format: Format::default(),
+		}
+	}
+}
+
+impl Options {
+	/// Limits the output dimension, including for thumbnails requested at native size.
+	pub fn with_dimension_cap(mut self, cap: u32) -> Self {
+		self.max_dimension = Some(self.max_dimension.map_or(cap, |d| d.min(cap)));
+		self
+	}
+
+	/// Deprecated alias of `fit`, from when non-square artwork could only be padded: padding
+	/// maps to `Fit::Pad`, while no padding keeps the aspect ratio of the artwork.
+	pub fn with_pad_to_square(mut self, pad_to_square: bool) -> Self {
+		self.fit = pad_to_square.then_some(Fit::Pad);
+		self
+	}
+}
+
+const DEFAULT_MAX_DIMENSION: u32 = 400;
+const DEFAULT_DIMENSION_CAP: u32 = 2000;
+const DEFAULT_MAX_ARTWORK_BYTES: usize = 8 * 1024 * 1024;
+const MAX_COLLAGE_COVERS: usize = 4;
+
+/// Decodes artwork, avoiding full-size decoding of images above a size threshold.
+#[derive(Clone, Copy, Debug)]
+struct ArtworkDecoder {
+	max_bytes: usize,
+	target_dimension: u32,
+}
+
+impl Default for ArtworkDecoder {
+	fn default() -> Self {
+		Self {
+			max_bytes: DEFAULT_MAX_ARTWORK_BYTES,
+			target_dimension: DEFAULT_MAX_DIMENSION,
+		}
+	}
+}
+
+impl ArtworkDecoder {
+	/// Images larger than `max_bytes` are decoded at the smallest scale which still covers
+	/// the target dimension. Formats which do not support decoding at a reduced scale are refused.
+	fn decode(&self, path: &Path, data: &[u8]) -> Result<DynamicImage, Error> {
+		if data.len() <= self.max_bytes {
+			return image::load_from_memory(data).map_err(|e| Error::Image(path.to_owned(), e));
+		}
+
+		let format = image::guess_format(data).map_err(|e| Error::Image(path.to_owned(), e))?;
+		if format != ImageFormat::Jpeg {
+			warn!(
+				"Refusing to decode artwork in `{}` ({} bytes)",
+				path.display(),
+				data.len()
+			);
+			return Err(Error::ArtworkTooLarge(path.to_owned(), data.len()));
+		}
+
+		warn!(
+			"Decoding artwork in `{}` at reduced scale ({} bytes)",
+			path.display(),
+			data.len()
+		);
+		let target_dimension = cmp::min(self.target_dimension, u16::MAX as u32) as u16;
+		let mut decoder =
+			JpegDecoder::new(Cursor::new(data)).map_err(|e| Error::Image(path.to_owned(), e))?;
+		decoder
+			.scale(target_dimension, target_dimension)
+			.map_err(|e| Error::Image(path.to_owned(), e))?;
+		DynamicImage::from_decoder(decoder).map_err(|e| Error::Image(path.to_owned(), e))
+	}
+}
+
+/// Limits how many thumbnails can be generated at the same time.
+struct Semaphore {
+	available_permits: Mutex<usize>,
+	condvar: Condvar,
+}
+
+struct SemaphorePermit<'a> {
+	semaphore: &'a Semaphore,
+}
+
+impl Semaphore {
+	fn new(permits: usize) -> Self {
+		Self {
+			available_permits: Mutex::new(permits.max(1)),
+			condvar: Condvar::new(),
+		}
+	}
+
+	fn acquire(&self) -> SemaphorePermit<'_> {
+		let mut available_permits = self.available_permits.lock().unwrap();
+		while *available_permits == 0 {
+			available_permits = self.condvar.wait(available_permits).unwrap();
 		}
+		*available_permits -= 1;
+		SemaphorePermit { semaphore: self }
 	}
 }
 
+impl Drop for SemaphorePermit<'_> {
+	fn drop(&mut self) {
+		*self.semaphore.available_permits.lock().unwrap() += 1;
+		self.semaphore.condvar.notify_one();
+	}
+}
+
+/// Progress of the latest thumbnail prewarm.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrewarmProgress {
+	pub running: bool,
+	pub cancelled: bool,
+	pub total: usize,
+	pub generated: usize,
+	pub skipped: usize,
+	pub failed: usize,
+}
+
 #[derive(Clone)]
 pub struct Manager {
 	thumbnails_dir_path: PathBuf,
+	generation_permits: Arc<Semaphore>,
+	max_artwork_bytes: usize,
+	dimension_cap: u32,
+	prewarm_progress: Arc<Mutex<PrewarmProgress>>,
+	prewarm_cancelled: Arc<AtomicBool>,
 }
 
 impl Manager {
 	pub fn new(thumbnails_dir_path: PathBuf) -> Self {
+		let key = "POLARIS_MAX_CONCURRENT_THUMBNAILS";
+		let max_concurrent_generations = std::env::var_os(key)
+			.map(|v| v.to_string_lossy().to_string())
+			.and_then(|v| usize::from_str(&v).ok())
+			.unwrap_or_else(num_cpus::get);
+		let key = "POLARIS_MAX_ARTWORK_BYTES";
+		let max_artwork_bytes = std::env::var_os(key)
+			.map(|v| v.to_string_lossy().to_string())
+			.and_then(|v| usize::from_str(&v).ok())
+			.unwrap_or(DEFAULT_MAX_ARTWORK_BYTES);
+		let key = "POLARIS_MAX_THUMBNAIL_DIMENSION";
+		let dimension_cap = std::env::var_os(key)
+			.map(|v| v.to_string_lossy().to_string())
+			.and_then(|v| u32::from_str(&v).ok())
+			.unwrap_or(DEFAULT_DIMENSION_CAP);
+		Self::with_max_concurrent_generations(thumbnails_dir_path, max_concurrent_generations)
+			.with_max_artwork_bytes(max_artwork_bytes)
+			.with_dimension_cap(dimension_cap)
+	}
+
+	pub fn with_max_concurrent_generations(
+		thumbnails_dir_path: PathBuf,
+		max_concurrent_generations: usize,
+	) -> Self {
 		Self {
 			thumbnails_dir_path,
+			generation_permits: Arc::new(Semaphore::new(max_concurrent_generations)),
+			max_artwork_bytes: DEFAULT_MAX_ARTWORK_BYTES,
+			dimension_cap: DEFAULT_DIMENSION_CAP,
+			prewarm_progress: Arc::new(Mutex::new(PrewarmProgress::default())),
+			prewarm_cancelled: Arc::new(AtomicBool::new(false)),
 		}
 	}
 
+	/// Sets the size above which artwork is decoded at reduced scale, or not at all.
+	pub fn with_max_artwork_bytes(mut self, max_artwork_bytes: usize) -> Self {
+		self.max_artwork_bytes = max_artwork_bytes;
+		self
+	}
+
+	/// Sets the largest dimension thumbnails can have, regardless of requested options.
+	pub fn with_dimension_cap(mut self, dimension_cap: u32) -> Self {
+		self.dimension_cap = dimension_cap;
+		self
+	}
+
 	pub fn get_thumbnail(
 		&self,
 		image_path: &Path,
 		thumbnailoptions: &Options,
 	) -> Result<PathBuf, Error> {
+		if !image_path.is_file() {
+			return Err(Error::ArtworkNotFound(image_path.to_owned()));
+		}
+
+		let thumbnailoptions = &thumbnailoptions
+			.clone()
+			.with_dimension_cap(self.dimension_cap);
+		if let Some(path) = self.retrieve_thumbnail(image_path, thumbnailoptions) {
+			return Ok(path);
+		}
+
+		let _permit = self.generation_permits.acquire();
+
+		// The same thumbnail may have been generated while waiting for a permit
 		match self.retrieve_thumbnail(image_path, thumbnailoptions) {
 			Some(path) => Ok(path),
 			None => self.create_thumbnail(image_path, thumbnailoptions),
 		}
 	}
 
-	fn get_thumbnail_path(&self, image_path: &Path, thumbnailoptions: &Options) -> PathBuf {
-		let hash = Manager::hash(image_path, thumbnailoptions);
+	/// Builds a single thumbnail out of several pieces of artwork, laid out in a two by two grid.
+	/// Only the first four images are used, and a single image makes a regular thumbnail.
+	pub fn get_collage(
+		&self,
+		image_paths: &[PathBuf],
+		thumbnailoptions: &Options,
+	) -> Result<PathBuf, Error> {
+		let image_paths = &image_paths[..image_paths.len().min(MAX_COLLAGE_COVERS)];
+		match image_paths {
+			[] => return Err(Error::ArtworkNotFound(PathBuf::new())),
+			[image_path] => return self.get_thumbnail(image_path, thumbnailoptions),
+			_ => (),
+		}
+		if let Some(image_path) = image_paths.iter().find(|p| !p.is_file()) {
+			return Err(Error::ArtworkNotFound(image_path.to_owned()));
+		}
+
+		let thumbnailoptions = &thumbnailoptions
+			.clone()
+			.with_dimension_cap(self.dimension_cap);
+		if let Some(path) = self.retrieve_collage(image_paths, thumbnailoptions) {
+			return Ok(path);
+		}
+
+		let _permit = self.generation_permits.acquire();
+
+		match self.retrieve_collage(image_paths, thumbnailoptions) {
+			Some(path) => Ok(path),
+			None => self.create_collage(image_paths, thumbnailoptions),
+		}
+	}
+
+	/// Same as `get_collage`, but uses a placeholder image when some of the artwork is missing.
+	/// The returned flag indicates whether the thumbnail was made from the placeholder.
+	pub fn get_collage_or_placeholder(
+		&self,
+		image_paths: &[PathBuf],
+		placeholder_path: &Path,
+		thumbnailoptions: &Options,
+	) -> Result<(PathBuf, bool), Error> {
+		let thumbnail = self.get_collage(image_paths, thumbnailoptions);
+		self.or_placeholder(thumbnail, placeholder_path, thumbnailoptions)
+	}
+
+	fn or_placeholder(
+		&self,
+		thumbnail: Result<PathBuf, Error>,
+		placeholder_path: &Path,
+		thumbnailoptions: &Options,
+	) -> Result<(PathBuf, bool), Error> {
+		match thumbnail {
+			Ok(path) => Ok((path, false)),
+			Err(Error::ArtworkNotFound(_) | Error::EmbeddedArtworkNotFound(_)) => {
+				let placeholder_options = Options {
+					picture_type: None,
+					..thumbnailoptions.clone()
+				};
+				self.get_thumbnail(placeholder_path, &placeholder_options)
+					.map(|path| (path, true))
+			}
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Generates missing thumbnails for a list of images, sharing generation permits with
+	/// regular thumbnail requests. Thumbnails which already exist are skipped.
+	pub fn prewarm(
+		&self,
+		image_paths: &[PathBuf],
+		thumbnailoptions: &Options,
+	) -> Result<PrewarmProgress, Error> {
+		{
+			let mut progress = self.prewarm_progress.lock().unwrap();
+			if progress.running {
+				return Err(Error::PrewarmInProgress);
+			}
+			*progress = PrewarmProgress {
+				running: true,
+				total: image_paths.len(),
+				..Default::default()
+			};
+			self.prewarm_cancelled.store(false, Ordering::SeqCst);
+		}
+
+		let thumbnailoptions = &thumbnailoptions
+			.clone()
+			.with_dimension_cap(self.dimension_cap);
+		image_paths.par_iter().for_each(|image_path| {
+			if self.prewarm_cancelled.load(Ordering::SeqCst) {
+				return;
+			}
+			let generated = match self.retrieve_thumbnail(image_path, thumbnailoptions) {
+				Some(_) => Ok(false),
+				None => {
+					let _permit = self.generation_permits.acquire();
+					if self.prewarm_cancelled.load(Ordering::SeqCst) {
+						return;
+					}
+					match self.retrieve_thumbnail(image_path, thumbnailoptions) {
+						Some(_) => Ok(false),
+						None => self
+							.create_thumbnail(image_path, thumbnailoptions)
+							.map(|_| true),
+					}
+				}
+			};
+			let mut progress = self.prewarm_progress.lock().unwrap();
+			match generated {
+				Ok(true) => progress.generated += 1,
+				Ok(false) => progress.skipped += 1,
+				Err(e) => {
+					warn!("Could not prewarm thumbnail: {}", e);
+					progress.failed += 1;
+				}
+			}
+		});
+
+		let mut progress = self.prewarm_progress.lock().unwrap();
+		progress.running = false;
+		progress.cancelled = self.prewarm_cancelled.load(Ordering::SeqCst);
+		Ok(*progress)
+	}
+
+	pub fn prewarm_progress(&self) -> PrewarmProgress {
+		*self.prewarm_progress.lock().unwrap()
+	}
+
+	/// Stops an ongoing prewarm. Thumbnails being generated are finished.
+	pub fn cancel_prewarm(&self) {
+		if self.prewarm_progress.lock().unwrap().running {
+			self.prewarm_cancelled.store(true, Ordering::SeqCst);
+		}
+	}
+
+	/// Thumbnails of a given source and options live in their own directory, and are named after
+	/// the modification time of the source. Editing the source therefore invalidates its thumbnails.
+	fn get_thumbnail_path(
+		&self,
+		image_path: &Path,
+		thumbnailoptions: &Options,
+		format: Format,
+	) -> PathBuf {
+		let source_hash = Manager::hash(image_path, thumbnailoptions);
+		let modified = fs::metadata(image_path).and_then(|m| m.modified()).ok();
+		let mut hasher = DefaultHasher::new();
+		modified.hash(&mut hasher);
 		let mut thumbnail_path = self.thumbnails_dir_path.clone();
-		thumbnail_path.push(format!("{}.jpg", hash));
+		thumbnail_path.push(source_hash.to_string());
+		thumbnail_path.push(format!("{}.{}", hasher.finish(), format.extension()));
 		thumbnail_path
 	}
 
 	fn retrieve_thumbnail(&self, image_path: &Path, thumbnailoptions: &Options) -> Option<PathBuf> {
-		let path = self.get_thumbnail_path(image_path, thumbnailoptions);
-		if path.exists() {
-			Some(path)
-		} else {
-			None
+		thumbnailoptions
+			.format
+			.candidates()
+			.iter()
+			.map(|format| self.get_thumbnail_path(image_path, thumbnailoptions, *format))
+			.find(|path| path.exists())
+	}
+
+	/// Collages are cached like thumbnails, under a directory named after all their sources.
+	/// Editing any of the sources invalidates the collage.
+	fn get_collage_path(
+		&self,
+		image_paths: &[PathBuf],
+		thumbnailoptions: &Options,
+		format: Format,
+	) -> PathBuf {
+		let mut source_hasher = DefaultHasher::new();
+		image_paths.hash(&mut source_hasher);
+		thumbnailoptions.hash(&mut source_hasher);
+		let mut hasher = DefaultHasher::new();
+		for image_path in image_paths {
+			let modified = fs::metadata(image_path).and_then(|m| m.modified()).ok();
+			modified.hash(&mut hasher);
+		}
+		let mut collage_path = self.thumbnails_dir_path.clone();
+		collage_path.push(source_hasher.finish().to_string());
+		collage_path.push(format!("{}.{}", hasher.finish(), format.extension()));
+		collage_path
+	}
+
+	fn retrieve_collage(
+		&self,
+		image_paths: &[PathBuf],
+		thumbnailoptions: &Options,
+	) -> Option<PathBuf> {
+		thumbnailoptions
+			.format
+			.candidates()
+			.iter()
+			.map(|format| self.get_collage_path(image_paths, thumbnailoptions, *format))
+			.find(|path| path.exists())
+	}
+
+	/// Deletes thumbnails generated from previous versions of a source.
+	fn evict_stale_thumbnails(&self, thumbnail_path: &Path) {
+		let entries = match thumbnail_path.parent().map(fs::read_dir) {
+			Some(Ok(entries)) => entries,
+			_ => return,
+		};
+		for entry in entries.flatten() {
+			let path = entry.path();
+			if path == thumbnail_path {
+				continue;
+			}
+			if let Err(e) = fs::remove_file(&path) {
+				warn!(
+					"Could not delete stale thumbnail `{}`: {}",
+					path.display(),
+					e
+				);
+			}
 		}
 	}
 
@@ -86,17 +626,92 @@ impl Manager {
 		image_path: &Path,
 		thumbnailoptions: &Options,
 	) -> Result<PathBuf, Error> {
-		let thumbnail = generate_thumbnail(image_path, thumbnailoptions)?;
+		let decoder = ArtworkDecoder {
+			max_bytes: self.max_artwork_bytes,
+			target_dimension: thumbnailoptions
+				.max_dimension
+				.unwrap_or(DEFAULT_MAX_DIMENSION),
+		};
+		let mut thumbnail = generate_thumbnail(image_path, thumbnailoptions, &decoder)?;
 		let quality = 80;
 
-		fs::create_dir_all(&self.thumbnails_dir_path)
-			.map_err(|e| Error::Io(self.thumbnails_dir_path.clone(), e))?;
-		let path = self.get_thumbnail_path(image_path, thumbnailoptions);
+		let format = thumbnailoptions.format.resolve(&thumbnail);
+		if !format.preserves_alpha() {
+			thumbnail = DynamicImage::ImageRgb8(thumbnail.into_rgb8());
+		}
+		let path = self.get_thumbnail_path(image_path, thumbnailoptions, format);
+		let source_dir_path = path.parent().unwrap_or(&self.thumbnails_dir_path);
+		fs::create_dir_all(source_dir_path)
+			.map_err(|e| Error::Io(source_dir_path.to_owned(), e))?;
 		let mut out_file =
 			File::create(&path).map_err(|e| Error::Io(self.thumbnails_dir_path.clone(), e))?;
 		thumbnail
-			.write_to(&mut out_file, ImageOutputFormat::Jpeg(quality))
+			.write_to(&mut out_file, format.output_format(quality))
 			.map_err(|e| Error::Image(image_path.to_owned(), e))?;
+		self.evict_stale_thumbnails(&path);
+		Ok(path)
+	}
+
+	fn create_collage(
+		&self,
+		image_paths: &[PathBuf],
+		thumbnailoptions: &Options,
+	) -> Result<PathBuf, Error> {
+		let dimension = thumbnailoptions
+			.max_dimension
+			.unwrap_or(DEFAULT_MAX_DIMENSION);
+		let tile_dimension = cmp::max(dimension / 2, 1);
+		let tile_options = Options {
+			max_dimension: Some(tile_dimension),
+			resize_if_almost_square: true,
+			fit: Some(Fit::Pad),
+			..thumbnailoptions.clone()
+		};
+		let decoder = ArtworkDecoder {
+			max_bytes: self.max_artwork_bytes,
+			target_dimension: tile_dimension,
+		};
+		let tiles = image_paths
+			.iter()
+			.map(|image_path| {
+				let tile = generate_thumbnail(image_path, &tile_options, &decoder)?;
+				Ok(resize(&tile, tile_dimension, true, tile_options.filter).into_rgba8())
+			})
+			.collect::<Result<Vec<_>, Error>>()?;
+
+		let mut collage =
+			DynamicImage::ImageRgba8(ImageBuffer::new(tile_dimension * 2, tile_dimension * 2));
+		for position in 0..MAX_COLLAGE_COVERS {
+			let (row, column) = (position / 2, position % 2);
+			// With two covers, each one fills a diagonal of the grid
+			let tile_index = match tiles.len() {
+				2 => (row + column) % 2,
+				n => position % n,
+			};
+			collage
+				.copy_from(
+					&tiles[tile_index],
+					column as u32 * tile_dimension,
+					row as u32 * tile_dimension,
+				)
+				.map_err(|e| Error::Image(image_paths[tile_index].clone(), e))?;
+		}
+
+		let quality = 80;
+		let format = thumbnailoptions.format.resolve(&collage);
+		if !format.preserves_alpha() {
+			collage = DynamicImage::ImageRgb8(collage.into_rgb8());
+		}
+		let path = self.get_collage_path(image_paths, thumbnailoptions, format);
+		let source_dir_path = path.parent().unwrap_or(&self.thumbnails_dir_path);
+		fs::create_dir_all(source_dir_path)
+			.map_err(|e| Error::Io(source_dir_path.to_owned(), e))?;
+		let mut out_file =
+			File::create(&path).map_err(|e| Error::Io(self.thumbnails_dir_path.clone(), e))?;
+		collage
+			.write_to(&mut out_file, format.output_format(quality))
+			.map_err(|e| Error::Image(image_paths[0].clone(), e))?;
+		self.evict_stale_thumbnails(&path);
 		Ok(path)
 	}
 
@@ -108,8 +723,18 @@ impl Manager {
 	}
 }
 
-fn generate_thumbnail(image_path: &Path, options: &Options) -> Result<DynamicImage, Error> {
-	let source_image = DynamicImage::ImageRgb8(read(image_path)?.into_rgb8());
+fn generate_thumbnail(
+	image_path: &Path,
+	options: &Options,
+	decoder: &ArtworkDecoder,
+) -> Result<DynamicImage, Error> {
+	let source_image = read(image_path, options.picture_type, decoder)?;
+	let keep_alpha = options.format.preserves_alpha() && has_transparency(&source_image);
+	let source_image = if keep_alpha {
+		DynamicImage::ImageRgba8(source_image.into_rgba8())
+	} else {
+		DynamicImage::ImageRgb8(source_image.into_rgb8())
+	};
 	let (source_width, source_height) = source_image.dimensions();
 	let largest_dimension = cmp::max(source_width, source_height);
 	let out_dimension = cmp::min(
@@ -122,16 +747,41 @@ fn generate_thumbnail(image_path: &Path, options: &Options) -> Result<DynamicIma
 
 	let mut final_image;
 	if is_almost_square && options.resize_if_almost_square {
-		final_image = source_image.thumbnail_exact(out_dimension, out_dimension);
-	} else if options.pad_to_square {
-		let scaled_image = source_image.thumbnail(out_dimension, out_dimension);
+		final_image = resize(&source_image, out_dimension, true, options.filter);
+	} else if options.fit == Some(Fit::Crop) {
+		let side = cmp::min(source_width, source_height);
+		let cropped_image = source_image.crop_imm(
+			(source_width - side) / 2,
+			(source_height - side) / 2,
+			side,
+			side,
+		);
+		final_image = resize(
+			&cropped_image,
+			cmp::min(out_dimension, side),
+			true,
+			options.filter,
+		);
+	} else if options.fit == Some(Fit::Stretch) {
+		final_image = resize(&source_image, out_dimension, true, options.filter);
+	} else if options.fit == Some(Fit::Pad) {
+		let scaled_image = resize(&source_image, out_dimension, false, options.filter);
 		let (scaled_width, scaled_height) = scaled_image.dimensions();
-		let background = image::Rgb([255, 255_u8, 255_u8]);
-		final_image = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(
-			out_dimension,
-			out_dimension,
-			background,
-		));
+		final_image = if keep_alpha {
+			let background = image::Rgba([0_u8, 0_u8, 0_u8, 0_u8]);
+			DynamicImage::ImageRgba8(ImageBuffer::from_pixel(
+				out_dimension,
+				out_dimension,
+				background,
+			))
+		} else {
+			let background = image::Rgb([255, 255_u8, 255_u8]);
+			DynamicImage::ImageRgb8(ImageBuffer::from_pixel(
+				out_dimension,
+				out_dimension,
+				background,
+			))
+		};
 		final_image
 			.copy_from(
 				&scaled_image,
@@ -140,70 +790,159 @@ fn generate_thumbnail(image_path: &Path, options: &Options) -> Result<DynamicIma
 			)
 			.map_err(|e| Error::Image(image_path.to_owned(), e))?;
 	} else {
-		final_image = source_image.thumbnail(out_dimension, out_dimension);
+		final_image = resize(&source_image, out_dimension, false, options.filter);
 	}
 
 	Ok(final_image)
 }
 
-fn read(image_path: &Path) -> Result<DynamicImage, Error> {
+/// Scales an image to fit within a square, or to fill it exactly.
+fn resize(
+	image: &DynamicImage,
+	dimension: u32,
+	exact: bool,
+	filter: Option<Filter>,
+) -> DynamicImage {
+	match (exact, filter) {
+		(true, None) => image.thumbnail_exact(dimension, dimension),
+		(true, Some(f)) => image.resize_exact(dimension, dimension, f.into()),
+		(false, None) => image.thumbnail(dimension, dimension),
+		(false, Some(f)) => image.resize(dimension, dimension, f.into()),
+	}
+}
+
+fn read(
+	image_path: &Path,
+	picture_type: Option<PictureType>,
+	decoder: &ArtworkDecoder,
+) -> Result<DynamicImage, Error> {
 	match get_audio_format(image_path) {
-		Some(AudioFormat::AIFF) => read_aiff(image_path),
+		Some(AudioFormat::AIFF) => read_aiff(image_path, picture_type, decoder),
 		Some(AudioFormat::APE) => read_ape(image_path),
-		Some(AudioFormat::FLAC) => read_flac(image_path),
-		Some(AudioFormat::MP3) => read_mp3(image_path),
-		Some(AudioFormat::MP4) => read_mp4(image_path),
+		Some(AudioFormat::FLAC) => read_flac(image_path, picture_type, decoder),
+		Some(AudioFormat::MP3) => read_mp3(image_path, picture_type, decoder),
+		Some(AudioFormat::MP4) => read_mp4(image_path, picture_type, decoder),
 		Some(AudioFormat::MPC) => read_ape(image_path),
 		Some(AudioFormat::OGG) => read_vorbis(image_path),
 		Some(AudioFormat::OPUS) => read_opus(image_path),
-		Some(AudioFormat::WAVE) => read_wave(image_path),
-		None => image::open(image_path).map_err(|e| Error::Image(image_path.to_owned(), e)),
+		Some(AudioFormat::WAVE) => read_wave(image_path, picture_type, decoder),
+		Some(AudioFormat::WMA) => read_wma(image_path),
+		None => read_image(image_path, picture_type, decoder),
 	}
 }
 
+/// Finds the picture of the requested type, or the front cover (falling back to the first
+/// picture) when no type is requested.
+fn select_picture<'a, T, F>(
+	pictures: impl Iterator<Item = &'a T>,
+	picture_type: Option<PictureType>,
+	matches: F,
+) -> Option<&'a T>
+where
+	T: 'a,
+	F: Fn(&T, PictureType) -> bool,
+{
+	let mut pictures = pictures.peekable();
+	let first = *pictures.peek()?;
+	match picture_type {
+		Some(t) => pictures.find(|p| matches(p, t)),
+		None => pictures
+			.find(|p| matches(p, PictureType::Front))
+			.or(Some(first)),
+	}
+}
+
+fn read_image(
+	path: &Path,
+	picture_type: Option<PictureType>,
+	decoder: &ArtworkDecoder,
+) -> Result<DynamicImage, Error> {
+	// Standalone images are album covers
+	if !matches!(picture_type, None | Some(PictureType::Front)) {
+		return Err(Error::EmbeddedArtworkNotFound(path.to_owned()));
+	}
+	let size = fs::metadata(path)
+		.map_err(|e| Error::Io(path.to_owned(), e))?
+		.len();
+	if size as usize <= decoder.max_bytes {
+		return image::open(path).map_err(|e| Error::Image(path.to_owned(), e));
+	}
+	let data = fs::read(path).map_err(|e| Error::Io(path.to_owned(), e))?;
+	decoder.decode(path, &data)
+}
+
 fn read_ape(_: &Path) -> Result<DynamicImage, Error> {
 	Err(Error::UnsupportedFormat("ape"))
 }
 
-fn read_flac(path: &Path) -> Result<DynamicImage, Error> {
+fn read_flac(
+	path: &Path,
+	picture_type: Option<PictureType>,
+	decoder: &ArtworkDecoder,
+) -> Result<DynamicImage, Error> {
 	let tag =
 		metaflac::Tag::read_from_path(path).map_err(|e| Error::Metaflac(path.to_owned(), e))?;
-	if let Some(p) = tag.pictures().next() {
-		return image::load_from_memory(&p.data).map_err(|e| Error::Image(path.to_owned(), e));
-	}
-	Err(Error::EmbeddedArtworkNotFound(path.to_owned()))
+	select_picture(tag.pictures(), picture_type, |p, t| {
+		t.matches_flac(p.picture_type)
+	})
+	.ok_or_else(|| Error::EmbeddedArtworkNotFound(path.to_owned()))
+	.and_then(|p| decoder.decode(path, &p.data))
 }
 
-fn read_mp3(path: &Path) -> Result<DynamicImage, Error> {
+fn read_mp3(
+	path: &Path,
+	picture_type: Option<PictureType>,
+	decoder: &ArtworkDecoder,
+) -> Result<DynamicImage, Error> {
 	let tag = id3::Tag::read_from_path(path).map_err(|e| Error::Id3(path.to_owned(), e))?;
-	read_id3(path, &tag)
+	read_id3(path, &tag, picture_type, decoder)
 }
 
-fn read_aiff(path: &Path) -> Result<DynamicImage, Error> {
+fn read_aiff(
+	path: &Path,
+	picture_type: Option<PictureType>,
+	decoder: &ArtworkDecoder,
+) -> Result<DynamicImage, Error> {
 	let tag = id3::Tag::read_from_aiff_path(path).map_err(|e| Error::Id3(path.to_owned(), e))?;
-	read_id3(path, &tag)
+	read_id3(path, &tag, picture_type, decoder)
 }
 
-fn read_wave(path: &Path) -> Result<DynamicImage, Error> {
+fn read_wave(
+	path: &Path,
+	picture_type: Option<PictureType>,
+	decoder: &ArtworkDecoder,
+) -> Result<DynamicImage, Error> {
 	let tag = id3::Tag::read_from_wav_path(path).map_err(|e| Error::Id3(path.to_owned(), e))?;
-	read_id3(path, &tag)
+	read_id3(path, &tag, picture_type, decoder)
 }
 
-fn read_id3(path: &Path, tag: &id3::Tag) -> Result<DynamicImage, Error> {
-	tag.pictures()
-		.next()
-		.ok_or_else(|| Error::EmbeddedArtworkNotFound(path.to_owned()))
-		.and_then(|d| {
-			image::load_from_memory(&d.data).map_err(|e| Error::Image(path.to_owned(), e))
-		})
+fn read_id3(
+	path: &Path,
+	tag: &id3::Tag,
+	picture_type: Option<PictureType>,
+	decoder: &ArtworkDecoder,
+) -> Result<DynamicImage, Error> {
+	select_picture(tag.pictures(), picture_type, |p, t| {
+		t.matches_id3(p.picture_type)
+	})
+	.ok_or_else(|| Error::EmbeddedArtworkNotFound(path.to_owned()))
+	.and_then(|p| decoder.decode(path, &p.data))
 }
 
-fn read_mp4(path: &Path) -> Result<DynamicImage, Error> {
+fn read_mp4(
+	path: &Path,
+	picture_type: Option<PictureType>,
+	decoder: &ArtworkDecoder,
+) -> Result<DynamicImage, Error> {
+	// MP4 artwork does not record picture types, it is treated as the front cover
+	if !matches!(picture_type, None | Some(PictureType::Front)) {
+		return Err(Error::EmbeddedArtworkNotFound(path.to_owned()));
+	}
 	let tag =
 		mp4ameta::Tag::read_from_path(path).map_err(|e| Error::Mp4aMeta(path.to_owned(), e))?;
 	tag.artwork()
 		.ok_or_else(|| Error::EmbeddedArtworkNotFound(path.to_owned()))
-		.and_then(|d| image::load_from_memory(d.data).map_err(|e| Error::Image(path.to_owned(), e)))
+		.and_then(|d| decoder.decode(path, d.data))
 }
 
 fn read_vorbis(_: &Path) -> Result<DynamicImage, Error> {
@@ -214,10 +953,24 @@ fn read_opus(_: &Path) -> Result<DynamicImage, Error> {
 	Err(Error::UnsupportedFormat("opus"))
 }
 
+fn read_wma(_: &Path) -> Result<DynamicImage, Error> {
+	Err(Error::UnsupportedFormat("wma"))
+}
+
 #[cfg(test)]
 mod test {
 
+	use std::sync::mpsc;
+	use std::thread;
+	use std::time::Duration;
+
 	use super::*;
+	use crate::test::prepare_test_directory;
+	use crate::test_name;
+
+	fn read_default(image_path: &Path) -> Result<DynamicImage, Error> {
+		read(image_path, None, &ArtworkDecoder::default())
+	}
 
 	#[test]
 	fn can_read_artwork_data() {
@@ -228,49 +981,540 @@ mod test {
 			.unwrap()
 			.to_rgb8();
 
-		let folder_img = read(Path::new("test-data/artwork/Folder.png"))
+		let folder_img = read_default(Path::new("test-data/artwork/Folder.png"))
 			.unwrap()
 			.to_rgb8();
 		assert_eq!(folder_img, ext_img);
 
-		let aiff_img = read(Path::new("test-data/artwork/sample.aif"))
+		let aiff_img = read_default(Path::new("test-data/artwork/sample.aif"))
 			.unwrap()
 			.to_rgb8();
 		assert_eq!(aiff_img, embedded_img);
 
-		let ape_img = read(Path::new("test-data/artwork/sample.ape"))
+		let ape_img = read_default(Path::new("test-data/artwork/sample.ape"))
 			.map(|d| d.to_rgb8())
 			.ok();
 		assert_eq!(ape_img, None);
 
-		let flac_img = read(Path::new("test-data/artwork/sample.flac"))
+		let flac_img = read_default(Path::new("test-data/artwork/sample.flac"))
 			.unwrap()
 			.to_rgb8();
 		assert_eq!(flac_img, embedded_img);
 
-		let mp3_img = read(Path::new("test-data/artwork/sample.mp3"))
+		let mp3_img = read_default(Path::new("test-data/artwork/sample.mp3"))
 			.unwrap()
 			.to_rgb8();
 		assert_eq!(mp3_img, embedded_img);
 
-		let m4a_img = read(Path::new("test-data/artwork/sample.m4a"))
+		let m4a_img = read_default(Path::new("test-data/artwork/sample.m4a"))
 			.unwrap()
 			.to_rgb8();
 		assert_eq!(m4a_img, embedded_img);
 
-		let ogg_img = read(Path::new("test-data/artwork/sample.ogg"))
+		let ogg_img = read_default(Path::new("test-data/artwork/sample.ogg"))
 			.map(|d| d.to_rgb8())
 			.ok();
 		assert_eq!(ogg_img, None);
 
-		let opus_img = read(Path::new("test-data/artwork/sample.opus"))
+		let opus_img = read_default(Path::new("test-data/artwork/sample.opus"))
 			.map(|d| d.to_rgb8())
 			.ok();
 		assert_eq!(opus_img, None);
 
-		let wave_img = read(Path::new("test-data/artwork/sample.wav"))
+		let wave_img = read_default(Path::new("test-data/artwork/sample.wav"))
 			.unwrap()
 			.to_rgb8();
 		assert_eq!(wave_img, embedded_img);
 	}
+
+	#[test]
+	fn thumbnail_generation_waits_for_available_permit() {
+		let thumbnails_dir_path = prepare_test_directory(test_name!());
+		let manager = Manager::with_max_concurrent_generations(thumbnails_dir_path, 1);
+		let image_path = Path::new("test-data/artwork/Folder.png");
+
+		// Simulate another thumbnail being generated
+		let permit = manager.generation_permits.acquire();
+
+		let (sender, receiver) = mpsc::channel();
+		{
+			let manager = manager.clone();
+			thread::spawn(move || {
+				let result = manager.get_thumbnail(image_path, &Options::default());
+				sender.send(result.is_ok()).unwrap();
+			});
+		}
+
+		assert_eq!(
+			receiver.recv_timeout(Duration::from_millis(200)),
+			Err(mpsc::RecvTimeoutError::Timeout)
+		);
+
+		drop(permit);
+		assert!(receiver.recv_timeout(Duration::from_secs(10)).unwrap());
+	}
+
+	#[test]
+	fn cached_thumbnail_does_not_wait_for_permit() {
+		let thumbnails_dir_path = prepare_test_directory(test_name!());
+		let manager = Manager::with_max_concurrent_generations(thumbnails_dir_path, 1);
+		let image_path = Path::new("test-data/artwork/Folder.png");
+		let options = Options::default();
+
+		let thumbnail_path = manager.get_thumbnail(image_path, &options).unwrap();
+
+		let _permit = manager.generation_permits.acquire();
+		assert_eq!(
+			manager.get_thumbnail(image_path, &options).unwrap(),
+			thumbnail_path
+		);
+	}
+
+	#[test]
+	fn modified_source_regenerates_thumbnail() {
+		let output_dir = prepare_test_directory(test_name!());
+		let image_path = output_dir.join("Folder.png");
+		fs::copy("test-data/artwork/Folder.png", &image_path).unwrap();
+		let manager = Manager::with_max_concurrent_generations(output_dir.join("thumbnails"), 1);
+		let options = Options::default();
+
+		let original_thumbnail_path = manager.get_thumbnail(&image_path, &options).unwrap();
+		assert_eq!(
+			manager.get_thumbnail(&image_path, &options).unwrap(),
+			original_thumbnail_path
+		);
+
+		let modified = fs::metadata(&image_path).unwrap().modified().unwrap();
+		File::options()
+			.write(true)
+			.open(&image_path)
+			.unwrap()
+			.set_modified(modified + Duration::from_secs(60))
+			.unwrap();
+
+		let new_thumbnail_path = manager.get_thumbnail(&image_path, &options).unwrap();
+		assert_ne!(new_thumbnail_path, original_thumbnail_path);
+		assert!(new_thumbnail_path.exists());
+		assert!(!original_thumbnail_path.exists());
+	}
+
+	#[test]
+	fn thumbnail_dimension_is_capped() {
+		let output_dir = prepare_test_directory(test_name!());
+		let image_path = output_dir.join("large.jpg");
+		fs::write(&image_path, make_jpeg(600)).unwrap();
+		let manager = Manager::with_max_concurrent_generations(output_dir.join("thumbnails"), 1)
+			.with_dimension_cap(200);
+
+		for max_dimension in [None, Some(1200)] {
+			let options = Options {
+				max_dimension,
+				..Default::default()
+			};
+			let thumbnail_path = manager.get_thumbnail(&image_path, &options).unwrap();
+			let thumbnail = image::open(thumbnail_path).unwrap();
+			assert_eq!(thumbnail.dimensions(), (200, 200));
+		}
+	}
+
+	#[test]
+	fn filters_produce_different_thumbnails() {
+		let output_dir = prepare_test_directory(test_name!());
+		let image_path = output_dir.join("large.jpg");
+		fs::write(&image_path, make_jpeg(600)).unwrap();
+		let manager = Manager::with_max_concurrent_generations(output_dir.join("thumbnails"), 1);
+
+		let thumbnails: Vec<Vec<u8>> = [Filter::Nearest, Filter::Lanczos3]
+			.into_iter()
+			.map(|filter| {
+				let options = Options {
+					max_dimension: Some(100),
+					filter: Some(filter),
+					..Default::default()
+				};
+				let thumbnail_path = manager.get_thumbnail(&image_path, &options).unwrap();
+				let thumbnail = image::open(thumbnail_path).unwrap();
+				assert_eq!(thumbnail.dimensions(), (100, 100));
+				thumbnail.into_bytes()
+			})
+			.collect();
+		assert_ne!(thumbnails[0], thumbnails[1]);
+	}
+
+	#[test]
+	fn formats_are_cached_separately() {
+		let output_dir = prepare_test_directory(test_name!());
+		let image_path = output_dir.join("large.jpg");
+		fs::write(&image_path, make_jpeg(600)).unwrap();
+		let manager = Manager::with_max_concurrent_generations(output_dir.join("thumbnails"), 1);
+
+		let jpeg_path = manager
+			.get_thumbnail(&image_path, &Options::default())
+			.unwrap();
+		let webp_options = Options {
+			format: Format::WebP,
+			..Default::default()
+		};
+		let webp_path = manager.get_thumbnail(&image_path, &webp_options).unwrap();
+
+		assert_ne!(jpeg_path, webp_path);
+		assert_eq!(webp_path.extension().unwrap(), "webp");
+		let webp_data = fs::read(&webp_path).unwrap();
+		assert_eq!(&webp_data[0..4], b"RIFF");
+		assert_eq!(&webp_data[8..12], b"WEBP");
+		assert!(jpeg_path.exists());
+	}
+
+	#[test]
+	fn auto_format_preserves_transparency() {
+		let output_dir = prepare_test_directory(test_name!());
+		let image_path = output_dir.join("transparent.png");
+		let image = ImageBuffer::from_fn(300, 200, |x, _| {
+			image::Rgba([(x % 256) as u8, 0, 0, if x < 150 { 0 } else { 255 }])
+		});
+		DynamicImage::ImageRgba8(image).save(&image_path).unwrap();
+		let manager = Manager::with_max_concurrent_generations(output_dir.join("thumbnails"), 1);
+		let options = Options {
+			format: Format::Auto,
+			..Default::default()
+		};
+
+		let thumbnail_path = manager.get_thumbnail(&image_path, &options).unwrap();
+		assert_eq!(thumbnail_path.extension().unwrap(), "png");
+		let thumbnail = image::open(&thumbnail_path).unwrap();
+		assert!(has_transparency(&thumbnail));
+		assert_eq!(
+			manager.get_thumbnail(&image_path, &options).unwrap(),
+			thumbnail_path
+		);
+	}
+
+	#[test]
+	fn auto_format_uses_jpeg_for_photographic_artwork() {
+		let output_dir = prepare_test_directory(test_name!());
+		let image_path = output_dir.join("photo.jpg");
+		fs::write(&image_path, make_jpeg(600)).unwrap();
+		let manager = Manager::with_max_concurrent_generations(output_dir.join("thumbnails"), 1);
+		let options = Options {
+			format: Format::Auto,
+			..Default::default()
+		};
+
+		let thumbnail_path = manager.get_thumbnail(&image_path, &options).unwrap();
+		assert_eq!(thumbnail_path.extension().unwrap(), "jpg");
+	}
+
+	#[test]
+	fn missing_artwork_can_use_placeholder() {
+		let thumbnails_dir_path = prepare_test_directory(test_name!());
+		let manager = Manager::with_max_concurrent_generations(thumbnails_dir_path, 1);
+		let placeholder_path = Path::new("test-data/artwork/Folder.png");
+		let options = Options::default();
+
+		let directory_path = PathBuf::from("test-data/small-collection/Khemmis");
+		assert!(matches!(
+			manager.get_thumbnail(&directory_path, &options),
+			Err(Error::ArtworkNotFound(_))
+		));
+		let (path, is_placeholder) = manager
+			.get_collage_or_placeholder(&[directory_path], placeholder_path, &options)
+			.unwrap();
+		assert!(is_placeholder);
+		assert_eq!(
+			path,
+			manager.get_thumbnail(placeholder_path, &options).unwrap()
+		);
+
+		let image_path = PathBuf::from("test-data/artwork/sample.mp3");
+		let (_, is_placeholder) = manager
+			.get_collage_or_placeholder(&[image_path], placeholder_path, &options)
+			.unwrap();
+		assert!(!is_placeholder);
+	}
+
+	#[test]
+	fn collage_is_composed_of_multiple_covers() {
+		let output_dir = prepare_test_directory(test_name!());
+		let red_path = output_dir.join("red.png");
+		let blue_path = output_dir.join("blue.png");
+		for (path, color) in [(&red_path, [255, 0, 0]), (&blue_path, [0, 0, 255])] {
+			let image = ImageBuffer::from_pixel(300, 300, image::Rgb(color));
+			DynamicImage::ImageRgb8(image).save(path).unwrap();
+		}
+		let manager = Manager::with_max_concurrent_generations(output_dir.join("thumbnails"), 1);
+		let options = Options {
+			format: Format::Png,
+			..Default::default()
+		};
+
+		let image_paths = vec![red_path.clone(), blue_path];
+		let collage_path = manager.get_collage(&image_paths, &options).unwrap();
+		let collage = image::open(&collage_path).unwrap().into_rgb8();
+		assert_eq!(
+			collage.dimensions(),
+			(DEFAULT_MAX_DIMENSION, DEFAULT_MAX_DIMENSION)
+		);
+		let quarter = DEFAULT_MAX_DIMENSION / 4;
+		let three_quarters = 3 * quarter;
+		assert_eq!(collage.get_pixel(quarter, quarter).0, [255, 0, 0]);
+		assert_eq!(collage.get_pixel(three_quarters, quarter).0, [0, 0, 255]);
+		assert_eq!(collage.get_pixel(quarter, three_quarters).0, [0, 0, 255]);
+		assert_eq!(
+			collage.get_pixel(three_quarters, three_quarters).0,
+			[255, 0, 0]
+		);
+
+		assert_eq!(
+			manager.get_collage(&image_paths, &options).unwrap(),
+			collage_path
+		);
+		assert_eq!(
+			manager.get_collage(&image_paths[..1], &options).unwrap(),
+			manager.get_thumbnail(&red_path, &options).unwrap()
+		);
+	}
+
+	#[test]
+	fn prewarm_skips_existing_thumbnails() {
+		let thumbnails_dir_path = prepare_test_directory(test_name!());
+		let manager = Manager::with_max_concurrent_generations(thumbnails_dir_path, 1);
+		let image_paths = vec![
+			PathBuf::from("test-data/artwork/Folder.png"),
+			PathBuf::from("test-data/artwork/sample.mp3"),
+		];
+		let options = Options::default();
+
+		let progress = manager.prewarm(&image_paths, &options).unwrap();
+		assert_eq!(progress.generated, 2);
+		assert_eq!(progress.skipped, 0);
+		assert!(manager
+			.retrieve_thumbnail(&image_paths[0], &options)
+			.is_some());
+
+		let progress = manager.prewarm(&image_paths, &options).unwrap();
+		assert_eq!(progress.generated, 0);
+		assert_eq!(progress.skipped, 2);
+		assert_eq!(manager.prewarm_progress(), progress);
+	}
+
+	#[test]
+	fn prewarm_can_be_cancelled() {
+		let thumbnails_dir_path = prepare_test_directory(test_name!());
+		let manager = Manager::with_max_concurrent_generations(thumbnails_dir_path, 1);
+		let image_paths = vec![PathBuf::from("test-data/artwork/Folder.png"); 8];
+
+		// Stall generation until the prewarm is cancelled
+		let permit = manager.generation_permits.acquire();
+
+		let (sender, receiver) = mpsc::channel();
+		{
+			let manager = manager.clone();
+			thread::spawn(move || {
+				let result = manager.prewarm(&image_paths, &Options::default());
+				sender.send(result.unwrap()).unwrap();
+			});
+		}
+
+		while !manager.prewarm_progress().running {
+			thread::sleep(Duration::from_millis(10));
+		}
+		assert!(matches!(
+			manager.prewarm(&[], &Options::default()),
+			Err(Error::PrewarmInProgress)
+		));
+
+		manager.cancel_prewarm();
+		drop(permit);
+
+		let progress = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+		assert!(progress.cancelled);
+		assert!(!progress.running);
+		assert!(progress.generated + progress.skipped < progress.total);
+	}
+
+	fn make_jpeg(dimension: u32) -> Vec<u8> {
+		let image = ImageBuffer::from_fn(dimension, dimension, |x, y| {
+			image::Rgb([(x % 256) as u8, (y % 256) as u8, ((x * y) % 256) as u8])
+		});
+		let mut data = Vec::new();
+		DynamicImage::ImageRgb8(image)
+			.write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Jpeg(90))
+			.unwrap();
+		data
+	}
+
+	fn make_wide_image(output_dir: &Path) -> PathBuf {
+		let image_path = output_dir.join("wide.png");
+		DynamicImage::ImageRgb8(ImageBuffer::from_pixel(400, 200, image::Rgb([255, 0, 0])))
+			.save(&image_path)
+			.unwrap();
+		image_path
+	}
+
+	fn thumbnail_dimensions(image_path: &Path, options: &Options) -> (u32, u32) {
+		generate_thumbnail(image_path, options, &ArtworkDecoder::default())
+			.unwrap()
+			.dimensions()
+	}
+
+	#[test]
+	fn fit_modes_produce_expected_dimensions() {
+		let image_path = make_wide_image(&prepare_test_directory(test_name!()));
+		let options = |fit| Options {
+			max_dimension: Some(100),
+			fit,
+			..Options::default()
+		};
+		assert_eq!(
+			thumbnail_dimensions(&image_path, &options(Some(Fit::Pad))),
+			(100, 100)
+		);
+		assert_eq!(
+			thumbnail_dimensions(&image_path, &options(Some(Fit::Crop))),
+			(100, 100)
+		);
+		assert_eq!(
+			thumbnail_dimensions(&image_path, &options(Some(Fit::Stretch))),
+			(100, 100)
+		);
+		assert_eq!(thumbnail_dimensions(&image_path, &options(None)), (100, 50));
+
+		// Cropping does not upscale the cropped square
+		let native = |fit| Options {
+			max_dimension: None,
+			fit,
+			..Options::default()
+		};
+		assert_eq!(
+			thumbnail_dimensions(&image_path, &native(Some(Fit::Pad))),
+			(400, 400)
+		);
+		assert_eq!(
+			thumbnail_dimensions(&image_path, &native(Some(Fit::Crop))),
+			(200, 200)
+		);
+		assert_eq!(
+			thumbnail_dimensions(&image_path, &native(Some(Fit::Stretch))),
+			(400, 400)
+		);
+	}
+
+	#[test]
+	fn pad_to_square_is_an_alias_of_fit() {
+		let image_path = make_wide_image(&prepare_test_directory(test_name!()));
+		let options = Options {
+			max_dimension: Some(100),
+			..Options::default()
+		};
+		let padded = options.clone().with_pad_to_square(true);
+		assert_eq!(padded.fit, Some(Fit::Pad));
+		assert_eq!(thumbnail_dimensions(&image_path, &padded), (100, 100));
+
+		let unpadded = options.with_pad_to_square(false);
+		assert_eq!(unpadded.fit, None);
+		assert_eq!(thumbnail_dimensions(&image_path, &unpadded), (100, 50));
+	}
+
+	#[test]
+	fn oversized_jpeg_is_decoded_at_reduced_scale() {
+		let data = make_jpeg(1600);
+		let decoder = ArtworkDecoder {
+			max_bytes: 1024,
+			target_dimension: 200,
+		};
+		let image = decoder.decode(Path::new("oversized.jpg"), &data).unwrap();
+		let (width, height) = image.dimensions();
+		assert!((200..1600).contains(&width));
+		assert!((200..1600).contains(&height));
+	}
+
+	#[test]
+	fn oversized_artwork_without_scaled_decoding_is_refused() {
+		let mut data = Vec::new();
+		DynamicImage::ImageRgb8(ImageBuffer::new(64, 64))
+			.write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png)
+			.unwrap();
+		let decoder = ArtworkDecoder {
+			max_bytes: 16,
+			target_dimension: 200,
+		};
+		assert!(matches!(
+			decoder.decode(Path::new("oversized.png"), &data),
+			Err(Error::ArtworkTooLarge(_, _))
+		));
+	}
+
+	#[test]
+	fn can_read_embedded_artwork_by_picture_type() {
+		use id3::TagLike;
+
+		let output_dir = prepare_test_directory(test_name!());
+		let song_path = output_dir.join("pictures.mp3");
+		fs::copy("test-data/artwork/sample.mp3", &song_path).unwrap();
+
+		let make_png = |color: [u8; 3]| {
+			let mut data = Vec::new();
+			DynamicImage::ImageRgb8(ImageBuffer::from_pixel(16, 16, image::Rgb(color)))
+				.write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png)
+				.unwrap();
+			data
+		};
+		let pictures = [
+			(id3::frame::PictureType::CoverBack, [0, 0, 255]),
+			(id3::frame::PictureType::CoverFront, [255, 0, 0]),
+			(id3::frame::PictureType::Leaflet, [0, 255, 0]),
+		];
+		let mut tag = id3::Tag::new();
+		for (picture_type, color) in pictures {
+			tag.add_frame(id3::frame::Picture {
+				mime_type: "image/png".to_owned(),
+				picture_type,
+				description: format!("{:?}", picture_type),
+				data: make_png(color),
+			});
+		}
+		tag.write_to_path(&song_path, id3::Version::Id3v24).unwrap();
+
+		let decoder = ArtworkDecoder::default();
+		let read_color = |picture_type| {
+			read(&song_path, picture_type, &decoder)
+				.map(|image| image.to_rgb8().get_pixel(0, 0).0)
+				.ok()
+		};
+		assert_eq!(read_color(None), Some([255, 0, 0]));
+		assert_eq!(read_color(Some(PictureType::Front)), Some([255, 0, 0]));
+		assert_eq!(read_color(Some(PictureType::Back)), Some([0, 0, 255]));
+		assert_eq!(read_color(Some(PictureType::Leaflet)), Some([0, 255, 0]));
+		assert_eq!(read_color(Some(PictureType::Media)), None);
+		assert_eq!(read_color(Some(PictureType::Artist)), None);
+	}
+
+	#[test]
+	fn can_thumbnail_oversized_embedded_artwork() {
+		use id3::TagLike;
+
+		let output_dir = prepare_test_directory(test_name!());
+		let song_path = output_dir.join("oversized.mp3");
+		fs::copy("test-data/artwork/sample.mp3", &song_path).unwrap();
+
+		let mut tag = id3::Tag::new();
+		tag.add_frame(id3::frame::Picture {
+			mime_type: "image/jpeg".to_owned(),
+			picture_type: id3::frame::PictureType::CoverFront,
+			description: String::new(),
+			data: make_jpeg(1600),
+		});
+		tag.write_to_path(&song_path, id3::Version::Id3v24).unwrap();
+
+		let manager = Manager::with_max_concurrent_generations(output_dir.join("thumbnails"), 1)
+			.with_max_artwork_bytes(1024);
+		let thumbnail_path = manager
+			.get_thumbnail(&song_path, &Options::default())
+			.unwrap();
+		let thumbnail = image::open(thumbnail_path).unwrap();
+		assert_eq!(
+			thumbnail.dimensions(),
+			(DEFAULT_MAX_DIMENSION, DEFAULT_MAX_DIMENSION)
+		);
+	}
 }
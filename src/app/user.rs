@@ -1,12 +1,15 @@
 use diesel::prelude::*;
 use pbkdf2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
 use pbkdf2::Pbkdf2;
+use rand::distributions::Alphanumeric;
 use rand::rngs::OsRng;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
 
 use crate::app::settings::AuthSecret;
 use crate::db::{self, users, DB};
+use crate::utils::now;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -24,8 +27,12 @@ pub enum Error {
 	IncorrectPassword,
 	#[error("Invalid auth token")]
 	InvalidAuthToken,
+	#[error("Auth token has expired")]
+	ExpiredAuthToken,
 	#[error("Incorrect authorization scope")]
 	IncorrectAuthorizationScope,
+	#[error("Invalid bootstrap token")]
+	InvalidBootstrapToken,
 	#[error("Last.fm session key is missing")]
 	MissingLastFMSessionKey,
 	#[error("Failed to hash password")]
@@ -72,22 +79,100 @@ pub struct Authorization {
 	pub scope: AuthorizationScope,
 }
 
+/// Grants access to a single song without a session, until it expires.
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StreamAuthorization {
+	pub username: String,
+	pub path: String,
+	/// Seconds since the UNIX epoch.
+	pub expires_at: u64,
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Preferences {
 	pub lastfm_username: Option<String>,
 	pub web_theme_base: Option<String>,
 	pub web_theme_accent: Option<String>,
+	/// Whether the user's plays appear in the activity feed administrators can read.
+	#[serde(default)]
+	pub share_activity: bool,
+}
+
+const BOOTSTRAP_TOKEN_LENGTH: usize = 32;
+
+/// How the first administrator can be created on a server without any user.
+#[derive(Debug, Default, PartialEq, Eq)]
+enum Bootstrap {
+	/// Anyone reaching the server can configure it until a user exists.
+	#[default]
+	Open,
+	/// Creating the first administrator requires this one-time token.
+	Token(String),
+	/// The one-time token was used, or the server already had users.
+	Closed,
 }
 
 #[derive(Clone)]
 pub struct Manager {
 	db: DB,
 	auth_secret: AuthSecret,
+	bootstrap: Arc<Mutex<Bootstrap>>,
 }
 
 impl Manager {
 	pub fn new(db: DB, auth_secret: AuthSecret) -> Self {
-		Self { db, auth_secret }
+		Self {
+			db,
+			auth_secret,
+			bootstrap: Arc::default(),
+		}
+	}
+
+	/// Closes the open setup flow. While the server has no user, the first administrator can
+	/// only be created with the returned one-time token.
+	pub fn require_bootstrap_token(&self) -> Result<Option<String>, Error> {
+		let mut bootstrap = self.bootstrap.lock().unwrap();
+		if self.count()? > 0 {
+			*bootstrap = Bootstrap::Closed;
+			return Ok(None);
+		}
+		let token: String = rand::thread_rng()
+			.sample_iter(&Alphanumeric)
+			.take(BOOTSTRAP_TOKEN_LENGTH)
+			.map(char::from)
+			.collect();
+		*bootstrap = Bootstrap::Token(token.clone());
+		Ok(Some(token))
+	}
+
+	/// Whether anyone may configure the server while it has no user.
+	pub fn allows_open_setup(&self) -> bool {
+		*self.bootstrap.lock().unwrap() == Bootstrap::Open
+	}
+
+	/// Creates the first administrator, consuming the one-time bootstrap token.
+	pub fn bootstrap_admin(
+		&self,
+		token: &str,
+		username: &str,
+		password: &str,
+	) -> Result<(), Error> {
+		let mut bootstrap = self.bootstrap.lock().unwrap();
+		match &*bootstrap {
+			Bootstrap::Token(t) if t == token => (),
+			_ => return Err(Error::InvalidBootstrapToken),
+		};
+		if self.count()? > 0 {
+			*bootstrap = Bootstrap::Closed;
+			return Err(Error::InvalidBootstrapToken);
+		}
+		self.create(&NewUser {
+			name: username.to_owned(),
+			password: password.to_owned(),
+			admin: true,
+		})?;
+		*bootstrap = Bootstrap::Closed;
+		Ok(())
 	}
 
 	pub fn create(&self, new_user: &NewUser) -> Result<(), Error> {
@@ -196,16 +281,54 @@ impl Manager {
 	fn generate_auth_token(&self, authorization: &Authorization) -> Result<AuthToken, Error> {
 		let serialized_authorization =
 			serde_json::to_string(&authorization).or(Err(Error::AuthorizationTokenEncoding))?;
-		branca::encode(
-			serialized_authorization.as_bytes(),
-			&self.auth_secret.key,
-			SystemTime::now()
-				.duration_since(UNIX_EPOCH)
-				.unwrap_or_default()
-				.as_secs() as u32,
-		)
-		.or(Err(Error::BrancaTokenEncoding))
-		.map(AuthToken)
+		self.encode_token(serialized_authorization.as_bytes())
+	}
+
+	fn encode_token(&self, data: &[u8]) -> Result<AuthToken, Error> {
+		branca::encode(data, &self.auth_secret.key, now() as u32)
+			.or(Err(Error::BrancaTokenEncoding))
+			.map(AuthToken)
+	}
+
+	/// Mints a token which lets anyone holding it stream the song at `virtual_path` on behalf of
+	/// a user, until `expires_at` (in seconds since the UNIX epoch).
+	pub fn generate_stream_token(
+		&self,
+		username: &str,
+		virtual_path: &str,
+		expires_at: u64,
+	) -> Result<AuthToken, Error> {
+		let authorization = StreamAuthorization {
+			username: username.to_owned(),
+			path: virtual_path.to_owned(),
+			expires_at,
+		};
+		let serialized_authorization =
+			serde_json::to_string(&authorization).or(Err(Error::AuthorizationTokenEncoding))?;
+		self.encode_token(serialized_authorization.as_bytes())
+	}
+
+	/// Checks that a stream token was minted for `virtual_path` and has not expired yet.
+	pub fn authenticate_stream(
+		&self,
+		auth_token: &AuthToken,
+		virtual_path: &str,
+	) -> Result<StreamAuthorization, Error> {
+		let AuthToken(data) = auth_token;
+		let authorization =
+			branca::decode(data, &self.auth_secret.key, 0).map_err(|_| Error::InvalidAuthToken)?;
+		let authorization: StreamAuthorization =
+			serde_json::from_slice(&authorization[..]).map_err(|_| Error::InvalidAuthToken)?;
+		if authorization.path != virtual_path {
+			return Err(Error::InvalidAuthToken);
+		}
+		if authorization.expires_at <= now() as u64 {
+			return Err(Error::ExpiredAuthToken);
+		}
+		if !self.exists(&authorization.username)? {
+			return Err(Error::IncorrectUsername);
+		}
+		Ok(authorization)
 	}
 
 	pub fn count(&self) -> Result<i64, Error> {
@@ -247,14 +370,20 @@ impl Manager {
 	pub fn read_preferences(&self, username: &str) -> Result<Preferences, Error> {
 		use crate::db::users::dsl::*;
 		let mut connection = self.db.connect()?;
-		let (theme_base, theme_accent, read_lastfm_username) = users
-			.select((web_theme_base, web_theme_accent, lastfm_username))
+		let (theme_base, theme_accent, read_lastfm_username, read_share_activity) = users
+			.select((
+				web_theme_base,
+				web_theme_accent,
+				lastfm_username,
+				share_activity,
+			))
 			.filter(name.eq(username))
 			.get_result(&mut connection)?;
 		Ok(Preferences {
 			web_theme_base: theme_base,
 			web_theme_accent: theme_accent,
 			lastfm_username: read_lastfm_username,
+			share_activity: read_share_activity,
 		})
 	}
 
@@ -269,6 +398,7 @@ impl Manager {
 			.set((
 				web_theme_base.eq(&preferences.web_theme_base),
 				web_theme_accent.eq(&preferences.web_theme_accent),
+				share_activity.eq(preferences.share_activity),
 			))
 			.execute(&mut connection)?;
 		Ok(())
@@ -409,6 +539,68 @@ mod test {
 		ctx.user_manager.create(&new_user).unwrap_err();
 	}
 
+	#[test]
+	fn bootstrap_token_creates_first_admin() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		assert!(ctx.user_manager.allows_open_setup());
+
+		let token = ctx.user_manager.require_bootstrap_token().unwrap().unwrap();
+		assert!(!ctx.user_manager.allows_open_setup());
+
+		ctx.user_manager
+			.bootstrap_admin(&token, TEST_USERNAME, TEST_PASSWORD)
+			.unwrap();
+		assert!(ctx.user_manager.is_admin(TEST_USERNAME).unwrap());
+		assert!(ctx.user_manager.login(TEST_USERNAME, TEST_PASSWORD).is_ok());
+	}
+
+	#[test]
+	fn bootstrap_token_is_rejected_after_use() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		let token = ctx.user_manager.require_bootstrap_token().unwrap().unwrap();
+		ctx.user_manager
+			.bootstrap_admin(&token, TEST_USERNAME, TEST_PASSWORD)
+			.unwrap();
+		assert!(matches!(
+			ctx.user_manager
+				.bootstrap_admin(&token, "Intruder", TEST_PASSWORD)
+				.unwrap_err(),
+			Error::InvalidBootstrapToken
+		));
+		assert_eq!(ctx.user_manager.count().unwrap(), 1);
+	}
+
+	#[test]
+	fn bootstrap_token_is_rejected_when_incorrect() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		ctx.user_manager.require_bootstrap_token().unwrap().unwrap();
+		assert!(matches!(
+			ctx.user_manager
+				.bootstrap_admin("garbage", TEST_USERNAME, TEST_PASSWORD)
+				.unwrap_err(),
+			Error::InvalidBootstrapToken
+		));
+		assert_eq!(ctx.user_manager.count().unwrap(), 0);
+	}
+
+	#[test]
+	fn no_bootstrap_token_when_users_exist() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+		ctx.user_manager
+			.create(&NewUser {
+				name: TEST_USERNAME.to_owned(),
+				password: TEST_PASSWORD.to_owned(),
+				admin: true,
+			})
+			.unwrap();
+		assert!(ctx
+			.user_manager
+			.require_bootstrap_token()
+			.unwrap()
+			.is_none());
+		assert!(!ctx.user_manager.allows_open_setup());
+	}
+
 	#[test]
 	fn can_read_write_preferences() {
 		let ctx = test::ContextBuilder::new(test_name!()).build();
@@ -417,6 +609,7 @@ mod test {
 			web_theme_base: Some("very-dark-theme".to_owned()),
 			web_theme_accent: Some("#FF0000".to_owned()),
 			lastfm_username: None,
+			share_activity: true,
 		};
 
 		let new_user = NewUser {
@@ -534,4 +727,74 @@ mod test {
 			Error::IncorrectAuthorizationScope
 		));
 	}
+
+	#[test]
+	fn authenticate_stream_golden_path() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+
+		let new_user = NewUser {
+			name: TEST_USERNAME.to_owned(),
+			password: TEST_PASSWORD.to_owned(),
+			admin: false,
+		};
+		ctx.user_manager.create(&new_user).unwrap();
+
+		let token = ctx
+			.user_manager
+			.generate_stream_token(TEST_USERNAME, "root/song.mp3", now() as u64 + 60)
+			.unwrap();
+		let authorization = ctx
+			.user_manager
+			.authenticate_stream(&token, "root/song.mp3")
+			.unwrap();
+		assert_eq!(authorization.username, TEST_USERNAME);
+	}
+
+	#[test]
+	fn authenticate_stream_rejects_other_path() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+
+		let new_user = NewUser {
+			name: TEST_USERNAME.to_owned(),
+			password: TEST_PASSWORD.to_owned(),
+			admin: false,
+		};
+		ctx.user_manager.create(&new_user).unwrap();
+
+		let token = ctx
+			.user_manager
+			.generate_stream_token(TEST_USERNAME, "root/song.mp3", now() as u64 + 60)
+			.unwrap();
+		let authorization = ctx
+			.user_manager
+			.authenticate_stream(&token, "root/other_song.mp3");
+		assert!(matches!(
+			authorization.unwrap_err(),
+			Error::InvalidAuthToken
+		));
+	}
+
+	#[test]
+	fn authenticate_stream_rejects_expired_token() {
+		let ctx = test::ContextBuilder::new(test_name!()).build();
+
+		let new_user = NewUser {
+			name: TEST_USERNAME.to_owned(),
+			password: TEST_PASSWORD.to_owned(),
+			admin: false,
+		};
+		ctx.user_manager.create(&new_user).unwrap();
+
+		let token = ctx
+			.user_manager
+			.generate_stream_token(TEST_USERNAME, "root/song.mp3", now() as u64 - 1)
+			.unwrap();
+		let authorization = ctx
+			.user_manager
+			.authenticate_stream(&token, "root/song.mp3");
+		assert!(matches!(
+			authorization.unwrap_err(),
+			Error::ExpiredAuthToken
+		));
+	}
 }
@@ -1,11 +1,20 @@
 use core::ops::Deref;
 use diesel::prelude::*;
+use log::error;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::{self, Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
 
 use crate::db::{self, mount_points, DB};
 
+mod s3;
+pub mod storage;
+
+pub use self::s3::S3;
+use self::storage::{Backend, BackendFactories, BackendFactory, RemoteLocation};
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
 	#[error("The following real path could not be mapped to a virtual path: `{0}`")]
@@ -21,38 +30,65 @@ pub enum Error {
 #[derive(Clone, Debug, Deserialize, Insertable, PartialEq, Eq, Queryable, Serialize)]
 #[diesel(table_name = mount_points)]
 pub struct MountDir {
+	/// Local directory, or URI of a remote location served by a storage backend (`s3://bucket/music`).
 	pub source: String,
 	pub name: String,
+	/// Overrides the global album art pattern for files within this mount.
+	#[serde(default)]
+	pub album_art_pattern: Option<String>,
+	/// Overrides the global interval between automatic scans of this mount.
+	#[serde(default)]
+	pub reindex_every_n_seconds: Option<i32>,
+	/// Leaves this mount out of the root listing of the collection. Its content can still be
+	/// browsed by path.
+	#[serde(default)]
+	pub hide_from_root: bool,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 pub struct Mount {
 	pub source: PathBuf,
 	pub name: String,
+	pub album_art_pattern: Option<String>,
+	pub reindex_every_n_seconds: Option<i32>,
+	pub hide_from_root: bool,
 }
 
 impl From<MountDir> for Mount {
 	fn from(m: MountDir) -> Self {
-		let separator_regex = Regex::new(r"\\|/").unwrap();
-		let mut correct_separator = String::new();
-		correct_separator.push(path::MAIN_SEPARATOR);
-		let path_string = separator_regex.replace_all(&m.source, correct_separator.as_str());
-		let source = PathBuf::from(path_string.deref());
+		let source = if storage::get_scheme(&m.source).is_some() {
+			PathBuf::from(&m.source)
+		} else {
+			let separator_regex = Regex::new(r"\\|/").unwrap();
+			let mut correct_separator = String::new();
+			correct_separator.push(path::MAIN_SEPARATOR);
+			let path_string = separator_regex.replace_all(&m.source, correct_separator.as_str());
+			PathBuf::from(path_string.deref())
+		};
 		Self {
 			name: m.name,
 			source,
+			album_art_pattern: m.album_art_pattern,
+			reindex_every_n_seconds: m.reindex_every_n_seconds,
+			hide_from_root: m.hide_from_root,
 		}
 	}
 }
 
 #[allow(clippy::upper_case_acronyms)]
+#[derive(Clone)]
 pub struct VFS {
 	mounts: Vec<Mount>,
+	/// Storage backends of remote mounts, by mount name.
+	backends: HashMap<String, Arc<dyn Backend>>,
 }
 
 impl VFS {
 	pub fn new(mounts: Vec<Mount>) -> VFS {
-		VFS { mounts }
+		VFS {
+			mounts,
+			backends: HashMap::new(),
+		}
 	}
 
 	pub fn real_to_virtual<P: AsRef<Path>>(&self, real_path: P) -> Result<PathBuf, Error> {
@@ -86,29 +122,125 @@ impl VFS {
 	pub fn mounts(&self) -> &Vec<Mount> {
 		&self.mounts
 	}
+
+	/// Storage backend serving a real path, along with the location of the path within its mount.
+	/// Returns `None` for paths on the local file system.
+	pub fn locate_remote<P: AsRef<Path>>(&self, real_path: P) -> Option<RemoteLocation> {
+		self.mounts.iter().find_map(|mount| {
+			let backend = self.backends.get(&mount.name)?;
+			let path = real_path.as_ref().strip_prefix(&mount.source).ok()?;
+			Some(RemoteLocation {
+				backend: backend.clone(),
+				path: path.to_owned(),
+			})
+		})
+	}
+}
+
+/// Gives unique names to mount points sharing the same name, by appending
+/// a numeric suffix (`music`, `music-2`, `music-3`...).
+pub fn resolve_name_collisions(mount_dirs: &[MountDir]) -> Vec<MountDir> {
+	let original_names: HashSet<&str> = mount_dirs.iter().map(|m| m.name.as_str()).collect();
+	let mut used_names: HashSet<String> = HashSet::new();
+	let mut resolved = Vec::with_capacity(mount_dirs.len());
+	for mount_dir in mount_dirs {
+		let mut name = mount_dir.name.clone();
+		let mut suffix = 2;
+		while used_names.contains(&name) {
+			let candidate = format!("{}-{}", mount_dir.name, suffix);
+			suffix += 1;
+			if !original_names.contains(candidate.as_str()) {
+				name = candidate;
+			}
+		}
+		used_names.insert(name.clone());
+		resolved.push(MountDir {
+			name,
+			..mount_dir.clone()
+		});
+	}
+	resolved
 }
 
 #[derive(Clone)]
 pub struct Manager {
 	db: DB,
+	backend_factories: Arc<RwLock<BackendFactories>>,
+	/// Storage backends of remote mounts, by source. They are kept around so that connections to
+	/// the remotes can be reused.
+	backends: Arc<Mutex<HashMap<String, Arc<dyn Backend>>>>,
 }
 
 impl Manager {
 	pub fn new(db: DB) -> Self {
-		Self { db }
+		let mut backend_factories = BackendFactories::new();
+		backend_factories.insert("s3".to_owned(), Arc::new(S3::open) as BackendFactory);
+		Self {
+			db,
+			backend_factories: Arc::new(RwLock::new(backend_factories)),
+			backends: Arc::default(),
+		}
+	}
+
+	/// Serves mounts whose source uses the given URI scheme with backends made by this factory.
+	#[cfg(test)]
+	pub fn register_backend(&self, scheme: &str, factory: BackendFactory) {
+		self.backend_factories
+			.write()
+			.unwrap()
+			.insert(scheme.to_owned(), factory);
+		self.backends.lock().unwrap().clear();
 	}
 
 	pub fn get_vfs(&self) -> Result<VFS, Error> {
 		let mount_dirs = self.mount_dirs()?;
+		let mut backends = HashMap::new();
+		for mount_dir in &mount_dirs {
+			if let Some(scheme) = storage::get_scheme(&mount_dir.source) {
+				match self.get_backend(scheme, &mount_dir.source) {
+					Ok(backend) => {
+						backends.insert(mount_dir.name.clone(), backend);
+					}
+					Err(e) => error!(
+						"Could not access storage of mount `{}`: {}",
+						mount_dir.name, e
+					),
+				}
+			}
+		}
 		let mounts = mount_dirs.into_iter().map(|p| p.into()).collect();
-		Ok(VFS::new(mounts))
+		Ok(VFS { mounts, backends })
+	}
+
+	fn get_backend(&self, scheme: &str, source: &str) -> Result<Arc<dyn Backend>, storage::Error> {
+		let mut backends = self.backends.lock().unwrap();
+		if let Some(backend) = backends.get(source) {
+			return Ok(backend.clone());
+		}
+		let factory = self
+			.backend_factories
+			.read()
+			.unwrap()
+			.get(scheme)
+			.cloned()
+			.ok_or_else(|| storage::Error::UnsupportedScheme(scheme.to_owned()))?;
+		let backend = factory(source)?;
+		backends.insert(source.to_owned(), backend.clone());
+		Ok(backend)
 	}
 
 	pub fn mount_dirs(&self) -> Result<Vec<MountDir>, Error> {
 		use self::mount_points::dsl::*;
 		let mut connection = self.db.connect()?;
 		let mount_dirs: Vec<MountDir> = mount_points
-			.select((source, name))
+			.select((
+				source,
+				name,
+				album_art_pattern,
+				reindex_every_n_seconds,
+				hide_from_root,
+			))
+			.order(id)
 			.get_results(&mut connection)?;
 		Ok(mount_dirs)
 	}
@@ -131,12 +263,16 @@ impl Manager {
 mod test {
 
 	use super::*;
+	use crate::app::test::MockBackend;
 
 	#[test]
 	fn converts_virtual_to_real() {
 		let vfs = VFS::new(vec![Mount {
 			name: "root".to_owned(),
 			source: Path::new("test_dir").to_owned(),
+			album_art_pattern: None,
+			reindex_every_n_seconds: None,
+			hide_from_root: false,
 		}]);
 		let real_path: PathBuf = ["test_dir", "somewhere", "something.png"].iter().collect();
 		let virtual_path: PathBuf = ["root", "somewhere", "something.png"].iter().collect();
@@ -149,6 +285,9 @@ mod test {
 		let vfs = VFS::new(vec![Mount {
 			name: "root".to_owned(),
 			source: Path::new("test_dir").to_owned(),
+			album_art_pattern: None,
+			reindex_every_n_seconds: None,
+			hide_from_root: false,
 		}]);
 		let real_path = Path::new("test_dir");
 		let converted_path = vfs.virtual_to_real(Path::new("root")).unwrap();
@@ -160,6 +299,9 @@ mod test {
 		let vfs = VFS::new(vec![Mount {
 			name: "root".to_owned(),
 			source: Path::new("test_dir").to_owned(),
+			album_art_pattern: None,
+			reindex_every_n_seconds: None,
+			hide_from_root: false,
 		}]);
 		let virtual_path: PathBuf = ["root", "somewhere", "something.png"].iter().collect();
 		let real_path: PathBuf = ["test_dir", "somewhere", "something.png"].iter().collect();
@@ -200,9 +342,70 @@ mod test {
 			let mount_dir = MountDir {
 				source: test.to_owned(),
 				name: "name".to_owned(),
+				album_art_pattern: None,
+				reindex_every_n_seconds: None,
+				hide_from_root: false,
 			};
 			let mount: Mount = mount_dir.into();
 			assert_eq!(mount.source, correct_path);
 		}
 	}
+
+	#[test]
+	fn locates_remote_paths() {
+		let mount: Mount = MountDir {
+			source: "mock://bucket/music".to_owned(),
+			name: "root".to_owned(),
+			album_art_pattern: None,
+			reindex_every_n_seconds: None,
+			hide_from_root: false,
+		}
+		.into();
+		assert_eq!(mount.source, PathBuf::from("mock://bucket/music"));
+
+		let backend: Arc<dyn Backend> = MockBackend::new("test-data");
+		let vfs = VFS {
+			mounts: vec![mount],
+			backends: HashMap::from([("root".to_owned(), backend)]),
+		};
+		let real_path = PathBuf::from("mock://bucket/music").join("Khemmis");
+		let virtual_path: PathBuf = ["root", "Khemmis"].iter().collect();
+		assert_eq!(vfs.real_to_virtual(&real_path).unwrap(), virtual_path);
+		assert_eq!(vfs.virtual_to_real(&virtual_path).unwrap(), real_path);
+		assert_eq!(
+			vfs.locate_remote(&real_path).unwrap().path,
+			Path::new("Khemmis")
+		);
+		assert!(vfs.locate_remote(Path::new("/home/music")).is_none());
+	}
+
+	#[test]
+	fn resolves_mount_name_collisions() {
+		let mount_dirs = vec![
+			MountDir {
+				source: "/home/music".to_owned(),
+				name: "music".to_owned(),
+				album_art_pattern: None,
+				reindex_every_n_seconds: None,
+				hide_from_root: false,
+			},
+			MountDir {
+				source: "/mnt/music".to_owned(),
+				name: "music".to_owned(),
+				album_art_pattern: None,
+				reindex_every_n_seconds: None,
+				hide_from_root: false,
+			},
+			MountDir {
+				source: "/mnt/other".to_owned(),
+				name: "music-2".to_owned(),
+				album_art_pattern: None,
+				reindex_every_n_seconds: None,
+				hide_from_root: false,
+			},
+		];
+		let resolved = resolve_name_collisions(&mount_dirs);
+		let names: Vec<&str> = resolved.iter().map(|m| m.name.as_str()).collect();
+		assert_eq!(names, vec!["music", "music-3", "music-2"]);
+	}
 }
@@ -0,0 +1,155 @@
+use rand::Rng;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Amount of data requested from a remote at a time when copying a file locally.
+const COPY_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("No storage backend handles the `{0}` scheme")]
+	UnsupportedScheme(String),
+	#[error("Invalid storage location: `{0}`")]
+	InvalidLocation(String),
+	#[error("Storage request failed with HTTP status code `{0}`")]
+	RequestFailed(u16),
+	#[error("Storage request failed due to a transport error")]
+	RequestTransport,
+	#[error("Unexpected response from storage: {0}")]
+	UnexpectedResponse(String),
+	#[error(transparent)]
+	Io(#[from] io::Error),
+}
+
+/// File or directory listed by a storage backend.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+	/// Location of the entry, relative to the root of its mount.
+	pub path: PathBuf,
+	pub is_dir: bool,
+	/// Size of the file in bytes. Always zero for directories.
+	pub size: u64,
+	/// Opaque identifier of the content of a file, which changes whenever the file is modified.
+	pub etag: Option<String>,
+}
+
+/// Read-only access to the files of a mount which do not live on the local file system. Paths are
+/// relative to the root of the mount, which is an empty path.
+pub trait Backend: Send + Sync {
+	/// Lists the files and directories directly within a directory.
+	fn list(&self, path: &Path) -> Result<Vec<Entry>, Error>;
+	/// Whether a file or a directory exists at this location.
+	fn exists(&self, path: &Path) -> Result<bool, Error>;
+	/// Size of a file, in bytes.
+	fn size(&self, path: &Path) -> Result<u64, Error>;
+	/// Reads up to `length` bytes of a file, starting at `offset`.
+	fn read_range(&self, path: &Path, offset: u64, length: u64) -> Result<Vec<u8>, Error>;
+}
+
+/// Creates the backend serving a mount, from the URI of its source.
+pub type BackendFactory = Arc<dyn Fn(&str) -> Result<Arc<dyn Backend>, Error> + Send + Sync>;
+
+/// Storage backends by URI scheme.
+pub type BackendFactories = HashMap<String, BackendFactory>;
+
+/// Returns the URI scheme of a mount source (`s3` for `s3://bucket/music`), or `None` for
+/// sources on the local file system. Windows drive letters are not mistaken for schemes.
+pub fn get_scheme(source: &str) -> Option<&str> {
+	let (scheme, _) = source.split_once("://")?;
+	let is_scheme = scheme.len() > 1
+		&& scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+		&& scheme
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+	is_scheme.then_some(scheme)
+}
+
+/// File or directory within a mount served by a storage backend.
+#[derive(Clone)]
+pub struct RemoteLocation {
+	pub backend: Arc<dyn Backend>,
+	/// Location relative to the root of the mount.
+	pub path: PathBuf,
+}
+
+impl RemoteLocation {
+	pub fn list(&self) -> Result<Vec<Entry>, Error> {
+		self.backend.list(&self.path)
+	}
+
+	pub fn exists(&self) -> Result<bool, Error> {
+		self.backend.exists(&self.path)
+	}
+
+	pub fn size(&self) -> Result<u64, Error> {
+		self.backend.size(&self.path)
+	}
+
+	pub fn read_range(&self, offset: u64, length: u64) -> Result<Vec<u8>, Error> {
+		self.backend.read_range(&self.path, offset, length)
+	}
+
+	/// Downloads the file to a temporary location, for readers which only work with local files.
+	/// The file extension is preserved.
+	pub fn make_local_copy(&self) -> Result<LocalCopy, Error> {
+		let mut name = format!("polaris-{:016x}", rand::thread_rng().gen::<u64>());
+		if let Some(extension) = self.path.extension() {
+			name.push('.');
+			name.push_str(&extension.to_string_lossy());
+		}
+		let local_copy = LocalCopy {
+			path: std::env::temp_dir().join(name),
+		};
+		let mut file = fs::File::create(&local_copy.path)?;
+		let size = self.size()?;
+		let mut offset = 0;
+		while offset < size {
+			let chunk = self.read_range(offset, COPY_CHUNK_SIZE.min(size - offset))?;
+			if chunk.is_empty() {
+				return Err(Error::UnexpectedResponse(format!(
+					"file ended after {} of {} bytes",
+					offset, size
+				)));
+			}
+			file.write_all(&chunk)?;
+			offset += chunk.len() as u64;
+		}
+		Ok(local_copy)
+	}
+}
+
+/// Temporary copy of a remote file, deleted when dropped.
+pub struct LocalCopy {
+	path: PathBuf,
+}
+
+impl LocalCopy {
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl Drop for LocalCopy {
+	fn drop(&mut self) {
+		fs::remove_file(&self.path).ok();
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+
+	#[test]
+	fn recognizes_remote_sources() {
+		assert_eq!(get_scheme("s3://bucket/music"), Some("s3"));
+		assert_eq!(get_scheme("webdav://host/music"), Some("webdav"));
+		assert_eq!(get_scheme("/home/music"), None);
+		assert_eq!(get_scheme("C://music"), None);
+		assert_eq!(get_scheme("C:\\music"), None);
+		assert_eq!(get_scheme("music://"), Some("music"));
+	}
+}
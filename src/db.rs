@@ -1,6 +1,6 @@
+use diesel::connection::SimpleConnection;
 use diesel::r2d2::{self, ConnectionManager, PooledConnection};
 use diesel::sqlite::SqliteConnection;
-use diesel::RunQueryDsl;
 use diesel_migrations::EmbeddedMigrations;
 use diesel_migrations::MigrationHarness;
 use std::path::{Path, PathBuf};
@@ -11,10 +11,15 @@ pub use self::schema::*;
 
 const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
+/// Number of read-only connections serving collection queries, unless configured otherwise.
+pub const DEFAULT_READ_POOL_SIZE: u32 = 4;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
 	#[error("Could not initialize database connection pool")]
 	ConnectionPoolBuild,
+	#[error("Database path `{0}` does not point to a file")]
+	InvalidPath(PathBuf),
 	#[error("Could not acquire database connection from pool")]
 	ConnectionPool,
 	#[error("Filesystem error for `{0}`: `{1}`")]
@@ -26,6 +31,7 @@ pub enum Error {
 #[derive(Clone)]
 pub struct DB {
 	pool: r2d2::Pool<ConnectionManager<SqliteConnection>>,
+	read_pool: r2d2::Pool<ConnectionManager<SqliteConnection>>,
 }
 
 #[derive(Debug)]
@@ -34,31 +40,75 @@ impl diesel::r2d2::CustomizeConnection<SqliteConnection, diesel::r2d2::Error>
 	for ConnectionCustomizer
 {
 	fn on_acquire(&self, connection: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
-		let query = diesel::sql_query(
-			r#"
+		connection
+			.batch_execute(
+				r#"
 			PRAGMA busy_timeout = 60000;
 			PRAGMA journal_mode = WAL;
 			PRAGMA synchronous = NORMAL;
 			PRAGMA foreign_keys = ON;
 		"#,
-		);
-		query
-			.execute(connection)
+			)
+			.map_err(diesel::r2d2::Error::QueryError)?;
+		Ok(())
+	}
+}
+
+/// Read-only connections let heavy browsing proceed alongside an index update, since WAL mode
+/// allows readers to work while a writer holds the database.
+#[derive(Debug)]
+struct ReadConnectionCustomizer {}
+impl diesel::r2d2::CustomizeConnection<SqliteConnection, diesel::r2d2::Error>
+	for ReadConnectionCustomizer
+{
+	fn on_acquire(&self, connection: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+		// Each statement must run, which a single prepared query would not do
+		connection
+			.batch_execute(
+				r#"
+			PRAGMA busy_timeout = 60000;
+			PRAGMA query_only = ON;
+		"#,
+			)
 			.map_err(diesel::r2d2::Error::QueryError)?;
 		Ok(())
 	}
 }
 
 impl DB {
+	#[cfg(test)]
 	pub fn new(path: &Path) -> Result<DB, Error> {
-		let directory = path.parent().unwrap();
+		Self::with_read_pool_size(path, DEFAULT_READ_POOL_SIZE)
+	}
+
+	/// Opens the database file at `path`, creating it and its parent directories if needed.
+	/// Collection queries are served by `read_pool_size` read-only connections.
+	pub fn with_read_pool_size(path: &Path, read_pool_size: u32) -> Result<DB, Error> {
+		let directory = match path.parent() {
+			Some(directory) if path.file_name().is_some() && !path.is_dir() => directory,
+			_ => return Err(Error::InvalidPath(path.to_owned())),
+		};
 		std::fs::create_dir_all(directory).map_err(|e| Error::Io(directory.to_owned(), e))?;
+		// Surface permission problems up front rather than as a connection pool timeout
+		std::fs::OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(path)
+			.map_err(|e| Error::Io(path.to_owned(), e))?;
 		let manager = ConnectionManager::<SqliteConnection>::new(path.to_string_lossy());
 		let pool = diesel::r2d2::Pool::builder()
 			.connection_customizer(Box::new(ConnectionCustomizer {}))
 			.build(manager)
 			.or(Err(Error::ConnectionPoolBuild))?;
-		let db = DB { pool };
+		// Switches the database to WAL mode before any reader opens it
+		pool.get().or(Err(Error::ConnectionPool))?;
+		let read_manager = ConnectionManager::<SqliteConnection>::new(path.to_string_lossy());
+		let read_pool = diesel::r2d2::Pool::builder()
+			.max_size(read_pool_size.max(1))
+			.connection_customizer(Box::new(ReadConnectionCustomizer {}))
+			.build(read_manager)
+			.or(Err(Error::ConnectionPoolBuild))?;
+		let db = DB { pool, read_pool };
 		db.migrate_up()?;
 		Ok(db)
 	}
@@ -67,6 +117,13 @@ impl DB {
 		self.pool.get().or(Err(Error::ConnectionPool))
 	}
 
+	/// Connection for queries which never write, served from the read-only pool.
+	pub fn connect_read(
+		&self,
+	) -> Result<PooledConnection<ConnectionManager<SqliteConnection>>, Error> {
+		self.read_pool.get().or(Err(Error::ConnectionPool))
+	}
+
 	#[cfg(test)]
 	fn migrate_down(&self) -> Result<(), Error> {
 		let mut connection = self.connect()?;
@@ -96,3 +153,43 @@ fn run_migrations() {
 	db.migrate_down().unwrap();
 	db.migrate_up().unwrap();
 }
+
+#[test]
+fn creates_database_at_configured_path() {
+	use crate::test::*;
+	use crate::test_name;
+	let output_dir = prepare_test_directory(test_name!());
+	let db_path = output_dir
+		.join("volume")
+		.join("index")
+		.join("polaris.sqlite");
+	let db = DB::new(&db_path).unwrap();
+
+	assert!(db_path.is_file());
+	db.connect().unwrap();
+}
+
+#[test]
+fn read_connections_cannot_write() {
+	use crate::test::*;
+	use crate::test_name;
+	use diesel::RunQueryDsl;
+	let output_dir = prepare_test_directory(test_name!());
+	let db = DB::with_read_pool_size(&output_dir.join("db.sqlite"), 2).unwrap();
+
+	let mut connection = db.connect_read().unwrap();
+	diesel::sql_query("SELECT COUNT(*) FROM songs")
+		.execute(&mut connection)
+		.unwrap();
+	diesel::sql_query("DELETE FROM songs")
+		.execute(&mut connection)
+		.unwrap_err();
+}
+
+#[test]
+fn rejects_directory_as_database_path() {
+	use crate::test::*;
+	use crate::test_name;
+	let output_dir = prepare_test_directory(test_name!());
+	assert!(matches!(DB::new(&output_dir), Err(Error::InvalidPath(_))));
+}
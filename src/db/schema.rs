@@ -1,3 +1,20 @@
+table! {
+	bandwidth_usage (id) {
+		id -> Integer,
+		owner -> Integer,
+		period_start -> BigInt,
+		bytes -> BigInt,
+	}
+}
+
+table! {
+	cover_overrides (id) {
+		id -> Integer,
+		path -> Text,
+		file_name -> Text,
+	}
+}
+
 table! {
 	ddns_config (id) {
 		id -> Integer,
@@ -17,6 +34,10 @@ table! {
 		album -> Nullable<Text>,
 		artwork -> Nullable<Text>,
 		date_added -> Integer,
+		artwork_mime -> Nullable<Text>,
+		duration -> Nullable<Integer>,
+		media -> Nullable<Text>,
+		genres -> Nullable<Text>,
 	}
 }
 
@@ -26,6 +47,45 @@ table! {
 		auth_secret -> Binary,
 		index_sleep_duration_seconds -> Integer,
 		index_album_art_pattern -> Text,
+		auto_rename_duplicate_mounts -> Bool,
+		normalize_genres -> Bool,
+		genre_aliases -> Text,
+		fallback_artist -> Text,
+		fallback_album -> Text,
+		track_bandwidth -> Bool,
+		bandwidth_rollover -> Text,
+		scan_on_startup -> Bool,
+		album_artist_grouping -> Text,
+		snapshot_index_updates -> Bool,
+		expose_extra_tags -> Bool,
+		index_max_depth -> Integer,
+		public_base_url -> Text,
+		min_file_size_bytes -> BigInt,
+		artwork_placeholder_path -> Text,
+		skip_hidden_files -> Bool,
+		artist_image_pattern -> Text,
+		merge_duplicate_albums -> Bool,
+		preferred_album_format -> Text,
+		tag_priority -> Text,
+		estimate_missing_durations -> Bool,
+		album_artist_fallback -> Bool,
+		default_page_size -> Integer,
+		max_page_size -> Integer,
+		artwork_priority -> Text,
+		normalize_artist_names -> Bool,
+		artist_name_articles -> Text,
+		directory_thumbnail -> Text,
+		scan_failure_limit -> Integer,
+		analyze_loudness -> Bool,
+		symlinked_duplicates -> Text,
+		max_cover_bytes -> Integer,
+		audiobook_directories -> Text,
+		podcast_directories -> Text,
+		hide_empty_directories -> Bool,
+		prewarm_thumbnails_on_scan -> Bool,
+		metadata_readers -> Text,
+		mount_order -> Text,
+		reindex_on_config_change -> Bool,
 	}
 }
 
@@ -34,6 +94,38 @@ table! {
 		id -> Integer,
 		source -> Text,
 		name -> Text,
+		album_art_pattern -> Nullable<Text>,
+		reindex_every_n_seconds -> Nullable<Integer>,
+		hide_from_root -> Bool,
+	}
+}
+
+table! {
+	play_counts (id) {
+		id -> Integer,
+		owner -> Integer,
+		path -> Text,
+		count -> Integer,
+		last_played -> BigInt,
+	}
+}
+
+table! {
+	plays (id) {
+		id -> Integer,
+		owner -> Integer,
+		path -> Text,
+		played_at -> BigInt,
+	}
+}
+
+table! {
+	playback_positions (id) {
+		id -> Integer,
+		owner -> Integer,
+		path -> Text,
+		seconds -> Integer,
+		updated_at -> BigInt,
 	}
 }
 
@@ -51,6 +143,20 @@ table! {
 		id -> Integer,
 		owner -> Integer,
 		name -> Text,
+		created_at -> BigInt,
+		updated_at -> BigInt,
+	}
+}
+
+table! {
+	shares (id) {
+		id -> Integer,
+		token -> Text,
+		path -> Text,
+		created_at -> BigInt,
+		expires_at -> BigInt,
+		max_downloads -> Nullable<Integer>,
+		download_count -> Integer,
 	}
 }
 
@@ -72,6 +178,38 @@ table! {
 		composer -> Nullable<Text>,
 		genre -> Nullable<Text>,
 		label -> Nullable<Text>,
+		hidden -> Bool,
+		grouping -> Nullable<Text>,
+		work -> Nullable<Text>,
+		movement_name -> Nullable<Text>,
+		movement_number -> Nullable<Integer>,
+		original_genre -> Nullable<Text>,
+		isrc -> Nullable<Text>,
+		catalog_number -> Nullable<Text>,
+		format -> Nullable<Text>,
+		original_year -> Nullable<Integer>,
+		conductor -> Nullable<Text>,
+		remixer -> Nullable<Text>,
+		file_size -> Nullable<BigInt>,
+		extra -> Nullable<Text>,
+		disc_total -> Nullable<Integer>,
+		track_total -> Nullable<Integer>,
+		encoded_by -> Nullable<Text>,
+		encoder_settings -> Nullable<Text>,
+		original_artist -> Nullable<Text>,
+		original_album -> Nullable<Text>,
+		rating -> Nullable<Integer>,
+		effective_album_artist -> Nullable<Text>,
+		language -> Nullable<Text>,
+		media_type -> Nullable<Text>,
+		disc_subtitle -> Nullable<Text>,
+		initial_key -> Nullable<Text>,
+		track_loudness -> Nullable<Integer>,
+		album_loudness -> Nullable<Integer>,
+		duration_source -> Nullable<Text>,
+		content_type -> Text,
+		arranger -> Nullable<Text>,
+		performer -> Nullable<Text>,
 	}
 }
 
@@ -85,19 +223,30 @@ table! {
 		lastfm_session_key -> Nullable<Text>,
 		web_theme_base -> Nullable<Text>,
 		web_theme_accent -> Nullable<Text>,
+		share_activity -> Bool,
 	}
 }
 
+joinable!(bandwidth_usage -> users (owner));
+joinable!(playback_positions -> users (owner));
 joinable!(playlist_songs -> playlists (playlist));
+joinable!(play_counts -> users (owner));
 joinable!(playlists -> users (owner));
+joinable!(plays -> users (owner));
 
 allow_tables_to_appear_in_same_query!(
+	bandwidth_usage,
+	cover_overrides,
 	ddns_config,
 	directories,
 	misc_settings,
 	mount_points,
+	play_counts,
+	playback_positions,
 	playlist_songs,
 	playlists,
+	plays,
+	shares,
 	songs,
 	users,
 );
@@ -12,6 +12,7 @@ use simplelog::{
 };
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 mod app;
 mod db;
@@ -140,14 +141,43 @@ fn main() -> Result<(), Error> {
 	info!("Web client files location is {:#?}", paths.web_dir_path);
 
 	// Create and run app
-	let app = app::App::new(cli_options.port.unwrap_or(5050), paths)?;
+	let app = app::App::new(
+		cli_options.port.unwrap_or(5050),
+		paths,
+		cli_options
+			.db_read_connections
+			.unwrap_or(db::DEFAULT_READ_POOL_SIZE),
+	)?;
+	if cli_options.require_bootstrap_token {
+		if let Some(token) = app
+			.user_manager
+			.require_bootstrap_token()
+			.map_err(app::Error::from)?
+		{
+			info!("Create the first admin user with bootstrap token {}", token);
+		}
+	}
 	app.index.begin_periodic_updates();
 	app.ddns_manager.begin_periodic_updates();
 
 	// Start server
 	info!("Starting up server");
+	let server_options = service::ServerOptions {
+		max_concurrent_streams: cli_options.max_concurrent_streams,
+		request_timeout: cli_options.request_timeout_seconds.map(Duration::from_secs),
+		header_timeout: cli_options.header_timeout_seconds.map(Duration::from_secs),
+		max_connections: cli_options.max_connections,
+		stream_buffer_size: cli_options.stream_buffer_size,
+		tls: match (&cli_options.tls_certificate_path, &cli_options.tls_key_path) {
+			(Some(certificate_path), Some(key_path)) => Some(service::TlsOptions {
+				certificate_path: certificate_path.clone(),
+				key_path: key_path.clone(),
+			}),
+			_ => None,
+		},
+	};
 	std::thread::spawn(move || {
-		let _ = service::run(app);
+		let _ = service::run(app, server_options);
 	});
 
 	// Send readiness notification
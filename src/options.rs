@@ -13,7 +13,16 @@ pub struct CLIOptions {
 	pub web_dir_path: Option<PathBuf>,
 	pub swagger_dir_path: Option<PathBuf>,
 	pub port: Option<u16>,
+	pub max_concurrent_streams: Option<usize>,
+	pub request_timeout_seconds: Option<u64>,
+	pub header_timeout_seconds: Option<u64>,
+	pub max_connections: Option<usize>,
+	pub stream_buffer_size: Option<usize>,
+	pub db_read_connections: Option<u32>,
+	pub tls_certificate_path: Option<PathBuf>,
+	pub tls_key_path: Option<PathBuf>,
 	pub log_level: Option<LevelFilter>,
+	pub require_bootstrap_token: bool,
 }
 
 pub struct Manager {
@@ -45,7 +54,31 @@ impl Manager {
 			web_dir_path: matches.opt_str("w").map(PathBuf::from),
 			swagger_dir_path: matches.opt_str("s").map(PathBuf::from),
 			port: matches.opt_str("p").and_then(|p| p.parse().ok()),
+			max_concurrent_streams: matches
+				.opt_str("max-concurrent-streams")
+				.and_then(|m| m.parse().ok())
+				.filter(|m| *m > 0),
+			request_timeout_seconds: matches
+				.opt_str("request-timeout")
+				.and_then(|t| t.parse().ok()),
+			header_timeout_seconds: matches
+				.opt_str("header-timeout")
+				.and_then(|t| t.parse().ok()),
+			max_connections: matches
+				.opt_str("max-connections")
+				.and_then(|m| m.parse().ok()),
+			stream_buffer_size: matches
+				.opt_str("stream-buffer-size")
+				.and_then(|b| b.parse().ok())
+				.filter(|b| *b > 0),
+			db_read_connections: matches
+				.opt_str("db-read-connections")
+				.and_then(|c| c.parse().ok())
+				.filter(|c| *c > 0),
+			tls_certificate_path: matches.opt_str("tls-certificate").map(PathBuf::from),
+			tls_key_path: matches.opt_str("tls-key").map(PathBuf::from),
 			log_level: matches.opt_str("log-level").and_then(|l| l.parse().ok()),
+			require_bootstrap_token: matches.opt_present("bootstrap-token"),
 		})
 	}
 
@@ -58,7 +91,60 @@ fn get_options() -> getopts::Options {
 	let mut options = getopts::Options::new();
 	options.optopt("c", "config", "set the configuration file", "FILE");
 	options.optopt("p", "port", "set polaris to run on a custom port", "PORT");
-	options.optopt("d", "database", "set the path to index database", "FILE");
+	options.optopt(
+		"",
+		"max-concurrent-streams",
+		"set how many requests an HTTP/2 connection can have in progress at once",
+		"COUNT",
+	);
+	options.optopt(
+		"",
+		"request-timeout",
+		"set how long requests can take before failing (default 300)",
+		"SECONDS",
+	);
+	options.optopt(
+		"",
+		"header-timeout",
+		"set how long clients have to send request headers (default 5)",
+		"SECONDS",
+	);
+	options.optopt(
+		"",
+		"max-connections",
+		"set the maximum number of concurrent connections per worker thread",
+		"COUNT",
+	);
+	options.optopt(
+		"",
+		"stream-buffer-size",
+		"set the size of reads when streaming media files (default 262144)",
+		"BYTES",
+	);
+	options.optopt(
+		"",
+		"db-read-connections",
+		"set how many database connections serve browsing and search (default 4)",
+		"COUNT",
+	);
+	options.optopt(
+		"",
+		"tls-certificate",
+		"serve over HTTPS using this PEM certificate chain",
+		"FILE",
+	);
+	options.optopt(
+		"",
+		"tls-key",
+		"set the PEM private key matching the TLS certificate",
+		"FILE",
+	);
+	options.optopt(
+		"d",
+		"database",
+		"set the path to index database (default from POLARIS_DB_FILE)",
+		"FILE",
+	);
 	options.optopt("w", "web", "set the path to web client files", "DIRECTORY");
 	options.optopt("s", "swagger", "set the path to swagger files", "DIRECTORY");
 	options.optopt(
@@ -76,6 +162,12 @@ fn get_options() -> getopts::Options {
 		"LEVEL",
 	);
 
+	options.optflag(
+		"",
+		"bootstrap-token",
+		"require a one-time token printed to the log to create the first admin",
+	);
+
 	#[cfg(unix)]
 	options.optflag(
 		"f",
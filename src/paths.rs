@@ -79,6 +79,9 @@ impl Paths {
 
 	pub fn new(cli_options: &CLIOptions) -> Self {
 		let mut paths = Self::from_build();
+		if let Some(path) = std::env::var_os("POLARIS_DB_FILE") {
+			paths.db_file_path = PathBuf::from(path);
+		}
 		if let Some(path) = &cli_options.cache_dir_path {
 			paths.cache_dir_path = path.clone();
 		}
@@ -1,15 +1,28 @@
 use actix_web::{
-	dev::Service,
+	dev::{Server, Service},
 	middleware::{Compress, Logger, NormalizePath},
 	rt::System,
 	web::{self, ServiceConfig},
 	App as ActixApp, HttpServer,
 };
-use log::error;
+use log::{error, info};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use crate::app::App;
 
+use streams::{ConnectionStreams, StreamLimit};
+use timeout::RequestTimeout;
+
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+const DEFAULT_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
 mod api;
+mod streams;
+mod timeout;
 
 #[cfg(test)]
 pub mod test;
@@ -17,11 +30,17 @@ pub mod test;
 pub fn make_config(app: App) -> impl FnOnce(&mut ServiceConfig) + Clone {
 	move |cfg: &mut ServiceConfig| {
 		cfg.app_data(web::Data::new(app.index))
+			.app_data(web::Data::new(app.archive_manager))
+			.app_data(web::Data::new(app.bandwidth_manager))
 			.app_data(web::Data::new(app.config_manager))
+			.app_data(web::Data::new(app.cover_manager))
 			.app_data(web::Data::new(app.ddns_manager))
 			.app_data(web::Data::new(app.lastfm_manager))
+			.app_data(web::Data::new(app.play_count_manager))
 			.app_data(web::Data::new(app.playlist_manager))
+			.app_data(web::Data::new(app.progress_manager))
 			.app_data(web::Data::new(app.settings_manager))
+			.app_data(web::Data::new(app.share_manager))
 			.app_data(web::Data::new(app.thumbnail_manager))
 			.app_data(web::Data::new(app.user_manager))
 			.app_data(web::Data::new(app.vfs_manager))
@@ -43,34 +62,306 @@ pub fn make_config(app: App) -> impl FnOnce(&mut ServiceConfig) + Clone {
 	}
 }
 
-pub fn run(app: App) -> Result<(), std::io::Error> {
+#[derive(Clone, Debug, Default)]
+pub struct ServerOptions {
+	/// Maximum number of requests a single connection can have in progress at once. Only HTTP/2
+	/// clients send several requests at a time, others wait for a response before the next request.
+	pub max_concurrent_streams: Option<usize>,
+	pub request_timeout: Option<Duration>,
+	/// How long clients have to send the headers of a new connection's first request.
+	pub header_timeout: Option<Duration>,
+	/// Maximum number of concurrent connections handled by each worker thread.
+	pub max_connections: Option<usize>,
+	/// Size of the reads used to stream media files (defaults to 256KiB).
+	pub stream_buffer_size: Option<usize>,
+	pub tls: Option<TlsOptions>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TlsOptions {
+	pub certificate_path: PathBuf,
+	pub key_path: PathBuf,
+}
+
+fn load_tls_config(options: &TlsOptions) -> Result<rustls::ServerConfig, io::Error> {
+	let certificate_file = File::open(&options.certificate_path)?;
+	let certificates = rustls_pemfile::certs(&mut BufReader::new(certificate_file))?
+		.into_iter()
+		.map(rustls::Certificate)
+		.collect();
+
+	let key_file = File::open(&options.key_path)?;
+	let key = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(key_file))?
+		.into_iter()
+		.next()
+		.map(rustls::PrivateKey)
+		.ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("No PKCS8 private key found in {:?}", options.key_path),
+			)
+		})?;
+
+	rustls::ServerConfig::builder()
+		.with_safe_defaults()
+		.with_no_client_auth()
+		.with_single_cert(certificates, key)
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn run(app: App, options: ServerOptions) -> Result<(), io::Error> {
 	let address = ("0.0.0.0", app.port);
-	System::new().block_on(
-		HttpServer::new(move || {
-			ActixApp::new()
-				.wrap(Logger::default())
-				.wrap_fn(|req, srv| {
-					// For some reason, actix logs error as DEBUG level.
-					// This logs them as ERROR level
-					// See https://github.com/actix/actix-web/issues/2637
-					let response_future = srv.call(req);
-					async {
-						let response = response_future.await?;
-						if let Some(error) = response.response().error() {
-							error!("{}", error);
-						}
-						Ok(response)
-					}
-				})
-				.wrap(Compress::default())
-				.configure(make_config(app.clone()))
-		})
-		.disable_signals()
-		.bind(address)
+	let system = System::new();
+	let server = TcpListener::bind(address)
+		.and_then(|listener| make_server(app, &options, listener))
 		.map_err(|e| {
 			error!("Error starting HTTP server: {:?}", e);
 			e
-		})?
-		.run(),
-	)
+		})?;
+
+	system.block_on(server)
+}
+
+/// Slow or idle clients are dropped by the header timeout, and by the request timeout while
+/// their request body is being read. Neither applies once a response has started streaming.
+fn make_server(
+	app: App,
+	options: &ServerOptions,
+	listener: TcpListener,
+) -> Result<Server, io::Error> {
+	let request_timeout = options.request_timeout.unwrap_or(DEFAULT_REQUEST_TIMEOUT);
+	let stream_buffer_size = options
+		.stream_buffer_size
+		.map(api::StreamBufferSize)
+		.unwrap_or_default();
+	let tls_enabled = api::TlsEnabled(options.tls.is_some());
+	let mut server = HttpServer::new(move || {
+		ActixApp::new()
+			.wrap(RequestTimeout::new(request_timeout))
+			.wrap(StreamLimit)
+			.wrap(Logger::default())
+			.wrap_fn(|req, srv| {
+				// For some reason, actix logs error as DEBUG level.
+				// This logs them as ERROR level
+				// See https://github.com/actix/actix-web/issues/2637
+				let response_future = srv.call(req);
+				async {
+					let response = response_future.await?;
+					if let Some(error) = response.response().error() {
+						error!("{}", error);
+					}
+					Ok(response)
+				}
+			})
+			.wrap(Compress::default())
+			.app_data(web::Data::new(stream_buffer_size))
+			.app_data(web::Data::new(tls_enabled))
+			.configure(make_config(app.clone()))
+	})
+	.disable_signals()
+	.client_request_timeout(options.header_timeout.unwrap_or(DEFAULT_HEADER_TIMEOUT));
+
+	if let Some(max_streams) = options.max_concurrent_streams {
+		server = server.on_connect(move |_, extensions| {
+			extensions.insert(ConnectionStreams::new(max_streams));
+		});
+	}
+
+	if let Some(max_connections) = options.max_connections {
+		server = server.max_connections(max_connections);
+	}
+
+	let server = match &options.tls {
+		Some(tls_options) => {
+			info!("Serving over HTTPS");
+			load_tls_config(tls_options).and_then(|c| server.listen_rustls_0_21(listener, c))
+		}
+		// Plain HTTP/1.1 clients are unaffected, HTTP/2 clients can connect with prior knowledge (h2c)
+		None => server.listen_auto_h2c(listener),
+	}?;
+
+	Ok(server.run())
+}
+
+#[cfg(test)]
+mod tls_test {
+	use super::*;
+
+	#[test]
+	fn can_load_tls_config() {
+		let options = TlsOptions {
+			certificate_path: ["test-data", "tls", "certificate.pem"].iter().collect(),
+			key_path: ["test-data", "tls", "key.pem"].iter().collect(),
+		};
+		assert!(load_tls_config(&options).is_ok());
+	}
+
+	#[test]
+	fn rejects_tls_key_in_wrong_format() {
+		let options = TlsOptions {
+			certificate_path: ["test-data", "tls", "certificate.pem"].iter().collect(),
+			key_path: ["test-data", "tls", "certificate.pem"].iter().collect(),
+		};
+		assert!(load_tls_config(&options).is_err());
+	}
+}
+
+#[cfg(test)]
+mod connection_test {
+	use actix_web::dev::ServerHandle;
+	use std::io::{Read, Write};
+	use std::net::{SocketAddr, TcpStream};
+	use std::sync::{mpsc, Arc};
+
+	use super::*;
+	use crate::db;
+	use crate::paths::Paths;
+	use crate::test::prepare_test_directory;
+	use crate::test_name;
+
+	/// Serves a new app on an ephemeral port, from a thread of its own.
+	fn start_server(test_name: &str, options: ServerOptions) -> (SocketAddr, ServerHandle) {
+		let output_dir = prepare_test_directory(test_name);
+		let paths = Paths {
+			cache_dir_path: output_dir.clone(),
+			config_file_path: None,
+			db_file_path: output_dir.join("db.sqlite"),
+			#[cfg(unix)]
+			pid_file_path: output_dir.join("polaris.pid"),
+			log_file_path: None,
+			swagger_dir_path: ["docs", "swagger"].iter().collect(),
+			web_dir_path: ["test-data", "web"].iter().collect(),
+		};
+		let app = App::new(5050, paths, db::DEFAULT_READ_POOL_SIZE).unwrap();
+
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let address = listener.local_addr().unwrap();
+		let (sender, receiver) = mpsc::channel();
+		std::thread::spawn(move || {
+			let system = System::new();
+			let server = make_server(app, &options, listener).unwrap();
+			sender.send(server.handle()).unwrap();
+			system.block_on(server).unwrap();
+		});
+		(address, receiver.recv().unwrap())
+	}
+
+	#[test]
+	fn slow_headers_are_dropped() {
+		let options = ServerOptions {
+			header_timeout: Some(Duration::from_millis(500)),
+			max_connections: Some(16),
+			..Default::default()
+		};
+		let (address, handle) = start_server(&test_name!(), options);
+
+		let mut slow_client = TcpStream::connect(address).unwrap();
+		slow_client
+			.set_read_timeout(Some(Duration::from_secs(10)))
+			.unwrap();
+		slow_client
+			.write_all(b"GET /api/version HTTP/1.1\r\n")
+			.unwrap();
+		let mut response = Vec::new();
+		if let Err(e) = slow_client.read_to_end(&mut response) {
+			// A reset also means the connection was dropped, as opposed to timing out client-side
+			assert_eq!(e.kind(), io::ErrorKind::ConnectionReset);
+		}
+		let response = String::from_utf8_lossy(&response);
+		assert!(response.is_empty() || response.starts_with("HTTP/1.1 408"));
+
+		let mut client = TcpStream::connect(address).unwrap();
+		client
+			.set_read_timeout(Some(Duration::from_secs(10)))
+			.unwrap();
+		client
+			.write_all(b"GET /api/version HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+			.unwrap();
+		let mut response = String::new();
+		client.read_to_string(&mut response).unwrap();
+		assert!(response.starts_with("HTTP/1.1 200"));
+
+		System::new().block_on(handle.stop(false));
+	}
+
+	#[test]
+	fn serves_requests_over_tls() {
+		let certificate_path: PathBuf = ["test-data", "tls", "certificate.pem"].iter().collect();
+		let options = ServerOptions {
+			tls: Some(TlsOptions {
+				certificate_path: certificate_path.clone(),
+				key_path: ["test-data", "tls", "key.pem"].iter().collect(),
+			}),
+			..Default::default()
+		};
+		let (address, handle) = start_server(&test_name!(), options);
+
+		// The self-signed test certificate is the only one trusted
+		let mut roots = rustls::RootCertStore::empty();
+		let certificate_file = File::open(certificate_path).unwrap();
+		for certificate in rustls_pemfile::certs(&mut BufReader::new(certificate_file)).unwrap() {
+			roots.add(&rustls::Certificate(certificate)).unwrap();
+		}
+		let config = rustls::ClientConfig::builder()
+			.with_safe_defaults()
+			.with_root_certificates(roots)
+			.with_no_client_auth();
+		let server_name = rustls::ServerName::try_from("localhost").unwrap();
+		let connection = rustls::ClientConnection::new(Arc::new(config), server_name).unwrap();
+		let socket = TcpStream::connect(address).unwrap();
+		socket
+			.set_read_timeout(Some(Duration::from_secs(10)))
+			.unwrap();
+		let mut client = rustls::StreamOwned::new(connection, socket);
+
+		client
+			.write_all(b"GET /api/version HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+			.unwrap();
+		let mut response = Vec::new();
+		// The server may close the connection without notifying the TLS session first
+		let _ = client.read_to_end(&mut response);
+		let response = String::from_utf8_lossy(&response);
+		assert!(response.starts_with("HTTP/1.1 200"));
+		assert!(response.contains("\"major\""));
+
+		System::new().block_on(handle.stop(false));
+	}
+
+	#[test]
+	fn serves_several_h2c_requests_over_one_connection() {
+		let options = ServerOptions {
+			max_concurrent_streams: Some(1),
+			..Default::default()
+		};
+		let (address, handle) = start_server(&test_name!(), options);
+
+		System::new().block_on(async move {
+			// HTTP/2 with prior knowledge, as opposed to upgrading an HTTP/1.1 connection
+			let socket = actix_web::rt::net::TcpStream::connect(address)
+				.await
+				.unwrap();
+			let (mut client, connection) = h2::client::handshake(socket).await.unwrap();
+			actix_web::rt::spawn(async move {
+				connection.await.ok();
+			});
+
+			// Both requests are in flight at once, beyond the limit of streams handled concurrently
+			let mut responses = Vec::new();
+			for _ in 0..2 {
+				client = client.ready().await.unwrap();
+				let request = http::Request::get(format!("http://{}/api/version", address))
+					.body(())
+					.unwrap();
+				let (response, _) = client.send_request(request, true).unwrap();
+				responses.push(response);
+			}
+			for response in responses {
+				let response = response.await.unwrap();
+				assert_eq!(response.status(), http::StatusCode::OK);
+				assert_eq!(response.version(), http::Version::HTTP_2);
+			}
+
+			handle.stop(false).await;
+		});
+	}
 }
@@ -1,46 +1,73 @@
 use actix_files::NamedFile;
-use actix_web::body::BoxBody;
-use actix_web::http::header::ContentEncoding;
+use actix_web::body::{BodySize, BodyStream, BoxBody, MessageBody, SizedStream};
+use actix_web::http::header::{self, ContentEncoding, Header};
 use actix_web::{
 	delete,
 	dev::Payload,
 	error::{ErrorForbidden, ErrorInternalServerError, ErrorUnauthorized},
 	get,
 	http::StatusCode,
-	post, put,
-	web::{self, Data, Json, JsonConfig, ServiceConfig},
-	FromRequest, HttpRequest, HttpResponse, Responder, ResponseError,
+	patch, post, put, route,
+	web::{self, Bytes, Data, Json, JsonConfig, ServiceConfig},
+	CustomizeResponder, Either, FromRequest, HttpRequest, HttpResponse, Responder, ResponseError,
 };
 use actix_web_httpauth::extractors::bearer::BearerAuth;
 use base64::prelude::*;
 use futures_util::future::err;
-use percent_encoding::percent_decode_str;
+use futures_util::stream::{self, Stream};
+use log::error;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
 use std::future::Future;
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::str;
+use std::task::{Context, Poll};
 
 use crate::app::{
-	config, ddns,
+	archive, bandwidth, config, cover, ddns,
 	index::{self, Index},
-	lastfm, playlist, settings, thumbnail, user,
-	vfs::{self, MountDir},
+	lastfm, lyrics, play_count, playlist, progress, settings, share, thumbnail, user,
+	vfs::{self, storage::RemoteLocation, MountDir},
 };
 use crate::service::{dto, error::*};
+use crate::utils;
+
+/// Set on thumbnail responses which show the configured placeholder instead of actual artwork.
+const PLACEHOLDER_HEADER: &str = "X-Polaris-Placeholder";
+
+/// Set on collection responses to the generation of the index they were read from.
+const INDEX_GENERATION_HEADER: &str = "X-Index-Generation";
+
+/// Number of plays read from the database at a time when exporting play history.
+const HISTORY_PAGE_SIZE: usize = 500;
+
+/// How long signed stream URLs remain valid, unless requested otherwise.
+const DEFAULT_STREAM_URL_TTL_SECONDS: u64 = 60 * 60;
+
+/// Longest lifetime which can be requested for a signed stream URL.
+const MAX_STREAM_URL_TTL_SECONDS: u64 = 24 * 60 * 60;
 
 pub fn make_config() -> impl FnOnce(&mut ServiceConfig) + Clone {
 	move |cfg: &mut ServiceConfig| {
 		let megabyte = 1024 * 1024;
 		cfg.app_data(JsonConfig::default().limit(4 * megabyte)) // 4MB
 			.service(version)
+			.service(get_capabilities)
 			.service(initial_setup)
+			.service(bootstrap_admin)
 			.service(apply_config)
+			.service(patch_config)
+			.service(export_config)
+			.service(import_config)
 			.service(get_settings)
+			.service(get_bandwidth_usage)
 			.service(put_settings)
 			.service(list_mount_dirs)
 			.service(put_mount_dirs)
 			.service(get_ddns_config)
 			.service(put_ddns_config)
+			.service(test_ddns_config)
 			.service(list_users)
 			.service(create_user)
 			.service(update_user)
@@ -48,26 +75,86 @@ pub fn make_config() -> impl FnOnce(&mut ServiceConfig) + Clone {
 			.service(get_preferences)
 			.service(put_preferences)
 			.service(trigger_index)
+			.service(get_index_status)
+			.service(get_index_errors)
+			.service(get_index_events)
+			.service(refresh_index)
+			.service(lint_metadata)
+			.service(raw_metadata)
+			.service(rebuild_index)
+			.service(pause_index)
+			.service(resume_index)
+			.service(cancel_index)
 			.service(login)
+			.service(list_mounts)
 			.service(browse_root)
 			.service(browse)
 			.service(flatten_root)
 			.service(flatten)
+			.service(get_song)
+			.service(get_song_siblings)
+			.service(get_songs)
+			.service(ancestors)
+			.service(mark_played)
+			.service(export_history)
+			.service(get_activity)
+			.service(set_progress)
+			.service(get_progress)
+			.service(recent_progress)
+			.service(top)
 			.service(random)
+			.service(shuffle)
 			.service(recent)
+			.service(artists)
+			.service(get_artist_image)
+			.service(years)
+			.service(groupings)
+			.service(grouping)
+			.service(labels)
+			.service(get_label)
+			.service(languages)
+			.service(language)
+			.service(decade)
+			.service(format_stats)
+			.service(missing_artwork)
 			.service(search_root)
 			.service(search)
 			.service(get_audio)
+			.service(get_stream_url)
+			.service(get_album)
+			.service(play_album)
+			.service(get_zip)
 			.service(get_thumbnail)
+			.service(set_cover)
+			.service(clear_cover)
+			.service(prewarm_thumbnails)
+			.service(get_thumbnail_prewarm_progress)
+			.service(cancel_thumbnail_prewarm)
+			.service(get_lyrics)
 			.service(list_playlists)
 			.service(save_playlist)
+			.service(begin_playlist_upload)
+			.service(append_playlist_upload)
+			.service(commit_playlist_upload)
 			.service(read_playlist)
+			.service(read_playlist_page)
+			.service(export_playlist)
+			.service(move_playlist_track)
+			.service(playlist_orphans)
+			.service(prune_playlist)
 			.service(delete_playlist)
+			.service(delete_playlists)
 			.service(lastfm_now_playing)
 			.service(lastfm_scrobble)
 			.service(lastfm_link_token)
 			.service(lastfm_link)
-			.service(lastfm_unlink);
+			.service(lastfm_unlink)
+			.service(list_shares)
+			.service(create_share)
+			.service(delete_share)
+			.service(browse_share_root)
+			.service(browse_share)
+			.service(get_share_audio);
 	}
 }
 
@@ -76,9 +163,16 @@ impl ResponseError for APIError {
 		match self {
 			APIError::AuthorizationTokenEncoding => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::AdminPermissionRequired => StatusCode::UNAUTHORIZED,
+			APIError::ArchiveInvalidPath => StatusCode::BAD_REQUEST,
+			APIError::ArchiveTooLarge => StatusCode::INTERNAL_SERVER_ERROR,
+			APIError::ArtistImageNotFound => StatusCode::NOT_FOUND,
 			APIError::AudioFileIOError => StatusCode::NOT_FOUND,
 			APIError::AuthenticationRequired => StatusCode::UNAUTHORIZED,
 			APIError::BrancaTokenEncoding => StatusCode::INTERNAL_SERVER_ERROR,
+			APIError::CoverContentBase64DecodeError => StatusCode::BAD_REQUEST,
+			APIError::CoverImageDecoding(_) => StatusCode::BAD_REQUEST,
+			APIError::CoverNotFound => StatusCode::NOT_FOUND,
+			APIError::CoverTooLarge(_, _) => StatusCode::PAYLOAD_TOO_LARGE,
 			APIError::DdnsUpdateQueryFailed(s) => {
 				StatusCode::from_u16(*s).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
 			}
@@ -89,6 +183,10 @@ impl ResponseError for APIError {
 			APIError::EmptyUsername => StatusCode::BAD_REQUEST,
 			APIError::IncorrectCredentials => StatusCode::UNAUTHORIZED,
 			APIError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+			APIError::InvalidConfig(_) => StatusCode::UNPROCESSABLE_ENTITY,
+			APIError::InvalidDecade(_) => StatusCode::BAD_REQUEST,
+			APIError::LyricsId3Decoding(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
+			APIError::LyricsNotFound => StatusCode::NOT_FOUND,
 			APIError::Io(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::LastFMAccountNotLinked => StatusCode::NO_CONTENT,
 			APIError::LastFMLinkContentBase64DecodeError => StatusCode::BAD_REQUEST,
@@ -99,14 +197,28 @@ impl ResponseError for APIError {
 			APIError::OwnAdminPrivilegeRemoval => StatusCode::CONFLICT,
 			APIError::PasswordHashing => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::PlaylistNotFound => StatusCode::NOT_FOUND,
+			APIError::PlaybackPositionNotFound => StatusCode::NOT_FOUND,
+			APIError::PlaylistInvalidTracks(_) => StatusCode::BAD_REQUEST,
+			APIError::PlaylistTrackIndexOutOfRange(_) => StatusCode::BAD_REQUEST,
+			APIError::PlaylistSaveSessionNotFound => StatusCode::NOT_FOUND,
+			APIError::PlaylistNameConflict(_) => StatusCode::CONFLICT,
+			APIError::RequestTimeout => StatusCode::GATEWAY_TIMEOUT,
 			APIError::Settings(_) => StatusCode::INTERNAL_SERVER_ERROR,
+			APIError::ShareInvalidExpiry => StatusCode::BAD_REQUEST,
+			APIError::ShareExpired => StatusCode::GONE,
+			APIError::ShareDownloadLimitReached => StatusCode::FORBIDDEN,
+			APIError::ShareNotFound => StatusCode::NOT_FOUND,
+			APIError::SharePathOutsideScope => StatusCode::FORBIDDEN,
 			APIError::SongMetadataNotFound => StatusCode::NOT_FOUND,
+			APIError::ThumbnailArtworkTooLarge(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::ThumbnailFlacDecoding(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::ThumbnailFileIOError => StatusCode::NOT_FOUND,
 			APIError::ThumbnailId3Decoding(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::ThumbnailImageDecoding(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::ThumbnailMp4Decoding(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
+			APIError::ThumbnailPrewarmInProgress => StatusCode::CONFLICT,
 			APIError::TomlDeserialization(_) => StatusCode::INTERNAL_SERVER_ERROR,
+			APIError::UnsupportedCoverFormat => StatusCode::UNSUPPORTED_MEDIA_TYPE,
 			APIError::UnsupportedThumbnailFormat(_) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::UserNotFound => StatusCode::NOT_FOUND,
 			APIError::VFSPathNotFound => StatusCode::NOT_FOUND,
@@ -114,10 +226,24 @@ impl ResponseError for APIError {
 	}
 
 	fn error_response(&self) -> HttpResponse<BoxBody> {
-		HttpResponse::new(self.status_code())
+		match self {
+			APIError::InvalidConfig(problems) => {
+				let problems: Vec<dto::ConfigProblem> =
+					problems.iter().cloned().map(|p| p.into()).collect();
+				HttpResponse::build(self.status_code()).json(problems)
+			}
+			_ => HttpResponse::new(self.status_code()),
+		}
 	}
 }
 
+/// Name of the cookie which can hold an authentication token, as an alternative to the
+/// `Authorization` header or the `auth_token` query parameter.
+const AUTH_COOKIE_NAME: &str = "auth_token";
+
+/// Authenticated user. Tokens are looked for in the `auth_token` query parameter, then in the
+/// `Authorization` header, then in the `auth_token` cookie. The first valid token wins, so that an
+/// invalid or outdated token in one place does not shadow a valid one found later.
 #[derive(Debug)]
 struct Auth {
 	username: String,
@@ -136,33 +262,44 @@ impl FromRequest for Auth {
 		let bearer_auth_future = BearerAuth::from_request(request, payload);
 		let query_params_future =
 			web::Query::<dto::AuthQueryParameters>::from_request(request, payload);
+		let auth_cookie = request.cookie(AUTH_COOKIE_NAME);
 
 		Box::pin(async move {
-			// Auth via bearer token in query parameter
+			let mut auth_tokens = Vec::new();
 			if let Ok(query) = query_params_future.await {
-				let auth_token = user::AuthToken(query.auth_token.clone());
-				let authorization = block(move || {
-					user_manager.authenticate(&auth_token, user::AuthorizationScope::PolarisAuth)
-				})
-				.await?;
-				return Ok(Auth {
-					username: authorization.username,
-				});
+				auth_tokens.push(query.auth_token.clone());
 			}
-
-			// Auth via bearer token in authorization header
 			if let Ok(bearer_auth) = bearer_auth_future.await {
-				let auth_token = user::AuthToken(bearer_auth.token().to_owned());
+				auth_tokens.push(bearer_auth.token().to_owned());
+			}
+			if let Some(cookie) = auth_cookie {
+				auth_tokens.push(cookie.value().to_owned());
+			}
+
+			let mut first_error = None;
+			for auth_token in auth_tokens {
+				let user_manager = user_manager.clone();
+				let auth_token = user::AuthToken(auth_token);
 				let authorization = block(move || {
 					user_manager.authenticate(&auth_token, user::AuthorizationScope::PolarisAuth)
 				})
-				.await?;
-				return Ok(Auth {
-					username: authorization.username,
-				});
+				.await;
+				match authorization {
+					Ok(authorization) => {
+						return Ok(Auth {
+							username: authorization.username,
+						})
+					}
+					Err(e) => {
+						first_error.get_or_insert(e);
+					}
+				}
 			}
 
-			Err(ErrorUnauthorized(APIError::AuthenticationRequired))
+			match first_error {
+				Some(e) => Err(e.into()),
+				None => Err(ErrorUnauthorized(APIError::AuthenticationRequired)),
+			}
 		})
 	}
 }
@@ -189,7 +326,7 @@ impl FromRequest for AdminRights {
 			let user_count = block(move || user_manager_count.count()).await;
 			match user_count {
 				Err(e) => return Err(e.into()),
-				Ok(0) => return Ok(AdminRights { auth: None }),
+				Ok(0) if user_manager.allows_open_setup() => return Ok(AdminRights { auth: None }),
 				_ => (),
 			};
 
@@ -205,13 +342,51 @@ impl FromRequest for AdminRights {
 	}
 }
 
+/// Size of the reads used when streaming media files.
+///
+/// Each chunk costs one read (and one blocking task) on the server, so larger buffers
+/// reduce per-chunk overhead and help saturate fast local networks. The tradeoff is that
+/// every ongoing stream holds a buffer of this size in memory, which adds up on small devices
+/// serving many concurrent clients.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StreamBufferSize(pub usize);
+
+impl Default for StreamBufferSize {
+	fn default() -> Self {
+		Self(256 * 1024)
+	}
+}
+
+/// Whether the server accepts connections over HTTPS.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TlsEnabled(pub bool);
+
 struct MediaFile {
 	named_file: NamedFile,
+	start_offset: Option<u64>,
+	bandwidth_tracker: Option<BandwidthTracker>,
 }
 
 impl MediaFile {
 	fn new(named_file: NamedFile) -> Self {
-		Self { named_file }
+		Self {
+			named_file,
+			start_offset: None,
+			bandwidth_tracker: None,
+		}
+	}
+
+	/// Counts the bytes of this file sent to the user.
+	fn with_bandwidth_tracker(mut self, bandwidth_tracker: BandwidthTracker) -> Self {
+		self.bandwidth_tracker = Some(bandwidth_tracker);
+		self
+	}
+
+	/// Serves the file as a partial response starting at the given byte offset,
+	/// regardless of the range requested by the client.
+	fn with_start_offset(mut self, start_offset: u64) -> Self {
+		self.start_offset = Some(start_offset);
+		self
 	}
 }
 
@@ -219,12 +394,396 @@ impl Responder for MediaFile {
 	type Body = BoxBody;
 
 	fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+		let MediaFile {
+			named_file,
+			start_offset,
+			bandwidth_tracker,
+		} = self;
+		let path = named_file.path().to_owned();
+
+		// Re-stat the file right before streaming it, in case it was removed or truncated since it was opened
+		let named_file = match std::fs::metadata(&path) {
+			Ok(m) if m.len() == named_file.metadata().len() => named_file,
+			Ok(_) => match NamedFile::open(&path) {
+				Ok(f) => f,
+				Err(e) => {
+					error!("Could not reopen media file `{}`: {}", path.display(), e);
+					return HttpResponse::new(StatusCode::NOT_FOUND);
+				}
+			},
+			Err(e) => {
+				error!(
+					"Media file `{}` is no longer available: {}",
+					path.display(),
+					e
+				);
+				return HttpResponse::new(StatusCode::NOT_FOUND);
+			}
+		};
+
+		let buffer_size = req
+			.app_data::<Data<StreamBufferSize>>()
+			.map(|b| *b.get_ref())
+			.unwrap_or_default();
+
+		if let Some(start_offset) = start_offset {
+			return respond_from_offset(
+				named_file,
+				start_offset,
+				true,
+				buffer_size,
+				bandwidth_tracker,
+			);
+		}
+
+		// Some players request `bytes=0-` and expect a regular response, which is also valid for other clients
+		if requests_whole_file(req, named_file.metadata().len()) {
+			return respond_from_offset(named_file, 0, false, buffer_size, bandwidth_tracker);
+		}
+
 		// Intentionally turn off content encoding for media files because:
 		// 1. There is little value in compressing files that are already compressed (mp3, jpg, etc.)
 		// 2. The Content-Length header is incompatible with content encoding (other than identity), and can be valuable for clients
-		self.named_file
+		named_file
 			.set_content_encoding(ContentEncoding::Identity)
 			.into_response(req)
+			.map_body(|_, body| {
+				BoxBody::new(MediaFileBody {
+					path,
+					inner: body,
+					bandwidth_tracker,
+				})
+			})
+	}
+}
+
+/// Whether the request asks for a single range covering the entire file, like `bytes=0-`.
+fn requests_whole_file(req: &HttpRequest, length: u64) -> bool {
+	match header::Range::parse(req) {
+		Ok(header::Range::Bytes(ranges)) => match ranges.as_slice() {
+			[header::ByteRangeSpec::From(0)] => true,
+			[header::ByteRangeSpec::FromTo(0, end)] => end.saturating_add(1) >= length,
+			_ => false,
+		},
+		_ => false,
+	}
+}
+
+/// Returns whether a request picks up a download where an earlier request left off.
+fn resumes_download(req: &HttpRequest) -> bool {
+	match header::Range::parse(req) {
+		Ok(header::Range::Bytes(ranges)) => match ranges.first() {
+			Some(header::ByteRangeSpec::From(start)) => *start > 0,
+			Some(header::ByteRangeSpec::FromTo(start, _)) => *start > 0,
+			Some(header::ByteRangeSpec::Last(_)) => true,
+			None => false,
+		},
+		_ => false,
+	}
+}
+
+/// Streams a file starting at the given byte offset, either as partial content
+/// or as a regular response when the offset is the start of the file.
+fn respond_from_offset(
+	named_file: NamedFile,
+	start_offset: u64,
+	partial: bool,
+	buffer_size: StreamBufferSize,
+	bandwidth_tracker: Option<BandwidthTracker>,
+) -> HttpResponse {
+	let path = named_file.path().to_owned();
+	let length = named_file.metadata().len();
+	if partial && start_offset >= length {
+		return HttpResponse::new(StatusCode::RANGE_NOT_SATISFIABLE);
+	}
+
+	let file = match named_file.file().try_clone() {
+		Ok(f) => f,
+		Err(e) => {
+			error!("Could not read media file `{}`: {}", path.display(), e);
+			return HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR);
+		}
+	};
+
+	let content_length = length - start_offset;
+	let body = SizedStream::new(
+		content_length,
+		read_file_range(file, start_offset, content_length, buffer_size),
+	);
+	let mut response = if partial {
+		let mut response = HttpResponse::PartialContent();
+		response.insert_header(header::ContentRange(header::ContentRangeSpec::Bytes {
+			range: Some((start_offset, length - 1)),
+			instance_length: Some(length),
+		}));
+		response
+	} else {
+		HttpResponse::Ok()
+	};
+	response
+		.content_type(named_file.content_type().clone())
+		.insert_header((header::CONTENT_ENCODING, "identity"))
+		.insert_header((header::ACCEPT_RANGES, "bytes"))
+		.body(MediaFileBody {
+			path,
+			inner: BoxBody::new(body),
+			bandwidth_tracker,
+		})
+}
+
+fn read_file_range(
+	file: std::fs::File,
+	offset: u64,
+	length: u64,
+	buffer_size: StreamBufferSize,
+) -> impl Stream<Item = Result<Bytes, io::Error>> {
+	let buffer_size = buffer_size.0.max(1) as u64;
+	stream::try_unfold(
+		(file, offset, length),
+		move |(mut file, offset, remaining)| async move {
+			if remaining == 0 {
+				return Ok(None);
+			}
+			let chunk_size = remaining.min(buffer_size);
+			let (file, chunk) = web::block(move || -> io::Result<_> {
+				let mut chunk = vec![0; chunk_size as usize];
+				file.seek(SeekFrom::Start(offset))?;
+				file.read_exact(&mut chunk)?;
+				Ok((file, Bytes::from(chunk)))
+			})
+			.await
+			.map_err(io::Error::other)??;
+			Ok(Some((
+				chunk,
+				(file, offset + chunk_size, remaining - chunk_size),
+			)))
+		},
+	)
+}
+
+/// Audio file of a remote mount, streamed from its storage backend.
+struct RemoteMediaFile {
+	path: PathBuf,
+	remote: RemoteLocation,
+	length: u64,
+	start_offset: Option<u64>,
+	bandwidth_tracker: Option<BandwidthTracker>,
+}
+
+impl RemoteMediaFile {
+	fn new(path: PathBuf, remote: RemoteLocation, length: u64) -> Self {
+		Self {
+			path,
+			remote,
+			length,
+			start_offset: None,
+			bandwidth_tracker: None,
+		}
+	}
+
+	/// Counts the bytes of this file sent to the user.
+	fn with_bandwidth_tracker(mut self, bandwidth_tracker: BandwidthTracker) -> Self {
+		self.bandwidth_tracker = Some(bandwidth_tracker);
+		self
+	}
+
+	/// Serves the file as a partial response starting at the given byte offset,
+	/// regardless of the range requested by the client.
+	fn with_start_offset(mut self, start_offset: u64) -> Self {
+		self.start_offset = Some(start_offset);
+		self
+	}
+
+	/// First and last byte to send, and whether they make a partial response.
+	fn get_range(&self, req: &HttpRequest) -> Option<((u64, u64), bool)> {
+		let length = self.length;
+		let whole_file = ((0, length.saturating_sub(1)), false);
+		if let Some(start_offset) = self.start_offset {
+			return (start_offset < length).then_some(((start_offset, length - 1), true));
+		}
+		if length == 0 || requests_whole_file(req, length) {
+			return Some(whole_file);
+		}
+		match header::Range::parse(req) {
+			Ok(header::Range::Bytes(ranges)) if ranges.len() == 1 => {
+				Some((ranges[0].to_satisfiable_range(length)?, true))
+			}
+			// Multiple ranges are not supported, the whole file is sent instead
+			_ => Some(whole_file),
+		}
+	}
+}
+
+impl Responder for RemoteMediaFile {
+	type Body = BoxBody;
+
+	fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+		let ((first, last), partial) = match self.get_range(req) {
+			Some(range) => range,
+			None => {
+				let mut response = HttpResponse::RangeNotSatisfiable();
+				response.insert_header(header::ContentRange(header::ContentRangeSpec::Bytes {
+					range: None,
+					instance_length: Some(self.length),
+				}));
+				return response.finish();
+			}
+		};
+
+		let buffer_size = req
+			.app_data::<Data<StreamBufferSize>>()
+			.map(|b| *b.get_ref())
+			.unwrap_or_default();
+
+		let content_length = if self.length == 0 {
+			0
+		} else {
+			last - first + 1
+		};
+		let body = SizedStream::new(
+			content_length,
+			read_remote_range(self.remote, first, content_length, buffer_size),
+		);
+		let mut response = if partial {
+			let mut response = HttpResponse::PartialContent();
+			response.insert_header(header::ContentRange(header::ContentRangeSpec::Bytes {
+				range: Some((first, last)),
+				instance_length: Some(self.length),
+			}));
+			response
+		} else {
+			HttpResponse::Ok()
+		};
+		let extension = self
+			.path
+			.extension()
+			.map(|e| e.to_string_lossy().into_owned())
+			.unwrap_or_default();
+		response
+			.content_type(actix_files::file_extension_to_mime(&extension))
+			.insert_header((header::CONTENT_ENCODING, "identity"))
+			.insert_header((header::ACCEPT_RANGES, "bytes"))
+			.body(MediaFileBody {
+				path: self.path,
+				inner: BoxBody::new(body),
+				bandwidth_tracker: self.bandwidth_tracker,
+			})
+	}
+}
+
+fn read_remote_range(
+	remote: RemoteLocation,
+	offset: u64,
+	length: u64,
+	buffer_size: StreamBufferSize,
+) -> impl Stream<Item = Result<Bytes, io::Error>> {
+	let buffer_size = buffer_size.0.max(1) as u64;
+	stream::try_unfold(
+		(remote, offset, length),
+		move |(remote, offset, remaining)| async move {
+			if remaining == 0 {
+				return Ok(None);
+			}
+			let chunk_size = remaining.min(buffer_size);
+			let (remote, chunk) = web::block(move || -> io::Result<_> {
+				let chunk = remote
+					.read_range(offset, chunk_size)
+					.map_err(io::Error::other)?;
+				if chunk.is_empty() {
+					return Err(io::ErrorKind::UnexpectedEof.into());
+				}
+				Ok((remote, Bytes::from(chunk)))
+			})
+			.await
+			.map_err(io::Error::other)??;
+			let chunk_size = chunk.len() as u64;
+			Ok(Some((
+				chunk,
+				(remote, offset + chunk_size, remaining - chunk_size),
+			)))
+		},
+	)
+}
+
+/// Wraps the body of a media file response so that read failures happening mid-stream
+/// (file deleted, network mount dropped, etc.) are logged along with the offending path.
+/// The error is still forwarded, which makes the server close the connection.
+struct MediaFileBody {
+	path: PathBuf,
+	inner: BoxBody,
+	bandwidth_tracker: Option<BandwidthTracker>,
+}
+
+/// Counts bytes streamed to a user, and adds them to the user's bandwidth usage
+/// once the response body is dropped (fully sent or interrupted).
+struct BandwidthTracker {
+	bandwidth_manager: bandwidth::Manager,
+	username: String,
+	bytes: u64,
+}
+
+impl BandwidthTracker {
+	fn new(bandwidth_manager: bandwidth::Manager, username: String) -> Self {
+		Self {
+			bandwidth_manager,
+			username,
+			bytes: 0,
+		}
+	}
+}
+
+/// Starts counting bytes streamed to a user, if bandwidth tracking is enabled.
+async fn make_bandwidth_tracker(
+	bandwidth_manager: Data<bandwidth::Manager>,
+	username: String,
+) -> Result<Option<BandwidthTracker>, APIError> {
+	let bandwidth_manager = bandwidth_manager.get_ref().clone();
+	block(move || -> Result<_, APIError> {
+		Ok(bandwidth_manager
+			.is_enabled()?
+			.then(|| BandwidthTracker::new(bandwidth_manager, username)))
+	})
+	.await
+}
+
+impl Drop for BandwidthTracker {
+	fn drop(&mut self) {
+		if self.bytes == 0 {
+			return;
+		}
+		if let Err(e) = self.bandwidth_manager.record(&self.username, self.bytes) {
+			error!(
+				"Could not record bandwidth usage of `{}`: {}",
+				self.username, e
+			);
+		}
+	}
+}
+
+impl MessageBody for MediaFileBody {
+	type Error = <BoxBody as MessageBody>::Error;
+
+	fn size(&self) -> BodySize {
+		self.inner.size()
+	}
+
+	fn poll_next(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+	) -> Poll<Option<Result<Bytes, Self::Error>>> {
+		let poll = Pin::new(&mut self.inner).poll_next(cx);
+		match poll {
+			Poll::Ready(Some(Ok(ref bytes))) => {
+				if let Some(bandwidth_tracker) = self.bandwidth_tracker.as_mut() {
+					bandwidth_tracker.bytes += bytes.len() as u64;
+				}
+			}
+			Poll::Ready(Some(Err(ref e))) => {
+				error!("Error while streaming `{}`: {}", self.path.display(), e);
+			}
+			_ => (),
+		}
+		poll
 	}
 }
 
@@ -249,21 +808,66 @@ async fn version() -> Json<dto::Version> {
 	Json(current_version)
 }
 
+#[get("/capabilities")]
+async fn get_capabilities(
+	request: HttpRequest,
+	ddns_manager: Data<ddns::Manager>,
+	settings_manager: Data<settings::Manager>,
+) -> Result<Json<dto::Capabilities>, APIError> {
+	let tls_enabled = request
+		.app_data::<Data<TlsEnabled>>()
+		.map(|t| *t.get_ref())
+		.unwrap_or_default();
+	let (ddns_config, settings) = block(move || -> Result<_, APIError> {
+		Ok((ddns_manager.config()?, settings_manager.read()?))
+	})
+	.await?;
+	Ok(Json(dto::Capabilities {
+		tls: tls_enabled.0,
+		ddns: !ddns_config.host.is_empty(),
+		lastfm: true,
+		bandwidth_tracking: settings.track_bandwidth,
+		native_ui: cfg!(feature = "ui"),
+		// Not implemented by this server
+		transcoding: false,
+		subsonic: false,
+		listenbrainz: false,
+	}))
+}
+
 #[get("/initial_setup")]
 async fn initial_setup(
 	user_manager: Data<user::Manager>,
+	vfs_manager: Data<vfs::Manager>,
+	index: Data<Index>,
 ) -> Result<Json<dto::InitialSetup>, APIError> {
 	let initial_setup = block(move || -> Result<dto::InitialSetup, APIError> {
 		let users = user_manager.list()?;
 		let has_any_admin = users.iter().any(|u| u.is_admin());
+		let has_any_mounts = !vfs_manager.mount_dirs()?.is_empty();
+		let has_indexed_collection = index.has_indexed_content()?;
 		Ok(dto::InitialSetup {
 			has_any_users: has_any_admin,
+			has_any_mounts,
+			has_indexed_collection,
 		})
 	})
 	.await?;
 	Ok(Json(initial_setup))
 }
 
+#[post("/bootstrap")]
+async fn bootstrap_admin(
+	user_manager: Data<user::Manager>,
+	request: Json<dto::BootstrapAdmin>,
+) -> Result<HttpResponse, APIError> {
+	block(move || {
+		user_manager.bootstrap_admin(&request.token, &request.username, &request.password)
+	})
+	.await?;
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
 #[put("/config")]
 async fn apply_config(
 	_admin_rights: AdminRights,
@@ -274,6 +878,50 @@ async fn apply_config(
 	Ok(HttpResponse::new(StatusCode::OK))
 }
 
+#[patch("/config")]
+async fn patch_config(
+	_admin_rights: AdminRights,
+	config_manager: Data<config::Manager>,
+	config: Json<dto::Config>,
+) -> Result<HttpResponse, APIError> {
+	// Sections omitted from the payload are left untouched, empty lists clear their section
+	block(move || config_manager.apply(&config.to_owned().into())).await?;
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
+#[get("/config/export")]
+async fn export_config(
+	_admin_rights: AdminRights,
+	config_manager: Data<config::Manager>,
+) -> Result<HttpResponse, APIError> {
+	let config = block(move || config_manager.export()).await?;
+	Ok(HttpResponse::Ok()
+		.insert_header(header::ContentDisposition::attachment(
+			"polaris-config.json",
+		))
+		.json(dto::Config::from(config)))
+}
+
+#[post("/config/import")]
+async fn import_config(
+	_admin_rights: AdminRights,
+	config_manager: Data<config::Manager>,
+	config: Json<dto::Config>,
+) -> Result<HttpResponse, APIError> {
+	// Users and DDNS settings without a password keep their existing password
+	block(move || config_manager.apply(&config.to_owned().into())).await?;
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
+#[get("/bandwidth")]
+async fn get_bandwidth_usage(
+	bandwidth_manager: Data<bandwidth::Manager>,
+	_admin_rights: AdminRights,
+) -> Result<Json<Vec<dto::BandwidthUsage>>, APIError> {
+	let usage = block(move || bandwidth_manager.usage()).await?;
+	Ok(Json(usage.into_iter().map(|u| u.into()).collect()))
+}
+
 #[get("/settings")]
 async fn get_settings(
 	settings_manager: Data<settings::Manager>,
@@ -306,12 +954,13 @@ async fn list_mount_dirs(
 #[put("/mount_dirs")]
 async fn put_mount_dirs(
 	_admin_rights: AdminRights,
-	vfs_manager: Data<vfs::Manager>,
+	config_manager: Data<config::Manager>,
 	new_mount_dirs: Json<Vec<dto::MountDir>>,
-) -> Result<HttpResponse, APIError> {
+) -> Result<Json<Vec<dto::MountDir>>, APIError> {
 	let new_mount_dirs: Vec<MountDir> = new_mount_dirs.iter().cloned().map(|m| m.into()).collect();
-	block(move || vfs_manager.set_mount_dirs(&new_mount_dirs)).await?;
-	Ok(HttpResponse::new(StatusCode::OK))
+	let mount_dirs = block(move || config_manager.set_mount_dirs(&new_mount_dirs)).await?;
+	let mount_dirs = mount_dirs.into_iter().map(|m| m.into()).collect();
+	Ok(Json(mount_dirs))
 }
 
 #[get("/ddns")]
@@ -333,6 +982,19 @@ async fn put_ddns_config(
 	Ok(HttpResponse::new(StatusCode::OK))
 }
 
+#[post("/ddns/test")]
+async fn test_ddns_config(
+	_admin_rights: AdminRights,
+	ddns_manager: Data<ddns::Manager>,
+	ddns_config: Json<dto::DDNSConfig>,
+) -> Result<Json<dto::DDNSTestReport>, APIError> {
+	let report = block(move || {
+		Ok::<_, ddns::Error>(ddns_manager.test_config(&ddns_config.to_owned().into()))
+	})
+	.await?;
+	Ok(Json(report.into()))
+}
+
 #[get("/users")]
 async fn list_users(
 	user_manager: Data<user::Manager>,
@@ -423,6 +1085,147 @@ async fn trigger_index(
 	Ok(HttpResponse::new(StatusCode::OK))
 }
 
+#[get("/index/status")]
+async fn get_index_status(
+	index: Data<Index>,
+	_admin_rights: AdminRights,
+) -> Result<Json<dto::IndexStatus>, APIError> {
+	Ok(Json(dto::IndexStatus {
+		state: index.update_control().status(),
+	}))
+}
+
+#[get("/index/errors")]
+async fn get_index_errors(
+	index: Data<Index>,
+	_admin_rights: AdminRights,
+) -> Result<Json<Vec<dto::ScanError>>, APIError> {
+	Ok(Json(
+		index.scan_errors().into_iter().map(|e| e.into()).collect(),
+	))
+}
+
+/// Streams progress of the update currently running (or of the next one) as server-sent events.
+/// The response ends when that update completes.
+#[get("/index/events")]
+async fn get_index_events(
+	index: Data<Index>,
+	_admin_rights: AdminRights,
+) -> Result<HttpResponse, APIError> {
+	let receiver = index.subscribe_to_scan_events();
+	Ok(HttpResponse::Ok()
+		.content_type("text/event-stream")
+		.insert_header(header::CacheControl(vec![header::CacheDirective::NoCache]))
+		.insert_header(ContentEncoding::Identity)
+		.streaming(stream_scan_events(receiver)))
+}
+
+fn stream_scan_events(
+	receiver: crossbeam_channel::Receiver<index::ScanEvent>,
+) -> impl Stream<Item = Result<Bytes, io::Error>> {
+	stream::try_unfold(receiver, |receiver| async move {
+		let (receiver, event) = web::block(move || {
+			let event = receiver.recv().ok();
+			(receiver, event)
+		})
+		.await
+		.map_err(io::Error::other)?;
+		match event {
+			Some(event) => {
+				let data = serde_json::to_string(&dto::ScanEvent::from(event))?;
+				Ok(Some((Bytes::from(format!("data: {}\n\n", data)), receiver)))
+			}
+			None => Ok(None),
+		}
+	})
+}
+
+#[post("/index/rebuild")]
+async fn rebuild_index(
+	index: Data<Index>,
+	_admin_rights: AdminRights,
+) -> Result<HttpResponse, APIError> {
+	index.trigger_rebuild();
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
+#[post("/index/refresh")]
+async fn refresh_index(
+	index: Data<Index>,
+	_admin_rights: AdminRights,
+	query: web::Query<dto::IndexRefreshQuery>,
+) -> Result<HttpResponse, APIError> {
+	block(move || index.refresh(Path::new(&query.path))).await?;
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
+#[post("/metadata/lint")]
+async fn lint_metadata(
+	vfs_manager: Data<vfs::Manager>,
+	_admin_rights: AdminRights,
+	input: Json<dto::MetadataLintInput>,
+) -> Result<Json<dto::MetadataLintReport>, APIError> {
+	let report = block(move || -> Result<dto::MetadataLintReport, APIError> {
+		let vfs = vfs_manager.get_vfs()?;
+		let path = Path::new(&input.path);
+		let real_path = vfs
+			.virtual_to_real(path)
+			.unwrap_or_else(|_| path.to_owned());
+		let (tags, warnings) = index::lint(&real_path).ok_or(APIError::SongMetadataNotFound)?;
+		Ok(dto::MetadataLintReport {
+			tags: tags.into(),
+			warnings: warnings.into_iter().map(|w| w.into()).collect(),
+		})
+	})
+	.await?;
+	Ok(Json(report))
+}
+
+#[get("/metadata/raw")]
+async fn raw_metadata(
+	vfs_manager: Data<vfs::Manager>,
+	_admin_rights: AdminRights,
+	query: web::Query<dto::RawMetadataQuery>,
+) -> Result<Json<index::RawTags>, APIError> {
+	let raw_tags = block(move || -> Result<index::RawTags, APIError> {
+		let vfs = vfs_manager.get_vfs()?;
+		let path = Path::new(&query.path);
+		let real_path = vfs
+			.virtual_to_real(path)
+			.unwrap_or_else(|_| path.to_owned());
+		index::read_raw(&real_path).ok_or(APIError::SongMetadataNotFound)
+	})
+	.await?;
+	Ok(Json(raw_tags))
+}
+
+#[post("/index/pause")]
+async fn pause_index(
+	index: Data<Index>,
+	_admin_rights: AdminRights,
+) -> Result<HttpResponse, APIError> {
+	index.update_control().pause();
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
+#[post("/index/resume")]
+async fn resume_index(
+	index: Data<Index>,
+	_admin_rights: AdminRights,
+) -> Result<HttpResponse, APIError> {
+	index.update_control().resume();
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
+#[post("/index/cancel")]
+async fn cancel_index(
+	index: Data<Index>,
+	_admin_rights: AdminRights,
+) -> Result<HttpResponse, APIError> {
+	index.update_control().cancel();
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
 #[post("/auth")]
 async fn login(
 	user_manager: Data<user::Manager>,
@@ -445,128 +1248,1175 @@ async fn login(
 	Ok(response)
 }
 
-#[get("/browse")]
-async fn browse_root(
-	index: Data<Index>,
-	_auth: Auth,
-) -> Result<Json<Vec<index::CollectionFile>>, APIError> {
-	let result = block(move || index.browse(Path::new(""))).await?;
-	Ok(Json(result))
+fn make_song(
+	song: index::Song,
+	play_count: Option<&play_count::PlayCount>,
+	expose_extra_tags: bool,
+) -> dto::Song {
+	let song = dto::Song::new(song, play_count);
+	if expose_extra_tags {
+		song.with_extra_tags()
+	} else {
+		song
+	}
 }
 
-#[get("/browse/{path:.*}")]
-async fn browse(
-	index: Data<Index>,
-	_auth: Auth,
-	path: web::Path<String>,
-) -> Result<Json<Vec<index::CollectionFile>>, APIError> {
-	let result = block(move || {
-		let path = percent_decode_str(&path).decode_utf8_lossy();
-		index.browse(Path::new(path.as_ref()))
-	})
-	.await?;
-	Ok(Json(result))
+fn with_play_counts(
+	settings_manager: &settings::Manager,
+	play_count_manager: &play_count::Manager,
+	username: &str,
+	songs: Vec<index::Song>,
+) -> Result<Vec<dto::Song>, APIError> {
+	let expose_extra_tags = settings_manager.read()?.expose_extra_tags;
+	let play_counts =
+		play_count_manager.read_play_counts(username, songs.iter().map(|s| s.path.as_str()))?;
+	Ok(songs
+		.into_iter()
+		.map(|s| {
+			let play_count = play_counts.get(&s.path);
+			make_song(s, play_count, expose_extra_tags)
+		})
+		.collect())
 }
 
-#[get("/flatten")]
-async fn flatten_root(index: Data<Index>, _auth: Auth) -> Result<Json<Vec<index::Song>>, APIError> {
-	let songs = block(move || index.flatten(Path::new(""))).await?;
-	Ok(Json(songs))
+fn browse_with_play_counts(
+	index: &Index,
+	settings_manager: &settings::Manager,
+	play_count_manager: &play_count::Manager,
+	username: &str,
+	path: &Path,
+	options: dto::BrowseOptions,
+) -> Result<Vec<dto::CollectionFile>, APIError> {
+	let expose_extra_tags = settings_manager.read()?.expose_extra_tags;
+	let files = index.browse(path, options.include_hidden)?;
+	let play_counts = play_count_manager.read_play_counts(
+		username,
+		files.iter().filter_map(|f| match f {
+			index::CollectionFile::Directory(_) => None,
+			index::CollectionFile::Song(s) => Some(s.path.as_str()),
+		}),
+	)?;
+	let mut output: Vec<dto::CollectionFile> = files
+		.into_iter()
+		.map(|f| match f {
+			index::CollectionFile::Directory(d) => dto::CollectionFile::Directory(d),
+			index::CollectionFile::Song(s) => {
+				let play_count = play_counts.get(&s.path);
+				dto::CollectionFile::Song(make_song(s, play_count, expose_extra_tags))
+			}
+		})
+		.collect();
+	if options.include_media {
+		let media_files = index.get_media_files(path)?;
+		output.extend(media_files.into_iter().map(dto::CollectionFile::Media));
+	}
+	Ok(output)
 }
 
-#[get("/flatten/{path:.*}")]
-async fn flatten(
-	index: Data<Index>,
-	_auth: Auth,
+#[get("/mounts")]
+async fn list_mounts(
+	vfs_manager: Data<vfs::Manager>,
+	user_manager: Data<user::Manager>,
+	auth: Auth,
+) -> Result<Json<Vec<dto::MountDir>>, APIError> {
+	let mount_dirs = block(move || -> Result<_, APIError> {
+		let is_admin = user_manager.is_admin(&auth.username)?;
+		let mount_dirs = vfs_manager.mount_dirs()?;
+		Ok(mount_dirs
+			.into_iter()
+			.map(|m| match is_admin {
+				true => m.into(),
+				false => dto::MountDir {
+					name: m.name,
+					..Default::default()
+				},
+			})
+			.collect())
+	})
+	.await?;
+	Ok(Json(mount_dirs))
+}
+
+#[get("/browse")]
+async fn browse_root(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	options: web::Query<dto::BrowseOptions>,
+) -> Result<CustomizeResponder<Json<Vec<dto::CollectionFile>>>, APIError> {
+	let generation = index.get_generation();
+	let result = block(move || {
+		browse_with_play_counts(
+			&index,
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			Path::new(""),
+			options.into_inner(),
+		)
+	})
+	.await?;
+	Ok(with_index_generation(Json(result), generation))
+}
+
+#[get("/browse/{path:.*}")]
+async fn browse(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
 	path: web::Path<String>,
-) -> Result<Json<Vec<index::Song>>, APIError> {
+	options: web::Query<dto::BrowseOptions>,
+) -> Result<CustomizeResponder<Json<Vec<dto::CollectionFile>>>, APIError> {
+	let generation = index.get_generation();
+	let result = block(move || {
+		// Path parameters were percent-decoded when extracted. Decoding them again would alter
+		// names which contain `%` characters.
+		let path = path.into_inner();
+		browse_with_play_counts(
+			&index,
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			Path::new(&path),
+			options.into_inner(),
+		)
+	})
+	.await?;
+	Ok(with_index_generation(Json(result), generation))
+}
+
+#[get("/flatten")]
+async fn flatten_root(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	options: web::Query<dto::FlattenOptions>,
+) -> Result<CustomizeResponder<Json<Vec<dto::Song>>>, APIError> {
+	let generation = index.get_generation();
+	let songs = block(move || {
+		let songs = index.flatten(Path::new(""), options.include_hidden)?;
+		let songs = paginate_songs(&settings_manager, songs, &options)?;
+		with_play_counts(
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			songs,
+		)
+	})
+	.await?;
+	Ok(with_index_generation(Json(songs), generation))
+}
+
+#[get("/flatten/{path:.*}")]
+async fn flatten(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	path: web::Path<String>,
+	options: web::Query<dto::FlattenOptions>,
+) -> Result<CustomizeResponder<Json<Vec<dto::Song>>>, APIError> {
+	let generation = index.get_generation();
 	let songs = block(move || {
-		let path = percent_decode_str(&path).decode_utf8_lossy();
-		index.flatten(Path::new(path.as_ref()))
+		let songs = index.flatten(Path::new(path.as_str()), options.include_hidden)?;
+		let songs = paginate_songs(&settings_manager, songs, &options)?;
+		with_play_counts(
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			songs,
+		)
+	})
+	.await?;
+	Ok(with_index_generation(Json(songs), generation))
+}
+
+/// Keeps a page of flattened songs. Pages are only requested when a count is set, in which case
+/// it is capped by the maximum page size.
+fn paginate_songs(
+	settings_manager: &settings::Manager,
+	songs: Vec<index::Song>,
+	options: &dto::FlattenOptions,
+) -> Result<Vec<index::Song>, APIError> {
+	let count = match options.count {
+		Some(count) => settings_manager.get_page_size(Some(count))?,
+		None => usize::MAX,
+	};
+	Ok(songs.into_iter().skip(options.offset).take(count).collect())
+}
+
+#[get("/ancestors")]
+async fn ancestors(
+	index: Data<Index>,
+	_auth: Auth,
+	query: web::Query<dto::AncestorsQuery>,
+) -> Result<Json<Vec<index::Directory>>, APIError> {
+	let result = block(move || index.get_ancestors(Path::new(&query.path))).await?;
+	Ok(Json(result))
+}
+
+#[get("/song")]
+async fn get_song(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	query: web::Query<dto::SongQuery>,
+) -> Result<Json<dto::Song>, APIError> {
+	let song = block(move || -> Result<dto::Song, APIError> {
+		let song = index.get_song(Path::new(&query.path))?;
+		let mut songs = with_play_counts(
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			vec![song],
+		)?;
+		songs.pop().ok_or(APIError::SongMetadataNotFound)
+	})
+	.await?;
+	Ok(Json(song))
+}
+
+#[get("/song/siblings")]
+async fn get_song_siblings(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	query: web::Query<dto::SongQuery>,
+) -> Result<Json<dto::SongSiblings>, APIError> {
+	let siblings = block(move || -> Result<dto::SongSiblings, APIError> {
+		let siblings = index.get_song_siblings(Path::new(&query.path))?;
+		let has_next = siblings.next.is_some();
+		let mut songs = with_play_counts(
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			siblings.previous.into_iter().chain(siblings.next).collect(),
+		)?;
+		let next = if has_next { songs.pop() } else { None };
+		Ok(dto::SongSiblings {
+			previous: songs.pop(),
+			next,
+		})
+	})
+	.await?;
+	Ok(Json(siblings))
+}
+
+#[post("/songs")]
+async fn get_songs(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	input: Json<dto::GetSongsInput>,
+) -> Result<Json<dto::GetSongsOutput>, APIError> {
+	let output = block(move || -> Result<dto::GetSongsOutput, APIError> {
+		let batch = index.get_songs(&input.paths)?;
+		let songs = with_play_counts(
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			batch.songs,
+		)?;
+		Ok(dto::GetSongsOutput {
+			songs,
+			not_found: batch.not_found,
+		})
+	})
+	.await?;
+	Ok(Json(output))
+}
+
+#[post("/play")]
+async fn mark_played(
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	query: web::Query<dto::PlayQuery>,
+) -> Result<Json<dto::PlayCount>, APIError> {
+	let play_count =
+		block(move || play_count_manager.record_play(&auth.username, &query.path)).await?;
+	Ok(Json(play_count.into()))
+}
+
+/// Lists recent plays across all users who share their activity, for displays shared by a household.
+#[get("/activity")]
+async fn get_activity(
+	_admin_rights: AdminRights,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	query: web::Query<dto::ActivityQuery>,
+) -> Result<Json<Vec<dto::ActivityEntry>>, APIError> {
+	let activity = block(move || -> Result<Vec<dto::ActivityEntry>, APIError> {
+		let count = settings_manager.get_page_size(query.count)?;
+		let activity = play_count_manager.read_activity(count)?;
+		Ok(activity.into_iter().map(|e| e.into()).collect())
+	})
+	.await?;
+	Ok(Json(activity))
+}
+
+/// Exports every play of the authenticated user, oldest first, as JSON or CSV.
+/// The response is streamed so that large histories are never held in memory at once.
+#[get("/play/history")]
+async fn export_history(
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	query: web::Query<dto::HistoryQuery>,
+) -> Result<HttpResponse, APIError> {
+	let (content_type, file_name) = match query.format {
+		dto::HistoryFormat::Json => ("application/json", "polaris-history.json"),
+		dto::HistoryFormat::Csv => ("text/csv; charset=utf-8", "polaris-history.csv"),
+	};
+	let history = HistoryExport {
+		play_count_manager: play_count_manager.get_ref().clone(),
+		username: auth.username,
+		format: query.format,
+		after: None,
+		started: false,
+		wrote_entry: false,
+		done: false,
+	};
+	Ok(HttpResponse::Ok()
+		.content_type(content_type)
+		.insert_header(header::ContentDisposition::attachment(file_name))
+		.streaming(stream_history(history)))
+}
+
+struct HistoryExport {
+	play_count_manager: play_count::Manager,
+	username: String,
+	format: dto::HistoryFormat,
+	after: Option<i32>,
+	started: bool,
+	wrote_entry: bool,
+	done: bool,
+}
+
+fn stream_history(history: HistoryExport) -> impl Stream<Item = Result<Bytes, io::Error>> {
+	stream::try_unfold(history, |mut history| async move {
+		if history.done {
+			return Ok(None);
+		}
+
+		let play_count_manager = history.play_count_manager.clone();
+		let username = history.username.clone();
+		let after = history.after;
+		let page = web::block(move || {
+			play_count_manager.read_history(&username, after, HISTORY_PAGE_SIZE)
+		})
+		.await
+		.map_err(io::Error::other)?
+		.map_err(|e| io::Error::other(e.to_string()))?;
+
+		let mut chunk = String::new();
+		if !history.started {
+			chunk.push_str(match history.format {
+				dto::HistoryFormat::Json => "[",
+				dto::HistoryFormat::Csv => "path,played_at,play_count\n",
+			});
+			history.started = true;
+		}
+
+		for entry in page.entries {
+			match history.format {
+				dto::HistoryFormat::Json => {
+					if history.wrote_entry {
+						chunk.push(',');
+					}
+					chunk.push_str(&serde_json::to_string(&dto::HistoryEntry::from(entry))?);
+				}
+				dto::HistoryFormat::Csv => {
+					chunk.push_str(&format!(
+						"{},{},{}\n",
+						csv_field(&entry.path),
+						entry.played_at,
+						entry.play_count
+					));
+				}
+			}
+			history.wrote_entry = true;
+		}
+
+		match page.next {
+			Some(next) => history.after = Some(next),
+			None => {
+				if history.format == dto::HistoryFormat::Json {
+					chunk.push(']');
+				}
+				history.done = true;
+			}
+		}
+
+		Ok(Some((Bytes::from(chunk), history)))
+	})
+}
+
+fn csv_field(value: &str) -> String {
+	if value.contains([',', '"', '\n', '\r']) {
+		format!("\"{}\"", value.replace('"', "\"\""))
+	} else {
+		value.to_owned()
+	}
+}
+
+#[post("/progress")]
+async fn set_progress(
+	progress_manager: Data<progress::Manager>,
+	auth: Auth,
+	query: web::Query<dto::SetProgressQuery>,
+) -> Result<Json<dto::Progress>, APIError> {
+	let position =
+		block(move || progress_manager.set_position(&auth.username, &query.path, query.seconds))
+			.await?;
+	Ok(Json(position.into()))
+}
+
+#[get("/progress")]
+async fn get_progress(
+	progress_manager: Data<progress::Manager>,
+	auth: Auth,
+	query: web::Query<dto::ProgressQuery>,
+) -> Result<Json<dto::Progress>, APIError> {
+	let position =
+		block(move || progress_manager.get_position(&auth.username, &query.path)).await?;
+	Ok(Json(position.into()))
+}
+
+#[get("/progress/recent")]
+async fn recent_progress(
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	progress_manager: Data<progress::Manager>,
+	auth: Auth,
+	query: web::Query<dto::RecentProgressQuery>,
+) -> Result<Json<Vec<dto::SongProgress>>, APIError> {
+	let result = block(move || -> Result<Vec<dto::SongProgress>, APIError> {
+		let count = settings_manager.get_page_size(query.count)?;
+		let in_progress = progress_manager.get_recent(&auth.username, count)?;
+		let (songs, positions): (Vec<index::Song>, Vec<progress::Position>) =
+			in_progress.into_iter().unzip();
+		let songs = with_play_counts(
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			songs,
+		)?;
+		Ok(songs
+			.into_iter()
+			.zip(positions)
+			.map(|(song, position)| dto::SongProgress {
+				song,
+				progress: position.into(),
+			})
+			.collect())
+	})
+	.await?;
+	Ok(Json(result))
+}
+
+#[get("/top")]
+async fn top(
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	query: web::Query<dto::TopQuery>,
+) -> Result<Json<dto::Top>, APIError> {
+	let result = block(move || -> Result<dto::Top, APIError> {
+		let count = settings_manager.get_page_size(query.count)?;
+		let window = query.window.into();
+		let expose_extra_tags = settings_manager.read()?.expose_extra_tags;
+		Ok(match query.kind {
+			dto::TopKind::Songs => dto::Top::Songs(
+				play_count_manager
+					.get_top_songs(&auth.username, window, count)?
+					.into_iter()
+					.map(|(song, play_count)| make_song(song, Some(&play_count), expose_extra_tags))
+					.collect(),
+			),
+			dto::TopKind::Albums => dto::Top::Albums(play_count_manager.get_top_albums(
+				&auth.username,
+				window,
+				count,
+			)?),
+		})
+	})
+	.await?;
+	Ok(Json(result))
+}
+
+#[get("/shuffle")]
+async fn shuffle(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	query: web::Query<dto::ShuffleQuery>,
+) -> Result<Json<Vec<dto::Song>>, APIError> {
+	let songs = block(move || -> Result<Vec<dto::Song>, APIError> {
+		let count = settings_manager.get_page_size(Some(query.count.unwrap_or(100)))?;
+		let songs = index.shuffle(
+			count,
+			query.seed,
+			query.genre.as_deref(),
+			query.artist.as_deref(),
+		)?;
+		with_play_counts(
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			songs,
+		)
 	})
 	.await?;
 	Ok(Json(songs))
 }
 
 #[get("/random")]
-async fn random(index: Data<Index>, _auth: Auth) -> Result<Json<Vec<index::Directory>>, APIError> {
-	let result = block(move || index.get_random_albums(20)).await?;
-	Ok(Json(result))
+async fn random(
+	index: Data<Index>,
+	_auth: Auth,
+	options: web::Query<dto::HiddenSongsOptions>,
+) -> Result<Json<Vec<dto::Album>>, APIError> {
+	let result = block(move || -> Result<_, APIError> {
+		let albums = index.get_random_albums(20, options.include_hidden)?;
+		Ok(index.merge_duplicate_albums(albums)?)
+	})
+	.await?;
+	Ok(Json(result.into_iter().map(|a| a.into()).collect()))
 }
 
 #[get("/recent")]
-async fn recent(index: Data<Index>, _auth: Auth) -> Result<Json<Vec<index::Directory>>, APIError> {
-	let result = block(move || index.get_recent_albums(20)).await?;
+async fn recent(
+	index: Data<Index>,
+	_auth: Auth,
+	options: web::Query<dto::HiddenSongsOptions>,
+) -> Result<Json<Vec<dto::Album>>, APIError> {
+	let result = block(move || -> Result<_, APIError> {
+		let albums = index.get_recent_albums(20, options.include_hidden)?;
+		Ok(index.merge_duplicate_albums(albums)?)
+	})
+	.await?;
+	Ok(Json(result.into_iter().map(|a| a.into()).collect()))
+}
+
+#[get("/artists")]
+async fn artists(index: Data<Index>, _auth: Auth) -> Result<Json<Vec<index::Artist>>, APIError> {
+	let result = block(move || index.get_artists()).await?;
+	Ok(Json(result))
+}
+
+#[get("/years")]
+async fn years(index: Data<Index>, _auth: Auth) -> Result<Json<Vec<index::YearCount>>, APIError> {
+	let result = block(move || index.get_years()).await?;
+	Ok(Json(result))
+}
+
+#[get("/groupings")]
+async fn groupings(index: Data<Index>, _auth: Auth) -> Result<Json<Vec<String>>, APIError> {
+	let result = block(move || index.get_groupings()).await?;
+	Ok(Json(result))
+}
+
+#[get("/grouping/{name}")]
+async fn grouping(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	name: web::Path<String>,
+) -> Result<Json<Vec<dto::Song>>, APIError> {
+	let name = name.into_inner();
+	let result = block(move || -> Result<_, APIError> {
+		let songs = index.get_grouping_songs(&name)?;
+		with_play_counts(
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			songs,
+		)
+	})
+	.await?;
+	Ok(Json(result))
+}
+
+#[get("/labels")]
+async fn labels(index: Data<Index>, _auth: Auth) -> Result<Json<Vec<index::Label>>, APIError> {
+	let result = block(move || index.get_labels()).await?;
+	Ok(Json(result))
+}
+
+#[get("/label/{name}")]
+async fn get_label(
+	index: Data<Index>,
+	_auth: Auth,
+	name: web::Path<String>,
+) -> Result<Json<Vec<dto::Album>>, APIError> {
+	let name = name.into_inner();
+	let result = block(move || -> Result<_, APIError> {
+		let albums = index.get_label_albums(&name)?;
+		Ok(index.merge_duplicate_albums(albums)?)
+	})
+	.await?;
+	Ok(Json(result.into_iter().map(|a| a.into()).collect()))
+}
+
+#[get("/languages")]
+async fn languages(index: Data<Index>, _auth: Auth) -> Result<Json<Vec<String>>, APIError> {
+	let result = block(move || index.get_languages()).await?;
+	Ok(Json(result))
+}
+
+#[get("/language/{name}")]
+async fn language(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	name: web::Path<String>,
+) -> Result<Json<Vec<dto::Song>>, APIError> {
+	let name = name.into_inner();
+	let result = block(move || -> Result<_, APIError> {
+		let songs = index.get_language_songs(&name)?;
+		with_play_counts(
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			songs,
+		)
+	})
+	.await?;
+	Ok(Json(result))
+}
+
+#[get("/decade/{decade}")]
+async fn decade(
+	index: Data<Index>,
+	_auth: Auth,
+	decade: web::Path<String>,
+) -> Result<Json<Vec<dto::Album>>, APIError> {
+	// Albums without a year are listed under an `unknown` decade
+	let decade = match decade.as_str() {
+		d if d.eq_ignore_ascii_case("unknown") => None,
+		d => Some(
+			d.parse::<i32>()
+				.map_err(|_| APIError::InvalidDecade(d.to_owned()))?,
+		),
+	};
+	let result = block(move || -> Result<_, APIError> {
+		let albums = index.get_decade_albums(decade)?;
+		Ok(index.merge_duplicate_albums(albums)?)
+	})
+	.await?;
+	Ok(Json(result.into_iter().map(|a| a.into()).collect()))
+}
+
+#[get("/artwork/missing")]
+async fn missing_artwork(
+	index: Data<Index>,
+	_admin_rights: AdminRights,
+) -> Result<Json<Vec<index::Directory>>, APIError> {
+	let result = block(move || index.get_albums_without_artwork()).await?;
+	Ok(Json(result))
+}
+
+#[get("/stats/formats")]
+async fn format_stats(
+	index: Data<Index>,
+	_auth: Auth,
+) -> Result<Json<Vec<index::FormatCount>>, APIError> {
+	let result = block(move || index.get_format_counts()).await?;
 	Ok(Json(result))
 }
 
 #[get("/search")]
 async fn search_root(
 	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
 	_auth: Auth,
-) -> Result<Json<Vec<index::CollectionFile>>, APIError> {
-	let result = block(move || index.search("")).await?;
-	Ok(Json(result))
+	options: web::Query<dto::SearchOptions>,
+) -> Result<CustomizeResponder<Json<dto::SearchResults>>, APIError> {
+	let generation = index.get_generation();
+	let result = block(move || run_search(&index, &settings_manager, "", &options)).await?;
+	Ok(with_index_generation(Json(result), generation))
 }
 
 #[get("/search/{query:.*}")]
 async fn search(
 	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
 	_auth: Auth,
 	query: web::Path<String>,
-) -> Result<Json<Vec<index::CollectionFile>>, APIError> {
-	let result = block(move || index.search(&query)).await?;
-	Ok(Json(result))
+	options: web::Query<dto::SearchOptions>,
+) -> Result<CustomizeResponder<Json<dto::SearchResults>>, APIError> {
+	let generation = index.get_generation();
+	let result = block(move || run_search(&index, &settings_manager, &query, &options)).await?;
+	Ok(with_index_generation(Json(result), generation))
+}
+
+fn with_index_generation<R: Responder>(responder: R, generation: u64) -> CustomizeResponder<R> {
+	responder
+		.customize()
+		.insert_header((INDEX_GENERATION_HEADER, generation.to_string()))
+}
+
+fn run_search(
+	index: &Index,
+	settings_manager: &settings::Manager,
+	query: &str,
+	options: &dto::SearchOptions,
+) -> Result<dto::SearchResults, APIError> {
+	if options.group {
+		let count = options.count.map(|c| c.max(0) as usize);
+		let count = settings_manager.get_page_size(count)? as i64;
+		Ok(dto::SearchResults::Grouped(
+			index.search_grouped(query, count)?,
+		))
+	} else {
+		Ok(dto::SearchResults::Files(index.search(query)?))
+	}
 }
 
 #[get("/audio/{path:.*}")]
 async fn get_audio(
 	vfs_manager: Data<vfs::Manager>,
-	_auth: Auth,
+	bandwidth_manager: Data<bandwidth::Manager>,
+	user_manager: Data<user::Manager>,
+	auth: Option<Auth>,
 	path: web::Path<String>,
-) -> Result<MediaFile, APIError> {
-	let audio_path = block(move || {
+	options: web::Query<dto::AudioOptions>,
+) -> Result<Either<MediaFile, RemoteMediaFile>, APIError> {
+	let options = options.into_inner();
+	let seek_percent = options.seek_percent;
+	let path = path.into_inner();
+	let username = match (auth, options.signature) {
+		(Some(auth), _) => auth.username,
+		(None, Some(signature)) => {
+			let path = path.clone();
+			let authorization =
+				block(move || user_manager.authenticate_stream(&user::AuthToken(signature), &path))
+					.await?;
+			authorization.username
+		}
+		(None, None) => return Err(APIError::AuthenticationRequired),
+	};
+	let bandwidth_tracker = make_bandwidth_tracker(bandwidth_manager, username).await?;
+	let (audio_path, remote, start_offset) = block(move || -> Result<_, APIError> {
 		let vfs = vfs_manager.get_vfs()?;
-		let path = percent_decode_str(&path).decode_utf8_lossy();
-		vfs.virtual_to_real(Path::new(path.as_ref()))
+		let audio_path = vfs.virtual_to_real(Path::new(&path))?;
+		if let Some(remote) = vfs.locate_remote(&audio_path) {
+			let length = remote.size().map_err(|_| APIError::AudioFileIOError)?;
+			// Leading metadata is not skipped, which would take extra requests to the remote
+			let start_offset = seek_percent.map(|percent| {
+				let fraction = (percent / 100.0).clamp(0.0, 1.0);
+				((length as f64 * fraction) as u64).min(length.saturating_sub(1))
+			});
+			return Ok((audio_path, Some((remote, length)), start_offset));
+		}
+		let start_offset = match seek_percent {
+			Some(percent) => Some(
+				utils::estimate_seek_offset(&audio_path, percent / 100.0)
+					.map_err(|_| APIError::AudioFileIOError)?,
+			),
+			None => None,
+		};
+		Ok((audio_path, None, start_offset))
 	})
 	.await?;
 
+	if let Some((remote, length)) = remote {
+		let mut media_file = RemoteMediaFile::new(audio_path, remote, length);
+		if let Some(bandwidth_tracker) = bandwidth_tracker {
+			media_file = media_file.with_bandwidth_tracker(bandwidth_tracker);
+		}
+		return Ok(Either::Right(match start_offset {
+			Some(offset) => media_file.with_start_offset(offset),
+			None => media_file,
+		}));
+	}
+
 	let named_file = NamedFile::open(audio_path).map_err(|_| APIError::AudioFileIOError)?;
-	Ok(MediaFile::new(named_file))
+	let mut media_file = MediaFile::new(named_file);
+	if let Some(bandwidth_tracker) = bandwidth_tracker {
+		media_file = media_file.with_bandwidth_tracker(bandwidth_tracker);
+	}
+	Ok(Either::Left(match start_offset {
+		Some(offset) => media_file.with_start_offset(offset),
+		None => media_file,
+	}))
+}
+
+#[post("/stream_url")]
+async fn get_stream_url(
+	vfs_manager: Data<vfs::Manager>,
+	user_manager: Data<user::Manager>,
+	auth: Auth,
+	query: web::Query<dto::StreamUrlQuery>,
+) -> Result<Json<dto::StreamUrl>, APIError> {
+	let stream_url = block(move || -> Result<dto::StreamUrl, APIError> {
+		vfs_manager
+			.get_vfs()?
+			.virtual_to_real(Path::new(&query.path))?;
+		make_signed_stream_url(
+			&user_manager,
+			&auth.username,
+			&query.path,
+			query.ttl_seconds,
+		)
+	})
+	.await?;
+	Ok(Json(stream_url))
+}
+
+fn make_signed_stream_url(
+	user_manager: &user::Manager,
+	username: &str,
+	path: &str,
+	ttl_seconds: Option<u64>,
+) -> Result<dto::StreamUrl, APIError> {
+	let ttl_seconds = ttl_seconds
+		.unwrap_or(DEFAULT_STREAM_URL_TTL_SECONDS)
+		.min(MAX_STREAM_URL_TTL_SECONDS);
+	let expires_at = utils::now() as u64 + ttl_seconds;
+	let user::AuthToken(signature) =
+		user_manager.generate_stream_token(username, path, expires_at)?;
+	let url = format!(
+		"/api/audio/{}?signature={}",
+		utf8_percent_encode(path, NON_ALPHANUMERIC),
+		utf8_percent_encode(&signature, NON_ALPHANUMERIC)
+	);
+	Ok(dto::StreamUrl { url, expires_at })
+}
+
+/// Lists the songs of an album in disc and track order, along with the URL each one can be streamed from.
+#[get("/album")]
+async fn get_album(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	auth: Auth,
+	query: web::Query<dto::AlbumQuery>,
+) -> Result<Json<dto::AlbumDetails>, APIError> {
+	let album = block(move || -> Result<dto::AlbumDetails, APIError> {
+		let album = index.get_album_details(Path::new(&query.path))?;
+		let songs = with_play_counts(
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			album.songs,
+		)?;
+		Ok(dto::AlbumDetails::new(album.directory, songs))
+	})
+	.await?;
+	Ok(Json(album))
 }
 
-#[get("/thumbnail/{path:.*}")]
+#[get("/album/play")]
+async fn play_album(
+	index: Data<Index>,
+	settings_manager: Data<settings::Manager>,
+	play_count_manager: Data<play_count::Manager>,
+	user_manager: Data<user::Manager>,
+	auth: Auth,
+	query: web::Query<dto::AlbumPlayQuery>,
+) -> Result<Json<Vec<dto::AlbumTrack>>, APIError> {
+	let tracks = block(move || -> Result<Vec<dto::AlbumTrack>, APIError> {
+		let mut songs = index.flatten(Path::new(&query.path), false)?;
+		songs.sort_by(index::Song::cmp_album_order);
+		let songs = with_play_counts(
+			&settings_manager,
+			&play_count_manager,
+			&auth.username,
+			songs,
+		)?;
+		songs
+			.into_iter()
+			.map(|song| {
+				let (stream_url, expires_at) = if query.signed {
+					let stream_url = make_signed_stream_url(
+						&user_manager,
+						&auth.username,
+						&song.song.path,
+						query.ttl_seconds,
+					)?;
+					(stream_url.url, Some(stream_url.expires_at))
+				} else {
+					let path = utf8_percent_encode(&song.song.path, NON_ALPHANUMERIC);
+					(format!("/api/audio/{}", path), None)
+				};
+				Ok(dto::AlbumTrack {
+					song,
+					stream_url,
+					expires_at,
+				})
+			})
+			.collect()
+	})
+	.await?;
+	Ok(Json(tracks))
+}
+
+#[get("/zip/{path:.*}")]
+async fn get_zip(
+	archive_manager: Data<archive::Manager>,
+	bandwidth_manager: Data<bandwidth::Manager>,
+	auth: Auth,
+	path: web::Path<String>,
+) -> Result<HttpResponse, APIError> {
+	let bandwidth_tracker = make_bandwidth_tracker(bandwidth_manager, auth.username).await?;
+	let virtual_path = PathBuf::from(path.into_inner());
+	let archive = {
+		let virtual_path = virtual_path.clone();
+		block(move || archive_manager.prepare(&virtual_path)).await?
+	};
+
+	Ok(HttpResponse::Ok()
+		.content_type("application/zip")
+		.insert_header(header::ContentDisposition::attachment(archive.file_name()))
+		.body(MediaFileBody {
+			path: virtual_path,
+			inner: BoxBody::new(BodyStream::new(stream_archive(archive))),
+			bandwidth_tracker,
+		}))
+}
+
+fn stream_archive(archive: archive::Archive) -> impl Stream<Item = Result<Bytes, io::Error>> {
+	stream::try_unfold(archive, |mut archive| async move {
+		let (archive, chunk) = web::block(move || {
+			let chunk = archive.next_chunk();
+			(archive, chunk)
+		})
+		.await
+		.map_err(io::Error::other)?;
+		match chunk {
+			Ok(Some(chunk)) => Ok(Some((Bytes::from(chunk), archive))),
+			Ok(None) => Ok(None),
+			Err(e) => Err(io::Error::other(e.to_string())),
+		}
+	})
+}
+
+/// Also answers HEAD requests, so that clients can check the type and size of a thumbnail
+/// before downloading it. Thumbnails which are not cached yet are generated to report their size.
+#[route("/thumbnail/{path:.*}", method = "GET", method = "HEAD")]
+#[allow(clippy::too_many_arguments)]
 async fn get_thumbnail(
+	index: Data<Index>,
 	vfs_manager: Data<vfs::Manager>,
+	settings_manager: Data<settings::Manager>,
+	cover_manager: Data<cover::Manager>,
 	thumbnails_manager: Data<thumbnail::Manager>,
 	_auth: Auth,
+	request: HttpRequest,
 	path: web::Path<String>,
 	options_input: web::Query<dto::ThumbnailOptions>,
-) -> Result<MediaFile, APIError> {
-	let options = thumbnail::Options::from(options_input.0);
-
-	let thumbnail_path = block(move || -> Result<PathBuf, APIError> {
+) -> Result<CustomizeResponder<MediaFile>, APIError> {
+	let mut options_input = options_input.into_inner();
+	options_input.format = options_input
+		.format
+		.or_else(|| negotiate_thumbnail_format(&request));
+	let options = thumbnail::Options::from(options_input);
+
+	let (thumbnail_path, is_placeholder) = block(move || -> Result<(PathBuf, bool), APIError> {
 		let vfs = vfs_manager.get_vfs()?;
-		let path = percent_decode_str(&path).decode_utf8_lossy();
-		let image_path = vfs.virtual_to_real(Path::new(path.as_ref()))?;
+		let image_path = vfs.virtual_to_real(Path::new(path.as_str()))?;
+		if let Some(cover_path) = cover_manager.find(&image_path)? {
+			return Ok((
+				thumbnails_manager.get_thumbnail(&cover_path, &options)?,
+				false,
+			));
+		}
+		let settings = settings_manager.read()?;
+		let image_paths = get_thumbnail_sources(&index, &settings, path.as_str(), image_path)?;
+		let placeholder_path = settings.artwork_placeholder_path;
+		if placeholder_path.is_empty() {
+			let thumbnail_path = thumbnails_manager.get_collage(&image_paths, &options)?;
+			return Ok((thumbnail_path, false));
+		}
 		thumbnails_manager
-			.get_thumbnail(&image_path, &options)
+			.get_collage_or_placeholder(&image_paths, Path::new(&placeholder_path), &options)
 			.map_err(|e| e.into())
 	})
 	.await?;
 
 	let named_file = NamedFile::open(thumbnail_path).map_err(|_| APIError::ThumbnailFileIOError)?;
 
-	Ok(MediaFile::new(named_file))
+	let mut response = MediaFile::new(named_file)
+		.customize()
+		.insert_header((header::VARY, "Accept"));
+	if is_placeholder {
+		response = response.insert_header((PLACEHOLDER_HEADER, "true"));
+	}
+	Ok(response)
+}
+
+/// Replaces the artwork of a location within the collection, and of the songs it contains.
+#[put("/cover/{path:.*}")]
+async fn set_cover(
+	cover_manager: Data<cover::Manager>,
+	_admin_rights: AdminRights,
+	path: web::Path<String>,
+	input: Json<dto::CoverUpload>,
+) -> Result<HttpResponse, APIError> {
+	let content = BASE64_STANDARD
+		.decode(input.content.as_bytes())
+		.map_err(|_| APIError::CoverContentBase64DecodeError)?;
+	block(move || cover_manager.set(Path::new(path.as_str()), &content)).await?;
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
+#[delete("/cover/{path:.*}")]
+async fn clear_cover(
+	cover_manager: Data<cover::Manager>,
+	_admin_rights: AdminRights,
+	path: web::Path<String>,
+) -> Result<HttpResponse, APIError> {
+	block(move || cover_manager.clear(Path::new(path.as_str()))).await?;
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
+/// Lists the artwork a thumbnail is made from. Directories use the covers of the albums they
+/// contain, as configured by the directory thumbnail setting.
+fn get_thumbnail_sources(
+	index: &Index,
+	settings: &settings::Settings,
+	virtual_path: &str,
+	image_path: PathBuf,
+) -> Result<Vec<PathBuf>, APIError> {
+	if !image_path.is_dir() {
+		return Ok(vec![image_path]);
+	}
+	let mut artwork_paths = match settings.directory_thumbnail {
+		settings::DirectoryThumbnail::Disabled => Vec::new(),
+		settings::DirectoryThumbnail::FirstCover | settings::DirectoryThumbnail::Collage => {
+			index.get_artwork_paths(Path::new(virtual_path))?
+		}
+	};
+	if settings.directory_thumbnail == settings::DirectoryThumbnail::FirstCover {
+		artwork_paths.truncate(1);
+	}
+	if artwork_paths.is_empty() {
+		return Ok(vec![image_path]);
+	}
+	Ok(artwork_paths)
+}
+
+#[route("/artist/{name}/image", method = "GET", method = "HEAD")]
+async fn get_artist_image(
+	index: Data<Index>,
+	thumbnails_manager: Data<thumbnail::Manager>,
+	_auth: Auth,
+	request: HttpRequest,
+	name: web::Path<String>,
+	options_input: web::Query<dto::ThumbnailOptions>,
+) -> Result<CustomizeResponder<MediaFile>, APIError> {
+	let mut options_input = options_input.into_inner();
+	options_input.format = options_input
+		.format
+		.or_else(|| negotiate_thumbnail_format(&request));
+	let options = thumbnail::Options::from(options_input);
+
+	let thumbnail_path = block(move || -> Result<PathBuf, APIError> {
+		let image_path = index.get_artist_image(&name)?;
+		Ok(thumbnails_manager.get_thumbnail(&image_path, &options)?)
+	})
+	.await?;
+
+	let named_file = NamedFile::open(thumbnail_path).map_err(|_| APIError::ThumbnailFileIOError)?;
+	Ok(MediaFile::new(named_file)
+		.customize()
+		.insert_header((header::VARY, "Accept")))
+}
+
+/// Picks a thumbnail format from the `Accept` header, leaving the default (JPEG) when the
+/// client does not explicitly accept WebP.
+fn negotiate_thumbnail_format(request: &HttpRequest) -> Option<dto::ThumbnailFormat> {
+	let accept = request.headers().get(header::ACCEPT)?.to_str().ok()?;
+	let accepts_webp = accept.split(',').any(|media_range| {
+		let mut parameters = media_range.split(';').map(str::trim);
+		let is_webp = parameters
+			.next()
+			.is_some_and(|t| t.eq_ignore_ascii_case("image/webp"));
+		let is_refused = parameters.any(|p| {
+			p.strip_prefix("q=")
+				.and_then(|q| q.parse::<f32>().ok())
+				.is_some_and(|q| q <= 0.0)
+		});
+		is_webp && !is_refused
+	});
+	accepts_webp.then_some(dto::ThumbnailFormat::Webp)
+}
+
+#[post("/thumbnails/prewarm")]
+async fn prewarm_thumbnails(
+	index: Data<Index>,
+	thumbnails_manager: Data<thumbnail::Manager>,
+	_admin_rights: AdminRights,
+	input: Json<dto::ThumbnailPrewarmInput>,
+) -> Result<Json<thumbnail::PrewarmProgress>, APIError> {
+	let input = input.into_inner();
+	let options = thumbnail::Options::from(dto::ThumbnailOptions {
+		size: input.size,
+		fit: input.fit,
+		pad: input.pad,
+		filter: input.filter,
+		picture_type: None,
+		format: None,
+	});
+	let progress = block(move || -> Result<thumbnail::PrewarmProgress, APIError> {
+		let path = input.path.unwrap_or_default();
+		let image_paths = index.get_artwork_paths(Path::new(&path))?;
+		Ok(thumbnails_manager.prewarm(&image_paths, &options)?)
+	})
+	.await?;
+	Ok(Json(progress))
+}
+
+#[get("/thumbnails/prewarm")]
+async fn get_thumbnail_prewarm_progress(
+	thumbnails_manager: Data<thumbnail::Manager>,
+	_admin_rights: AdminRights,
+) -> Result<Json<thumbnail::PrewarmProgress>, APIError> {
+	Ok(Json(thumbnails_manager.prewarm_progress()))
+}
+
+#[post("/thumbnails/prewarm/cancel")]
+async fn cancel_thumbnail_prewarm(
+	thumbnails_manager: Data<thumbnail::Manager>,
+	_admin_rights: AdminRights,
+) -> Result<HttpResponse, APIError> {
+	thumbnails_manager.cancel_prewarm();
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
+#[get("/lyrics/{path:.*}")]
+async fn get_lyrics(
+	vfs_manager: Data<vfs::Manager>,
+	_auth: Auth,
+	path: web::Path<String>,
+) -> Result<Json<dto::Lyrics>, APIError> {
+	let lyrics = block(move || -> Result<lyrics::Lyrics, APIError> {
+		let vfs = vfs_manager.get_vfs()?;
+		let audio_path = vfs.virtual_to_real(Path::new(path.as_str()))?;
+		lyrics::read(&audio_path).map_err(|e| e.into())
+	})
+	.await?;
+	Ok(Json(lyrics.into()))
 }
 
 #[get("/playlists")]
 async fn list_playlists(
 	playlist_manager: Data<playlist::Manager>,
 	auth: Auth,
+	query: web::Query<dto::ListPlaylistsQuery>,
 ) -> Result<Json<Vec<dto::ListPlaylistsEntry>>, APIError> {
-	let playlist_names = block(move || playlist_manager.list_playlists(&auth.username)).await?;
+	let order = query.order_by.into();
+	let playlist_names =
+		block(move || playlist_manager.list_playlists(&auth.username, order)).await?;
 	let playlists: Vec<dto::ListPlaylistsEntry> = playlist_names
 		.into_iter()
 		.map(|p| dto::ListPlaylistsEntry { name: p })
@@ -581,11 +2431,59 @@ async fn save_playlist(
 	auth: Auth,
 	name: web::Path<String>,
 	playlist: Json<dto::SavePlaylistInput>,
+) -> Result<Json<dto::SavePlaylistOutput>, APIError> {
+	let invalid_tracks = block(move || {
+		playlist_manager.save_playlist(
+			&name,
+			&auth.username,
+			&playlist.tracks,
+			playlist.strict,
+			playlist.sort_by_disc_and_track,
+		)
+	})
+	.await?;
+	Ok(Json(dto::SavePlaylistOutput { invalid_tracks }))
+}
+
+/// Playlists too large to save in a single request are uploaded in chunks: an upload is started,
+/// tracks are appended to it and the upload is then committed.
+#[post("/playlist/{name}/upload")]
+async fn begin_playlist_upload(
+	playlist_manager: Data<playlist::Manager>,
+	auth: Auth,
+	name: web::Path<String>,
+	input: Json<dto::BeginPlaylistUploadInput>,
+) -> Result<Json<dto::PlaylistUpload>, APIError> {
+	let id = playlist_manager.begin_save(
+		&name,
+		&auth.username,
+		input.strict,
+		input.sort_by_disc_and_track,
+	);
+	Ok(Json(dto::PlaylistUpload { id }))
+}
+
+#[post("/playlist_upload/{id}")]
+async fn append_playlist_upload(
+	playlist_manager: Data<playlist::Manager>,
+	auth: Auth,
+	id: web::Path<String>,
+	input: Json<dto::AppendPlaylistUploadInput>,
 ) -> Result<HttpResponse, APIError> {
-	block(move || playlist_manager.save_playlist(&name, &auth.username, &playlist.tracks)).await?;
+	playlist_manager.append_to_save(&id, &auth.username, &input.tracks)?;
 	Ok(HttpResponse::new(StatusCode::OK))
 }
 
+#[post("/playlist_upload/{id}/commit")]
+async fn commit_playlist_upload(
+	playlist_manager: Data<playlist::Manager>,
+	auth: Auth,
+	id: web::Path<String>,
+) -> Result<Json<dto::SavePlaylistOutput>, APIError> {
+	let invalid_tracks = block(move || playlist_manager.commit_save(&id, &auth.username)).await?;
+	Ok(Json(dto::SavePlaylistOutput { invalid_tracks }))
+}
+
 #[get("/playlist/{name}")]
 async fn read_playlist(
 	playlist_manager: Data<playlist::Manager>,
@@ -596,6 +2494,102 @@ async fn read_playlist(
 	Ok(Json(songs))
 }
 
+#[get("/playlist/{name}/page")]
+async fn read_playlist_page(
+	playlist_manager: Data<playlist::Manager>,
+	settings_manager: Data<settings::Manager>,
+	auth: Auth,
+	name: web::Path<String>,
+	page: web::Query<dto::PlaylistPageQuery>,
+) -> Result<Json<dto::PlaylistPage>, APIError> {
+	let page = block(move || -> Result<_, APIError> {
+		let count = settings_manager.get_page_size(page.count)?;
+		Ok(playlist_manager.read_playlist_page(&name, &auth.username, page.offset, count)?)
+	})
+	.await?;
+	Ok(Json(page.into()))
+}
+
+/// Links to audio files are made absolute using the public base URL from settings,
+/// or the address this request was sent to when none is set.
+#[get("/playlist/{name}/m3u")]
+async fn export_playlist(
+	playlist_manager: Data<playlist::Manager>,
+	settings_manager: Data<settings::Manager>,
+	auth: Auth,
+	name: web::Path<String>,
+	request: HttpRequest,
+) -> Result<HttpResponse, APIError> {
+	let request_base_url = {
+		let connection_info = request.connection_info();
+		format!("{}://{}", connection_info.scheme(), connection_info.host())
+	};
+	let m3u = block(move || -> Result<String, APIError> {
+		let public_base_url = settings_manager.read()?.public_base_url;
+		let base_url = match settings::parse_public_base_url(&public_base_url)? {
+			Some(url) => url.to_string(),
+			None => request_base_url,
+		};
+		let songs = playlist_manager.read_playlist(&name, &auth.username)?;
+		Ok(make_m3u(&base_url, &songs))
+	})
+	.await?;
+	Ok(HttpResponse::Ok()
+		.content_type("audio/x-mpegurl; charset=utf-8")
+		.body(m3u))
+}
+
+fn make_m3u(base_url: &str, songs: &[index::Song]) -> String {
+	let base_url = base_url.trim_end_matches('/');
+	let mut m3u = String::from("#EXTM3U\n");
+	for song in songs {
+		let duration = song.duration.unwrap_or(-1);
+		let label = match (&song.artist, &song.title) {
+			(Some(artist), Some(title)) => format!("{} - {}", artist, title),
+			(None, Some(title)) => title.clone(),
+			_ => song.path.clone(),
+		};
+		let path = utf8_percent_encode(&song.path, NON_ALPHANUMERIC);
+		m3u.push_str(&format!("#EXTINF:{},{}\n", duration, label));
+		m3u.push_str(&format!("{}/api/audio/{}\n", base_url, path));
+	}
+	m3u
+}
+
+#[patch("/playlist/{name}/move")]
+async fn move_playlist_track(
+	playlist_manager: Data<playlist::Manager>,
+	auth: Auth,
+	name: web::Path<String>,
+	input: Json<dto::MovePlaylistTrackInput>,
+) -> Result<HttpResponse, APIError> {
+	block(move || {
+		playlist_manager.move_track(&name, &auth.username, input.from_index, input.to_index)
+	})
+	.await?;
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
+#[get("/playlist/{name}/orphans")]
+async fn playlist_orphans(
+	playlist_manager: Data<playlist::Manager>,
+	auth: Auth,
+	name: web::Path<String>,
+) -> Result<Json<Vec<String>>, APIError> {
+	let orphans = block(move || playlist_manager.find_orphans(&name, &auth.username)).await?;
+	Ok(Json(orphans))
+}
+
+#[post("/playlist/{name}/prune")]
+async fn prune_playlist(
+	playlist_manager: Data<playlist::Manager>,
+	auth: Auth,
+	name: web::Path<String>,
+) -> Result<Json<Vec<String>>, APIError> {
+	let pruned = block(move || playlist_manager.prune_orphans(&name, &auth.username)).await?;
+	Ok(Json(pruned))
+}
+
 #[delete("/playlist/{name}")]
 async fn delete_playlist(
 	playlist_manager: Data<playlist::Manager>,
@@ -606,6 +2600,30 @@ async fn delete_playlist(
 	Ok(HttpResponse::new(StatusCode::OK))
 }
 
+#[delete("/playlists")]
+async fn delete_playlists(
+	playlist_manager: Data<playlist::Manager>,
+	user_manager: Data<user::Manager>,
+	auth: Auth,
+	input: Json<dto::DeletePlaylistsInput>,
+) -> Result<Json<dto::DeletePlaylistsOutput>, APIError> {
+	let input = input.into_inner();
+	let deletion = block(move || -> Result<playlist::BulkDeletion, APIError> {
+		let owner = match input.owner {
+			Some(owner) if owner != auth.username => {
+				if !user_manager.is_admin(&auth.username)? {
+					return Err(APIError::AdminPermissionRequired);
+				}
+				owner
+			}
+			_ => auth.username,
+		};
+		Ok(playlist_manager.delete_playlists(&input.names, &owner)?)
+	})
+	.await?;
+	Ok(Json(deletion.into()))
+}
+
 #[put("/lastfm/now_playing/{path:.*}")]
 async fn lastfm_now_playing(
 	lastfm_manager: Data<lastfm::Manager>,
@@ -617,8 +2635,7 @@ async fn lastfm_now_playing(
 		if !user_manager.is_lastfm_linked(&auth.username) {
 			return Err(APIError::LastFMAccountNotLinked);
 		}
-		let path = percent_decode_str(&path).decode_utf8_lossy();
-		lastfm_manager.now_playing(&auth.username, Path::new(path.as_ref()))?;
+		lastfm_manager.now_playing(&auth.username, Path::new(path.as_str()))?;
 		Ok(())
 	})
 	.await?;
@@ -636,8 +2653,7 @@ async fn lastfm_scrobble(
 		if !user_manager.is_lastfm_linked(&auth.username) {
 			return Err(APIError::LastFMAccountNotLinked);
 		}
-		let path = percent_decode_str(&path).decode_utf8_lossy();
-		lastfm_manager.scrobble(&auth.username, Path::new(path.as_ref()))?;
+		lastfm_manager.scrobble(&auth.username, Path::new(path.as_str()))?;
 		Ok(())
 	})
 	.await?;
@@ -695,3 +2711,214 @@ async fn lastfm_unlink(
 	block(move || lastfm_manager.unlink(&auth.username)).await?;
 	Ok(HttpResponse::new(StatusCode::OK))
 }
+
+#[get("/shares")]
+async fn list_shares(
+	share_manager: Data<share::Manager>,
+	_admin_rights: AdminRights,
+) -> Result<Json<Vec<dto::Share>>, APIError> {
+	let shares = block(move || share_manager.list()).await?;
+	Ok(Json(shares.into_iter().map(|s| s.into()).collect()))
+}
+
+#[post("/shares")]
+async fn create_share(
+	share_manager: Data<share::Manager>,
+	_admin_rights: AdminRights,
+	new_share: Json<dto::NewShare>,
+) -> Result<Json<dto::Share>, APIError> {
+	let new_share = new_share.into_inner().into();
+	let share = block(move || share_manager.create(&new_share)).await?;
+	Ok(Json(share.into()))
+}
+
+#[delete("/shares/{token}")]
+async fn delete_share(
+	share_manager: Data<share::Manager>,
+	_admin_rights: AdminRights,
+	token: web::Path<String>,
+) -> Result<HttpResponse, APIError> {
+	block(move || share_manager.delete(&token)).await?;
+	Ok(HttpResponse::new(StatusCode::OK))
+}
+
+fn browse_shared_path(
+	index: &Index,
+	share_manager: &share::Manager,
+	token: &str,
+	path: Option<&Path>,
+) -> Result<Vec<dto::CollectionFile>, APIError> {
+	let path = match path {
+		Some(p) => {
+			share_manager.authorize(token, p)?;
+			p.to_owned()
+		}
+		None => PathBuf::from(share_manager.get(token)?.path),
+	};
+	let files = index.browse(path, false)?;
+	Ok(files
+		.into_iter()
+		.map(|f| match f {
+			index::CollectionFile::Directory(d) => dto::CollectionFile::Directory(d),
+			index::CollectionFile::Song(s) => dto::CollectionFile::Song(dto::Song::new(s, None)),
+		})
+		.collect())
+}
+
+#[get("/share/{token}/browse")]
+async fn browse_share_root(
+	index: Data<Index>,
+	share_manager: Data<share::Manager>,
+	token: web::Path<String>,
+) -> Result<Json<Vec<dto::CollectionFile>>, APIError> {
+	let result = block(move || browse_shared_path(&index, &share_manager, &token, None)).await?;
+	Ok(Json(result))
+}
+
+#[get("/share/{token}/browse/{path:.*}")]
+async fn browse_share(
+	index: Data<Index>,
+	share_manager: Data<share::Manager>,
+	path: web::Path<(String, String)>,
+) -> Result<Json<Vec<dto::CollectionFile>>, APIError> {
+	let result = block(move || {
+		let (token, path) = path.into_inner();
+		browse_shared_path(&index, &share_manager, &token, Some(Path::new(&path)))
+	})
+	.await?;
+	Ok(Json(result))
+}
+
+#[get("/share/{token}/audio/{path:.*}")]
+async fn get_share_audio(
+	request: HttpRequest,
+	vfs_manager: Data<vfs::Manager>,
+	share_manager: Data<share::Manager>,
+	path: web::Path<(String, String)>,
+) -> Result<Either<MediaFile, RemoteMediaFile>, APIError> {
+	// Resuming an interrupted download does not count against the download limit
+	let counts_as_download = !resumes_download(&request);
+	let (audio_path, remote) = block(move || -> Result<_, APIError> {
+		let (token, path) = path.into_inner();
+		let virtual_path = Path::new(&path);
+		share_manager.authorize(&token, virtual_path)?;
+		let vfs = vfs_manager.get_vfs()?;
+		let audio_path = vfs.virtual_to_real(virtual_path)?;
+		let remote = match vfs.locate_remote(&audio_path) {
+			Some(remote) => {
+				let length = remote.size().map_err(|_| APIError::AudioFileIOError)?;
+				Some((remote, length))
+			}
+			None if audio_path.is_file() => None,
+			None => return Err(APIError::AudioFileIOError),
+		};
+		if counts_as_download {
+			share_manager.record_download(&token)?;
+		}
+		Ok((audio_path, remote))
+	})
+	.await?;
+
+	if let Some((remote, length)) = remote {
+		return Ok(Either::Right(RemoteMediaFile::new(
+			audio_path, remote, length,
+		)));
+	}
+
+	let named_file = NamedFile::open(audio_path).map_err(|_| APIError::AudioFileIOError)?;
+	Ok(Either::Left(MediaFile::new(named_file)))
+}
+
+#[cfg(test)]
+mod test {
+	use actix_web::test::TestRequest;
+	use futures_util::TryStreamExt;
+	use std::fs;
+
+	use super::*;
+	use crate::app::test::MockBackend;
+	use crate::test::prepare_test_directory;
+	use crate::test_name;
+
+	#[test]
+	fn media_file_removed_before_streaming_is_not_found() {
+		let output_dir = prepare_test_directory(test_name!());
+		let path = output_dir.join("sample.mp3");
+		fs::copy("test-data/formats/sample.mp3", &path).unwrap();
+
+		let named_file = NamedFile::open(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+
+		let request = TestRequest::default().to_http_request();
+		let response = MediaFile::new(named_file).respond_to(&request);
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+	}
+
+	#[test]
+	fn media_file_truncated_before_streaming_has_new_length() {
+		let output_dir = prepare_test_directory(test_name!());
+		let path = output_dir.join("sample.mp3");
+		fs::copy("test-data/formats/sample.mp3", &path).unwrap();
+
+		let named_file = NamedFile::open(&path).unwrap();
+		fs::write(&path, [0u8; 16]).unwrap();
+
+		let request = TestRequest::default().to_http_request();
+		let response = MediaFile::new(named_file).respond_to(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(response.body().size(), BodySize::Sized(16));
+	}
+
+	#[test]
+	fn larger_stream_buffer_needs_fewer_reads() {
+		let output_dir = prepare_test_directory(test_name!());
+		let path = output_dir.join("large.bin");
+		let length = 1024 * 1024;
+		fs::write(&path, vec![7u8; length]).unwrap();
+
+		let count_reads = |buffer_size: usize| {
+			let file = fs::File::open(&path).unwrap();
+			let stream = read_file_range(file, 0, length as u64, StreamBufferSize(buffer_size));
+			let chunks: Vec<Bytes> = actix_web::rt::System::new()
+				.block_on(stream.try_collect())
+				.unwrap();
+			assert_eq!(chunks.iter().map(|c| c.len()).sum::<usize>(), length);
+			chunks.len()
+		};
+
+		assert_eq!(count_reads(64 * 1024), 16);
+		assert_eq!(count_reads(256 * 1024), 4);
+	}
+
+	#[test]
+	fn remote_media_file_serves_ranges() {
+		let content = fs::read("test-data/formats/sample.mp3").unwrap();
+		let length = content.len() as u64;
+		let path = PathBuf::from("mock://formats/sample.mp3");
+		let remote = RemoteLocation {
+			backend: MockBackend::new("test-data/formats"),
+			path: PathBuf::from("sample.mp3"),
+		};
+
+		let request = TestRequest::default()
+			.insert_header((header::RANGE, "bytes=100-199"))
+			.to_http_request();
+		let response =
+			RemoteMediaFile::new(path.clone(), remote.clone(), length).respond_to(&request);
+		assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+		assert_eq!(
+			response.headers().get(header::CONTENT_RANGE).unwrap(),
+			format!("bytes 100-199/{}", length).as_str()
+		);
+		let body = actix_web::rt::System::new()
+			.block_on(actix_web::body::to_bytes(response.into_body()))
+			.unwrap();
+		assert_eq!(body, &content[100..200]);
+
+		let request = TestRequest::default()
+			.insert_header((header::RANGE, format!("bytes={}-", length)))
+			.to_http_request();
+		let response = RemoteMediaFile::new(path, remote, length).respond_to(&request);
+		assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+	}
+}
@@ -0,0 +1,126 @@
+use actix_web::{
+	dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+	Error,
+};
+use futures_util::future::{poll_fn, ready, LocalBoxFuture, Ready};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::task::{Poll, Waker};
+
+/// Requests in progress on a single connection. HTTP/2 clients can send many of them at once.
+#[derive(Clone)]
+pub struct ConnectionStreams(Rc<StreamSlots>);
+
+struct StreamSlots {
+	max_streams: usize,
+	in_use: Cell<usize>,
+	waiting: RefCell<Vec<Waker>>,
+}
+
+impl ConnectionStreams {
+	pub fn new(max_streams: usize) -> Self {
+		Self(Rc::new(StreamSlots {
+			max_streams: max_streams.max(1),
+			in_use: Cell::new(0),
+			waiting: RefCell::new(Vec::new()),
+		}))
+	}
+
+	/// Waits until the connection has fewer than the maximum number of requests in progress.
+	async fn acquire(&self) -> StreamPermit {
+		poll_fn(|context| {
+			let slots = &self.0;
+			if slots.in_use.get() < slots.max_streams {
+				slots.in_use.set(slots.in_use.get() + 1);
+				Poll::Ready(StreamPermit(slots.clone()))
+			} else {
+				slots.waiting.borrow_mut().push(context.waker().clone());
+				Poll::Pending
+			}
+		})
+		.await
+	}
+}
+
+struct StreamPermit(Rc<StreamSlots>);
+
+impl Drop for StreamPermit {
+	fn drop(&mut self) {
+		self.0.in_use.set(self.0.in_use.get() - 1);
+		// Requests which stopped waiting never claim the slot, so every waiting request gets a chance
+		for waker in self.0.waiting.take() {
+			waker.wake();
+		}
+	}
+}
+
+/// Holds back requests of a connection while it already has the maximum number of requests in
+/// progress. The limit comes from the `ConnectionStreams` registered when the connection opened,
+/// and connections without one are not limited.
+pub struct StreamLimit;
+
+impl<S, B> Transform<S, ServiceRequest> for StreamLimit
+where
+	S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+	B: 'static,
+{
+	type Response = ServiceResponse<B>;
+	type Error = Error;
+	type Transform = StreamLimitMiddleware<S>;
+	type InitError = ();
+	type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+	fn new_transform(&self, service: S) -> Self::Future {
+		ready(Ok(StreamLimitMiddleware {
+			service: Rc::new(service),
+		}))
+	}
+}
+
+pub struct StreamLimitMiddleware<S> {
+	service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for StreamLimitMiddleware<S>
+where
+	S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+	B: 'static,
+{
+	type Response = ServiceResponse<B>;
+	type Error = Error;
+	type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+	forward_ready!(service);
+
+	fn call(&self, request: ServiceRequest) -> Self::Future {
+		let streams = request.conn_data::<ConnectionStreams>().cloned();
+		let service = self.service.clone();
+		Box::pin(async move {
+			let _permit = match streams {
+				Some(streams) => Some(streams.acquire().await),
+				None => None,
+			};
+			service.call(request).await
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use futures_util::FutureExt;
+
+	use super::*;
+
+	#[test]
+	fn requests_beyond_the_limit_wait_for_a_free_stream() {
+		let streams = ConnectionStreams::new(2);
+		let first = streams.acquire().now_or_never().unwrap();
+		let _second = streams.acquire().now_or_never().unwrap();
+
+		let mut third = Box::pin(streams.acquire());
+		assert!((&mut third).now_or_never().is_none());
+
+		drop(first);
+		assert!(third.now_or_never().is_some());
+	}
+}
@@ -11,12 +11,15 @@ use serde::Serialize;
 use std::ops::Deref;
 
 use crate::app::App;
+use crate::db;
 use crate::paths::Paths;
 use crate::service::actix::*;
 use crate::service::dto;
 use crate::service::test::TestService;
 use crate::test::*;
 
+const MAX_BODY_SIZE: usize = 64 * 1024 * 1024;
+
 pub struct ActixTestService {
 	system_runner: SystemRunner,
 	authorization: Option<dto::Authorization>,
@@ -29,15 +32,17 @@ impl ActixTestService {
 	fn process_internal<T: Serialize + Clone + 'static>(
 		&mut self,
 		request: &Request<T>,
-	) -> (Builder, Option<Bytes>) {
+	) -> (Builder, Bytes) {
 		let url = request.uri().to_string();
 		let body = request.body().clone();
 
 		let mut actix_request = match *request.method() {
 			Method::GET => self.server.get(url),
+			Method::HEAD => self.server.head(url),
 			Method::POST => self.server.post(url),
 			Method::PUT => self.server.put(url),
 			Method::DELETE => self.server.delete(url),
+			Method::PATCH => self.server.patch(url),
 			_ => unimplemented!(),
 		}
 		.timeout(std::time::Duration::from_secs(30));
@@ -60,15 +65,10 @@ impl ActixTestService {
 			headers.append(name, value.clone());
 		}
 
-		let is_success = actix_response.status().is_success();
-		let body = if is_success {
-			Some(
-				self.system_runner
-					.block_on(async move { actix_response.body().await.unwrap() }),
-			)
-		} else {
-			None
-		};
+		// Large playlists echo back every track that could not be found
+		let body = self
+			.system_runner
+			.block_on(async move { actix_response.body().limit(MAX_BODY_SIZE).await.unwrap() });
 
 		(response_builder, body)
 	}
@@ -89,7 +89,7 @@ impl TestService for ActixTestService {
 			web_dir_path: ["test-data", "web"].iter().collect(),
 		};
 
-		let app = App::new(5050, paths).unwrap();
+		let app = App::new(5050, paths, db::DEFAULT_READ_POOL_SIZE).unwrap();
 
 		let system_runner = System::new();
 		let server = actix_test::start(move || {
@@ -117,9 +117,7 @@ impl TestService for ActixTestService {
 		request: &Request<T>,
 	) -> Response<Vec<u8>> {
 		let (response_builder, body) = self.process_internal(request);
-		response_builder
-			.body(body.unwrap().deref().to_owned())
-			.unwrap()
+		response_builder.body(body.deref().to_owned()).unwrap()
 	}
 
 	fn fetch_json<T: Serialize + Clone + 'static, U: DeserializeOwned>(
@@ -127,7 +125,7 @@ impl TestService for ActixTestService {
 		request: &Request<T>,
 	) -> Response<U> {
 		let (response_builder, body) = self.process_internal(request);
-		let body = serde_json::from_slice(&body.unwrap()).unwrap();
+		let body = serde_json::from_slice(&body).unwrap();
 		response_builder.body(body).unwrap()
 	}
 
@@ -0,0 +1,113 @@
+use actix_web::{
+	dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+	Error,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use log::error;
+use std::time::Duration;
+
+use crate::service::error::APIError;
+
+/// Answers with a 504 status when the handler of a request takes longer than a given duration to
+/// produce a response.
+/// The timeout surfaces as an error rather than a response of its own, because routing panics if
+/// this middleware holds on to a copy of the request while it happens.
+/// Only the time until the response starts is limited, so streamed bodies (audio files,
+/// archives) can take as long as they need to be sent.
+pub struct RequestTimeout {
+	duration: Duration,
+}
+
+impl RequestTimeout {
+	pub fn new(duration: Duration) -> Self {
+		Self { duration }
+	}
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTimeout
+where
+	S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+	S::Future: 'static,
+	B: 'static,
+{
+	type Response = ServiceResponse<B>;
+	type Error = Error;
+	type Transform = RequestTimeoutMiddleware<S>;
+	type InitError = ();
+	type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+	fn new_transform(&self, service: S) -> Self::Future {
+		ready(Ok(RequestTimeoutMiddleware {
+			service,
+			duration: self.duration,
+		}))
+	}
+}
+
+pub struct RequestTimeoutMiddleware<S> {
+	service: S,
+	duration: Duration,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTimeoutMiddleware<S>
+where
+	S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+	S::Future: 'static,
+	B: 'static,
+{
+	type Response = ServiceResponse<B>;
+	type Error = Error;
+	type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+	forward_ready!(service);
+
+	fn call(&self, request: ServiceRequest) -> Self::Future {
+		let duration = self.duration;
+		let path = request.path().to_owned();
+		let response_future = self.service.call(request);
+		Box::pin(async move {
+			match actix_web::rt::time::timeout(duration, response_future).await {
+				Ok(response) => response,
+				Err(_) => {
+					error!("Request to `{}` timed out after {:?}", path, duration);
+					Err(APIError::RequestTimeout.into())
+				}
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use actix_web::test::{call_service, init_service, try_call_service, TestRequest};
+	use actix_web::{http::StatusCode, rt::System, web, App, HttpResponse};
+
+	use super::*;
+
+	#[test]
+	fn slow_handler_is_cut_off() {
+		System::new().block_on(async {
+			let app = init_service(
+				App::new()
+					.wrap(RequestTimeout::new(Duration::from_millis(100)))
+					.route(
+						"/slow",
+						web::get().to(|| async {
+							actix_web::rt::time::sleep(Duration::from_secs(10)).await;
+							HttpResponse::Ok().finish()
+						}),
+					)
+					.route("/fast", web::get().to(HttpResponse::Ok)),
+			)
+			.await;
+
+			let request = TestRequest::get().uri("/slow").to_request();
+			let error = try_call_service(&app, request).await.unwrap_err();
+			assert_eq!(error.error_response().status(), StatusCode::GATEWAY_TIMEOUT);
+
+			let request = TestRequest::get().uri("/fast").to_request();
+			let response = call_service(&app, request).await;
+			assert_eq!(response.status(), StatusCode::OK);
+		});
+	}
+}
@@ -1,7 +1,13 @@
 use serde::{Deserialize, Serialize};
 
-use crate::app::{config, ddns, settings, thumbnail, user, vfs};
+use crate::app::{
+	bandwidth, config, ddns, index, lyrics, play_count, playlist, progress, settings, share,
+	thumbnail, user, vfs,
+};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
 use std::convert::From;
+use std::hash::{Hash, Hasher};
 
 pub const API_MAJOR_VERSION: i32 = 7;
 pub const API_MINOR_VERSION: i32 = 0;
@@ -12,9 +18,88 @@ pub struct Version {
 	pub minor: i32,
 }
 
+/// Optional features and whether they are available on this server.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Capabilities {
+	pub tls: bool,
+	pub ddns: bool,
+	pub lastfm: bool,
+	pub bandwidth_tracking: bool,
+	pub native_ui: bool,
+	pub transcoding: bool,
+	pub subsonic: bool,
+	pub listenbrainz: bool,
+}
+
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct InitialSetup {
 	pub has_any_users: bool,
+	pub has_any_mounts: bool,
+	pub has_indexed_collection: bool,
+}
+
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct IndexStatus {
+	pub state: index::UpdateStatus,
+}
+
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScanError {
+	PathCollision { kept: String, ignored: Vec<String> },
+	DepthLimitReached { path: String },
+	FileTooSmall { path: String, size: u64 },
+	PersistentlyBroken { path: String, failures: u32 },
+}
+
+impl From<index::ScanError> for ScanError {
+	fn from(e: index::ScanError) -> Self {
+		match e {
+			index::ScanError::PathCollision { kept, ignored } => Self::PathCollision {
+				kept: kept.to_string_lossy().into_owned(),
+				ignored: ignored
+					.iter()
+					.map(|p| p.to_string_lossy().into_owned())
+					.collect(),
+			},
+			index::ScanError::DepthLimitReached { path } => Self::DepthLimitReached {
+				path: path.to_string_lossy().into_owned(),
+			},
+			index::ScanError::FileTooSmall { path, size } => Self::FileTooSmall {
+				path: path.to_string_lossy().into_owned(),
+				size,
+			},
+			index::ScanError::PersistentlyBroken { path, failures } => Self::PersistentlyBroken {
+				path: path.to_string_lossy().into_owned(),
+				failures,
+			},
+		}
+	}
+}
+
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ScanEvent {
+	DirectoryEntered { path: String },
+	FileIndexed { path: String },
+	FileFailed { path: String, reason: String },
+}
+
+impl From<index::ScanEvent> for ScanEvent {
+	fn from(e: index::ScanEvent) -> Self {
+		match e {
+			index::ScanEvent::DirectoryEntered { path } => Self::DirectoryEntered {
+				path: path.to_string_lossy().into_owned(),
+			},
+			index::ScanEvent::FileIndexed { path } => Self::FileIndexed {
+				path: path.to_string_lossy().into_owned(),
+			},
+			index::ScanEvent::FileFailed { path, reason } => Self::FileFailed {
+				path: path.to_string_lossy().into_owned(),
+				reason,
+			},
+		}
+	}
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -23,6 +108,13 @@ pub struct Credentials {
 	pub password: String,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BootstrapAdmin {
+	pub token: String,
+	pub username: String,
+	pub password: String,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Authorization {
 	pub username: String,
@@ -35,21 +127,503 @@ pub struct AuthQueryParameters {
 	pub auth_token: String,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lyrics {
+	pub synced: bool,
+	pub lines: Vec<LyricsLine>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LyricsLine {
+	pub timestamp_ms: Option<u64>,
+	pub text: String,
+}
+
+impl From<lyrics::Lyrics> for Lyrics {
+	fn from(l: lyrics::Lyrics) -> Self {
+		Self {
+			synced: l.synced,
+			lines: l
+				.lines
+				.into_iter()
+				.map(|line| LyricsLine {
+					timestamp_ms: line.timestamp.map(|t| t.as_millis() as u64),
+					text: line.text,
+				})
+				.collect(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayQuery {
+	pub path: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SongQuery {
+	pub path: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AncestorsQuery {
+	pub path: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AlbumQuery {
+	pub path: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayCount {
+	pub count: u32,
+	pub last_played: Option<i64>,
+}
+
+impl From<play_count::PlayCount> for PlayCount {
+	fn from(p: play_count::PlayCount) -> Self {
+		Self {
+			count: p.count,
+			last_played: p.last_played,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryFormat {
+	#[default]
+	Json,
+	Csv,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HistoryQuery {
+	#[serde(default)]
+	pub format: HistoryFormat,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActivityQuery {
+	pub count: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityEntry {
+	pub username: String,
+	pub path: String,
+	pub played_at: i64,
+}
+
+impl From<play_count::ActivityEntry> for ActivityEntry {
+	fn from(e: play_count::ActivityEntry) -> Self {
+		Self {
+			username: e.username,
+			path: e.path,
+			played_at: e.played_at,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+	pub path: String,
+	pub played_at: i64,
+	pub play_count: u32,
+}
+
+impl From<play_count::HistoryEntry> for HistoryEntry {
+	fn from(e: play_count::HistoryEntry) -> Self {
+		Self {
+			path: e.path,
+			played_at: e.played_at,
+			play_count: e.play_count,
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Song {
+	#[serde(flatten)]
+	pub song: index::Song,
+	/// Individual names within the album artist tag.
+	pub album_artists: Vec<String>,
+	pub conductors: Vec<String>,
+	pub remixers: Vec<String>,
+	pub arrangers: Vec<String>,
+	pub performers: Vec<String>,
+	/// Performers of the original work, for cover versions.
+	pub original_artists: Vec<String>,
+	pub languages: Vec<String>,
+	/// Shared by songs on the same disc of the same album, so clients can tell consecutive
+	/// tracks apart from album changes (eg. to decide whether to crossfade).
+	pub album_group_id: String,
+	/// Heading of the disc within its album, such as `Disc 1: The Early Years`.
+	pub disc_label: Option<String>,
+	pub play_count: u32,
+	pub last_played: Option<i64>,
+	/// Custom tags, only included when enabled in settings.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub extra: Option<HashMap<String, Vec<String>>>,
+}
+
+impl Song {
+	pub fn new(song: index::Song, play_count: Option<&play_count::PlayCount>) -> Self {
+		let play_count = play_count.copied().unwrap_or_default();
+		Self {
+			album_artists: index::split_names(song.album_artist.as_deref()),
+			conductors: index::split_names(song.conductor.as_deref()),
+			remixers: index::split_names(song.remixer.as_deref()),
+			arrangers: index::split_names(song.arranger.as_deref()),
+			performers: index::split_names(song.performer.as_deref()),
+			original_artists: index::split_names(song.original_artist.as_deref()),
+			languages: index::split_names(song.language.as_deref()),
+			album_group_id: album_group_id(&song),
+			disc_label: disc_label(&song),
+			song,
+			play_count: play_count.count,
+			last_played: play_count.last_played,
+			extra: None,
+		}
+	}
+
+	pub fn with_extra_tags(mut self) -> Self {
+		let extra = self
+			.song
+			.extra
+			.as_deref()
+			.and_then(|e| serde_json::from_str(e).ok());
+		self.extra = Some(extra.unwrap_or_default());
+		self
+	}
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GetSongsInput {
+	pub paths: Vec<String>,
+}
+
+/// Songs surrounding a song within its directory, in album order.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SongSiblings {
+	pub previous: Option<Song>,
+	pub next: Option<Song>,
+}
+
+/// An album directory with its tracks in album order, and statistics about them.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlbumDetails {
+	pub directory: index::Directory,
+	pub songs: Vec<Song>,
+	pub track_count: usize,
+	/// Sum of the known durations of the tracks, in seconds.
+	pub total_duration: Option<i32>,
+	/// Distinct genres of the tracks, sorted alphabetically.
+	pub genres: Vec<String>,
+	/// Distinct release years of the tracks, oldest first.
+	pub years: Vec<i32>,
+}
+
+impl AlbumDetails {
+	pub fn new(directory: index::Directory, songs: Vec<Song>) -> Self {
+		let total_duration = songs
+			.iter()
+			.filter_map(|s| s.song.duration)
+			.reduce(|a, b| a + b);
+		let genres: BTreeSet<String> = songs
+			.iter()
+			.flat_map(|s| index::split_names(s.song.genre.as_deref()))
+			.collect();
+		let years: BTreeSet<i32> = songs.iter().filter_map(|s| s.song.year).collect();
+		Self {
+			directory,
+			track_count: songs.len(),
+			total_duration,
+			genres: genres.into_iter().collect(),
+			years: years.into_iter().collect(),
+			songs,
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GetSongsOutput {
+	pub songs: Vec<Song>,
+	/// Requested paths which are not in the collection.
+	pub not_found: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProgressQuery {
+	pub path: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SetProgressQuery {
+	pub path: String,
+	pub seconds: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecentProgressQuery {
+	pub count: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+	pub seconds: u32,
+	pub updated_at: i64,
+}
+
+impl From<progress::Position> for Progress {
+	fn from(p: progress::Position) -> Self {
+		Self {
+			seconds: p.seconds,
+			updated_at: p.updated_at,
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SongProgress {
+	pub song: Song,
+	pub progress: Progress,
+}
+
+fn album_group_id(song: &index::Song) -> String {
+	let mut hasher = DefaultHasher::new();
+	song.album_artist.hash(&mut hasher);
+	song.album.hash(&mut hasher);
+	song.disc_number.hash(&mut hasher);
+	format!("{:016x}", hasher.finish())
+}
+
+fn disc_label(song: &index::Song) -> Option<String> {
+	match (song.disc_number, &song.disc_subtitle) {
+		(Some(number), Some(subtitle)) => Some(format!("Disc {}: {}", number, subtitle)),
+		(Some(number), None) => Some(format!("Disc {}", number)),
+		(None, subtitle) => subtitle.clone(),
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TopWindow {
+	#[default]
+	AllTime,
+	LastThirtyDays,
+}
+
+impl From<TopWindow> for play_count::Window {
+	fn from(w: TopWindow) -> Self {
+		match w {
+			TopWindow::AllTime => Self::AllTime,
+			TopWindow::LastThirtyDays => Self::LastThirtyDays,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TopKind {
+	#[default]
+	Songs,
+	Albums,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ShuffleQuery {
+	pub count: Option<usize>,
+	pub seed: Option<u64>,
+	pub genre: Option<String>,
+	pub artist: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TopQuery {
+	pub count: Option<usize>,
+	#[serde(default)]
+	pub window: TopWindow,
+	#[serde(default)]
+	pub kind: TopKind,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Top {
+	Songs(Vec<Song>),
+	Albums(Vec<index::Directory>),
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Album {
+	#[serde(flatten)]
+	pub directory: index::Directory,
+	/// Other copies of this album, when duplicate albums are merged.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub merged_paths: Vec<String>,
+	/// Combined track listing of all copies, when duplicate albums are merged.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub songs: Vec<index::Song>,
+}
+
+impl From<index::Album> for Album {
+	fn from(a: index::Album) -> Self {
+		Self {
+			directory: a.directory,
+			merged_paths: a.merged_paths,
+			songs: a.songs,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SearchOptions {
+	/// Groups results into songs, albums and artists instead of a flat list.
+	#[serde(default)]
+	pub group: bool,
+	/// Maximum number of entries in each group.
+	pub count: Option<i64>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SearchResults {
+	Files(Vec<index::CollectionFile>),
+	Grouped(index::SearchResults),
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CollectionFile {
+	Directory(index::Directory),
+	Song(Song),
+	Media(index::MediaFile),
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ThumbnailOptions {
 	pub size: Option<ThumbnailSize>,
+	pub fit: Option<ThumbnailFit>,
+	/// Deprecated in favor of `fit`. Ignored when `fit` is set.
 	pub pad: Option<bool>,
+	pub filter: Option<ThumbnailFilter>,
+	pub picture_type: Option<ThumbnailPictureType>,
+	pub format: Option<ThumbnailFormat>,
 }
 
 impl From<ThumbnailOptions> for thumbnail::Options {
 	fn from(dto: ThumbnailOptions) -> Self {
 		let mut options = thumbnail::Options::default();
 		options.max_dimension = dto.size.map_or(options.max_dimension, Into::into);
-		options.pad_to_square = dto.pad.unwrap_or(options.pad_to_square);
+		if let Some(pad) = dto.pad {
+			options = options.with_pad_to_square(pad);
+		}
+		options.fit = dto.fit.map(Into::into).or(options.fit);
+		options.filter = dto.filter.map(Into::into).or(options.filter);
+		options.picture_type = dto.picture_type.map(Into::into).or(options.picture_type);
+		options.format = dto.format.map_or(options.format, Into::into);
 		options
 	}
 }
 
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ThumbnailPrewarmInput {
+	pub path: Option<String>,
+	pub size: Option<ThumbnailSize>,
+	pub fit: Option<ThumbnailFit>,
+	/// Deprecated in favor of `fit`. Ignored when `fit` is set.
+	pub pad: Option<bool>,
+	pub filter: Option<ThumbnailFilter>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CoverUpload {
+	/// Base64 encoded image, in JPEG, PNG, GIF or BMP format.
+	pub content: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThumbnailFit {
+	Pad,
+	Crop,
+	Stretch,
+}
+
+impl From<ThumbnailFit> for thumbnail::Fit {
+	fn from(f: ThumbnailFit) -> Self {
+		match f {
+			ThumbnailFit::Pad => Self::Pad,
+			ThumbnailFit::Crop => Self::Crop,
+			ThumbnailFit::Stretch => Self::Stretch,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThumbnailFilter {
+	Nearest,
+	Triangle,
+	CatmullRom,
+	Lanczos3,
+}
+
+impl From<ThumbnailFilter> for thumbnail::Filter {
+	fn from(f: ThumbnailFilter) -> Self {
+		match f {
+			ThumbnailFilter::Nearest => Self::Nearest,
+			ThumbnailFilter::Triangle => Self::Triangle,
+			ThumbnailFilter::CatmullRom => Self::CatmullRom,
+			ThumbnailFilter::Lanczos3 => Self::Lanczos3,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThumbnailPictureType {
+	Front,
+	Back,
+	Leaflet,
+	Media,
+	Artist,
+}
+
+impl From<ThumbnailPictureType> for thumbnail::PictureType {
+	fn from(t: ThumbnailPictureType) -> Self {
+		match t {
+			ThumbnailPictureType::Front => Self::Front,
+			ThumbnailPictureType::Back => Self::Back,
+			ThumbnailPictureType::Leaflet => Self::Leaflet,
+			ThumbnailPictureType::Media => Self::Media,
+			ThumbnailPictureType::Artist => Self::Artist,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThumbnailFormat {
+	Jpeg,
+	Webp,
+	Png,
+	Auto,
+}
+
+impl From<ThumbnailFormat> for thumbnail::Format {
+	fn from(f: ThumbnailFormat) -> Self {
+		match f {
+			ThumbnailFormat::Jpeg => Self::Jpeg,
+			ThumbnailFormat::Webp => Self::WebP,
+			ThumbnailFormat::Png => Self::Png,
+			ThumbnailFormat::Auto => Self::Auto,
+		}
+	}
+}
+
 #[derive(Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ThumbnailSize {
@@ -69,14 +643,276 @@ impl Into<Option<u32>> for ThumbnailSize {
 	}
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistOrder {
+	#[default]
+	Name,
+	Created,
+	Updated,
+}
+
+impl From<PlaylistOrder> for playlist::ListOrder {
+	fn from(o: PlaylistOrder) -> Self {
+		match o {
+			PlaylistOrder::Name => Self::Name,
+			PlaylistOrder::Created => Self::Created,
+			PlaylistOrder::Updated => Self::Updated,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct ListPlaylistsQuery {
+	#[serde(default)]
+	pub order_by: PlaylistOrder,
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ListPlaylistsEntry {
 	pub name: String,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MovePlaylistTrackInput {
+	pub from_index: usize,
+	pub to_index: usize,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DeletePlaylistsInput {
+	pub names: Vec<String>,
+	/// Deletes playlists of another user. Requires administrator permission.
+	#[serde(default)]
+	pub owner: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeletePlaylistsOutput {
+	pub deleted: Vec<String>,
+	pub not_found: Vec<String>,
+}
+
+impl From<playlist::BulkDeletion> for DeletePlaylistsOutput {
+	fn from(d: playlist::BulkDeletion) -> Self {
+		Self {
+			deleted: d.deleted,
+			not_found: d.not_found,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AudioOptions {
+	pub seek_percent: Option<f64>,
+	/// Token from a signed stream URL, which grants access without a session.
+	pub signature: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StreamUrlQuery {
+	pub path: String,
+	pub ttl_seconds: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamUrl {
+	/// Relative to the server root.
+	pub url: String,
+	/// Seconds since the UNIX epoch.
+	pub expires_at: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AlbumPlayQuery {
+	pub path: String,
+	/// When set, stream URLs carry a signature and can be used without authentication.
+	#[serde(default)]
+	pub signed: bool,
+	pub ttl_seconds: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlbumTrack {
+	#[serde(flatten)]
+	pub song: Song,
+	/// Relative to the server root.
+	pub stream_url: String,
+	/// Seconds since the UNIX epoch, for signed stream URLs.
+	pub expires_at: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct HiddenSongsOptions {
+	#[serde(default)]
+	pub include_hidden: bool,
+}
+
+/// Songs are listed by virtual path, so that pages stay consistent for as long as the index
+/// generation does not change.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct FlattenOptions {
+	#[serde(default)]
+	pub include_hidden: bool,
+	#[serde(default)]
+	pub offset: usize,
+	/// When unset, every song after `offset` is listed.
+	pub count: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct BrowseOptions {
+	#[serde(default)]
+	pub include_hidden: bool,
+	/// Lists companion files such as cover scans or cue sheets after songs.
+	#[serde(default)]
+	pub include_media: bool,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SavePlaylistInput {
 	pub tracks: Vec<String>,
+	#[serde(default)]
+	pub strict: bool,
+	/// Reorders tracks by disc and track number before saving.
+	#[serde(default)]
+	pub sort_by_disc_and_track: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavePlaylistOutput {
+	pub invalid_tracks: Vec<String>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct BeginPlaylistUploadInput {
+	#[serde(default)]
+	pub strict: bool,
+	/// Reorders tracks by disc and track number before saving.
+	#[serde(default)]
+	pub sort_by_disc_and_track: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlaylistUpload {
+	pub id: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AppendPlaylistUploadInput {
+	pub tracks: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IndexRefreshQuery {
+	pub path: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MetadataLintInput {
+	/// Virtual path within the collection, or real path on the server.
+	pub path: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RawMetadataQuery {
+	/// Virtual path within the collection, or real path on the server.
+	pub path: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataLintReport {
+	pub tags: SongTags,
+	pub warnings: Vec<LintWarning>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SongTags {
+	pub track_number: Option<u32>,
+	pub disc_number: Option<u32>,
+	pub track_total: Option<u32>,
+	pub disc_total: Option<u32>,
+	pub title: Option<String>,
+	pub artist: Option<String>,
+	pub album_artist: Option<String>,
+	pub album: Option<String>,
+	pub year: Option<i32>,
+	pub genre: Option<String>,
+	pub duration: Option<u32>,
+	/// Where the duration was obtained from: `tag`, `stream` or `estimate`.
+	pub duration_source: Option<String>,
+	/// Kind of content declared by the tags: `music`, `audiobook` or `podcast`.
+	pub content_type: Option<String>,
+	pub composer: Option<String>,
+	pub lyricist: Option<String>,
+	pub label: Option<String>,
+	pub has_artwork: bool,
+}
+
+impl From<index::SongTags> for SongTags {
+	fn from(t: index::SongTags) -> Self {
+		Self {
+			track_number: t.track_number,
+			disc_number: t.disc_number,
+			track_total: t.track_total,
+			disc_total: t.disc_total,
+			title: t.title,
+			artist: t.artist,
+			album_artist: t.album_artist,
+			album: t.album,
+			year: t.year,
+			genre: t.genre,
+			duration: t.duration,
+			duration_source: t.duration_source.map(|s| s.as_str().to_owned()),
+			content_type: t.content_type.map(|c| c.as_str().to_owned()),
+			composer: (!t.composers.is_empty()).then(|| t.composers.join("; ")),
+			lyricist: (!t.lyricists.is_empty()).then(|| t.lyricists.join("; ")),
+			label: t.label,
+			has_artwork: t.has_artwork,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LintWarning {
+	MissingTitle,
+	MissingArtist,
+	UnparseableYear { value: String },
+	MissingArtwork,
+}
+
+impl From<index::LintWarning> for LintWarning {
+	fn from(w: index::LintWarning) -> Self {
+		match w {
+			index::LintWarning::MissingTitle => Self::MissingTitle,
+			index::LintWarning::MissingArtist => Self::MissingArtist,
+			index::LintWarning::UnparseableYear(value) => Self::UnparseableYear { value },
+			index::LintWarning::MissingArtwork => Self::MissingArtwork,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlaylistPageQuery {
+	#[serde(default)]
+	pub offset: usize,
+	pub count: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlaylistPage {
+	pub songs: Vec<index::Song>,
+	pub total: usize,
+}
+
+impl From<playlist::PlaylistPage> for PlaylistPage {
+	fn from(p: playlist::PlaylistPage) -> Self {
+		Self {
+			songs: p.songs,
+			total: p.total,
+		}
+	}
 }
 
 #[derive(Serialize, Deserialize)]
@@ -113,6 +949,16 @@ pub struct NewUser {
 	pub admin: bool,
 }
 
+impl From<user::NewUser> for NewUser {
+	fn from(u: user::NewUser) -> Self {
+		Self {
+			name: u.name,
+			password: u.password,
+			admin: u.admin,
+		}
+	}
+}
+
 impl From<NewUser> for user::NewUser {
 	fn from(u: NewUser) -> Self {
 		Self {
@@ -129,13 +975,23 @@ pub struct UserUpdate {
 	pub new_is_admin: Option<bool>,
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[derive(Clone, Deserialize, PartialEq, Eq, Serialize)]
 pub struct DDNSConfig {
 	pub host: String,
 	pub username: String,
 	pub password: String,
 }
 
+impl std::fmt::Debug for DDNSConfig {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("DDNSConfig")
+			.field("host", &self.host)
+			.field("username", &self.username)
+			.field("password", &"<redacted>")
+			.finish()
+	}
+}
+
 impl From<DDNSConfig> for ddns::Config {
 	fn from(c: DDNSConfig) -> Self {
 		Self {
@@ -156,10 +1012,36 @@ impl From<ddns::Config> for DDNSConfig {
 	}
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DDNSTestReport {
+	pub success: bool,
+	pub status: Option<u16>,
+	pub response: String,
+}
+
+impl From<ddns::TestReport> for DDNSTestReport {
+	fn from(r: ddns::TestReport) -> Self {
+		Self {
+			success: r.success,
+			status: r.status,
+			response: r.response,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
 pub struct MountDir {
+	/// Left empty (and omitted) when listing mounts for non-admin users
+	#[serde(default, skip_serializing_if = "String::is_empty")]
 	pub source: String,
 	pub name: String,
+	#[serde(default)]
+	pub album_art_pattern: Option<String>,
+	#[serde(default)]
+	pub reindex_every_n_seconds: Option<i32>,
+	/// Leaves this mount out of the root listing of the collection.
+	#[serde(default)]
+	pub hide_from_root: bool,
 }
 
 impl From<MountDir> for vfs::MountDir {
@@ -167,6 +1049,9 @@ impl From<MountDir> for vfs::MountDir {
 		Self {
 			name: m.name,
 			source: m.source,
+			album_art_pattern: m.album_art_pattern,
+			reindex_every_n_seconds: m.reindex_every_n_seconds,
+			hide_from_root: m.hide_from_root,
 		}
 	}
 }
@@ -176,6 +1061,9 @@ impl From<vfs::MountDir> for MountDir {
 		Self {
 			name: m.name,
 			source: m.source,
+			album_art_pattern: m.album_art_pattern,
+			reindex_every_n_seconds: m.reindex_every_n_seconds,
+			hide_from_root: m.hide_from_root,
 		}
 	}
 }
@@ -201,10 +1089,129 @@ impl From<Config> for config::Config {
 	}
 }
 
+impl From<config::Config> for Config {
+	fn from(c: config::Config) -> Self {
+		Self {
+			settings: c.settings.map(|s| s.into()),
+			users: c.users.map(|v| v.into_iter().map(|u| u.into()).collect()),
+			mount_dirs: c
+				.mount_dirs
+				.map(|v| v.into_iter().map(|m| m.into()).collect()),
+			ydns: c.ydns.map(|c| c.into()),
+		}
+	}
+}
+
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ConfigProblem {
+	pub field: String,
+	pub message: String,
+}
+
+impl From<config::Problem> for ConfigProblem {
+	fn from(p: config::Problem) -> Self {
+		Self {
+			field: p.field,
+			message: p.message,
+		}
+	}
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct NewSettings {
 	pub album_art_pattern: Option<String>,
 	pub reindex_every_n_seconds: Option<i32>,
+	pub auto_rename_duplicate_mounts: Option<bool>,
+	pub normalize_genres: Option<bool>,
+	pub genre_aliases: Option<HashMap<String, String>>,
+	pub fallback_artist: Option<String>,
+	pub fallback_album: Option<String>,
+	pub track_bandwidth: Option<bool>,
+	pub bandwidth_rollover: Option<BandwidthRollover>,
+	pub scan_on_startup: Option<bool>,
+	pub album_artist_grouping: Option<AlbumArtistGrouping>,
+	pub snapshot_index_updates: Option<bool>,
+	pub expose_extra_tags: Option<bool>,
+	pub index_max_depth: Option<i32>,
+	pub public_base_url: Option<String>,
+	pub min_file_size_bytes: Option<i64>,
+	pub artwork_placeholder_path: Option<String>,
+	pub skip_hidden_files: Option<bool>,
+	pub artist_image_pattern: Option<String>,
+	pub merge_duplicate_albums: Option<bool>,
+	pub preferred_album_format: Option<String>,
+	pub tag_priority: Option<Vec<TagContainer>>,
+	pub estimate_missing_durations: Option<bool>,
+	pub album_artist_fallback: Option<bool>,
+	pub default_page_size: Option<i32>,
+	pub max_page_size: Option<i32>,
+	pub artwork_priority: Option<ArtworkPriority>,
+	pub normalize_artist_names: Option<bool>,
+	pub artist_name_articles: Option<Vec<String>>,
+	pub directory_thumbnail: Option<DirectoryThumbnail>,
+	pub scan_failure_limit: Option<i32>,
+	pub analyze_loudness: Option<bool>,
+	pub symlinked_duplicates: Option<SymlinkedDuplicates>,
+	pub max_cover_bytes: Option<i32>,
+	pub audiobook_directories: Option<Vec<String>>,
+	pub podcast_directories: Option<Vec<String>>,
+	pub hide_empty_directories: Option<bool>,
+	pub prewarm_thumbnails_on_scan: Option<bool>,
+	pub metadata_readers: Option<HashMap<String, MetadataReader>>,
+	pub mount_order: Option<MountOrder>,
+	pub reindex_on_config_change: Option<bool>,
+}
+
+impl From<settings::NewSettings> for NewSettings {
+	fn from(s: settings::NewSettings) -> Self {
+		Self {
+			album_art_pattern: s.album_art_pattern,
+			reindex_every_n_seconds: s.reindex_every_n_seconds,
+			auto_rename_duplicate_mounts: s.auto_rename_duplicate_mounts,
+			normalize_genres: s.normalize_genres,
+			genre_aliases: s.genre_aliases,
+			fallback_artist: s.fallback_artist,
+			fallback_album: s.fallback_album,
+			track_bandwidth: s.track_bandwidth,
+			bandwidth_rollover: s.bandwidth_rollover.map(|r| r.into()),
+			scan_on_startup: s.scan_on_startup,
+			album_artist_grouping: s.album_artist_grouping.map(|g| g.into()),
+			snapshot_index_updates: s.snapshot_index_updates,
+			expose_extra_tags: s.expose_extra_tags,
+			index_max_depth: s.index_max_depth,
+			public_base_url: s.public_base_url,
+			min_file_size_bytes: s.min_file_size_bytes,
+			artwork_placeholder_path: s.artwork_placeholder_path,
+			skip_hidden_files: s.skip_hidden_files,
+			artist_image_pattern: s.artist_image_pattern,
+			merge_duplicate_albums: s.merge_duplicate_albums,
+			preferred_album_format: s.preferred_album_format,
+			tag_priority: s
+				.tag_priority
+				.map(|p| p.into_iter().map(|c| c.into()).collect()),
+			estimate_missing_durations: s.estimate_missing_durations,
+			album_artist_fallback: s.album_artist_fallback,
+			default_page_size: s.default_page_size,
+			max_page_size: s.max_page_size,
+			artwork_priority: s.artwork_priority.map(|p| p.into()),
+			normalize_artist_names: s.normalize_artist_names,
+			artist_name_articles: s.artist_name_articles,
+			directory_thumbnail: s.directory_thumbnail.map(|d| d.into()),
+			scan_failure_limit: s.scan_failure_limit,
+			analyze_loudness: s.analyze_loudness,
+			symlinked_duplicates: s.symlinked_duplicates.map(|d| d.into()),
+			max_cover_bytes: s.max_cover_bytes,
+			audiobook_directories: s.audiobook_directories,
+			podcast_directories: s.podcast_directories,
+			hide_empty_directories: s.hide_empty_directories,
+			prewarm_thumbnails_on_scan: s.prewarm_thumbnails_on_scan,
+			metadata_readers: s
+				.metadata_readers
+				.map(|r| r.into_iter().map(|(e, r)| (e, r.into())).collect()),
+			mount_order: s.mount_order.map(|o| o.into()),
+			reindex_on_config_change: s.reindex_on_config_change,
+		}
+	}
 }
 
 impl From<NewSettings> for settings::NewSettings {
@@ -212,6 +1219,350 @@ impl From<NewSettings> for settings::NewSettings {
 		Self {
 			album_art_pattern: s.album_art_pattern,
 			reindex_every_n_seconds: s.reindex_every_n_seconds,
+			auto_rename_duplicate_mounts: s.auto_rename_duplicate_mounts,
+			normalize_genres: s.normalize_genres,
+			genre_aliases: s.genre_aliases,
+			fallback_artist: s.fallback_artist,
+			fallback_album: s.fallback_album,
+			track_bandwidth: s.track_bandwidth,
+			bandwidth_rollover: s.bandwidth_rollover.map(|r| r.into()),
+			scan_on_startup: s.scan_on_startup,
+			album_artist_grouping: s.album_artist_grouping.map(|g| g.into()),
+			snapshot_index_updates: s.snapshot_index_updates,
+			expose_extra_tags: s.expose_extra_tags,
+			index_max_depth: s.index_max_depth,
+			public_base_url: s.public_base_url,
+			min_file_size_bytes: s.min_file_size_bytes,
+			artwork_placeholder_path: s.artwork_placeholder_path,
+			skip_hidden_files: s.skip_hidden_files,
+			artist_image_pattern: s.artist_image_pattern,
+			merge_duplicate_albums: s.merge_duplicate_albums,
+			preferred_album_format: s.preferred_album_format,
+			tag_priority: s
+				.tag_priority
+				.map(|p| p.into_iter().map(|c| c.into()).collect()),
+			estimate_missing_durations: s.estimate_missing_durations,
+			album_artist_fallback: s.album_artist_fallback,
+			default_page_size: s.default_page_size,
+			max_page_size: s.max_page_size,
+			artwork_priority: s.artwork_priority.map(|p| p.into()),
+			normalize_artist_names: s.normalize_artist_names,
+			artist_name_articles: s.artist_name_articles,
+			directory_thumbnail: s.directory_thumbnail.map(|d| d.into()),
+			scan_failure_limit: s.scan_failure_limit,
+			analyze_loudness: s.analyze_loudness,
+			symlinked_duplicates: s.symlinked_duplicates.map(|d| d.into()),
+			max_cover_bytes: s.max_cover_bytes,
+			audiobook_directories: s.audiobook_directories,
+			podcast_directories: s.podcast_directories,
+			hide_empty_directories: s.hide_empty_directories,
+			prewarm_thumbnails_on_scan: s.prewarm_thumbnails_on_scan,
+			metadata_readers: s
+				.metadata_readers
+				.map(|r| r.into_iter().map(|(e, r)| (e, r.into())).collect()),
+			mount_order: s.mount_order.map(|o| o.into()),
+			reindex_on_config_change: s.reindex_on_config_change,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BandwidthUsage {
+	pub username: String,
+	pub bytes: u64,
+	pub period_start: i64,
+}
+
+impl From<bandwidth::Usage> for BandwidthUsage {
+	fn from(u: bandwidth::Usage) -> Self {
+		Self {
+			username: u.username,
+			bytes: u.bytes,
+			period_start: u.period_start,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewShare {
+	pub path: String,
+	/// Seconds since the UNIX epoch.
+	pub expires_at: i64,
+	#[serde(default)]
+	pub max_downloads: Option<u32>,
+}
+
+impl From<NewShare> for share::NewShare {
+	fn from(s: NewShare) -> Self {
+		Self {
+			path: s.path,
+			expires_at: s.expires_at,
+			max_downloads: s.max_downloads,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Share {
+	pub token: String,
+	pub path: String,
+	pub created_at: i64,
+	pub expires_at: i64,
+	pub max_downloads: Option<u32>,
+	pub download_count: u32,
+}
+
+impl From<share::Share> for Share {
+	fn from(s: share::Share) -> Self {
+		Self {
+			token: s.token,
+			path: s.path,
+			created_at: s.created_at,
+			expires_at: s.expires_at,
+			max_downloads: s.max_downloads,
+			download_count: s.download_count,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BandwidthRollover {
+	Never,
+	Daily,
+	#[default]
+	Monthly,
+}
+
+impl From<settings::BandwidthRollover> for BandwidthRollover {
+	fn from(r: settings::BandwidthRollover) -> Self {
+		match r {
+			settings::BandwidthRollover::Never => Self::Never,
+			settings::BandwidthRollover::Daily => Self::Daily,
+			settings::BandwidthRollover::Monthly => Self::Monthly,
+		}
+	}
+}
+
+impl From<BandwidthRollover> for settings::BandwidthRollover {
+	fn from(r: BandwidthRollover) -> Self {
+		match r {
+			BandwidthRollover::Never => Self::Never,
+			BandwidthRollover::Daily => Self::Daily,
+			BandwidthRollover::Monthly => Self::Monthly,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlbumArtistGrouping {
+	#[default]
+	Each,
+	Joined,
+}
+
+impl From<settings::AlbumArtistGrouping> for AlbumArtistGrouping {
+	fn from(g: settings::AlbumArtistGrouping) -> Self {
+		match g {
+			settings::AlbumArtistGrouping::Each => Self::Each,
+			settings::AlbumArtistGrouping::Joined => Self::Joined,
+		}
+	}
+}
+
+impl From<AlbumArtistGrouping> for settings::AlbumArtistGrouping {
+	fn from(g: AlbumArtistGrouping) -> Self {
+		match g {
+			AlbumArtistGrouping::Each => Self::Each,
+			AlbumArtistGrouping::Joined => Self::Joined,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtworkPriority {
+	#[default]
+	Embedded,
+	Folder,
+	Smaller,
+}
+
+impl From<settings::ArtworkPriority> for ArtworkPriority {
+	fn from(p: settings::ArtworkPriority) -> Self {
+		match p {
+			settings::ArtworkPriority::Embedded => Self::Embedded,
+			settings::ArtworkPriority::Folder => Self::Folder,
+			settings::ArtworkPriority::Smaller => Self::Smaller,
+		}
+	}
+}
+
+impl From<ArtworkPriority> for settings::ArtworkPriority {
+	fn from(p: ArtworkPriority) -> Self {
+		match p {
+			ArtworkPriority::Embedded => Self::Embedded,
+			ArtworkPriority::Folder => Self::Folder,
+			ArtworkPriority::Smaller => Self::Smaller,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirectoryThumbnail {
+	#[default]
+	Disabled,
+	FirstCover,
+	Collage,
+}
+
+impl From<settings::DirectoryThumbnail> for DirectoryThumbnail {
+	fn from(d: settings::DirectoryThumbnail) -> Self {
+		match d {
+			settings::DirectoryThumbnail::Disabled => Self::Disabled,
+			settings::DirectoryThumbnail::FirstCover => Self::FirstCover,
+			settings::DirectoryThumbnail::Collage => Self::Collage,
+		}
+	}
+}
+
+impl From<DirectoryThumbnail> for settings::DirectoryThumbnail {
+	fn from(d: DirectoryThumbnail) -> Self {
+		match d {
+			DirectoryThumbnail::Disabled => Self::Disabled,
+			DirectoryThumbnail::FirstCover => Self::FirstCover,
+			DirectoryThumbnail::Collage => Self::Collage,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkedDuplicates {
+	#[default]
+	Alias,
+	Skip,
+}
+
+impl From<settings::SymlinkedDuplicates> for SymlinkedDuplicates {
+	fn from(d: settings::SymlinkedDuplicates) -> Self {
+		match d {
+			settings::SymlinkedDuplicates::Alias => Self::Alias,
+			settings::SymlinkedDuplicates::Skip => Self::Skip,
+		}
+	}
+}
+
+impl From<SymlinkedDuplicates> for settings::SymlinkedDuplicates {
+	fn from(d: SymlinkedDuplicates) -> Self {
+		match d {
+			SymlinkedDuplicates::Alias => Self::Alias,
+			SymlinkedDuplicates::Skip => Self::Skip,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataReader {
+	Aiff,
+	Ape,
+	Flac,
+	Id3,
+	Mp3,
+	Mp4,
+	Opus,
+	Vorbis,
+	Wave,
+	Wma,
+	Skip,
+}
+
+impl From<settings::MetadataReader> for MetadataReader {
+	fn from(r: settings::MetadataReader) -> Self {
+		match r {
+			settings::MetadataReader::Aiff => Self::Aiff,
+			settings::MetadataReader::Ape => Self::Ape,
+			settings::MetadataReader::Flac => Self::Flac,
+			settings::MetadataReader::Id3 => Self::Id3,
+			settings::MetadataReader::Mp3 => Self::Mp3,
+			settings::MetadataReader::Mp4 => Self::Mp4,
+			settings::MetadataReader::Opus => Self::Opus,
+			settings::MetadataReader::Vorbis => Self::Vorbis,
+			settings::MetadataReader::Wave => Self::Wave,
+			settings::MetadataReader::Wma => Self::Wma,
+			settings::MetadataReader::Skip => Self::Skip,
+		}
+	}
+}
+
+impl From<MetadataReader> for settings::MetadataReader {
+	fn from(r: MetadataReader) -> Self {
+		match r {
+			MetadataReader::Aiff => Self::Aiff,
+			MetadataReader::Ape => Self::Ape,
+			MetadataReader::Flac => Self::Flac,
+			MetadataReader::Id3 => Self::Id3,
+			MetadataReader::Mp3 => Self::Mp3,
+			MetadataReader::Mp4 => Self::Mp4,
+			MetadataReader::Opus => Self::Opus,
+			MetadataReader::Vorbis => Self::Vorbis,
+			MetadataReader::Wave => Self::Wave,
+			MetadataReader::Wma => Self::Wma,
+			MetadataReader::Skip => Self::Skip,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MountOrder {
+	#[default]
+	Alphabetical,
+	Config,
+}
+
+impl From<settings::MountOrder> for MountOrder {
+	fn from(o: settings::MountOrder) -> Self {
+		match o {
+			settings::MountOrder::Alphabetical => Self::Alphabetical,
+			settings::MountOrder::Config => Self::Config,
+		}
+	}
+}
+
+impl From<MountOrder> for settings::MountOrder {
+	fn from(o: MountOrder) -> Self {
+		match o {
+			MountOrder::Alphabetical => Self::Alphabetical,
+			MountOrder::Config => Self::Config,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagContainer {
+	Id3,
+	Ape,
+}
+
+impl From<settings::TagContainer> for TagContainer {
+	fn from(c: settings::TagContainer) -> Self {
+		match c {
+			settings::TagContainer::Id3 => Self::Id3,
+			settings::TagContainer::Ape => Self::Ape,
+		}
+	}
+}
+
+impl From<TagContainer> for settings::TagContainer {
+	fn from(c: TagContainer) -> Self {
+		match c {
+			TagContainer::Id3 => Self::Id3,
+			TagContainer::Ape => Self::Ape,
 		}
 	}
 }
@@ -220,6 +1571,45 @@ impl From<NewSettings> for settings::NewSettings {
 pub struct Settings {
 	pub album_art_pattern: String,
 	pub reindex_every_n_seconds: i32,
+	pub auto_rename_duplicate_mounts: bool,
+	pub normalize_genres: bool,
+	pub genre_aliases: HashMap<String, String>,
+	pub fallback_artist: String,
+	pub fallback_album: String,
+	pub track_bandwidth: bool,
+	pub bandwidth_rollover: BandwidthRollover,
+	pub scan_on_startup: bool,
+	pub album_artist_grouping: AlbumArtistGrouping,
+	pub snapshot_index_updates: bool,
+	pub expose_extra_tags: bool,
+	pub index_max_depth: i32,
+	pub public_base_url: String,
+	pub min_file_size_bytes: i64,
+	pub artwork_placeholder_path: String,
+	pub skip_hidden_files: bool,
+	pub artist_image_pattern: String,
+	pub merge_duplicate_albums: bool,
+	pub preferred_album_format: String,
+	pub tag_priority: Vec<TagContainer>,
+	pub estimate_missing_durations: bool,
+	pub album_artist_fallback: bool,
+	pub default_page_size: i32,
+	pub max_page_size: i32,
+	pub artwork_priority: ArtworkPriority,
+	pub normalize_artist_names: bool,
+	pub artist_name_articles: Vec<String>,
+	pub directory_thumbnail: DirectoryThumbnail,
+	pub scan_failure_limit: i32,
+	pub analyze_loudness: bool,
+	pub symlinked_duplicates: SymlinkedDuplicates,
+	pub max_cover_bytes: i32,
+	pub audiobook_directories: Vec<String>,
+	pub podcast_directories: Vec<String>,
+	pub hide_empty_directories: bool,
+	pub prewarm_thumbnails_on_scan: bool,
+	pub metadata_readers: HashMap<String, MetadataReader>,
+	pub mount_order: MountOrder,
+	pub reindex_on_config_change: bool,
 }
 
 impl From<settings::Settings> for Settings {
@@ -227,9 +1617,52 @@ impl From<settings::Settings> for Settings {
 		Self {
 			album_art_pattern: s.index_album_art_pattern,
 			reindex_every_n_seconds: s.index_sleep_duration_seconds,
+			auto_rename_duplicate_mounts: s.auto_rename_duplicate_mounts,
+			normalize_genres: s.normalize_genres,
+			genre_aliases: s.genre_aliases,
+			fallback_artist: s.fallback_artist,
+			fallback_album: s.fallback_album,
+			track_bandwidth: s.track_bandwidth,
+			bandwidth_rollover: s.bandwidth_rollover.into(),
+			scan_on_startup: s.scan_on_startup,
+			album_artist_grouping: s.album_artist_grouping.into(),
+			snapshot_index_updates: s.snapshot_index_updates,
+			expose_extra_tags: s.expose_extra_tags,
+			index_max_depth: s.index_max_depth,
+			public_base_url: s.public_base_url,
+			min_file_size_bytes: s.min_file_size_bytes,
+			artwork_placeholder_path: s.artwork_placeholder_path,
+			skip_hidden_files: s.skip_hidden_files,
+			artist_image_pattern: s.artist_image_pattern,
+			merge_duplicate_albums: s.merge_duplicate_albums,
+			preferred_album_format: s.preferred_album_format,
+			tag_priority: s.tag_priority.into_iter().map(|c| c.into()).collect(),
+			estimate_missing_durations: s.estimate_missing_durations,
+			album_artist_fallback: s.album_artist_fallback,
+			default_page_size: s.default_page_size,
+			max_page_size: s.max_page_size,
+			artwork_priority: s.artwork_priority.into(),
+			normalize_artist_names: s.normalize_artist_names,
+			artist_name_articles: s.artist_name_articles,
+			directory_thumbnail: s.directory_thumbnail.into(),
+			scan_failure_limit: s.scan_failure_limit,
+			analyze_loudness: s.analyze_loudness,
+			symlinked_duplicates: s.symlinked_duplicates.into(),
+			max_cover_bytes: s.max_cover_bytes,
+			audiobook_directories: s.audiobook_directories,
+			podcast_directories: s.podcast_directories,
+			hide_empty_directories: s.hide_empty_directories,
+			prewarm_thumbnails_on_scan: s.prewarm_thumbnails_on_scan,
+			metadata_readers: s
+				.metadata_readers
+				.into_iter()
+				.map(|(e, r)| (e, r.into()))
+				.collect(),
+			mount_order: s.mount_order.into(),
+			reindex_on_config_change: s.reindex_on_config_change,
 		}
 	}
 }
 
-// TODO: Preferences, CollectionFile, Song and Directory should have dto types
+// TODO: Preferences and Directory should have dto types
 // TODO Song dto type should skip `None` values when serializing, to lower payload sizes by a lot
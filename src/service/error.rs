@@ -1,8 +1,11 @@
 use std::path::PathBuf;
 use thiserror::Error;
 
-use crate::app::index::QueryError;
-use crate::app::{config, ddns, lastfm, playlist, settings, thumbnail, user, vfs};
+use crate::app::index::{self, QueryError};
+use crate::app::{
+	archive, bandwidth, config, cover, ddns, lastfm, lyrics, play_count, playlist, progress,
+	settings, share, thumbnail, user, vfs,
+};
 use crate::db;
 
 #[derive(Error, Debug)]
@@ -11,12 +14,26 @@ pub enum APIError {
 	AuthorizationTokenEncoding,
 	#[error("Administrator permission is required")]
 	AdminPermissionRequired,
+	#[error("Path is not a valid location within the collection")]
+	ArchiveInvalidPath,
+	#[error("Archive would exceed the size limits of the zip format")]
+	ArchiveTooLarge,
+	#[error("Artist image not found")]
+	ArtistImageNotFound,
 	#[error("Audio file could not be opened")]
 	AudioFileIOError,
 	#[error("Authentication is required")]
 	AuthenticationRequired,
 	#[error("Could not encode Branca token")]
 	BrancaTokenEncoding,
+	#[error("Could not decode cover content as base64")]
+	CoverContentBase64DecodeError,
+	#[error("Could not decode cover image:\n\n{0}")]
+	CoverImageDecoding(image::error::ImageError),
+	#[error("No cover override was found")]
+	CoverNotFound,
+	#[error("Cover image is too large ({0} bytes, at most {1} are allowed)")]
+	CoverTooLarge(usize, usize),
 	#[error("Database error:\n\n{0}")]
 	Database(diesel::result::Error),
 	#[error("DDNS update query failed with HTTP status {0}")]
@@ -45,18 +62,50 @@ pub enum APIError {
 	LastFMScrobblerAuthentication(rustfm_scrobble::ScrobblerError),
 	#[error("Internal server error")]
 	Internal,
+	#[error("Invalid configuration: {0:?}")]
+	InvalidConfig(Vec<config::Problem>),
+	#[error("Invalid decade: `{0}`")]
+	InvalidDecade(String),
+	#[error("Could not read lyrics from ID3 tag in `{0}`:\n\n{1}")]
+	LyricsId3Decoding(PathBuf, id3::Error),
+	#[error("No lyrics were found")]
+	LyricsNotFound,
 	#[error("File I/O error for `{0}`:\n\n{1}")]
 	Io(PathBuf, std::io::Error),
 	#[error("Cannot remove your own admin privilege")]
 	OwnAdminPrivilegeRemoval,
 	#[error("Could not hash password")]
 	PasswordHashing,
+	#[error("Request took too long to complete")]
+	RequestTimeout,
 	#[error("Playlist not found")]
 	PlaylistNotFound,
+	#[error("No playback position was saved for this song")]
+	PlaybackPositionNotFound,
+	#[error("Playlist contains tracks which are not in the collection")]
+	PlaylistInvalidTracks(Vec<String>),
+	#[error("Playlist has no track at index {0}")]
+	PlaylistTrackIndexOutOfRange(usize),
+	#[error("Playlist save session not found")]
+	PlaylistSaveSessionNotFound,
+	#[error("A playlist named `{0}` already exists")]
+	PlaylistNameConflict(String),
 	#[error("Settings error:\n\n{0}")]
 	Settings(settings::Error),
+	#[error("Share link expiry must be in the future")]
+	ShareInvalidExpiry,
+	#[error("Share link has expired")]
+	ShareExpired,
+	#[error("Share link has reached its download limit")]
+	ShareDownloadLimitReached,
+	#[error("Share link not found")]
+	ShareNotFound,
+	#[error("Path is outside of the shared location")]
+	SharePathOutsideScope,
 	#[error("Song not found")]
 	SongMetadataNotFound,
+	#[error("Artwork in `{0}` is too large to decode ({1} bytes)")]
+	ThumbnailArtworkTooLarge(PathBuf, usize),
 	#[error("Could not decode thumbnail from flac file `{0}`:\n\n{1}")]
 	ThumbnailFlacDecoding(PathBuf, metaflac::Error),
 	#[error("Thumbnail file could not be opened")]
@@ -67,8 +116,12 @@ pub enum APIError {
 	ThumbnailImageDecoding(PathBuf, image::error::ImageError),
 	#[error("Could not decode thumbnail from mp4 file `{0}`:\n\n{1}")]
 	ThumbnailMp4Decoding(PathBuf, mp4ameta::Error),
+	#[error("Thumbnails are already being prewarmed")]
+	ThumbnailPrewarmInProgress,
 	#[error("Toml deserialization error:\n\n{0}")]
 	TomlDeserialization(toml::de::Error),
+	#[error("Cover images must be JPEG, PNG, GIF or BMP files")]
+	UnsupportedCoverFormat,
 	#[error("Unsupported thumbnail format: `{0}`")]
 	UnsupportedThumbnailFormat(&'static str),
 	#[error("User not found")]
@@ -81,6 +134,8 @@ impl From<config::Error> for APIError {
 	fn from(error: config::Error) -> APIError {
 		match error {
 			config::Error::Ddns(e) => e.into(),
+			config::Error::Index(e) => e.into(),
+			config::Error::Invalid(problems) => APIError::InvalidConfig(problems),
 			config::Error::Io(p, e) => APIError::Io(p, e),
 			config::Error::Settings(e) => e.into(),
 			config::Error::Toml(e) => APIError::TomlDeserialization(e),
@@ -90,24 +145,136 @@ impl From<config::Error> for APIError {
 	}
 }
 
+impl From<archive::Error> for APIError {
+	fn from(error: archive::Error) -> APIError {
+		match error {
+			archive::Error::InvalidPath(_) => APIError::ArchiveInvalidPath,
+			archive::Error::Io(p, e) => APIError::Io(p, e),
+			archive::Error::NoSongs(_) => APIError::VFSPathNotFound,
+			archive::Error::Query(e) => e.into(),
+			archive::Error::TooLarge(_) => APIError::ArchiveTooLarge,
+			archive::Error::Vfs(e) => e.into(),
+		}
+	}
+}
+
+impl From<lyrics::Error> for APIError {
+	fn from(error: lyrics::Error) -> APIError {
+		match error {
+			lyrics::Error::Id3(p, e) => APIError::LyricsId3Decoding(p, e),
+			lyrics::Error::Io(p, e) => APIError::Io(p, e),
+			lyrics::Error::LyricsNotFound(_) => APIError::LyricsNotFound,
+		}
+	}
+}
+
+impl From<bandwidth::Error> for APIError {
+	fn from(error: bandwidth::Error) -> APIError {
+		match error {
+			bandwidth::Error::Database(e) => APIError::Database(e),
+			bandwidth::Error::DatabaseConnection(e) => e.into(),
+			bandwidth::Error::Settings(e) => e.into(),
+			bandwidth::Error::UserNotFound => APIError::UserNotFound,
+		}
+	}
+}
+
+impl From<play_count::Error> for APIError {
+	fn from(error: play_count::Error) -> APIError {
+		match error {
+			play_count::Error::Database(e) => APIError::Database(e),
+			play_count::Error::DatabaseConnection(e) => e.into(),
+			play_count::Error::SongNotFound(_) => APIError::SongMetadataNotFound,
+			play_count::Error::UserNotFound => APIError::UserNotFound,
+			play_count::Error::Vfs(e) => e.into(),
+		}
+	}
+}
+
+impl From<progress::Error> for APIError {
+	fn from(error: progress::Error) -> APIError {
+		match error {
+			progress::Error::Database(e) => APIError::Database(e),
+			progress::Error::DatabaseConnection(e) => e.into(),
+			progress::Error::SongNotFound(_) => APIError::SongMetadataNotFound,
+			progress::Error::PositionNotFound(_) => APIError::PlaybackPositionNotFound,
+			progress::Error::UserNotFound => APIError::UserNotFound,
+			progress::Error::Vfs(e) => e.into(),
+		}
+	}
+}
+
 impl From<playlist::Error> for APIError {
 	fn from(error: playlist::Error) -> APIError {
 		match error {
 			playlist::Error::Database(e) => APIError::Database(e),
 			playlist::Error::DatabaseConnection(e) => e.into(),
 			playlist::Error::PlaylistNotFound => APIError::PlaylistNotFound,
+			playlist::Error::InvalidTracks(t) => APIError::PlaylistInvalidTracks(t),
+			playlist::Error::TrackIndexOutOfRange(i) => APIError::PlaylistTrackIndexOutOfRange(i),
+			playlist::Error::SaveSessionNotFound => APIError::PlaylistSaveSessionNotFound,
+			playlist::Error::PlaylistNameConflict(n) => APIError::PlaylistNameConflict(n),
 			playlist::Error::UserNotFound => APIError::UserNotFound,
 			playlist::Error::Vfs(e) => e.into(),
 		}
 	}
 }
 
+impl From<cover::Error> for APIError {
+	fn from(error: cover::Error) -> APIError {
+		match error {
+			cover::Error::CoverNotFound(_) => APIError::CoverNotFound,
+			cover::Error::CoverTooLarge(s, m) => APIError::CoverTooLarge(s, m),
+			cover::Error::Database(e) => APIError::Database(e),
+			cover::Error::DatabaseConnection(e) => e.into(),
+			cover::Error::Image(e) => APIError::CoverImageDecoding(e),
+			cover::Error::Io(p, e) => APIError::Io(p, e),
+			cover::Error::PathNotFound(_) => APIError::VFSPathNotFound,
+			cover::Error::Settings(e) => e.into(),
+			cover::Error::UnsupportedFormat => APIError::UnsupportedCoverFormat,
+			cover::Error::Vfs(e) => e.into(),
+		}
+	}
+}
+
+impl From<share::Error> for APIError {
+	fn from(error: share::Error) -> APIError {
+		match error {
+			share::Error::Database(e) => APIError::Database(e),
+			share::Error::DatabaseConnection(e) => e.into(),
+			share::Error::InvalidExpiry => APIError::ShareInvalidExpiry,
+			share::Error::PathNotFound(_) => APIError::VFSPathNotFound,
+			share::Error::PathOutsideShare(_) => APIError::SharePathOutsideScope,
+			share::Error::ShareExpired => APIError::ShareExpired,
+			share::Error::ShareDownloadLimitReached => APIError::ShareDownloadLimitReached,
+			share::Error::ShareNotFound => APIError::ShareNotFound,
+			share::Error::Vfs(e) => e.into(),
+		}
+	}
+}
+
+impl From<index::Error> for APIError {
+	fn from(error: index::Error) -> APIError {
+		match error {
+			index::Error::IndexClean(_) => APIError::Internal,
+			index::Error::Database(e) => APIError::Database(e),
+			index::Error::DatabaseConnection(e) => e.into(),
+			index::Error::Vfs(e) => e.into(),
+			index::Error::Settings(e) => e.into(),
+			index::Error::SongNotFound(_) => APIError::SongMetadataNotFound,
+		}
+	}
+}
+
 impl From<QueryError> for APIError {
 	fn from(error: QueryError) -> APIError {
 		match error {
 			QueryError::Database(e) => APIError::Database(e),
 			QueryError::DatabaseConnection(e) => e.into(),
+			QueryError::PathNotFound(_) => APIError::VFSPathNotFound,
 			QueryError::SongNotFound(_) => APIError::SongMetadataNotFound,
+			QueryError::ArtistImageNotFound(_) => APIError::ArtistImageNotFound,
+			QueryError::Settings(e) => e.into(),
 			QueryError::Vfs(e) => e.into(),
 		}
 	}
@@ -121,6 +288,19 @@ impl From<settings::Error> for APIError {
 			settings::Error::AuthenticationSecretInvalid => APIError::Settings(error),
 			settings::Error::MiscSettingsNotFound => APIError::Settings(error),
 			settings::Error::IndexAlbumArtPatternInvalid => APIError::Settings(error),
+			settings::Error::ArtistImagePatternInvalid => APIError::Settings(error),
+			settings::Error::GenreAliasesInvalid(_) => APIError::Settings(error),
+			settings::Error::BandwidthRolloverInvalid(_) => APIError::Settings(error),
+			settings::Error::AlbumArtistGroupingInvalid(_) => APIError::Settings(error),
+			settings::Error::ArtworkPriorityInvalid(_) => APIError::Settings(error),
+			settings::Error::DirectoryThumbnailInvalid(_) => APIError::Settings(error),
+			settings::Error::SymlinkedDuplicatesInvalid(_) => APIError::Settings(error),
+			settings::Error::MetadataReaderInvalid(_) => APIError::Settings(error),
+			settings::Error::MountOrderInvalid(_) => APIError::Settings(error),
+			settings::Error::TagContainerInvalid(_) => APIError::Settings(error),
+			settings::Error::TagPriorityEmpty => APIError::Settings(error),
+			settings::Error::PublicBaseUrlInvalid(_) => APIError::Settings(error),
+			settings::Error::PageSizeInvalid(_) => APIError::Settings(error),
 			settings::Error::Database(e) => APIError::Database(e),
 		}
 	}
@@ -139,6 +319,8 @@ impl From<user::Error> for APIError {
 			user::Error::IncorrectPassword => APIError::IncorrectCredentials,
 			user::Error::IncorrectUsername => APIError::IncorrectCredentials,
 			user::Error::InvalidAuthToken => APIError::IncorrectCredentials,
+			user::Error::InvalidBootstrapToken => APIError::IncorrectCredentials,
+			user::Error::ExpiredAuthToken => APIError::IncorrectCredentials,
 			user::Error::MissingLastFMSessionKey => APIError::IncorrectCredentials,
 			user::Error::PasswordHashing => APIError::PasswordHashing,
 		}
@@ -172,6 +354,7 @@ impl From<db::Error> for APIError {
 		match error {
 			db::Error::ConnectionPoolBuild => APIError::Internal,
 			db::Error::ConnectionPool => APIError::Internal,
+			db::Error::InvalidPath(_) => APIError::Internal,
 			db::Error::Io(p, e) => APIError::Io(p, e),
 			db::Error::Migration => APIError::Internal,
 		}
@@ -193,12 +376,15 @@ impl From<lastfm::Error> for APIError {
 impl From<thumbnail::Error> for APIError {
 	fn from(error: thumbnail::Error) -> APIError {
 		match error {
+			thumbnail::Error::ArtworkNotFound(_) => APIError::EmbeddedArtworkNotFound,
+			thumbnail::Error::ArtworkTooLarge(p, s) => APIError::ThumbnailArtworkTooLarge(p, s),
 			thumbnail::Error::EmbeddedArtworkNotFound(_) => APIError::EmbeddedArtworkNotFound,
 			thumbnail::Error::Id3(p, e) => APIError::ThumbnailId3Decoding(p, e),
 			thumbnail::Error::Image(p, e) => APIError::ThumbnailImageDecoding(p, e),
 			thumbnail::Error::Io(p, e) => APIError::Io(p, e),
 			thumbnail::Error::Metaflac(p, e) => APIError::ThumbnailFlacDecoding(p, e),
 			thumbnail::Error::Mp4aMeta(p, e) => APIError::ThumbnailMp4Decoding(p, e),
+			thumbnail::Error::PrewarmInProgress => APIError::ThumbnailPrewarmInProgress,
 			thumbnail::Error::UnsupportedFormat(f) => APIError::UnsupportedThumbnailFormat(f),
 		}
 	}
@@ -15,6 +15,7 @@ mod lastfm;
 mod media;
 mod playlist;
 mod settings;
+mod share;
 mod swagger;
 mod user;
 mod web;
@@ -54,6 +55,9 @@ pub trait TestService {
 			mount_dirs: Some(vec![dto::MountDir {
 				name: TEST_MOUNT_NAME.into(),
 				source: TEST_MOUNT_SOURCE.into(),
+				album_art_pattern: None,
+				reindex_every_n_seconds: None,
+				hide_from_root: false,
 			}]),
 			..Default::default()
 		};
@@ -1,8 +1,9 @@
 use http::StatusCode;
+use std::path::PathBuf;
 
 use crate::app::index;
 use crate::service::dto;
-use crate::service::test::{protocol, ServiceType, TestService};
+use crate::service::test::{constants::*, protocol, ServiceType, TestService};
 use crate::test_name;
 
 #[test]
@@ -13,6 +14,31 @@ fn returns_api_version() {
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[test]
+fn capabilities_reflect_configuration() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+
+	let request = protocol::capabilities();
+	let response = service.fetch_json::<_, dto::Capabilities>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let capabilities = response.into_body();
+	assert!(!capabilities.tls);
+	assert!(!capabilities.bandwidth_tracking);
+
+	service.login_admin();
+	let request = protocol::put_settings(dto::NewSettings {
+		track_bandwidth: Some(true),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::capabilities();
+	let response = service.fetch_json::<_, dto::Capabilities>(&request);
+	assert!(response.into_body().bandwidth_tracking);
+}
+
 #[test]
 fn initial_setup_golden_path() {
 	let mut service = ServiceType::new(&test_name!());
@@ -24,7 +50,9 @@ fn initial_setup_golden_path() {
 		assert_eq!(
 			initial_setup,
 			&dto::InitialSetup {
-				has_any_users: false
+				has_any_users: false,
+				has_any_mounts: false,
+				has_indexed_collection: false,
 			}
 		);
 	}
@@ -36,12 +64,42 @@ fn initial_setup_golden_path() {
 		assert_eq!(
 			initial_setup,
 			&dto::InitialSetup {
-				has_any_users: true
+				has_any_users: true,
+				has_any_mounts: true,
+				has_indexed_collection: false,
+			}
+		);
+	}
+	service.login_admin();
+	service.index();
+	service.logout();
+	{
+		let response = service.fetch_json::<_, dto::InitialSetup>(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+		let initial_setup = response.body();
+		assert_eq!(
+			initial_setup,
+			&dto::InitialSetup {
+				has_any_users: true,
+				has_any_mounts: true,
+				has_indexed_collection: true,
 			}
 		);
 	}
 }
 
+#[test]
+fn bootstrap_rejects_token_when_setup_is_open() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::bootstrap_admin("garbage", TEST_USERNAME_ADMIN, TEST_PASSWORD_ADMIN);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	let request = protocol::initial_setup();
+	let response = service.fetch_json::<_, dto::InitialSetup>(&request);
+	assert!(!response.body().has_any_users);
+}
+
 #[test]
 fn trigger_index_golden_path() {
 	let mut service = ServiceType::new(&test_name!());
@@ -79,3 +137,185 @@ fn trigger_index_requires_admin() {
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
+
+#[test]
+fn index_status_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::index_status();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn index_status_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::index_status();
+	let response = service.fetch_json::<_, dto::IndexStatus>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.body(),
+		&dto::IndexStatus {
+			state: index::UpdateStatus::Idle
+		}
+	);
+}
+
+#[test]
+fn index_errors_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::index_errors();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn index_errors_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::index_errors();
+	let response = service.fetch_json::<_, Vec<dto::ScanError>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+}
+
+#[test]
+fn index_events_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::index_events();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn rebuild_index_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::rebuild_index();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn refresh_index_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::refresh_index(TEST_MOUNT_NAME);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn refresh_index_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::refresh_index(path.to_str().unwrap());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn lint_metadata_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::lint_metadata(TEST_MOUNT_NAME);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn lint_metadata_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::lint_metadata(path.to_str().unwrap());
+	let response = service.fetch_json::<_, dto::MetadataLintReport>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let report = response.body();
+	assert_eq!(report.tags.title, Some("Candlelight".to_owned()));
+	assert!(!report.warnings.contains(&dto::LintWarning::MissingTitle));
+
+	let request = protocol::lint_metadata("test-data/artwork/Folder.png");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn raw_metadata_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::raw_metadata(TEST_MOUNT_NAME);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn raw_metadata_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::raw_metadata(path.to_str().unwrap());
+	let response = service.fetch_json::<_, index::RawTags>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let raw_tags = response.body();
+	assert_eq!(raw_tags.get("TPE1"), Some(&vec!["Khemmis".to_owned()]));
+
+	let request = protocol::raw_metadata("test-data/artwork/Folder.png");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn refresh_index_bad_path_returns_not_found() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::refresh_index("not_my_collection");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn pause_resume_cancel_index_require_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	for request in [
+		protocol::pause_index(),
+		protocol::resume_index(),
+		protocol::cancel_index(),
+	] {
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::FORBIDDEN);
+	}
+}
@@ -110,3 +110,86 @@ fn authentication_via_query_param_golden_path() {
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::OK);
 }
+
+fn get_token(service: &mut ServiceType, username: &str, password: &str) -> String {
+	let request = protocol::login(username, password);
+	let response = service.fetch_json::<_, dto::Authorization>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	response.into_body().token
+}
+
+fn insert_auth_cookie<T>(request: &mut http::Request<T>, token: &str) {
+	let cookie = http::HeaderValue::from_str(&format!("auth_token={}", token)).unwrap();
+	request.headers_mut().insert(http::header::COOKIE, cookie);
+}
+
+fn insert_bearer<T>(request: &mut http::Request<T>, token: &str) {
+	let bearer = headers::Authorization::bearer(token).unwrap();
+	request.headers_mut().typed_insert(bearer);
+}
+
+#[test]
+fn authentication_via_cookie_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	let token = get_token(&mut service, TEST_USERNAME, TEST_PASSWORD);
+
+	let mut request = protocol::random();
+	insert_auth_cookie(&mut request, &token);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn authentication_via_cookie_rejects_bad_token() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+
+	let mut request = protocol::random();
+	insert_auth_cookie(&mut request, "garbage");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn authentication_requires_token() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+
+	let request = protocol::random();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn authentication_prefers_valid_header_over_cookie() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	let admin_token = get_token(&mut service, TEST_USERNAME_ADMIN, TEST_PASSWORD_ADMIN);
+	let user_token = get_token(&mut service, TEST_USERNAME, TEST_PASSWORD);
+
+	let mut request = protocol::get_settings();
+	insert_bearer(&mut request, &admin_token);
+	insert_auth_cookie(&mut request, &user_token);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let mut request = protocol::get_settings();
+	insert_bearer(&mut request, &user_token);
+	insert_auth_cookie(&mut request, &admin_token);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn authentication_falls_back_to_cookie_when_header_is_invalid() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	let token = get_token(&mut service, TEST_USERNAME, TEST_PASSWORD);
+
+	let mut request = protocol::random();
+	insert_bearer(&mut request, "garbage");
+	insert_auth_cookie(&mut request, &token);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+}
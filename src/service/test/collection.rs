@@ -1,8 +1,10 @@
 use http::StatusCode;
 use std::path::{Path, PathBuf};
 
-use crate::app::index;
+use crate::app::{index, user};
+use crate::service::dto;
 use crate::service::test::{add_trailing_slash, constants::*, protocol, ServiceType, TestService};
+use crate::test::prepare_test_directory;
 use crate::test_name;
 
 #[test]
@@ -13,6 +15,29 @@ fn browse_requires_auth() {
 	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
 
+#[test]
+fn mounts_only_show_sources_to_admins() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	let request = protocol::mounts();
+
+	service.login_admin();
+	let response = service.fetch_json::<_, Vec<dto::MountDir>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let mounts = response.body();
+	assert_eq!(mounts.len(), 1);
+	assert_eq!(mounts[0].name, TEST_MOUNT_NAME);
+	assert!(!mounts[0].source.is_empty());
+
+	service.login();
+	let response = service.fetch_json::<_, Vec<dto::MountDir>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let mounts = response.body();
+	assert_eq!(mounts.len(), 1);
+	assert_eq!(mounts[0].name, TEST_MOUNT_NAME);
+	assert!(mounts[0].source.is_empty());
+}
+
 #[test]
 fn browse_root() {
 	let mut service = ServiceType::new(&test_name!());
@@ -21,128 +46,1142 @@ fn browse_root() {
 	service.index();
 	service.login();
 
-	let request = protocol::browse(&PathBuf::new());
-	let response = service.fetch_json::<_, Vec<index::CollectionFile>>(&request);
+	let request = protocol::browse(&PathBuf::new());
+	let response = service.fetch_json::<_, Vec<index::CollectionFile>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 1);
+}
+
+#[test]
+fn browse_directory() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::browse(&path);
+	let response = service.fetch_json::<_, Vec<index::CollectionFile>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 5);
+}
+
+#[test]
+fn browse_directory_with_media() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::browse_with_media(&path);
+	let response = service.fetch_json::<_, Vec<dto::CollectionFile>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 6);
+	let cover_path = path.join("Folder.jpg").to_string_lossy().into_owned();
+	assert_eq!(
+		entries.last(),
+		Some(&dto::CollectionFile::Media(index::MediaFile {
+			path: cover_path,
+			kind: index::MediaKind::Image,
+		}))
+	);
+}
+
+#[test]
+fn browse_directory_with_special_characters() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let collection_dir = prepare_test_directory(format!("{}-collection", test_name!()));
+	let album_dir = collection_dir.join("Café #1 Hits");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	let source: PathBuf = [
+		TEST_MOUNT_SOURCE,
+		"Khemmis",
+		"Hunted",
+		"02 - Candlelight.mp3",
+	]
+	.iter()
+	.collect();
+	std::fs::copy(&source, album_dir.join("Café #1.mp3")).unwrap();
+	std::fs::copy(&source, album_dir.join("100%25 Pure.mp3")).unwrap();
+
+	let request = protocol::apply_config(dto::Config {
+		mount_dirs: Some(vec![dto::MountDir {
+			source: collection_dir.to_str().unwrap().to_owned(),
+			name: TEST_MOUNT_NAME.to_owned(),
+			album_art_pattern: None,
+			reindex_every_n_seconds: None,
+			hide_from_root: false,
+		}]),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Café #1 Hits"].iter().collect();
+	let request = protocol::browse(&path);
+	let response = service.fetch_json::<_, Vec<index::CollectionFile>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let mut song_paths: Vec<PathBuf> = response
+		.body()
+		.iter()
+		.filter_map(|f| match f {
+			index::CollectionFile::Song(s) => Some(PathBuf::from(&s.path)),
+			index::CollectionFile::Directory(_) => None,
+		})
+		.collect();
+	song_paths.sort();
+	assert_eq!(
+		song_paths,
+		vec![path.join("100%25 Pure.mp3"), path.join("Café #1.mp3")]
+	);
+}
+
+#[test]
+fn browse_bad_directory() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let path: PathBuf = ["not_my_collection"].iter().collect();
+	let request = protocol::browse(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn browse_song() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::browse(&path);
+	let response = service.fetch_json::<_, Vec<index::CollectionFile>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 1);
+	match &entries[0] {
+		index::CollectionFile::Song(s) => assert_eq!(s.path, path.to_string_lossy()),
+		_ => panic!("Expected song"),
+	}
+}
+
+#[test]
+fn browse_missing_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Not An Album"]
+		.iter()
+		.collect();
+	let request = protocol::browse(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn flatten_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::flatten(&PathBuf::new());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn flatten_root() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::flatten(&PathBuf::new());
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 13);
+}
+
+#[test]
+fn flatten_directory() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::flatten(Path::new(TEST_MOUNT_NAME));
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 13);
+}
+
+#[test]
+fn flatten_is_sorted_by_path_and_can_be_paginated() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::flatten(Path::new(TEST_MOUNT_NAME));
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	let paths: Vec<String> = response.body().iter().map(|s| s.path.clone()).collect();
+	let mut sorted_paths = paths.clone();
+	sorted_paths.sort();
+	assert_eq!(paths, sorted_paths);
+
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	let repeated_paths: Vec<String> = response.body().iter().map(|s| s.path.clone()).collect();
+	assert_eq!(paths, repeated_paths);
+
+	let mut paginated_paths = Vec::new();
+	for offset in (0..paths.len() + 5).step_by(5) {
+		let request = protocol::flatten_page(Path::new(TEST_MOUNT_NAME), offset, 5);
+		let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+		paginated_paths.extend(response.body().iter().map(|s| s.path.clone()));
+	}
+	assert_eq!(paginated_paths, paths);
+}
+
+#[test]
+fn flatten_bad_directory() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let path: PathBuf = ["not_my_collection"].iter().collect();
+	let request = protocol::flatten(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn flatten_song() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::flatten(&path);
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 1);
+	assert_eq!(entries[0].path, path.to_string_lossy());
+}
+
+#[test]
+fn flatten_missing_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Not An Album"]
+		.iter()
+		.collect();
+	let request = protocol::flatten(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn flatten_songs_include_disc_label() {
+	use id3::TagLike;
+
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let collection_dir = prepare_test_directory(format!("{}-collection", test_name!()));
+	let album_dir = collection_dir.join("Box Set");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	let source: PathBuf = [
+		TEST_MOUNT_SOURCE,
+		"Khemmis",
+		"Hunted",
+		"02 - Candlelight.mp3",
+	]
+	.iter()
+	.collect();
+	for (file_name, disc, subtitle) in [
+		("1-01.mp3", 1, Some("The Early Years")),
+		("2-01.mp3", 2, None),
+	] {
+		let song_path = album_dir.join(file_name);
+		std::fs::copy(&source, &song_path).unwrap();
+		let mut tag = id3::Tag::read_from_path(&song_path).unwrap();
+		tag.set_disc(disc);
+		tag.remove("TSST");
+		if let Some(subtitle) = subtitle {
+			tag.set_text("TSST", subtitle);
+		}
+		tag.write_to_path(&song_path, id3::Version::Id3v24).unwrap();
+	}
+
+	let request = protocol::apply_config(dto::Config {
+		mount_dirs: Some(vec![dto::MountDir {
+			source: collection_dir.to_str().unwrap().to_owned(),
+			name: TEST_MOUNT_NAME.to_owned(),
+			album_art_pattern: None,
+			reindex_every_n_seconds: None,
+			hide_from_root: false,
+		}]),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	service.index();
+	service.login();
+
+	let request = protocol::flatten(Path::new(TEST_MOUNT_NAME));
+	let response = service.fetch_json::<_, Vec<dto::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let songs = response.body();
+	let song = |file_name: &str| {
+		songs
+			.iter()
+			.find(|s| s.song.path.ends_with(file_name))
+			.unwrap()
+	};
+
+	assert_eq!(
+		song("1-01.mp3").song.disc_subtitle,
+		Some("The Early Years".to_owned())
+	);
+	assert_eq!(
+		song("1-01.mp3").disc_label,
+		Some("Disc 1: The Early Years".to_owned())
+	);
+	assert_eq!(song("2-01.mp3").song.disc_subtitle, None);
+	assert_eq!(song("2-01.mp3").disc_label, Some("Disc 2".to_owned()));
+}
+
+#[test]
+fn flatten_songs_of_same_album_share_group_id() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::flatten(Path::new(TEST_MOUNT_NAME));
+	let response = service.fetch_json::<_, Vec<dto::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let songs = response.body();
+	let group_of = |file_name: &str| {
+		songs
+			.iter()
+			.find(|s| s.song.path.ends_with(file_name))
+			.map(|s| s.album_group_id.clone())
+			.unwrap()
+	};
+
+	assert_eq!(
+		group_of("02 - Candlelight.mp3"),
+		group_of("03 - Three Gates.mp3")
+	);
+	assert_ne!(
+		group_of("02 - Candlelight.mp3"),
+		group_of("07 - なぜ (Why).mp3")
+	);
+}
+
+#[test]
+fn flatten_includes_extra_tags_when_enabled() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::flatten(&PathBuf::new());
+	let response = service.fetch_json::<_, Vec<dto::Song>>(&request);
+	assert!(response.body().iter().all(|s| s.extra.is_none()));
+
+	let request = protocol::put_settings(dto::NewSettings {
+		expose_extra_tags: Some(true),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::flatten(&PathBuf::new());
+	let response = service.fetch_json::<_, Vec<dto::Song>>(&request);
+	assert!(response.body().iter().all(|s| s.extra.is_some()));
+}
+
+#[test]
+fn song_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::song("collection/Khemmis/Hunted/02 - Candlelight.mp3");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn song_siblings_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::song_siblings("collection/Khemmis/Hunted/02 - Candlelight.mp3");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn song_siblings_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let album: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let track_path = |name: &str| album.join(name).to_string_lossy().into_owned();
+
+	let request = protocol::song_siblings(&track_path("03 - Three Gates.mp3"));
+	let response = service.fetch_json::<_, dto::SongSiblings>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let siblings = response.body();
+	let previous = siblings.previous.as_ref().unwrap();
+	assert_eq!(previous.song.path, track_path("02 - Candlelight.mp3"));
+	assert_eq!(previous.song.track_number, Some(2));
+	let next = siblings.next.as_ref().unwrap();
+	assert_eq!(next.song.path, track_path("04 - Beyond The Door.mp3"));
+	assert_eq!(next.song.track_number, Some(4));
+
+	let request = protocol::song_siblings(&track_path("01 - Above The Water.mp3"));
+	let response = service.fetch_json::<_, dto::SongSiblings>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().previous.is_none());
+	assert!(response.body().next.is_some());
+}
+
+#[test]
+fn album_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::album(&path.to_string_lossy());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn album_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::album(&path.to_string_lossy());
+	let response = service.fetch_json::<_, dto::AlbumDetails>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let album = response.body();
+
+	assert_eq!(album.directory.path, path.to_string_lossy());
+	assert_eq!(album.track_count, 5);
+	let track_numbers: Vec<Option<i32>> = album.songs.iter().map(|s| s.song.track_number).collect();
+	assert_eq!(
+		track_numbers,
+		vec![Some(1), Some(2), Some(3), Some(4), Some(5)]
+	);
+	let mut years: Vec<i32> = album.songs.iter().filter_map(|s| s.song.year).collect();
+	years.sort();
+	years.dedup();
+	assert_eq!(album.years, years);
+	assert!(album.years.contains(&2016));
+	let durations: Vec<i32> = album.songs.iter().filter_map(|s| s.song.duration).collect();
+	assert_eq!(
+		album.total_duration,
+		(!durations.is_empty()).then(|| durations.iter().sum())
+	);
+
+	let request = protocol::album(&path.join("01 - Above The Water.mp3").to_string_lossy());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn ancestors_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::ancestors(TEST_MOUNT_NAME);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn ancestors_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [
+		TEST_MOUNT_NAME,
+		"Tobokegao",
+		"Picnic",
+		"05 - シャーベット (Sherbet).mp3",
+	]
+	.iter()
+	.collect();
+	let request = protocol::ancestors(&path.to_string_lossy());
+	let response = service.fetch_json::<_, Vec<index::Directory>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let paths: Vec<PathBuf> = response
+		.body()
+		.iter()
+		.map(|d| PathBuf::from(&d.path))
+		.collect();
+	assert_eq!(
+		paths,
+		vec![
+			PathBuf::from(TEST_MOUNT_NAME),
+			[TEST_MOUNT_NAME, "Tobokegao"].iter().collect(),
+			[TEST_MOUNT_NAME, "Tobokegao", "Picnic"].iter().collect(),
+		]
+	);
+	assert_eq!(response.body()[2].album, Some("Picnic".to_owned()));
+}
+
+#[test]
+fn ancestors_outside_mounts_is_not_found() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::ancestors("not_a_mount/Tobokegao");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn song_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [
+		TEST_MOUNT_NAME,
+		"Tobokegao",
+		"Picnic",
+		"05 - シャーベット (Sherbet).mp3",
+	]
+	.iter()
+	.collect();
+	let path = path.to_string_lossy();
+	let request = protocol::song(&path);
+	let response = service.fetch_json::<_, dto::Song>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let song = &response.body().song;
+	assert_eq!(song.path, path);
+	assert_eq!(song.track_number, Some(5));
+	assert_eq!(song.title, Some("シャーベット (Sherbet)".to_owned()));
+	assert_eq!(song.artist, Some("Tobokegao".to_owned()));
+	assert_eq!(song.album, Some("Picnic".to_owned()));
+	assert_eq!(song.year, Some(2016));
+	assert_eq!(song.format, Some("mp3".to_owned()));
+	assert_eq!(response.body().play_count, 0);
+}
+
+#[test]
+fn song_bad_path_returns_not_found() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "not_a_song.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::song(&path.to_string_lossy());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn songs_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let hunted: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let candlelight = hunted.join("02 - Candlelight.mp3");
+	let bogus = hunted.join("not_a_song.mp3");
+	let picnic: PathBuf = [
+		TEST_MOUNT_NAME,
+		"Tobokegao",
+		"Picnic",
+		"05 - シャーベット (Sherbet).mp3",
+	]
+	.iter()
+	.collect();
+	let (candlelight, bogus, picnic) = (
+		candlelight.to_string_lossy(),
+		bogus.to_string_lossy(),
+		picnic.to_string_lossy(),
+	);
+
+	let request = protocol::songs(&[&picnic, &bogus, &candlelight]);
+	let response = service.fetch_json::<_, dto::GetSongsOutput>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let output = response.body();
+	let paths: Vec<&str> = output.songs.iter().map(|s| s.song.path.as_str()).collect();
+	assert_eq!(paths, vec![picnic.as_ref(), candlelight.as_ref()]);
+	assert_eq!(output.not_found, vec![bogus.into_owned()]);
+}
+
+#[test]
+fn mark_played_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::mark_played(TEST_MOUNT_NAME);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn mark_played_increments_play_count_of_user_only() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let path = path.to_string_lossy();
+
+	let request = protocol::mark_played(&path);
+	let response = service.fetch_json::<_, dto::PlayCount>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().count, 1);
+
+	let play_count_of = |service: &mut ServiceType| {
+		let request = protocol::flatten(Path::new(TEST_MOUNT_NAME));
+		let response = service.fetch_json::<_, Vec<dto::Song>>(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+		let song = response
+			.body()
+			.iter()
+			.find(|s| s.song.path == path)
+			.map(|s| (s.play_count, s.last_played.is_some()));
+		song.unwrap()
+	};
+
+	assert_eq!(play_count_of(&mut service), (1, true));
+
+	service.login_admin();
+	assert_eq!(play_count_of(&mut service), (0, false));
+}
+
+#[test]
+fn mark_played_unknown_song_is_not_found() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Not A Song.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::mark_played(&path.to_string_lossy());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn export_history_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::export_history(dto::HistoryFormat::Json);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn activity_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::activity(None);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn activity_lists_plays_across_users() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let hunted: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let candlelight = hunted.join("02 - Candlelight.mp3");
+	let candlelight = candlelight.to_string_lossy();
+	let three_gates = hunted.join("03 - Three Gates.mp3");
+	let three_gates = three_gates.to_string_lossy();
+	let sharing = user::Preferences {
+		share_activity: true,
+		..Default::default()
+	};
+
+	let request = protocol::put_preferences(sharing.clone());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let request = protocol::mark_played(&three_gates);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	service.login();
+	let request = protocol::put_preferences(sharing);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let request = protocol::mark_played(&candlelight);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	service.login_admin();
+	let request = protocol::activity(Some(10));
+	let response = service.fetch_json::<_, Vec<dto::ActivityEntry>>(&request);
 	assert_eq!(response.status(), StatusCode::OK);
-	let entries = response.body();
-	assert_eq!(entries.len(), 1);
+	let activity: Vec<(&str, &str)> = response
+		.body()
+		.iter()
+		.map(|e| (e.username.as_str(), e.path.as_str()))
+		.collect();
+	assert_eq!(
+		activity,
+		vec![
+			(TEST_USERNAME, candlelight.as_ref()),
+			(TEST_USERNAME_ADMIN, three_gates.as_ref()),
+		]
+	);
+	assert!(response.body()[0].played_at >= response.body()[1].played_at);
 }
 
 #[test]
-fn browse_directory() {
+fn export_history_lists_plays_of_user_only() {
 	let mut service = ServiceType::new(&test_name!());
 	service.complete_initial_setup();
 	service.login_admin();
 	service.index();
+
+	let hunted: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let candlelight = hunted.join("02 - Candlelight.mp3");
+	let candlelight = candlelight.to_string_lossy();
+	let three_gates = hunted.join("03 - Three Gates.mp3");
+	let three_gates = three_gates.to_string_lossy();
+
+	let request = protocol::mark_played(&three_gates);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
 	service.login();
+	for _ in 0..2 {
+		let request = protocol::mark_played(&candlelight);
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+	}
 
-	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
-	let request = protocol::browse(&path);
-	let response = service.fetch_json::<_, Vec<index::CollectionFile>>(&request);
+	let request = protocol::export_history(dto::HistoryFormat::Json);
+	let response = service.fetch_json::<_, Vec<dto::HistoryEntry>>(&request);
 	assert_eq!(response.status(), StatusCode::OK);
-	let entries = response.body();
-	assert_eq!(entries.len(), 5);
+	let history: Vec<(&str, u32)> = response
+		.body()
+		.iter()
+		.map(|e| (e.path.as_str(), e.play_count))
+		.collect();
+	assert_eq!(
+		history,
+		vec![(candlelight.as_ref(), 2), (candlelight.as_ref(), 2)]
+	);
+
+	let request = protocol::export_history(dto::HistoryFormat::Csv);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let csv = String::from_utf8(response.into_body()).unwrap();
+	let mut lines = csv.lines();
+	assert_eq!(lines.next(), Some("path,played_at,play_count"));
+	let rows: Vec<&str> = lines.collect();
+	assert_eq!(rows.len(), 2);
+	assert!(rows
+		.iter()
+		.all(|r| r.contains("Candlelight") && r.ends_with(",2")));
 }
 
 #[test]
-fn browse_bad_directory() {
+fn progress_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::set_progress(&path.to_string_lossy(), 10);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn progress_round_trips_for_user_only() {
 	let mut service = ServiceType::new(&test_name!());
 	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
 	service.login();
 
-	let path: PathBuf = ["not_my_collection"].iter().collect();
-	let request = protocol::browse(&path);
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let path = path.to_string_lossy();
+
+	let request = protocol::set_progress(&path, 125);
+	let response = service.fetch_json::<_, dto::Progress>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().seconds, 125);
+
+	let request = protocol::get_progress(&path);
+	let response = service.fetch_json::<_, dto::Progress>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().seconds, 125);
+
+	let request = protocol::recent_progress();
+	let response = service.fetch_json::<_, Vec<dto::SongProgress>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let recent = response.body();
+	assert_eq!(recent.len(), 1);
+	assert_eq!(recent[0].song.song.path, path);
+	assert_eq!(recent[0].progress.seconds, 125);
+
+	service.login_admin();
+	let request = protocol::get_progress(&path);
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let request = protocol::recent_progress();
+	let response = service.fetch_json::<_, Vec<dto::SongProgress>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
 }
 
 #[test]
-fn flatten_requires_auth() {
+fn top_requires_auth() {
 	let mut service = ServiceType::new(&test_name!());
-	let request = protocol::flatten(&PathBuf::new());
+	let request = protocol::top(10, dto::TopWindow::AllTime, dto::TopKind::Songs);
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
 
 #[test]
-fn flatten_root() {
+fn top_ranks_songs_by_play_count() {
 	let mut service = ServiceType::new(&test_name!());
 	service.complete_initial_setup();
 	service.login_admin();
 	service.index();
 	service.login();
 
-	let request = protocol::flatten(&PathBuf::new());
-	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	let hunted: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let candlelight = hunted.join("02 - Candlelight.mp3");
+	let three_gates = hunted.join("03 - Three Gates.mp3");
+	for (path, plays) in [(&candlelight, 1), (&three_gates, 2)] {
+		for _ in 0..plays {
+			let request = protocol::mark_played(&path.to_string_lossy());
+			let response = service.fetch(&request);
+			assert_eq!(response.status(), StatusCode::OK);
+		}
+	}
+
+	let request = protocol::top(10, dto::TopWindow::AllTime, dto::TopKind::Songs);
+	let response = service.fetch_json::<_, Vec<dto::Song>>(&request);
 	assert_eq!(response.status(), StatusCode::OK);
-	let entries = response.body();
-	assert_eq!(entries.len(), 13);
+	let ranking: Vec<(PathBuf, u32)> = response
+		.body()
+		.iter()
+		.map(|s| (PathBuf::from(&s.song.path), s.play_count))
+		.collect();
+	assert_eq!(ranking, vec![(three_gates, 2), (candlelight, 1)]);
+
+	let request = protocol::top(10, dto::TopWindow::LastThirtyDays, dto::TopKind::Albums);
+	let response = service.fetch_json::<_, Vec<index::Directory>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let albums: Vec<PathBuf> = response
+		.body()
+		.iter()
+		.map(|d| PathBuf::from(&d.path))
+		.collect();
+	assert_eq!(albums, vec![hunted]);
 }
 
 #[test]
-fn flatten_directory() {
+fn random_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::random();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn random_golden_path() {
 	let mut service = ServiceType::new(&test_name!());
 	service.complete_initial_setup();
 	service.login_admin();
 	service.index();
 	service.login();
 
-	let request = protocol::flatten(Path::new(TEST_MOUNT_NAME));
-	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	let request = protocol::random();
+	let response = service.fetch_json::<_, Vec<index::Directory>>(&request);
 	assert_eq!(response.status(), StatusCode::OK);
 	let entries = response.body();
-	assert_eq!(entries.len(), 13);
+	assert_eq!(entries.len(), 3);
 }
 
 #[test]
-fn flatten_bad_directory() {
+fn random_with_trailing_slash() {
 	let mut service = ServiceType::new(&test_name!());
 	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
 	service.login();
 
-	let path: PathBuf = ["not_my_collection"].iter().collect();
-	let request = protocol::flatten(&path);
+	let mut request = protocol::random();
+	add_trailing_slash(&mut request);
+	let response = service.fetch_json::<_, Vec<index::Directory>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 3);
+}
+
+#[test]
+fn shuffle_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::shuffle(&dto::ShuffleQuery::default());
 	let response = service.fetch(&request);
-	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
 
 #[test]
-fn random_requires_auth() {
+fn shuffle_with_seed_is_deterministic() {
 	let mut service = ServiceType::new(&test_name!());
-	let request = protocol::random();
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let query = dto::ShuffleQuery {
+		count: Some(8),
+		seed: Some(42),
+		..Default::default()
+	};
+	let mut shuffle = |query: &dto::ShuffleQuery| -> Vec<String> {
+		let request = protocol::shuffle(query);
+		let response = service.fetch_json::<_, Vec<dto::Song>>(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+		response
+			.body()
+			.iter()
+			.map(|s| s.song.path.clone())
+			.collect()
+	};
+
+	let first = shuffle(&query);
+	assert_eq!(first.len(), 8);
+	let mut distinct = first.clone();
+	distinct.sort();
+	distinct.dedup();
+	assert_eq!(distinct.len(), 8);
+	assert_eq!(shuffle(&query), first);
+
+	let everything = shuffle(&dto::ShuffleQuery {
+		count: Some(100),
+		..Default::default()
+	});
+	assert_eq!(everything.len(), 13);
+}
+
+#[test]
+fn shuffle_can_filter_by_artist() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::shuffle(&dto::ShuffleQuery {
+		artist: Some("Khemmis".to_owned()),
+		..Default::default()
+	});
+	let response = service.fetch_json::<_, Vec<dto::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let songs = response.body();
+	assert_eq!(songs.len(), 5);
+	assert!(songs
+		.iter()
+		.all(|s| s.song.artist.as_deref() == Some("Khemmis")));
+}
+
+#[test]
+fn artists_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::artists();
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
 
 #[test]
-fn random_golden_path() {
+fn artists_golden_path() {
 	let mut service = ServiceType::new(&test_name!());
 	service.complete_initial_setup();
 	service.login_admin();
 	service.index();
 	service.login();
 
-	let request = protocol::random();
-	let response = service.fetch_json::<_, Vec<index::Directory>>(&request);
+	let request = protocol::artists();
+	let response = service.fetch_json::<_, Vec<index::Artist>>(&request);
 	assert_eq!(response.status(), StatusCode::OK);
 	let entries = response.body();
-	assert_eq!(entries.len(), 3);
+	assert_eq!(entries.len(), 2);
 }
 
 #[test]
-fn random_with_trailing_slash() {
+fn groupings_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::groupings();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn grouping_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::grouping("Warm Up");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn grouping_golden_path() {
 	let mut service = ServiceType::new(&test_name!());
 	service.complete_initial_setup();
 	service.login_admin();
 	service.index();
 	service.login();
 
-	let mut request = protocol::random();
-	add_trailing_slash(&mut request);
+	let request = protocol::groupings();
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::grouping("Warm Up");
+	let response = service.fetch_json::<_, Vec<dto::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+}
+
+#[test]
+fn labels_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::labels();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn label_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::labels();
+	let response = service.fetch_json::<_, Vec<index::Label>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::label("Unknown Records");
+	let response = service.fetch_json::<_, Vec<dto::Album>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+}
+
+#[test]
+fn missing_artwork_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::missing_artwork();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn missing_artwork_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::missing_artwork();
 	let response = service.fetch_json::<_, Vec<index::Directory>>(&request);
 	assert_eq!(response.status(), StatusCode::OK);
-	let entries = response.body();
-	assert_eq!(entries.len(), 3);
+}
+
+#[test]
+fn format_stats_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::format_stats();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn format_stats_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::format_stats();
+	let response = service.fetch_json::<_, Vec<index::FormatCount>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.body(),
+		&vec![index::FormatCount {
+			format: "mp3".to_owned(),
+			count: 13,
+		}]
+	);
 }
 
 #[test]
@@ -168,6 +1207,62 @@ fn recent_golden_path() {
 	assert_eq!(entries.len(), 3);
 }
 
+#[test]
+fn years_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::years();
+	let response = service.fetch_json::<_, Vec<index::YearCount>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.body(),
+		&vec![index::YearCount {
+			year: Some(2016),
+			song_count: 13,
+			album_count: 3,
+		}]
+	);
+}
+
+#[test]
+fn decade_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::decade("2010");
+	let response = service.fetch_json::<_, Vec<index::Directory>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 3);
+
+	let request = protocol::decade("1990");
+	let response = service.fetch_json::<_, Vec<index::Directory>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+
+	let request = protocol::decade("unknown");
+	let response = service.fetch_json::<_, Vec<index::Directory>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+}
+
+#[test]
+fn decade_rejects_invalid_decade() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let request = protocol::decade("nineties");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
 #[test]
 fn recent_with_trailing_slash() {
 	let mut service = ServiceType::new(&test_name!());
@@ -222,3 +1317,62 @@ fn search_with_query() {
 		_ => panic!(),
 	}
 }
+
+#[test]
+fn search_grouped_by_type() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::search_grouped("hunted");
+	let response = service.fetch_json::<_, index::SearchResults>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let results = response.body();
+	assert_eq!(results.songs.len(), 5);
+	assert_eq!(results.albums.len(), 1);
+	assert_eq!(results.albums[0].album, Some("Hunted".to_owned()));
+	assert!(results.artists.is_empty());
+}
+
+#[test]
+fn index_generation_changes_after_reindex() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let get_generation = |service: &mut ServiceType, request: &http::Request<()>| {
+		let response = service.fetch(request);
+		assert_eq!(response.status(), StatusCode::OK);
+		response.headers()["X-Index-Generation"]
+			.to_str()
+			.unwrap()
+			.to_owned()
+	};
+
+	let generation = get_generation(&mut service, &protocol::browse(Path::new("")));
+	assert_eq!(
+		get_generation(&mut service, &protocol::browse(Path::new(""))),
+		generation
+	);
+	assert_eq!(
+		get_generation(&mut service, &protocol::flatten(Path::new(""))),
+		generation
+	);
+	assert_eq!(
+		get_generation(&mut service, &protocol::search("door")),
+		generation
+	);
+
+	let response = service.fetch(&protocol::trigger_index());
+	assert_eq!(response.status(), StatusCode::OK);
+	loop {
+		let new_generation = get_generation(&mut service, &protocol::browse(Path::new("")));
+		if new_generation != generation {
+			break;
+		}
+		std::thread::sleep(std::time::Duration::from_secs(1));
+	}
+}
@@ -61,3 +61,48 @@ fn put_ddns_config_golden_path() {
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::OK);
 }
+
+#[test]
+fn test_ddns_config_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::test_ddns_config(dto::DDNSConfig {
+		host: "test".to_owned(),
+		username: "test".to_owned(),
+		password: "test".to_owned(),
+	});
+	service.complete_initial_setup();
+
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	service.login();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn test_ddns_config_does_not_save_config() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let request = protocol::get_ddns_config();
+	let initial_config = service
+		.fetch_json::<_, dto::DDNSConfig>(&request)
+		.into_body();
+
+	let request = protocol::test_ddns_config(dto::DDNSConfig {
+		host: "invalid host".to_owned(),
+		username: "test".to_owned(),
+		password: "test".to_owned(),
+	});
+	let response = service.fetch_json::<_, dto::DDNSTestReport>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(!response.body().success);
+
+	let request = protocol::get_ddns_config();
+	let config = service
+		.fetch_json::<_, dto::DDNSConfig>(&request)
+		.into_body();
+	assert_eq!(config, initial_config);
+}
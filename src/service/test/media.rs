@@ -1,8 +1,10 @@
-use http::{header, HeaderValue, StatusCode};
+use http::{header, HeaderValue, Method, Request, StatusCode};
 use std::path::PathBuf;
 
-use crate::service::dto::ThumbnailSize;
+use crate::app::thumbnail;
+use crate::service::dto::{self, ThumbnailSize};
 use crate::service::test::{constants::*, protocol, ServiceType, TestService};
+use crate::test::prepare_test_directory;
 use crate::test_name;
 
 #[test]
@@ -40,6 +42,119 @@ fn audio_golden_path() {
 	);
 }
 
+#[test]
+fn signed_stream_url_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::stream_url(&path, 60);
+	let response = service.fetch_json::<_, dto::StreamUrl>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let stream_url = response.body().url.clone();
+
+	service.logout();
+	let request = Request::builder()
+		.method(Method::GET)
+		.uri(&stream_url)
+		.body(())
+		.unwrap();
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 24_142);
+}
+
+#[test]
+fn play_album_lists_tracks_in_order_with_stream_urls() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::play_album(&path, true);
+	let response = service.fetch_json::<_, Vec<dto::AlbumTrack>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let tracks = response.body();
+	let track_numbers: Vec<Option<i32>> = tracks.iter().map(|t| t.song.song.track_number).collect();
+	assert_eq!(
+		track_numbers,
+		vec![Some(1), Some(2), Some(3), Some(4), Some(5)]
+	);
+
+	service.logout();
+	for track in tracks {
+		assert!(track.expires_at.is_some());
+		let request = Request::builder()
+			.method(Method::GET)
+			.uri(&track.stream_url)
+			.body(())
+			.unwrap();
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+}
+
+#[test]
+fn signed_stream_url_rejects_other_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::stream_url(&path, 60);
+	let response = service.fetch_json::<_, dto::StreamUrl>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let stream_url = response
+		.body()
+		.url
+		.replace("02%20%2D%20Candlelight", "03%20%2D%20Three%20Gates");
+	assert_ne!(stream_url, response.body().url);
+
+	service.logout();
+	let request = Request::builder()
+		.method(Method::GET)
+		.uri(&stream_url)
+		.body(())
+		.unwrap();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn audio_seek_percent_starts_near_midpoint() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::audio_seek(&path, 50.0);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+	assert_eq!(
+		response.headers().get(header::CONTENT_RANGE).unwrap(),
+		"bytes 13171-24141/24142"
+	);
+	assert_eq!(response.body().len(), 24_142 - 13_171);
+}
+
 #[test]
 fn audio_does_not_encode_content() {
 	let mut service = ServiceType::new(&test_name!());
@@ -69,6 +184,124 @@ fn audio_does_not_encode_content() {
 	);
 }
 
+fn read_zip_file_names(bytes: &[u8]) -> Vec<String> {
+	let read_u16 = |o: usize| u16::from_le_bytes([bytes[o], bytes[o + 1]]) as usize;
+	let read_u32 = |o: usize| u32::from_le_bytes(bytes[o..o + 4].try_into().unwrap()) as usize;
+
+	let end_of_central_directory = bytes.len() - 22;
+	let num_entries = read_u16(end_of_central_directory + 10);
+	let mut offset = read_u32(end_of_central_directory + 16);
+	let mut names = Vec::new();
+	for _ in 0..num_entries {
+		let name_length = read_u16(offset + 28);
+		let extra_length = read_u16(offset + 30);
+		let comment_length = read_u16(offset + 32);
+		let name = &bytes[offset + 46..offset + 46 + name_length];
+		names.push(String::from_utf8(name.to_vec()).unwrap());
+		offset += 46 + name_length + extra_length + comment_length;
+	}
+	names
+}
+
+#[test]
+fn zip_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::zip(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn zip_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::zip(&path);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.headers().get(header::CONTENT_TYPE).unwrap(),
+		"application/zip"
+	);
+	assert_eq!(
+		response.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+		"attachment; filename=\"Hunted.zip\""
+	);
+	assert_eq!(
+		read_zip_file_names(response.body()),
+		vec![
+			"Hunted/01 - Above The Water.mp3",
+			"Hunted/02 - Candlelight.mp3",
+			"Hunted/03 - Three Gates.mp3",
+			"Hunted/04 - Beyond The Door.mp3",
+			"Hunted/05 - Hunted.mp3",
+		]
+	);
+}
+
+#[test]
+fn zip_rejects_paths_escaping_mount() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "..", ".."].iter().collect();
+	let request = protocol::zip(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn audio_with_special_characters_in_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let collection_dir = prepare_test_directory(format!("{}-collection", test_name!()));
+	let album_dir = collection_dir.join("Café #1 Hits");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	let source: PathBuf = [
+		TEST_MOUNT_SOURCE,
+		"Khemmis",
+		"Hunted",
+		"02 - Candlelight.mp3",
+	]
+	.iter()
+	.collect();
+	std::fs::copy(&source, album_dir.join("Café #1.mp3")).unwrap();
+	std::fs::copy(&source, album_dir.join("100%25 Pure.mp3")).unwrap();
+
+	let request = protocol::apply_config(dto::Config {
+		mount_dirs: Some(vec![dto::MountDir {
+			source: collection_dir.to_str().unwrap().to_owned(),
+			name: TEST_MOUNT_NAME.to_owned(),
+			album_art_pattern: None,
+			reindex_every_n_seconds: None,
+			hide_from_root: false,
+		}]),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	service.index();
+	service.login();
+
+	for name in ["Café #1.mp3", "100%25 Pure.mp3"] {
+		let path: PathBuf = [TEST_MOUNT_NAME, "Café #1 Hits", name].iter().collect();
+		let request = protocol::audio(&path);
+		let response = service.fetch_bytes(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(response.body().len(), 24_142);
+	}
+}
+
 #[test]
 fn audio_partial_content() {
 	let mut service = ServiceType::new(&test_name!());
@@ -97,6 +330,93 @@ fn audio_partial_content() {
 	);
 }
 
+#[test]
+fn audio_open_ended_range_from_start_is_full_response() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let mut request = protocol::audio(&path);
+	request
+		.headers_mut()
+		.append(header::RANGE, HeaderValue::from_str("bytes=0-").unwrap());
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.headers().get(header::CONTENT_RANGE).is_none());
+	assert_eq!(response.body().len(), 24_142);
+
+	let mut request = protocol::audio(&path);
+	request
+		.headers_mut()
+		.append(header::RANGE, HeaderValue::from_str("bytes=100-").unwrap());
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+	assert_eq!(
+		response.headers().get(header::CONTENT_RANGE).unwrap(),
+		"bytes 100-24141/24142"
+	);
+	assert_eq!(response.body().len(), 24_142 - 100);
+}
+
+#[test]
+fn audio_partial_content_counts_towards_bandwidth_usage() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::put_settings(dto::NewSettings {
+		track_bandwidth: Some(true),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	service.login();
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let mut request = protocol::audio(&path);
+	let headers = request.headers_mut();
+	headers.append(
+		header::RANGE,
+		HeaderValue::from_str("bytes=100-299").unwrap(),
+	);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+	assert_eq!(response.body().len(), 200);
+
+	service.login_admin();
+	let request = protocol::bandwidth_usage();
+	let response = service.fetch_json::<_, Vec<dto::BandwidthUsage>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let usage = response.body();
+	let bytes_of = |username: &str| {
+		usage
+			.iter()
+			.find(|u| u.username == username)
+			.map(|u| u.bytes)
+	};
+	assert_eq!(bytes_of(TEST_USERNAME), Some(200));
+	assert_eq!(bytes_of(TEST_USERNAME_ADMIN), Some(0));
+}
+
+#[test]
+fn bandwidth_usage_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::bandwidth_usage();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
 #[test]
 fn audio_bad_path_returns_not_found() {
 	let mut service = ServiceType::new(&test_name!());
@@ -110,6 +430,34 @@ fn audio_bad_path_returns_not_found() {
 	assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
+#[test]
+fn lyrics_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::lyrics(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn lyrics_missing_returns_not_found() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::lyrics(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
 #[test]
 fn thumbnail_requires_auth() {
 	let mut service = ServiceType::new(&test_name!());
@@ -144,6 +492,328 @@ fn thumbnail_golden_path() {
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[test]
+fn thumbnail_format_negotiation() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "Folder.jpg"]
+		.iter()
+		.collect();
+
+	let request = protocol::thumbnail(&path, None, None);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.headers().get(header::CONTENT_TYPE).unwrap(),
+		"image/jpeg"
+	);
+
+	let mut request = protocol::thumbnail(&path, None, None);
+	request.headers_mut().append(
+		header::ACCEPT,
+		HeaderValue::from_static("image/avif,image/webp,*/*;q=0.8"),
+	);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.headers().get(header::CONTENT_TYPE).unwrap(),
+		"image/webp"
+	);
+	assert_eq!(&response.body()[8..12], b"WEBP");
+
+	let mut request = protocol::thumbnail_in_format(&path, dto::ThumbnailFormat::Jpeg);
+	request
+		.headers_mut()
+		.append(header::ACCEPT, HeaderValue::from_static("image/webp"));
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.headers().get(header::CONTENT_TYPE).unwrap(),
+		"image/jpeg"
+	);
+	assert!(image::load_from_memory(response.body()).is_ok());
+}
+
+#[test]
+fn thumbnail_head_returns_headers_without_body() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "Folder.jpg"]
+		.iter()
+		.collect();
+
+	let mut request = protocol::thumbnail(&path, None, None);
+	*request.method_mut() = Method::HEAD;
+	let head_response = service.fetch_bytes(&request);
+	assert_eq!(head_response.status(), StatusCode::OK);
+	assert!(head_response.body().is_empty());
+
+	let request = protocol::thumbnail(&path, None, None);
+	let get_response = service.fetch_bytes(&request);
+	assert_eq!(get_response.status(), StatusCode::OK);
+
+	for name in [header::CONTENT_TYPE, header::ETAG] {
+		assert!(head_response.headers().contains_key(&name));
+		assert_eq!(
+			head_response.headers().get(&name),
+			get_response.headers().get(&name)
+		);
+	}
+	assert_eq!(
+		head_response.headers().get(header::CONTENT_LENGTH).unwrap(),
+		&get_response.body().len().to_string()
+	);
+}
+
+#[test]
+fn artist_image_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let collection_dir = prepare_test_directory(format!("{}-collection", test_name!()));
+	let hunted_dir: PathBuf = [TEST_MOUNT_SOURCE, "Khemmis", "Hunted"].iter().collect();
+	let album_dir = collection_dir.join("Khemmis").join("Hunted");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	std::fs::copy(
+		hunted_dir.join("02 - Candlelight.mp3"),
+		album_dir.join("02 - Candlelight.mp3"),
+	)
+	.unwrap();
+	std::fs::copy(
+		hunted_dir.join("Folder.jpg"),
+		collection_dir.join("Khemmis").join("artist.jpg"),
+	)
+	.unwrap();
+
+	let request = protocol::apply_config(dto::Config {
+		mount_dirs: Some(vec![dto::MountDir {
+			source: collection_dir.to_str().unwrap().to_owned(),
+			name: TEST_MOUNT_NAME.to_owned(),
+			album_art_pattern: None,
+			reindex_every_n_seconds: None,
+			hide_from_root: false,
+		}]),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	service.index();
+	service.login();
+
+	let request = protocol::artist_image("Khemmis", Some(ThumbnailSize::Small));
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let image = image::load_from_memory(response.body()).unwrap().to_rgb8();
+	assert_eq!(image.width(), 400);
+	assert_eq!(image.height(), 400);
+}
+
+#[test]
+fn artist_image_not_found() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let request = protocol::artist_image("Khemmis", None);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let request = protocol::artist_image("Not An Artist", None);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn cover_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let cover = std::fs::read("test-data/artwork/Folder.png").unwrap();
+
+	let request = protocol::set_cover(&path, &cover);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	let request = protocol::clear_cover(&path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn cover_replaces_embedded_artwork() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let collection_dir = prepare_test_directory(format!("{}-collection", test_name!()));
+	let album_dir = collection_dir.join("Album");
+	std::fs::create_dir_all(&album_dir).unwrap();
+	std::fs::copy("test-data/artwork/sample.mp3", album_dir.join("sample.mp3")).unwrap();
+
+	let request = protocol::apply_config(dto::Config {
+		mount_dirs: Some(vec![dto::MountDir {
+			source: collection_dir.to_str().unwrap().to_owned(),
+			name: TEST_MOUNT_NAME.to_owned(),
+			album_art_pattern: None,
+			reindex_every_n_seconds: None,
+			hide_from_root: false,
+		}]),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let album_path: PathBuf = [TEST_MOUNT_NAME, "Album"].iter().collect();
+	let song_path = album_path.join("sample.mp3");
+	let read_thumbnail = |service: &mut ServiceType| -> Vec<u8> {
+		let request = protocol::thumbnail_in_format(&song_path, dto::ThumbnailFormat::Png);
+		let response = service.fetch_bytes(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+		response.into_body()
+	};
+	let embedded_thumbnail = read_thumbnail(&mut service);
+
+	let cover = std::fs::read("test-data/artwork/Folder.png").unwrap();
+	let request = protocol::set_cover(&album_path, &cover);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let cover_thumbnail = read_thumbnail(&mut service);
+	assert_ne!(cover_thumbnail, embedded_thumbnail);
+
+	service.index();
+	assert_eq!(read_thumbnail(&mut service), cover_thumbnail);
+
+	let request = protocol::clear_cover(&album_path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(read_thumbnail(&mut service), embedded_thumbnail);
+
+	let request = protocol::clear_cover(&album_path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn cover_rejects_invalid_uploads() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+
+	let request = protocol::set_cover(&path, b"definitely not an image");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+	let request = protocol::put_settings(dto::NewSettings {
+		max_cover_bytes: Some(16),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let cover = std::fs::read("test-data/artwork/Folder.png").unwrap();
+	let request = protocol::set_cover(&path, &cover);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[test]
+fn thumbnail_of_directory_without_artwork() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis"].iter().collect();
+	let request = protocol::thumbnail(&path, None, None);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let request = protocol::put_settings(dto::NewSettings {
+		artwork_placeholder_path: Some("test-data/artwork/Folder.png".to_owned()),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::thumbnail(&path, Some(ThumbnailSize::Small), None);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.headers().get("X-Polaris-Placeholder"),
+		Some(&HeaderValue::from_static("true"))
+	);
+	assert!(image::load_from_memory(response.body()).is_ok());
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "Folder.jpg"]
+		.iter()
+		.collect();
+	let request = protocol::thumbnail(&path, None, None);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.headers().get("X-Polaris-Placeholder").is_none());
+}
+
+#[test]
+fn thumbnail_of_directory_with_multiple_albums() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let cover: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "Folder.jpg"]
+		.iter()
+		.collect();
+	let request = protocol::thumbnail(&cover, None, None);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let cover_thumbnail = response.into_body();
+
+	let request = protocol::put_settings(dto::NewSettings {
+		directory_thumbnail: Some(dto::DirectoryThumbnail::FirstCover),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let root = PathBuf::from(TEST_MOUNT_NAME);
+	let request = protocol::thumbnail(&root, None, None);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body(), &cover_thumbnail);
+
+	let request = protocol::put_settings(dto::NewSettings {
+		directory_thumbnail: Some(dto::DirectoryThumbnail::Collage),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::thumbnail(&root, None, None);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_ne!(response.body(), &cover_thumbnail);
+	let collage = image::load_from_memory(response.body()).unwrap();
+	let cover = image::load_from_memory(&cover_thumbnail).unwrap();
+	assert_eq!(collage.width(), cover.width());
+	assert_eq!(collage.height(), cover.height());
+}
+
 #[test]
 fn thumbnail_bad_path_returns_not_found() {
 	let mut service = ServiceType::new(&test_name!());
@@ -199,3 +869,79 @@ fn thumbnail_size(name: &str, size: Option<ThumbnailSize>, pad: Option<bool>, ex
 	assert_eq!(thumbnail.width(), expected);
 	assert_eq!(thumbnail.height(), expected);
 }
+
+#[test]
+fn thumbnail_prewarm_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let request = protocol::prewarm_thumbnails(dto::ThumbnailPrewarmInput::default());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn thumbnail_prewarm_populates_cache_for_each_album() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::prewarm_thumbnails(dto::ThumbnailPrewarmInput::default());
+	let response = service.fetch_json::<_, thumbnail::PrewarmProgress>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let progress = response.body();
+	assert!(progress.total >= 2);
+	assert_eq!(progress.generated, progress.total);
+	assert!(!progress.running);
+
+	let request = protocol::prewarm_thumbnails(dto::ThumbnailPrewarmInput::default());
+	let response = service.fetch_json::<_, thumbnail::PrewarmProgress>(&request);
+	let progress = response.body();
+	assert_eq!(progress.generated, 0);
+	assert_eq!(progress.skipped, progress.total);
+
+	let request = protocol::thumbnail_prewarm_progress();
+	let response = service.fetch_json::<_, thumbnail::PrewarmProgress>(&request);
+	assert_eq!(response.body(), progress);
+}
+
+#[test]
+fn thumbnail_prewarm_can_be_cancelled() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let request = protocol::cancel_thumbnail_prewarm();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	service.login_admin();
+	let request = protocol::cancel_thumbnail_prewarm();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::thumbnail_prewarm_progress();
+	let response = service.fetch_json::<_, thumbnail::PrewarmProgress>(&request);
+	assert!(!response.body().running);
+}
+
+#[test]
+fn thumbnail_prewarm_can_target_subtree() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis"].iter().collect();
+	let request = protocol::prewarm_thumbnails(dto::ThumbnailPrewarmInput {
+		path: Some(path.to_string_lossy().into_owned()),
+		size: Some(ThumbnailSize::Small),
+		..Default::default()
+	});
+	let response = service.fetch_json::<_, thumbnail::PrewarmProgress>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().total, 1);
+	assert_eq!(response.body().generated, 1);
+}
@@ -1,4 +1,6 @@
 use http::StatusCode;
+use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
+use std::path::PathBuf;
 
 use crate::app::index;
 use crate::service::dto;
@@ -23,10 +25,51 @@ fn list_playlists_golden_path() {
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[test]
+fn list_playlists_ordering() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	for name in ["Chill", "Road Trip"] {
+		let my_playlist = dto::SavePlaylistInput {
+			tracks: Vec::new(),
+			strict: false,
+			sort_by_disc_and_track: false,
+		};
+		let request = protocol::save_playlist(name, my_playlist);
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+
+	let names = |service: &mut ServiceType, request: http::Request<()>| {
+		let response = service.fetch_json::<_, Vec<dto::ListPlaylistsEntry>>(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+		response
+			.body()
+			.iter()
+			.map(|p| p.name.clone())
+			.collect::<Vec<_>>()
+	};
+
+	let request = protocol::playlists();
+	assert_eq!(names(&mut service, request), vec!["Chill", "Road Trip"]);
+
+	let request = protocol::playlists_ordered_by(dto::PlaylistOrder::Created);
+	assert_eq!(names(&mut service, request), vec!["Road Trip", "Chill"]);
+
+	let request = protocol::playlists_ordered_by(dto::PlaylistOrder::Name);
+	assert_eq!(names(&mut service, request), vec!["Chill", "Road Trip"]);
+}
+
 #[test]
 fn save_playlist_requires_auth() {
 	let mut service = ServiceType::new(&test_name!());
-	let my_playlist = dto::SavePlaylistInput { tracks: Vec::new() };
+	let my_playlist = dto::SavePlaylistInput {
+		tracks: Vec::new(),
+		strict: false,
+		sort_by_disc_and_track: false,
+	};
 	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
@@ -38,7 +81,11 @@ fn save_playlist_golden_path() {
 	service.complete_initial_setup();
 	service.login();
 
-	let my_playlist = dto::SavePlaylistInput { tracks: Vec::new() };
+	let my_playlist = dto::SavePlaylistInput {
+		tracks: Vec::new(),
+		strict: false,
+		sort_by_disc_and_track: false,
+	};
 	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::OK);
@@ -53,12 +100,50 @@ fn save_playlist_large() {
 	let tracks = (0..100_000)
 		.map(|_| "My Super Cool Song".to_string())
 		.collect();
-	let my_playlist = dto::SavePlaylistInput { tracks };
+	let my_playlist = dto::SavePlaylistInput {
+		tracks,
+		strict: false,
+		sort_by_disc_and_track: false,
+	};
 	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[test]
+fn save_playlist_reports_invalid_tracks() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let tracks = vec!["My Super Cool Song".to_string()];
+	let my_playlist = dto::SavePlaylistInput {
+		tracks: tracks.clone(),
+		strict: false,
+		sort_by_disc_and_track: false,
+	};
+	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+	let response = service.fetch_json::<_, dto::SavePlaylistOutput>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().invalid_tracks, tracks);
+}
+
+#[test]
+fn save_playlist_strict_rejects_invalid_tracks() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let my_playlist = dto::SavePlaylistInput {
+		tracks: vec!["My Super Cool Song".to_string()],
+		strict: true,
+		sort_by_disc_and_track: false,
+	};
+	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
 #[test]
 fn get_playlist_requires_auth() {
 	let mut service = ServiceType::new(&test_name!());
@@ -74,7 +159,11 @@ fn get_playlist_golden_path() {
 	service.login();
 
 	{
-		let my_playlist = dto::SavePlaylistInput { tracks: Vec::new() };
+		let my_playlist = dto::SavePlaylistInput {
+			tracks: Vec::new(),
+			strict: false,
+			sort_by_disc_and_track: false,
+		};
 		let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
 		let response = service.fetch(&request);
 		assert_eq!(response.status(), StatusCode::OK);
@@ -96,6 +185,190 @@ fn get_playlist_bad_name_returns_not_found() {
 	assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
+#[test]
+fn playlist_orphans_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::playlist_orphans(TEST_PLAYLIST_NAME);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn playlist_orphans_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let tracks: Vec<String> = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect::<PathBuf>()
+		.to_str()
+		.map(|p| vec![p.to_owned()])
+		.unwrap();
+	let my_playlist = dto::SavePlaylistInput {
+		tracks,
+		strict: true,
+		sort_by_disc_and_track: false,
+	};
+	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::playlist_orphans(TEST_PLAYLIST_NAME);
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+
+	let request = protocol::prune_playlist(TEST_PLAYLIST_NAME);
+	let response = service.fetch_json::<_, Vec<String>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+
+	let request = protocol::prune_playlist("not a playlist");
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn get_playlist_page_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::read_playlist_page(TEST_PLAYLIST_NAME, 0, 10);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn get_playlist_page_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let tracks: Vec<String> = {
+		let request = protocol::flatten(&PathBuf::new());
+		let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+		response.body().iter().map(|s| s.path.clone()).collect()
+	};
+
+	{
+		let my_playlist = dto::SavePlaylistInput {
+			tracks: tracks.clone(),
+			strict: false,
+			sort_by_disc_and_track: false,
+		};
+		let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+
+	let request = protocol::read_playlist_page(TEST_PLAYLIST_NAME, 5, 4);
+	let response = service.fetch_json::<_, dto::PlaylistPage>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let page = response.body();
+	assert_eq!(page.total, tracks.len());
+	let paths: Vec<String> = page.songs.iter().map(|s| s.path.clone()).collect();
+	assert_eq!(paths, tracks[5..9].to_vec());
+}
+
+#[test]
+fn get_playlist_page_is_clamped_to_max_page_size() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::put_settings(dto::NewSettings {
+		max_page_size: Some(3),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let tracks: Vec<String> = {
+		let request = protocol::flatten(&PathBuf::new());
+		let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+		response.body().iter().map(|s| s.path.clone()).collect()
+	};
+
+	{
+		let my_playlist = dto::SavePlaylistInput {
+			tracks: tracks.clone(),
+			strict: false,
+			sort_by_disc_and_track: false,
+		};
+		let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+
+	let request = protocol::read_playlist_page(TEST_PLAYLIST_NAME, 0, 10);
+	let response = service.fetch_json::<_, dto::PlaylistPage>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let page = response.body();
+	assert_eq!(page.total, tracks.len());
+	let paths: Vec<String> = page.songs.iter().map(|s| s.path.clone()).collect();
+	assert_eq!(paths, tracks[0..3].to_vec());
+}
+
+#[test]
+fn export_playlist_requires_auth() {
+	let mut service = ServiceType::new(&test_name!());
+	let request = protocol::export_playlist(TEST_PLAYLIST_NAME);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn export_playlist_links_to_public_base_url() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+
+	let request = protocol::put_settings(dto::NewSettings {
+		public_base_url: Some("https://music.example.com/polaris".to_owned()),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	service.login();
+	let tracks: Vec<String> = {
+		let request = protocol::flatten(&PathBuf::new());
+		let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+		response.body().iter().map(|s| s.path.clone()).collect()
+	};
+	{
+		let my_playlist = dto::SavePlaylistInput {
+			tracks: tracks.clone(),
+			strict: false,
+			sort_by_disc_and_track: false,
+		};
+		let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+
+	let request = protocol::export_playlist(TEST_PLAYLIST_NAME);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let m3u = String::from_utf8(response.body().clone()).unwrap();
+	let mut lines = m3u.lines();
+	assert_eq!(lines.next(), Some("#EXTM3U"));
+	let links: Vec<&str> = lines.filter(|l| !l.starts_with('#')).collect();
+	let expected_links: Vec<String> = tracks
+		.iter()
+		.map(|t| {
+			let path = percent_encode(t.as_bytes(), NON_ALPHANUMERIC);
+			format!("https://music.example.com/polaris/api/audio/{}", path)
+		})
+		.collect();
+	assert_eq!(links, expected_links);
+}
+
 #[test]
 fn delete_playlist_requires_auth() {
 	let mut service = ServiceType::new(&test_name!());
@@ -111,7 +384,11 @@ fn delete_playlist_golden_path() {
 	service.login();
 
 	{
-		let my_playlist = dto::SavePlaylistInput { tracks: Vec::new() };
+		let my_playlist = dto::SavePlaylistInput {
+			tracks: Vec::new(),
+			strict: false,
+			sort_by_disc_and_track: false,
+		};
 		let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
 		let response = service.fetch(&request);
 		assert_eq!(response.status(), StatusCode::OK);
@@ -132,3 +409,165 @@ fn delete_playlist_bad_name_returns_not_found() {
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
+
+#[test]
+fn move_playlist_track_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let album_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::flatten(&album_path);
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	let tracks: Vec<String> = response.into_body().into_iter().map(|s| s.path).collect();
+
+	let my_playlist = dto::SavePlaylistInput {
+		tracks: tracks.clone(),
+		strict: false,
+		sort_by_disc_and_track: false,
+	};
+	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::move_playlist_track(
+		TEST_PLAYLIST_NAME,
+		dto::MovePlaylistTrackInput {
+			from_index: 0,
+			to_index: 2,
+		},
+	);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::read_playlist(TEST_PLAYLIST_NAME);
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let paths: Vec<String> = response.into_body().into_iter().map(|s| s.path).collect();
+	assert_eq!(
+		paths,
+		vec![
+			tracks[1].clone(),
+			tracks[2].clone(),
+			tracks[0].clone(),
+			tracks[3].clone(),
+			tracks[4].clone()
+		]
+	);
+
+	let request = protocol::move_playlist_track(
+		TEST_PLAYLIST_NAME,
+		dto::MovePlaylistTrackInput {
+			from_index: 0,
+			to_index: 5,
+		},
+	);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn delete_playlists_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	for name in ["Chill", "Road Trip", "Workout"] {
+		let my_playlist = dto::SavePlaylistInput {
+			tracks: Vec::new(),
+			strict: false,
+			sort_by_disc_and_track: false,
+		};
+		let request = protocol::save_playlist(name, my_playlist);
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+
+	let request = protocol::delete_playlists(dto::DeletePlaylistsInput {
+		names: ["Chill", "Road Trip", "Nonexistent"]
+			.map(str::to_owned)
+			.to_vec(),
+		owner: None,
+	});
+	let response = service.fetch_json::<_, dto::DeletePlaylistsOutput>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.body(),
+		&dto::DeletePlaylistsOutput {
+			deleted: vec!["Chill".to_owned(), "Road Trip".to_owned()],
+			not_found: vec!["Nonexistent".to_owned()],
+		}
+	);
+
+	let request = protocol::playlists();
+	let response = service.fetch_json::<_, Vec<dto::ListPlaylistsEntry>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.body(),
+		&vec![dto::ListPlaylistsEntry {
+			name: "Workout".to_owned()
+		}]
+	);
+}
+
+#[test]
+fn delete_playlists_of_other_user_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let request = protocol::delete_playlists(dto::DeletePlaylistsInput {
+		names: vec![TEST_PLAYLIST_NAME.to_owned()],
+		owner: Some(TEST_USERNAME_ADMIN.to_owned()),
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn playlist_upload_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	service.login();
+
+	let album_path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::flatten(&album_path);
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	let tracks: Vec<String> = response.into_body().into_iter().map(|s| s.path).collect();
+
+	let request = protocol::begin_playlist_upload(
+		TEST_PLAYLIST_NAME,
+		dto::BeginPlaylistUploadInput::default(),
+	);
+	let response = service.fetch_json::<_, dto::PlaylistUpload>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let upload_id = response.into_body().id;
+
+	for chunk in tracks.chunks(2) {
+		let request = protocol::append_playlist_upload(
+			&upload_id,
+			dto::AppendPlaylistUploadInput {
+				tracks: chunk.to_vec(),
+			},
+		);
+		let response = service.fetch(&request);
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+
+	let request = protocol::commit_playlist_upload(&upload_id);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::read_playlist(TEST_PLAYLIST_NAME);
+	let response = service.fetch_json::<_, Vec<index::Song>>(&request);
+	let paths: Vec<String> = response.into_body().into_iter().map(|s| s.path).collect();
+	assert_eq!(paths, tracks);
+
+	let request = protocol::commit_playlist_upload(&upload_id);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
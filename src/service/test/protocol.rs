@@ -1,3 +1,4 @@
+use base64::prelude::*;
 use http::{Method, Request};
 use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
 use std::path::Path;
@@ -29,6 +30,14 @@ pub fn version() -> Request<()> {
 		.unwrap()
 }
 
+pub fn capabilities() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/capabilities")
+		.body(())
+		.unwrap()
+}
+
 pub fn initial_setup() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -49,6 +58,23 @@ pub fn login(username: &str, password: &str) -> Request<dto::Credentials> {
 		.unwrap()
 }
 
+pub fn bootstrap_admin(
+	token: &str,
+	username: &str,
+	password: &str,
+) -> Request<dto::BootstrapAdmin> {
+	let bootstrap = dto::BootstrapAdmin {
+		token: token.into(),
+		username: username.into(),
+		password: password.into(),
+	};
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/bootstrap")
+		.body(bootstrap)
+		.unwrap()
+}
+
 pub fn apply_config(config: dto::Config) -> Request<dto::Config> {
 	Request::builder()
 		.method(Method::PUT)
@@ -57,6 +83,38 @@ pub fn apply_config(config: dto::Config) -> Request<dto::Config> {
 		.unwrap()
 }
 
+pub fn patch_config(config: dto::Config) -> Request<dto::Config> {
+	Request::builder()
+		.method(Method::PATCH)
+		.uri("/api/config")
+		.body(config)
+		.unwrap()
+}
+
+pub fn export_config() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/config/export")
+		.body(())
+		.unwrap()
+}
+
+pub fn import_config(config: dto::Config) -> Request<dto::Config> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/config/import")
+		.body(config)
+		.unwrap()
+}
+
+pub fn bandwidth_usage() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/bandwidth")
+		.body(())
+		.unwrap()
+}
+
 pub fn get_settings() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -73,6 +131,14 @@ pub fn put_settings(settings: dto::NewSettings) -> Request<dto::NewSettings> {
 		.unwrap()
 }
 
+pub fn list_mount_dirs() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/mount_dirs")
+		.body(())
+		.unwrap()
+}
+
 pub fn get_ddns_config() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -89,6 +155,14 @@ pub fn put_ddns_config(ddns_config: dto::DDNSConfig) -> Request<dto::DDNSConfig>
 		.unwrap()
 }
 
+pub fn test_ddns_config(ddns_config: dto::DDNSConfig) -> Request<dto::DDNSConfig> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/ddns/test")
+		.body(ddns_config)
+		.unwrap()
+}
+
 pub fn list_users() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -145,6 +219,132 @@ pub fn trigger_index() -> Request<()> {
 		.unwrap()
 }
 
+pub fn index_status() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/index/status")
+		.body(())
+		.unwrap()
+}
+
+pub fn index_errors() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/index/errors")
+		.body(())
+		.unwrap()
+}
+
+pub fn index_events() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/index/events")
+		.body(())
+		.unwrap()
+}
+
+pub fn rebuild_index() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/index/rebuild")
+		.body(())
+		.unwrap()
+}
+
+pub fn refresh_index(path: &str) -> Request<()> {
+	let endpoint = format!("/api/index/refresh?path={}", url_encode(path));
+	Request::builder()
+		.method(Method::POST)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn lint_metadata(path: &str) -> Request<dto::MetadataLintInput> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/metadata/lint")
+		.body(dto::MetadataLintInput {
+			path: path.to_owned(),
+		})
+		.unwrap()
+}
+
+pub fn raw_metadata(path: &str) -> Request<()> {
+	let endpoint = format!("/api/metadata/raw?path={}", url_encode(path));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn pause_index() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/index/pause")
+		.body(())
+		.unwrap()
+}
+
+pub fn resume_index() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/index/resume")
+		.body(())
+		.unwrap()
+}
+
+pub fn cancel_index() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/index/cancel")
+		.body(())
+		.unwrap()
+}
+
+pub fn mounts() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/mounts")
+		.body(())
+		.unwrap()
+}
+
+pub fn groupings() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/groupings")
+		.body(())
+		.unwrap()
+}
+
+pub fn grouping(name: &str) -> Request<()> {
+	let endpoint = format!("/api/grouping/{}", url_encode(name));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn labels() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/labels")
+		.body(())
+		.unwrap()
+}
+
+pub fn label(name: &str) -> Request<()> {
+	let endpoint = format!("/api/label/{}", url_encode(name));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn browse(path: &Path) -> Request<()> {
 	let path = path.to_string_lossy();
 	let endpoint = format!("/api/browse/{}", url_encode(path.as_ref()));
@@ -155,6 +355,19 @@ pub fn browse(path: &Path) -> Request<()> {
 		.unwrap()
 }
 
+pub fn browse_with_media(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/browse/{}?include_media=true",
+		url_encode(path.as_ref())
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn flatten(path: &Path) -> Request<()> {
 	let path = path.to_string_lossy();
 	let endpoint = format!("/api/flatten/{}", url_encode(path.as_ref()));
@@ -165,6 +378,21 @@ pub fn flatten(path: &Path) -> Request<()> {
 		.unwrap()
 }
 
+pub fn flatten_page(path: &Path, offset: usize, count: usize) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/flatten/{}?offset={}&count={}",
+		url_encode(path.as_ref()),
+		offset,
+		count
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn random() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -173,6 +401,73 @@ pub fn random() -> Request<()> {
 		.unwrap()
 }
 
+pub fn artists() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/artists")
+		.body(())
+		.unwrap()
+}
+
+pub fn missing_artwork() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/artwork/missing")
+		.body(())
+		.unwrap()
+}
+
+pub fn prewarm_thumbnails(
+	input: dto::ThumbnailPrewarmInput,
+) -> Request<dto::ThumbnailPrewarmInput> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/thumbnails/prewarm")
+		.body(input)
+		.unwrap()
+}
+
+pub fn thumbnail_prewarm_progress() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/thumbnails/prewarm")
+		.body(())
+		.unwrap()
+}
+
+pub fn cancel_thumbnail_prewarm() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/thumbnails/prewarm/cancel")
+		.body(())
+		.unwrap()
+}
+
+pub fn format_stats() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/stats/formats")
+		.body(())
+		.unwrap()
+}
+
+pub fn years() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/years")
+		.body(())
+		.unwrap()
+}
+
+pub fn decade(decade: &str) -> Request<()> {
+	let endpoint = format!("/api/decade/{}", url_encode(decade));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn recent() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -190,6 +485,15 @@ pub fn search(query: &str) -> Request<()> {
 		.unwrap()
 }
 
+pub fn search_grouped(query: &str) -> Request<()> {
+	let endpoint = format!("/api/search/{}?group=true", url_encode(query));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn audio(path: &Path) -> Request<()> {
 	let path = path.to_string_lossy();
 	let endpoint = format!("/api/audio/{}", url_encode(path.as_ref()));
@@ -200,6 +504,68 @@ pub fn audio(path: &Path) -> Request<()> {
 		.unwrap()
 }
 
+pub fn stream_url(path: &Path, ttl_seconds: u64) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/stream_url?path={}&ttl_seconds={}",
+		url_encode(path.as_ref()),
+		ttl_seconds
+	);
+	Request::builder()
+		.method(Method::POST)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn play_album(path: &Path, signed: bool) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/album/play?path={}&signed={}",
+		url_encode(path.as_ref()),
+		signed
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn zip(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/zip/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn lyrics(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/lyrics/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn audio_seek(path: &Path, seek_percent: f64) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/audio/{}?seek_percent={}",
+		url_encode(path.as_ref()),
+		seek_percent
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn thumbnail(path: &Path, size: Option<ThumbnailSize>, pad: Option<bool>) -> Request<()> {
 	let path = path.to_string_lossy();
 	let mut params = String::new();
@@ -233,6 +599,63 @@ pub fn thumbnail(path: &Path, size: Option<ThumbnailSize>, pad: Option<bool>) ->
 		.unwrap()
 }
 
+pub fn set_cover(path: &Path, content: &[u8]) -> Request<dto::CoverUpload> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/cover/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::PUT)
+		.uri(&endpoint)
+		.body(dto::CoverUpload {
+			content: BASE64_STANDARD.encode(content),
+		})
+		.unwrap()
+}
+
+pub fn clear_cover(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/cover/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::DELETE)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn artist_image(name: &str, size: Option<ThumbnailSize>) -> Request<()> {
+	let mut endpoint = format!("/api/artist/{}/image", url_encode(name));
+	match size {
+		Some(ThumbnailSize::Small) => endpoint.push_str("?size=small"),
+		Some(ThumbnailSize::Large) => endpoint.push_str("?size=large"),
+		Some(ThumbnailSize::Native) => endpoint.push_str("?size=native"),
+		None => (),
+	};
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn thumbnail_in_format(path: &Path, format: dto::ThumbnailFormat) -> Request<()> {
+	let path = path.to_string_lossy();
+	let format = match format {
+		dto::ThumbnailFormat::Jpeg => "jpeg",
+		dto::ThumbnailFormat::Webp => "webp",
+		dto::ThumbnailFormat::Png => "png",
+		dto::ThumbnailFormat::Auto => "auto",
+	};
+	let endpoint = format!(
+		"/api/thumbnail/{}?format={}",
+		url_encode(path.as_ref()),
+		format
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn playlists() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -241,6 +664,20 @@ pub fn playlists() -> Request<()> {
 		.unwrap()
 }
 
+pub fn playlists_ordered_by(order_by: dto::PlaylistOrder) -> Request<()> {
+	let order_by = match order_by {
+		dto::PlaylistOrder::Name => "name",
+		dto::PlaylistOrder::Created => "created",
+		dto::PlaylistOrder::Updated => "updated",
+	};
+	let endpoint = format!("/api/playlists?order_by={order_by}");
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn save_playlist(
 	name: &str,
 	playlist: dto::SavePlaylistInput,
@@ -253,6 +690,48 @@ pub fn save_playlist(
 		.unwrap()
 }
 
+pub fn begin_playlist_upload(
+	name: &str,
+	input: dto::BeginPlaylistUploadInput,
+) -> Request<dto::BeginPlaylistUploadInput> {
+	let endpoint = format!("/api/playlist/{}/upload", url_encode(name));
+	Request::builder()
+		.method(Method::POST)
+		.uri(&endpoint)
+		.body(input)
+		.unwrap()
+}
+
+pub fn append_playlist_upload(
+	id: &str,
+	input: dto::AppendPlaylistUploadInput,
+) -> Request<dto::AppendPlaylistUploadInput> {
+	let endpoint = format!("/api/playlist_upload/{}", url_encode(id));
+	Request::builder()
+		.method(Method::POST)
+		.uri(&endpoint)
+		.body(input)
+		.unwrap()
+}
+
+pub fn commit_playlist_upload(id: &str) -> Request<()> {
+	let endpoint = format!("/api/playlist_upload/{}/commit", url_encode(id));
+	Request::builder()
+		.method(Method::POST)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn export_playlist(name: &str) -> Request<()> {
+	let endpoint = format!("/api/playlist/{}/m3u", url_encode(name));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn read_playlist(name: &str) -> Request<()> {
 	let endpoint = format!("/api/playlist/{}", url_encode(name));
 	Request::builder()
@@ -262,6 +741,38 @@ pub fn read_playlist(name: &str) -> Request<()> {
 		.unwrap()
 }
 
+pub fn playlist_orphans(name: &str) -> Request<()> {
+	let endpoint = format!("/api/playlist/{}/orphans", url_encode(name));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn prune_playlist(name: &str) -> Request<()> {
+	let endpoint = format!("/api/playlist/{}/prune", url_encode(name));
+	Request::builder()
+		.method(Method::POST)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn read_playlist_page(name: &str, offset: usize, count: usize) -> Request<()> {
+	let endpoint = format!(
+		"/api/playlist/{}/page?offset={}&count={}",
+		url_encode(name),
+		offset,
+		count
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 pub fn delete_playlist(name: &str) -> Request<()> {
 	let endpoint = format!("/api/playlist/{}", url_encode(name));
 	Request::builder()
@@ -271,6 +782,26 @@ pub fn delete_playlist(name: &str) -> Request<()> {
 		.unwrap()
 }
 
+pub fn move_playlist_track(
+	name: &str,
+	input: dto::MovePlaylistTrackInput,
+) -> Request<dto::MovePlaylistTrackInput> {
+	let endpoint = format!("/api/playlist/{}/move", url_encode(name));
+	Request::builder()
+		.method(Method::PATCH)
+		.uri(&endpoint)
+		.body(input)
+		.unwrap()
+}
+
+pub fn delete_playlists(input: dto::DeletePlaylistsInput) -> Request<dto::DeletePlaylistsInput> {
+	Request::builder()
+		.method(Method::DELETE)
+		.uri("/api/playlists")
+		.body(input)
+		.unwrap()
+}
+
 pub fn lastfm_link_token() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -299,6 +830,204 @@ pub fn lastfm_scrobble(path: &Path) -> Request<()> {
 		.unwrap()
 }
 
+pub fn song(path: &str) -> Request<()> {
+	let endpoint = format!("/api/song?path={}", url_encode(path));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn song_siblings(path: &str) -> Request<()> {
+	let endpoint = format!("/api/song/siblings?path={}", url_encode(path));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn album(path: &str) -> Request<()> {
+	let endpoint = format!("/api/album?path={}", url_encode(path));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn ancestors(path: &str) -> Request<()> {
+	let endpoint = format!("/api/ancestors?path={}", url_encode(path));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn songs(paths: &[&str]) -> Request<dto::GetSongsInput> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/songs")
+		.body(dto::GetSongsInput {
+			paths: paths.iter().map(|p| p.to_string()).collect(),
+		})
+		.unwrap()
+}
+
+pub fn mark_played(path: &str) -> Request<()> {
+	let endpoint = format!("/api/play?path={}", url_encode(path));
+	Request::builder()
+		.method(Method::POST)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn activity(count: Option<usize>) -> Request<()> {
+	let endpoint = match count {
+		Some(count) => format!("/api/activity?count={count}"),
+		None => "/api/activity".to_owned(),
+	};
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn export_history(format: dto::HistoryFormat) -> Request<()> {
+	let format = match format {
+		dto::HistoryFormat::Json => "json",
+		dto::HistoryFormat::Csv => "csv",
+	};
+	let endpoint = format!("/api/play/history?format={format}");
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn set_progress(path: &str, seconds: u32) -> Request<()> {
+	let endpoint = format!("/api/progress?path={}&seconds={seconds}", url_encode(path));
+	Request::builder()
+		.method(Method::POST)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn get_progress(path: &str) -> Request<()> {
+	let endpoint = format!("/api/progress?path={}", url_encode(path));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn recent_progress() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/progress/recent")
+		.body(())
+		.unwrap()
+}
+
+pub fn top(count: usize, window: dto::TopWindow, kind: dto::TopKind) -> Request<()> {
+	let window = match window {
+		dto::TopWindow::AllTime => "all_time",
+		dto::TopWindow::LastThirtyDays => "last_thirty_days",
+	};
+	let kind = match kind {
+		dto::TopKind::Songs => "songs",
+		dto::TopKind::Albums => "albums",
+	};
+	let endpoint = format!("/api/top?count={count}&window={window}&kind={kind}");
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn shuffle(query: &dto::ShuffleQuery) -> Request<()> {
+	let mut parameters = Vec::new();
+	if let Some(count) = query.count {
+		parameters.push(format!("count={count}"));
+	}
+	if let Some(seed) = query.seed {
+		parameters.push(format!("seed={seed}"));
+	}
+	if let Some(genre) = &query.genre {
+		parameters.push(format!("genre={}", url_encode(genre)));
+	}
+	if let Some(artist) = &query.artist {
+		parameters.push(format!("artist={}", url_encode(artist)));
+	}
+	let endpoint = format!("/api/shuffle?{}", parameters.join("&"));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn shares() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/shares")
+		.body(())
+		.unwrap()
+}
+
+pub fn create_share(new_share: dto::NewShare) -> Request<dto::NewShare> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/shares")
+		.body(new_share)
+		.unwrap()
+}
+
+pub fn delete_share(token: &str) -> Request<()> {
+	let endpoint = format!("/api/shares/{}", url_encode(token));
+	Request::builder()
+		.method(Method::DELETE)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn share_browse(token: &str, path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/share/{}/browse/{}",
+		url_encode(token),
+		url_encode(path.as_ref())
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn share_audio(token: &str, path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!(
+		"/api/share/{}/audio/{}",
+		url_encode(token),
+		url_encode(path.as_ref())
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 fn url_encode(input: &str) -> String {
 	percent_encode(input.as_bytes(), NON_ALPHANUMERIC).to_string()
 }
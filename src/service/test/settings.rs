@@ -1,7 +1,8 @@
 use http::StatusCode;
+use std::collections::HashMap;
 
 use crate::service::dto::{self, Settings};
-use crate::service::test::{protocol, ServiceType, TestService};
+use crate::service::test::{constants::*, protocol, ServiceType, TestService};
 use crate::test_name;
 
 #[test]
@@ -64,6 +65,48 @@ fn put_settings_golden_path() {
 	let request = protocol::put_settings(dto::NewSettings {
 		album_art_pattern: Some("test_pattern".to_owned()),
 		reindex_every_n_seconds: Some(31),
+		auto_rename_duplicate_mounts: Some(true),
+		normalize_genres: Some(true),
+		genre_aliases: Some(HashMap::from([("hiphop".to_owned(), "Hip-Hop".to_owned())])),
+		fallback_artist: Some("Various".to_owned()),
+		fallback_album: Some("Unsorted".to_owned()),
+		track_bandwidth: Some(true),
+		bandwidth_rollover: Some(dto::BandwidthRollover::Daily),
+		scan_on_startup: Some(false),
+		album_artist_grouping: Some(dto::AlbumArtistGrouping::Joined),
+		snapshot_index_updates: Some(true),
+		expose_extra_tags: Some(true),
+		index_max_depth: Some(12),
+		public_base_url: Some("https://music.example.com".to_owned()),
+		min_file_size_bytes: Some(1024),
+		artwork_placeholder_path: Some("test-data/artwork/Folder.png".to_owned()),
+		skip_hidden_files: Some(false),
+		artist_image_pattern: Some("^artist\\.png$".to_owned()),
+		merge_duplicate_albums: Some(true),
+		preferred_album_format: Some("flac".to_owned()),
+		tag_priority: Some(vec![dto::TagContainer::Ape, dto::TagContainer::Id3]),
+		estimate_missing_durations: Some(true),
+		album_artist_fallback: Some(false),
+		default_page_size: Some(25),
+		max_page_size: Some(500),
+		artwork_priority: Some(dto::ArtworkPriority::Folder),
+		normalize_artist_names: Some(true),
+		artist_name_articles: Some(vec!["the".to_owned(), "die".to_owned()]),
+		directory_thumbnail: Some(dto::DirectoryThumbnail::Collage),
+		scan_failure_limit: Some(2),
+		analyze_loudness: Some(true),
+		symlinked_duplicates: Some(dto::SymlinkedDuplicates::Skip),
+		max_cover_bytes: Some(1024),
+		audiobook_directories: Some(vec!["Audiobooks".to_owned()]),
+		podcast_directories: Some(vec!["Podcasts".to_owned()]),
+		hide_empty_directories: Some(true),
+		prewarm_thumbnails_on_scan: Some(true),
+		metadata_readers: Some(HashMap::from([(
+			".DSF".to_owned(),
+			dto::MetadataReader::Id3,
+		)])),
+		mount_order: Some(dto::MountOrder::Config),
+		reindex_on_config_change: Some(true),
 	});
 	let response = service.fetch(&request);
 	assert_eq!(response.status(), StatusCode::OK);
@@ -76,6 +119,207 @@ fn put_settings_golden_path() {
 		&Settings {
 			album_art_pattern: "test_pattern".to_owned(),
 			reindex_every_n_seconds: 31,
+			auto_rename_duplicate_mounts: true,
+			normalize_genres: true,
+			genre_aliases: HashMap::from([("hiphop".to_owned(), "Hip-Hop".to_owned())]),
+			fallback_artist: "Various".to_owned(),
+			fallback_album: "Unsorted".to_owned(),
+			track_bandwidth: true,
+			bandwidth_rollover: dto::BandwidthRollover::Daily,
+			scan_on_startup: false,
+			album_artist_grouping: dto::AlbumArtistGrouping::Joined,
+			snapshot_index_updates: true,
+			expose_extra_tags: true,
+			index_max_depth: 12,
+			public_base_url: "https://music.example.com".to_owned(),
+			min_file_size_bytes: 1024,
+			artwork_placeholder_path: "test-data/artwork/Folder.png".to_owned(),
+			skip_hidden_files: false,
+			artist_image_pattern: "^artist\\.png$".to_owned(),
+			merge_duplicate_albums: true,
+			preferred_album_format: "flac".to_owned(),
+			tag_priority: vec![dto::TagContainer::Ape, dto::TagContainer::Id3],
+			estimate_missing_durations: true,
+			album_artist_fallback: false,
+			default_page_size: 25,
+			max_page_size: 500,
+			artwork_priority: dto::ArtworkPriority::Folder,
+			normalize_artist_names: true,
+			artist_name_articles: vec!["the".to_owned(), "die".to_owned()],
+			directory_thumbnail: dto::DirectoryThumbnail::Collage,
+			scan_failure_limit: 2,
+			analyze_loudness: true,
+			symlinked_duplicates: dto::SymlinkedDuplicates::Skip,
+			max_cover_bytes: 1024,
+			audiobook_directories: vec!["Audiobooks".to_owned()],
+			podcast_directories: vec!["Podcasts".to_owned()],
+			hide_empty_directories: true,
+			prewarm_thumbnails_on_scan: true,
+			metadata_readers: HashMap::from([("dsf".to_owned(), dto::MetadataReader::Id3)]),
+			mount_order: dto::MountOrder::Config,
+			reindex_on_config_change: true,
 		},
 	);
 }
+
+#[test]
+fn put_settings_rejects_invalid_public_base_url() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let request = protocol::put_settings(dto::NewSettings {
+		public_base_url: Some("music.example.com".to_owned()),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert!(!response.status().is_success());
+}
+
+#[test]
+fn patch_config_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::patch_config(dto::Config::default());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn patch_config_leaves_other_sections_intact() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let request = protocol::list_users();
+	let users_before: Vec<String> = service
+		.fetch_json::<_, Vec<dto::User>>(&request)
+		.into_body()
+		.into_iter()
+		.map(|u| u.name)
+		.collect();
+	let request = protocol::list_mount_dirs();
+	let mount_dirs_before = service
+		.fetch_json::<_, Vec<dto::MountDir>>(&request)
+		.into_body();
+
+	let request = protocol::patch_config(dto::Config {
+		settings: Some(dto::NewSettings {
+			reindex_every_n_seconds: Some(31),
+			..Default::default()
+		}),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::get_settings();
+	let settings = service.fetch_json::<_, dto::Settings>(&request).into_body();
+	assert_eq!(settings.reindex_every_n_seconds, 31);
+
+	let request = protocol::list_users();
+	let users_after: Vec<String> = service
+		.fetch_json::<_, Vec<dto::User>>(&request)
+		.into_body()
+		.into_iter()
+		.map(|u| u.name)
+		.collect();
+	assert_eq!(users_after, users_before);
+	let request = protocol::list_mount_dirs();
+	let mount_dirs_after = service
+		.fetch_json::<_, Vec<dto::MountDir>>(&request)
+		.into_body();
+	assert_eq!(mount_dirs_after, mount_dirs_before);
+}
+
+#[test]
+fn export_config_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::export_config();
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn import_config_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+	let request = protocol::import_config(dto::Config::default());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn apply_config_reports_invalid_fields() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let request = protocol::apply_config(dto::Config {
+		settings: Some(dto::NewSettings {
+			album_art_pattern: Some("(".to_owned()),
+			..Default::default()
+		}),
+		mount_dirs: Some(vec![dto::MountDir {
+			source: "".to_owned(),
+			name: TEST_MOUNT_NAME.to_owned(),
+			album_art_pattern: None,
+			reindex_every_n_seconds: None,
+			hide_from_root: false,
+		}]),
+		..Default::default()
+	});
+	let response = service.fetch_json::<_, Vec<dto::ConfigProblem>>(&request);
+	assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+	let fields: Vec<String> = response.into_body().into_iter().map(|p| p.field).collect();
+	assert_eq!(
+		fields,
+		vec![
+			"settings.album_art_pattern".to_owned(),
+			"mount_dirs[0].source".to_owned()
+		]
+	);
+}
+
+#[test]
+fn export_import_config_round_trip() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let request = protocol::export_config();
+	let response = service.fetch_json::<_, dto::Config>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	let exported = response.into_body();
+	let users = exported.users.clone().unwrap();
+	assert!(users.iter().any(|u| u.name == TEST_USERNAME && !u.admin));
+	assert!(users
+		.iter()
+		.any(|u| u.name == TEST_USERNAME_ADMIN && u.admin));
+	assert!(users.iter().all(|u| u.password.is_empty()));
+	let mount_dirs = exported.mount_dirs.clone().unwrap();
+	assert!(mount_dirs.iter().any(|m| m.name == TEST_MOUNT_NAME));
+
+	let request = protocol::patch_config(dto::Config {
+		mount_dirs: Some(Vec::new()),
+		..Default::default()
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::import_config(exported.clone());
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::export_config();
+	let reexported = service.fetch_json::<_, dto::Config>(&request).into_body();
+	assert_eq!(reexported, exported);
+
+	// Passwords were left intact
+	service.login();
+	service.login_admin();
+}
@@ -0,0 +1,140 @@
+use http::{header, HeaderValue, StatusCode};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::service::dto;
+use crate::service::test::{constants::*, protocol, ServiceType, TestService};
+use crate::test_name;
+
+fn hunted_path() -> PathBuf {
+	[TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect()
+}
+
+fn tomorrow() -> i64 {
+	let now = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap()
+		.as_secs() as i64;
+	now + 24 * 60 * 60
+}
+
+fn create_share(service: &mut ServiceType, max_downloads: Option<u32>) -> dto::Share {
+	let request = protocol::create_share(dto::NewShare {
+		path: hunted_path().to_string_lossy().into_owned(),
+		expires_at: tomorrow(),
+		max_downloads,
+	});
+	let response = service.fetch_json::<_, dto::Share>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	response.into_body()
+}
+
+#[test]
+fn create_share_requires_admin() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login();
+
+	let request = protocol::create_share(dto::NewShare {
+		path: hunted_path().to_string_lossy().into_owned(),
+		expires_at: tomorrow(),
+		max_downloads: None,
+	});
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn shares_golden_path() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+
+	let share = create_share(&mut service, Some(3));
+
+	let request = protocol::shares();
+	let response = service.fetch_json::<_, Vec<dto::Share>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body(), &vec![share.clone()]);
+
+	let request = protocol::delete_share(&share.token);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::shares();
+	let response = service.fetch_json::<_, Vec<dto::Share>>(&request);
+	assert!(response.body().is_empty());
+}
+
+#[test]
+fn share_browses_and_streams_within_scope() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	let share = create_share(&mut service, None);
+	service.logout();
+
+	let request = protocol::share_browse(&share.token, &hunted_path());
+	let response = service.fetch_json::<_, Vec<dto::CollectionFile>>(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 5);
+
+	let song_path = hunted_path().join("02 - Candlelight.mp3");
+	let request = protocol::share_audio(&share.token, &song_path);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 24_142);
+}
+
+#[test]
+fn share_rejects_paths_outside_scope() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	let share = create_share(&mut service, None);
+	service.logout();
+
+	let song_path: PathBuf = [
+		TEST_MOUNT_NAME,
+		"Tobokegao",
+		"Picnic",
+		"05 - シャーベット (Sherbet).mp3",
+	]
+	.iter()
+	.collect();
+	let request = protocol::share_audio(&share.token, &song_path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	let request = protocol::share_audio("not-a-token", &hunted_path().join("05 - Hunted.mp3"));
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn share_download_limit_allows_resuming() {
+	let mut service = ServiceType::new(&test_name!());
+	service.complete_initial_setup();
+	service.login_admin();
+	service.index();
+	let share = create_share(&mut service, Some(1));
+	service.logout();
+
+	let song_path = hunted_path().join("02 - Candlelight.mp3");
+	let request = protocol::share_audio(&share.token, &song_path);
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let mut request = protocol::share_audio(&share.token, &song_path);
+	request
+		.headers_mut()
+		.append(header::RANGE, HeaderValue::from_str("bytes=100-").unwrap());
+	let response = service.fetch_bytes(&request);
+	assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+
+	let request = protocol::share_audio(&share.token, &song_path);
+	let response = service.fetch(&request);
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
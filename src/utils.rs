@@ -1,4 +1,7 @@
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[macro_export]
 macro_rules! match_ignore_case {
@@ -13,7 +16,7 @@ macro_rules! match_ignore_case {
 pub use crate::match_ignore_case;
 
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AudioFormat {
 	AIFF,
 	APE,
@@ -24,6 +27,24 @@ pub enum AudioFormat {
 	OGG,
 	OPUS,
 	WAVE,
+	WMA,
+}
+
+impl AudioFormat {
+	pub fn name(&self) -> &'static str {
+		match self {
+			AudioFormat::AIFF => "aiff",
+			AudioFormat::APE => "ape",
+			AudioFormat::FLAC => "flac",
+			AudioFormat::MP3 => "mp3",
+			AudioFormat::MP4 => "mp4",
+			AudioFormat::MPC => "mpc",
+			AudioFormat::OGG => "ogg",
+			AudioFormat::OPUS => "opus",
+			AudioFormat::WAVE => "wave",
+			AudioFormat::WMA => "wma",
+		}
+	}
 }
 
 pub fn get_audio_format(path: &Path) -> Option<AudioFormat> {
@@ -42,14 +63,88 @@ pub fn get_audio_format(path: &Path) -> Option<AudioFormat> {
 		"flac" => Some(AudioFormat::FLAC),
 		"mp3" => Some(AudioFormat::MP3),
 		"m4a" => Some(AudioFormat::MP4),
+		"m4b" => Some(AudioFormat::MP4),
 		"mpc" => Some(AudioFormat::MPC),
 		"ogg" => Some(AudioFormat::OGG),
 		"opus" => Some(AudioFormat::OPUS),
 		"wav" => Some(AudioFormat::WAVE),
+		"wma" => Some(AudioFormat::WMA),
 		_ => None,
 	}
 }
 
+/// Estimates the byte offset corresponding to a fraction (between 0 and 1) of the playback time
+/// of an audio file. Leading metadata (ID3v2 tags, FLAC metadata blocks) is skipped and the audio
+/// data is assumed to have a constant average bitrate.
+pub fn estimate_seek_offset(path: &Path, fraction: f64) -> io::Result<u64> {
+	let mut file = fs::File::open(path)?;
+	let length = file.metadata()?.len();
+	let data_start = match get_audio_format(path) {
+		Some(AudioFormat::FLAC) => get_flac_metadata_length(&mut file)?,
+		Some(AudioFormat::MP3) => get_id3v2_tag_length(&mut file)?,
+		_ => 0,
+	}
+	.min(length);
+	let fraction = fraction.clamp(0.0, 1.0);
+	let offset = data_start + ((length - data_start) as f64 * fraction) as u64;
+	Ok(offset.min(length.saturating_sub(1)))
+}
+
+fn get_id3v2_tag_length(file: &mut fs::File) -> io::Result<u64> {
+	let mut header = [0u8; 10];
+	file.seek(SeekFrom::Start(0))?;
+	if file.read_exact(&mut header).is_err() || &header[0..3] != b"ID3" {
+		return Ok(0);
+	}
+	let size = header[6..10]
+		.iter()
+		.fold(0u64, |size, b| (size << 7) | (*b & 0x7F) as u64);
+	let has_footer = header[5] & 0x10 != 0;
+	Ok(10 + size + if has_footer { 10 } else { 0 })
+}
+
+fn get_flac_metadata_length(file: &mut fs::File) -> io::Result<u64> {
+	let mut marker = [0u8; 4];
+	file.seek(SeekFrom::Start(0))?;
+	if file.read_exact(&mut marker).is_err() || &marker != b"fLaC" {
+		return Ok(0);
+	}
+	let mut length = 4;
+	loop {
+		let mut block_header = [0u8; 4];
+		file.read_exact(&mut block_header)?;
+		let block_length =
+			u32::from_be_bytes([0, block_header[1], block_header[2], block_header[3]]);
+		length += 4 + block_length as u64;
+		let is_last_block = block_header[0] & 0x80 != 0;
+		if is_last_block {
+			return Ok(length);
+		}
+		file.seek(SeekFrom::Current(block_length as i64))?;
+	}
+}
+
+/// Current time, in seconds since the UNIX epoch.
+pub fn now() -> i64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs() as i64)
+		.unwrap_or_default()
+}
+
+#[test]
+fn can_estimate_seek_offset() {
+	let path = Path::new("test-data/small-collection/Khemmis/Hunted/02 - Candlelight.mp3");
+	assert_eq!(estimate_seek_offset(path, 0.0).unwrap(), 2200);
+	assert_eq!(estimate_seek_offset(path, 0.5).unwrap(), 13171);
+	assert_eq!(estimate_seek_offset(path, 1.0).unwrap(), 24141);
+
+	let path = Path::new("test-data/formats/sample.flac");
+	let length = fs::metadata(path).unwrap().len();
+	let start = estimate_seek_offset(path, 0.0).unwrap();
+	assert!(start > 4 && start < length);
+}
+
 #[test]
 fn can_guess_audio_format() {
 	assert_eq!(get_audio_format(Path::new("animals/🐷/my🐖file.jpg")), None);